@@ -0,0 +1,102 @@
+//! Mide el mapeo de filas a `Entrada` con su `FromRow` posicional, solo y seguido de la
+//! serialización a JSON.
+//!
+//! Ejecutar con `cargo bench --bench mapeo_filas`.
+
+use std::hint::black_box;
+use std::sync::Arc;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use mysql_async::consts::ColumnType;
+use mysql_async::{from_row, Column, Row, Value};
+use mysql_common::row::new_row;
+use rust_crud::models::Entrada;
+
+const FILAS: usize = 50_000;
+
+/// Genera filas con las mismas columnas que devuelve `SELECT_ENTRADAS`, con las del cliente,
+/// la función y su sala, la versión, las fechas de alta y de último cambio, el total y el
+/// estado.
+fn filas_de_prueba() -> Vec<Row> {
+    let columnas: Arc<[Column]> = [
+        ("id", ColumnType::MYSQL_TYPE_LONG),
+        ("cantidad_entradas", ColumnType::MYSQL_TYPE_LONG),
+        ("id", ColumnType::MYSQL_TYPE_LONG),
+        ("numero_cedula", ColumnType::MYSQL_TYPE_VAR_STRING),
+        ("nombre", ColumnType::MYSQL_TYPE_VAR_STRING),
+        ("id", ColumnType::MYSQL_TYPE_LONG),
+        ("titulo", ColumnType::MYSQL_TYPE_VAR_STRING),
+        ("horario", ColumnType::MYSQL_TYPE_DATETIME),
+        ("precio", ColumnType::MYSQL_TYPE_NEWDECIMAL),
+        ("id", ColumnType::MYSQL_TYPE_LONG),
+        ("nombre", ColumnType::MYSQL_TYPE_VAR_STRING),
+        ("capacidad", ColumnType::MYSQL_TYPE_LONG),
+        ("version", ColumnType::MYSQL_TYPE_LONG),
+        ("created_at", ColumnType::MYSQL_TYPE_VAR_STRING),
+        ("updated_at", ColumnType::MYSQL_TYPE_VAR_STRING),
+        ("total", ColumnType::MYSQL_TYPE_NEWDECIMAL),
+        ("estado", ColumnType::MYSQL_TYPE_STRING),
+    ]
+    .into_iter()
+    .map(|(nombre, tipo)| Column::new(tipo).with_name(nombre.as_bytes()))
+    .collect();
+
+    (0..FILAS)
+        .map(|i| {
+            new_row(
+                vec![
+                    Value::Int(i as i64),
+                    Value::Int(2),
+                    Value::Int(i as i64),
+                    Value::Bytes(format!("{:010}", i).into_bytes()),
+                    Value::Bytes(b"Maria Perez".to_vec()),
+                    Value::Int((i % 8) as i64),
+                    Value::Bytes(b"Dune: Parte Dos".to_vec()),
+                    Value::Date(2024, 3, 2, 0, 0, 0, 0),
+                    Value::Bytes(b"6.50".to_vec()),
+                    Value::Int((i % 4) as i64),
+                    Value::Bytes(b"Sala 1".to_vec()),
+                    Value::Int(120),
+                    Value::Int(1),
+                    Value::Bytes(b"2024-03-01 18:30:00".to_vec()),
+                    Value::Bytes(b"2024-03-01 18:30:00".to_vec()),
+                    Value::Bytes(b"13.00".to_vec()),
+                    Value::Bytes(b"pagada".to_vec()),
+                ],
+                columnas.clone(),
+            )
+        })
+        .collect()
+}
+
+fn mapeo_filas(c: &mut Criterion) {
+    let filas = filas_de_prueba();
+    let mut grupo = c.benchmark_group("mapeo_filas_50k");
+
+    grupo.bench_function("from_row", |b| {
+        b.iter_batched(
+            || filas.clone(),
+            |filas| filas.into_iter().map(from_row::<Entrada>).collect::<Vec<_>>(),
+            BatchSize::LargeInput,
+        )
+    });
+
+    grupo.bench_function("from_row_y_serializacion", |b| {
+        b.iter_batched(
+            || filas.clone(),
+            |filas| {
+                let mut salida = Vec::new();
+                for fila in filas {
+                    serde_json::to_writer(&mut salida, &from_row::<Entrada>(fila)).unwrap();
+                }
+                black_box(salida)
+            },
+            BatchSize::LargeInput,
+        )
+    });
+
+    grupo.finish();
+}
+
+criterion_group!(benches, mapeo_filas);
+criterion_main!(benches);
@@ -0,0 +1,15 @@
+//! Con la feature `grpc`, genera el servidor de `proto/entradas.proto` (ver `src/grpc.rs`).
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    #[cfg(feature = "grpc")]
+    {
+        let protoc = protoc_bin_vendored::protoc_bin_path().expect("No hay protoc para esta plataforma");
+        // SAFETY: el script de compilación no tiene otros hilos que lean el entorno.
+        unsafe { std::env::set_var("PROTOC", protoc) };
+        tonic_build::configure()
+            .build_client(false)
+            .compile_protos(&["proto/entradas.proto"], &["proto"])
+            .expect("No se pudo compilar proto/entradas.proto");
+    }
+}
@@ -0,0 +1,12 @@
+-- Tabla inicial. Con IF NOT EXISTS para adoptar las bases creadas a mano con el esquema anterior.
+CREATE TABLE IF NOT EXISTS entradas (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    numero_cedula VARCHAR(255) NOT NULL UNIQUE,
+    nombre_cliente VARCHAR(255) NOT NULL,
+    nombre_funcion VARCHAR(255) NOT NULL,
+    cantidad_entradas INT NOT NULL,
+    horario_funcion VARCHAR(255) NOT NULL,
+    -- Búsquedas por prefijo de /autocomplete
+    INDEX idx_entradas_nombre_cliente (nombre_cliente),
+    INDEX idx_entradas_nombre_funcion (nombre_funcion)
+);
@@ -0,0 +1,9 @@
+-- Kioscos registrados y su último latido (/dispositivos)
+CREATE TABLE IF NOT EXISTS dispositivos (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    nombre VARCHAR(255) NOT NULL UNIQUE,
+    sucursal VARCHAR(255) NOT NULL,
+    version VARCHAR(64) NOT NULL,
+    registrado_en TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    ultimo_latido TIMESTAMP NULL
+);
@@ -0,0 +1,19 @@
+-- Usuarios de la API (/auth). La clave se guarda como hash Argon2 en formato PHC.
+CREATE TABLE IF NOT EXISTS usuarios (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    usuario VARCHAR(100) NOT NULL UNIQUE,
+    clave_hash VARCHAR(255) NOT NULL,
+    rol ENUM('admin', 'taquillero') NOT NULL DEFAULT 'taquillero',
+    creado_en TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Tokens de refresco (/auth/refresh). Sólo se guarda su SHA-256; cada uso lo revoca.
+CREATE TABLE IF NOT EXISTS tokens_refresco (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    usuario_id INT NOT NULL,
+    hash CHAR(64) NOT NULL UNIQUE,
+    expira_en TIMESTAMP NOT NULL,
+    revocado_en TIMESTAMP NULL,
+    creado_en TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (usuario_id) REFERENCES usuarios (id) ON DELETE CASCADE
+);
@@ -0,0 +1,10 @@
+-- Claves de API para integraciones como los kioscos (cabecera X-Api-Key)
+CREATE TABLE IF NOT EXISTS api_keys (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    nombre VARCHAR(255) NOT NULL,
+    prefijo CHAR(8) NOT NULL,
+    hash CHAR(64) NOT NULL UNIQUE,
+    rol ENUM('admin', 'taquillero') NOT NULL DEFAULT 'taquillero',
+    creado_en TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    revocado_en TIMESTAMP NULL
+);
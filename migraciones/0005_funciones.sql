@@ -0,0 +1,29 @@
+-- Funciones (película, sala y horario) a las que pertenece cada entrada (/funciones)
+CREATE TABLE IF NOT EXISTS funciones (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    titulo VARCHAR(255) NOT NULL,
+    sala VARCHAR(100) NOT NULL,
+    horario VARCHAR(255) NOT NULL,
+    precio DECIMAL(10, 2) NOT NULL,
+    capacidad INT NOT NULL,
+    -- También sirve a las búsquedas por prefijo del título en /autocomplete
+    UNIQUE KEY uq_funciones_titulo_sala_horario (titulo, sala, horario)
+);
+
+-- Las funciones que ya aparecían en las entradas se crean sin sala, precio ni capacidad,
+-- para que un administrador las complete después
+INSERT INTO funciones (titulo, sala, horario, precio, capacidad)
+SELECT DISTINCT nombre_funcion, 'Sin asignar', horario_funcion, 0, 0 FROM entradas;
+
+ALTER TABLE entradas ADD COLUMN funcion_id INT NULL AFTER nombre_cliente;
+
+UPDATE entradas e
+JOIN funciones f ON f.titulo = e.nombre_funcion AND f.horario = e.horario_funcion AND f.sala = 'Sin asignar'
+SET e.funcion_id = f.id;
+
+ALTER TABLE entradas
+    MODIFY funcion_id INT NOT NULL,
+    ADD CONSTRAINT fk_entradas_funcion FOREIGN KEY (funcion_id) REFERENCES funciones (id),
+    DROP INDEX idx_entradas_nombre_funcion,
+    DROP COLUMN nombre_funcion,
+    DROP COLUMN horario_funcion;
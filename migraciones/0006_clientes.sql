@@ -0,0 +1,25 @@
+-- Clientes (/clientes), identificados por su cédula
+CREATE TABLE IF NOT EXISTS clientes (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    numero_cedula VARCHAR(255) NOT NULL UNIQUE,
+    nombre VARCHAR(255) NOT NULL,
+    -- Búsquedas por prefijo de /autocomplete
+    INDEX idx_clientes_nombre (nombre)
+);
+
+-- Cada cédula tenía una sola entrada, así que sus datos pasan tal cual a su cliente
+INSERT INTO clientes (numero_cedula, nombre)
+SELECT numero_cedula, nombre_cliente FROM entradas;
+
+ALTER TABLE entradas ADD COLUMN cliente_id INT NULL AFTER id;
+
+UPDATE entradas e JOIN clientes c ON c.numero_cedula = e.numero_cedula SET e.cliente_id = c.id;
+
+-- Se mantiene una entrada por cliente, como antes por cédula
+ALTER TABLE entradas
+    MODIFY cliente_id INT NOT NULL,
+    ADD CONSTRAINT uq_entradas_cliente UNIQUE (cliente_id),
+    ADD CONSTRAINT fk_entradas_cliente FOREIGN KEY (cliente_id) REFERENCES clientes (id),
+    DROP INDEX idx_entradas_nombre_cliente,
+    DROP COLUMN numero_cedula,
+    DROP COLUMN nombre_cliente;
@@ -0,0 +1,24 @@
+-- Salas (/salas), con la capacidad de la que dependen sus funciones
+CREATE TABLE IF NOT EXISTS salas (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    nombre VARCHAR(100) NOT NULL UNIQUE,
+    capacidad INT NOT NULL
+);
+
+-- Cada sala conserva la mayor capacidad que tenía entre sus funciones
+INSERT INTO salas (nombre, capacidad)
+SELECT sala, MAX(capacidad) FROM funciones GROUP BY sala;
+
+ALTER TABLE funciones ADD COLUMN sala_id INT NULL AFTER titulo;
+
+UPDATE funciones f JOIN salas s ON s.nombre = f.sala SET f.sala_id = s.id;
+
+ALTER TABLE funciones
+    MODIFY sala_id INT NOT NULL,
+    ADD CONSTRAINT fk_funciones_sala FOREIGN KEY (sala_id) REFERENCES salas (id),
+    DROP INDEX uq_funciones_titulo_sala_horario;
+
+ALTER TABLE funciones
+    ADD UNIQUE KEY uq_funciones_titulo_sala_horario (titulo, sala_id, horario),
+    DROP COLUMN sala,
+    DROP COLUMN capacidad;
@@ -0,0 +1,12 @@
+-- Asientos numerados ocupados en cada función. Los libres son los números entre 1 y la
+-- capacidad de su sala que no aparecen aquí
+CREATE TABLE IF NOT EXISTS asientos (
+    funcion_id INT NOT NULL,
+    numero INT NOT NULL,
+    entrada_id INT NOT NULL,
+    PRIMARY KEY (funcion_id, numero),
+    INDEX idx_asientos_entrada (entrada_id),
+    CONSTRAINT fk_asientos_funcion FOREIGN KEY (funcion_id) REFERENCES funciones (id),
+    -- Eliminar una entrada libera sus asientos
+    CONSTRAINT fk_asientos_entrada FOREIGN KEY (entrada_id) REFERENCES entradas (id) ON DELETE CASCADE
+);
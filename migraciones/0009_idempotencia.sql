@@ -0,0 +1,10 @@
+-- Claves Idempotency-Key de POST /entradas. Mientras la petición se procesa, estado y
+-- cuerpo quedan a NULL. Al terminar bien guardan la respuesta que se repite
+CREATE TABLE IF NOT EXISTS claves_idempotencia (
+    clave VARCHAR(255) NOT NULL PRIMARY KEY,
+    huella CHAR(64) NOT NULL,
+    estado SMALLINT UNSIGNED NULL,
+    cuerpo MEDIUMTEXT NULL,
+    creada_en TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    INDEX idx_claves_idempotencia_creada (creada_en)
+);
@@ -0,0 +1,3 @@
+-- Versión de cada entrada para el control de concurrencia optimista. Aumenta con cada
+-- cambio y se expone como ETag, que PUT, PATCH y DELETE exigen en If-Match
+ALTER TABLE entradas ADD COLUMN version INT UNSIGNED NOT NULL DEFAULT 1;
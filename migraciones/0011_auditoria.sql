@@ -0,0 +1,13 @@
+-- Historial de cambios de las entradas: cada alta, cambio o baja guarda la entrada antes
+-- y después, quién la hizo y cuándo. Sin clave foránea, para conservar el historial de
+-- las entradas eliminadas
+CREATE TABLE IF NOT EXISTS auditoria (
+    id BIGINT UNSIGNED AUTO_INCREMENT PRIMARY KEY,
+    entrada_id INT NOT NULL,
+    operacion ENUM('crear', 'actualizar', 'eliminar') NOT NULL,
+    actor VARCHAR(255) NOT NULL,
+    valor_anterior JSON NULL,
+    valor_nuevo JSON NULL,
+    realizada_en TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    INDEX idx_auditoria_entrada (entrada_id, id)
+);
@@ -0,0 +1,6 @@
+-- Fecha de alta y de la última modificación de cada entrada. Las escribe el repositorio
+-- en cada alta y cambio. Las entradas anteriores toman la fecha de esta migración
+ALTER TABLE entradas
+    ADD COLUMN created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    ADD COLUMN updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    ADD INDEX idx_entradas_created_at (created_at);
@@ -0,0 +1,21 @@
+-- El horario de cada función pasa de texto libre ("19:00", "7pm") a DATETIME en UTC. Los
+-- que ya tenían fecha y hora se conservan, los que sólo tenían la hora quedan en el día
+-- de la migración y el resto en su medianoche, desplazados tantos segundos como su ID
+-- para no chocar entre sí, hasta que un administrador los corrija
+ALTER TABLE funciones ADD COLUMN horario_nuevo DATETIME NULL AFTER horario;
+
+UPDATE funciones SET horario_nuevo = CASE
+    WHEN horario REGEXP '^[0-9]{4}-[0-9]{2}-[0-9]{2}[ T][0-9]{2}:[0-9]{2}(:[0-9]{2})?$'
+        THEN CAST(REPLACE(horario, 'T', ' ') AS DATETIME)
+    WHEN horario REGEXP '^[0-9]{1,2}:[0-9]{2}$'
+        THEN TIMESTAMP(CURRENT_DATE, CAST(CONCAT(horario, ':00') AS TIME))
+    ELSE TIMESTAMP(CURRENT_DATE) + INTERVAL id SECOND
+END;
+
+ALTER TABLE funciones
+    DROP INDEX uq_funciones_titulo_sala_horario,
+    DROP COLUMN horario;
+
+ALTER TABLE funciones
+    CHANGE horario_nuevo horario DATETIME NOT NULL,
+    ADD UNIQUE KEY uq_funciones_titulo_sala_horario (titulo, sala_id, horario);
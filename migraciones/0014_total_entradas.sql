@@ -0,0 +1,6 @@
+-- Importe de cada entrada, el precio de su función por la cantidad. Se guarda al venderla
+-- y se recalcula cuando cambia la función o la cantidad, así que un cambio de precio de la
+-- función no altera lo ya vendido
+ALTER TABLE entradas ADD COLUMN total DECIMAL(10, 2) NOT NULL DEFAULT 0 AFTER cantidad_entradas;
+
+UPDATE entradas e JOIN funciones f ON f.id = e.funcion_id SET e.total = f.precio * e.cantidad_entradas;
@@ -0,0 +1,7 @@
+-- Ciclo de vida de cada entrada: reservada, pagada, usada o cancelada. Las canceladas no
+-- ocupan capacidad. Las entradas anteriores ya estaban vendidas, así que quedan pagadas
+ALTER TABLE entradas
+    ADD COLUMN estado ENUM('reservada', 'pagada', 'usada', 'cancelada') NOT NULL DEFAULT 'reservada' AFTER total,
+    ADD INDEX idx_entradas_funcion_estado (funcion_id, estado);
+
+UPDATE entradas SET estado = 'pagada';
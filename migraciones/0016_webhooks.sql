@@ -0,0 +1,27 @@
+-- Suscripciones a los eventos de las entradas y registro de sus entregas. El secreto se
+-- guarda tal cual porque hace falta para firmar cada envío
+CREATE TABLE IF NOT EXISTS webhooks (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    url VARCHAR(2048) NOT NULL,
+    secreto VARCHAR(255) NOT NULL,
+    eventos VARCHAR(255) NOT NULL,
+    creado_en TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    desactivado_en TIMESTAMP NULL
+);
+
+CREATE TABLE IF NOT EXISTS webhook_entregas (
+    id BIGINT UNSIGNED AUTO_INCREMENT PRIMARY KEY,
+    webhook_id INT NOT NULL,
+    evento VARCHAR(32) NOT NULL,
+    carga JSON NOT NULL,
+    estado ENUM('pendiente', 'entregada', 'fallida') NOT NULL DEFAULT 'pendiente',
+    intentos INT UNSIGNED NOT NULL DEFAULT 0,
+    siguiente_intento TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    ultimo_codigo SMALLINT UNSIGNED NULL,
+    ultimo_error VARCHAR(512) NULL,
+    creado_en TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    entregado_en TIMESTAMP NULL,
+    INDEX idx_webhook_entregas_pendientes (estado, siguiente_intento),
+    INDEX idx_webhook_entregas_webhook (webhook_id, id),
+    CONSTRAINT fk_webhook_entregas_webhook FOREIGN KEY (webhook_id) REFERENCES webhooks (id)
+);
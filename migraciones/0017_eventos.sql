@@ -0,0 +1,12 @@
+-- Bandeja de salida de los eventos de las entradas: cada cambio deja aquí su evento en la
+-- misma transacción, y se reparte a los webhooks después. Los publicados se borran pasado
+-- un tiempo
+CREATE TABLE IF NOT EXISTS eventos (
+    id BIGINT UNSIGNED AUTO_INCREMENT PRIMARY KEY,
+    evento VARCHAR(32) NOT NULL,
+    entrada_id INT NOT NULL,
+    entrada JSON NULL,
+    creado_en TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    publicado_en TIMESTAMP NULL,
+    INDEX idx_eventos_publicado (publicado_en, id)
+);
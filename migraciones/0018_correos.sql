@@ -0,0 +1,15 @@
+-- Correo de los clientes y cola de las confirmaciones de compra que se les envían
+ALTER TABLE clientes ADD COLUMN email VARCHAR(255) NULL AFTER nombre;
+
+CREATE TABLE IF NOT EXISTS correos (
+    id BIGINT UNSIGNED AUTO_INCREMENT PRIMARY KEY,
+    entrada_id INT NOT NULL,
+    destinatario VARCHAR(255) NOT NULL,
+    estado ENUM('pendiente', 'enviado', 'fallido') NOT NULL DEFAULT 'pendiente',
+    intentos INT UNSIGNED NOT NULL DEFAULT 0,
+    siguiente_intento TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    ultimo_error VARCHAR(512) NULL,
+    creado_en TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    enviado_en TIMESTAMP NULL,
+    INDEX idx_correos_pendientes (estado, siguiente_intento)
+);
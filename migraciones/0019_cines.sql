@@ -0,0 +1,55 @@
+-- Cines de la cadena. Cada sala, cliente y entrada es de un cine; lo que ya existía pasa
+-- al cine 1
+CREATE TABLE IF NOT EXISTS cines (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    nombre VARCHAR(100) NOT NULL,
+    creado_en TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE KEY uq_cines_nombre (nombre)
+);
+
+INSERT INTO cines (id, nombre) VALUES (1, 'Principal');
+
+-- Las salas y los clientes se repiten por cine: otro cine puede tener su "Sala 1" y
+-- registrar a la misma persona
+ALTER TABLE salas
+    ADD COLUMN cine_id INT NOT NULL DEFAULT 1 AFTER id,
+    ADD CONSTRAINT fk_salas_cine FOREIGN KEY (cine_id) REFERENCES cines (id),
+    DROP INDEX nombre,
+    ADD UNIQUE KEY uq_salas_cine_nombre (cine_id, nombre);
+
+ALTER TABLE clientes
+    ADD COLUMN cine_id INT NOT NULL DEFAULT 1 AFTER id,
+    ADD CONSTRAINT fk_clientes_cine FOREIGN KEY (cine_id) REFERENCES cines (id),
+    DROP INDEX numero_cedula,
+    ADD UNIQUE KEY uq_clientes_cine_cedula (cine_id, numero_cedula);
+
+-- La entrada repite el cine de su función para filtrar sin unir con la sala
+ALTER TABLE entradas
+    ADD COLUMN cine_id INT NOT NULL DEFAULT 1 AFTER id,
+    ADD CONSTRAINT fk_entradas_cine FOREIGN KEY (cine_id) REFERENCES cines (id),
+    ADD INDEX idx_entradas_cine (cine_id, id);
+
+-- El historial y la cola de correos conservan el cine de las entradas eliminadas
+ALTER TABLE auditoria ADD COLUMN cine_id INT NOT NULL DEFAULT 1 AFTER entrada_id;
+
+ALTER TABLE correos ADD COLUMN cine_id INT NOT NULL DEFAULT 1 AFTER entrada_id;
+
+-- A partir de aquí cada alta indica su cine
+ALTER TABLE salas ALTER COLUMN cine_id DROP DEFAULT;
+
+ALTER TABLE clientes ALTER COLUMN cine_id DROP DEFAULT;
+
+ALTER TABLE entradas ALTER COLUMN cine_id DROP DEFAULT;
+
+ALTER TABLE auditoria ALTER COLUMN cine_id DROP DEFAULT;
+
+ALTER TABLE correos ALTER COLUMN cine_id DROP DEFAULT;
+
+-- Usuarios y claves de API atados a un cine; NULL puede elegir cualquiera
+ALTER TABLE usuarios
+    ADD COLUMN cine_id INT NULL AFTER rol,
+    ADD CONSTRAINT fk_usuarios_cine FOREIGN KEY (cine_id) REFERENCES cines (id);
+
+ALTER TABLE api_keys
+    ADD COLUMN cine_id INT NULL AFTER rol,
+    ADD CONSTRAINT fk_api_keys_cine FOREIGN KEY (cine_id) REFERENCES cines (id);
@@ -0,0 +1,2 @@
+-- Nombre del archivo del póster de cada función en el almacén de pósters
+ALTER TABLE funciones ADD COLUMN poster VARCHAR(64) NULL;
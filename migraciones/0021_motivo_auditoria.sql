@@ -0,0 +1,3 @@
+-- Motivo de un cambio, cuando quien lo hace lo indica (por ejemplo, al cancelar las
+-- entradas de una función)
+ALTER TABLE auditoria ADD COLUMN motivo VARCHAR(255) NULL AFTER actor;
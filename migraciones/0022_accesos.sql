@@ -0,0 +1,13 @@
+-- Registro de accesos: una fila por petición atendida
+CREATE TABLE IF NOT EXISTS accesos (
+    id BIGINT UNSIGNED AUTO_INCREMENT PRIMARY KEY,
+    metodo VARCHAR(10) NOT NULL,
+    ruta VARCHAR(255) NOT NULL,
+    estado SMALLINT UNSIGNED NOT NULL,
+    latencia_ms INT UNSIGNED NOT NULL,
+    actor VARCHAR(100) NULL,
+    ip VARCHAR(45) NULL,
+    registrado_en TIMESTAMP(3) NOT NULL,
+    INDEX idx_accesos_registrado (registrado_en),
+    INDEX idx_accesos_actor (actor, registrado_en)
+);
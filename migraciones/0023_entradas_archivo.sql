@@ -0,0 +1,23 @@
+-- Entradas archivadas: las usadas o canceladas hace tiempo salen de `entradas` y quedan
+-- aquí con los datos de su cliente y su función, por si después se eliminan
+CREATE TABLE IF NOT EXISTS entradas_archivo (
+    id INT NOT NULL PRIMARY KEY,
+    cine_id INT NOT NULL,
+    cliente_id INT NOT NULL,
+    numero_cedula VARCHAR(255) NOT NULL,
+    nombre_cliente VARCHAR(255) NOT NULL,
+    funcion_id INT NOT NULL,
+    titulo_funcion VARCHAR(255) NOT NULL,
+    horario_funcion DATETIME NOT NULL,
+    sala VARCHAR(100) NOT NULL,
+    cantidad_entradas INT NOT NULL,
+    total DECIMAL(10, 2) NOT NULL,
+    estado ENUM('usada', 'cancelada') NOT NULL,
+    version INT UNSIGNED NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP NOT NULL,
+    archivada_en TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    INDEX idx_entradas_archivo_cine (cine_id, archivada_en),
+    INDEX idx_entradas_archivo_cedula (cine_id, numero_cedula),
+    INDEX idx_entradas_archivo_funcion (cine_id, funcion_id)
+);
@@ -0,0 +1,5 @@
+-- Un cliente puede comprar varias veces, también para la misma función; lo que se limita
+-- es el total de entradas por cédula y función. El índice nuevo sostiene la clave foránea
+-- y las búsquedas por cliente y función
+ALTER TABLE entradas ADD INDEX idx_entradas_cliente_funcion (cliente_id, funcion_id);
+ALTER TABLE entradas DROP INDEX uq_entradas_cliente;
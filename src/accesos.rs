@@ -0,0 +1,312 @@
+//! Registro de accesos para auditoría de seguridad.
+//!
+//! El middleware [`registrar_acceso`] anota de cada petición el método, la ruta, el estado
+//! de la respuesta, la latencia, el usuario de la sesión y la IP del cliente. Las anota en
+//! memoria, para no añadir una escritura a cada petición, y la tarea de [`guardar_accesos`]
+//! las pasa a la tabla `accesos` cada [`PERIODO_GUARDADO`] y borra las que superan
+//! `Config::accesos_retencion_dias`. Se consultan en `GET /admin/accesos`.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{web, Error, HttpMessage};
+use chrono::{DateTime, FixedOffset, Utc};
+use mysql_async::{prelude::*, Pool, Value};
+use serde::{Deserialize, Serialize};
+
+use crate::auth::{Administrador, Sesion};
+use crate::db;
+use crate::error::ApiError;
+use crate::listado::{escapar_like, POR_PAGINA_DEFECTO, POR_PAGINA_MAXIMO};
+use crate::respuesta::{ApiResponse, Paginacion};
+use crate::tareas::{self, Intervalo, Trabajo};
+
+/// Cada cuánto se guardan los accesos anotados y se borran los vencidos.
+const PERIODO_GUARDADO: Duration = Duration::from_secs(5);
+
+/// Accesos que se guardan en cada `INSERT`.
+const ACCESOS_POR_INSERCION: usize = 500;
+
+/// Accesos vencidos que se borran como máximo en cada ronda, para no bloquear la tabla.
+const BORRADOS_POR_RONDA: u32 = 5000;
+
+/// Accesos que se anotan como máximo a la espera de guardarse. Si la base de datos no
+/// responde, los que pasan de aquí se descartan en lugar de acumularse en memoria.
+const PENDIENTES_MAXIMOS: usize = 10_000;
+
+/// Rutas que no se registran: las consultan los balanceadores y Prometheus cada pocos
+/// segundos y no dicen nada de quién accede a los datos.
+const RUTAS_OMITIDAS: &[&str] = &["/health", "/ready", "/metrics"];
+
+/// Una petición atendida.
+#[derive(Debug, Clone, PartialEq)]
+struct Acceso {
+    metodo: String,
+    ruta: String,
+    estado: u16,
+    latencia_ms: u32,
+    actor: Option<String>,
+    ip: Option<String>,
+    registrado_en: DateTime<Utc>,
+}
+
+/// Accesos anotados a la espera de [`guardar_accesos`]. Se comparte entre los workers.
+pub struct RegistroAccesos {
+    activo: bool,
+    pendientes: Mutex<Vec<Acceso>>,
+}
+
+impl RegistroAccesos {
+    /// Con `activo` a `false` el middleware no anota nada.
+    pub fn new(activo: bool) -> Self {
+        RegistroAccesos { activo, pendientes: Mutex::new(Vec::new()) }
+    }
+
+    fn anotar(&self, acceso: Acceso) {
+        let mut pendientes = self.pendientes.lock().unwrap();
+        if pendientes.len() < PENDIENTES_MAXIMOS {
+            pendientes.push(acceso);
+        }
+    }
+
+    /// Guarda los accesos anotados. Si falla, los devuelve a la espera de la siguiente ronda.
+    pub async fn guardar(&self, pool: &Pool) -> Result<(), mysql_async::Error> {
+        let accesos = std::mem::take(&mut *self.pendientes.lock().unwrap());
+        if accesos.is_empty() {
+            return Ok(());
+        }
+        let mut guardados = 0;
+        let resultado = async {
+            let mut conn = db::conectar(pool).await?;
+            for lote in accesos.chunks(ACCESOS_POR_INSERCION) {
+                let filas = vec!["(?, LEFT(?, 255), ?, ?, LEFT(?, 100), ?, FROM_UNIXTIME(? / 1000))"; lote.len()];
+                let valores: Vec<Value> = lote
+                    .iter()
+                    .flat_map(|acceso| {
+                        [
+                            acceso.metodo.as_str().into(),
+                            acceso.ruta.as_str().into(),
+                            acceso.estado.into(),
+                            acceso.latencia_ms.into(),
+                            acceso.actor.as_deref().into(),
+                            acceso.ip.as_deref().into(),
+                            acceso.registrado_en.timestamp_millis().into(),
+                        ]
+                    })
+                    .collect();
+                conn.exec_drop(
+                    format!(
+                        "INSERT INTO accesos (metodo, ruta, estado, latencia_ms, actor, ip, registrado_en) VALUES {}",
+                        filas.join(", ")
+                    ),
+                    valores,
+                )
+                .await?;
+                guardados += lote.len();
+            }
+            Ok(())
+        }
+        .await;
+        if resultado.is_err() {
+            let mut pendientes = self.pendientes.lock().unwrap();
+            let mut restantes = accesos[guardados..].to_vec();
+            restantes.truncate(PENDIENTES_MAXIMOS.saturating_sub(pendientes.len()));
+            pendientes.splice(0..0, restantes);
+        }
+        resultado
+    }
+}
+
+/// Middleware que anota cada petición en el [`RegistroAccesos`] de la aplicación.
+pub async fn registrar_acceso(
+    registro: web::Data<RegistroAccesos>,
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    if !registro.activo || RUTAS_OMITIDAS.contains(&req.path()) {
+        return next.call(req).await;
+    }
+    let metodo = req.method().to_string();
+    let ruta = req.path().to_string();
+    let ip = req.peer_addr().map(|direccion| direccion.ip().to_string());
+    let registrado_en = Utc::now();
+    let inicio = Instant::now();
+    let respuesta = next.call(req).await;
+
+    // La sesión la deja más adentro `auth::exigir_autenticacion`. Los handlers responden
+    // sus errores como `Ok`; sólo los middlewares devuelven `Err`, y los que lo hacen antes
+    // de autenticar no tienen sesión.
+    let (estado, actor) = match &respuesta {
+        Ok(res) => (res.status(), res.request().extensions().get::<Sesion>().map(|sesion| sesion.sub.clone())),
+        Err(e) => (e.as_response_error().status_code(), None),
+    };
+    registro.anotar(Acceso {
+        metodo,
+        ruta,
+        estado: estado.as_u16(),
+        latencia_ms: inicio.elapsed().as_millis().try_into().unwrap_or(u32::MAX),
+        actor,
+        ip,
+        registrado_en,
+    });
+    respuesta
+}
+
+/// Tarea que guarda los accesos anotados cada [`PERIODO_GUARDADO`] y borra los de hace
+/// más de `retencion_dias`.
+pub fn guardar_accesos(registro: Arc<RegistroAccesos>, pool: Pool, retencion_dias: u32) -> (Intervalo, Trabajo) {
+    let trabajo = tareas::trabajo(move || {
+        let registro = registro.clone();
+        let pool = pool.clone();
+        async move {
+            registro
+                .guardar(&pool)
+                .await
+                .map_err(|e| format!("Error al guardar los accesos: {:?}", e))?;
+            let mut conn = db::conectar(&pool)
+                .await
+                .map_err(|e| format!("Error al borrar los accesos vencidos: {:?}", e))?;
+            conn.exec_drop(
+                "DELETE FROM accesos WHERE registrado_en < NOW() - INTERVAL :dias DAY ORDER BY registrado_en LIMIT :limite",
+                params! { "dias" => retencion_dias, "limite" => BORRADOS_POR_RONDA },
+            )
+            .await
+            .map_err(|e| format!("Error al borrar los accesos vencidos: {:?}", e))
+        }
+    });
+    (Intervalo::cada(PERIODO_GUARDADO), trabajo)
+}
+
+/// Parámetros de `GET /admin/accesos`. Las fechas son RFC 3339.
+#[derive(Debug, Deserialize)]
+pub struct FiltrosAccesos {
+    pub actor: Option<String>,
+    pub metodo: Option<String>,
+    /// Prefijo de la ruta.
+    pub ruta: Option<String>,
+    pub estado: Option<u16>,
+    pub desde: Option<DateTime<FixedOffset>>,
+    pub hasta: Option<DateTime<FixedOffset>>,
+    pub page: Option<u32>,
+    pub per_page: Option<u32>,
+}
+
+/// Acceso tal como lo devuelve `GET /admin/accesos`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RegistroAcceso {
+    pub id: u64,
+    pub metodo: String,
+    pub ruta: String,
+    pub estado: u16,
+    pub latencia_ms: u32,
+    pub actor: Option<String>,
+    pub ip: Option<String>,
+    pub registrado_en: String,
+}
+
+/// Handler con los accesos guardados que cumplen los filtros, del más reciente al más
+/// antiguo. Los de los últimos segundos pueden no estar guardados todavía. Como recoge
+/// peticiones de todos los cines, sólo lo consulta un administrador de toda la cadena.
+pub async fn listar_accesos(
+    admin: Administrador,
+    pool: web::Data<Pool>,
+    filtros: web::Query<FiltrosAccesos>,
+) -> Result<ApiResponse<Vec<RegistroAcceso>>, ApiError> {
+    if admin.0.cine.is_some() {
+        return Err(ApiError::Prohibido(
+            "Sólo un administrador de toda la cadena puede consultar el registro de accesos".to_string(),
+        ));
+    }
+    let pagina = filtros.page.unwrap_or(1);
+    if pagina == 0 {
+        return Err(ApiError::Validacion("El parámetro 'page' empieza en 1".to_string()));
+    }
+    let por_pagina = filtros.per_page.unwrap_or(POR_PAGINA_DEFECTO);
+    if por_pagina == 0 {
+        return Err(ApiError::Validacion("El parámetro 'per_page' debe ser mayor que 0".to_string()));
+    }
+    let por_pagina = por_pagina.min(POR_PAGINA_MAXIMO);
+
+    let condicion = "(:actor IS NULL OR actor = :actor) AND (:metodo IS NULL OR metodo = :metodo) \
+                     AND (:ruta IS NULL OR ruta LIKE :ruta) AND (:estado IS NULL OR estado = :estado) \
+                     AND (:desde IS NULL OR registrado_en >= FROM_UNIXTIME(:desde / 1000)) \
+                     AND (:hasta IS NULL OR registrado_en < FROM_UNIXTIME(:hasta / 1000))";
+    let parametros = params! {
+        "actor" => filtros.actor.as_deref(),
+        "metodo" => filtros.metodo.as_deref().map(str::to_uppercase),
+        "ruta" => filtros.ruta.as_deref().map(|ruta| format!("{}%", escapar_like(ruta))),
+        "estado" => filtros.estado,
+        "desde" => filtros.desde.map(|desde| desde.timestamp_millis()),
+        "hasta" => filtros.hasta.map(|hasta| hasta.timestamp_millis()),
+    };
+    let mut conn = db::conectar(&pool).await.map_err(ApiError::conexion)?;
+    let total: u64 = conn
+        .exec_first(format!("SELECT COUNT(*) FROM accesos WHERE {}", condicion), parametros.clone())
+        .await
+        .map_err(ApiError::base_datos("Error al obtener los accesos"))?
+        .unwrap_or_default();
+    let accesos = conn
+        .exec_map(
+            format!(
+                "SELECT id, metodo, ruta, estado, latencia_ms, actor, ip, \
+                 DATE_FORMAT(registrado_en, '%Y-%m-%d %H:%i:%s.%f') \
+                 FROM accesos WHERE {} ORDER BY registrado_en DESC, id DESC LIMIT {} OFFSET {}",
+                condicion,
+                por_pagina,
+                u64::from(pagina - 1) * u64::from(por_pagina)
+            ),
+            parametros,
+            |(id, metodo, ruta, estado, latencia_ms, actor, ip, registrado_en): (_, _, _, _, _, _, _, String)| {
+                RegistroAcceso {
+                    id,
+                    metodo,
+                    ruta,
+                    estado,
+                    latencia_ms,
+                    actor,
+                    ip,
+                    // `%f` da microsegundos; la columna sólo guarda milisegundos.
+                    registrado_en: registrado_en[..registrado_en.len() - 3].to_string(),
+                }
+            },
+        )
+        .await
+        .map_err(ApiError::base_datos("Error al obtener los accesos"))?;
+    Ok(ApiResponse::ok(accesos).paginada(Paginacion { pagina, por_pagina, total }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+    use actix_web::middleware::from_fn;
+    use actix_web::test::{call_service, init_service, TestRequest};
+    use actix_web::{App, HttpResponse};
+
+    #[actix_web::test]
+    async fn anota_cada_peticion_menos_las_de_salud() {
+        let registro = web::Data::new(RegistroAccesos::new(true));
+        let app = init_service(
+            App::new()
+                .app_data(registro.clone())
+                .route("/health", web::get().to(HttpResponse::Ok))
+                .route("/entradas/{id}", web::get().to(HttpResponse::NotFound))
+                .wrap(from_fn(registrar_acceso)),
+        )
+        .await;
+
+        call_service(&app, TestRequest::get().uri("/health").to_request()).await;
+        let res = call_service(&app, TestRequest::get().uri("/entradas/7?campos=id").to_request()).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+        let pendientes = registro.pendientes.lock().unwrap();
+        assert_eq!(pendientes.len(), 1);
+        assert_eq!(pendientes[0].metodo, "GET");
+        assert_eq!(pendientes[0].ruta, "/entradas/7");
+        assert_eq!(pendientes[0].estado, 404);
+        assert_eq!(pendientes[0].actor, None);
+    }
+}
@@ -0,0 +1,98 @@
+//! Actualizaciones parciales de una entrada.
+//!
+//! La sentencia es siempre [`UPDATE_PARCIAL`](crate::db::UPDATE_PARCIAL): las columnas que
+//! no se envían van como NULL y conservan su valor, así que no se arma SQL según los campos
+//! recibidos y la sentencia se puede preparar una sola vez.
+
+use async_graphql::InputObject;
+use mysql_async::{params, Params};
+use serde::{Deserialize, Serialize};
+
+/// Estructura para la actualización de una entrada.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, InputObject)]
+#[graphql(rename_fields = "snake_case")]
+pub struct ActualizarEntrada {
+    pub cliente_id: Option<u32>,
+    pub funcion_id: Option<u32>,
+    pub cantidad_entradas: Option<u32>,
+}
+
+impl ActualizarEntrada {
+    /// Indica si no se pidió cambiar ningún campo.
+    pub fn es_vacia(&self) -> bool {
+        self.cliente_id.is_none() && self.funcion_id.is_none()
+            && self.cantidad_entradas.is_none()
+    }
+
+    /// Parámetros de [`UPDATE_PARCIAL`](crate::db::UPDATE_PARCIAL) para la entrada `id`.
+    pub fn parametros(&self, id: u32) -> Params {
+        params! {
+            "id" => id,
+            "cliente_id" => self.cliente_id,
+            "funcion_id" => self.funcion_id,
+            "cantidad_entradas" => self.cantidad_entradas,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::UPDATE_PARCIAL;
+    use mysql_async::Value;
+    use proptest::prelude::*;
+
+    const COLUMNAS: [&str; 3] = ["cliente_id", "funcion_id", "cantidad_entradas"];
+
+    prop_compose! {
+        fn actualizacion()(
+            cliente_id in proptest::option::of(any::<u32>()),
+            funcion_id in proptest::option::of(any::<u32>()),
+            cantidad_entradas in proptest::option::of(any::<u32>()),
+        ) -> ActualizarEntrada {
+            ActualizarEntrada { cliente_id, funcion_id, cantidad_entradas }
+        }
+    }
+
+    /// Marcadores `:nombre` de la sentencia, en orden de aparición.
+    fn marcadores(sentencia: &str) -> Vec<&str> {
+        sentencia
+            .split(':')
+            .skip(1)
+            .map(|resto| resto.split(|c: char| !c.is_ascii_alphanumeric() && c != '_').next().unwrap_or(""))
+            .collect()
+    }
+
+    #[test]
+    fn cada_columna_conserva_su_valor_si_llega_null() {
+        for columna in COLUMNAS {
+            let asignacion = format!("{columna} = COALESCE(:{columna}, {columna})");
+            assert!(UPDATE_PARCIAL.contains(&asignacion), "Falta {asignacion}");
+        }
+        assert!(UPDATE_PARCIAL.ends_with("WHERE id = :id"));
+    }
+
+    proptest! {
+        #[test]
+        fn los_parametros_corresponden_a_la_sentencia(entrada_id in any::<u32>(), datos in actualizacion()) {
+            let Params::Named(parametros) = datos.parametros(entrada_id) else {
+                panic!("Los parámetros deben ser nombrados");
+            };
+
+            // Cada marcador tiene exactamente un parámetro y no sobra ninguno.
+            let mut nombres: Vec<&[u8]> = marcadores(UPDATE_PARCIAL).into_iter().map(str::as_bytes).collect();
+            nombres.sort();
+            nombres.dedup();
+            let mut recibidos: Vec<&[u8]> = parametros.keys().map(Vec::as_slice).collect();
+            recibidos.sort();
+            prop_assert_eq!(recibidos, nombres);
+
+            // Los campos presentes viajan con su valor; los ausentes, como NULL.
+            let esperados = [datos.cliente_id, datos.funcion_id, datos.cantidad_entradas];
+            prop_assert_eq!(&parametros[b"id".as_slice()], &Value::from(entrada_id));
+            for (columna, esperado) in COLUMNAS.into_iter().zip(esperados) {
+                prop_assert_eq!(&parametros[columna.as_bytes()], &esperado.map_or(Value::NULL, Value::from));
+            }
+        }
+    }
+}
@@ -0,0 +1,131 @@
+//! Traducción de los parámetros `group_by`/`agg` a consultas GROUP BY.
+
+use serde::ser::{SerializeMap, SerializeSeq};
+use serde::{Deserialize, Serialize, Serializer};
+
+/// Parámetros de consulta del endpoint de agregación.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ParametrosAgregado {
+    pub group_by: Option<String>,
+    pub agg: Option<String>,
+}
+
+/// Campos por los que se permite agrupar en `/entradas/agregado`, con su columna en
+/// [`crate::db::TABLAS_ENTRADAS`].
+pub const CAMPOS_AGRUPABLES: &[(&str, &str)] = &[
+    ("cliente_id", "e.cliente_id"),
+    ("numero_cedula", "c.numero_cedula"),
+    ("nombre_cliente", "c.nombre"),
+    ("funcion_id", "e.funcion_id"),
+    ("nombre_funcion", "f.titulo"),
+    ("horario_funcion", "DATE_FORMAT(f.horario, '%Y-%m-%dT%H:%i:%sZ')"),
+    ("sala_id", "f.sala_id"),
+    ("sala", "s.nombre"),
+];
+
+/// Campos numéricos sobre los que se permite aplicar funciones de agregación.
+pub const CAMPOS_AGREGABLES: &[&str] = &["cantidad_entradas", "total"];
+
+/// Construye la consulta GROUP BY de las entradas de `cine` a partir de los parámetros,
+/// validando cada campo contra las listas permitidas. Devuelve la consulta y el alias de
+/// cada columna.
+pub fn construir_consulta_agregado(params: &ParametrosAgregado, cine: u32) -> Result<(String, Vec<String>), String> {
+    let mut grupos = Vec::new();
+    for campo in params.group_by.as_deref().unwrap_or("").split(',').map(str::trim).filter(|c| !c.is_empty()) {
+        let Some(grupo) = CAMPOS_AGRUPABLES.iter().find(|(nombre, _)| *nombre == campo) else {
+            return Err(format!("No se permite agrupar por el campo '{}'", campo));
+        };
+        if !grupos.contains(grupo) {
+            grupos.push(*grupo);
+        }
+    }
+
+    let mut columnas: Vec<String> = grupos.iter().map(|(nombre, columna)| format!("{} AS {}", columna, nombre)).collect();
+    let mut alias: Vec<String> = grupos.iter().map(|(nombre, _)| nombre.to_string()).collect();
+    let agg = params.agg.as_deref().unwrap_or("count");
+    for expresion in agg.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let (funcion, campo) = match expresion.split_once(':') {
+            Some((funcion, campo)) => (funcion.trim(), Some(campo.trim())),
+            None => (expresion, None),
+        };
+        let (columna, nombre) = match (funcion, campo) {
+            ("count", None) => ("COUNT(*)".to_string(), "count".to_string()),
+            ("count" | "sum" | "avg" | "min" | "max", Some(campo)) if CAMPOS_AGREGABLES.contains(&campo) => {
+                (format!("{}(e.{})", funcion.to_uppercase(), campo), format!("{}_{}", funcion, campo))
+            }
+            ("sum" | "avg" | "min" | "max", None) => {
+                return Err(format!("La agregación '{}' requiere un campo (por ejemplo {}:cantidad_entradas)", funcion, funcion));
+            }
+            ("count" | "sum" | "avg" | "min" | "max", Some(campo)) => {
+                return Err(format!("No se permite agregar sobre el campo '{}'", campo));
+            }
+            _ => return Err(format!("Función de agregación no soportada: '{}'", funcion)),
+        };
+        if !alias.contains(&nombre) {
+            columnas.push(format!("{} AS {}", columna, nombre));
+            alias.push(nombre);
+        }
+    }
+
+    let mut query = format!(
+        "SELECT {} FROM {} WHERE e.cine_id = {}",
+        columnas.join(", "),
+        crate::db::TABLAS_ENTRADAS,
+        cine
+    );
+    if !grupos.is_empty() {
+        let nombres = alias[..grupos.len()].join(", ");
+        query.push_str(&format!(" GROUP BY {} ORDER BY {}", nombres, nombres));
+    }
+    Ok((query, alias))
+}
+
+/// Filas de una agregación. Los alias se guardan una sola vez y se emparejan con los
+/// valores de cada fila al serializar, sin copiarlos por fila.
+#[derive(Debug, Clone)]
+pub struct ResultadoAgregado {
+    pub alias: Vec<String>,
+    pub filas: Vec<Vec<serde_json::Value>>,
+}
+
+impl Serialize for ResultadoAgregado {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut filas = serializer.serialize_seq(Some(self.filas.len()))?;
+        for valores in &self.filas {
+            filas.serialize_element(&FilaAgregado { alias: &self.alias, valores })?;
+        }
+        filas.end()
+    }
+}
+
+/// Vista de una fila como objeto JSON `{alias: valor}`.
+struct FilaAgregado<'a> {
+    alias: &'a [String],
+    valores: &'a [serde_json::Value],
+}
+
+impl Serialize for FilaAgregado<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut objeto = serializer.serialize_map(Some(self.alias.len()))?;
+        for (alias, valor) in self.alias.iter().zip(self.valores) {
+            objeto.serialize_entry(alias, valor)?;
+        }
+        objeto.end()
+    }
+}
+
+/// Convierte un valor devuelto por MySQL en JSON, priorizando los tipos numéricos.
+pub fn valor_a_json(valor: mysql_async::Value) -> serde_json::Value {
+    if valor == mysql_async::Value::NULL {
+        return serde_json::Value::Null;
+    }
+    if let Ok(entero) = mysql_async::from_value_opt::<i64>(valor.clone()) {
+        return entero.into();
+    }
+    if let Ok(decimal) = mysql_async::from_value_opt::<f64>(valor.clone()) {
+        return decimal.into();
+    }
+    mysql_async::from_value_opt::<String>(valor)
+        .map(serde_json::Value::from)
+        .unwrap_or(serde_json::Value::Null)
+}
@@ -0,0 +1,269 @@
+//! Archivo de las entradas que ya no cambian.
+//!
+//! Las entradas usadas o canceladas que llevan más de `Config::archivo_antiguedad_dias` sin
+//! cambios salen de `entradas` y pasan a `entradas_archivo` con los datos de su cliente y
+//! su función, para que la tabla de trabajo no crezca sin fin. Lo hace la tarea de
+//! [`archivar_entradas`] y, a mano, `POST /admin/archivo`; se consultan en
+//! `GET /admin/archivo`. Archivar no es un cambio de la entrada: no deja auditoría ni
+//! eventos. Los listados, reportes y búsquedas de entradas dejan de contar las archivadas.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix_web::web;
+use chrono::{DateTime, Days, FixedOffset, NaiveDateTime};
+use mysql_async::prelude::*;
+use mysql_async::{FromRowError, Pool, Row, TxOpts};
+use serde::{Deserialize, Serialize};
+
+use crate::auth::Administrador;
+use crate::cache::CacheLecturas;
+use crate::cines::CineActual;
+use crate::config::Config;
+use crate::db;
+use crate::error::ApiError;
+use crate::listado::{POR_PAGINA_DEFECTO, POR_PAGINA_MAXIMO};
+use crate::models::EstadoEntrada;
+use crate::reloj::Reloj;
+use crate::respuesta::{ApiResponse, Paginacion};
+use crate::tareas::{self, Intervalo, Trabajo};
+
+/// Cada cuánto se archivan las entradas antiguas.
+const PERIODO_ARCHIVO: Duration = Duration::from_secs(3600);
+
+/// Entradas que se archivan en cada transacción, para no bloquear la tabla mucho tiempo.
+const ENTRADAS_POR_LOTE: u32 = 1000;
+
+/// Pasa a `entradas_archivo` las entradas usadas o canceladas sin cambios desde hace más
+/// de `antiguedad_dias` según `reloj`, de todos los cines, y devuelve cuántas eran. Archiva
+/// por lotes, cada uno en su transacción; si falla a mitad, las de los lotes anteriores
+/// quedan archivadas.
+pub async fn archivar(
+    pool: &Pool,
+    cache: &CacheLecturas,
+    reloj: &dyn Reloj,
+    antiguedad_dias: u32,
+) -> Result<u64, mysql_async::Error> {
+    let antes_de = reloj
+        .ahora_naive()
+        .checked_sub_days(Days::new(antiguedad_dias.into()))
+        .unwrap_or(DateTime::UNIX_EPOCH.naive_utc());
+    let mut conn = db::conectar(pool).await?;
+    let mut archivadas = 0;
+    let resultado = loop {
+        let lote = async {
+            let mut tx = conn.start_transaction(TxOpts::default()).await?;
+            let ids: Vec<u32> = tx
+                .exec(
+                    "SELECT id FROM entradas WHERE estado IN ('usada', 'cancelada') \
+                     AND updated_at < :antes_de ORDER BY id LIMIT :limite FOR UPDATE SKIP LOCKED",
+                    params! { "antes_de" => antes_de, "limite" => ENTRADAS_POR_LOTE },
+                )
+                .await?;
+            if !ids.is_empty() {
+                let ids: Vec<String> = ids.iter().map(u32::to_string).collect();
+                tx.query_drop(format!(
+                    "INSERT INTO entradas_archivo (id, cine_id, cliente_id, numero_cedula, nombre_cliente, funcion_id, \
+                     titulo_funcion, horario_funcion, sala, cantidad_entradas, total, estado, version, created_at, updated_at) \
+                     SELECT e.id, e.cine_id, c.id, c.numero_cedula, c.nombre, f.id, f.titulo, f.horario, s.nombre, \
+                     e.cantidad_entradas, e.total, e.estado, e.version, e.created_at, e.updated_at \
+                     FROM entradas e JOIN clientes c ON c.id = e.cliente_id JOIN funciones f ON f.id = e.funcion_id \
+                     JOIN salas s ON s.id = f.sala_id WHERE e.id IN ({})",
+                    ids.join(",")
+                ))
+                .await?;
+                tx.query_drop(format!("DELETE FROM entradas WHERE id IN ({})", ids.join(",")))
+                    .await?;
+            }
+            tx.commit().await?;
+            Ok::<_, mysql_async::Error>(ids.len() as u64)
+        };
+        match lote.await {
+            Ok(0) => break Ok(archivadas),
+            Ok(n) => archivadas += n,
+            Err(e) => break Err(e),
+        }
+    };
+    if archivadas > 0 {
+        cache.entradas.vaciar().await;
+    }
+    resultado
+}
+
+/// Tarea que archiva las entradas con más de `antiguedad_dias` cada [`PERIODO_ARCHIVO`].
+pub fn archivar_entradas(
+    pool: Pool,
+    cache: Arc<CacheLecturas>,
+    reloj: Arc<dyn Reloj>,
+    antiguedad_dias: u32,
+) -> (Intervalo, Trabajo) {
+    let trabajo = tareas::trabajo(move || {
+        let pool = pool.clone();
+        let cache = cache.clone();
+        let reloj = reloj.clone();
+        async move {
+            let archivadas = archivar(&pool, &cache, reloj.as_ref(), antiguedad_dias)
+                .await
+                .map_err(|e| format!("Error al archivar las entradas: {:?}", e))?;
+            if archivadas > 0 {
+                log::info!("Se archivaron {} entradas con más de {} días sin cambios", archivadas, antiguedad_dias);
+            }
+            Ok(())
+        }
+    });
+    (Intervalo::cada(PERIODO_ARCHIVO), trabajo)
+}
+
+/// Parámetros de `POST /admin/archivo`.
+#[derive(Debug, Deserialize)]
+pub struct ParametrosArchivo {
+    /// Días sin cambios a partir de los que se archiva; sin él, los de la configuración.
+    pub antiguedad_dias: Option<u32>,
+}
+
+/// Resultado de `POST /admin/archivo`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResultadoArchivo {
+    pub archivadas: u64,
+}
+
+/// Handler que archiva ahora, sin esperar a la tarea. Como archiva las de todos los cines,
+/// sólo lo usa un administrador de toda la cadena.
+pub async fn archivar_ahora(
+    admin: Administrador,
+    pool: web::Data<Pool>,
+    cache: web::Data<CacheLecturas>,
+    reloj: web::Data<dyn Reloj>,
+    config: web::Data<Config>,
+    parametros: web::Query<ParametrosArchivo>,
+) -> Result<ApiResponse<ResultadoArchivo>, ApiError> {
+    if admin.0.cine.is_some() {
+        return Err(ApiError::Prohibido(
+            "Sólo un administrador de toda la cadena puede archivar entradas".to_string(),
+        ));
+    }
+    let antiguedad_dias = parametros.antiguedad_dias.unwrap_or(config.archivo_antiguedad_dias);
+    if antiguedad_dias == 0 {
+        return Err(ApiError::Validacion("Indique antiguedad_dias mayor que 0".to_string()));
+    }
+    let archivadas = archivar(&pool, &cache, reloj.as_ref(), antiguedad_dias)
+        .await
+        .map_err(ApiError::escritura("Error al archivar las entradas"))?;
+    Ok(ApiResponse::ok(ResultadoArchivo { archivadas }))
+}
+
+/// Filtros de `GET /admin/archivo`.
+#[derive(Debug, Deserialize)]
+pub struct FiltrosArchivo {
+    pub numero_cedula: Option<String>,
+    pub funcion_id: Option<u32>,
+    pub page: Option<u32>,
+    pub per_page: Option<u32>,
+}
+
+/// Entrada archivada tal como la devuelve `GET /admin/archivo`, con el horario de su
+/// función en la zona configurada.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EntradaArchivada {
+    pub id: u32,
+    pub cliente_id: u32,
+    pub numero_cedula: String,
+    pub nombre_cliente: String,
+    pub funcion_id: u32,
+    pub titulo_funcion: String,
+    pub horario_funcion: DateTime<FixedOffset>,
+    pub sala: String,
+    pub cantidad_entradas: u32,
+    pub total: f64,
+    pub estado: EstadoEntrada,
+    pub version: u32,
+    pub created_at: String,
+    pub updated_at: String,
+    pub archivada_en: String,
+}
+
+/// Handler con las entradas archivadas del cine de la petición, de la archivada más
+/// recientemente a la más antigua.
+pub async fn listar_archivadas(
+    _admin: Administrador,
+    CineActual(cine): CineActual,
+    pool: web::Data<Pool>,
+    config: web::Data<Config>,
+    filtros: web::Query<FiltrosArchivo>,
+) -> Result<ApiResponse<Vec<EntradaArchivada>>, ApiError> {
+    let pagina = filtros.page.unwrap_or(1);
+    if pagina == 0 {
+        return Err(ApiError::Validacion("El parámetro 'page' empieza en 1".to_string()));
+    }
+    let por_pagina = filtros.per_page.unwrap_or(POR_PAGINA_DEFECTO);
+    if por_pagina == 0 {
+        return Err(ApiError::Validacion("El parámetro 'per_page' debe ser mayor que 0".to_string()));
+    }
+    let por_pagina = por_pagina.min(POR_PAGINA_MAXIMO);
+
+    let condicion = "cine_id = :cine_id AND (:numero_cedula IS NULL OR numero_cedula = :numero_cedula) \
+                     AND (:funcion_id IS NULL OR funcion_id = :funcion_id)";
+    let parametros = params! {
+        "cine_id" => cine,
+        "numero_cedula" => filtros.numero_cedula.as_deref().map(str::trim),
+        "funcion_id" => filtros.funcion_id,
+    };
+    let mut conn = db::conectar(&pool).await.map_err(ApiError::conexion)?;
+    let total: u64 = conn
+        .exec_first(format!("SELECT COUNT(*) FROM entradas_archivo WHERE {}", condicion), parametros.clone())
+        .await
+        .map_err(ApiError::base_datos("Error al obtener las entradas archivadas"))?
+        .unwrap_or_default();
+    let entradas: Vec<EntradaArchivada> = conn
+        .exec(
+            format!(
+                "SELECT id, cliente_id, numero_cedula, nombre_cliente, funcion_id, titulo_funcion, horario_funcion, sala, \
+                 cantidad_entradas, CAST(total AS DOUBLE), estado, version, \
+                 DATE_FORMAT(created_at, '%Y-%m-%d %H:%i:%s'), DATE_FORMAT(updated_at, '%Y-%m-%d %H:%i:%s'), \
+                 DATE_FORMAT(archivada_en, '%Y-%m-%d %H:%i:%s') \
+                 FROM entradas_archivo WHERE {} ORDER BY archivada_en DESC, id DESC LIMIT {} OFFSET {}",
+                condicion,
+                por_pagina,
+                u64::from(pagina - 1) * u64::from(por_pagina)
+            ),
+            parametros,
+        )
+        .await
+        .map_err(ApiError::base_datos("Error al obtener las entradas archivadas"))?;
+    let entradas = entradas
+        .into_iter()
+        .map(|entrada| EntradaArchivada { horario_funcion: entrada.horario_funcion.with_timezone(&config.zona_horaria), ..entrada })
+        .collect();
+    Ok(ApiResponse::ok(entradas).paginada(Paginacion { pagina, por_pagina, total }))
+}
+
+/// Mapeo posicional según las columnas de [`listar_archivadas`], con el horario en UTC.
+/// Son más columnas de las que admite una tupla, así que se toman una a una.
+impl FromRow for EntradaArchivada {
+    fn from_row_opt(mut row: Row) -> Result<Self, FromRowError> {
+        fn columna<T: FromValue>(row: &mut Row, indice: usize) -> Option<T> {
+            row.take_opt(indice)?.ok()
+        }
+
+        let entrada = (|| {
+            Some(EntradaArchivada {
+                id: columna(&mut row, 0)?,
+                cliente_id: columna(&mut row, 1)?,
+                numero_cedula: columna(&mut row, 2)?,
+                nombre_cliente: columna(&mut row, 3)?,
+                funcion_id: columna(&mut row, 4)?,
+                titulo_funcion: columna(&mut row, 5)?,
+                horario_funcion: columna::<NaiveDateTime>(&mut row, 6)?.and_utc().fixed_offset(),
+                sala: columna(&mut row, 7)?,
+                cantidad_entradas: columna(&mut row, 8)?,
+                total: columna(&mut row, 9)?,
+                estado: EstadoEntrada::desde_str(&columna::<String>(&mut row, 10)?)?,
+                version: columna(&mut row, 11)?,
+                created_at: columna(&mut row, 12)?,
+                updated_at: columna(&mut row, 13)?,
+                archivada_en: columna(&mut row, 14)?,
+            })
+        })();
+        entrada.ok_or(FromRowError(row))
+    }
+}
@@ -0,0 +1,496 @@
+//! Autenticación con JWT.
+//!
+//! `POST /auth/login` entrega un token firmado con HS256 y [`exigir_autenticacion`] protege las
+//! rutas que lo envuelven, dejando la [`Sesion`] del token en la petición para los handlers.
+//! Las integraciones sin login interactivo pueden enviar en su lugar una clave de
+//! [`crate::claves_api`] en la cabecera `X-Api-Key`.
+//!
+//! Los usuarios se guardan en la tabla `usuarios` con la clave hasheada con Argon2. El
+//! primer usuario se puede registrar sin token y queda como administrador; a partir de
+//! ahí `POST /auth/register` exige una sesión de administrador.
+//!
+//! El token de acceso dura poco; junto a él se entrega un token de refresco opaco, del
+//! que sólo se guarda el hash, para obtener otro par en `POST /auth/refresh` sin volver a
+//! iniciar sesión. Cada refresco revoca el token usado y `POST /auth/logout` revoca el actual.
+//!
+//! El rol viaja en el token: los taquilleros crean y consultan entradas, y sólo los
+//! administradores pueden modificarlas o eliminarlas, lo que se exige con el extractor
+//! [`Administrador`].
+
+use std::future::{ready, Ready};
+use std::sync::LazyLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderMap, AUTHORIZATION};
+use actix_web::middleware::Next;
+use actix_web::{web, Error, FromRequest, HttpMessage, HttpRequest, HttpResponse};
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use mysql_async::{prelude::*, Conn, Pool, TxOpts};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::config::Config;
+use crate::claves_api::{sesion_de_clave, CABECERA_CLAVE_API};
+use crate::db;
+use crate::db::errores::{self, FalloMysql};
+use crate::error::ApiError;
+use crate::json::Json;
+use crate::models::{Rol, Usuario};
+use crate::respuesta::ApiResponse;
+
+/// Longitud mínima de una clave nueva.
+const CLAVE_MINIMA: usize = 8;
+/// Longitud máxima del nombre de usuario, la de la columna.
+const USUARIO_MAXIMO: usize = 100;
+
+/// Configuración de la emisión y verificación de tokens.
+#[derive(Debug, Clone)]
+pub struct ConfigAuth {
+    /// Secreto HMAC con el que se firman los tokens. No puede estar vacío.
+    pub secreto: String,
+    /// Tiempo de validez de un token de acceso desde su emisión.
+    pub duracion_token: Duration,
+    /// Tiempo de validez de un token de refresco, lo que puede durar una sesión sin volver a iniciarla.
+    pub duracion_refresco: Duration,
+}
+
+/// Datos contenidos en un token válido.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Sesion {
+    /// Usuario autenticado.
+    pub sub: String,
+    pub rol: Rol,
+    /// Cine al que está atado el usuario; sin él, la sesión elige cine en cada petición
+    /// (ver [`crate::cines`]).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cine: Option<u32>,
+    pub iat: u64,
+    pub exp: u64,
+}
+
+/// Credenciales de `/auth/login`.
+#[derive(Debug, Deserialize)]
+pub struct Credenciales {
+    pub usuario: String,
+    pub clave: String,
+}
+
+/// Cuerpo de `/auth/refresh` y `/auth/logout`.
+#[derive(Debug, Deserialize)]
+pub struct TokenRefresco {
+    pub token_refresco: String,
+}
+
+/// Datos de `/auth/register`. Sin `rol`, el usuario se crea como taquillero; sin `cine_id`,
+/// en el cine de quien lo registra, o en ninguno si quien lo registra no tiene.
+#[derive(Debug, Deserialize)]
+pub struct RegistrarUsuario {
+    pub usuario: String,
+    pub clave: String,
+    pub rol: Option<Rol>,
+    pub cine_id: Option<u32>,
+}
+
+pub(crate) fn ahora() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default()
+}
+
+/// Firma un token para `usuario` con su `rol` y su `cine`, válido durante `config.duracion_token`.
+pub fn emitir_token(
+    config: &ConfigAuth,
+    usuario: &str,
+    rol: Rol,
+    cine: Option<u32>,
+) -> Result<String, jsonwebtoken::errors::Error> {
+    let iat = ahora();
+    let sesion = Sesion {
+        sub: usuario.to_string(),
+        rol,
+        cine,
+        iat,
+        exp: iat + config.duracion_token.as_secs(),
+    };
+    encode(&Header::default(), &sesion, &EncodingKey::from_secret(config.secreto.as_bytes()))
+}
+
+/// Comprueba la firma y la caducidad del token.
+pub fn verificar_token(config: &ConfigAuth, token: &str) -> Result<Sesion, jsonwebtoken::errors::Error> {
+    let mut validacion = Validation::default();
+    validacion.leeway = 0;
+    decode::<Sesion>(token, &DecodingKey::from_secret(config.secreto.as_bytes()), &validacion).map(|t| t.claims)
+}
+
+/// Genera un secreto aleatorio (token de refresco o clave de API) y lo devuelve junto a su hash.
+pub(crate) fn generar_secreto() -> (String, String) {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    let token: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    let hash = hash_secreto(&token);
+    (token, hash)
+}
+
+/// SHA-256 en hexadecimal. Basta un hash rápido porque el secreto ya tiene 256 bits de azar.
+pub(crate) fn hash_secreto(token: &str) -> String {
+    Sha256::digest(token.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Hash contra el que se verifica la clave de un usuario que no existe, para que el login
+/// tarde lo mismo y no delate qué usuarios hay.
+static HASH_FICTICIO: LazyLock<String> = LazyLock::new(|| hashear_clave("clave-ficticia").unwrap_or_default());
+
+/// Genera el hash Argon2 de una clave, en formato PHC con su sal.
+pub fn hashear_clave(clave: &str) -> Result<String, argon2::password_hash::Error> {
+    let sal = SaltString::generate(&mut OsRng);
+    Ok(Argon2::default().hash_password(clave.as_bytes(), &sal)?.to_string())
+}
+
+/// Comprueba una clave contra un hash generado por [`hashear_clave`].
+pub fn verificar_clave(clave: &str, hash: &str) -> bool {
+    PasswordHash::new(hash)
+        .map(|hash| Argon2::default().verify_password(clave.as_bytes(), &hash).is_ok())
+        .unwrap_or(false)
+}
+
+/// Extrae el token de la cabecera `Authorization: Bearer <token>`.
+fn token_bearer(cabeceras: &HeaderMap) -> Option<&str> {
+    let valor = cabeceras.get(AUTHORIZATION)?.to_str().ok()?;
+    let (esquema, token) = valor.split_once(' ')?;
+    esquema.eq_ignore_ascii_case("bearer").then(|| token.trim())
+}
+
+/// Valida el token de las cabeceras y devuelve su sesión.
+fn sesion_de(config: &ConfigAuth, cabeceras: &HeaderMap) -> Result<Sesion, ApiError> {
+    let token = token_bearer(cabeceras)
+        .ok_or_else(|| ApiError::NoAutorizado("Falta la cabecera 'Authorization: Bearer <token>'".to_string()))?;
+    verificar_token(config, token).map_err(|_| ApiError::NoAutorizado("Token inválido o caducado".to_string()))
+}
+
+/// Middleware que rechaza con 401 las peticiones sin un token o una clave de API válidos.
+/// El 401 se devuelve como respuesta, no como error, para que los middlewares externos
+/// (CORS, métricas) la vean como cualquier otra.
+pub async fn exigir_autenticacion<B: MessageBody>(
+    config: web::Data<Config>,
+    pool: web::Data<Pool>,
+    req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<EitherBody<B>>, Error> {
+    let clave = req.headers().get(CABECERA_CLAVE_API).map(|v| v.to_str().unwrap_or_default().to_string());
+    let sesion = match clave {
+        Some(clave) => sesion_de_clave(&pool, &clave).await,
+        None => sesion_de(&config.auth, req.headers()),
+    };
+    match sesion {
+        Ok(sesion) => {
+            req.extensions_mut().insert(sesion);
+            next.call(req).await.map(ServiceResponse::map_into_left_body)
+        }
+        Err(error) => Ok(req.error_response(error).map_into_right_body()),
+    }
+}
+
+/// Sesión de la petición, disponible en las rutas protegidas por [`exigir_autenticacion`].
+impl FromRequest for Sesion {
+    type Error = ApiError;
+    type Future = Ready<Result<Sesion, ApiError>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(
+            req.extensions()
+                .get::<Sesion>()
+                .cloned()
+                .ok_or_else(|| ApiError::NoAutorizado("Se requiere autenticación".to_string())),
+        )
+    }
+}
+
+/// Sesión de un administrador. Como parámetro de un handler, responde 403 a cualquier otro rol.
+#[derive(Debug, Clone)]
+pub struct Administrador(pub Sesion);
+
+impl Administrador {
+    pub(crate) fn desde_sesion(sesion: Sesion) -> Result<Administrador, ApiError> {
+        match sesion.rol {
+            Rol::Admin => Ok(Administrador(sesion)),
+            _ => Err(ApiError::Prohibido("Esta operación requiere el rol de administrador".to_string())),
+        }
+    }
+}
+
+impl FromRequest for Administrador {
+    type Error = ApiError;
+    type Future = Ready<Result<Administrador, ApiError>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        ready(Sesion::from_request(req, payload).into_inner().and_then(Administrador::desde_sesion))
+    }
+}
+
+/// Emite un token de acceso y uno de refresco para el usuario, guardando el hash del
+/// segundo, y construye la respuesta de `/auth/login` y `/auth/refresh`.
+async fn abrir_sesion(
+    conn: &mut Conn,
+    config: &ConfigAuth,
+    usuario_id: u32,
+    usuario: &str,
+    rol: Rol,
+    cine: Option<u32>,
+) -> Result<ApiResponse<serde_json::Value>, ApiError> {
+    let token = emitir_token(config, usuario, rol, cine).map_err(|e| {
+        log::error!("Error al firmar token: {:?}", e);
+        ApiError::BaseDatos("Error al emitir el token".to_string())
+    })?;
+    let (token_refresco, hash) = generar_secreto();
+    conn.exec_drop(
+        "INSERT INTO tokens_refresco (usuario_id, hash, expira_en) \
+         VALUES (:usuario_id, :hash, NOW() + INTERVAL :segundos SECOND)",
+        params! {
+            "usuario_id" => usuario_id,
+            "hash" => hash,
+            "segundos" => config.duracion_refresco.as_secs(),
+        },
+    )
+    .await
+    .map_err(ApiError::base_datos("Error al emitir el token"))?;
+
+    Ok(ApiResponse::ok(serde_json::json!({
+        "token": token,
+        "tipo": "Bearer",
+        "expira_en": config.duracion_token.as_secs(),
+        "token_refresco": token_refresco,
+        "refresco_expira_en": config.duracion_refresco.as_secs(),
+    })))
+}
+
+/// Handler que valida las credenciales contra la tabla `usuarios` y devuelve los tokens.
+pub async fn iniciar_sesion(
+    pool: web::Data<Pool>,
+    config: web::Data<Config>,
+    credenciales: Json<Credenciales>,
+) -> Result<ApiResponse<serde_json::Value>, ApiError> {
+    let Credenciales { usuario, clave } = credenciales.into_inner();
+    let mut conn = db::conectar(&pool).await.map_err(ApiError::conexion)?;
+    let (id, rol, cine) = comprobar_credenciales(&mut conn, &usuario, clave).await?;
+    abrir_sesion(&mut conn, &config.auth, id, &usuario, rol, cine).await
+}
+
+/// ID, rol y cine del usuario si la clave es la suya; si no, 401.
+pub(crate) async fn comprobar_credenciales(
+    conn: &mut Conn,
+    usuario: &str,
+    clave: String,
+) -> Result<(u32, Rol, Option<u32>), ApiError> {
+    let fila: Option<(u32, String, String, Option<u32>)> = conn
+        .exec_first(
+            "SELECT id, clave_hash, rol, cine_id FROM usuarios WHERE usuario = :usuario",
+            params! { "usuario" => usuario },
+        )
+        .await
+        .map_err(ApiError::base_datos("Error al iniciar sesión"))?;
+
+    // Argon2 es costoso a propósito, así que se verifica fuera de los workers. Un usuario
+    // inexistente también se verifica, contra un hash ficticio.
+    let (hash, usuario) = match fila {
+        Some((id, hash, rol, cine)) => (hash, Some((id, rol, cine))),
+        None => (HASH_FICTICIO.clone(), None),
+    };
+    let valida = web::block(move || verificar_clave(&clave, &hash)).await.unwrap_or(false);
+    match usuario {
+        Some((id, rol, cine)) if valida => Ok((id, Rol::desde_str(&rol).unwrap_or(Rol::Taquillero), cine)),
+        _ => Err(ApiError::NoAutorizado("Usuario o clave incorrectos".to_string())),
+    }
+}
+
+/// Handler que cambia un token de refresco vigente por un par nuevo. El token usado
+/// queda revocado, así que cada uno sirve una sola vez.
+pub async fn refrescar_sesion(
+    pool: web::Data<Pool>,
+    config: web::Data<Config>,
+    datos: Json<TokenRefresco>,
+) -> Result<ApiResponse<serde_json::Value>, ApiError> {
+    let hash = hash_secreto(&datos.token_refresco);
+    let mut conn = db::conectar(&pool).await.map_err(ApiError::conexion)?;
+    conn.exec_drop(
+        "UPDATE tokens_refresco SET revocado_en = NOW() \
+         WHERE hash = :hash AND revocado_en IS NULL AND expira_en > NOW()",
+        params! { "hash" => &hash },
+    )
+    .await
+    .map_err(ApiError::base_datos("Error al refrescar la sesión"))?;
+    if conn.affected_rows() == 0 {
+        return Err(ApiError::NoAutorizado("Token de refresco inválido, caducado o revocado".to_string()));
+    }
+
+    let (id, usuario, rol, cine): (u32, String, String, Option<u32>) = conn
+        .exec_first(
+            "SELECT u.id, u.usuario, u.rol, u.cine_id FROM tokens_refresco t JOIN usuarios u ON u.id = t.usuario_id \
+             WHERE t.hash = :hash",
+            params! { "hash" => &hash },
+        )
+        .await
+        .map_err(ApiError::base_datos("Error al refrescar la sesión"))?
+        .ok_or_else(|| ApiError::NoAutorizado("El usuario ya no existe".to_string()))?;
+    let rol = Rol::desde_str(&rol).unwrap_or(Rol::Taquillero);
+
+    abrir_sesion(&mut conn, &config.auth, id, &usuario, rol, cine).await
+}
+
+/// Handler que revoca un token de refresco al cerrar la sesión. Responde 204 aunque el
+/// token ya no fuera válido, para no revelar cuáles existen.
+pub async fn cerrar_sesion(
+    pool: web::Data<Pool>,
+    datos: Json<TokenRefresco>,
+) -> Result<HttpResponse, ApiError> {
+    let mut conn = db::conectar(&pool).await.map_err(ApiError::conexion)?;
+    conn.exec_drop(
+        "UPDATE tokens_refresco SET revocado_en = NOW() WHERE hash = :hash AND revocado_en IS NULL",
+        params! { "hash" => hash_secreto(&datos.token_refresco) },
+    )
+    .await
+    .map_err(ApiError::base_datos("Error al cerrar la sesión"))?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Cine de un usuario o una clave nuevos que da de alta `admin`: el que pide, si `admin` es
+/// de toda la cadena, o el de `admin`, que no puede dar acceso a otro.
+pub(crate) fn cine_propio(admin: &Sesion, pedido: Option<u32>) -> Result<Option<u32>, ApiError> {
+    match (admin.cine, pedido) {
+        (Some(propio), Some(pedido)) if propio != pedido => {
+            Err(ApiError::Prohibido("No puede dar acceso a otro cine".to_string()))
+        }
+        (Some(propio), _) => Ok(Some(propio)),
+        (None, pedido) => Ok(pedido),
+    }
+}
+
+/// Handler que registra un usuario. Mientras no exista ninguno se permite sin token y el
+/// usuario se crea como administrador; después sólo pueden registrar los administradores.
+pub async fn registrar_usuario(
+    req: HttpRequest,
+    pool: web::Data<Pool>,
+    config: web::Data<Config>,
+    datos: Json<RegistrarUsuario>,
+) -> Result<ApiResponse<Usuario>, ApiError> {
+    let RegistrarUsuario { usuario, clave, rol, cine_id } = datos.into_inner();
+    let usuario = usuario.trim().to_string();
+    if usuario.is_empty() || usuario.chars().count() > USUARIO_MAXIMO {
+        return Err(ApiError::Validacion(format!(
+            "El usuario debe tener entre 1 y {} caracteres",
+            USUARIO_MAXIMO
+        )));
+    }
+    if clave.chars().count() < CLAVE_MINIMA {
+        return Err(ApiError::Validacion(format!(
+            "La clave debe tener al menos {} caracteres",
+            CLAVE_MINIMA
+        )));
+    }
+
+    let mut conn = db::conectar(&pool).await.map_err(ApiError::conexion)?;
+    // El recuento bloquea la tabla hasta el alta, así que de dos registros simultáneos
+    // sin usuarios sólo uno queda como administrador; el otro espera o choca y falla.
+    let mut tx = conn
+        .start_transaction(TxOpts::default())
+        .await
+        .map_err(ApiError::base_datos("Error al registrar usuario"))?;
+    let existentes: u64 = tx
+        .query_first("SELECT COUNT(*) FROM usuarios FOR UPDATE")
+        .await
+        .map_err(ApiError::base_datos("Error al registrar usuario"))?
+        .unwrap_or_default();
+    let (rol, cine) = if existentes == 0 {
+        (Rol::Admin, cine_id)
+    } else {
+        let Administrador(admin) = Administrador::desde_sesion(sesion_de(&config.auth, req.headers())?)?;
+        (rol.unwrap_or(Rol::Taquillero), cine_propio(&admin, cine_id)?)
+    };
+
+    let hash = match web::block(move || hashear_clave(&clave)).await {
+        Ok(Ok(hash)) => hash,
+        error => {
+            log::error!("Error al hashear la clave: {:?}", error);
+            return Err(ApiError::BaseDatos("Error al registrar usuario".to_string()));
+        }
+    };
+
+    let result = tx
+        .exec_drop(
+            "INSERT INTO usuarios (usuario, clave_hash, rol, cine_id) VALUES (:usuario, :clave_hash, :rol, :cine_id)",
+            params! { "usuario" => &usuario, "clave_hash" => hash, "rol" => rol.como_str(), "cine_id" => cine },
+        )
+        .await;
+    match result {
+        Ok(_) => {
+            let id = tx.last_insert_id().unwrap_or_default() as u32;
+            tx.commit().await.map_err(ApiError::escritura("Error al registrar usuario"))?;
+            Ok(ApiResponse::creada(Usuario { id, usuario, rol, cine_id: cine }))
+        }
+        Err(e) => Err(match errores::clasificar(&e) {
+            FalloMysql::Duplicado(restriccion) => ApiError::Duplicado {
+                mensaje: "Ya existe un usuario con ese nombre".to_string(),
+                restriccion,
+            },
+            FalloMysql::ReferenciaInexistente(restriccion) => ApiError::ReferenciaInexistente {
+                mensaje: "El cine indicado no existe".to_string(),
+                restriccion,
+            },
+            _ => ApiError::escritura("Error al registrar usuario")(e),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> ConfigAuth {
+        ConfigAuth {
+            secreto: "secreto".to_string(),
+            duracion_token: Duration::from_secs(60),
+            duracion_refresco: Duration::from_secs(3600),
+        }
+    }
+
+    #[test]
+    fn verifica_los_tokens_emitidos() {
+        let token = emitir_token(&config(), "taquilla", Rol::Taquillero, Some(2)).unwrap();
+        let sesion = verificar_token(&config(), &token).unwrap();
+        assert_eq!((sesion.sub.as_str(), sesion.rol, sesion.cine), ("taquilla", Rol::Taquillero, Some(2)));
+        assert!(Administrador::desde_sesion(sesion).is_err());
+
+        let otro_secreto = ConfigAuth { secreto: "otro".to_string(), ..config() };
+        assert!(verificar_token(&otro_secreto, &token).is_err());
+    }
+
+    #[test]
+    fn rechaza_tokens_caducados() {
+        let caducado = ConfigAuth { duracion_token: Duration::ZERO, ..config() };
+        let token = emitir_token(&caducado, "taquilla", Rol::Admin, None).unwrap();
+        std::thread::sleep(Duration::from_millis(1100));
+        assert!(verificar_token(&config(), &token).is_err());
+    }
+
+    #[test]
+    fn verifica_claves_hasheadas() {
+        let hash = hashear_clave("clave-secreta").unwrap();
+        assert!(hash.starts_with("$argon2"));
+        assert!(verificar_clave("clave-secreta", &hash));
+        assert!(!verificar_clave("otra-clave", &hash));
+        assert!(!verificar_clave("clave-secreta", "no-es-un-hash"));
+        assert!(HASH_FICTICIO.starts_with("$argon2"));
+        assert!(!verificar_clave("clave-secreta", &HASH_FICTICIO));
+    }
+
+    #[test]
+    fn los_tokens_de_refresco_solo_se_guardan_hasheados() {
+        let (token, hash) = generar_secreto();
+        assert_eq!(token.len(), 64);
+        assert_ne!(token, hash);
+        assert_eq!(hash_secreto(&token), hash);
+        assert_ne!(generar_secreto().0, token);
+    }
+}
@@ -0,0 +1,129 @@
+//! Sugerencias por prefijo para los campos de texto libre del formulario de venta, a partir
+//! de las entradas del cine de la petición.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use actix_web::web;
+use mysql_async::{prelude::*, Pool};
+use serde::{Deserialize, Serialize};
+
+use crate::cines::CineActual;
+use crate::db;
+use crate::error::ApiError;
+use crate::listado::escapar_like;
+use crate::respuesta::ApiResponse;
+
+/// Campos sobre los que se ofrecen sugerencias, con su columna en [`db::TABLAS_ENTRADAS`].
+/// Ambas tienen índice: el nombre del cliente desde `0006_clientes.sql` y el título de la
+/// función desde `0005_funciones.sql`.
+const CAMPOS_AUTOCOMPLETABLES: &[(&str, &str)] = &[("nombre_cliente", "c.nombre"), ("nombre_funcion", "f.titulo")];
+
+const LIMITE_POR_DEFECTO: u32 = 10;
+const LIMITE_MAXIMO: u32 = 50;
+
+/// Tiempo durante el que se reutiliza una respuesta ya calculada.
+const DURACION_CACHE: Duration = Duration::from_secs(30);
+
+/// Entradas máximas en la caché antes de descartarla por completo.
+const CAPACIDAD_CACHE: usize = 1_000;
+
+/// Parámetros de consulta de `/autocomplete`.
+#[derive(Debug, Deserialize)]
+pub struct ParametrosAutocompletado {
+    pub campo: String,
+    pub q: String,
+    pub limite: Option<u32>,
+}
+
+/// Valor sugerido y cuántas entradas lo usan.
+#[derive(Debug, Clone, Serialize)]
+pub struct Sugerencia {
+    pub valor: String,
+    pub total: u64,
+}
+
+/// Cine, campo, prefijo y límite.
+type ClaveCache = (u32, String, String, u32);
+type SugerenciasGuardadas = (Instant, Arc<Vec<Sugerencia>>);
+
+/// Caché breve de sugerencias compartida por los workers.
+#[derive(Default)]
+pub struct CacheAutocompletado {
+    sugerencias: Mutex<HashMap<ClaveCache, SugerenciasGuardadas>>,
+}
+
+impl CacheAutocompletado {
+    fn obtener(&self, clave: &ClaveCache) -> Option<Arc<Vec<Sugerencia>>> {
+        let sugerencias = self.sugerencias.lock().unwrap();
+        sugerencias
+            .get(clave)
+            .filter(|(guardado, _)| guardado.elapsed() < DURACION_CACHE)
+            .map(|(_, valor)| valor.clone())
+    }
+
+    fn guardar(&self, clave: ClaveCache, valor: Arc<Vec<Sugerencia>>) {
+        let mut sugerencias = self.sugerencias.lock().unwrap();
+        if sugerencias.len() >= CAPACIDAD_CACHE {
+            sugerencias.retain(|_, (guardado, _)| guardado.elapsed() < DURACION_CACHE);
+            if sugerencias.len() >= CAPACIDAD_CACHE {
+                sugerencias.clear();
+            }
+        }
+        sugerencias.insert(clave, (Instant::now(), valor));
+    }
+}
+
+/// Handler que devuelve los valores distintos de `campo` que empiezan por `q`, con su número de entradas.
+pub async fn autocompletar(
+    CineActual(cine): CineActual,
+    pool: web::Data<Pool>,
+    cache: web::Data<CacheAutocompletado>,
+    query: web::Query<ParametrosAutocompletado>,
+) -> Result<ApiResponse<Arc<Vec<Sugerencia>>>, ApiError> {
+    let (campo, columna) = CAMPOS_AUTOCOMPLETABLES
+        .iter()
+        .find(|(c, _)| *c == query.campo)
+        .copied()
+        .ok_or_else(|| {
+            let disponibles: Vec<&str> = CAMPOS_AUTOCOMPLETABLES.iter().map(|(c, _)| *c).collect();
+            ApiError::Validacion(format!(
+                "No se ofrecen sugerencias para el campo '{}'. Campos disponibles: {}",
+                query.campo,
+                disponibles.join(", ")
+            ))
+        })?;
+    let prefijo = query.q.trim();
+    if prefijo.is_empty() {
+        return Err(ApiError::Validacion("El parámetro 'q' no puede estar vacío".to_string()));
+    }
+    let limite = query.limite.unwrap_or(LIMITE_POR_DEFECTO).clamp(1, LIMITE_MAXIMO);
+
+    let clave = (cine, campo.to_string(), prefijo.to_lowercase(), limite);
+    if let Some(sugerencias) = cache.obtener(&clave) {
+        return Ok(ApiResponse::ok(sugerencias));
+    }
+
+    let mut conn = db::conectar(&pool).await.map_err(ApiError::conexion)?;
+
+    // LIKE 'prefijo%' sin comodín inicial permite a MySQL recorrer el índice del campo.
+    let consulta = format!(
+        "SELECT {columna}, COUNT(*) AS total FROM {tablas} WHERE e.cine_id = :cine_id AND {columna} LIKE :prefijo \
+         GROUP BY {columna} ORDER BY total DESC, {columna} LIMIT :limite",
+        columna = columna,
+        tablas = db::TABLAS_ENTRADAS
+    );
+    let sugerencias = conn
+        .exec_map(
+            consulta,
+            params! { "cine_id" => cine, "prefijo" => format!("{}%", escapar_like(prefijo)), "limite" => limite },
+            |(valor, total)| Sugerencia { valor, total },
+        )
+        .await
+        .map_err(ApiError::base_datos("Error al obtener sugerencias"))?;
+
+    let sugerencias = Arc::new(sugerencias);
+    cache.guardar(clave, sugerencias.clone());
+    Ok(ApiResponse::ok(sugerencias))
+}
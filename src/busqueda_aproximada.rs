@@ -0,0 +1,177 @@
+//! Búsqueda de clientes tolerante a errores de escritura.
+//!
+//! Los nombres se cargan en un índice en memoria que se reconstruye cada
+//! [`VIGENCIA_INDICE`], y cada candidato se puntúa con Jaro-Winkler tanto sobre el
+//! nombre completo como palabra a palabra, para tolerar letras cambiadas y el orden
+//! de nombres y apellidos. El índice abarca todos los cines y cada búsqueda se limita a
+//! los clientes del suyo.
+
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use actix_web::web;
+use mysql_async::{prelude::*, Pool};
+use serde::{Deserialize, Serialize};
+use strsim::jaro_winkler;
+
+use crate::cines::CineActual;
+use crate::db;
+use crate::error::ApiError;
+use crate::respuesta::ApiResponse;
+
+/// Tiempo tras el cual el índice se vuelve a cargar desde la base de datos.
+const VIGENCIA_INDICE: Duration = Duration::from_secs(60);
+
+const LIMITE_POR_DEFECTO: usize = 10;
+const LIMITE_MAXIMO: usize = 50;
+const UMBRAL_POR_DEFECTO: f64 = 0.8;
+
+/// Parámetros de `/entradas/buscar-aproximado`.
+#[derive(Debug, Deserialize)]
+pub struct ParametrosBusquedaAproximada {
+    pub nombre: String,
+    pub limite: Option<usize>,
+    /// Puntuación mínima entre 0 y 1 para devolver un candidato.
+    pub umbral: Option<f64>,
+}
+
+/// Entrada candidata con la similitud de su cliente respecto al nombre buscado.
+#[derive(Debug, Clone, Serialize)]
+pub struct Candidato {
+    pub id: u32,
+    pub numero_cedula: String,
+    pub nombre_cliente: String,
+    pub puntuacion: f64,
+}
+
+struct ClienteIndexado {
+    id: u32,
+    cine: u32,
+    numero_cedula: String,
+    nombre_cliente: String,
+    normalizado: String,
+}
+
+struct Indice {
+    cargado: Instant,
+    clientes: Vec<ClienteIndexado>,
+}
+
+/// Índice en memoria de los nombres de clientes, compartido por los workers.
+#[derive(Default)]
+pub struct IndiceClientes {
+    indice: RwLock<Option<Arc<Indice>>>,
+}
+
+impl IndiceClientes {
+    /// Devuelve el índice vigente, recargándolo si caducó.
+    async fn vigente(&self, pool: &Pool) -> Result<Arc<Indice>, mysql_async::Error> {
+        if let Some(indice) = self.indice.read().unwrap().as_ref()
+            && indice.cargado.elapsed() < VIGENCIA_INDICE
+        {
+            return Ok(indice.clone());
+        }
+
+        let mut conn = db::conectar(pool).await?;
+        let clientes = conn
+            .query_map(
+                "SELECT e.id, e.cine_id, c.numero_cedula, c.nombre FROM entradas e JOIN clientes c ON c.id = e.cliente_id",
+                |(id, cine, numero_cedula, nombre_cliente): (u32, u32, String, String)| ClienteIndexado {
+                    id,
+                    cine,
+                    numero_cedula,
+                    normalizado: normalizar(&nombre_cliente),
+                    nombre_cliente,
+                },
+            )
+            .await?;
+        let indice = Arc::new(Indice {
+            cargado: Instant::now(),
+            clientes,
+        });
+        *self.indice.write().unwrap() = Some(indice.clone());
+        Ok(indice)
+    }
+}
+
+/// Pasa a minúsculas, quita tildes y deja las palabras separadas por un único espacio.
+fn normalizar(texto: &str) -> String {
+    texto
+        .to_lowercase()
+        .chars()
+        .map(|c| match c {
+            'á' | 'à' | 'ä' | 'â' => 'a',
+            'é' | 'è' | 'ë' | 'ê' => 'e',
+            'í' | 'ì' | 'ï' | 'î' => 'i',
+            'ó' | 'ò' | 'ö' | 'ô' => 'o',
+            'ú' | 'ù' | 'ü' | 'û' => 'u',
+            'ñ' => 'n',
+            c if c.is_alphanumeric() => c,
+            _ => ' ',
+        })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Similitud entre dos nombres normalizados: la mejor entre la comparación del nombre
+/// completo y la media, por cada palabra buscada, de su mejor coincidencia en el candidato.
+fn similitud(buscado: &str, candidato: &str) -> f64 {
+    let completa = jaro_winkler(buscado, candidato);
+    let palabras_candidato: Vec<&str> = candidato.split(' ').collect();
+    let palabras_buscadas: Vec<&str> = buscado.split(' ').collect();
+    let por_palabras = palabras_buscadas
+        .iter()
+        .map(|p| {
+            palabras_candidato
+                .iter()
+                .map(|c| jaro_winkler(p, c))
+                .fold(0.0, f64::max)
+        })
+        .sum::<f64>()
+        / palabras_buscadas.len() as f64;
+    completa.max(por_palabras)
+}
+
+/// Handler que devuelve las entradas cuyo cliente se parece al nombre buscado, de más a menos similar.
+pub async fn buscar_cliente_aproximado(
+    CineActual(cine): CineActual,
+    pool: web::Data<Pool>,
+    indice: web::Data<IndiceClientes>,
+    query: web::Query<ParametrosBusquedaAproximada>,
+) -> Result<ApiResponse<Vec<Candidato>>, ApiError> {
+    let buscado = normalizar(&query.nombre);
+    if buscado.is_empty() {
+        return Err(ApiError::Validacion("El parámetro 'nombre' no puede estar vacío".to_string()));
+    }
+    let umbral = query.umbral.unwrap_or(UMBRAL_POR_DEFECTO);
+    if !(0.0..=1.0).contains(&umbral) {
+        return Err(ApiError::Validacion("El parámetro 'umbral' debe estar entre 0 y 1".to_string()));
+    }
+    let limite = query.limite.unwrap_or(LIMITE_POR_DEFECTO).clamp(1, LIMITE_MAXIMO);
+
+    let indice = indice
+        .vigente(&pool)
+        .await
+        .map_err(ApiError::base_datos("Error al buscar clientes"))?;
+
+    let mut candidatos: Vec<Candidato> = indice
+        .clientes
+        .iter()
+        .filter(|cliente| cliente.cine == cine)
+        .filter_map(|cliente| {
+            let puntuacion = similitud(&buscado, &cliente.normalizado);
+            (puntuacion >= umbral).then(|| Candidato {
+                id: cliente.id,
+                numero_cedula: cliente.numero_cedula.clone(),
+                nombre_cliente: cliente.nombre_cliente.clone(),
+                puntuacion,
+            })
+        })
+        .collect();
+    candidatos.sort_by(|a, b| b.puntuacion.total_cmp(&a.puntuacion));
+    candidatos.truncate(limite);
+
+    Ok(ApiResponse::ok(candidatos))
+}
@@ -0,0 +1,364 @@
+//! Caché en memoria de las lecturas más frecuentes: las entradas por ID y el listado de
+//! funciones. Cada valor vence a los [`Config::cache_ttl`](crate::config::Config) y se
+//! descarta antes si se modifica.
+//!
+//! Las entradas se cachean envolviendo el repositorio en [`RepositorioCache`], así que el
+//! servicio no cambia. Como una entrada lleva los datos de su cliente, su función y su
+//! sala, cambiar cualquiera de ellos vacía la caché de entradas entera.
+//!
+//! Con Redis configurado (ver [`crate::compartido`]) los valores se guardan allí en lugar
+//! de en el proceso, para que una réplica no sirva lo que otra ya modificó. Vaciar una caché
+//! cambia su generación, que forma parte de cada clave, así que no hay que recorrerlas. Si
+//! Redis falla, las lecturas van a la base de datos.
+
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::future::BoxFuture;
+use futures_util::FutureExt;
+use moka::sync::Cache;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::agregado::{ParametrosAgregado, ResultadoAgregado};
+use crate::compartido::Redis;
+use crate::db::repository::{
+    EntradaGuardada, EntradaRepository, EntradaSimulada, Escritura, FlujoEntradas, ResultadoRepositorio,
+};
+use crate::listado::ConsultaListado;
+use crate::models::{
+    ActualizarEntrada, CrearEntrada, EliminarEntradas, Entrada, EstadoEntrada, Funcion, GuardarEntrada,
+    RegistroAuditoria,
+};
+
+/// Entradas que se guardan como máximo; al llenarse se descartan las menos usadas.
+const CAPACIDAD_ENTRADAS: u64 = 10_000;
+
+/// Listados de funciones que se guardan como máximo, uno por título consultado.
+const CAPACIDAD_FUNCIONES: u64 = 100;
+
+/// Aciertos y fallos de una caché.
+#[derive(Debug, Clone, Copy)]
+pub struct EstadisticasCache {
+    pub aciertos: u64,
+    pub fallos: u64,
+}
+
+/// Una caché con sus contadores.
+pub struct Almacen<K, V> {
+    nombre: &'static str,
+    ttl: Duration,
+    valores: Cache<K, V>,
+    redis: Option<Arc<Redis>>,
+    aciertos: AtomicU64,
+    fallos: AtomicU64,
+}
+
+impl<K, V> Almacen<K, V>
+where
+    K: Eq + Hash + Serialize + Send + Sync + 'static,
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+{
+    /// Con `ttl` cero no guarda nada. Con `redis`, la memoria del proceso no se usa.
+    fn new(nombre: &'static str, capacidad: u64, ttl: Duration, redis: Option<Arc<Redis>>) -> Self {
+        let capacidad = if ttl.is_zero() || redis.is_some() { 0 } else { capacidad };
+        Almacen {
+            nombre,
+            ttl,
+            valores: Cache::builder().max_capacity(capacidad).time_to_live(ttl.max(Duration::from_millis(1))).build(),
+            redis: redis.filter(|_| !ttl.is_zero()),
+            aciertos: AtomicU64::new(0),
+            fallos: AtomicU64::new(0),
+        }
+    }
+
+    fn clave_generacion(&self) -> String {
+        format!("cache:{}:generacion", self.nombre)
+    }
+
+    /// Clave de Redis de `clave` en la generación actual.
+    async fn clave_redis(&self, redis: &Redis, clave: &K) -> Result<String, String> {
+        let generacion = redis.contador(&self.clave_generacion()).await?;
+        Ok(format!(
+            "cache:{}:{}:{}",
+            self.nombre,
+            generacion,
+            serde_json::to_string(clave).unwrap_or_default()
+        ))
+    }
+
+    pub async fn obtener(&self, clave: &K) -> Option<V> {
+        let valor = match &self.redis {
+            Some(redis) => {
+                let valor = match self.clave_redis(redis, clave).await {
+                    Ok(clave) => redis.leer(&clave).await,
+                    Err(e) => Err(e),
+                };
+                valor.unwrap_or_else(|e| {
+                    log::error!("Error al leer la caché de {} en Redis: {}", self.nombre, e);
+                    None
+                })
+            }
+            None => self.valores.get(clave),
+        };
+        let contador = if valor.is_some() { &self.aciertos } else { &self.fallos };
+        contador.fetch_add(1, Ordering::Relaxed);
+        valor
+    }
+
+    pub async fn guardar(&self, clave: K, valor: V) {
+        let Some(redis) = &self.redis else {
+            self.valores.insert(clave, valor);
+            return;
+        };
+        let resultado = match self.clave_redis(redis, &clave).await {
+            Ok(clave) => redis.escribir(&clave, &valor, self.ttl).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = resultado {
+            log::error!("Error al guardar en la caché de {} en Redis: {}", self.nombre, e);
+        }
+    }
+
+    pub async fn invalidar(&self, clave: &K) {
+        let Some(redis) = &self.redis else {
+            self.valores.invalidate(clave);
+            return;
+        };
+        let resultado = match self.clave_redis(redis, clave).await {
+            Ok(clave) => redis.borrar(&clave).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = resultado {
+            log::error!("Error al invalidar la caché de {} en Redis: {}", self.nombre, e);
+        }
+    }
+
+    pub async fn vaciar(&self) {
+        let Some(redis) = &self.redis else {
+            self.valores.invalidate_all();
+            return;
+        };
+        if let Err(e) = redis.incrementar(&self.clave_generacion()).await {
+            log::error!("Error al vaciar la caché de {} en Redis: {}", self.nombre, e);
+        }
+    }
+
+    pub fn estadisticas(&self) -> EstadisticasCache {
+        EstadisticasCache {
+            aciertos: self.aciertos.load(Ordering::Relaxed),
+            fallos: self.fallos.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Cachés de lecturas, compartidas por los workers. Los valores se guardan como los lee la
+/// base de datos, con los horarios en UTC, y por cine.
+pub struct CacheLecturas {
+    /// Entradas por cine e ID.
+    pub entradas: Almacen<(u32, u32), Entrada>,
+    /// Listado de `GET /funciones` por cine y título filtrado, o `None` sin filtro.
+    pub funciones: Almacen<(u32, Option<String>), Arc<Vec<Funcion>>>,
+}
+
+impl CacheLecturas {
+    /// Cachés en la memoria del proceso, o en `redis` si se indica.
+    pub fn new(ttl: Duration, redis: Option<Arc<Redis>>) -> Self {
+        CacheLecturas {
+            entradas: Almacen::new("entradas", CAPACIDAD_ENTRADAS, ttl, redis.clone()),
+            funciones: Almacen::new("funciones", CAPACIDAD_FUNCIONES, ttl, redis),
+        }
+    }
+
+    /// Descarta lo que depende de funciones, salas o clientes, tras modificar alguno.
+    pub async fn invalidar_catalogo(&self) {
+        self.funciones.vaciar().await;
+        self.entradas.vaciar().await;
+    }
+}
+
+/// Repositorio que guarda en [`CacheLecturas`] las entradas leídas por ID y las descarta
+/// cuando `interno` las modifica. El resto de las operaciones pasan sin más.
+pub struct RepositorioCache {
+    interno: Arc<dyn EntradaRepository>,
+    cache: Arc<CacheLecturas>,
+}
+
+impl RepositorioCache {
+    pub fn new(interno: Arc<dyn EntradaRepository>, cache: Arc<CacheLecturas>) -> Self {
+        RepositorioCache { interno, cache }
+    }
+}
+
+impl EntradaRepository for RepositorioCache {
+    fn listar<'a>(&'a self, cine: u32, consulta: &'a ConsultaListado) -> BoxFuture<'a, ResultadoRepositorio<FlujoEntradas>> {
+        self.interno.listar(cine, consulta)
+    }
+
+    fn contar<'a>(&'a self, cine: u32, consulta: &'a ConsultaListado) -> BoxFuture<'a, ResultadoRepositorio<u64>> {
+        self.interno.contar(cine, consulta)
+    }
+
+    fn obtener(&self, cine: u32, id: u32) -> BoxFuture<'_, ResultadoRepositorio<Option<Entrada>>> {
+        async move {
+            if let Some(entrada) = self.cache.entradas.obtener(&(cine, id)).await {
+                return Ok(Some(entrada));
+            }
+            // Las que no existen no se guardan: así crear una no tiene que invalidar nada.
+            let entrada = self.interno.obtener(cine, id).await?;
+            if let Some(entrada) = &entrada {
+                self.cache.entradas.guardar((cine, id), entrada.clone()).await;
+            }
+            Ok(entrada)
+        }
+        .boxed()
+    }
+
+    fn crear<'a>(
+        &'a self,
+        cine: u32,
+        entrada: &'a CrearEntrada,
+        actor: &'a str,
+    ) -> BoxFuture<'a, ResultadoRepositorio<u32>> {
+        self.interno.crear(cine, entrada, actor)
+    }
+
+    fn crear_lote<'a>(
+        &'a self,
+        cine: u32,
+        entradas: &'a [CrearEntrada],
+        actor: &'a str,
+    ) -> BoxFuture<'a, ResultadoRepositorio<Vec<ResultadoRepositorio<u32>>>> {
+        self.interno.crear_lote(cine, entradas, actor)
+    }
+
+    fn guardar_por_cedula<'a>(
+        &'a self,
+        cine: u32,
+        numero_cedula: &'a str,
+        datos: &'a GuardarEntrada,
+        actor: &'a str,
+    ) -> BoxFuture<'a, ResultadoRepositorio<EntradaGuardada>> {
+        async move {
+            let guardada = self.interno.guardar_por_cedula(cine, numero_cedula, datos, actor).await?;
+            if let EntradaGuardada::Actualizada(id) = guardada {
+                self.cache.entradas.invalidar(&(cine, id)).await;
+            }
+            Ok(guardada)
+        }
+        .boxed()
+    }
+
+    fn actualizar<'a>(
+        &'a self,
+        cine: u32,
+        id: u32,
+        datos: &'a ActualizarEntrada,
+        version: Option<u32>,
+        actor: &'a str,
+    ) -> BoxFuture<'a, ResultadoRepositorio<bool>> {
+        async move {
+            let resultado = self.interno.actualizar(cine, id, datos, version, actor).await;
+            self.cache.entradas.invalidar(&(cine, id)).await;
+            resultado
+        }
+        .boxed()
+    }
+
+    fn simular<'a>(
+        &'a self,
+        cine: u32,
+        escritura: Escritura<'a>,
+        actor: &'a str,
+    ) -> BoxFuture<'a, ResultadoRepositorio<Vec<ResultadoRepositorio<EntradaSimulada>>>> {
+        self.interno.simular(cine, escritura, actor)
+    }
+
+    fn eliminar<'a>(
+        &'a self,
+        cine: u32,
+        id: u32,
+        version: Option<u32>,
+        actor: &'a str,
+    ) -> BoxFuture<'a, ResultadoRepositorio<bool>> {
+        async move {
+            let resultado = self.interno.eliminar(cine, id, version, actor).await;
+            self.cache.entradas.invalidar(&(cine, id)).await;
+            resultado
+        }
+        .boxed()
+    }
+
+    fn cambiar_estado<'a>(
+        &'a self,
+        cine: u32,
+        id: u32,
+        estado: EstadoEntrada,
+        actor: &'a str,
+    ) -> BoxFuture<'a, ResultadoRepositorio<bool>> {
+        async move {
+            let resultado = self.interno.cambiar_estado(cine, id, estado, actor).await;
+            self.cache.entradas.invalidar(&(cine, id)).await;
+            resultado
+        }
+        .boxed()
+    }
+
+    fn cancelar_vencidas<'a>(&'a self, antiguedad: Duration, actor: &'a str) -> BoxFuture<'a, ResultadoRepositorio<u64>> {
+        async move {
+            let canceladas = self.interno.cancelar_vencidas(antiguedad, actor).await?;
+            if canceladas > 0 {
+                self.cache.entradas.vaciar().await;
+            }
+            Ok(canceladas)
+        }
+        .boxed()
+    }
+
+    fn cancelar_funcion<'a>(
+        &'a self,
+        cine: u32,
+        funcion_id: u32,
+        motivo: &'a str,
+        actor: &'a str,
+    ) -> BoxFuture<'a, ResultadoRepositorio<Vec<u32>>> {
+        async move {
+            let canceladas = self.interno.cancelar_funcion(cine, funcion_id, motivo, actor).await?;
+            for id in &canceladas {
+                self.cache.entradas.invalidar(&(cine, *id)).await;
+            }
+            Ok(canceladas)
+        }
+        .boxed()
+    }
+
+    fn eliminar_filtradas<'a>(
+        &'a self,
+        cine: u32,
+        filtro: &'a EliminarEntradas,
+        actor: &'a str,
+    ) -> BoxFuture<'a, ResultadoRepositorio<u64>> {
+        async move {
+            let eliminadas = self.interno.eliminar_filtradas(cine, filtro, actor).await?;
+            if eliminadas > 0 {
+                self.cache.entradas.vaciar().await;
+            }
+            Ok(eliminadas)
+        }
+        .boxed()
+    }
+
+    fn historial(&self, cine: u32, id: u32) -> BoxFuture<'_, ResultadoRepositorio<Vec<RegistroAuditoria>>> {
+        self.interno.historial(cine, id)
+    }
+
+    fn agregar<'a>(
+        &'a self,
+        cine: u32,
+        parametros: &'a ParametrosAgregado,
+    ) -> BoxFuture<'a, ResultadoRepositorio<ResultadoAgregado>> {
+        self.interno.agregar(cine, parametros)
+    }
+}
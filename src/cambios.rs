@@ -0,0 +1,157 @@
+//! Cambios de las entradas en vivo, por Server-Sent Events en `GET /entradas/stream`, para
+//! que las pantallas del vestíbulo no tengan que consultar el listado cada pocos segundos.
+//!
+//! Los handlers que modifican entradas publican cada cambio en un canal [`broadcast`] del
+//! proceso, y cada conexión abierta lo recibe como un evento con el nombre del [`Evento`] y
+//! `{"entrada_id": ...}` en `data`. Cada conexión recibe sólo los cambios de su cine (ver
+//! [`CineActual`]). Sólo llegan los cambios que atiende esta réplica; los sistemas que
+//! necesitan todos tienen los webhooks (ver [`crate::webhooks`]).
+//!
+//! Cuando no se puede decir qué entradas cambiaron, tras una eliminación en bloque, el
+//! vencimiento de reservas o una conexión que se quedó atrás, se envía [`EVENTO_RECARGAR`]
+//! y el cliente debe volver a leer el listado.
+
+use std::convert::Infallible;
+use std::future::ready;
+use std::time::{Duration, Instant};
+
+use actix_web::http::header::CACHE_CONTROL;
+use actix_web::rt::time::timeout;
+use actix_web::web::{self, Bytes};
+use actix_web::HttpResponse;
+use futures_util::stream::{self, Stream, StreamExt};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::cines::CineActual;
+use crate::db::eventos::Evento;
+
+/// Evento que pide al cliente volver a leer el listado.
+pub const EVENTO_RECARGAR: &str = "entradas.recargar";
+
+/// Cambios que puede tener pendientes una conexión antes de quedarse atrás.
+const CAPACIDAD: usize = 256;
+
+/// Silencio tras el que se envía un comentario, para que los proxies no cierren la conexión.
+const LATIDO: Duration = Duration::from_secs(15);
+
+/// Cambio que se avisa a las conexiones abiertas.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cambio {
+    /// Cambio de la entrada `id`, del cine `cine`.
+    Entrada { cine: u32, evento: Evento, id: u32 },
+    /// Se avisa a las conexiones de todos los cines.
+    Recargar,
+}
+
+impl Cambio {
+    /// Indica si el cambio se avisa a las conexiones del cine `cine`.
+    fn es_para(&self, cine: u32) -> bool {
+        match self {
+            Cambio::Entrada { cine: de, .. } => *de == cine,
+            Cambio::Recargar => true,
+        }
+    }
+
+    /// El cambio como evento SSE.
+    fn como_sse(self) -> Bytes {
+        let (evento, datos) = match self {
+            Cambio::Entrada { evento, id, .. } => (evento.como_str(), format!("{{\"entrada_id\":{}}}", id)),
+            Cambio::Recargar => (EVENTO_RECARGAR, "{}".to_string()),
+        };
+        Bytes::from(format!("event: {}\ndata: {}\n\n", evento, datos))
+    }
+}
+
+/// Canal por el que los handlers avisan los cambios a `GET /entradas/stream`.
+pub struct CanalCambios(broadcast::Sender<Cambio>);
+
+impl Default for CanalCambios {
+    fn default() -> Self {
+        CanalCambios(broadcast::channel(CAPACIDAD).0)
+    }
+}
+
+impl CanalCambios {
+    /// Avisa `cambio` a las conexiones abiertas, si hay alguna.
+    pub fn publicar(&self, cambio: Cambio) {
+        let _ = self.0.send(cambio);
+    }
+
+    /// Avisa `evento` de la entrada `id` del cine `cine`.
+    pub fn publicar_entrada(&self, cine: u32, evento: Evento, id: u32) {
+        self.publicar(Cambio::Entrada { cine, evento, id });
+    }
+
+    /// Avisa `evento` de cada una de las entradas `ids` del cine `cine`.
+    pub fn publicar_entradas(&self, cine: u32, evento: Evento, ids: impl IntoIterator<Item = u32>) {
+        for id in ids {
+            self.publicar_entrada(cine, evento, id);
+        }
+    }
+
+    pub fn suscribir(&self) -> broadcast::Receiver<Cambio> {
+        self.0.subscribe()
+    }
+}
+
+/// Fragmentos SSE de los cambios del cine `cine` que llegan por `receptor`, con un latido
+/// tras cada [`LATIDO`] sin ninguno.
+fn eventos_del_cine(receptor: broadcast::Receiver<Cambio>, cine: u32) -> impl Stream<Item = Bytes> {
+    stream::unfold(receptor, move |mut receptor| async move {
+        // Los cambios de otros cines no cuentan como actividad: el latido sale igual.
+        let latido = Instant::now() + LATIDO;
+        let fragmento = loop {
+            match timeout(latido.saturating_duration_since(Instant::now()), receptor.recv()).await {
+                Err(_) => break Bytes::from_static(b": latido\n\n"),
+                Ok(Ok(cambio)) if cambio.es_para(cine) => break cambio.como_sse(),
+                Ok(Ok(_)) => {}
+                Ok(Err(RecvError::Lagged(_))) => break Cambio::Recargar.como_sse(),
+                Ok(Err(RecvError::Closed)) => return None,
+            }
+        };
+        Some((fragmento, receptor))
+    })
+}
+
+/// Handler de `GET /entradas/stream`: deja la respuesta abierta y envía cada cambio del
+/// cine a medida que ocurre.
+pub async fn transmitir_cambios(CineActual(cine): CineActual, cambios: web::Data<CanalCambios>) -> HttpResponse {
+    let eventos = eventos_del_cine(cambios.suscribir(), cine).map(Ok::<_, Infallible>);
+    // El primer fragmento envía las cabeceras en seguida y fija la espera para reconectar.
+    let inicio = stream::once(ready(Ok(Bytes::from_static(b"retry: 3000\n\n"))));
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header((CACHE_CONTROL, "no-cache"))
+        .insert_header(("X-Accel-Buffering", "no"))
+        .streaming(inicio.chain(eventos))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cada_cambio_es_un_evento_con_su_nombre() {
+        let canal = CanalCambios::default();
+        canal.publicar(Cambio::Recargar);
+        let mut receptor = canal.suscribir();
+        canal.publicar_entradas(1, Evento::Creada, [4, 5]);
+        assert_eq!(receptor.try_recv().unwrap(), Cambio::Entrada { cine: 1, evento: Evento::Creada, id: 4 });
+        assert_eq!(
+            receptor.try_recv().unwrap().como_sse(),
+            "event: entrada.creada\ndata: {\"entrada_id\":5}\n\n"
+        );
+        assert_eq!(Cambio::Recargar.como_sse(), "event: entradas.recargar\ndata: {}\n\n");
+    }
+
+    #[actix_web::test]
+    async fn cada_conexion_recibe_solo_los_cambios_de_su_cine() {
+        let canal = CanalCambios::default();
+        let mut eventos = Box::pin(eventos_del_cine(canal.suscribir(), 2));
+        canal.publicar_entrada(1, Evento::Creada, 7);
+        canal.publicar_entrada(2, Evento::Eliminada, 8);
+        canal.publicar(Cambio::Recargar);
+        assert_eq!(eventos.next().await.unwrap(), "event: entrada.eliminada\ndata: {\"entrada_id\":8}\n\n");
+        assert_eq!(eventos.next().await.unwrap(), "event: entradas.recargar\ndata: {}\n\n");
+    }
+}
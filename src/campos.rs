@@ -0,0 +1,104 @@
+//! Selección de campos con `?fields=id,nombre_cliente` en los GET de entradas, para los
+//! clientes con poca conexión.
+//!
+//! En los listados, las columnas de [`db::COLUMNAS_ENTRADA`] que no hacen falta para los
+//! campos pedidos se sustituyen por un valor fijo, así que la fila se sigue leyendo como
+//! [`Entrada`] pero MySQL no envía esos datos. Al serializar, cada entrada se reduce a un
+//! objeto plano con sólo los campos pedidos, en el orden en que se pidieron.
+
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+use crate::db;
+use crate::models::Entrada;
+
+/// Un campo que se puede pedir: su nombre, las posiciones en [`db::COLUMNAS_ENTRADA`] de
+/// las columnas de las que sale y su valor en la entrada ya leída.
+struct Campo {
+    nombre: &'static str,
+    columnas: &'static [usize],
+    valor: fn(&Entrada) -> Value,
+}
+
+/// Campos seleccionables. Los del cliente y la función usan los nombres de los filtros
+/// del listado.
+const CAMPOS: &[Campo] = &[
+    Campo { nombre: "id", columnas: &[0], valor: |e| json!(e.id) },
+    Campo { nombre: "estado", columnas: &[16], valor: |e| json!(e.estado) },
+    Campo { nombre: "cantidad_entradas", columnas: &[1], valor: |e| json!(e.cantidad_entradas) },
+    Campo { nombre: "total", columnas: &[15], valor: |e| json!(e.total) },
+    Campo { nombre: "cliente_id", columnas: &[2], valor: |e| json!(e.cliente.id) },
+    Campo { nombre: "numero_cedula", columnas: &[3], valor: |e| json!(e.cliente.numero_cedula) },
+    Campo { nombre: "nombre_cliente", columnas: &[4], valor: |e| json!(e.cliente.nombre) },
+    Campo { nombre: "funcion_id", columnas: &[5], valor: |e| json!(e.funcion.id) },
+    Campo { nombre: "nombre_funcion", columnas: &[6], valor: |e| json!(e.funcion.titulo) },
+    Campo { nombre: "horario_funcion", columnas: &[7], valor: |e| json!(e.funcion.horario) },
+    Campo { nombre: "precio", columnas: &[8], valor: |e| json!(e.funcion.precio) },
+    Campo { nombre: "sala", columnas: &[10], valor: |e| json!(e.funcion.sala.nombre) },
+    Campo { nombre: "version", columnas: &[12], valor: |e| json!(e.version) },
+    Campo { nombre: "created_at", columnas: &[13], valor: |e| json!(e.created_at) },
+    Campo { nombre: "updated_at", columnas: &[14], valor: |e| json!(e.updated_at) },
+];
+
+/// Parámetro `fields` de `GET /entradas/{id}`.
+#[derive(Debug, Default, Deserialize)]
+pub struct ParametrosCampos {
+    pub fields: Option<String>,
+}
+
+fn campo(nombre: &str) -> &'static Campo {
+    CAMPOS.iter().find(|c| c.nombre == nombre).expect("Campo validado con `leer`")
+}
+
+/// Valida la lista de campos separados por comas y quita los repetidos.
+pub fn leer(fields: &str) -> Result<Vec<&'static str>, String> {
+    let mut campos = Vec::new();
+    for nombre in fields.split(',').map(str::trim).filter(|nombre| !nombre.is_empty()) {
+        let campo = CAMPOS.iter().find(|c| c.nombre == nombre).ok_or_else(|| {
+            let disponibles: Vec<&str> = CAMPOS.iter().map(|c| c.nombre).collect();
+            format!("No existe el campo '{}'. Campos disponibles: {}", nombre, disponibles.join(", "))
+        })?;
+        if !campos.contains(&campo.nombre) {
+            campos.push(campo.nombre);
+        }
+    }
+    if campos.is_empty() {
+        return Err("El parámetro 'fields' no puede estar vacío".to_string());
+    }
+    Ok(campos)
+}
+
+/// Columnas del SELECT de entradas para `campos`: las suyas y el relleno en las demás.
+pub fn columnas(campos: &[&str]) -> String {
+    let usadas: Vec<usize> = campos.iter().flat_map(|nombre| campo(nombre).columnas).copied().collect();
+    let columnas: Vec<&str> = db::COLUMNAS_ENTRADA
+        .iter()
+        .enumerate()
+        .map(|(indice, (columna, relleno))| if usadas.contains(&indice) { *columna } else { *relleno })
+        .collect();
+    columnas.join(", ")
+}
+
+/// La entrada como objeto plano con sólo `campos`.
+pub fn proyectar(entrada: &Entrada, campos: &[&str]) -> Map<String, Value> {
+    campos.iter().map(|nombre| (nombre.to_string(), (campo(nombre).valor)(entrada))).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn valida_los_campos_y_quita_repetidos() {
+        assert_eq!(leer("id, nombre_cliente,id").unwrap(), ["id", "nombre_cliente"]);
+        assert!(leer("id,contrasena").is_err());
+        assert!(leer(" , ").is_err());
+    }
+
+    #[test]
+    fn rellena_las_columnas_no_pedidas() {
+        let columnas = columnas(&["nombre_cliente", "id"]);
+        assert!(columnas.starts_with("e.id, 0, 0, '', c.nombre, 0, '', CAST('1970-01-01' AS DATETIME),"));
+        assert!(columnas.ends_with(", 0.00, 'reservada'"));
+    }
+}
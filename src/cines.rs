@@ -0,0 +1,230 @@
+//! Cines de la cadena (`/admin/cines`). Cada sala, cliente, función y entrada es de un
+//! cine, y la API sólo muestra y modifica los del cine de la petición ([`CineActual`]).
+//!
+//! El cine sale del token o de la clave de API si el usuario está atado a uno; si no, de
+//! la cabecera `X-Cine-Id`, y sin ella es el [`CINE_PRINCIPAL`], al que pasaron los datos
+//! anteriores a la cadena. Una sesión atada a un cine no puede pedir otro.
+//!
+//! Quedan fuera del cine los usuarios, los webhooks, los dispositivos, las tareas y las
+//! métricas, que son de toda la instalación. Sólo un administrador sin cine propio gestiona
+//! los cines.
+
+use std::future::{ready, Ready};
+
+use actix_web::dev::Payload;
+use actix_web::{web, FromRequest, HttpMessage, HttpRequest, HttpResponse};
+use mysql_async::{prelude::*, Pool};
+use serde::{Deserialize, Serialize};
+
+use crate::auth::{Administrador, Sesion};
+use crate::db;
+use crate::db::errores::{self, FalloMysql};
+use crate::error::ApiError;
+use crate::json::Json;
+use crate::respuesta::ApiResponse;
+
+/// Cine de las peticiones que no indican ninguno.
+pub const CINE_PRINCIPAL: u32 = 1;
+
+/// Cabecera con la que se elige el cine.
+pub const CABECERA_CINE: &str = "X-Cine-Id";
+
+/// Longitud máxima del nombre, la de la columna.
+const NOMBRE_MAXIMO: usize = 100;
+
+/// Cine registrado.
+#[derive(Debug, Clone, Serialize)]
+pub struct Cine {
+    pub id: u32,
+    pub nombre: String,
+    pub creado_en: String,
+}
+
+const SELECT_CINES: &str = "SELECT id, nombre, DATE_FORMAT(creado_en, '%Y-%m-%d %H:%i:%s') FROM cines";
+
+impl Cine {
+    fn desde_fila((id, nombre, creado_en): (u32, String, String)) -> Cine {
+        Cine { id, nombre, creado_en }
+    }
+}
+
+/// Datos para crear o renombrar un cine.
+#[derive(Debug, Deserialize)]
+pub struct DatosCine {
+    pub nombre: String,
+}
+
+/// Cine de la petición. Como parámetro de un handler, responde 400 si `X-Cine-Id` no es un
+/// ID y 403 si la sesión está atada a otro cine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CineActual(pub u32);
+
+/// Cine de una petición con la `sesion` y el valor de `X-Cine-Id` indicados.
+pub fn resolver(sesion: Option<&Sesion>, cabecera: Option<&str>) -> Result<u32, ApiError> {
+    let pedido = cabecera
+        .map(|valor| {
+            valor.trim().parse::<u32>().ok().filter(|&id| id > 0).ok_or_else(|| {
+                ApiError::Validacion(format!("La cabecera {} debe ser el ID de un cine", CABECERA_CINE))
+            })
+        })
+        .transpose()?;
+    match (sesion.and_then(|sesion| sesion.cine), pedido) {
+        (Some(propio), Some(pedido)) if propio != pedido => {
+            Err(ApiError::Prohibido("La sesión no tiene acceso a ese cine".to_string()))
+        }
+        (Some(propio), _) => Ok(propio),
+        (None, pedido) => Ok(pedido.unwrap_or(CINE_PRINCIPAL)),
+    }
+}
+
+impl FromRequest for CineActual {
+    type Error = ApiError;
+    type Future = Ready<Result<CineActual, ApiError>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let cabecera = req.headers().get(CABECERA_CINE).map(|valor| valor.to_str().unwrap_or_default());
+        ready(resolver(req.extensions().get::<Sesion>(), cabecera).map(CineActual))
+    }
+}
+
+/// Exige un administrador de toda la cadena.
+fn de_la_cadena(admin: &Administrador) -> Result<(), ApiError> {
+    match admin.0.cine {
+        None => Ok(()),
+        Some(_) => Err(ApiError::Prohibido(
+            "Sólo un administrador de toda la cadena puede gestionar los cines".to_string(),
+        )),
+    }
+}
+
+fn no_encontrado() -> ApiError {
+    ApiError::NoEncontrado("Cine no encontrado".to_string())
+}
+
+/// Nombre sin espacios alrededor, si es válido.
+fn validar(datos: DatosCine) -> Result<String, ApiError> {
+    let nombre = datos.nombre.trim().to_string();
+    if nombre.is_empty() || nombre.chars().count() > NOMBRE_MAXIMO {
+        return Err(ApiError::Validacion(format!(
+            "El nombre del cine debe tener entre 1 y {} caracteres",
+            NOMBRE_MAXIMO
+        )));
+    }
+    Ok(nombre)
+}
+
+/// Clasifica un error al escribir un cine, distinguiendo los nombres repetidos.
+fn error_escritura(mensaje: &'static str) -> impl FnOnce(mysql_async::Error) -> ApiError {
+    move |e| match errores::clasificar(&e) {
+        FalloMysql::Duplicado(restriccion) => ApiError::Duplicado {
+            mensaje: "Ya existe un cine con ese nombre".to_string(),
+            restriccion,
+        },
+        _ => ApiError::escritura(mensaje)(e),
+    }
+}
+
+async fn leer_cine(conn: &mut db::Conexion, id: u32) -> Result<Option<Cine>, mysql_async::Error> {
+    let fila = conn.exec_first(format!("{} WHERE id = :id", SELECT_CINES), params! { "id" => id }).await?;
+    Ok(fila.map(Cine::desde_fila))
+}
+
+/// Handler que lista los cines.
+pub async fn listar_cines(admin: Administrador, pool: web::Data<Pool>) -> Result<ApiResponse<Vec<Cine>>, ApiError> {
+    de_la_cadena(&admin)?;
+    let mut conn = db::conectar(&pool).await.map_err(ApiError::conexion)?;
+    let cines = conn
+        .query_map(format!("{} ORDER BY id", SELECT_CINES), Cine::desde_fila)
+        .await
+        .map_err(ApiError::base_datos("Error al obtener los cines"))?;
+    Ok(ApiResponse::ok(cines))
+}
+
+/// Handler que da de alta un cine, todavía sin salas ni clientes.
+pub async fn crear_cine(
+    admin: Administrador,
+    pool: web::Data<Pool>,
+    datos: Json<DatosCine>,
+) -> Result<ApiResponse<Cine>, ApiError> {
+    de_la_cadena(&admin)?;
+    let nombre = validar(datos.into_inner())?;
+    let mut conn = db::conectar(&pool).await.map_err(ApiError::conexion)?;
+    conn.exec_drop("INSERT INTO cines (nombre) VALUES (:nombre)", params! { "nombre" => nombre })
+        .await
+        .map_err(error_escritura("Error al crear el cine"))?;
+    let id = conn.last_insert_id().unwrap_or_default() as u32;
+    leer_cine(&mut conn, id)
+        .await
+        .map_err(ApiError::base_datos("Error al crear el cine"))?
+        .map(ApiResponse::creada)
+        .ok_or_else(no_encontrado)
+}
+
+/// Handler que cambia el nombre de un cine.
+pub async fn renombrar_cine(
+    admin: Administrador,
+    pool: web::Data<Pool>,
+    id: web::Path<u32>,
+    datos: Json<DatosCine>,
+) -> Result<ApiResponse<Cine>, ApiError> {
+    de_la_cadena(&admin)?;
+    let id = id.into_inner();
+    let nombre = validar(datos.into_inner())?;
+    let mut conn = db::conectar(&pool).await.map_err(ApiError::conexion)?;
+    conn.exec_drop("UPDATE cines SET nombre = :nombre WHERE id = :id", params! { "id" => id, "nombre" => nombre })
+        .await
+        .map_err(error_escritura("Error al renombrar el cine"))?;
+    leer_cine(&mut conn, id)
+        .await
+        .map_err(ApiError::base_datos("Error al renombrar el cine"))?
+        .map(ApiResponse::ok)
+        .ok_or_else(no_encontrado)
+}
+
+/// Handler que elimina un cine sin salas, clientes, entradas, usuarios ni claves de API.
+/// El [`CINE_PRINCIPAL`] no se puede eliminar.
+pub async fn eliminar_cine(
+    admin: Administrador,
+    pool: web::Data<Pool>,
+    id: web::Path<u32>,
+) -> Result<HttpResponse, ApiError> {
+    de_la_cadena(&admin)?;
+    let id = id.into_inner();
+    if id == CINE_PRINCIPAL {
+        return Err(ApiError::Conflicto("El cine principal no se puede eliminar".to_string()));
+    }
+    let mut conn = db::conectar(&pool).await.map_err(ApiError::conexion)?;
+    let resultado = conn.exec_drop("DELETE FROM cines WHERE id = :id", params! { "id" => id }).await;
+    match resultado {
+        Ok(()) if conn.affected_rows() == 0 => Err(no_encontrado()),
+        Ok(()) => Ok(HttpResponse::NoContent().finish()),
+        Err(e) => Err(match errores::clasificar(&e) {
+            FalloMysql::Referenciada(restriccion) => ApiError::EnUso {
+                mensaje: "El cine todavía tiene datos o usuarios".to_string(),
+                restriccion,
+            },
+            _ => ApiError::escritura("Error al eliminar el cine")(e),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Rol;
+
+    fn sesion(cine: Option<u32>) -> Sesion {
+        Sesion { sub: "pruebas".to_string(), rol: Rol::Taquillero, cine, iat: 0, exp: u64::MAX }
+    }
+
+    #[test]
+    fn el_cine_sale_de_la_sesion_o_de_la_cabecera() {
+        assert_eq!(resolver(None, None), Ok(CINE_PRINCIPAL));
+        assert_eq!(resolver(Some(&sesion(None)), Some(" 3")), Ok(3));
+        assert_eq!(resolver(Some(&sesion(Some(2))), None), Ok(2));
+        assert_eq!(resolver(Some(&sesion(Some(2))), Some("2")), Ok(2));
+        assert!(matches!(resolver(Some(&sesion(Some(2))), Some("3")), Err(ApiError::Prohibido(_))));
+        assert!(matches!(resolver(None, Some("0")), Err(ApiError::Validacion(_))));
+        assert!(matches!(resolver(None, Some("norte")), Err(ApiError::Validacion(_))));
+    }
+}
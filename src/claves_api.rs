@@ -0,0 +1,161 @@
+//! Claves de API para integraciones que no pueden iniciar sesión, como los kioscos.
+//!
+//! Un administrador crea las claves en `/admin/api-keys`; la clave completa sólo se
+//! muestra al crearla y en la base de datos se guarda su SHA-256. Las rutas protegidas
+//! por [`crate::auth::exigir_autenticacion`] aceptan la clave en la cabecera `X-Api-Key`
+//! con el rol y el cine con que se creó. Un administrador atado a un cine sólo ve y crea
+//! claves de su cine.
+
+use actix_web::{web, HttpResponse};
+use mysql_async::{prelude::*, Pool};
+use serde::{Deserialize, Serialize};
+
+use crate::auth::{ahora, cine_propio, generar_secreto, hash_secreto, Administrador, Sesion};
+use crate::db;
+use crate::error::ApiError;
+use crate::json::Json;
+use crate::models::Rol;
+use crate::respuesta::ApiResponse;
+
+/// Cabecera con la que se envía la clave.
+pub const CABECERA_CLAVE_API: &str = "X-Api-Key";
+
+/// Caracteres iniciales de la clave que se guardan para poder identificarla.
+const LONGITUD_PREFIJO: usize = 8;
+
+/// Datos para crear una clave. Sin `rol`, la clave actúa como taquillero; sin `cine_id`,
+/// en el cine de quien la crea (ver [`crate::auth::RegistrarUsuario`]).
+#[derive(Debug, Deserialize)]
+pub struct CrearClave {
+    pub nombre: String,
+    pub rol: Option<Rol>,
+    pub cine_id: Option<u32>,
+}
+
+/// Clave registrada, sin el secreto.
+#[derive(Debug, Clone, Serialize)]
+pub struct ClaveApi {
+    pub id: u32,
+    pub nombre: String,
+    pub prefijo: String,
+    pub rol: Rol,
+    pub cine_id: Option<u32>,
+    pub creado_en: String,
+    pub revocado_en: Option<String>,
+}
+
+type FilaClave = (u32, String, String, String, Option<u32>, String, Option<String>);
+
+impl ClaveApi {
+    fn desde_fila((id, nombre, prefijo, rol, cine_id, creado_en, revocado_en): FilaClave) -> ClaveApi {
+        ClaveApi {
+            id,
+            nombre,
+            prefijo,
+            rol: Rol::desde_str(&rol).unwrap_or(Rol::Taquillero),
+            cine_id,
+            creado_en,
+            revocado_en,
+        }
+    }
+}
+
+/// Busca una clave vigente y devuelve la sesión equivalente, que dura lo que la petición.
+pub async fn sesion_de_clave(pool: &Pool, clave: &str) -> Result<Sesion, ApiError> {
+    let mut conn = db::conectar(pool).await.map_err(ApiError::conexion)?;
+    let (nombre, rol, cine): (String, String, Option<u32>) = conn
+        .exec_first(
+            "SELECT nombre, rol, cine_id FROM api_keys WHERE hash = :hash AND revocado_en IS NULL",
+            params! { "hash" => hash_secreto(clave) },
+        )
+        .await
+        .map_err(ApiError::base_datos("Error al verificar la clave de API"))?
+        .ok_or_else(|| ApiError::NoAutorizado("Clave de API inválida o revocada".to_string()))?;
+    let ahora = ahora();
+    Ok(Sesion {
+        sub: format!("api-key:{}", nombre),
+        rol: Rol::desde_str(&rol).unwrap_or(Rol::Taquillero),
+        cine,
+        iat: ahora,
+        exp: ahora,
+    })
+}
+
+/// Handler que crea una clave y la devuelve completa, la única vez que se muestra.
+pub async fn crear_clave(
+    Administrador(admin): Administrador,
+    pool: web::Data<Pool>,
+    datos: Json<CrearClave>,
+) -> Result<ApiResponse<serde_json::Value>, ApiError> {
+    let CrearClave { nombre, rol, cine_id } = datos.into_inner();
+    if nombre.trim().is_empty() {
+        return Err(ApiError::Validacion("El nombre de la clave es obligatorio".to_string()));
+    }
+    let rol = rol.unwrap_or(Rol::Taquillero);
+    let cine = cine_propio(&admin, cine_id)?;
+    let (clave, hash) = generar_secreto();
+    let prefijo = clave[..LONGITUD_PREFIJO].to_string();
+
+    let mut conn = db::conectar(&pool).await.map_err(ApiError::conexion)?;
+    conn.exec_drop(
+        "INSERT INTO api_keys (nombre, prefijo, hash, rol, cine_id) VALUES (:nombre, :prefijo, :hash, :rol, :cine_id)",
+        params! {
+            "nombre" => &nombre,
+            "prefijo" => &prefijo,
+            "hash" => hash,
+            "rol" => rol.como_str(),
+            "cine_id" => cine,
+        },
+    )
+    .await
+    .map_err(ApiError::escritura("Error al crear la clave de API"))?;
+
+    Ok(ApiResponse::creada(serde_json::json!({
+        "id": conn.last_insert_id().unwrap_or_default(),
+        "nombre": nombre,
+        "prefijo": prefijo,
+        "rol": rol,
+        "cine_id": cine,
+        "clave": clave,
+    })))
+}
+
+/// Handler que lista las claves, vigentes y revocadas.
+pub async fn listar_claves(
+    Administrador(admin): Administrador,
+    pool: web::Data<Pool>,
+) -> Result<ApiResponse<Vec<ClaveApi>>, ApiError> {
+    let mut conn = db::conectar(&pool).await.map_err(ApiError::conexion)?;
+    let claves = conn
+        .exec_map(
+            "SELECT id, nombre, prefijo, rol, cine_id, DATE_FORMAT(creado_en, '%Y-%m-%d %H:%i:%s'), \
+             DATE_FORMAT(revocado_en, '%Y-%m-%d %H:%i:%s') FROM api_keys \
+             WHERE :cine_id IS NULL OR cine_id = :cine_id ORDER BY id",
+            params! { "cine_id" => admin.cine },
+            ClaveApi::desde_fila,
+        )
+        .await
+        .map_err(ApiError::base_datos("Error al obtener las claves de API"))?;
+    Ok(ApiResponse::ok(claves))
+}
+
+/// Handler que revoca una clave. Las peticiones con ella se rechazan desde ese momento.
+pub async fn revocar_clave(
+    Administrador(admin): Administrador,
+    pool: web::Data<Pool>,
+    id: web::Path<u32>,
+) -> Result<HttpResponse, ApiError> {
+    let mut conn = db::conectar(&pool).await.map_err(ApiError::conexion)?;
+    conn.exec_drop(
+        "UPDATE api_keys SET revocado_en = NOW() \
+         WHERE id = :id AND revocado_en IS NULL AND (:cine_id IS NULL OR cine_id = :cine_id)",
+        params! { "id" => id.into_inner(), "cine_id" => admin.cine },
+    )
+    .await
+    .map_err(ApiError::base_datos("Error al revocar la clave de API"))?;
+
+    if conn.affected_rows() == 0 {
+        return Err(ApiError::NoEncontrado("Clave de API no encontrada o ya revocada".to_string()));
+    }
+    Ok(HttpResponse::NoContent().finish())
+}
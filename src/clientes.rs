@@ -0,0 +1,163 @@
+//! Clientes (`/clientes`) del cine de la petición, identificados en las rutas por su
+//! número de cédula, que se repite sólo en otros cines.
+//!
+//! Cada entrada referencia a su cliente por `cliente_id`, así que un cliente con una
+//! entrada comprada no se puede eliminar.
+
+use actix_web::{web, HttpResponse};
+use mysql_async::{prelude::*, Pool};
+
+use crate::auth::Administrador;
+use crate::cache::CacheLecturas;
+use crate::cines::CineActual;
+use crate::config::Config;
+use crate::db;
+use crate::db::errores::{self, FalloMysql};
+use crate::error::ApiError;
+use crate::json::Json;
+use crate::models::{ActualizarCliente, Cliente, CrearCliente};
+use crate::respuesta::ApiResponse;
+use crate::validacion::Validar;
+
+const SELECT_CLIENTES: &str = "SELECT id, numero_cedula, nombre, email FROM clientes";
+
+fn no_encontrado() -> ApiError {
+    ApiError::NoEncontrado("Cliente no encontrado".to_string())
+}
+
+/// Handler que lista los clientes por nombre.
+pub async fn listar_clientes(
+    CineActual(cine): CineActual,
+    pool: web::Data<Pool>,
+) -> Result<ApiResponse<Vec<Cliente>>, ApiError> {
+    let mut conn = db::conectar(&pool).await.map_err(ApiError::conexion)?;
+    let clientes = conn
+        .exec(
+            format!("{} WHERE cine_id = :cine_id ORDER BY nombre, numero_cedula", SELECT_CLIENTES),
+            params! { "cine_id" => cine },
+        )
+        .await
+        .map_err(ApiError::base_datos("Error al obtener clientes"))?;
+    Ok(ApiResponse::ok(clientes))
+}
+
+/// Handler para obtener un cliente por su cédula.
+pub async fn obtener_cliente(
+    CineActual(cine): CineActual,
+    pool: web::Data<Pool>,
+    cedula: web::Path<String>,
+) -> Result<ApiResponse<Cliente>, ApiError> {
+    let mut conn = db::conectar(&pool).await.map_err(ApiError::conexion)?;
+    conn.exec_first(
+        format!("{} WHERE numero_cedula = :numero_cedula AND cine_id = :cine_id", SELECT_CLIENTES),
+        params! { "numero_cedula" => cedula.into_inner(), "cine_id" => cine },
+    )
+    .await
+    .map_err(ApiError::base_datos("Error al obtener cliente"))?
+    .map(ApiResponse::ok)
+    .ok_or_else(no_encontrado)
+}
+
+/// Handler para registrar un cliente.
+pub async fn crear_cliente(
+    CineActual(cine): CineActual,
+    pool: web::Data<Pool>,
+    config: web::Data<Config>,
+    datos: Json<CrearCliente>,
+) -> Result<ApiResponse<Cliente>, ApiError> {
+    let datos = datos.into_inner();
+    datos
+        .validar(&config.reglas_validacion())
+        .map_err(ApiError::CamposInvalidos)?;
+
+    let mut conn = db::conectar(&pool).await.map_err(ApiError::conexion)?;
+    let resultado = conn
+        .exec_drop(
+            "INSERT INTO clientes (cine_id, numero_cedula, nombre, email) \
+             VALUES (:cine_id, :numero_cedula, :nombre, :email)",
+            params! {
+                "cine_id" => cine,
+                "numero_cedula" => &datos.numero_cedula,
+                "nombre" => &datos.nombre,
+                "email" => &datos.email,
+            },
+        )
+        .await;
+    match resultado {
+        Ok(()) => {
+            let CrearCliente { numero_cedula, nombre, email } = datos;
+            let id = conn.last_insert_id().unwrap_or_default() as u32;
+            Ok(ApiResponse::creada(Cliente { id, numero_cedula, nombre, email }))
+        }
+        Err(e) => Err(match errores::clasificar(&e) {
+            FalloMysql::Duplicado(restriccion) => ApiError::Duplicado {
+                mensaje: "Ya existe un cliente con ese número de cédula".to_string(),
+                restriccion,
+            },
+            _ => ApiError::escritura("Error al crear cliente")(e),
+        }),
+    }
+}
+
+/// Handler que cambia el nombre y el correo de un cliente. Sólo para administradores.
+pub async fn actualizar_cliente(
+    _admin: Administrador,
+    CineActual(cine): CineActual,
+    pool: web::Data<Pool>,
+    cache: web::Data<CacheLecturas>,
+    config: web::Data<Config>,
+    cedula: web::Path<String>,
+    datos: Json<ActualizarCliente>,
+) -> Result<ApiResponse<Cliente>, ApiError> {
+    let datos = datos.into_inner();
+    datos
+        .validar(&config.reglas_validacion())
+        .map_err(ApiError::CamposInvalidos)?;
+
+    let mut conn = db::conectar(&pool).await.map_err(ApiError::conexion)?;
+    let id: u32 = conn
+        .exec_first(
+            "SELECT id FROM clientes WHERE numero_cedula = :numero_cedula AND cine_id = :cine_id",
+            params! { "numero_cedula" => cedula.as_str(), "cine_id" => cine },
+        )
+        .await
+        .map_err(ApiError::base_datos("Error al actualizar cliente"))?
+        .ok_or_else(no_encontrado)?;
+    conn.exec_drop(
+        "UPDATE clientes SET nombre = :nombre, email = :email WHERE id = :id",
+        params! { "id" => id, "nombre" => &datos.nombre, "email" => &datos.email },
+    )
+    .await
+    .map_err(ApiError::base_datos("Error al actualizar cliente"))?;
+    cache.entradas.vaciar().await;
+
+    let ActualizarCliente { nombre, email } = datos;
+    Ok(ApiResponse::ok(Cliente { id, numero_cedula: cedula.into_inner(), nombre, email }))
+}
+
+/// Handler que elimina un cliente sin entradas. Sólo para administradores.
+pub async fn eliminar_cliente(
+    _admin: Administrador,
+    CineActual(cine): CineActual,
+    pool: web::Data<Pool>,
+    cedula: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let mut conn = db::conectar(&pool).await.map_err(ApiError::conexion)?;
+    let resultado = conn
+        .exec_drop(
+            "DELETE FROM clientes WHERE numero_cedula = :numero_cedula AND cine_id = :cine_id",
+            params! { "numero_cedula" => cedula.into_inner(), "cine_id" => cine },
+        )
+        .await;
+    match resultado {
+        Ok(()) if conn.affected_rows() == 0 => Err(no_encontrado()),
+        Ok(()) => Ok(HttpResponse::NoContent().finish()),
+        Err(e) => Err(match errores::clasificar(&e) {
+            FalloMysql::Referenciada(restriccion) => ApiError::EnUso {
+                mensaje: "El cliente tiene entradas compradas".to_string(),
+                restriccion,
+            },
+            _ => ApiError::escritura("Error al eliminar cliente")(e),
+        }),
+    }
+}
@@ -0,0 +1,219 @@
+//! Coalescencia de lecturas idénticas concurrentes ("single flight").
+//!
+//! Cuando llegan varias peticiones con la misma clave mientras la primera consulta sigue
+//! en curso, todas esperan ese mismo resultado en lugar de lanzar una consulta cada una.
+//! Las escrituras usan [`GrupoSerializado`], que además ejecuta de una en una las que
+//! tienen la misma clave y datos distintos.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use futures_util::future::{BoxFuture, FutureExt, Shared};
+use serde::Serialize;
+use tokio::sync::oneshot;
+
+use crate::agregado::ResultadoAgregado;
+use crate::models::Entrada;
+use crate::servicio::ErrorEntrada;
+
+type EnVuelo<K, V> = Arc<Mutex<HashMap<K, Shared<BoxFuture<'static, V>>>>>;
+
+/// Grupo de consultas en vuelo indexadas por clave.
+pub struct GrupoVuelo<K, V> {
+    en_vuelo: EnVuelo<K, V>,
+    ejecutadas: AtomicU64,
+    coalescidas: AtomicU64,
+}
+
+/// Contadores de un [`GrupoVuelo`].
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct EstadisticasCoalescencia {
+    /// Consultas que llegaron a la base de datos.
+    pub ejecutadas: u64,
+    /// Peticiones que reutilizaron una consulta ya en curso.
+    pub coalescidas: u64,
+}
+
+impl<K, V> Default for GrupoVuelo<K, V> {
+    fn default() -> Self {
+        GrupoVuelo {
+            en_vuelo: Arc::new(Mutex::new(HashMap::new())),
+            ejecutadas: AtomicU64::new(0),
+            coalescidas: AtomicU64::new(0),
+        }
+    }
+}
+
+impl<K, V> GrupoVuelo<K, V>
+where
+    K: Eq + Hash + Clone + Send + 'static,
+    V: Clone + Send + Sync + 'static,
+{
+    /// Ejecuta `consulta` salvo que ya haya una en curso para `clave`, en cuyo caso espera su resultado.
+    pub async fn ejecutar<F>(&self, clave: K, consulta: F) -> V
+    where
+        F: Future<Output = V> + Send + 'static,
+    {
+        let futuro = {
+            let mut en_vuelo = self.en_vuelo.lock().unwrap();
+            match en_vuelo.get(&clave) {
+                Some(existente) => {
+                    self.coalescidas.fetch_add(1, Ordering::Relaxed);
+                    existente.clone()
+                }
+                None => {
+                    self.ejecutadas.fetch_add(1, Ordering::Relaxed);
+                    // El propio futuro se retira del mapa al terminar, aunque quien lo lanzó
+                    // haya abandonado la petición.
+                    let mapa = self.en_vuelo.clone();
+                    let clave_liberar = clave.clone();
+                    let futuro = async move {
+                        let resultado = consulta.await;
+                        mapa.lock().unwrap().remove(&clave_liberar);
+                        resultado
+                    }
+                    .boxed()
+                    .shared();
+                    en_vuelo.insert(clave, futuro.clone());
+                    futuro
+                }
+            }
+        };
+        futuro.await
+    }
+
+    pub fn estadisticas(&self) -> EstadisticasCoalescencia {
+        EstadisticasCoalescencia {
+            ejecutadas: self.ejecutadas.load(Ordering::Relaxed),
+            coalescidas: self.coalescidas.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Resultado de una lectura compartida entre las peticiones coalescidas.
+pub type Lectura<T> = Result<T, ErrorEntrada>;
+
+/// Grupos de coalescencia para las lecturas más solicitadas de la API.
+#[derive(Default)]
+pub struct LecturasCoalescidas {
+    /// Entradas por cine e ID.
+    pub entradas: GrupoVuelo<(u32, u32), Lectura<Option<Entrada>>>,
+    pub agregados: GrupoVuelo<String, Lectura<Arc<ResultadoAgregado>>>,
+}
+
+/// Resultado de una operación de [`GrupoSerializado`]: `None` si se canceló.
+type Resultado<V> = Shared<BoxFuture<'static, Option<V>>>;
+
+/// Operaciones en curso indexadas por clave, que se ejecutan de una en una por clave. Una
+/// operación con los mismos datos que la que está en curso recibe su resultado en lugar de
+/// ejecutarse; con otros datos, espera a que termine y después se ejecuta.
+///
+/// A diferencia de [`GrupoVuelo`], la operación la ejecuta quien llegó primero y no se
+/// comparte: si abandona la petición a mitad, la operación se cancela y las que esperaban
+/// vuelven a intentarlo.
+pub struct GrupoSerializado<K, D, V> {
+    en_curso: Mutex<HashMap<K, (D, Resultado<V>)>>,
+    ejecutadas: AtomicU64,
+    coalescidas: AtomicU64,
+}
+
+impl<K, D, V> Default for GrupoSerializado<K, D, V> {
+    fn default() -> Self {
+        GrupoSerializado {
+            en_curso: Mutex::new(HashMap::new()),
+            ejecutadas: AtomicU64::new(0),
+            coalescidas: AtomicU64::new(0),
+        }
+    }
+}
+
+/// Retira del grupo la operación de `clave` al terminar o al cancelarse.
+struct Turno<'a, K: Eq + Hash, D, V> {
+    grupo: &'a GrupoSerializado<K, D, V>,
+    clave: &'a K,
+}
+
+impl<K: Eq + Hash, D, V> Drop for Turno<'_, K, D, V> {
+    fn drop(&mut self) {
+        self.grupo.en_curso.lock().unwrap().remove(self.clave);
+    }
+}
+
+impl<K, D, V> GrupoSerializado<K, D, V>
+where
+    K: Eq + Hash + Clone,
+    D: Eq + Clone,
+    V: Clone + Send + Sync + 'static,
+{
+    /// Ejecuta `operacion` cuando no haya otra en curso para `clave`, salvo que la que está
+    /// en curso tenga los mismos `datos`: entonces devuelve su resultado. El segundo valor
+    /// indica si el resultado es el de otra petición.
+    pub async fn ejecutar<F>(&self, clave: K, datos: D, operacion: impl FnOnce() -> F) -> (V, bool)
+    where
+        F: Future<Output = V>,
+    {
+        let emisor = loop {
+            let (espera, iguales) = {
+                let mut en_curso = self.en_curso.lock().unwrap();
+                match en_curso.get(&clave) {
+                    Some((otros, resultado)) => (resultado.clone(), *otros == datos),
+                    None => {
+                        let (emisor, receptor) = oneshot::channel();
+                        en_curso.insert(clave.clone(), (datos, receptor.map(Result::ok).boxed().shared()));
+                        break emisor;
+                    }
+                }
+            };
+            if let Some(resultado) = espera.await.filter(|_| iguales) {
+                self.coalescidas.fetch_add(1, Ordering::Relaxed);
+                return (resultado, true);
+            }
+        };
+        self.ejecutadas.fetch_add(1, Ordering::Relaxed);
+        let _turno = Turno { grupo: self, clave: &clave };
+        let resultado = operacion().await;
+        let _ = emisor.send(resultado.clone());
+        (resultado, false)
+    }
+
+    pub fn estadisticas(&self) -> EstadisticasCoalescencia {
+        EstadisticasCoalescencia {
+            ejecutadas: self.ejecutadas.load(Ordering::Relaxed),
+            coalescidas: self.coalescidas.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[actix_web::test]
+    async fn comparte_el_resultado_de_los_mismos_datos_y_espera_con_otros() {
+        let grupo: GrupoSerializado<u32, u32, u32> = GrupoSerializado::default();
+        let ejecutadas = AtomicU64::new(0);
+        let operacion = |valor: u32| {
+            let ejecutadas = &ejecutadas;
+            move || async move {
+                let orden = ejecutadas.fetch_add(1, Ordering::SeqCst);
+                actix_web::rt::time::sleep(Duration::from_millis(20)).await;
+                valor * 10 + orden as u32
+            }
+        };
+
+        let (primera, repetida, distinta) = futures_util::join!(
+            grupo.ejecutar(1, 5, operacion(5)),
+            grupo.ejecutar(1, 5, operacion(5)),
+            grupo.ejecutar(1, 6, operacion(6)),
+        );
+        assert_eq!(primera, (50, false));
+        assert_eq!(repetida, (50, true));
+        // La de otros datos se ejecutó después de la primera, no a la vez.
+        assert_eq!(distinta, (61, false));
+        assert_eq!(grupo.estadisticas().coalescidas, 1);
+    }
+}
@@ -0,0 +1,205 @@
+//! Almacén compartido entre réplicas en Redis, opcional (`REDIS_URL`).
+//!
+//! Con varias réplicas detrás de un balanceador, la caché de lecturas, las cubetas del
+//! límite de peticiones y las claves de idempotencia de cada proceso divergen. Con Redis
+//! configurado las tres se guardan aquí; sin él, la caché y los límites viven en la memoria
+//! del proceso y la idempotencia en MySQL.
+//!
+//! La conexión se abre con la primera operación y se restablece sola si se cae. Los
+//! valores se guardan como JSON.
+
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use redis::aio::ConnectionManager;
+use redis::{Client, Script};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tokio::sync::OnceCell;
+
+/// Prefijo de todas las claves, para poder compartir la instancia con otras aplicaciones.
+const PREFIJO: &str = "rust-crud:";
+
+/// Cubeta de tokens de [`crate::limite`]: la rellena según el tiempo transcurrido, según el
+/// reloj de Redis para que todas las réplicas cuenten igual, y gasta un token. Devuelve los
+/// segundos que faltan para el siguiente, o 0 si lo había; como texto, porque Redis trunca
+/// los números de Lua a enteros.
+static SCRIPT_CUBETA: LazyLock<Script> = LazyLock::new(|| {
+    Script::new(
+        r#"
+local tiempo = redis.call('TIME')
+local ahora = tonumber(tiempo[1]) + tonumber(tiempo[2]) / 1000000
+local ritmo = tonumber(ARGV[1])
+local rafaga = tonumber(ARGV[2])
+local cubeta = redis.call('HMGET', KEYS[1], 'tokens', 'actualizada')
+local tokens = tonumber(cubeta[1]) or rafaga
+local actualizada = tonumber(cubeta[2]) or ahora
+tokens = math.min(rafaga, tokens + math.max(0, ahora - actualizada) * ritmo)
+local espera = 0
+if tokens >= 1 then
+  tokens = tokens - 1
+else
+  espera = (1 - tokens) / ritmo
+end
+redis.call('HSET', KEYS[1], 'tokens', tostring(tokens), 'actualizada', tostring(ahora))
+redis.call('EXPIRE', KEYS[1], math.ceil(rafaga / ritmo) + 1)
+return tostring(espera)
+"#,
+    )
+});
+
+/// Sustituye el valor de una clave que existe conservando su caducidad. Es `SET XX KEEPTTL`,
+/// que no existe antes de Redis 6.
+static SCRIPT_ACTUALIZAR: LazyLock<Script> = LazyLock::new(|| {
+    Script::new(
+        r#"
+local ttl = redis.call('PTTL', KEYS[1])
+if ttl > 0 then
+  redis.call('SET', KEYS[1], ARGV[1], 'PX', ttl)
+end
+return ttl
+"#,
+    )
+});
+
+/// Resultado de una operación en Redis. El error ya viene como texto para el log.
+pub type ResultadoRedis<T> = Result<T, String>;
+
+/// Conexión perezosa a Redis, compartida por los workers.
+pub struct Redis {
+    cliente: Client,
+    conexion: OnceCell<ConnectionManager>,
+}
+
+impl Redis {
+    /// Valida la URL (`redis://host:6379/0`), sin conectar todavía.
+    pub fn new(url: &str) -> Result<Redis, String> {
+        let cliente = Client::open(url).map_err(|e| format!("REDIS_URL inválida: {}", e))?;
+        Ok(Redis {
+            cliente,
+            conexion: OnceCell::new(),
+        })
+    }
+
+    async fn conexion(&self) -> ResultadoRedis<ConnectionManager> {
+        self.conexion
+            .get_or_try_init(|| ConnectionManager::new(self.cliente.clone()))
+            .await
+            .cloned()
+            .map_err(|e| format!("No se pudo conectar a Redis: {}", e))
+    }
+
+    /// Valor JSON guardado en `clave`, si existe y se puede leer.
+    pub async fn leer<T: DeserializeOwned>(&self, clave: &str) -> ResultadoRedis<Option<T>> {
+        let mut conexion = self.conexion().await?;
+        let texto: Option<String> = redis::cmd("GET")
+            .arg(format!("{}{}", PREFIJO, clave))
+            .query_async(&mut conexion)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(texto.and_then(|texto| serde_json::from_str(&texto).ok()))
+    }
+
+    /// Guarda `valor` en `clave` durante `ttl`.
+    pub async fn escribir(&self, clave: &str, valor: &impl Serialize, ttl: Duration) -> ResultadoRedis<()> {
+        let mut conexion = self.conexion().await?;
+        redis::cmd("SET")
+            .arg(format!("{}{}", PREFIJO, clave))
+            .arg(serde_json::to_string(valor).unwrap_or_default())
+            .arg("PX")
+            .arg(ttl.as_millis().max(1) as u64)
+            .query_async(&mut conexion)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    /// Como [`Redis::escribir`], pero sólo si `clave` no existía. Devuelve si se guardó.
+    pub async fn reservar(&self, clave: &str, valor: &impl Serialize, ttl: Duration) -> ResultadoRedis<bool> {
+        let mut conexion = self.conexion().await?;
+        let guardado: Option<String> = redis::cmd("SET")
+            .arg(format!("{}{}", PREFIJO, clave))
+            .arg(serde_json::to_string(valor).unwrap_or_default())
+            .arg("NX")
+            .arg("PX")
+            .arg(ttl.as_millis().max(1) as u64)
+            .query_async(&mut conexion)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(guardado.is_some())
+    }
+
+    /// Sustituye el valor de `clave`, si existe, sin cambiar cuándo caduca.
+    pub async fn actualizar(&self, clave: &str, valor: &impl Serialize) -> ResultadoRedis<()> {
+        let mut conexion = self.conexion().await?;
+        let _: i64 = SCRIPT_ACTUALIZAR
+            .key(format!("{}{}", PREFIJO, clave))
+            .arg(serde_json::to_string(valor).unwrap_or_default())
+            .invoke_async(&mut conexion)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    pub async fn borrar(&self, clave: &str) -> ResultadoRedis<()> {
+        let mut conexion = self.conexion().await?;
+        redis::cmd("DEL")
+            .arg(format!("{}{}", PREFIJO, clave))
+            .query_async(&mut conexion)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    /// Valor numérico de `clave`, o 0 si no existe.
+    pub async fn contador(&self, clave: &str) -> ResultadoRedis<u64> {
+        let mut conexion = self.conexion().await?;
+        let valor: Option<u64> = redis::cmd("GET")
+            .arg(format!("{}{}", PREFIJO, clave))
+            .query_async(&mut conexion)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(valor.unwrap_or(0))
+    }
+
+    /// Suma uno a `clave` y devuelve el nuevo valor.
+    pub async fn incrementar(&self, clave: &str) -> ResultadoRedis<u64> {
+        let mut conexion = self.conexion().await?;
+        redis::cmd("INCR")
+            .arg(format!("{}{}", PREFIJO, clave))
+            .query_async(&mut conexion)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    /// Gasta un token de la cubeta `clave`, con el ritmo y la ráfaga indicados. Sin tokens,
+    /// devuelve cuánto falta para el siguiente.
+    pub async fn consumir_token(
+        &self,
+        clave: &str,
+        por_segundo: f64,
+        rafaga: u32,
+    ) -> ResultadoRedis<Result<(), Duration>> {
+        let mut conexion = self.conexion().await?;
+        let espera: String = SCRIPT_CUBETA
+            .key(format!("{}{}", PREFIJO, clave))
+            .arg(por_segundo)
+            .arg(rafaga)
+            .invoke_async(&mut conexion)
+            .await
+            .map_err(|e| e.to_string())?;
+        match espera.parse::<f64>() {
+            Ok(espera) if espera > 0.0 => Ok(Err(Duration::from_secs_f64(espera))),
+            _ => Ok(Ok(())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn valida_la_url_sin_conectar() {
+        assert!(Redis::new("redis://127.0.0.1:1/0").is_ok());
+        assert!(Redis::new("http://localhost").is_err());
+    }
+}
@@ -0,0 +1,274 @@
+//! Confirmación por correo de las entradas compradas.
+//!
+//! Con `Config::smtp_url`, cada entrada creada para un cliente con `email` deja un correo
+//! pendiente en la tabla `correos`, en la misma ronda en que
+//! [`crate::webhooks::publicar_eventos`] reparte su evento: la venta nunca espera al
+//! servidor de correo ni falla por él. La tarea de [`enviar_correos`] los envía por SMTP
+//! con los datos de la entrada y su ticket en PDF, con el código QR de la puerta, y
+//! reintenta los que fallan con esperas crecientes hasta [`INTENTOS`].
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::future::join_all;
+use lettre::message::header::ContentType;
+use lettre::message::{Attachment, Mailbox, MultiPart, SinglePart};
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use mysql_async::{prelude::*, Pool, Transaction, TxOpts};
+
+use crate::auth::ConfigAuth;
+use crate::db;
+use crate::models::Entrada;
+use crate::servicio::ServicioEntradas;
+use crate::tareas::{self, Intervalo, Trabajo};
+use crate::webhooks::espera;
+use crate::{qr, ticket, Estado};
+
+/// Intentos de envío de cada correo antes de darlo por fallido.
+pub const INTENTOS: u32 = 6;
+
+/// Cada cuánto se buscan correos pendientes.
+const PERIODO_ENVIO: Duration = Duration::from_secs(5);
+
+/// Correos que se envían como máximo en cada ronda.
+const CORREOS_POR_RONDA: u32 = 20;
+
+/// Tiempo durante el que un correo en curso no se vuelve a enviar, por si el proceso que
+/// lo envía cae a mitad. Cubre el tiempo máximo de una conexión SMTP.
+const RESERVA: Duration = Duration::from_secs(120);
+
+/// Encola la confirmación de las entradas `ids` cuyo cliente tiene correo.
+pub(crate) async fn encolar_confirmaciones(tx: &mut Transaction<'_>, ids: &[u32]) -> Result<(), mysql_async::Error> {
+    if ids.is_empty() {
+        return Ok(());
+    }
+    let ids: Vec<String> = ids.iter().map(u32::to_string).collect();
+    tx.query_drop(format!(
+        "INSERT INTO correos (entrada_id, cine_id, destinatario) \
+         SELECT e.id, e.cine_id, c.email FROM entradas e JOIN clientes c ON c.id = e.cliente_id \
+         WHERE e.id IN ({}) AND c.email IS NOT NULL",
+        ids.join(",")
+    ))
+    .await
+}
+
+/// Lo que necesita la tarea de envío: de dónde leer las entradas, con qué firmar su
+/// código y por dónde enviarlas.
+#[derive(Clone)]
+pub struct Confirmaciones {
+    servicio: Arc<ServicioEntradas>,
+    pool: Pool,
+    auth: ConfigAuth,
+    transporte: AsyncSmtpTransport<Tokio1Executor>,
+    remitente: Mailbox,
+}
+
+impl Confirmaciones {
+    /// Confirmaciones con la configuración SMTP de `estado`, o `None` si no hay `smtp_url`.
+    /// Falla si la URL o el remitente no son válidos.
+    pub fn new(estado: &Estado) -> Result<Option<Confirmaciones>, String> {
+        let Some(url) = &estado.config.smtp_url else {
+            return Ok(None);
+        };
+        let transporte = AsyncSmtpTransport::<Tokio1Executor>::from_url(url)
+            .map_err(|e| format!("SMTP_URL inválida: {}", e))?
+            .build();
+        let remitente = estado
+            .config
+            .correo_remitente
+            .parse()
+            .map_err(|e| format!("CORREO_REMITENTE inválido: {}", e))?;
+        Ok(Some(Confirmaciones {
+            servicio: estado.entradas.clone(),
+            pool: estado.pool.clone(),
+            auth: estado.config.auth.clone(),
+            transporte,
+            remitente,
+        }))
+    }
+
+    /// Una ronda de [`enviar_correos`]: envía a la vez los correos pendientes y anota el
+    /// resultado de cada uno.
+    pub async fn enviar_pendientes(&self) -> Result<(), String> {
+        let correos = self
+            .reservar()
+            .await
+            .map_err(|e| format!("Error al leer los correos pendientes: {:?}", e))?;
+        if correos.is_empty() {
+            return Ok(());
+        }
+        let resultados = join_all(correos.iter().map(|correo| self.enviar(correo))).await;
+        let mut conn = db::conectar(&self.pool)
+            .await
+            .map_err(|e| format!("Error al registrar los envíos: {:?}", e))?;
+        for (correo, resultado) in correos.iter().zip(resultados) {
+            let intentos = correo.intentos + 1;
+            let estado = match &resultado {
+                Ok(()) => "enviado",
+                Err(_) if intentos >= INTENTOS => "fallido",
+                Err(_) => "pendiente",
+            };
+            if let Err(error) = &resultado
+                && estado == "fallido"
+            {
+                log::warn!(
+                    "La confirmación {} a {} se da por fallida tras {} intentos: {}",
+                    correo.id, correo.destinatario, intentos, error
+                );
+            }
+            conn.exec_drop(
+                "UPDATE correos SET estado = :estado, intentos = :intentos, ultimo_error = LEFT(:error, 512), \
+                 siguiente_intento = NOW() + INTERVAL :espera SECOND, enviado_en = IF(:enviado, NOW(), NULL) \
+                 WHERE id = :id",
+                params! {
+                    "estado" => estado,
+                    "intentos" => intentos,
+                    "error" => resultado.as_ref().err(),
+                    "espera" => espera(intentos).as_secs(),
+                    "enviado" => resultado.is_ok(),
+                    "id" => correo.id,
+                },
+            )
+            .await
+            .map_err(|e| format!("Error al registrar el envío {}: {:?}", correo.id, e))?;
+        }
+        Ok(())
+    }
+
+    /// Toma los correos pendientes cuyo intento ya tocaba y aplaza el siguiente
+    /// [`RESERVA`], para que otra réplica no los envíe a la vez.
+    async fn reservar(&self) -> Result<Vec<Correo>, mysql_async::Error> {
+        let mut conn = db::conectar(&self.pool).await?;
+        let mut tx = conn.start_transaction(TxOpts::default()).await?;
+        let correos = tx
+            .exec_map(
+                "SELECT id, entrada_id, cine_id, destinatario, intentos FROM correos \
+                 WHERE estado = 'pendiente' AND siguiente_intento <= NOW() \
+                 ORDER BY id LIMIT :limite FOR UPDATE SKIP LOCKED",
+                params! { "limite" => CORREOS_POR_RONDA },
+                |(id, entrada_id, cine_id, destinatario, intentos)| Correo {
+                    id,
+                    entrada_id,
+                    cine_id,
+                    destinatario,
+                    intentos,
+                },
+            )
+            .await?;
+        if !correos.is_empty() {
+            let ids: Vec<String> = correos.iter().map(|correo| correo.id.to_string()).collect();
+            tx.exec_drop(
+                format!(
+                    "UPDATE correos SET siguiente_intento = NOW() + INTERVAL :reserva SECOND WHERE id IN ({})",
+                    ids.join(",")
+                ),
+                params! { "reserva" => RESERVA.as_secs() },
+            )
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(correos)
+    }
+
+    /// Envía un correo con la entrada como está ahora.
+    async fn enviar(&self, correo: &Correo) -> Result<(), String> {
+        let entrada = self.servicio.obtener(correo.cine_id, correo.entrada_id).await.map_err(|e| e.to_string())?;
+        let codigo = qr::firmar(&self.auth, correo.entrada_id);
+        let mensaje = mensaje(&self.remitente, &correo.destinatario, &entrada, &codigo)?;
+        self.transporte.send(mensaje).await.map(|_| ()).map_err(|e| e.to_string())
+    }
+}
+
+/// Correo pendiente.
+struct Correo {
+    id: u64,
+    entrada_id: u32,
+    cine_id: u32,
+    destinatario: String,
+    intentos: u32,
+}
+
+/// Tarea que envía los correos pendientes cada [`PERIODO_ENVIO`].
+pub fn enviar_correos(confirmaciones: Confirmaciones) -> (Intervalo, Trabajo) {
+    let trabajo = tareas::trabajo(move || {
+        let confirmaciones = confirmaciones.clone();
+        async move { confirmaciones.enviar_pendientes().await }
+    });
+    (Intervalo::cada(PERIODO_ENVIO), trabajo)
+}
+
+/// Correo de confirmación de `entrada` para `destinatario`, con los datos de la compra en
+/// el texto y el ticket con el código QR adjunto.
+fn mensaje(remitente: &Mailbox, destinatario: &str, entrada: &Entrada, codigo: &str) -> Result<Message, String> {
+    let destinatario: Mailbox = destinatario
+        .parse()
+        .map_err(|e| format!("Destinatario {} inválido: {}", destinatario, e))?;
+    let id = entrada.id.unwrap_or_default();
+    let funcion = &entrada.funcion;
+    let texto = format!(
+        "Hola, {}:\n\n\
+         Tu compra está confirmada.\n\n\
+         Entrada #{}\n\
+         Función: {}\n\
+         Sala: {}\n\
+         Horario: {}\n\
+         Cantidad: {}\n\
+         Total: {:.2}\n\n\
+         Presenta en la puerta el código QR del ticket adjunto.\n",
+        entrada.cliente.nombre,
+        id,
+        funcion.titulo,
+        funcion.sala.nombre,
+        funcion.horario.format("%d/%m/%Y %H:%M"),
+        entrada.cantidad_entradas,
+        entrada.total,
+    );
+    let pdf = ContentType::parse("application/pdf").expect("Tipo MIME válido");
+    Message::builder()
+        .from(remitente.clone())
+        .to(destinatario)
+        .subject(format!("Tu entrada para {}", funcion.titulo))
+        .multipart(
+            MultiPart::mixed()
+                .singlepart(SinglePart::plain(texto))
+                .singlepart(Attachment::new(format!("entrada-{}.pdf", id)).body(ticket::generar(entrada, codigo), pdf)),
+        )
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+
+    use super::*;
+    use crate::models::{Cliente, EstadoEntrada, Funcion, Sala};
+
+    #[test]
+    fn la_confirmacion_lleva_los_datos_y_el_ticket() {
+        let entrada = Entrada {
+            id: Some(7),
+            cantidad_entradas: 2,
+            total: 13.0,
+            estado: EstadoEntrada::Reservada,
+            cliente: Cliente { id: 1, numero_cedula: "1710034065".into(), nombre: "María Pérez".into(), email: None },
+            funcion: Funcion {
+                id: 1,
+                titulo: "Dune".into(),
+                horario: DateTime::parse_from_rfc3339("2024-03-01T19:00:00-05:00").unwrap(),
+                precio: 6.5,
+                sala: Sala { id: 1, nombre: "Sala 1".into(), capacidad: 120 },
+                poster: None,
+            },
+            version: 1,
+            created_at: "2024-03-01 18:30:00".into(),
+            updated_at: "2024-03-01 18:30:00".into(),
+        };
+        let remitente: Mailbox = "Cine <no-responder@example.com>".parse().unwrap();
+        let correo = mensaje(&remitente, "maria@example.com", &entrada, "codigo").unwrap();
+        let texto = String::from_utf8(correo.formatted()).unwrap();
+        assert!(texto.contains("To: maria@example.com"));
+        assert!(texto.contains("Content-Type: application/pdf"));
+        assert!(texto.contains("entrada-7.pdf"));
+        assert!(mensaje(&remitente, "no es un correo", &entrada, "codigo").is_err());
+    }
+}
@@ -0,0 +1,160 @@
+//! CORS, para que el front-end pueda llamar a la API desde el navegador.
+//!
+//! Sin orígenes configurados no se permite ninguno: las peticiones de otros orígenes se
+//! atienden como siempre, pero sin cabeceras CORS, y el navegador no deja leerlas.
+
+use actix_cors::Cors;
+use actix_web::http::header::HeaderName;
+use actix_web::http::{Method, Uri};
+
+/// Cabeceras propias de las respuestas que el navegador debe dejar leer al front-end.
+const CABECERAS_EXPUESTAS: [&str; 8] = [
+    "X-Total-Count",
+    "X-Pagina",
+    "X-Por-Pagina",
+    "X-Request-Id",
+    "WWW-Authenticate",
+    "Retry-After",
+    "Idempotent-Replayed",
+    "ETag",
+];
+
+/// Orígenes, métodos y cabeceras que se aceptan de otros orígenes.
+#[derive(Debug, Clone)]
+pub struct ConfigCors {
+    /// Orígenes como `https://taquilla.example.com`. `*` permite cualquiera.
+    pub origenes: Vec<String>,
+    pub metodos: Vec<String>,
+    pub cabeceras: Vec<String>,
+    /// Si el navegador puede enviar credenciales (cookies, certificados) en las peticiones.
+    pub credenciales: bool,
+}
+
+impl Default for ConfigCors {
+    fn default() -> Self {
+        ConfigCors {
+            origenes: Vec::new(),
+            metodos: ["GET", "POST", "PUT", "PATCH", "DELETE"].map(String::from).to_vec(),
+            cabeceras: [
+                "Authorization",
+                "Content-Type",
+                "X-Api-Key",
+                "Idempotency-Key",
+                "If-Match",
+                "If-None-Match",
+                "If-Modified-Since",
+            ]
+            .map(String::from)
+            .to_vec(),
+            credenciales: false,
+        }
+    }
+}
+
+impl ConfigCors {
+    /// Convierte una lista separada por comas, como la de `CORS_ORIGENES`.
+    pub fn parsear_lista(valor: &str) -> Vec<String> {
+        valor
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Comprueba los valores, que `actix-cors` sólo rechaza al arrancar los workers.
+    pub fn validar(&self) -> Result<(), String> {
+        for origen in self.origenes.iter().filter(|o| *o != "*") {
+            let uri: Uri = origen
+                .parse()
+                .map_err(|_| format!("Origen CORS inválido: {}", origen))?;
+            if uri.scheme().is_none() || uri.host().is_none() {
+                return Err(format!("El origen CORS debe incluir esquema y host: {}", origen));
+            }
+        }
+        for metodo in &self.metodos {
+            Method::from_bytes(metodo.as_bytes()).map_err(|_| format!("Método CORS inválido: {}", metodo))?;
+        }
+        for cabecera in &self.cabeceras {
+            HeaderName::try_from(cabecera.as_str()).map_err(|_| format!("Cabecera CORS inválida: {}", cabecera))?;
+        }
+        Ok(())
+    }
+
+    /// Middleware con esta configuración. Los valores deben haber pasado [`ConfigCors::validar`].
+    pub fn middleware(&self) -> Cors {
+        let mut cors = Cors::default()
+            .allowed_methods(self.metodos.iter().map(String::as_str))
+            .allowed_headers(self.cabeceras.iter().map(String::as_str))
+            .expose_headers(CABECERAS_EXPUESTAS)
+            .max_age(3600);
+        for origen in &self.origenes {
+            cors = if origen == "*" {
+                cors.allow_any_origin()
+            } else {
+                cors.allowed_origin(origen)
+            };
+        }
+        if self.credenciales {
+            cors = cors.supports_credentials();
+        }
+        cors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+    use actix_web::test::{call_service, init_service, TestRequest};
+    use actix_web::{web, App, HttpResponse};
+
+    #[test]
+    fn rechaza_valores_invalidos() {
+        let mut config = ConfigCors {
+            origenes: ConfigCors::parsear_lista("https://taquilla.example.com, *"),
+            ..ConfigCors::default()
+        };
+        assert_eq!(config.origenes, ["https://taquilla.example.com", "*"]);
+        assert!(config.validar().is_ok());
+        config.origenes = vec!["taquilla.example.com".to_string()];
+        assert!(config.validar().is_err());
+        config.origenes.clear();
+        config.cabeceras.push("X Malformada".to_string());
+        assert!(config.validar().is_err());
+    }
+
+    #[actix_web::test]
+    async fn responde_el_preflight_de_un_origen_permitido() {
+        let config = ConfigCors {
+            origenes: vec!["https://taquilla.example.com".to_string()],
+            ..ConfigCors::default()
+        };
+        let app = init_service(
+            App::new()
+                .route("/entradas", web::get().to(HttpResponse::Ok))
+                .wrap(config.middleware()),
+        )
+        .await;
+
+        let req = TestRequest::default()
+            .method(Method::OPTIONS)
+            .uri("/entradas")
+            .insert_header(("Origin", "https://taquilla.example.com"))
+            .insert_header(("Access-Control-Request-Method", "GET"))
+            .to_request();
+        let res = call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            res.headers().get("access-control-allow-origin").unwrap(),
+            "https://taquilla.example.com"
+        );
+
+        let req = TestRequest::get()
+            .uri("/entradas")
+            .insert_header(("Origin", "https://otro.example.com"))
+            .to_request();
+        let res = call_service(&app, req).await;
+        assert!(res.headers().get("access-control-allow-origin").is_none());
+    }
+}
@@ -0,0 +1,128 @@
+//! Clasificación de los errores de MySQL por su código, en lugar de por el texto del
+//! mensaje, que cambia entre versiones. Del mensaje sólo se saca el nombre de la
+//! restricción o la columna que falló.
+
+use mysql_async::{DriverError, Error, ServerError};
+
+/// `ER_DUP_ENTRY`: valor repetido en un índice único.
+pub const DUPLICADO: u16 = 1062;
+/// `ER_ROW_IS_REFERENCED_2`: otras filas apuntan a la que se borra o modifica.
+pub const REFERENCIADA: u16 = 1451;
+/// `ER_NO_REFERENCED_ROW_2`: la fila apunta a otra que no existe.
+pub const REFERENCIA_INEXISTENTE: u16 = 1452;
+/// `ER_DATA_TOO_LONG`: el valor no cabe en la columna.
+pub const DEMASIADO_LARGO: u16 = 1406;
+/// `ER_LOCK_DEADLOCK`: MySQL abortó la transacción por un interbloqueo.
+pub const INTERBLOQUEO: u16 = 1213;
+/// `ER_LOCK_WAIT_TIMEOUT`: se agotó la espera de un bloqueo.
+pub const ESPERA_BLOQUEO: u16 = 1205;
+/// `ER_CON_COUNT_ERROR`: el servidor no acepta más conexiones.
+pub const DEMASIADAS_CONEXIONES: u16 = 1040;
+/// `ER_SERVER_SHUTDOWN`: el servidor se está apagando.
+pub const SERVIDOR_APAGANDOSE: u16 = 1053;
+
+/// Error de MySQL según lo que significa para la API.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FalloMysql {
+    /// Valor repetido en el índice único indicado.
+    Duplicado(String),
+    /// La fila apunta a otra que no existe, según la clave foránea indicada.
+    ReferenciaInexistente(String),
+    /// Otras filas apuntan a la que se quiere borrar, según la clave foránea indicada.
+    Referenciada(String),
+    /// El valor no cabe en la columna indicada.
+    DemasiadoLargo(String),
+    /// Interbloqueo o espera de bloqueo agotada: la operación se puede repetir.
+    Bloqueo,
+    /// Se cortó la conexión con el servidor o este no aceptó abrir otra. Si ocurre a mitad
+    /// de una escritura, no se sabe si llegó a guardarse.
+    ConexionPerdida,
+    /// Cualquier otro error.
+    Otro,
+}
+
+/// Clasifica `error` por su código de MySQL.
+pub fn clasificar(error: &Error) -> FalloMysql {
+    let (code, message) = match error {
+        Error::Server(ServerError { code, message, .. }) => (code, message),
+        Error::Io(_) | Error::Driver(DriverError::ConnectionClosed) => return FalloMysql::ConexionPerdida,
+        _ => return FalloMysql::Otro,
+    };
+    match *code {
+        // "Duplicate entry '1' for key 'entradas.uq_entradas_cliente'"; antes de MySQL 8
+        // la clave no lleva la tabla delante.
+        DUPLICADO => {
+            let clave = entre(message, "for key '", "'").unwrap_or_default();
+            FalloMysql::Duplicado(clave.rsplit('.').next().unwrap_or_default().to_string())
+        }
+        // "... a foreign key constraint fails (`bd`.`entradas`, CONSTRAINT `fk_entradas_cliente` FOREIGN KEY ..."
+        REFERENCIA_INEXISTENTE => FalloMysql::ReferenciaInexistente(restriccion(message)),
+        REFERENCIADA => FalloMysql::Referenciada(restriccion(message)),
+        // "Data too long for column 'nombre' at row 1"
+        DEMASIADO_LARGO => FalloMysql::DemasiadoLargo(entre(message, "column '", "'").unwrap_or_default().to_string()),
+        INTERBLOQUEO | ESPERA_BLOQUEO => FalloMysql::Bloqueo,
+        DEMASIADAS_CONEXIONES | SERVIDOR_APAGANDOSE => FalloMysql::ConexionPerdida,
+        _ => FalloMysql::Otro,
+    }
+}
+
+fn restriccion(mensaje: &str) -> String {
+    entre(mensaje, "CONSTRAINT `", "`").unwrap_or_default().to_string()
+}
+
+/// Texto de `mensaje` entre `inicio` y la siguiente aparición de `fin`.
+fn entre<'a>(mensaje: &'a str, inicio: &str, fin: &str) -> Option<&'a str> {
+    let resto = &mensaje[mensaje.find(inicio)? + inicio.len()..];
+    Some(&resto[..resto.find(fin)?])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(code: u16, message: &str) -> Error {
+        Error::Server(ServerError {
+            code,
+            message: message.to_string(),
+            state: "23000".to_string(),
+        })
+    }
+
+    #[test]
+    fn clasifica_por_codigo_con_la_restriccion_o_columna() {
+        assert_eq!(
+            clasificar(&error(1062, "Duplicate entry '3' for key 'entradas.uq_entradas_cliente'")),
+            FalloMysql::Duplicado("uq_entradas_cliente".into())
+        );
+        assert_eq!(
+            clasificar(&error(1062, "Duplicate entry 'Sala 1' for key 'nombre'")),
+            FalloMysql::Duplicado("nombre".into())
+        );
+        assert_eq!(
+            clasificar(&error(
+                1452,
+                "Cannot add or update a child row: a foreign key constraint fails (`cine`.`entradas`, \
+                 CONSTRAINT `fk_entradas_funcion` FOREIGN KEY (`funcion_id`) REFERENCES `funciones` (`id`))"
+            )),
+            FalloMysql::ReferenciaInexistente("fk_entradas_funcion".into())
+        );
+        assert_eq!(
+            clasificar(&error(
+                1451,
+                "Cannot delete or update a parent row: a foreign key constraint fails (`cine`.`funciones`, \
+                 CONSTRAINT `fk_funciones_sala` FOREIGN KEY (`sala_id`) REFERENCES `salas` (`id`))"
+            )),
+            FalloMysql::Referenciada("fk_funciones_sala".into())
+        );
+        assert_eq!(
+            clasificar(&error(1406, "Data too long for column 'nombre' at row 1")),
+            FalloMysql::DemasiadoLargo("nombre".into())
+        );
+        assert_eq!(clasificar(&error(1213, "Deadlock found when trying to get lock")), FalloMysql::Bloqueo);
+        assert_eq!(clasificar(&error(1205, "Lock wait timeout exceeded")), FalloMysql::Bloqueo);
+        assert_eq!(clasificar(&error(1040, "Too many connections")), FalloMysql::ConexionPerdida);
+        assert_eq!(clasificar(&Error::Driver(DriverError::ConnectionClosed)), FalloMysql::ConexionPerdida);
+        // El texto no cuenta: sólo el código.
+        assert_eq!(clasificar(&error(1064, "Duplicate entry in syntax")), FalloMysql::Otro);
+    }
+}
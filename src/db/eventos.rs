@@ -0,0 +1,83 @@
+//! Bandeja de salida de los eventos de las entradas.
+//!
+//! Cada cambio que [`RepositorioMysql`](super::repository::RepositorioMysql) registra en la
+//! auditoría deja también una fila en `eventos`, dentro de la misma transacción: si el
+//! cambio se guarda, su evento también, aunque el proceso caiga justo después. La tarea de
+//! [`crate::webhooks::publicar_eventos`] los reparte luego entre los suscriptores y los
+//! marca como publicados.
+
+use mysql_async::{prelude::*, Transaction};
+use serde::{Deserialize, Serialize};
+
+/// Evento de una entrada.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Evento {
+    #[serde(rename = "entrada.creada")]
+    Creada,
+    /// Cambio de sus datos, pago o cancelación.
+    #[serde(rename = "entrada.actualizada")]
+    Actualizada,
+    #[serde(rename = "entrada.eliminada")]
+    Eliminada,
+    #[serde(rename = "entrada.checkin")]
+    Checkin,
+}
+
+impl Evento {
+    pub fn como_str(self) -> &'static str {
+        match self {
+            Evento::Creada => "entrada.creada",
+            Evento::Actualizada => "entrada.actualizada",
+            Evento::Eliminada => "entrada.eliminada",
+            Evento::Checkin => "entrada.checkin",
+        }
+    }
+
+    pub fn desde_str(valor: &str) -> Option<Evento> {
+        match valor {
+            "entrada.creada" => Some(Evento::Creada),
+            "entrada.actualizada" => Some(Evento::Actualizada),
+            "entrada.eliminada" => Some(Evento::Eliminada),
+            "entrada.checkin" => Some(Evento::Checkin),
+            _ => None,
+        }
+    }
+
+    /// Operación con la que el evento queda en la auditoría.
+    pub(super) fn operacion(self) -> &'static str {
+        match self {
+            Evento::Creada => "crear",
+            Evento::Actualizada | Evento::Checkin => "actualizar",
+            Evento::Eliminada => "eliminar",
+        }
+    }
+}
+
+/// Deja pendiente de publicar `evento` de la entrada `id`, con la entrada como queda, o
+/// como estaba si se eliminó, en el JSON de la auditoría.
+pub(super) async fn registrar(
+    tx: &mut Transaction<'_>,
+    evento: Evento,
+    id: u32,
+    entrada: Option<&str>,
+) -> Result<(), mysql_async::Error> {
+    tx.exec_drop(
+        "INSERT INTO eventos (evento, entrada_id, entrada) VALUES (:evento, :entrada_id, :entrada)",
+        params! { "evento" => evento.como_str(), "entrada_id" => id, "entrada" => entrada },
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn el_nombre_del_evento_es_el_de_su_json() {
+        for evento in [Evento::Creada, Evento::Actualizada, Evento::Eliminada, Evento::Checkin] {
+            assert_eq!(serde_json::to_string(&evento).unwrap(), format!("\"{}\"", evento.como_str()));
+            assert_eq!(Evento::desde_str(evento.como_str()), Some(evento));
+        }
+        assert_eq!(Evento::Checkin.operacion(), "actualizar");
+    }
+}
@@ -0,0 +1,230 @@
+//! Duración de cada operación del repositorio de entradas, para distinguir si una petición
+//! lenta lo es por la base de datos o por lo demás.
+//!
+//! [`RepositorioMedido`] envuelve al repositorio y guarda cuánto tarda cada operación en un
+//! histograma por nombre, que `/metrics` expone como `db_consulta_duracion_segundos`. Las
+//! que pasan de [`Config::consulta_lenta`](crate::config::Config) se avisan en el log. La
+//! duración incluye la espera por la conexión y los reintentos; la del listado llega hasta
+//! que se termina de leer.
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use futures_util::future::BoxFuture;
+use futures_util::{FutureExt, StreamExt};
+
+use super::repository::{
+    EntradaGuardada, EntradaRepository, EntradaSimulada, Escritura, FlujoEntradas, ResultadoRepositorio,
+};
+use crate::agregado::{ParametrosAgregado, ResultadoAgregado};
+use crate::listado::ConsultaListado;
+use crate::metricas::Histograma;
+use crate::models::{
+    ActualizarEntrada, CrearEntrada, EliminarEntradas, Entrada, EstadoEntrada, GuardarEntrada, RegistroAuditoria,
+};
+
+/// Duraciones de las operaciones por nombre, y cuántas fueron lentas, para `/metrics`.
+pub struct MetricasConsultas {
+    pub duraciones: Mutex<BTreeMap<&'static str, Histograma>>,
+    pub lentas: Mutex<BTreeMap<&'static str, u64>>,
+}
+
+pub static METRICAS_CONSULTAS: MetricasConsultas = MetricasConsultas {
+    duraciones: Mutex::new(BTreeMap::new()),
+    lentas: Mutex::new(BTreeMap::new()),
+};
+
+/// Registra la duración de `consulta` y la avisa si pasa de `lenta`, salvo que sea cero.
+fn registrar(consulta: &'static str, duracion: Duration, lenta: Duration) {
+    METRICAS_CONSULTAS.duraciones.lock().unwrap().entry(consulta).or_default().observar(duracion);
+    if !lenta.is_zero() && duracion >= lenta {
+        *METRICAS_CONSULTAS.lentas.lock().unwrap().entry(consulta).or_default() += 1;
+        log::warn!("Consulta lenta: {} tardó {} ms", consulta, duracion.as_millis());
+    }
+}
+
+/// Registra la duración de un listado al soltarse su flujo de filas, leído entero o no.
+struct MedicionListado {
+    inicio: Instant,
+    lenta: Duration,
+}
+
+impl Drop for MedicionListado {
+    fn drop(&mut self) {
+        registrar("listar", self.inicio.elapsed(), self.lenta);
+    }
+}
+
+/// Repositorio que mide cada operación de `interno`.
+pub struct RepositorioMedido {
+    interno: Arc<dyn EntradaRepository>,
+    lenta: Duration,
+}
+
+impl RepositorioMedido {
+    /// Avisa de las operaciones que tardan `lenta` o más; con cero, de ninguna.
+    pub fn new(interno: Arc<dyn EntradaRepository>, lenta: Duration) -> Self {
+        RepositorioMedido { interno, lenta }
+    }
+
+    fn medir<'a, T: Send + 'a>(
+        &self,
+        consulta: &'static str,
+        operacion: impl Future<Output = T> + Send + 'a,
+    ) -> BoxFuture<'a, T> {
+        let lenta = self.lenta;
+        async move {
+            let inicio = Instant::now();
+            let resultado = operacion.await;
+            registrar(consulta, inicio.elapsed(), lenta);
+            resultado
+        }
+        .boxed()
+    }
+}
+
+impl EntradaRepository for RepositorioMedido {
+    fn listar<'a>(&'a self, cine: u32, consulta: &'a ConsultaListado) -> BoxFuture<'a, ResultadoRepositorio<FlujoEntradas>> {
+        async move {
+            let medicion = MedicionListado { inicio: Instant::now(), lenta: self.lenta };
+            let filas = self.interno.listar(cine, consulta).await?;
+            // El flujo se queda con la medición, que se registra al soltarlo.
+            Ok(filas
+                .map(move |fila| {
+                    let _ = &medicion;
+                    fila
+                })
+                .boxed())
+        }
+        .boxed()
+    }
+
+    fn contar<'a>(&'a self, cine: u32, consulta: &'a ConsultaListado) -> BoxFuture<'a, ResultadoRepositorio<u64>> {
+        self.medir("contar", self.interno.contar(cine, consulta))
+    }
+
+    fn obtener(&self, cine: u32, id: u32) -> BoxFuture<'_, ResultadoRepositorio<Option<Entrada>>> {
+        self.medir("obtener", self.interno.obtener(cine, id))
+    }
+
+    fn crear<'a>(
+        &'a self,
+        cine: u32,
+        entrada: &'a CrearEntrada,
+        actor: &'a str,
+    ) -> BoxFuture<'a, ResultadoRepositorio<u32>> {
+        self.medir("crear", self.interno.crear(cine, entrada, actor))
+    }
+
+    fn crear_lote<'a>(
+        &'a self,
+        cine: u32,
+        entradas: &'a [CrearEntrada],
+        actor: &'a str,
+    ) -> BoxFuture<'a, ResultadoRepositorio<Vec<ResultadoRepositorio<u32>>>> {
+        self.medir("crear_lote", self.interno.crear_lote(cine, entradas, actor))
+    }
+
+    fn guardar_por_cedula<'a>(
+        &'a self,
+        cine: u32,
+        numero_cedula: &'a str,
+        datos: &'a GuardarEntrada,
+        actor: &'a str,
+    ) -> BoxFuture<'a, ResultadoRepositorio<EntradaGuardada>> {
+        self.medir("guardar_por_cedula", self.interno.guardar_por_cedula(cine, numero_cedula, datos, actor))
+    }
+
+    fn actualizar<'a>(
+        &'a self,
+        cine: u32,
+        id: u32,
+        datos: &'a ActualizarEntrada,
+        version: Option<u32>,
+        actor: &'a str,
+    ) -> BoxFuture<'a, ResultadoRepositorio<bool>> {
+        self.medir("actualizar", self.interno.actualizar(cine, id, datos, version, actor))
+    }
+
+    fn simular<'a>(
+        &'a self,
+        cine: u32,
+        escritura: Escritura<'a>,
+        actor: &'a str,
+    ) -> BoxFuture<'a, ResultadoRepositorio<Vec<ResultadoRepositorio<EntradaSimulada>>>> {
+        self.medir("simular", self.interno.simular(cine, escritura, actor))
+    }
+
+    fn eliminar<'a>(
+        &'a self,
+        cine: u32,
+        id: u32,
+        version: Option<u32>,
+        actor: &'a str,
+    ) -> BoxFuture<'a, ResultadoRepositorio<bool>> {
+        self.medir("eliminar", self.interno.eliminar(cine, id, version, actor))
+    }
+
+    fn cambiar_estado<'a>(
+        &'a self,
+        cine: u32,
+        id: u32,
+        estado: EstadoEntrada,
+        actor: &'a str,
+    ) -> BoxFuture<'a, ResultadoRepositorio<bool>> {
+        self.medir("cambiar_estado", self.interno.cambiar_estado(cine, id, estado, actor))
+    }
+
+    fn cancelar_vencidas<'a>(&'a self, antiguedad: Duration, actor: &'a str) -> BoxFuture<'a, ResultadoRepositorio<u64>> {
+        self.medir("cancelar_vencidas", self.interno.cancelar_vencidas(antiguedad, actor))
+    }
+
+    fn cancelar_funcion<'a>(
+        &'a self,
+        cine: u32,
+        funcion_id: u32,
+        motivo: &'a str,
+        actor: &'a str,
+    ) -> BoxFuture<'a, ResultadoRepositorio<Vec<u32>>> {
+        self.medir("cancelar_funcion", self.interno.cancelar_funcion(cine, funcion_id, motivo, actor))
+    }
+
+    fn eliminar_filtradas<'a>(
+        &'a self,
+        cine: u32,
+        filtro: &'a EliminarEntradas,
+        actor: &'a str,
+    ) -> BoxFuture<'a, ResultadoRepositorio<u64>> {
+        self.medir("eliminar_filtradas", self.interno.eliminar_filtradas(cine, filtro, actor))
+    }
+
+    fn historial(&self, cine: u32, id: u32) -> BoxFuture<'_, ResultadoRepositorio<Vec<RegistroAuditoria>>> {
+        self.medir("historial", self.interno.historial(cine, id))
+    }
+
+    fn agregar<'a>(
+        &'a self,
+        cine: u32,
+        parametros: &'a ParametrosAgregado,
+    ) -> BoxFuture<'a, ResultadoRepositorio<ResultadoAgregado>> {
+        self.medir("agregar", self.interno.agregar(cine, parametros))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::memoria::RepositorioMemoria;
+
+    #[actix_web::test]
+    async fn mide_cada_operacion_por_nombre() {
+        let repositorio = RepositorioMedido::new(Arc::new(RepositorioMemoria::default()), Duration::ZERO);
+        let cuenta = || METRICAS_CONSULTAS.duraciones.lock().unwrap().get("historial").map_or(0, Histograma::cuenta);
+        let antes = cuenta();
+        repositorio.historial(1, 7).await.unwrap();
+        assert_eq!(cuenta(), antes + 1);
+    }
+}
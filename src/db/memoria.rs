@@ -0,0 +1,483 @@
+//! Repositorio de entradas en memoria, para probar el servicio y los handlers sin base de
+//! datos o para levantar la API en local con [`crate::Server::builder`].
+//!
+//! No hay tablas de clientes ni de funciones: cada entrada se completa con un cliente y una
+//! función inventados a partir de sus IDs, la función en una sala de 100 asientos y a 6,50.
+//! El listado no filtra ni ordena y el agregado no está soportado. Cada entrada recuerda su
+//! cine, y cada cine tiene sus propios clientes.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveDateTime, TimeDelta};
+use futures_util::future::{BoxFuture, FutureExt};
+use futures_util::stream::{self, StreamExt};
+
+use super::repository::{
+    EntradaGuardada, EntradaRepository, EntradaSimulada, ErrorRepositorio, Escritura, FlujoEntradas, ResultadoRepositorio,
+};
+use crate::agregado::{ParametrosAgregado, ResultadoAgregado};
+use crate::listado::ConsultaListado;
+use crate::models::{
+    ActualizarEntrada, Cliente, CrearEntrada, EliminarEntradas, Entrada, EstadoEntrada, Funcion, GuardarEntrada,
+    RegistroAuditoria, Sala,
+};
+use crate::reloj::{Reloj, RelojSistema};
+
+/// Formato de las fechas de las entradas, el mismo con el que las lee MySQL.
+const FORMATO_FECHA: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Repositorio en memoria con las mismas restricciones que la base de datos.
+/// Las entradas y los registros de auditoría se guardan junto a su cine.
+pub struct RepositorioMemoria {
+    entradas: Mutex<BTreeMap<u32, (u32, Entrada)>>,
+    auditoria: Mutex<Vec<(u32, RegistroAuditoria)>>,
+    limite_por_cedula: Option<u32>,
+    reloj: Arc<dyn Reloj>,
+}
+
+impl Default for RepositorioMemoria {
+    fn default() -> Self {
+        RepositorioMemoria {
+            entradas: Mutex::default(),
+            auditoria: Mutex::default(),
+            limite_por_cedula: None,
+            reloj: Arc::new(RelojSistema),
+        }
+    }
+}
+
+impl RepositorioMemoria {
+    /// Limita las entradas que una cédula puede tener para cada función, como
+    /// [`RepositorioMysql::con_limite_por_cedula`](super::repository::RepositorioMysql::con_limite_por_cedula).
+    pub fn con_limite_por_cedula(mut self, limite: Option<u32>) -> Self {
+        self.limite_por_cedula = limite;
+        self
+    }
+
+    /// Fecha las altas y los cambios, y decide qué reservas vencieron, con `reloj`.
+    pub fn con_reloj(mut self, reloj: Arc<dyn Reloj>) -> Self {
+        self.reloj = reloj;
+        self
+    }
+
+    /// La hora del reloj con el formato de las fechas de las entradas.
+    fn ahora(&self) -> String {
+        self.reloj.ahora_naive().format(FORMATO_FECHA).to_string()
+    }
+
+    /// Comprueba, con las entradas ya bloqueadas, que el cliente pueda tener `cantidad`
+    /// entradas de la función sin contar la entrada `excepto` ni las canceladas.
+    fn verificar_limite(
+        &self,
+        entradas: &BTreeMap<u32, (u32, Entrada)>,
+        cine: u32,
+        (cliente_id, funcion_id): (u32, u32),
+        cantidad: u32,
+        excepto: Option<u32>,
+    ) -> ResultadoRepositorio<()> {
+        let Some(limite) = self.limite_por_cedula else {
+            return Ok(());
+        };
+        let vendidas: u32 = entradas
+            .values()
+            .filter(|(de, e)| {
+                *de == cine
+                    && e.cliente.id == cliente_id
+                    && e.funcion.id == funcion_id
+                    && e.estado != EstadoEntrada::Cancelada
+                    && e.id != excepto
+            })
+            .map(|(_, e)| e.cantidad_entradas)
+            .sum();
+        let restantes = limite.saturating_sub(vendidas);
+        if cantidad > restantes { Err(ErrorRepositorio::LimiteCedula(restantes)) } else { Ok(()) }
+    }
+
+    fn auditar(
+        &self,
+        cine: u32,
+        id: u32,
+        operacion: &str,
+        actor: &str,
+        anterior: Option<&Entrada>,
+        nuevo: Option<&Entrada>,
+    ) {
+        let mut auditoria = self.auditoria.lock().unwrap();
+        let registro = RegistroAuditoria {
+            id: auditoria.len() as u64 + 1,
+            entrada_id: id,
+            operacion: operacion.to_string(),
+            actor: actor.to_string(),
+            motivo: None,
+            valor_anterior: anterior.map(|entrada| serde_json::to_value(entrada).unwrap()),
+            valor_nuevo: nuevo.map(|entrada| serde_json::to_value(entrada).unwrap()),
+            realizada_en: self.ahora(),
+        };
+        auditoria.push((cine, registro));
+    }
+}
+
+/// El cliente con el que el repositorio en memoria completa las entradas.
+fn cliente(id: u32) -> Cliente {
+    Cliente {
+        id,
+        numero_cedula: format!("{:010}", id),
+        nombre: "Ana".to_string(),
+        email: None,
+    }
+}
+
+/// La función con la que el repositorio en memoria completa las entradas.
+fn funcion(id: u32) -> Funcion {
+    Funcion {
+        id,
+        titulo: "Dune".to_string(),
+        horario: DateTime::parse_from_rfc3339("2024-03-01T19:00:00Z").unwrap(),
+        precio: 6.5,
+        sala: Sala { id: 1, nombre: "Sala 1".to_string(), capacidad: 100 },
+        poster: None,
+    }
+}
+
+impl EntradaRepository for RepositorioMemoria {
+    fn listar<'a>(&'a self, cine: u32, consulta: &'a ConsultaListado) -> BoxFuture<'a, ResultadoRepositorio<FlujoEntradas>> {
+        let desde = ((consulta.pagina - 1) * consulta.por_pagina) as usize;
+        let entradas: Vec<_> = self
+            .entradas
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|(de, _)| *de == cine)
+            .skip(desde)
+            .take(consulta.por_pagina as usize)
+            .map(|(_, entrada)| Ok(entrada.clone()))
+            .collect();
+        async move { Ok(stream::iter(entradas).boxed()) }.boxed()
+    }
+
+    fn contar<'a>(&'a self, cine: u32, _: &'a ConsultaListado) -> BoxFuture<'a, ResultadoRepositorio<u64>> {
+        let total = self.entradas.lock().unwrap().values().filter(|(de, _)| *de == cine).count() as u64;
+        async move { Ok(total) }.boxed()
+    }
+
+    fn obtener(&self, cine: u32, id: u32) -> BoxFuture<'_, ResultadoRepositorio<Option<Entrada>>> {
+        let entrada = self
+            .entradas
+            .lock()
+            .unwrap()
+            .get(&id)
+            .filter(|(de, _)| *de == cine)
+            .map(|(_, entrada)| entrada.clone());
+        async move { Ok(entrada) }.boxed()
+    }
+
+    fn crear<'a>(
+        &'a self,
+        cine: u32,
+        entrada: &'a CrearEntrada,
+        actor: &'a str,
+    ) -> BoxFuture<'a, ResultadoRepositorio<u32>> {
+        async move {
+            let mut entradas = self.entradas.lock().unwrap();
+            let compra = (entrada.cliente_id, entrada.funcion_id);
+            self.verificar_limite(&entradas, cine, compra, entrada.cantidad_entradas, None)?;
+            let id = entradas.keys().last().map_or(1, |id| id + 1);
+            let nueva = Entrada {
+                id: Some(id),
+                cantidad_entradas: entrada.cantidad_entradas,
+                total: funcion(entrada.funcion_id).precio * f64::from(entrada.cantidad_entradas),
+                estado: EstadoEntrada::Reservada,
+                cliente: cliente(entrada.cliente_id),
+                funcion: funcion(entrada.funcion_id),
+                version: 1,
+                created_at: self.ahora(),
+                updated_at: self.ahora(),
+            };
+            self.auditar(cine, id, "crear", actor, None, Some(&nueva));
+            entradas.insert(id, (cine, nueva));
+            Ok(id)
+        }
+        .boxed()
+    }
+
+    fn crear_lote<'a>(
+        &'a self,
+        cine: u32,
+        entradas: &'a [CrearEntrada],
+        actor: &'a str,
+    ) -> BoxFuture<'a, ResultadoRepositorio<Vec<ResultadoRepositorio<u32>>>> {
+        async move {
+            let copia = self.entradas.lock().unwrap().clone();
+            let registros = self.auditoria.lock().unwrap().len();
+            let mut resultados = Vec::new();
+            for entrada in entradas {
+                resultados.push(self.crear(cine, entrada, actor).await);
+            }
+            if resultados.iter().any(Result::is_err) {
+                *self.entradas.lock().unwrap() = copia;
+                self.auditoria.lock().unwrap().truncate(registros);
+            }
+            Ok(resultados)
+        }
+        .boxed()
+    }
+
+    fn guardar_por_cedula<'a>(
+        &'a self,
+        cine: u32,
+        numero_cedula: &'a str,
+        datos: &'a GuardarEntrada,
+        actor: &'a str,
+    ) -> BoxFuture<'a, ResultadoRepositorio<EntradaGuardada>> {
+        async move {
+            let cliente_id: u32 = numero_cedula.parse().map_err(|_| ErrorRepositorio::ClienteInexistente)?;
+            let actual = self
+                .entradas
+                .lock()
+                .unwrap()
+                .values()
+                .rev()
+                .find(|(de, e)| *de == cine && e.cliente.id == cliente_id && e.funcion.id == datos.funcion_id)
+                .and_then(|(_, e)| e.id);
+            match actual {
+                None => {
+                    let entrada = CrearEntrada {
+                        cliente_id,
+                        funcion_id: datos.funcion_id,
+                        cantidad_entradas: datos.cantidad_entradas,
+                        asientos: Vec::new(),
+                    };
+                    Ok(EntradaGuardada::Creada(self.crear(cine, &entrada, actor).await?))
+                }
+                Some(id) => {
+                    let cambios = ActualizarEntrada {
+                        funcion_id: Some(datos.funcion_id),
+                        cantidad_entradas: Some(datos.cantidad_entradas),
+                        ..Default::default()
+                    };
+                    self.actualizar(cine, id, &cambios, None, actor).await?;
+                    Ok(EntradaGuardada::Actualizada(id))
+                }
+            }
+        }
+        .boxed()
+    }
+
+    fn actualizar<'a>(
+        &'a self,
+        cine: u32,
+        id: u32,
+        datos: &'a ActualizarEntrada,
+        version: Option<u32>,
+        actor: &'a str,
+    ) -> BoxFuture<'a, ResultadoRepositorio<bool>> {
+        async move {
+            let mut entradas = self.entradas.lock().unwrap();
+            let Some((_, entrada)) = entradas.get(&id).filter(|(de, _)| *de == cine) else {
+                return Ok(false);
+            };
+            if version.is_some_and(|version| version != entrada.version) {
+                return Err(ErrorRepositorio::VersionDistinta(entrada.version));
+            }
+            let compra = (
+                datos.cliente_id.unwrap_or(entrada.cliente.id),
+                datos.funcion_id.unwrap_or(entrada.funcion.id),
+            );
+            let cantidad = datos.cantidad_entradas.unwrap_or(entrada.cantidad_entradas);
+            self.verificar_limite(&entradas, cine, compra, cantidad, Some(id))?;
+            let Some((_, entrada)) = entradas.get_mut(&id) else {
+                return Ok(false);
+            };
+            let anterior = entrada.clone();
+            entrada.version += 1;
+            entrada.updated_at = self.ahora();
+            if let Some(cantidad) = datos.cantidad_entradas {
+                entrada.cantidad_entradas = cantidad;
+            }
+            if let Some(cliente_id) = datos.cliente_id {
+                entrada.cliente = cliente(cliente_id);
+            }
+            if let Some(funcion_id) = datos.funcion_id {
+                entrada.funcion = funcion(funcion_id);
+            }
+            entrada.total = entrada.funcion.precio * f64::from(entrada.cantidad_entradas);
+            self.auditar(cine, id, "actualizar", actor, Some(&anterior), Some(entrada));
+            Ok(true)
+        }
+        .boxed()
+    }
+
+    fn simular<'a>(
+        &'a self,
+        cine: u32,
+        escritura: Escritura<'a>,
+        actor: &'a str,
+    ) -> BoxFuture<'a, ResultadoRepositorio<Vec<ResultadoRepositorio<EntradaSimulada>>>> {
+        async move {
+            let copia = self.entradas.lock().unwrap().clone();
+            let registros = self.auditoria.lock().unwrap().len();
+            let releer = |id: u32, variante: fn(Entrada) -> EntradaSimulada| {
+                let entrada = self.entradas.lock().unwrap().get(&id).map(|(_, entrada)| entrada.clone());
+                entrada.map_or(EntradaSimulada::SinCambios, variante)
+            };
+            let mut resultados = Vec::new();
+            match escritura {
+                Escritura::Crear(entradas) => {
+                    for entrada in entradas {
+                        let creada = self.crear(cine, entrada, actor).await;
+                        resultados.push(creada.map(|id| releer(id, EntradaSimulada::Creada)));
+                    }
+                }
+                Escritura::GuardarPorCedula(numero_cedula, datos) => {
+                    resultados.push(self.guardar_por_cedula(cine, numero_cedula, datos, actor).await.map(
+                        |guardada| match guardada {
+                            EntradaGuardada::Creada(id) => releer(id, EntradaSimulada::Creada),
+                            EntradaGuardada::Actualizada(id) => releer(id, EntradaSimulada::Actualizada),
+                        },
+                    ));
+                }
+                Escritura::Actualizar(id, datos, version) => {
+                    resultados.push(self.actualizar(cine, id, datos, version, actor).await.map(|actualizada| {
+                        if actualizada { releer(id, EntradaSimulada::Actualizada) } else { EntradaSimulada::SinCambios }
+                    }));
+                }
+            }
+            *self.entradas.lock().unwrap() = copia;
+            self.auditoria.lock().unwrap().truncate(registros);
+            Ok(resultados)
+        }
+        .boxed()
+    }
+
+    fn eliminar<'a>(
+        &'a self,
+        cine: u32,
+        id: u32,
+        version: Option<u32>,
+        actor: &'a str,
+    ) -> BoxFuture<'a, ResultadoRepositorio<bool>> {
+        let mut entradas = self.entradas.lock().unwrap();
+        let resultado = match entradas.get(&id).filter(|(de, _)| *de == cine) {
+            None => Ok(false),
+            Some((_, entrada)) if version.is_some_and(|version| version != entrada.version) => {
+                Err(ErrorRepositorio::VersionDistinta(entrada.version))
+            }
+            Some(_) => {
+                let anterior = entradas.remove(&id).map(|(_, entrada)| entrada);
+                self.auditar(cine, id, "eliminar", actor, anterior.as_ref(), None);
+                Ok(true)
+            }
+        };
+        async move { resultado }.boxed()
+    }
+
+    fn cambiar_estado<'a>(
+        &'a self,
+        cine: u32,
+        id: u32,
+        estado: EstadoEntrada,
+        actor: &'a str,
+    ) -> BoxFuture<'a, ResultadoRepositorio<bool>> {
+        let mut entradas = self.entradas.lock().unwrap();
+        let resultado = match entradas.get_mut(&id).filter(|(de, _)| *de == cine) {
+            None => Ok(false),
+            Some((_, entrada)) if !entrada.estado.puede_pasar_a(estado) => {
+                Err(ErrorRepositorio::TransicionInvalida(entrada.estado))
+            }
+            Some((_, entrada)) => {
+                let anterior = entrada.clone();
+                entrada.estado = estado;
+                entrada.version += 1;
+                entrada.updated_at = self.ahora();
+                self.auditar(cine, id, "actualizar", actor, Some(&anterior), Some(entrada));
+                Ok(true)
+            }
+        };
+        async move { resultado }.boxed()
+    }
+
+    fn cancelar_vencidas<'a>(&'a self, antiguedad: Duration, actor: &'a str) -> BoxFuture<'a, ResultadoRepositorio<u64>> {
+        let antes_de = TimeDelta::from_std(antiguedad)
+            .ok()
+            .and_then(|antiguedad| self.reloj.ahora_naive().checked_sub_signed(antiguedad))
+            .unwrap_or(NaiveDateTime::MIN);
+        let vencida = |entrada: &Entrada| {
+            NaiveDateTime::parse_from_str(&entrada.created_at, FORMATO_FECHA).is_ok_and(|alta| alta < antes_de)
+        };
+        let mut canceladas = 0;
+        for (id, (cine, entrada)) in self.entradas.lock().unwrap().iter_mut() {
+            if entrada.estado == EstadoEntrada::Reservada && vencida(entrada) {
+                let anterior = entrada.clone();
+                entrada.estado = EstadoEntrada::Cancelada;
+                entrada.version += 1;
+                entrada.updated_at = self.ahora();
+                self.auditar(*cine, *id, "actualizar", actor, Some(&anterior), Some(entrada));
+                canceladas += 1;
+            }
+        }
+        async move { Ok(canceladas) }.boxed()
+    }
+
+    fn cancelar_funcion<'a>(
+        &'a self,
+        cine: u32,
+        funcion_id: u32,
+        motivo: &'a str,
+        actor: &'a str,
+    ) -> BoxFuture<'a, ResultadoRepositorio<Vec<u32>>> {
+        let mut canceladas = Vec::new();
+        for (id, (de, entrada)) in self.entradas.lock().unwrap().iter_mut() {
+            if *de == cine && entrada.funcion.id == funcion_id && entrada.estado.puede_pasar_a(EstadoEntrada::Cancelada) {
+                let anterior = entrada.clone();
+                entrada.estado = EstadoEntrada::Cancelada;
+                entrada.version += 1;
+                entrada.updated_at = self.ahora();
+                self.auditar(cine, *id, "actualizar", actor, Some(&anterior), Some(entrada));
+                if let Some((_, registro)) = self.auditoria.lock().unwrap().last_mut() {
+                    registro.motivo = Some(motivo.to_string());
+                }
+                canceladas.push(*id);
+            }
+        }
+        async move { Ok(canceladas) }.boxed()
+    }
+
+    fn eliminar_filtradas<'a>(
+        &'a self,
+        cine: u32,
+        filtro: &'a EliminarEntradas,
+        actor: &'a str,
+    ) -> BoxFuture<'a, ResultadoRepositorio<u64>> {
+        let mut entradas = self.entradas.lock().unwrap();
+        let antes = entradas.len();
+        entradas.retain(|id, (de, entrada)| {
+            let de_la_funcion = filtro.funcion_id.is_none_or(|funcion_id| entrada.funcion.id == funcion_id);
+            let de_la_lista = filtro.ids.as_ref().is_none_or(|ids| ids.contains(id));
+            let eliminar = *de == cine && de_la_funcion && de_la_lista;
+            if eliminar {
+                self.auditar(cine, *id, "eliminar", actor, Some(entrada), None);
+            }
+            !eliminar
+        });
+        let eliminadas = (antes - entradas.len()) as u64;
+        async move { Ok(eliminadas) }.boxed()
+    }
+
+    fn historial(&self, cine: u32, id: u32) -> BoxFuture<'_, ResultadoRepositorio<Vec<RegistroAuditoria>>> {
+        let registros = self
+            .auditoria
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(de, registro)| *de == cine && registro.entrada_id == id)
+            .map(|(_, registro)| registro.clone())
+            .collect();
+        async move { Ok(registros) }.boxed()
+    }
+
+    fn agregar<'a>(&'a self, _: u32, _: &'a ParametrosAgregado) -> BoxFuture<'a, ResultadoRepositorio<ResultadoAgregado>> {
+        async move { Err(ErrorRepositorio::ParametrosInvalidos("sin soporte".to_string())) }.boxed()
+    }
+}
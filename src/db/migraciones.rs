@@ -0,0 +1,126 @@
+//! Migraciones del esquema, embebidas en el binario.
+//!
+//! Cada archivo de `migraciones/` se aplica una sola vez, en orden de versión, y queda
+//! registrado en la tabla `schema_version`. Para cambiar el esquema se añade un archivo
+//! nuevo a [`MIGRACIONES`]; los ya publicados no se modifican.
+
+use mysql_async::{prelude::*, Pool};
+
+use super::conectar;
+
+/// Una migración: su versión, un nombre descriptivo y las sentencias separadas por `;`.
+#[derive(Debug, Clone, Copy)]
+pub struct Migracion {
+    pub version: u32,
+    pub nombre: &'static str,
+    pub sql: &'static str,
+}
+
+macro_rules! migracion {
+    ($version:literal, $nombre:literal) => {
+        Migracion {
+            version: $version,
+            nombre: $nombre,
+            sql: include_str!(concat!("../../migraciones/", $nombre, ".sql")),
+        }
+    };
+}
+
+/// Todas las migraciones, en orden.
+pub const MIGRACIONES: &[Migracion] = &[
+    migracion!(1, "0001_entradas"),
+    migracion!(2, "0002_dispositivos"),
+    migracion!(3, "0003_usuarios"),
+    migracion!(4, "0004_api_keys"),
+    migracion!(5, "0005_funciones"),
+    migracion!(6, "0006_clientes"),
+    migracion!(7, "0007_salas"),
+    migracion!(8, "0008_asientos"),
+    migracion!(9, "0009_idempotencia"),
+    migracion!(10, "0010_version_entradas"),
+    migracion!(11, "0011_auditoria"),
+    migracion!(12, "0012_fechas_entradas"),
+    migracion!(13, "0013_horario_funciones"),
+    migracion!(14, "0014_total_entradas"),
+    migracion!(15, "0015_estado_entradas"),
+    migracion!(16, "0016_webhooks"),
+    migracion!(17, "0017_eventos"),
+    migracion!(18, "0018_correos"),
+    migracion!(19, "0019_cines"),
+    migracion!(20, "0020_posters"),
+    migracion!(21, "0021_motivo_auditoria"),
+    migracion!(22, "0022_accesos"),
+    migracion!(23, "0023_entradas_archivo"),
+    migracion!(24, "0024_varias_entradas_por_cliente"),
+];
+
+/// Segundos que una instancia espera el bloqueo mientras otra migra.
+const ESPERA_BLOQUEO: u32 = 60;
+
+impl Migracion {
+    /// Sentencias de la migración, sin las vacías.
+    fn sentencias(&self) -> impl Iterator<Item = &'static str> {
+        self.sql.split(';').map(str::trim).filter(|s| !s.is_empty())
+    }
+}
+
+/// Aplica las migraciones pendientes y devuelve las que se aplicaron. Un bloqueo con
+/// nombre evita que varias instancias que arrancan a la vez migren en paralelo.
+pub async fn migrar(pool: &Pool) -> Result<Vec<Migracion>, mysql_async::Error> {
+    let mut conn = conectar(pool).await?;
+    conn.query_drop(
+        "CREATE TABLE IF NOT EXISTS schema_version (
+            version INT PRIMARY KEY,
+            nombre VARCHAR(255) NOT NULL,
+            aplicada_en TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )",
+    )
+    .await?;
+
+    let bloqueado: Option<i64> = conn
+        .exec_first(
+            "SELECT GET_LOCK('rust_crud_migraciones', :espera)",
+            params! { "espera" => ESPERA_BLOQUEO },
+        )
+        .await?;
+    if bloqueado != Some(1) {
+        return Err(mysql_async::Error::Other(
+            "Otra instancia está aplicando las migraciones y no terminó a tiempo".into(),
+        ));
+    }
+
+    let resultado = async {
+        let aplicadas: Vec<u32> = conn.query("SELECT version FROM schema_version").await?;
+        let mut nuevas = Vec::new();
+        for migracion in MIGRACIONES.iter().filter(|m| !aplicadas.contains(&m.version)) {
+            for sentencia in migracion.sentencias() {
+                conn.query_drop(sentencia).await?;
+            }
+            conn.exec_drop(
+                "INSERT INTO schema_version (version, nombre) VALUES (:version, :nombre)",
+                params! { "version" => migracion.version, "nombre" => migracion.nombre },
+            )
+            .await?;
+            nuevas.push(*migracion);
+        }
+        Ok(nuevas)
+    }
+    .await;
+
+    conn.query_drop("DO RELEASE_LOCK('rust_crud_migraciones')").await?;
+    resultado
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn las_versiones_son_consecutivas_y_coinciden_con_el_archivo() {
+        for (indice, migracion) in MIGRACIONES.iter().enumerate() {
+            assert_eq!(migracion.version as usize, indice + 1);
+            assert!(migracion.nombre.starts_with(&format!("{:04}_", migracion.version)));
+            assert!(migracion.sentencias().count() > 0);
+        }
+    }
+}
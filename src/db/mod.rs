@@ -0,0 +1,331 @@
+//! Acceso a la base de datos.
+
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use actix_web::rt::time::{sleep, timeout};
+
+use futures_util::future::try_join_all;
+use mysql_async::prelude::*;
+use mysql_async::{Conn, Opts, OptsBuilder, Pool, PoolConstraints, PoolOpts};
+
+use crate::config::Config;
+use crate::metricas::Histograma;
+
+pub mod errores;
+pub mod eventos;
+pub mod medicion;
+pub mod memoria;
+pub mod migraciones;
+pub mod reintentos;
+pub mod replica;
+pub mod repository;
+
+/// Columnas que mapea [`crate::models::Entrada`], con las de su cliente, su función y la
+/// sala de esta, compartidas por las consultas de lectura.
+macro_rules! columnas_entrada {
+    () => {
+        "e.id, e.cantidad_entradas, c.id, c.numero_cedula, c.nombre, \
+         f.id, f.titulo, f.horario, f.precio, s.id, s.nombre, s.capacidad, e.version, \
+         DATE_FORMAT(e.created_at, '%Y-%m-%d %H:%i:%s'), DATE_FORMAT(e.updated_at, '%Y-%m-%d %H:%i:%s'), e.total, e.estado"
+    };
+}
+
+/// Tablas de las lecturas de entradas: `e` es la entrada, `c` su cliente, `f` su función
+/// y `s` la sala de la función.
+macro_rules! tablas_entrada {
+    () => {
+        "entradas e JOIN clientes c ON c.id = e.cliente_id JOIN funciones f ON f.id = e.funcion_id \
+         JOIN salas s ON s.id = f.sala_id"
+    };
+}
+
+/// Cada una de las columnas de [`SELECT_ENTRADAS`], en su orden, con un valor fijo del
+/// mismo tipo que la sustituye cuando el cliente no pide ese dato (ver [`crate::campos`]).
+pub const COLUMNAS_ENTRADA: [(&str, &str); 17] = [
+    ("e.id", "0"),
+    ("e.cantidad_entradas", "0"),
+    ("c.id", "0"),
+    ("c.numero_cedula", "''"),
+    ("c.nombre", "''"),
+    ("f.id", "0"),
+    ("f.titulo", "''"),
+    ("f.horario", "CAST('1970-01-01' AS DATETIME)"),
+    ("f.precio", "0.00"),
+    ("s.id", "0"),
+    ("s.nombre", "''"),
+    ("s.capacidad", "0"),
+    ("e.version", "0"),
+    ("DATE_FORMAT(e.created_at, '%Y-%m-%d %H:%i:%s')", "''"),
+    ("DATE_FORMAT(e.updated_at, '%Y-%m-%d %H:%i:%s')", "''"),
+    ("e.total", "0.00"),
+    ("e.estado", "'reservada'"),
+];
+
+/// Tablas de las lecturas de entradas, para las consultas que arman sus propias columnas.
+pub const TABLAS_ENTRADAS: &str = tablas_entrada!();
+
+/// Listado completo de entradas.
+pub const SELECT_ENTRADAS: &str = concat!("SELECT ", columnas_entrada!(), " FROM ", tablas_entrada!());
+
+/// Entrada por su ID, con los parámetros `:id` y `:cine_id`.
+pub const SELECT_ENTRADA_POR_ID: &str = concat!(
+    "SELECT ",
+    columnas_entrada!(),
+    " FROM ",
+    tablas_entrada!(),
+    " WHERE e.id = :id AND e.cine_id = :cine_id"
+);
+
+/// Alta de una entrada con sus parámetros nombrados, fechada en `:ahora` y con su total al
+/// precio actual de la función. No inserta nada si la función o el cliente no son de `:cine_id`.
+pub const INSERT_ENTRADA: &str = "INSERT INTO entradas (cine_id, cliente_id, funcion_id, cantidad_entradas, total, created_at, updated_at) \
+                                  SELECT s.cine_id, c.id, f.id, :cantidad_entradas, f.precio * :cantidad_entradas, :ahora, :ahora \
+                                  FROM funciones f JOIN salas s ON s.id = f.sala_id \
+                                  JOIN clientes c ON c.id = :cliente_id AND c.cine_id = s.cine_id \
+                                  WHERE f.id = :funcion_id AND s.cine_id = :cine_id";
+
+/// Actualización parcial de una entrada con el parámetro `:id`: cada columna cuyo
+/// parámetro es NULL conserva su valor (ver [`crate::actualizacion`]).
+pub const UPDATE_PARCIAL: &str = "UPDATE entradas SET cliente_id = COALESCE(:cliente_id, cliente_id), \
+                                  funcion_id = COALESCE(:funcion_id, funcion_id), \
+                                  cantidad_entradas = COALESCE(:cantidad_entradas, cantidad_entradas) \
+                                  WHERE id = :id";
+
+/// Baja de una entrada por su ID.
+pub const DELETE_ENTRADA: &str = "DELETE FROM entradas WHERE id = :id";
+
+/// Sentencias que se preparan en cada conexión al precalentar la pool.
+const SENTENCIAS_FRECUENTES: &[&str] = &[SELECT_ENTRADA_POR_ID, INSERT_ENTRADA, UPDATE_PARCIAL, DELETE_ENTRADA];
+
+/// Primera espera entre intentos de [`esperar_base_datos`]. Se duplica en cada fallo.
+const ESPERA_INICIAL: Duration = Duration::from_millis(250);
+
+/// Espera máxima entre dos intentos, y también lo que puede tardar cada intento.
+const ESPERA_TOPE: Duration = Duration::from_secs(5);
+
+/// Comprueba que `DATABASE_URL` sea de MySQL, el único motor implementado.
+fn exigir_mysql(url: &str) -> Result<(), String> {
+    match url.split_once("://") {
+        Some((esquema, _)) if esquema.eq_ignore_ascii_case("mysql") => Ok(()),
+        _ => Err("DATABASE_URL debe empezar por mysql://; por ahora sólo se admite MySQL".to_string()),
+    }
+}
+
+/// Función para obtener la pool de conexiones a la base de datos.
+///
+/// La pool abre las conexiones a medida que se piden y descarta las que fallan, así que
+/// si la base de datos se cae con el servidor en marcha las peticiones fallan mientras
+/// tanto y se recuperan solas al volver, sin reiniciar el proceso.
+///
+/// Cada conexión trabaja en UTC (`time_zone = '+00:00'`), también tras devolverse a la pool,
+/// así que `NOW()` y las fechas que se leen y escriben no dependen de la zona del servidor.
+///
+/// Por ahora sólo hay implementación para MySQL: además del repositorio de entradas, la
+/// autenticación, los catálogos y las migraciones usan su SQL. Otros motores se rechazan
+/// aquí con un mensaje claro en lugar de un error de conexión.
+pub fn obtener_pool_db(config: &Config) -> Result<Pool, Box<dyn std::error::Error>> {
+    abrir_pool(&config.database_url, config)
+}
+
+/// Pool de la réplica de `DATABASE_URL_RO`, si hay, con los mismos límites que la primaria
+/// (ver [`replica`]).
+pub fn obtener_pool_replica(config: &Config) -> Result<Option<Pool>, Box<dyn std::error::Error>> {
+    config.database_url_ro.as_deref().map(|url| abrir_pool(url, config)).transpose()
+}
+
+fn abrir_pool(url: &str, config: &Config) -> Result<Pool, Box<dyn std::error::Error>> {
+    exigir_mysql(url)?;
+    let constraints = PoolConstraints::new(config.pool_min, config.pool_max)
+        .ok_or("Límites de la pool inválidos")?;
+    let pool_opts = PoolOpts::default()
+        .with_constraints(constraints)
+        .with_inactive_connection_ttl(config.pool_ttl_inactiva)
+        .with_abs_conn_ttl(config.pool_ttl);
+    let opts = OptsBuilder::from_opts(Opts::from_url(url)?)
+        .pool_opts(pool_opts)
+        .setup(vec!["SET time_zone = '+00:00'"]);
+    ESPERA_MAXIMA_MS.store(config.pool_espera_maxima.as_millis() as u64, Ordering::Relaxed);
+    Ok(Pool::new(opts))
+}
+
+/// Uso de la pool. `mysql_async` no expone sus contadores, así que se mide en [`conectar`];
+/// es global porque el proceso sólo abre una pool.
+pub struct MetricasPool {
+    pub en_uso: AtomicU64,
+    /// Peticiones esperando ahora mismo a que se libere una conexión.
+    pub esperando: AtomicU64,
+    /// Peticiones que se rindieron tras esperar [`Config::pool_espera_maxima`].
+    pub agotadas: AtomicU64,
+    pub espera: Mutex<Histograma>,
+}
+
+pub static METRICAS_POOL: MetricasPool = MetricasPool {
+    en_uso: AtomicU64::new(0),
+    esperando: AtomicU64::new(0),
+    agotadas: AtomicU64::new(0),
+    espera: Mutex::new(Histograma::new()),
+};
+
+/// [`Config::pool_espera_maxima`] en milisegundos, que fija [`obtener_pool_db`] para
+/// [`conectar`]. Cero no limita.
+static ESPERA_MAXIMA_MS: AtomicU64 = AtomicU64::new(0);
+
+/// Descuenta de [`MetricasPool::esperando`] al terminar la espera, aunque se cancele.
+struct Esperando;
+
+impl Esperando {
+    fn new() -> Self {
+        METRICAS_POOL.esperando.fetch_add(1, Ordering::Relaxed);
+        Esperando
+    }
+}
+
+impl Drop for Esperando {
+    fn drop(&mut self) {
+        METRICAS_POOL.esperando.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Conexión prestada por la pool. Se usa como un `Conn` y vuelve a la pool al soltarse.
+pub struct Conexion(Conn);
+
+impl Deref for Conexion {
+    type Target = Conn;
+
+    fn deref(&self) -> &Conn {
+        &self.0
+    }
+}
+
+impl DerefMut for Conexion {
+    fn deref_mut(&mut self) -> &mut Conn {
+        &mut self.0
+    }
+}
+
+impl Drop for Conexion {
+    fn drop(&mut self) {
+        METRICAS_POOL.en_uso.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Pide una conexión a la pool registrando cuánto se esperó y cuántas hay en uso. Falla si
+/// no se libera ninguna en [`Config::pool_espera_maxima`].
+///
+/// En las peticiones de lectura, si hay réplica, la conexión sale de ella en lugar de `pool`
+/// (ver [`replica`]).
+pub async fn conectar(pool: &Pool) -> Result<Conexion, mysql_async::Error> {
+    if let Some(replica) = replica::en_curso() {
+        match conectar_primaria(replica.pool()).await {
+            Ok(conn) => return Ok(conn),
+            Err(e) => replica.marcar_caida(&e),
+        }
+    }
+    conectar_primaria(pool).await
+}
+
+/// Como [`conectar`], pero siempre de `pool`, aunque la petición sea de lectura.
+pub async fn conectar_primaria(pool: &Pool) -> Result<Conexion, mysql_async::Error> {
+    let inicio = Instant::now();
+    let esperando = Esperando::new();
+    let espera_maxima = ESPERA_MAXIMA_MS.load(Ordering::Relaxed);
+    let conn = if espera_maxima == 0 {
+        pool.get_conn().await
+    } else {
+        match timeout(Duration::from_millis(espera_maxima), pool.get_conn()).await {
+            Ok(conn) => conn,
+            Err(_) => {
+                METRICAS_POOL.agotadas.fetch_add(1, Ordering::Relaxed);
+                Err(mysql_async::Error::Other(
+                    format!("La pool no prestó ninguna conexión en {} ms", espera_maxima).into(),
+                ))
+            }
+        }
+    };
+    drop(esperando);
+    METRICAS_POOL.espera.lock().unwrap().observar(inicio.elapsed());
+    let conn = conn?;
+    METRICAS_POOL.en_uso.fetch_add(1, Ordering::Relaxed);
+    Ok(Conexion(conn))
+}
+
+/// Abre `conexiones` conexiones a la vez y prepara en cada una las sentencias frecuentes,
+/// que quedan en la caché de sentencias de la conexión. Al terminar, las conexiones vuelven
+/// a la pool y quedan disponibles para las primeras peticiones.
+pub async fn precalentar_pool(pool: &Pool, conexiones: usize) -> Result<(), mysql_async::Error> {
+    let abiertas = try_join_all((0..conexiones).map(|_| async {
+        let mut conn = conectar(pool).await?;
+        for sentencia in SENTENCIAS_FRECUENTES {
+            conn.prep(*sentencia).await?;
+        }
+        Ok::<_, mysql_async::Error>(conn)
+    }))
+    .await?;
+    drop(abiertas);
+    Ok(())
+}
+
+/// Espera antes del reintento número `intento`, empezando en 0.
+fn espera_reintento(intento: u32) -> Duration {
+    ESPERA_INICIAL.saturating_mul(2u32.saturating_pow(intento)).min(ESPERA_TOPE)
+}
+
+/// Espera a que la base de datos acepte conexiones, reintentando con espera exponencial
+/// hasta agotar `limite`. Sirve para arrancar antes que MySQL, como pasa con docker-compose.
+/// Con `limite` cero se hace un solo intento.
+pub async fn esperar_base_datos(pool: &Pool, limite: Duration) -> Result<(), mysql_async::Error> {
+    let inicio = Instant::now();
+    let mut intento = 0;
+    loop {
+        let ping = timeout(ESPERA_TOPE, async { conectar(pool).await?.ping().await }).await;
+        let error = match ping {
+            Ok(Ok(())) => return Ok(()),
+            Ok(Err(e)) => e,
+            Err(_) => mysql_async::Error::Other(
+                format!("La base de datos no respondió en {} s", ESPERA_TOPE.as_secs()).into(),
+            ),
+        };
+        let espera = espera_reintento(intento);
+        if inicio.elapsed() + espera > limite {
+            return Err(error);
+        }
+        log::warn!(
+            "La base de datos no está disponible ({}), se reintenta en {} ms",
+            error,
+            espera.as_millis()
+        );
+        sleep(espera).await;
+        intento += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn las_columnas_de_entrada_coinciden_con_el_select() {
+        let columnas: Vec<&str> = COLUMNAS_ENTRADA.iter().map(|(columna, _)| *columna).collect();
+        assert_eq!(columnas.join(", "), columnas_entrada!());
+    }
+
+    #[test]
+    fn solo_acepta_urls_de_mysql() {
+        assert_eq!(exigir_mysql("MySQL://root@localhost/crud"), Ok(()));
+        assert!(exigir_mysql("localhost/crud").is_err());
+
+        let config = Config::new("postgres://localhost/crud");
+        let error = obtener_pool_db(&config).unwrap_err().to_string();
+        assert_eq!(error, "DATABASE_URL debe empezar por mysql://; por ahora sólo se admite MySQL");
+    }
+
+    #[test]
+    fn la_espera_se_duplica_hasta_el_tope() {
+        let esperas: Vec<_> = (0..7).map(|i| espera_reintento(i).as_millis()).collect();
+        assert_eq!(esperas, [250, 500, 1000, 2000, 4000, 5000, 5000]);
+        assert_eq!(espera_reintento(u32::MAX), ESPERA_TOPE);
+    }
+}
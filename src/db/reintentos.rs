@@ -0,0 +1,168 @@
+//! Reintentos de las operaciones de [`RepositorioMysql`](super::repository::RepositorioMysql)
+//! ante errores transitorios de MySQL: interbloqueos, esperas de bloqueo agotadas y
+//! conexiones caídas.
+//!
+//! Cada operación va entera en su transacción, así que repetirla tras un bloqueo es seguro:
+//! MySQL ya la deshizo. Una conexión que se corta a mitad de una escritura, en cambio, puede
+//! haberla guardado, así que las escrituras sólo se repiten si la conexión falló al pedirla.
+
+use std::collections::hash_map::RandomState;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use actix_web::rt::time::sleep;
+
+use super::errores::{self, FalloMysql};
+use super::repository::{ErrorRepositorio, ResultadoRepositorio};
+
+/// Espera máxima entre dos intentos, por larga que sea la serie.
+const ESPERA_TOPE: Duration = Duration::from_secs(1);
+
+/// Cuántas veces y con qué espera se repite una operación que falló por un error transitorio.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PoliticaReintentos {
+    /// Reintentos tras el primer intento. Con cero no se reintenta.
+    pub intentos: u32,
+    /// Espera antes del primer reintento. Se duplica en cada uno, con algo de azar para que
+    /// las peticiones que chocaron no vuelvan a chocar.
+    pub espera: Duration,
+}
+
+/// Reintentos desde el arranque por motivo, y operaciones que fallaron tras agotarlos,
+/// para `/metrics`.
+pub struct MetricasReintentos {
+    pub bloqueo: AtomicU64,
+    pub conexion: AtomicU64,
+    pub agotados: AtomicU64,
+}
+
+pub static METRICAS_REINTENTOS: MetricasReintentos = MetricasReintentos {
+    bloqueo: AtomicU64::new(0),
+    conexion: AtomicU64::new(0),
+    agotados: AtomicU64::new(0),
+};
+
+/// Número entre 0 y 1 para repartir las esperas. No hace falta que sea bueno.
+fn azar() -> f64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(0);
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+impl PoliticaReintentos {
+    /// Espera antes del reintento número `intento`, empezando en 0: entre la mitad y el
+    /// total de la espera exponencial, que nunca pasa de [`ESPERA_TOPE`].
+    fn espera(&self, intento: u32, azar: f64) -> Duration {
+        let tope = self.espera.saturating_mul(2u32.saturating_pow(intento)).min(ESPERA_TOPE);
+        tope.div_f64(2.0).mul_f64(1.0 + azar)
+    }
+
+    /// Ejecuta `operacion` y la repite mientras falle por un error transitorio, hasta
+    /// agotar los reintentos. Con `idempotente`, también tras perder la conexión a mitad
+    /// de la operación.
+    pub async fn ejecutar<T, F, Fut>(&self, idempotente: bool, mut operacion: F) -> ResultadoRepositorio<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = ResultadoRepositorio<T>>,
+    {
+        let mut intento = 0;
+        loop {
+            let error = match operacion().await {
+                Err(error) => error,
+                resultado => return resultado,
+            };
+            let Some(contador) = motivo(&error, idempotente) else {
+                return Err(error);
+            };
+            if intento >= self.intentos {
+                METRICAS_REINTENTOS.agotados.fetch_add(1, Ordering::Relaxed);
+                return Err(error);
+            }
+            let espera = self.espera(intento, azar());
+            log::warn!("Error transitorio de MySQL ({:?}), se reintenta en {} ms", error, espera.as_millis());
+            contador.fetch_add(1, Ordering::Relaxed);
+            sleep(espera).await;
+            intento += 1;
+        }
+    }
+}
+
+/// Contador del motivo por el que se puede repetir la operación que falló con `error`, o
+/// `None` si no se puede.
+fn motivo(error: &ErrorRepositorio, idempotente: bool) -> Option<&'static AtomicU64> {
+    let (e, al_conectar) = match error {
+        ErrorRepositorio::Conexion(e) => (e, true),
+        ErrorRepositorio::Consulta(e) => (e, false),
+        _ => return None,
+    };
+    match errores::clasificar(e) {
+        FalloMysql::Bloqueo => Some(&METRICAS_REINTENTOS.bloqueo),
+        FalloMysql::ConexionPerdida if al_conectar || idempotente => Some(&METRICAS_REINTENTOS.conexion),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicU32;
+
+    use mysql_async::ServerError;
+
+    use super::*;
+
+    fn interbloqueo() -> ErrorRepositorio {
+        ErrorRepositorio::Consulta(mysql_async::Error::Server(ServerError {
+            code: errores::INTERBLOQUEO,
+            message: "Deadlock found when trying to get lock".to_string(),
+            state: "40001".to_string(),
+        }))
+    }
+
+    #[test]
+    fn la_espera_crece_con_azar_hasta_el_tope() {
+        let politica = PoliticaReintentos { intentos: 5, espera: Duration::from_millis(100) };
+        assert_eq!(politica.espera(0, 0.0), Duration::from_millis(50));
+        assert_eq!(politica.espera(0, 1.0), Duration::from_millis(100));
+        assert_eq!(politica.espera(2, 1.0), Duration::from_millis(400));
+        assert_eq!(politica.espera(10, 1.0), ESPERA_TOPE);
+        assert!((0.0..1.0).contains(&azar()));
+    }
+
+    #[actix_web::test]
+    async fn repite_los_bloqueos_hasta_agotar_los_intentos() {
+        let politica = PoliticaReintentos { intentos: 2, espera: Duration::from_millis(1) };
+        let llamadas = AtomicU32::new(0);
+        let resultado = politica
+            .ejecutar(false, || async {
+                match llamadas.fetch_add(1, Ordering::Relaxed) {
+                    0 => Err(interbloqueo()),
+                    _ => Ok(7),
+                }
+            })
+            .await;
+        assert_eq!(resultado.unwrap(), 7);
+        assert_eq!(llamadas.load(Ordering::Relaxed), 2);
+
+        llamadas.store(0, Ordering::Relaxed);
+        let resultado: ResultadoRepositorio<()> = politica
+            .ejecutar(false, || async {
+                llamadas.fetch_add(1, Ordering::Relaxed);
+                Err(interbloqueo())
+            })
+            .await;
+        assert!(resultado.is_err());
+        assert_eq!(llamadas.load(Ordering::Relaxed), 3);
+    }
+
+    #[test]
+    fn las_escrituras_no_se_repiten_si_la_conexion_cae_a_mitad() {
+        let caida = || mysql_async::Error::Driver(mysql_async::DriverError::ConnectionClosed);
+        assert!(motivo(&ErrorRepositorio::Conexion(caida()), false).is_some());
+        assert!(motivo(&ErrorRepositorio::Consulta(caida()), false).is_none());
+        assert!(motivo(&ErrorRepositorio::Consulta(caida()), true).is_some());
+        assert!(motivo(&ErrorRepositorio::FuncionInexistente, true).is_none());
+        assert!(motivo(&interbloqueo(), false).is_some());
+    }
+}
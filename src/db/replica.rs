@@ -0,0 +1,103 @@
+//! Réplica de sólo lectura opcional (`DATABASE_URL_RO`).
+//!
+//! [`enrutar_lecturas`] marca las peticiones GET y HEAD, y [`super::conectar`] les presta
+//! conexiones de la réplica en lugar de la primaria. El resto de las peticiones, y las tareas
+//! que no salen de una petición de lectura, usan la primaria. Si la réplica no da conexión,
+//! la petición sigue en la primaria y la réplica no se vuelve a probar hasta pasada
+//! [`PAUSA_TRAS_CAIDA`].
+//!
+//! La réplica puede ir algo por detrás de la primaria: una lectura justo después de una
+//! escritura puede no verla todavía, y la caché de lecturas puede guardar ese valor hasta
+//! que venza.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::Method;
+use actix_web::middleware::Next;
+use actix_web::{web, Error};
+use mysql_async::Pool;
+
+/// Tiempo durante el que no se vuelve a probar una réplica que no dio conexión.
+pub const PAUSA_TRAS_CAIDA: Duration = Duration::from_secs(30);
+
+tokio::task_local! {
+    /// Réplica de la petición de lectura en curso.
+    static REPLICA: Arc<Replica>;
+}
+
+/// Pool de la réplica y cuándo se puede volver a probar si falló.
+pub struct Replica {
+    pool: Pool,
+    caida_hasta: Mutex<Option<Instant>>,
+    /// Veces que no dio conexión y la lectura pasó a la primaria, para `/admin/pool`.
+    pub caidas: AtomicU64,
+}
+
+impl Replica {
+    pub fn new(pool: Pool) -> Self {
+        Replica {
+            pool,
+            caida_hasta: Mutex::new(None),
+            caidas: AtomicU64::new(0),
+        }
+    }
+
+    pub fn pool(&self) -> &Pool {
+        &self.pool
+    }
+
+    /// Si no está en pausa tras una caída.
+    pub fn disponible(&self) -> bool {
+        self.caida_hasta.lock().unwrap().is_none_or(|hasta| Instant::now() >= hasta)
+    }
+
+    /// La deja en pausa tras fallar al pedirle una conexión.
+    pub(super) fn marcar_caida(&self, error: &mysql_async::Error) {
+        log::warn!(
+            "La réplica de lectura no responde ({}); se lee de la primaria durante {} s",
+            error,
+            PAUSA_TRAS_CAIDA.as_secs()
+        );
+        self.caidas.fetch_add(1, Ordering::Relaxed);
+        *self.caida_hasta.lock().unwrap() = Some(Instant::now() + PAUSA_TRAS_CAIDA);
+    }
+}
+
+/// Réplica que debe usar la petición en curso: la suya si es de lectura y la réplica no
+/// está en pausa.
+pub(super) fn en_curso() -> Option<Arc<Replica>> {
+    REPLICA.try_with(Arc::clone).ok().filter(|replica| replica.disponible())
+}
+
+/// Middleware que atiende las peticiones GET y HEAD con la réplica, si hay una configurada.
+pub async fn enrutar_lecturas(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let replica = req.app_data::<web::Data<Option<Arc<Replica>>>>().and_then(|replica| replica.as_ref().clone());
+    match replica {
+        Some(replica) if matches!(*req.method(), Method::GET | Method::HEAD) => REPLICA.scope(replica, next.call(req)).await,
+        _ => next.call(req).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[actix_web::test]
+    async fn solo_las_lecturas_usan_la_replica_y_no_tras_caer() {
+        let replica = Arc::new(Replica::new(Pool::new("mysql://root@127.0.0.1:1/pruebas")));
+        assert!(en_curso().is_none());
+        REPLICA.sync_scope(replica.clone(), || {
+            assert!(en_curso().is_some());
+            replica.marcar_caida(&mysql_async::Error::Driver(mysql_async::DriverError::ConnectionClosed));
+            assert!(en_curso().is_none());
+        });
+        assert_eq!(replica.caidas.load(Ordering::Relaxed), 1);
+    }
+}
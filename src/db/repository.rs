@@ -0,0 +1,1160 @@
+//! Repositorio de entradas: toda la SQL de la API de entradas vive detrás de
+//! [`EntradaRepository`], de modo que la capa de servicio puede probarse con otra
+//! implementación sin base de datos.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveDateTime, TimeDelta};
+use futures_util::future::BoxFuture;
+use futures_util::stream::{self, BoxStream};
+use futures_util::FutureExt;
+use mysql_async::{from_row, prelude::*, Params, Pool, Transaction, TxOpts, Value};
+use tokio::sync::mpsc;
+
+use super::errores::{self, FalloMysql};
+use super::eventos::{self, Evento};
+use super::reintentos::PoliticaReintentos;
+use super::{conectar, Conexion, DELETE_ENTRADA, INSERT_ENTRADA, SELECT_ENTRADA_POR_ID, UPDATE_PARCIAL};
+use crate::agregado::{construir_consulta_agregado, valor_a_json, ParametrosAgregado, ResultadoAgregado};
+use crate::listado::{ConsultaListado, SentenciaListado};
+use crate::models::{
+    ActualizarEntrada, CrearEntrada, EliminarEntradas, Entrada, EstadoEntrada, GuardarEntrada, RegistroAuditoria,
+};
+use crate::reloj::{Reloj, RelojSistema};
+
+/// Filas que pueden quedar en cola entre la consulta y quien consume el listado.
+const FILAS_EN_BUFFER: usize = 64;
+
+/// La entrada `e` como objeto JSON, con sus asientos, tal como se guarda en la auditoría.
+const JSON_ENTRADA: &str = "JSON_OBJECT('id', e.id, 'cliente_id', e.cliente_id, 'funcion_id', e.funcion_id, \
+     'cantidad_entradas', e.cantidad_entradas, 'total', e.total, 'estado', e.estado, 'version', e.version, \
+     'updated_at', e.updated_at, \
+     'asientos', (SELECT JSON_ARRAYAGG(a.numero) FROM asientos a WHERE a.entrada_id = e.id))";
+
+/// Historial de una entrada, del cambio más antiguo al más reciente, con los parámetros
+/// `:id` y `:cine_id`.
+const SELECT_AUDITORIA: &str = "SELECT id, entrada_id, operacion, actor, motivo, valor_anterior, valor_nuevo, \
+     DATE_FORMAT(realizada_en, '%Y-%m-%d %H:%i:%s') FROM auditoria WHERE entrada_id = :id AND cine_id = :cine_id \
+     ORDER BY id";
+
+/// Errores de acceso a los datos.
+#[derive(Debug)]
+pub enum ErrorRepositorio {
+    /// No se pudo obtener una conexión.
+    Conexion(mysql_async::Error),
+    /// La consulta falló.
+    Consulta(mysql_async::Error),
+    /// La función no tiene asientos suficientes; quedan los indicados.
+    SinCapacidad(u32),
+    /// La cédula del cliente llegó al límite de entradas por función; puede comprar las
+    /// indicadas.
+    LimiteCedula(u32),
+    /// Los asientos indicados ya pertenecen a otra entrada de la función.
+    AsientosOcupados(Vec<u32>),
+    /// El número de asiento supera la capacidad de la sala.
+    AsientoInexistente(u32),
+    /// La entrada tiene asientos numerados, así que no puede cambiar de función ni de cantidad.
+    AsientosAsignados,
+    /// La entrada no está en la versión esperada; está en la indicada.
+    VersionDistinta(u32),
+    /// El `cliente_id` no corresponde a ningún cliente.
+    ClienteInexistente,
+    /// El `funcion_id` no corresponde a ninguna función.
+    FuncionInexistente,
+    /// La entrada está en el estado indicado, desde el que no puede pasar al pedido.
+    TransicionInvalida(EstadoEntrada),
+    /// Los parámetros no forman una consulta válida.
+    ParametrosInvalidos(String),
+    /// El valor no cabe en la columna indicada.
+    DemasiadoLargo(String),
+}
+
+pub type ResultadoRepositorio<T> = Result<T, ErrorRepositorio>;
+
+/// Resultado de guardar la entrada de un cliente, con su ID.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntradaGuardada {
+    Creada(u32),
+    Actualizada(u32),
+}
+
+/// Escritura que [`EntradaRepository::simular`] comprueba sin guardar, con los mismos
+/// datos que la operación correspondiente.
+#[derive(Debug, Clone, Copy)]
+pub enum Escritura<'a> {
+    /// [`EntradaRepository::crear_lote`]; una sola entrada equivale a [`EntradaRepository::crear`].
+    Crear(&'a [CrearEntrada]),
+    /// [`EntradaRepository::guardar_por_cedula`], con la cédula y los datos.
+    GuardarPorCedula(&'a str, &'a GuardarEntrada),
+    /// [`EntradaRepository::actualizar`], con el ID, los datos y la versión esperada.
+    Actualizar(u32, &'a ActualizarEntrada, Option<u32>),
+}
+
+/// La entrada tal como quedaría tras una escritura simulada.
+#[derive(Debug, Clone)]
+pub enum EntradaSimulada {
+    Creada(Entrada),
+    Actualizada(Entrada),
+    /// La escritura no cambiaría ninguna fila.
+    SinCambios,
+}
+
+/// Listado de entradas que se va leyendo a medida que se consume.
+pub type FlujoEntradas = BoxStream<'static, ResultadoRepositorio<Entrada>>;
+
+/// Operaciones de persistencia sobre las entradas. Las que modifican entradas registran
+/// cada cambio en la auditoría, a nombre de `actor`, y en la bandeja de salida de eventos
+/// (ver [`super::eventos`]), dentro de la misma transacción.
+///
+/// Cada operación se limita a las entradas de `cine` (ver [`crate::cines`]): las de otro
+/// cine se tratan como inexistentes, igual que los clientes y las funciones de otro cine.
+pub trait EntradaRepository: Send + Sync {
+    /// Página de entradas, leída a medida que se consume.
+    fn listar<'a>(&'a self, cine: u32, consulta: &'a ConsultaListado) -> BoxFuture<'a, ResultadoRepositorio<FlujoEntradas>>;
+
+    /// Total de entradas que abarca el listado, sin paginar.
+    fn contar<'a>(&'a self, cine: u32, consulta: &'a ConsultaListado) -> BoxFuture<'a, ResultadoRepositorio<u64>>;
+
+    fn obtener(&self, cine: u32, id: u32) -> BoxFuture<'_, ResultadoRepositorio<Option<Entrada>>>;
+
+    /// Devuelve el ID de la entrada creada.
+    fn crear<'a>(
+        &'a self,
+        cine: u32,
+        entrada: &'a CrearEntrada,
+        actor: &'a str,
+    ) -> BoxFuture<'a, ResultadoRepositorio<u32>>;
+
+    /// Crea todas las entradas o ninguna. Devuelve el resultado de cada una, en orden: el
+    /// lote sólo se guarda si todas salen bien, y si no, los IDs de las demás no valen.
+    fn crear_lote<'a>(
+        &'a self,
+        cine: u32,
+        entradas: &'a [CrearEntrada],
+        actor: &'a str,
+    ) -> BoxFuture<'a, ResultadoRepositorio<Vec<ResultadoRepositorio<u32>>>>;
+
+    /// Crea la entrada del cliente con esa cédula para la función o, si ya tiene una, le
+    /// cambia la cantidad. Con varias para la misma función, cambia la más reciente.
+    fn guardar_por_cedula<'a>(
+        &'a self,
+        cine: u32,
+        numero_cedula: &'a str,
+        datos: &'a GuardarEntrada,
+        actor: &'a str,
+    ) -> BoxFuture<'a, ResultadoRepositorio<EntradaGuardada>>;
+
+    /// Devuelve `false` si ninguna fila cambió. Con `version`, sólo actualiza la entrada si
+    /// sigue en ella. Cada cambio aumenta la versión y renueva `updated_at`.
+    fn actualizar<'a>(
+        &'a self,
+        cine: u32,
+        id: u32,
+        datos: &'a ActualizarEntrada,
+        version: Option<u32>,
+        actor: &'a str,
+    ) -> BoxFuture<'a, ResultadoRepositorio<bool>>;
+
+    /// Ejecuta la escritura con todas sus comprobaciones y la deshace: no guarda nada, ni
+    /// en la auditoría ni en la bandeja de eventos. Devuelve el resultado de cada entrada
+    /// escrita, en orden; las de un lote se comprueban como si las anteriores se hubieran
+    /// guardado.
+    fn simular<'a>(
+        &'a self,
+        cine: u32,
+        escritura: Escritura<'a>,
+        actor: &'a str,
+    ) -> BoxFuture<'a, ResultadoRepositorio<Vec<ResultadoRepositorio<EntradaSimulada>>>>;
+
+    /// Devuelve `false` si la entrada no existía. Con `version`, sólo la elimina si sigue en ella.
+    fn eliminar<'a>(
+        &'a self,
+        cine: u32,
+        id: u32,
+        version: Option<u32>,
+        actor: &'a str,
+    ) -> BoxFuture<'a, ResultadoRepositorio<bool>>;
+
+    /// Pasa la entrada al `estado` indicado si su estado actual lo permite. Devuelve `false`
+    /// si la entrada no existía. Al cancelarla, sus asientos quedan libres.
+    fn cambiar_estado<'a>(
+        &'a self,
+        cine: u32,
+        id: u32,
+        estado: EstadoEntrada,
+        actor: &'a str,
+    ) -> BoxFuture<'a, ResultadoRepositorio<bool>>;
+
+    /// Cancela las entradas que siguen reservadas más de `antiguedad` después de su alta y
+    /// devuelve cuántas eran. Es la única operación de todos los cines a la vez.
+    fn cancelar_vencidas<'a>(&'a self, antiguedad: Duration, actor: &'a str) -> BoxFuture<'a, ResultadoRepositorio<u64>>;
+
+    /// Cancela las entradas de la función `funcion_id` que aún pueden cancelarse, con
+    /// `motivo` en la auditoría de cada una, y devuelve sus IDs. Se cancelan todas o ninguna.
+    fn cancelar_funcion<'a>(
+        &'a self,
+        cine: u32,
+        funcion_id: u32,
+        motivo: &'a str,
+        actor: &'a str,
+    ) -> BoxFuture<'a, ResultadoRepositorio<Vec<u32>>>;
+
+    /// Elimina las entradas que cumplen todos los criterios y devuelve cuántas eran.
+    fn eliminar_filtradas<'a>(
+        &'a self,
+        cine: u32,
+        filtro: &'a EliminarEntradas,
+        actor: &'a str,
+    ) -> BoxFuture<'a, ResultadoRepositorio<u64>>;
+
+    /// Cambios registrados de una entrada, exista aún o no, del más antiguo al más reciente.
+    fn historial(&self, cine: u32, id: u32) -> BoxFuture<'_, ResultadoRepositorio<Vec<RegistroAuditoria>>>;
+
+    fn agregar<'a>(
+        &'a self,
+        cine: u32,
+        parametros: &'a ParametrosAgregado,
+    ) -> BoxFuture<'a, ResultadoRepositorio<ResultadoAgregado>>;
+}
+
+/// Implementación sobre la pool de MySQL. Las operaciones que fallan por un bloqueo o
+/// una conexión caída se repiten según su [`PoliticaReintentos`].
+#[derive(Clone)]
+pub struct RepositorioMysql {
+    pool: Pool,
+    reintentos: PoliticaReintentos,
+    /// Máximo de entradas que una cédula puede tener en una misma función.
+    limite_por_cedula: Option<u32>,
+    reloj: Arc<dyn Reloj>,
+}
+
+impl RepositorioMysql {
+    pub fn new(pool: Pool, reintentos: PoliticaReintentos) -> Self {
+        RepositorioMysql { pool, reintentos, limite_por_cedula: None, reloj: Arc::new(RelojSistema) }
+    }
+
+    /// Limita las entradas que una cédula puede tener para cada función, contando las que
+    /// ya tiene sin cancelar. `None` no limita.
+    pub fn con_limite_por_cedula(mut self, limite: Option<u32>) -> Self {
+        self.limite_por_cedula = limite;
+        self
+    }
+
+    /// Fecha las altas y los cambios, y decide qué reservas vencieron, con `reloj`.
+    pub fn con_reloj(mut self, reloj: Arc<dyn Reloj>) -> Self {
+        self.reloj = reloj;
+        self
+    }
+
+    async fn conexion(&self) -> ResultadoRepositorio<Conexion> {
+        conectar(&self.pool).await.map_err(ErrorRepositorio::Conexion)
+    }
+}
+
+/// Parámetros nombrados para `exec_*`. Una lista vacía debe viajar como `Params::Empty`:
+/// MySQL rechaza parámetros nombrados en una sentencia sin marcadores.
+fn parametros(params: Vec<(String, Value)>) -> Params {
+    if params.is_empty() { Params::Empty } else { Params::from(params) }
+}
+
+/// Clasifica un error de escritura, distinguiendo los clientes o funciones que no existen
+/// por el nombre de la restricción que falla.
+fn error_escritura(e: mysql_async::Error) -> ErrorRepositorio {
+    match errores::clasificar(&e) {
+        FalloMysql::ReferenciaInexistente(restriccion) if restriccion == "fk_entradas_cliente" => {
+            ErrorRepositorio::ClienteInexistente
+        }
+        FalloMysql::ReferenciaInexistente(restriccion) if restriccion == "fk_entradas_funcion" => {
+            ErrorRepositorio::FuncionInexistente
+        }
+        FalloMysql::DemasiadoLargo(columna) => ErrorRepositorio::DemasiadoLargo(columna),
+        _ => ErrorRepositorio::Consulta(e),
+    }
+}
+
+/// Comprueba que la función, del cine `cine`, tenga `cantidad` asientos libres sin contar
+/// los de la entrada `excepto` ni los de las canceladas, y devuelve la capacidad de su
+/// sala. Bloquea la fila de la función hasta el final de la transacción, así que las
+/// ventas concurrentes de una misma función se comprueban de una en una.
+async fn verificar_capacidad(
+    tx: &mut Transaction<'_>,
+    cine: u32,
+    funcion_id: u32,
+    cantidad: u32,
+    excepto: Option<u32>,
+) -> ResultadoRepositorio<u32> {
+    let capacidad: u32 = tx
+        .exec_first(
+            "SELECT s.capacidad FROM funciones f JOIN salas s ON s.id = f.sala_id \
+             WHERE f.id = :id AND s.cine_id = :cine_id FOR UPDATE",
+            params! { "id" => funcion_id, "cine_id" => cine },
+        )
+        .await
+        .map_err(ErrorRepositorio::Consulta)?
+        .ok_or(ErrorRepositorio::FuncionInexistente)?;
+    let vendidas: u64 = tx
+        .exec_first(
+            "SELECT CAST(COALESCE(SUM(cantidad_entradas), 0) AS UNSIGNED) FROM entradas \
+             WHERE funcion_id = :funcion_id AND id <> :excepto AND estado <> 'cancelada'",
+            params! { "funcion_id" => funcion_id, "excepto" => excepto.unwrap_or(0) },
+        )
+        .await
+        .map_err(ErrorRepositorio::Consulta)?
+        .unwrap_or_default();
+    let disponibles = u64::from(capacidad).saturating_sub(vendidas) as u32;
+    if cantidad > disponibles {
+        return Err(ErrorRepositorio::SinCapacidad(disponibles));
+    }
+    Ok(capacidad)
+}
+
+/// Comprueba que la cédula del cliente `cliente_id` pueda tener `cantidad` entradas de la
+/// función sin pasar `limite`, sin contar la entrada `excepto` ni las canceladas. Bloquea
+/// la fila de la función hasta el final de la transacción, como [`verificar_capacidad`],
+/// así que las ventas a una misma función, aunque lleguen por otra réplica, se cuentan de
+/// una en una.
+async fn verificar_limite_cedula(
+    tx: &mut Transaction<'_>,
+    cine: u32,
+    limite: Option<u32>,
+    (cliente_id, funcion_id): (u32, u32),
+    cantidad: u32,
+    excepto: Option<u32>,
+) -> ResultadoRepositorio<()> {
+    let Some(limite) = limite else {
+        return Ok(());
+    };
+    tx.exec_drop("SELECT id FROM funciones WHERE id = :id FOR UPDATE", params! { "id" => funcion_id })
+        .await
+        .map_err(ErrorRepositorio::Consulta)?;
+    let vendidas: u64 = tx
+        .exec_first(
+            "SELECT CAST(COALESCE(SUM(e.cantidad_entradas), 0) AS UNSIGNED) FROM entradas e \
+             JOIN clientes c ON c.id = e.cliente_id \
+             JOIN clientes comprador ON comprador.cine_id = c.cine_id AND comprador.numero_cedula = c.numero_cedula \
+             WHERE comprador.id = :cliente_id AND e.cine_id = :cine_id AND e.funcion_id = :funcion_id \
+             AND e.id <> :excepto AND e.estado <> 'cancelada'",
+            params! {
+                "cliente_id" => cliente_id,
+                "cine_id" => cine,
+                "funcion_id" => funcion_id,
+                "excepto" => excepto.unwrap_or(0),
+            },
+        )
+        .await
+        .map_err(ErrorRepositorio::Consulta)?
+        .unwrap_or_default();
+    let restantes = u64::from(limite).saturating_sub(vendidas) as u32;
+    if cantidad > restantes {
+        return Err(ErrorRepositorio::LimiteCedula(restantes));
+    }
+    Ok(())
+}
+
+/// Asigna los asientos a la entrada, comprobando que existan en la sala y que ninguno esté
+/// ocupado. Debe llamarse después de [`verificar_capacidad`], con la función ya bloqueada.
+async fn reservar_asientos(
+    tx: &mut Transaction<'_>,
+    funcion_id: u32,
+    entrada_id: u64,
+    asientos: &[u32],
+    capacidad: u32,
+) -> ResultadoRepositorio<()> {
+    if let Some(&asiento) = asientos.iter().find(|&&asiento| asiento > capacidad) {
+        return Err(ErrorRepositorio::AsientoInexistente(asiento));
+    }
+    let marcadores = vec!["?"; asientos.len()].join(", ");
+    let valores: Vec<Value> = std::iter::once(funcion_id).chain(asientos.iter().copied()).map(Value::from).collect();
+    let ocupados: Vec<u32> = tx
+        .exec(
+            format!(
+                "SELECT numero FROM asientos WHERE funcion_id = ? AND numero IN ({}) ORDER BY numero",
+                marcadores
+            ),
+            valores,
+        )
+        .await
+        .map_err(ErrorRepositorio::Consulta)?;
+    if !ocupados.is_empty() {
+        return Err(ErrorRepositorio::AsientosOcupados(ocupados));
+    }
+    tx.exec_batch(
+        "INSERT INTO asientos (funcion_id, numero, entrada_id) VALUES (?, ?, ?)",
+        asientos.iter().map(|asiento| (funcion_id, asiento, entrada_id)),
+    )
+    .await
+    .map_err(ErrorRepositorio::Consulta)
+}
+
+/// Alta de una entrada dentro de una transacción: comprueba la capacidad y el límite por
+/// cédula, la inserta, reserva sus asientos y la registra en la auditoría. Devuelve su ID.
+async fn insertar_entrada(
+    tx: &mut Transaction<'_>,
+    cine: u32,
+    limite: Option<u32>,
+    ahora: NaiveDateTime,
+    entrada: &CrearEntrada,
+    actor: &str,
+) -> ResultadoRepositorio<u64> {
+    let capacidad = verificar_capacidad(tx, cine, entrada.funcion_id, entrada.cantidad_entradas, None).await?;
+    let compra = (entrada.cliente_id, entrada.funcion_id);
+    verificar_limite_cedula(tx, cine, limite, compra, entrada.cantidad_entradas, None).await?;
+    tx.exec_drop(
+        INSERT_ENTRADA,
+        params! {
+            "cine_id" => cine,
+            "cliente_id" => entrada.cliente_id,
+            "funcion_id" => entrada.funcion_id,
+            "cantidad_entradas" => entrada.cantidad_entradas,
+            "ahora" => ahora,
+        },
+    )
+    .await
+    .map_err(error_escritura)?;
+    // La función ya se comprobó, así que si no se insertó nada es el cliente el que no es del cine.
+    if tx.affected_rows() == 0 {
+        return Err(ErrorRepositorio::ClienteInexistente);
+    }
+    let entrada_id = tx.last_insert_id().unwrap_or_default();
+    if !entrada.asientos.is_empty() {
+        reservar_asientos(tx, entrada.funcion_id, entrada_id, &entrada.asientos, capacidad).await?;
+    }
+    auditar(tx, cine, entrada_id as u32, Evento::Creada, actor, None, None).await?;
+    Ok(entrada_id)
+}
+
+/// Alta de un lote dentro de una transacción. Cada entrada va dentro de un SAVEPOINT: si
+/// falla se deshace sólo lo suyo, de modo que las siguientes se comprueban como si no
+/// hubiera existido; las que sí se insertaron cuentan para el límite por cédula de las
+/// siguientes. Quien llama decide si confirmar el lote.
+async fn insertar_lote(
+    tx: &mut Transaction<'_>,
+    cine: u32,
+    limite: Option<u32>,
+    ahora: NaiveDateTime,
+    entradas: &[CrearEntrada],
+    actor: &str,
+) -> ResultadoRepositorio<Vec<ResultadoRepositorio<u32>>> {
+    let mut resultados = Vec::with_capacity(entradas.len());
+    for entrada in entradas {
+        tx.query_drop("SAVEPOINT entrada").await.map_err(ErrorRepositorio::Consulta)?;
+        let resultado = insertar_entrada(tx, cine, limite, ahora, entrada, actor).await;
+        if resultado.is_err() {
+            tx.query_drop("ROLLBACK TO SAVEPOINT entrada")
+                .await
+                .map_err(ErrorRepositorio::Consulta)?;
+        }
+        resultados.push(resultado.map(|id| id as u32));
+    }
+    Ok(resultados)
+}
+
+/// [`EntradaRepository::guardar_por_cedula`] dentro de una transacción.
+async fn guardar_cedula_en(
+    tx: &mut Transaction<'_>,
+    cine: u32,
+    limite: Option<u32>,
+    ahora: NaiveDateTime,
+    numero_cedula: &str,
+    datos: &GuardarEntrada,
+    actor: &str,
+) -> ResultadoRepositorio<EntradaGuardada> {
+    let cliente_id: u32 = tx
+        .exec_first(
+            "SELECT id FROM clientes WHERE numero_cedula = :numero_cedula AND cine_id = :cine_id FOR UPDATE",
+            params! { "numero_cedula" => numero_cedula, "cine_id" => cine },
+        )
+        .await
+        .map_err(ErrorRepositorio::Consulta)?
+        .ok_or(ErrorRepositorio::ClienteInexistente)?;
+    let actual: Option<(u32, u32, u32)> = tx
+        .exec_first(
+            "SELECT id, funcion_id, cantidad_entradas FROM entradas \
+             WHERE cliente_id = :cliente_id AND funcion_id = :funcion_id ORDER BY id DESC LIMIT 1 FOR UPDATE",
+            params! { "cliente_id" => cliente_id, "funcion_id" => datos.funcion_id },
+        )
+        .await
+        .map_err(ErrorRepositorio::Consulta)?;
+
+    let guardada = match actual {
+        None => {
+            let entrada = CrearEntrada {
+                cliente_id,
+                funcion_id: datos.funcion_id,
+                cantidad_entradas: datos.cantidad_entradas,
+                asientos: Vec::new(),
+            };
+            EntradaGuardada::Creada(insertar_entrada(tx, cine, limite, ahora, &entrada, actor).await? as u32)
+        }
+        Some((id, funcion_id, cantidad)) => {
+            let nuevo = (datos.funcion_id, datos.cantidad_entradas);
+            if nuevo != (funcion_id, cantidad) {
+                verificar_cambio(tx, cine, id, (funcion_id, cantidad), nuevo).await?;
+                verificar_limite_cedula(tx, cine, limite, (cliente_id, nuevo.0), nuevo.1, Some(id)).await?;
+                let anterior = instantanea(tx, id).await?;
+                tx.exec_drop(
+                    "UPDATE entradas e JOIN funciones f ON f.id = :funcion_id \
+                     SET e.funcion_id = f.id, e.cantidad_entradas = :cantidad_entradas, \
+                     e.total = f.precio * :cantidad_entradas, e.version = e.version + 1, e.updated_at = :ahora \
+                     WHERE e.id = :id",
+                    params! { "id" => id, "funcion_id" => nuevo.0, "cantidad_entradas" => nuevo.1, "ahora" => ahora },
+                )
+                .await
+                .map_err(error_escritura)?;
+                auditar(tx, cine, id, Evento::Actualizada, actor, None, anterior).await?;
+            }
+            EntradaGuardada::Actualizada(id)
+        }
+    };
+    Ok(guardada)
+}
+
+/// [`EntradaRepository::actualizar`] dentro de una transacción.
+#[allow(clippy::too_many_arguments)]
+async fn actualizar_en(
+    tx: &mut Transaction<'_>,
+    cine: u32,
+    limite: Option<u32>,
+    ahora: NaiveDateTime,
+    id: u32,
+    datos: &ActualizarEntrada,
+    version: Option<u32>,
+    actor: &str,
+) -> ResultadoRepositorio<bool> {
+    let Some((funcion_id, cantidad, actual)) = version_actual(tx, cine, id).await? else {
+        return Ok(false);
+    };
+    verificar_version(version, actual)?;
+    if let Some(cliente_id) = datos.cliente_id {
+        verificar_cliente(tx, cine, cliente_id).await?;
+    }
+    let nuevo = (
+        datos.funcion_id.unwrap_or(funcion_id),
+        datos.cantidad_entradas.unwrap_or(cantidad),
+    );
+    verificar_cambio(tx, cine, id, (funcion_id, cantidad), nuevo).await?;
+    if limite.is_some() && (datos.cliente_id.is_some() || nuevo != (funcion_id, cantidad)) {
+        let cliente_id = match datos.cliente_id {
+            Some(cliente_id) => cliente_id,
+            None => tx
+                .exec_first("SELECT cliente_id FROM entradas WHERE id = :id", params! { "id" => id })
+                .await
+                .map_err(ErrorRepositorio::Consulta)?
+                .unwrap_or_default(),
+        };
+        verificar_limite_cedula(tx, cine, limite, (cliente_id, nuevo.0), nuevo.1, Some(id)).await?;
+    }
+    let anterior = instantanea(tx, id).await?;
+    tx.exec_drop(UPDATE_PARCIAL, datos.parametros(id))
+        .await
+        .map_err(error_escritura)?;
+    let actualizada = tx.affected_rows() > 0;
+    if actualizada {
+        tx.exec_drop(
+            "UPDATE entradas e JOIN funciones f ON f.id = e.funcion_id \
+             SET e.version = e.version + 1, e.updated_at = :ahora, e.total = f.precio * e.cantidad_entradas \
+             WHERE e.id = :id",
+            params! { "id" => id, "ahora" => ahora },
+        )
+        .await
+        .map_err(ErrorRepositorio::Consulta)?;
+        auditar(tx, cine, id, Evento::Actualizada, actor, None, anterior).await?;
+    }
+    Ok(actualizada)
+}
+
+/// La entrada `id` tal como la ve la transacción, envuelta en `variante`.
+async fn releer(
+    tx: &mut Transaction<'_>,
+    cine: u32,
+    id: u32,
+    variante: fn(Entrada) -> EntradaSimulada,
+) -> ResultadoRepositorio<EntradaSimulada> {
+    let entrada: Option<Entrada> = tx
+        .exec_first(SELECT_ENTRADA_POR_ID, params! { "id" => id, "cine_id" => cine })
+        .await
+        .map_err(ErrorRepositorio::Consulta)?;
+    Ok(entrada.map_or(EntradaSimulada::SinCambios, variante))
+}
+
+/// Comprueba que el cliente sea del cine `cine`.
+async fn verificar_cliente(tx: &mut Transaction<'_>, cine: u32, cliente_id: u32) -> ResultadoRepositorio<()> {
+    let existe: Option<u32> = tx
+        .exec_first(
+            "SELECT id FROM clientes WHERE id = :id AND cine_id = :cine_id",
+            params! { "id" => cliente_id, "cine_id" => cine },
+        )
+        .await
+        .map_err(ErrorRepositorio::Consulta)?;
+    existe.map(|_| ()).ok_or(ErrorRepositorio::ClienteInexistente)
+}
+
+/// Comprueba que una entrada pueda pasar de `actual` a `nuevo`, ambos como (función,
+/// cantidad): si algo cambia, la entrada no debe tener asientos numerados y la función
+/// debe tener sitio, sin contar los asientos que la entrada ya tenía.
+async fn verificar_cambio(
+    tx: &mut Transaction<'_>,
+    cine: u32,
+    id: u32,
+    actual: (u32, u32),
+    nuevo: (u32, u32),
+) -> ResultadoRepositorio<()> {
+    if nuevo == actual {
+        return Ok(());
+    }
+    let numeradas: Option<u32> = tx
+        .exec_first("SELECT 1 FROM asientos WHERE entrada_id = :id LIMIT 1", params! { "id" => id })
+        .await
+        .map_err(ErrorRepositorio::Consulta)?;
+    if numeradas.is_some() {
+        return Err(ErrorRepositorio::AsientosAsignados);
+    }
+    verificar_capacidad(tx, cine, nuevo.0, nuevo.1, Some(id)).await?;
+    Ok(())
+}
+
+/// Función, cantidad y versión de una entrada del cine `cine`, bloqueándola hasta el final
+/// de la transacción.
+async fn version_actual(tx: &mut Transaction<'_>, cine: u32, id: u32) -> ResultadoRepositorio<Option<(u32, u32, u32)>> {
+    tx.exec_first(
+        "SELECT funcion_id, cantidad_entradas, version FROM entradas WHERE id = :id AND cine_id = :cine_id FOR UPDATE",
+        params! { "id" => id, "cine_id" => cine },
+    )
+    .await
+    .map_err(ErrorRepositorio::Consulta)
+}
+
+/// Comprueba que la entrada siga en la versión esperada, si se indicó alguna.
+fn verificar_version(esperada: Option<u32>, actual: u32) -> ResultadoRepositorio<()> {
+    match esperada {
+        Some(esperada) if esperada != actual => Err(ErrorRepositorio::VersionDistinta(actual)),
+        _ => Ok(()),
+    }
+}
+
+/// La entrada como texto JSON, o `None` si no existe.
+async fn instantanea(tx: &mut Transaction<'_>, id: u32) -> ResultadoRepositorio<Option<String>> {
+    tx.exec_first(format!("SELECT {} FROM entradas e WHERE e.id = :id", JSON_ENTRADA), params! { "id" => id })
+        .await
+        .map_err(ErrorRepositorio::Consulta)
+}
+
+/// Registra un cambio de la entrada con su valor `anterior` y el que tiene ahora, que en
+/// una baja ya no existe, y deja pendiente su `evento` con el último de los dos.
+async fn auditar(
+    tx: &mut Transaction<'_>,
+    cine: u32,
+    id: u32,
+    evento: Evento,
+    actor: &str,
+    motivo: Option<&str>,
+    anterior: Option<String>,
+) -> ResultadoRepositorio<()> {
+    let nuevo = instantanea(tx, id).await?;
+    eventos::registrar(tx, evento, id, nuevo.as_deref().or(anterior.as_deref()))
+        .await
+        .map_err(ErrorRepositorio::Consulta)?;
+    tx.exec_drop(
+        "INSERT INTO auditoria (entrada_id, cine_id, operacion, actor, motivo, valor_anterior, valor_nuevo) \
+         VALUES (:entrada_id, :cine_id, :operacion, :actor, :motivo, :anterior, :nuevo)",
+        params! {
+            "entrada_id" => id,
+            "cine_id" => cine,
+            "operacion" => evento.operacion(),
+            "actor" => actor,
+            "motivo" => motivo,
+            "anterior" => anterior,
+            "nuevo" => nuevo,
+        },
+    )
+    .await
+    .map_err(ErrorRepositorio::Consulta)
+}
+
+/// Cambia el estado de una entrada ya bloqueada, cuya transición se comprobó, y lo registra
+/// en la auditoría con su `motivo`; usarla es un check-in. Al cancelarla, sus asientos
+/// quedan libres.
+async fn pasar_a_estado(
+    tx: &mut Transaction<'_>,
+    cine: u32,
+    id: u32,
+    estado: EstadoEntrada,
+    ahora: NaiveDateTime,
+    actor: &str,
+    motivo: Option<&str>,
+) -> ResultadoRepositorio<()> {
+    let anterior = instantanea(tx, id).await?;
+    tx.exec_drop(
+        "UPDATE entradas SET estado = :estado, version = version + 1, updated_at = :ahora WHERE id = :id",
+        params! { "id" => id, "estado" => estado.como_str(), "ahora" => ahora },
+    )
+    .await
+    .map_err(ErrorRepositorio::Consulta)?;
+    if estado == EstadoEntrada::Cancelada {
+        tx.exec_drop("DELETE FROM asientos WHERE entrada_id = :id", params! { "id" => id })
+            .await
+            .map_err(ErrorRepositorio::Consulta)?;
+    }
+    let evento = if estado == EstadoEntrada::Usada { Evento::Checkin } else { Evento::Actualizada };
+    auditar(tx, cine, id, evento, actor, motivo, anterior).await
+}
+
+impl EntradaRepository for RepositorioMysql {
+    /// Las filas se leen en una tarea aparte y se entregan por un canal acotado, sin
+    /// cargar la tabla completa en memoria.
+    fn listar<'a>(&'a self, cine: u32, consulta: &'a ConsultaListado) -> BoxFuture<'a, ResultadoRepositorio<FlujoEntradas>> {
+        self.reintentos.ejecutar(true, move || async move {
+            let conn = self.conexion().await?;
+            let (tx, rx) = mpsc::channel(FILAS_EN_BUFFER);
+            actix_web::rt::spawn(transmitir_entradas(conn, consulta.sentencia(cine), tx));
+            let filas = stream::unfold(rx, |mut rx| async move { rx.recv().await.map(|fila| (fila, rx)) });
+            Ok(Box::pin(filas) as FlujoEntradas)
+        })
+        .boxed()
+    }
+
+    fn contar<'a>(&'a self, cine: u32, consulta: &'a ConsultaListado) -> BoxFuture<'a, ResultadoRepositorio<u64>> {
+        self.reintentos.ejecutar(true, move || async move {
+            let sentencia = consulta.sentencia(cine);
+            let mut conn = self.conexion().await?;
+            let total: Option<u64> = conn
+                .exec_first(sentencia.conteo, parametros(sentencia.params_conteo))
+                .await
+                .map_err(ErrorRepositorio::Consulta)?;
+            Ok(total.unwrap_or_default())
+        })
+        .boxed()
+    }
+
+    fn obtener(&self, cine: u32, id: u32) -> BoxFuture<'_, ResultadoRepositorio<Option<Entrada>>> {
+        self.reintentos.ejecutar(true, move || async move {
+            let mut conn = self.conexion().await?;
+            conn.exec_first(SELECT_ENTRADA_POR_ID, params! { "id" => id, "cine_id" => cine })
+                .await
+                .map_err(ErrorRepositorio::Consulta)
+        })
+        .boxed()
+    }
+
+    /// El alta, las comprobaciones de capacidad y del límite por cédula y la reserva de
+    /// asientos van en la misma transacción: o se guarda todo o nada.
+    fn crear<'a>(
+        &'a self,
+        cine: u32,
+        entrada: &'a CrearEntrada,
+        actor: &'a str,
+    ) -> BoxFuture<'a, ResultadoRepositorio<u32>> {
+        self.reintentos.ejecutar(false, move || async move {
+            let mut conn = self.conexion().await?;
+            let mut tx = conn
+                .start_transaction(TxOpts::default())
+                .await
+                .map_err(ErrorRepositorio::Consulta)?;
+            let ahora = self.reloj.ahora_naive();
+            let id = insertar_entrada(&mut tx, cine, self.limite_por_cedula, ahora, entrada, actor).await?;
+            tx.commit().await.map_err(ErrorRepositorio::Consulta)?;
+            Ok(id as u32)
+        })
+        .boxed()
+    }
+
+    /// Ver [`insertar_lote`].
+    fn crear_lote<'a>(
+        &'a self,
+        cine: u32,
+        entradas: &'a [CrearEntrada],
+        actor: &'a str,
+    ) -> BoxFuture<'a, ResultadoRepositorio<Vec<ResultadoRepositorio<u32>>>> {
+        self.reintentos.ejecutar(false, move || async move {
+            let mut conn = self.conexion().await?;
+            let mut tx = conn
+                .start_transaction(TxOpts::default())
+                .await
+                .map_err(ErrorRepositorio::Consulta)?;
+            let ahora = self.reloj.ahora_naive();
+            let resultados = insertar_lote(&mut tx, cine, self.limite_por_cedula, ahora, entradas, actor).await?;
+            if resultados.iter().all(Result::is_ok) {
+                tx.commit().await.map_err(ErrorRepositorio::Consulta)?;
+            } else {
+                tx.rollback().await.map_err(ErrorRepositorio::Consulta)?;
+            }
+            Ok(resultados)
+        })
+        .boxed()
+    }
+
+    /// La fila del cliente queda bloqueada durante la transacción, así que dos envíos
+    /// simultáneos con la misma cédula no pueden crear dos entradas: el segundo espera y
+    /// actualiza la que creó el primero.
+    fn guardar_por_cedula<'a>(
+        &'a self,
+        cine: u32,
+        numero_cedula: &'a str,
+        datos: &'a GuardarEntrada,
+        actor: &'a str,
+    ) -> BoxFuture<'a, ResultadoRepositorio<EntradaGuardada>> {
+        self.reintentos.ejecutar(false, move || async move {
+            let mut conn = self.conexion().await?;
+            let mut tx = conn
+                .start_transaction(TxOpts::default())
+                .await
+                .map_err(ErrorRepositorio::Consulta)?;
+            let ahora = self.reloj.ahora_naive();
+            let guardada =
+                guardar_cedula_en(&mut tx, cine, self.limite_por_cedula, ahora, numero_cedula, datos, actor).await?;
+            tx.commit().await.map_err(ErrorRepositorio::Consulta)?;
+            Ok(guardada)
+        })
+        .boxed()
+    }
+
+    /// Si cambia la función o la cantidad, se vuelve a comprobar la capacidad en la misma
+    /// transacción, sin contar los asientos que la entrada ya tenía. Las entradas con
+    /// asientos numerados no pueden cambiar ninguna de las dos. La versión, `updated_at` y el
+    /// total, al precio actual de la función, sólo cambian si alguna columna cambió.
+    fn actualizar<'a>(
+        &'a self,
+        cine: u32,
+        id: u32,
+        datos: &'a ActualizarEntrada,
+        version: Option<u32>,
+        actor: &'a str,
+    ) -> BoxFuture<'a, ResultadoRepositorio<bool>> {
+        self.reintentos.ejecutar(false, move || async move {
+            if datos.es_vacia() {
+                return Err(ErrorRepositorio::ParametrosInvalidos(
+                    "No se proporcionaron datos para actualizar".to_string(),
+                ));
+            }
+            let mut conn = self.conexion().await?;
+            let mut tx = conn
+                .start_transaction(TxOpts::default())
+                .await
+                .map_err(ErrorRepositorio::Consulta)?;
+            let ahora = self.reloj.ahora_naive();
+            let actualizada =
+                actualizar_en(&mut tx, cine, self.limite_por_cedula, ahora, id, datos, version, actor).await?;
+            tx.commit().await.map_err(ErrorRepositorio::Consulta)?;
+            Ok(actualizada)
+        })
+        .boxed()
+    }
+
+    /// Todo va en una transacción que se revierte al final, así que los bloqueos, la
+    /// capacidad y los asientos se comprueban igual que al escribir. Las entradas creadas
+    /// gastan su ID del `AUTO_INCREMENT` aunque no se guarden.
+    fn simular<'a>(
+        &'a self,
+        cine: u32,
+        escritura: Escritura<'a>,
+        actor: &'a str,
+    ) -> BoxFuture<'a, ResultadoRepositorio<Vec<ResultadoRepositorio<EntradaSimulada>>>> {
+        self.reintentos.ejecutar(false, move || async move {
+            let mut conn = self.conexion().await?;
+            let mut tx = conn
+                .start_transaction(TxOpts::default())
+                .await
+                .map_err(ErrorRepositorio::Consulta)?;
+            let ahora = self.reloj.ahora_naive();
+            let mut resultados = Vec::new();
+            match escritura {
+                Escritura::Crear(entradas) => {
+                    let creadas = insertar_lote(&mut tx, cine, self.limite_por_cedula, ahora, entradas, actor).await?;
+                    for resultado in creadas {
+                        resultados.push(match resultado {
+                            Ok(id) => releer(&mut tx, cine, id, EntradaSimulada::Creada).await,
+                            Err(e) => Err(e),
+                        });
+                    }
+                }
+                Escritura::GuardarPorCedula(numero_cedula, datos) => {
+                    let guardada =
+                        guardar_cedula_en(&mut tx, cine, self.limite_por_cedula, ahora, numero_cedula, datos, actor);
+                    resultados.push(match guardada.await {
+                        Ok(EntradaGuardada::Creada(id)) => releer(&mut tx, cine, id, EntradaSimulada::Creada).await,
+                        Ok(EntradaGuardada::Actualizada(id)) => {
+                            releer(&mut tx, cine, id, EntradaSimulada::Actualizada).await
+                        }
+                        Err(e) => Err(e),
+                    });
+                }
+                Escritura::Actualizar(id, datos, version) => {
+                    let actualizada =
+                        actualizar_en(&mut tx, cine, self.limite_por_cedula, ahora, id, datos, version, actor);
+                    resultados.push(match actualizada.await {
+                        Ok(true) => releer(&mut tx, cine, id, EntradaSimulada::Actualizada).await,
+                        Ok(false) => Ok(EntradaSimulada::SinCambios),
+                        Err(e) => Err(e),
+                    });
+                }
+            }
+            tx.rollback().await.map_err(ErrorRepositorio::Consulta)?;
+            Ok(resultados)
+        })
+        .boxed()
+    }
+
+    /// La fila se bloquea para comprobar la versión y guardar su último valor en la auditoría.
+    fn eliminar<'a>(
+        &'a self,
+        cine: u32,
+        id: u32,
+        version: Option<u32>,
+        actor: &'a str,
+    ) -> BoxFuture<'a, ResultadoRepositorio<bool>> {
+        self.reintentos.ejecutar(false, move || async move {
+            let mut conn = self.conexion().await?;
+            let mut tx = conn
+                .start_transaction(TxOpts::default())
+                .await
+                .map_err(ErrorRepositorio::Consulta)?;
+            let Some((_, _, actual)) = version_actual(&mut tx, cine, id).await? else {
+                return Ok(false);
+            };
+            verificar_version(version, actual)?;
+            let anterior = instantanea(&mut tx, id).await?;
+            tx.exec_drop(DELETE_ENTRADA, params! { "id" => id })
+                .await
+                .map_err(ErrorRepositorio::Consulta)?;
+            auditar(&mut tx, cine, id, Evento::Eliminada, actor, None, anterior).await?;
+            tx.commit().await.map_err(ErrorRepositorio::Consulta)?;
+            Ok(true)
+        })
+        .boxed()
+    }
+
+    /// La fila se bloquea mientras se comprueba la transición, así que de dos cambios
+    /// simultáneos el segundo ve el estado que dejó el primero.
+    fn cambiar_estado<'a>(
+        &'a self,
+        cine: u32,
+        id: u32,
+        estado: EstadoEntrada,
+        actor: &'a str,
+    ) -> BoxFuture<'a, ResultadoRepositorio<bool>> {
+        self.reintentos.ejecutar(false, move || async move {
+            let mut conn = self.conexion().await?;
+            let mut tx = conn
+                .start_transaction(TxOpts::default())
+                .await
+                .map_err(ErrorRepositorio::Consulta)?;
+            let ahora = self.reloj.ahora_naive();
+            let actual: Option<String> = tx
+                .exec_first(
+                    "SELECT estado FROM entradas WHERE id = :id AND cine_id = :cine_id FOR UPDATE",
+                    params! { "id" => id, "cine_id" => cine },
+                )
+                .await
+                .map_err(ErrorRepositorio::Consulta)?;
+            let Some(actual) = actual.as_deref().and_then(EstadoEntrada::desde_str) else {
+                return Ok(false);
+            };
+            if !actual.puede_pasar_a(estado) {
+                return Err(ErrorRepositorio::TransicionInvalida(actual));
+            }
+            pasar_a_estado(&mut tx, cine, id, estado, ahora, actor, None).await?;
+            tx.commit().await.map_err(ErrorRepositorio::Consulta)?;
+            Ok(true)
+        })
+        .boxed()
+    }
+
+    /// Las vencidas se bloquean y se cancelan de una en una para que cada cancelación
+    /// quede en la auditoría, en una sola transacción.
+    fn cancelar_vencidas<'a>(&'a self, antiguedad: Duration, actor: &'a str) -> BoxFuture<'a, ResultadoRepositorio<u64>> {
+        self.reintentos.ejecutar(false, move || async move {
+            let mut conn = self.conexion().await?;
+            let mut tx = conn
+                .start_transaction(TxOpts::default())
+                .await
+                .map_err(ErrorRepositorio::Consulta)?;
+            let ahora = self.reloj.ahora_naive();
+            // Una antigüedad desmedida no vence ninguna: TIMESTAMP no guarda nada anterior a 1970.
+            let antes_de = TimeDelta::from_std(antiguedad)
+                .ok()
+                .and_then(|antiguedad| ahora.checked_sub_signed(antiguedad))
+                .unwrap_or(DateTime::UNIX_EPOCH.naive_utc());
+            let vencidas: Vec<(u32, u32)> = tx
+                .exec(
+                    "SELECT id, cine_id FROM entradas WHERE estado = 'reservada' \
+                     AND created_at < :antes_de ORDER BY id FOR UPDATE",
+                    params! { "antes_de" => antes_de },
+                )
+                .await
+                .map_err(ErrorRepositorio::Consulta)?;
+            for &(id, cine) in &vencidas {
+                pasar_a_estado(&mut tx, cine, id, EstadoEntrada::Cancelada, ahora, actor, None).await?;
+            }
+            tx.commit().await.map_err(ErrorRepositorio::Consulta)?;
+            Ok(vencidas.len() as u64)
+        })
+        .boxed()
+    }
+
+    /// La función queda bloqueada, como en cada venta, así que no se venden entradas
+    /// nuevas mientras se cancelan las que tiene.
+    fn cancelar_funcion<'a>(
+        &'a self,
+        cine: u32,
+        funcion_id: u32,
+        motivo: &'a str,
+        actor: &'a str,
+    ) -> BoxFuture<'a, ResultadoRepositorio<Vec<u32>>> {
+        self.reintentos.ejecutar(false, move || async move {
+            let mut conn = self.conexion().await?;
+            let mut tx = conn
+                .start_transaction(TxOpts::default())
+                .await
+                .map_err(ErrorRepositorio::Consulta)?;
+            let ahora = self.reloj.ahora_naive();
+            tx.exec_first::<u32, _, _>(
+                "SELECT f.id FROM funciones f JOIN salas s ON s.id = f.sala_id \
+                 WHERE f.id = :id AND s.cine_id = :cine_id FOR UPDATE",
+                params! { "id" => funcion_id, "cine_id" => cine },
+            )
+            .await
+            .map_err(ErrorRepositorio::Consulta)?
+            .ok_or(ErrorRepositorio::FuncionInexistente)?;
+            // Las que `EstadoEntrada::puede_pasar_a` deja cancelar.
+            let ids: Vec<u32> = tx
+                .exec(
+                    "SELECT id FROM entradas WHERE funcion_id = :funcion_id AND cine_id = :cine_id \
+                     AND estado IN ('reservada', 'pagada') ORDER BY id FOR UPDATE",
+                    params! { "funcion_id" => funcion_id, "cine_id" => cine },
+                )
+                .await
+                .map_err(ErrorRepositorio::Consulta)?;
+            for &id in &ids {
+                pasar_a_estado(&mut tx, cine, id, EstadoEntrada::Cancelada, ahora, actor, Some(motivo)).await?;
+            }
+            tx.commit().await.map_err(ErrorRepositorio::Consulta)?;
+            Ok(ids)
+        })
+        .boxed()
+    }
+
+    /// El último valor de cada entrada pasa a la auditoría y a su evento antes de eliminarlas
+    /// todas.
+    fn eliminar_filtradas<'a>(
+        &'a self,
+        cine: u32,
+        filtro: &'a EliminarEntradas,
+        actor: &'a str,
+    ) -> BoxFuture<'a, ResultadoRepositorio<u64>> {
+        self.reintentos.ejecutar(false, move || async move {
+            let mut condiciones = vec!["cine_id = ?".to_string()];
+            let mut valores = vec![Value::from(cine)];
+            if let Some(funcion_id) = filtro.funcion_id {
+                condiciones.push("funcion_id = ?".to_string());
+                valores.push(Value::from(funcion_id));
+            }
+            if let Some(ids) = &filtro.ids {
+                condiciones.push(format!("id IN ({})", vec!["?"; ids.len()].join(", ")));
+                valores.extend(ids.iter().copied().map(Value::from));
+            }
+            if condiciones.len() == 1 {
+                return Err(ErrorRepositorio::ParametrosInvalidos("Falta el criterio de eliminación".to_string()));
+            }
+
+            let mut conn = self.conexion().await?;
+            let mut tx = conn
+                .start_transaction(TxOpts::default())
+                .await
+                .map_err(ErrorRepositorio::Consulta)?;
+            let condiciones = condiciones.join(" AND ");
+            let mut params_auditoria = vec![Value::from(actor)];
+            params_auditoria.extend(valores.iter().cloned());
+            tx.exec_drop(
+                format!(
+                    "INSERT INTO auditoria (entrada_id, cine_id, operacion, actor, valor_anterior) \
+                     SELECT e.id, e.cine_id, 'eliminar', ?, {} FROM entradas e WHERE {}",
+                    JSON_ENTRADA, condiciones
+                ),
+                params_auditoria,
+            )
+            .await
+            .map_err(ErrorRepositorio::Consulta)?;
+            tx.exec_drop(
+                format!(
+                    "INSERT INTO eventos (evento, entrada_id, entrada) SELECT ?, e.id, {} FROM entradas e WHERE {}",
+                    JSON_ENTRADA, condiciones
+                ),
+                std::iter::once(Value::from(Evento::Eliminada.como_str())).chain(valores.iter().cloned()).collect::<Vec<_>>(),
+            )
+            .await
+            .map_err(ErrorRepositorio::Consulta)?;
+            tx.exec_drop(format!("DELETE FROM entradas WHERE {}", condiciones), valores)
+                .await
+                .map_err(ErrorRepositorio::Consulta)?;
+            let eliminadas = tx.affected_rows();
+            tx.commit().await.map_err(ErrorRepositorio::Consulta)?;
+            Ok(eliminadas)
+        })
+        .boxed()
+    }
+
+    fn historial(&self, cine: u32, id: u32) -> BoxFuture<'_, ResultadoRepositorio<Vec<RegistroAuditoria>>> {
+        self.reintentos.ejecutar(true, move || async move {
+            let mut conn = self.conexion().await?;
+            conn.exec(SELECT_AUDITORIA, params! { "id" => id, "cine_id" => cine })
+                .await
+                .map_err(ErrorRepositorio::Consulta)
+        })
+        .boxed()
+    }
+
+    fn agregar<'a>(
+        &'a self,
+        cine: u32,
+        parametros: &'a ParametrosAgregado,
+    ) -> BoxFuture<'a, ResultadoRepositorio<ResultadoAgregado>> {
+        self.reintentos.ejecutar(true, move || async move {
+            let (consulta, alias) =
+                construir_consulta_agregado(parametros, cine).map_err(ErrorRepositorio::ParametrosInvalidos)?;
+            let mut conn = self.conexion().await?;
+            let filas = conn
+                .query::<mysql_async::Row, _>(consulta)
+                .await
+                .map_err(ErrorRepositorio::Consulta)?;
+            Ok(ResultadoAgregado {
+                alias,
+                filas: filas
+                    .into_iter()
+                    .map(|fila| fila.unwrap().into_iter().map(valor_a_json).collect())
+                    .collect(),
+            })
+        })
+        .boxed()
+    }
+}
+
+/// Lee las entradas fila a fila y las envía por el canal. Si quien consume el listado
+/// lo abandona, el envío falla y la consulta se interrumpe.
+async fn transmitir_entradas(
+    mut conn: Conexion,
+    sentencia: SentenciaListado,
+    tx: mpsc::Sender<ResultadoRepositorio<Entrada>>,
+) {
+    let mut resultado = match conn.exec_iter(sentencia.consulta, sentencia.params).await {
+        Ok(resultado) => resultado,
+        Err(e) => {
+            let _ = tx.send(Err(ErrorRepositorio::Consulta(e))).await;
+            return;
+        }
+    };
+
+    loop {
+        let fila = match resultado.next().await {
+            Ok(Some(fila)) => fila,
+            Ok(None) => break,
+            Err(e) => {
+                let _ = tx.send(Err(ErrorRepositorio::Consulta(e))).await;
+                return;
+            }
+        };
+        if tx.send(Ok(from_row(fila))).await.is_err() {
+            return;
+        }
+    }
+}
@@ -0,0 +1,59 @@
+//! Endpoints de diagnóstico, compilados sólo con la feature `debug-explain`.
+
+use actix_web::web;
+use mysql_async::{prelude::*, Params, Pool};
+use serde::Deserialize;
+
+use crate::agregado::{construir_consulta_agregado, ParametrosAgregado};
+use crate::cines::CineActual;
+use crate::db::SELECT_ENTRADA_POR_ID;
+use crate::db;
+use crate::error::ApiError;
+use crate::json::Json;
+use crate::listado::{ConsultaListado, ParametrosListado};
+use crate::respuesta::ApiResponse;
+
+/// Consultas de la aplicación que se pueden inspeccionar, con sus parámetros.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "consulta", content = "parametros", rename_all = "snake_case")]
+pub enum ConsultaExplicable {
+    ListarEntradas(ParametrosListado),
+    EntradaPorId { id: u32 },
+    Agregado(ParametrosAgregado),
+}
+
+impl ConsultaExplicable {
+    /// SQL y parámetros exactamente como los ejecutaría el handler correspondiente en `cine`.
+    fn sentencia(&self, cine: u32) -> Result<(String, Params), String> {
+        match self {
+            ConsultaExplicable::ListarEntradas(parametros) => {
+                let sentencia = ConsultaListado::desde_parametros(parametros)?.sentencia(cine);
+                Ok((sentencia.consulta, Params::from(sentencia.params)))
+            }
+            ConsultaExplicable::EntradaPorId { id } => Ok((SELECT_ENTRADA_POR_ID.to_string(), params! { "id" => id, "cine_id" => cine })),
+            ConsultaExplicable::Agregado(parametros) => {
+                construir_consulta_agregado(parametros, cine).map(|(consulta, _)| (consulta, Params::Empty))
+            }
+        }
+    }
+}
+
+/// Handler que devuelve el plan de ejecución (`EXPLAIN FORMAT=JSON`) de una consulta con nombre.
+/// Nunca ejecuta la consulta en sí.
+pub async fn explicar_consulta(
+    pool: web::Data<Pool>,
+    CineActual(cine): CineActual,
+    consulta: Json<ConsultaExplicable>,
+) -> Result<ApiResponse<serde_json::Value>, ApiError> {
+    let (sql, params) = consulta.sentencia(cine).map_err(ApiError::Validacion)?;
+
+    let mut conn = db::conectar(&pool).await.map_err(ApiError::conexion)?;
+    let plan = conn
+        .exec_first::<String, _, _>(format!("EXPLAIN FORMAT=JSON {}", sql), params)
+        .await
+        .map_err(ApiError::base_datos("Error al obtener el plan de la consulta"))?
+        .ok_or_else(|| ApiError::BaseDatos("EXPLAIN no devolvió ningún plan".to_string()))?;
+
+    let plan = serde_json::from_str(&plan).unwrap_or(serde_json::Value::String(plan));
+    Ok(ApiResponse::ok(serde_json::json!({ "sql": sql, "plan": plan })))
+}
@@ -0,0 +1,254 @@
+//! Disponibilidad de asientos en vivo por WebSocket en `/ws/funciones/{id}`, para la
+//! pantalla de selección de asientos.
+//!
+//! Al conectar, el cliente recibe la ocupación de la función como en
+//! `GET /funciones/{id}/asientos`, y después la nueva cada vez que cambia. Cada función
+//! con clientes conectados tiene una sola tarea que vuelve a leer su ocupación tras cada
+//! cambio de entradas que avisa [`CanalCambios`], y cada [`REVISION`] por los que hacen
+//! otras réplicas, y la reparte a todos sus clientes por un canal [`watch`]. Si la función
+//! se elimina, se cierran sus conexiones.
+//!
+//! El servidor envía un ping cada [`LATIDO`] y cierra las conexiones que pasan
+//! [`SILENCIO_MAXIMO`] sin dar señales; a los ping del cliente responde con pong.
+//!
+//! No exige autenticación, como el autocompletado: los navegadores no pueden enviar
+//! cabeceras al abrir un WebSocket, y la ocupación de una sala no es un dato reservado.
+//! Por lo mismo no depende del cine: el ID de una función es único en toda la cadena.
+
+use std::collections::HashMap;
+use std::future::ready;
+use std::pin::pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use actix_web::rt::time::{sleep, timeout};
+use actix_web::{rt, web, HttpRequest, HttpResponse};
+use actix_ws::{CloseCode, CloseReason, Message, Session};
+use futures_util::stream::{self, Stream, StreamExt};
+use mysql_async::Pool;
+use serde::Serialize;
+use tokio::sync::broadcast::{self, error::RecvError, error::TryRecvError};
+use tokio::sync::watch;
+
+use crate::cambios::{Cambio, CanalCambios};
+use crate::db;
+use crate::error::ApiError;
+use crate::funciones::leer_asientos;
+use crate::models::AsientosFuncion;
+
+/// Cada cuánto se envía un ping a cada cliente.
+pub const LATIDO: Duration = Duration::from_secs(15);
+
+/// Tiempo sin ningún mensaje del cliente, ni siquiera un pong, tras el que se le desconecta.
+pub const SILENCIO_MAXIMO: Duration = Duration::from_secs(45);
+
+/// Cada cuánto se vuelve a leer la ocupación aunque esta réplica no haya visto cambios.
+pub const REVISION: Duration = Duration::from_secs(30);
+
+/// Mensaje que recibe el cliente con la ocupación de la función.
+#[derive(Debug, Serialize)]
+struct MensajeAsientos<'a> {
+    funcion_id: u32,
+    #[serde(flatten)]
+    asientos: &'a AsientosFuncion,
+}
+
+/// Ocupación de las funciones con clientes conectados, una por función.
+#[derive(Default)]
+pub struct AsientosEnVivo {
+    funciones: Mutex<HashMap<u32, Arc<watch::Sender<AsientosFuncion>>>>,
+}
+
+impl AsientosEnVivo {
+    /// Receptor de la ocupación de la función `id`, que empieza en `actual` si nadie la
+    /// vigilaba todavía, en cuyo caso se pone en marcha su tarea.
+    fn suscribir(
+        self: &Arc<Self>,
+        id: u32,
+        actual: AsientosFuncion,
+        pool: &Pool,
+        cambios: &CanalCambios,
+    ) -> watch::Receiver<AsientosFuncion> {
+        let mut funciones = self.funciones.lock().unwrap();
+        if let Some(emisor) = funciones.get(&id) {
+            return emisor.subscribe();
+        }
+        let (emisor, receptor) = watch::channel(actual);
+        let emisor = Arc::new(emisor);
+        funciones.insert(id, emisor.clone());
+        rt::spawn(vigilar(self.clone(), id, emisor, pool.clone(), cambios.suscribir()));
+        receptor
+    }
+
+    /// Deja de vigilar la función `id` si ya no le quedan clientes, o si `eliminada`. Se
+    /// comprueba con el mapa bloqueado para que nadie se suscriba entretanto.
+    fn soltar(&self, id: u32, emisor: &Arc<watch::Sender<AsientosFuncion>>, eliminada: bool) -> bool {
+        let mut funciones = self.funciones.lock().unwrap();
+        if !eliminada && emisor.receiver_count() > 0 {
+            return false;
+        }
+        if funciones.get(&id).is_some_and(|actual| Arc::ptr_eq(actual, emisor)) {
+            funciones.remove(&id);
+        }
+        true
+    }
+
+    /// Funciones vigiladas.
+    pub fn funciones(&self) -> usize {
+        self.funciones.lock().unwrap().len()
+    }
+}
+
+/// Tarea que mantiene al día la ocupación de la función `id` mientras tenga clientes.
+async fn vigilar(
+    vivo: Arc<AsientosEnVivo>,
+    id: u32,
+    emisor: Arc<watch::Sender<AsientosFuncion>>,
+    pool: Pool,
+    mut cambios: broadcast::Receiver<Cambio>,
+) {
+    loop {
+        if let Ok(Err(RecvError::Closed)) = timeout(REVISION, cambios.recv()).await {
+            break;
+        }
+        // Los cambios que llegaron entretanto se atienden con la misma lectura.
+        while let Ok(_) | Err(TryRecvError::Lagged(_)) = cambios.try_recv() {}
+        if vivo.soltar(id, &emisor, false) {
+            return;
+        }
+        let leidos = match db::conectar(&pool).await {
+            Ok(mut conn) => leer_asientos(&mut conn, None, id).await,
+            Err(e) => Err(e),
+        };
+        match leidos {
+            Ok(Some(asientos)) => {
+                emisor.send_if_modified(|actual| {
+                    let cambia = *actual != asientos;
+                    *actual = asientos;
+                    cambia
+                });
+            }
+            Ok(None) => break,
+            Err(e) => log::error!("Error al leer los asientos de la función {}: {:?}", id, e),
+        }
+    }
+    vivo.soltar(id, &emisor, true);
+}
+
+/// Lo que atiende la conexión de un cliente.
+enum Suceso {
+    Asientos(AsientosFuncion),
+    Mensaje(Message),
+    Latido,
+    /// El cliente se desconectó, o la función se eliminó.
+    Fin,
+}
+
+/// Handler de `/ws/funciones/{id}`: abre el WebSocket y le envía la ocupación de la
+/// función cada vez que cambia. 404 si la función no existe.
+pub async fn conectar_asientos(
+    req: HttpRequest,
+    cuerpo: web::Payload,
+    pool: web::Data<Pool>,
+    cambios: web::Data<CanalCambios>,
+    vivo: web::Data<AsientosEnVivo>,
+    id: web::Path<u32>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let id = id.into_inner();
+    let mut conn = db::conectar(&pool).await.map_err(ApiError::conexion)?;
+    let actual = leer_asientos(&mut conn, None, id)
+        .await
+        .map_err(ApiError::base_datos("Error al obtener asientos"))?
+        .ok_or_else(|| ApiError::NoEncontrado("Función no encontrada".to_string()))?;
+    drop(conn);
+
+    let (respuesta, sesion, mensajes) = actix_ws::handle(&req, cuerpo)?;
+    let mut receptor = vivo.into_inner().suscribir(id, actual, &pool, &cambios);
+    let inicial = mensaje(id, &receptor.borrow_and_update());
+    let asientos = stream::unfold(receptor, |mut receptor| async move {
+        receptor.changed().await.ok()?;
+        let asientos = receptor.borrow_and_update().clone();
+        Some((Suceso::Asientos(asientos), receptor))
+    });
+    // Un error de protocolo termina la conexión como si el cliente la cerrara.
+    let mensajes = mensajes
+        .take_while(|mensaje| ready(mensaje.is_ok()))
+        .filter_map(|mensaje| ready(mensaje.ok().map(Suceso::Mensaje)));
+    let latidos = stream::unfold((), |()| async {
+        sleep(LATIDO).await;
+        Some((Suceso::Latido, ()))
+    });
+    let fin = || stream::once(ready(Suceso::Fin));
+    let sucesos = stream::select(asientos.chain(fin()), stream::select(mensajes.chain(fin()), latidos));
+    rt::spawn(atender(id, sesion, inicial, sucesos));
+    Ok(respuesta)
+}
+
+/// Atiende la conexión de un cliente, empezando por enviarle `inicial`, hasta que se cierra.
+async fn atender(id: u32, mut sesion: Session, inicial: String, sucesos: impl Stream<Item = Suceso>) {
+    let mut sucesos = pin!(sucesos);
+    let mut ultima_senal = Instant::now();
+    let mut enviar = Some(inicial);
+    let motivo = loop {
+        if let Some(texto) = enviar.take()
+            && sesion.text(texto).await.is_err()
+        {
+            return;
+        }
+        let Some(suceso) = sucesos.next().await else { break None };
+        match suceso {
+            Suceso::Asientos(asientos) => enviar = Some(mensaje(id, &asientos)),
+            Suceso::Mensaje(Message::Ping(datos)) => {
+                ultima_senal = Instant::now();
+                if sesion.pong(&datos).await.is_err() {
+                    return;
+                }
+            }
+            Suceso::Mensaje(Message::Close(motivo)) => break motivo,
+            Suceso::Mensaje(_) => ultima_senal = Instant::now(),
+            Suceso::Latido if ultima_senal.elapsed() > SILENCIO_MAXIMO => {
+                break Some(CloseReason { code: CloseCode::Away, description: Some("Sin respuesta".to_string()) });
+            }
+            Suceso::Latido => {
+                if sesion.ping(b"").await.is_err() {
+                    return;
+                }
+            }
+            Suceso::Fin => break Some(CloseCode::Normal.into()),
+        }
+    };
+    let _ = sesion.close(motivo).await;
+}
+
+/// Texto del mensaje con la ocupación de la función `id`.
+fn mensaje(id: u32, asientos: &AsientosFuncion) -> String {
+    serde_json::to_string(&MensajeAsientos { funcion_id: id, asientos }).expect("AsientosFuncion siempre es serializable")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[actix_web::test]
+    async fn cada_funcion_se_vigila_mientras_tenga_clientes() {
+        let vivo = Arc::new(AsientosEnVivo::default());
+        let pool = Pool::new("mysql://root@127.0.0.1:1/pruebas");
+        let cambios = CanalCambios::default();
+        let asientos = AsientosFuncion { capacidad: 2, disponibles: 1, asientos: vec![2] };
+        let primero = vivo.suscribir(7, asientos.clone(), &pool, &cambios);
+        let segundo = vivo.suscribir(7, AsientosFuncion { capacidad: 0, disponibles: 0, asientos: vec![] }, &pool, &cambios);
+        assert_eq!(*segundo.borrow(), asientos);
+        assert_eq!(vivo.funciones(), 1);
+
+        let emisor = vivo.funciones.lock().unwrap()[&7].clone();
+        drop(primero);
+        assert!(!vivo.soltar(7, &emisor, false));
+        drop(segundo);
+        assert!(vivo.soltar(7, &emisor, false));
+        assert_eq!(vivo.funciones(), 0);
+        assert_eq!(
+            mensaje(7, &asientos),
+            r#"{"funcion_id":7,"capacidad":2,"disponibles":1,"asientos":[2]}"#
+        );
+    }
+}
@@ -0,0 +1,213 @@
+//! Registro de kioscos y seguimiento de sus latidos.
+//!
+//! Cada kiosco se registra una vez y después envía latidos periódicos. Un dispositivo
+//! que lleva más de `Config::dispositivos_silencio` sin latir se considera silencioso y
+//! la tarea de [`vigilar_dispositivos`] lo avisa en el log.
+
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use actix_web::{web, HttpResponse};
+use mysql_async::{prelude::*, Pool};
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::db;
+use crate::db::errores::{self, FalloMysql};
+use crate::error::ApiError;
+use crate::json::Json;
+use crate::respuesta::ApiResponse;
+use crate::tareas::{self, Intervalo, Trabajo};
+
+const SELECT_DISPOSITIVOS: &str = "SELECT id, nombre, sucursal, version, \
+     DATE_FORMAT(ultimo_latido, '%Y-%m-%d %H:%i:%s'), TIMESTAMPDIFF(SECOND, ultimo_latido, NOW()) \
+     FROM dispositivos";
+
+/// Datos con los que se registra un kiosco.
+#[derive(Debug, Deserialize)]
+pub struct RegistrarDispositivo {
+    pub nombre: String,
+    pub sucursal: String,
+    pub version: String,
+}
+
+/// Cuerpo opcional de un latido, para informar de una actualización del kiosco.
+#[derive(Debug, Default, Deserialize)]
+pub struct Latido {
+    pub version: Option<String>,
+}
+
+/// Situación de un dispositivo según su último latido.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EstadoDispositivo {
+    Activo,
+    Silencioso,
+    SinLatidos,
+}
+
+/// Dispositivo registrado con su estado de conexión.
+#[derive(Debug, Clone, Serialize)]
+pub struct Dispositivo {
+    pub id: u32,
+    pub nombre: String,
+    pub sucursal: String,
+    pub version: String,
+    pub ultimo_latido: Option<String>,
+    pub segundos_desde_latido: Option<u64>,
+    pub estado: EstadoDispositivo,
+}
+
+type FilaDispositivo = (u32, String, String, String, Option<String>, Option<i64>);
+
+impl Dispositivo {
+    fn desde_fila(fila: FilaDispositivo, silencio: Duration) -> Dispositivo {
+        let (id, nombre, sucursal, version, ultimo_latido, segundos) = fila;
+        let segundos_desde_latido = segundos.map(|s| s.max(0) as u64);
+        let estado = match segundos_desde_latido {
+            None => EstadoDispositivo::SinLatidos,
+            Some(s) if s > silencio.as_secs() => EstadoDispositivo::Silencioso,
+            Some(_) => EstadoDispositivo::Activo,
+        };
+        Dispositivo {
+            id,
+            nombre,
+            sucursal,
+            version,
+            ultimo_latido,
+            segundos_desde_latido,
+            estado,
+        }
+    }
+}
+
+/// Handler para registrar un kiosco nuevo.
+pub async fn registrar_dispositivo(
+    pool: web::Data<Pool>,
+    datos: Json<RegistrarDispositivo>,
+) -> Result<ApiResponse<Dispositivo>, ApiError> {
+    let datos = datos.into_inner();
+    if datos.nombre.trim().is_empty() || datos.sucursal.trim().is_empty() || datos.version.trim().is_empty() {
+        return Err(ApiError::Validacion("nombre, sucursal y version son obligatorios".to_string()));
+    }
+
+    let mut conn = db::conectar(&pool).await.map_err(ApiError::conexion)?;
+    let result = conn
+        .exec_drop(
+            "INSERT INTO dispositivos (nombre, sucursal, version) VALUES (:nombre, :sucursal, :version)",
+            params! {
+                "nombre" => &datos.nombre,
+                "sucursal" => &datos.sucursal,
+                "version" => &datos.version,
+            },
+        )
+        .await;
+
+    match result {
+        Ok(_) => Ok(ApiResponse::creada(Dispositivo {
+            id: conn.last_insert_id().unwrap_or_default() as u32,
+            nombre: datos.nombre,
+            sucursal: datos.sucursal,
+            version: datos.version,
+            ultimo_latido: None,
+            segundos_desde_latido: None,
+            estado: EstadoDispositivo::SinLatidos,
+        })),
+        Err(e) => Err(match errores::clasificar(&e) {
+            FalloMysql::Duplicado(restriccion) => ApiError::Duplicado {
+                mensaje: "Ya existe un dispositivo con ese nombre".to_string(),
+                restriccion,
+            },
+            _ => ApiError::escritura("Error al registrar dispositivo")(e),
+        }),
+    }
+}
+
+/// Handler que lista los dispositivos con su último latido y estado.
+pub async fn obtener_dispositivos(
+    pool: web::Data<Pool>,
+    config: web::Data<Config>,
+) -> Result<ApiResponse<Vec<Dispositivo>>, ApiError> {
+    let mut conn = db::conectar(&pool).await.map_err(ApiError::conexion)?;
+    let silencio = config.dispositivos_silencio;
+    let dispositivos = conn
+        .query_map(format!("{} ORDER BY sucursal, nombre", SELECT_DISPOSITIVOS), move |fila| {
+            Dispositivo::desde_fila(fila, silencio)
+        })
+        .await
+        .map_err(ApiError::base_datos("Error al obtener dispositivos"))?;
+    Ok(ApiResponse::ok(dispositivos))
+}
+
+/// Handler para obtener un dispositivo por su ID.
+pub async fn obtener_dispositivo(
+    pool: web::Data<Pool>,
+    config: web::Data<Config>,
+    id: web::Path<u32>,
+) -> Result<ApiResponse<Dispositivo>, ApiError> {
+    let mut conn = db::conectar(&pool).await.map_err(ApiError::conexion)?;
+    let fila = conn
+        .exec_first(format!("{} WHERE id = :id", SELECT_DISPOSITIVOS), params! { "id" => id.into_inner() })
+        .await
+        .map_err(ApiError::base_datos("Error al obtener dispositivo"))?
+        .ok_or_else(|| ApiError::NoEncontrado("Dispositivo no encontrado".to_string()))?;
+    Ok(ApiResponse::ok(Dispositivo::desde_fila(fila, config.dispositivos_silencio)))
+}
+
+/// Handler que registra un latido del dispositivo y, si se indica, su nueva versión.
+pub async fn registrar_latido(
+    pool: web::Data<Pool>,
+    id: web::Path<u32>,
+    latido: Option<Json<Latido>>,
+) -> Result<HttpResponse, ApiError> {
+    let version = latido.and_then(|l| l.into_inner().version);
+    let mut conn = db::conectar(&pool).await.map_err(ApiError::conexion)?;
+    conn.exec_drop(
+        "UPDATE dispositivos SET ultimo_latido = NOW(), version = COALESCE(:version, version) WHERE id = :id",
+        params! { "version" => version, "id" => id.into_inner() },
+    )
+    .await
+    .map_err(ApiError::base_datos("Error al registrar latido"))?;
+
+    if conn.affected_rows() == 0 {
+        return Err(ApiError::NoEncontrado("Dispositivo no encontrado".to_string()));
+    }
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Tarea que revisa los dispositivos cada mitad de `silencio` y avisa una sola vez de cada
+/// uno que deja de enviar latidos, y de nuevo cuando se recupera.
+pub fn vigilar_dispositivos(pool: Pool, silencio: Duration) -> (Intervalo, Trabajo) {
+    let silenciosos = Arc::new(Mutex::new(HashSet::new()));
+    let trabajo = tareas::trabajo(move || revisar_dispositivos(pool.clone(), silencio, silenciosos.clone()));
+    (Intervalo::cada((silencio / 2).max(Duration::from_secs(1))), trabajo)
+}
+
+/// Una revisión de [`vigilar_dispositivos`]; `silenciosos` son los ya avisados.
+async fn revisar_dispositivos(pool: Pool, silencio: Duration, silenciosos: Arc<Mutex<HashSet<u32>>>) -> Result<(), String> {
+    let dispositivos = match db::conectar(&pool).await {
+        Ok(mut conn) => {
+            conn.query_map(SELECT_DISPOSITIVOS, |fila| Dispositivo::desde_fila(fila, silencio))
+                .await
+        }
+        Err(e) => Err(e),
+    }
+    .map_err(|e| format!("Error al revisar dispositivos: {:?}", e))?;
+
+    let mut silenciosos = silenciosos.lock().unwrap();
+    for dispositivo in dispositivos {
+        let silencioso = dispositivo.estado == EstadoDispositivo::Silencioso;
+        if silencioso && silenciosos.insert(dispositivo.id) {
+            log::warn!(
+                "Alerta: el dispositivo '{}' de {} no envía latidos desde hace {} s",
+                dispositivo.nombre,
+                dispositivo.sucursal,
+                dispositivo.segundos_desde_latido.unwrap_or_default()
+            );
+        } else if !silencioso && silenciosos.remove(&dispositivo.id) {
+            log::info!("El dispositivo '{}' de {} vuelve a enviar latidos", dispositivo.nombre, dispositivo.sucursal);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,161 @@
+//! Enlaces de hipermedia en las respuestas, para que un cliente genérico recorra la API
+//! sin conocer el formato de sus rutas.
+//!
+//! Cada entrada lleva en `links` lo que se puede hacer con ella (`self`, `update`,
+//! `delete` y, si está pagada, `checkin`) y sus recursos relacionados (`funcion` y
+//! `cliente`), con el método de cada uno. Las entradas reducidas con `fields` no los
+//! llevan. Los listados traen en `meta.links` las rutas de sus páginas vecinas, con los
+//! mismos filtros (ver [`EnlacesPagina`]). Las rutas no incluyen el servidor; las de las
+//! entradas son siempre las de `/v1` y las de las páginas, la de la petición.
+
+use std::borrow::Borrow;
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::{Entrada, EstadoEntrada};
+use crate::respuesta::Paginacion;
+
+/// Una acción o un recurso relacionado.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Enlace {
+    pub href: String,
+    pub method: String,
+}
+
+impl Enlace {
+    fn new(method: &str, href: String) -> Self {
+        Enlace { href, method: method.to_string() }
+    }
+}
+
+/// Enlaces de una entrada.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EnlacesEntrada {
+    #[serde(rename = "self")]
+    pub propio: Enlace,
+    pub update: Enlace,
+    pub delete: Enlace,
+    /// Sólo mientras la entrada se puede usar.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checkin: Option<Enlace>,
+    pub funcion: Enlace,
+    pub cliente: Enlace,
+}
+
+impl EnlacesEntrada {
+    /// Enlaces de `entrada`; una entrada sin ID, como las simuladas, no tiene.
+    pub fn de(entrada: &Entrada) -> Option<Self> {
+        let ruta = format!("/v1/entradas/{}", entrada.id?);
+        Some(EnlacesEntrada {
+            propio: Enlace::new("GET", ruta.clone()),
+            update: Enlace::new("PATCH", ruta.clone()),
+            delete: Enlace::new("DELETE", ruta.clone()),
+            checkin: entrada
+                .estado
+                .puede_pasar_a(EstadoEntrada::Usada)
+                .then(|| Enlace::new("POST", format!("{}/checkin", ruta))),
+            funcion: Enlace::new("GET", format!("/v1/funciones/{}", entrada.funcion.id)),
+            cliente: Enlace::new("GET", format!("/v1/clientes/{}", entrada.cliente.numero_cedula)),
+        })
+    }
+}
+
+/// Una entrada con sus enlaces en `links`, tal como la devuelve la API.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConEnlaces<T> {
+    #[serde(flatten)]
+    pub dato: T,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub links: Option<EnlacesEntrada>,
+}
+
+impl<T: Borrow<Entrada>> ConEnlaces<T> {
+    pub fn new(entrada: T) -> Self {
+        let links = EnlacesEntrada::de(entrada.borrow());
+        ConEnlaces { dato: entrada, links }
+    }
+}
+
+/// Rutas de las páginas de un listado: la actual, la primera, la anterior y la siguiente
+/// si las hay y, con paginación por número, la última.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EnlacesPagina {
+    #[serde(rename = "self")]
+    pub propio: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prev: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last: Option<String>,
+}
+
+impl EnlacesPagina {
+    /// Enlaces de la página `paginacion` del listado en `ruta` con la consulta `consulta`.
+    pub fn por_numero(ruta: &str, consulta: &str, paginacion: &Paginacion) -> Self {
+        let pagina = |numero: u32| con_parametro(ruta, consulta, "page", Some(&numero.to_string()));
+        let ultima = paginacion.total.div_ceil(u64::from(paginacion.por_pagina.max(1))).max(1);
+        let ultima = u32::try_from(ultima).unwrap_or(u32::MAX);
+        EnlacesPagina {
+            propio: pagina(paginacion.pagina),
+            first: Some(pagina(1)),
+            prev: (paginacion.pagina > 1).then(|| pagina((paginacion.pagina - 1).min(ultima))),
+            next: (paginacion.pagina < ultima).then(|| pagina(paginacion.pagina + 1)),
+            last: Some(pagina(ultima)),
+        }
+    }
+
+    /// Enlaces de una página por cursor: la siguiente sigue tras `next_cursor`.
+    pub fn por_cursor(ruta: &str, consulta: &str, next_cursor: Option<&str>) -> Self {
+        EnlacesPagina {
+            propio: if consulta.is_empty() { ruta.to_string() } else { format!("{}?{}", ruta, consulta) },
+            first: Some(con_parametro(ruta, consulta, "after", None)),
+            prev: None,
+            next: next_cursor.map(|cursor| con_parametro(ruta, consulta, "after", Some(cursor))),
+            last: None,
+        }
+    }
+}
+
+/// `ruta` con la consulta `consulta`, sin el parámetro `nombre` o, con `valor`, con
+/// `nombre=valor` al final. `valor` tiene que ir ya codificado para una URL.
+fn con_parametro(ruta: &str, consulta: &str, nombre: &str, valor: Option<&str>) -> String {
+    let mut pares: Vec<String> = consulta
+        .split('&')
+        .filter(|par| !par.is_empty() && par.split('=').next() != Some(nombre))
+        .map(str::to_string)
+        .collect();
+    pares.extend(valor.map(|valor| format!("{}={}", nombre, valor)));
+    if pares.is_empty() {
+        ruta.to_string()
+    } else {
+        format!("{}?{}", ruta, pares.join("&"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn enlaza_las_paginas_vecinas_con_los_mismos_filtros() {
+        let paginacion = Paginacion { pagina: 2, por_pagina: 10, total: 25 };
+        let enlaces = EnlacesPagina::por_numero("/v1/entradas", "funcion_id=3&page=2&per_page=10", &paginacion);
+        assert_eq!(enlaces.propio, "/v1/entradas?funcion_id=3&per_page=10&page=2");
+        assert_eq!(enlaces.prev.as_deref(), Some("/v1/entradas?funcion_id=3&per_page=10&page=1"));
+        assert_eq!(enlaces.next.as_deref(), Some("/v1/entradas?funcion_id=3&per_page=10&page=3"));
+        assert_eq!(enlaces.last.as_deref(), Some("/v1/entradas?funcion_id=3&per_page=10&page=3"));
+
+        let ultima = Paginacion { pagina: 1, por_pagina: 10, total: 0 };
+        let enlaces = EnlacesPagina::por_numero("/v1/salas", "", &ultima);
+        assert_eq!((enlaces.prev, enlaces.next), (None, None));
+        assert_eq!(enlaces.last.as_deref(), Some("/v1/salas?page=1"));
+
+        let enlaces = EnlacesPagina::por_cursor("/v1/entradas", "limit=5&after=abc", Some("def"));
+        assert_eq!(enlaces.first.as_deref(), Some("/v1/entradas?limit=5"));
+        assert_eq!(enlaces.propio, "/v1/entradas?limit=5&after=abc");
+        assert_eq!(enlaces.next.as_deref(), Some("/v1/entradas?limit=5&after=def"));
+    }
+}
@@ -0,0 +1,341 @@
+//! Error común de la API y su respuesta JSON `{"error": {"code": ..., "message": ...}}`.
+//! Los errores de validación por campo añaden `"campos": [{"campo": ..., "mensaje": ...}]`
+//! y los de restricciones de la base de datos, `"restriccion"` con el nombre de la que falló.
+//! Los mensajes van en el idioma de la petición (ver [`crate::i18n`]).
+
+use std::fmt;
+
+use actix_web::http::header::{RETRY_AFTER, WWW_AUTHENTICATE};
+use actix_web::error::JsonPayloadError;
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+use serde_json::json;
+
+use crate::db::errores::{self, FalloMysql};
+use crate::i18n::Idioma;
+use crate::servicio::ErrorEntrada;
+use crate::validacion::ErrorCampo;
+
+/// Error devuelto por los handlers. El mensaje es el que recibe el cliente; el detalle
+/// de los fallos internos sólo se registra en el log.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApiError {
+    /// El recurso solicitado no existe (404).
+    NoEncontrado(String),
+    /// La petición no es válida (400).
+    Validacion(String),
+    /// Falta el token o no es válido (401).
+    NoAutorizado(String),
+    /// La sesión no tiene permiso para la operación (403).
+    Prohibido(String),
+    /// Uno o más campos del cuerpo no son válidos (422).
+    CamposInvalidos(Vec<ErrorCampo>),
+    /// La petición choca con el estado actual, por ejemplo una cédula repetida (409).
+    Conflicto(String),
+    /// Ya existe un recurso con ese valor: `restriccion` es el índice único repetido (409).
+    Duplicado { mensaje: String, restriccion: String },
+    /// Otros recursos dependen del que se quiere eliminar por la clave foránea `restriccion` (409).
+    EnUso { mensaje: String, restriccion: String },
+    /// La petición apunta a un recurso que no existe, según la clave foránea `restriccion` (422).
+    ReferenciaInexistente { mensaje: String, restriccion: String },
+    /// La función no tiene asientos suficientes; quedan los indicados (409).
+    SinCapacidad(u32),
+    /// La cédula llegó al máximo de entradas por función; puede comprar las indicadas (409).
+    LimiteCedula(u32),
+    /// El recurso cambió desde que el cliente lo leyó: `If-Match` no coincide (412).
+    PrecondicionFallida(String),
+    /// Falta la cabecera `If-Match` que exige la operación (428).
+    PrecondicionRequerida(String),
+    /// La petición no terminó en el tiempo máximo configurado (408).
+    TiempoAgotado(String),
+    /// El cuerpo supera el tamaño máximo aceptado (413).
+    CuerpoDemasiadoGrande(String),
+    /// El cuerpo no es de un tipo que la operación acepte (415).
+    TipoNoAdmitido(String),
+    /// El cliente superó su límite de peticiones; puede reintentar en los segundos indicados (429).
+    DemasiadasPeticiones(u64),
+    /// La base de datos abortó la operación por un bloqueo; se puede reintentar (503).
+    Contencion(String),
+    /// Falló la base de datos (500).
+    BaseDatos(String),
+}
+
+impl ApiError {
+    /// Código estable que los clientes pueden usar en lugar del mensaje.
+    pub fn codigo(&self) -> &'static str {
+        match self {
+            ApiError::NoEncontrado(_) => "no_encontrado",
+            ApiError::Validacion(_) => "validacion",
+            ApiError::NoAutorizado(_) => "no_autorizado",
+            ApiError::Prohibido(_) => "prohibido",
+            ApiError::CamposInvalidos(_) => "campos_invalidos",
+            ApiError::Conflicto(_) => "conflicto",
+            ApiError::Duplicado { .. } => "duplicado",
+            ApiError::EnUso { .. } => "en_uso",
+            ApiError::ReferenciaInexistente { .. } => "referencia_inexistente",
+            ApiError::SinCapacidad(_) => "sin_capacidad",
+            ApiError::LimiteCedula(_) => "limite_cedula",
+            ApiError::PrecondicionFallida(_) => "precondicion_fallida",
+            ApiError::PrecondicionRequerida(_) => "precondicion_requerida",
+            ApiError::TiempoAgotado(_) => "tiempo_agotado",
+            ApiError::CuerpoDemasiadoGrande(_) => "cuerpo_demasiado_grande",
+            ApiError::TipoNoAdmitido(_) => "tipo_no_admitido",
+            ApiError::DemasiadasPeticiones(_) => "demasiadas_peticiones",
+            ApiError::Contencion(_) => "contencion",
+            ApiError::BaseDatos(_) => "base_datos",
+        }
+    }
+
+    /// Objeto `{"code": ..., "message": ...}` que va bajo `"error"` en la respuesta.
+    pub fn cuerpo(&self) -> serde_json::Value {
+        self.cuerpo_en(Idioma::Es)
+    }
+
+    /// Como [`ApiError::cuerpo`], con los mensajes en `idioma`.
+    pub fn cuerpo_en(&self, idioma: Idioma) -> serde_json::Value {
+        let mut error = json!({ "code": self.codigo(), "message": idioma.traducir(&self.to_string()) });
+        match self {
+            ApiError::CamposInvalidos(campos) => {
+                let campos: Vec<ErrorCampo> = campos
+                    .iter()
+                    .map(|campo| ErrorCampo {
+                        campo: campo.campo.clone(),
+                        mensaje: idioma.traducir(&campo.mensaje).into_owned(),
+                    })
+                    .collect();
+                error["campos"] = json!(campos);
+            }
+            ApiError::SinCapacidad(disponibles) => error["disponibles"] = json!(disponibles),
+            ApiError::LimiteCedula(restantes) => error["restantes"] = json!(restantes),
+            ApiError::Duplicado { restriccion, .. }
+            | ApiError::EnUso { restriccion, .. }
+            | ApiError::ReferenciaInexistente { restriccion, .. } => error["restriccion"] = json!(restriccion),
+            _ => {}
+        }
+        error
+    }
+
+    /// Error al obtener una conexión de la pool.
+    pub fn conexion(e: mysql_async::Error) -> ApiError {
+        log::error!("Error al obtener conexión: {:?}", e);
+        ApiError::BaseDatos("Error al conectar a la base de datos".to_string())
+    }
+
+    /// Convierte un error de consulta, registrándolo en el log con `mensaje`, que es
+    /// también lo que verá el cliente.
+    pub fn base_datos(mensaje: &'static str) -> impl FnOnce(mysql_async::Error) -> ApiError {
+        move |e| {
+            log::error!("{}: {:?}", mensaje, e);
+            ApiError::BaseDatos(mensaje.to_string())
+        }
+    }
+
+    /// Como [`ApiError::base_datos`], pero las violaciones de restricciones, los valores
+    /// demasiado largos y los bloqueos tienen su propio error. Los handlers que conocen la
+    /// restricción afectada usan [`db::errores::clasificar`] para dar un mensaje más claro.
+    pub fn escritura(mensaje: &'static str) -> impl FnOnce(mysql_async::Error) -> ApiError {
+        move |e| match errores::clasificar(&e) {
+            FalloMysql::Duplicado(restriccion) => ApiError::Duplicado {
+                mensaje: "Ya existe un registro con ese valor".to_string(),
+                restriccion,
+            },
+            FalloMysql::Referenciada(restriccion) => ApiError::EnUso {
+                mensaje: "Hay otros registros que dependen de este".to_string(),
+                restriccion,
+            },
+            FalloMysql::ReferenciaInexistente(restriccion) => ApiError::ReferenciaInexistente {
+                mensaje: "Uno de los recursos indicados no existe".to_string(),
+                restriccion,
+            },
+            FalloMysql::DemasiadoLargo(columna) => ApiError::CamposInvalidos(vec![ErrorCampo {
+                campo: columna.into(),
+                mensaje: "El valor es demasiado largo".to_string(),
+            }]),
+            FalloMysql::Bloqueo => {
+                log::error!("{}: {:?}", mensaje, e);
+                ApiError::contencion()
+            }
+            FalloMysql::ConexionPerdida | FalloMysql::Otro => ApiError::base_datos(mensaje)(e),
+        }
+    }
+
+    /// Error de una operación abortada por un bloqueo de la base de datos.
+    pub fn contencion() -> ApiError {
+        ApiError::Contencion("La base de datos está ocupada; reintenta la operación".to_string())
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::NoEncontrado(mensaje)
+            | ApiError::Validacion(mensaje)
+            | ApiError::NoAutorizado(mensaje)
+            | ApiError::Prohibido(mensaje)
+            | ApiError::Conflicto(mensaje)
+            | ApiError::Duplicado { mensaje, .. }
+            | ApiError::EnUso { mensaje, .. }
+            | ApiError::ReferenciaInexistente { mensaje, .. }
+            | ApiError::PrecondicionFallida(mensaje)
+            | ApiError::PrecondicionRequerida(mensaje)
+            | ApiError::TiempoAgotado(mensaje)
+            | ApiError::CuerpoDemasiadoGrande(mensaje)
+            | ApiError::TipoNoAdmitido(mensaje)
+            | ApiError::Contencion(mensaje)
+            | ApiError::BaseDatos(mensaje) => f.write_str(mensaje),
+            ApiError::CamposInvalidos(_) => f.write_str("La entrada contiene campos inválidos"),
+            ApiError::SinCapacidad(disponibles) => {
+                write!(f, "Sólo quedan {} asientos disponibles para la función", disponibles)
+            }
+            ApiError::LimiteCedula(restantes) => {
+                write!(f, "La cédula sólo puede comprar {} entradas más para la función", restantes)
+            }
+            ApiError::DemasiadasPeticiones(segundos) => {
+                write!(f, "Demasiadas peticiones, reintenta en {} s", segundos)
+            }
+        }
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::NoEncontrado(_) => StatusCode::NOT_FOUND,
+            ApiError::Validacion(_) => StatusCode::BAD_REQUEST,
+            ApiError::NoAutorizado(_) => StatusCode::UNAUTHORIZED,
+            ApiError::Prohibido(_) => StatusCode::FORBIDDEN,
+            ApiError::CamposInvalidos(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::Conflicto(_)
+            | ApiError::Duplicado { .. }
+            | ApiError::EnUso { .. }
+            | ApiError::SinCapacidad(_)
+            | ApiError::LimiteCedula(_) => StatusCode::CONFLICT,
+            ApiError::ReferenciaInexistente { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::PrecondicionFallida(_) => StatusCode::PRECONDITION_FAILED,
+            ApiError::PrecondicionRequerida(_) => StatusCode::PRECONDITION_REQUIRED,
+            ApiError::TiempoAgotado(_) => StatusCode::REQUEST_TIMEOUT,
+            ApiError::CuerpoDemasiadoGrande(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            ApiError::TipoNoAdmitido(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ApiError::DemasiadasPeticiones(_) => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::Contencion(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::BaseDatos(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        self.respuesta(Idioma::Es)
+    }
+}
+
+impl ApiError {
+    /// Respuesta del error con los mensajes en `idioma`.
+    pub fn respuesta(&self, idioma: Idioma) -> HttpResponse {
+        let mut respuesta = HttpResponse::build(self.status_code());
+        match self {
+            ApiError::NoAutorizado(_) => {
+                respuesta.insert_header((WWW_AUTHENTICATE, "Bearer"));
+            }
+            ApiError::DemasiadasPeticiones(segundos) => {
+                respuesta.insert_header((RETRY_AFTER, *segundos));
+            }
+            ApiError::Contencion(_) => {
+                respuesta.insert_header((RETRY_AFTER, 1));
+            }
+            _ => {}
+        }
+        respuesta.json(json!({ "error": self.cuerpo_en(idioma) }))
+    }
+}
+
+impl From<ErrorEntrada> for ApiError {
+    fn from(error: ErrorEntrada) -> Self {
+        let mensaje = error.to_string();
+        match error {
+            ErrorEntrada::NoEncontrada => ApiError::NoEncontrado(mensaje),
+            ErrorEntrada::SinDatos | ErrorEntrada::ParametrosInvalidos(_) => ApiError::Validacion(mensaje),
+            ErrorEntrada::CamposInvalidos(campos) => ApiError::CamposInvalidos(campos),
+            ErrorEntrada::AsientosOcupados(_)
+            | ErrorEntrada::AsientosAsignados
+            | ErrorEntrada::TransicionInvalida(_) => ApiError::Conflicto(mensaje),
+            ErrorEntrada::AsientoInexistente(_) => {
+                ApiError::CamposInvalidos(vec![ErrorCampo { campo: "asientos".into(), mensaje }])
+            }
+            ErrorEntrada::SinCapacidad(disponibles) => ApiError::SinCapacidad(disponibles),
+            ErrorEntrada::LimiteCedula(restantes) => ApiError::LimiteCedula(restantes),
+            ErrorEntrada::VersionDistinta(_) => ApiError::PrecondicionFallida(mensaje),
+            ErrorEntrada::ClienteInexistente => {
+                ApiError::CamposInvalidos(vec![ErrorCampo { campo: "cliente_id".into(), mensaje }])
+            }
+            ErrorEntrada::FuncionInexistente => {
+                ApiError::CamposInvalidos(vec![ErrorCampo { campo: "funcion_id".into(), mensaje }])
+            }
+            ErrorEntrada::ValorDemasiadoLargo(columna) => ApiError::CamposInvalidos(vec![ErrorCampo {
+                campo: columna.into(),
+                mensaje,
+            }]),
+            ErrorEntrada::Contencion => ApiError::Contencion(mensaje),
+            ErrorEntrada::Interno(_) => ApiError::BaseDatos(mensaje),
+        }
+    }
+}
+
+/// Errores de los extractores (`Json`, `Query`, `Path`) con el mismo formato que el resto.
+pub fn error_extractor(error: impl fmt::Display) -> actix_web::Error {
+    ApiError::Validacion(error.to_string()).into()
+}
+
+/// Como [`error_extractor`], pero un cuerpo JSON por encima del límite es un 413.
+pub fn error_json(error: JsonPayloadError) -> actix_web::Error {
+    match error {
+        JsonPayloadError::Overflow { limit } | JsonPayloadError::OverflowKnownLength { limit, .. } => {
+            ApiError::CuerpoDemasiadoGrande(format!("El cuerpo supera el máximo de {} bytes", limit)).into()
+        }
+        error => error_extractor(error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::body::to_bytes;
+
+    #[actix_web::test]
+    async fn responde_con_codigo_y_mensaje() {
+        let error = ApiError::Duplicado {
+            mensaje: "Ya existe un cliente con ese número de cédula".to_string(),
+            restriccion: "uq_clientes_cine_cedula".to_string(),
+        };
+        let respuesta = error.error_response();
+        assert_eq!(respuesta.status(), StatusCode::CONFLICT);
+
+        let cuerpo = to_bytes(respuesta.into_body()).await.unwrap();
+        let cuerpo: serde_json::Value = serde_json::from_slice(&cuerpo).unwrap();
+        assert_eq!(
+            cuerpo,
+            json!({ "error": {
+                "code": "duplicado",
+                "message": "Ya existe un cliente con ese número de cédula",
+                "restriccion": "uq_clientes_cine_cedula",
+            } })
+        );
+    }
+
+    #[actix_web::test]
+    async fn sin_capacidad_informa_los_asientos_que_quedan() {
+        let respuesta = ApiError::from(ErrorEntrada::SinCapacidad(3)).error_response();
+        assert_eq!(respuesta.status(), StatusCode::CONFLICT);
+
+        let cuerpo = to_bytes(respuesta.into_body()).await.unwrap();
+        let cuerpo: serde_json::Value = serde_json::from_slice(&cuerpo).unwrap();
+        assert_eq!(cuerpo["error"]["code"], "sin_capacidad");
+        assert_eq!(cuerpo["error"]["disponibles"], 3);
+    }
+
+    #[test]
+    fn un_json_demasiado_grande_es_un_413() {
+        let error = error_json(JsonPayloadError::OverflowKnownLength { length: 10, limit: 4 });
+        assert_eq!(error.as_response_error().status_code(), StatusCode::PAYLOAD_TOO_LARGE);
+        let error = error_json(JsonPayloadError::ContentType);
+        assert_eq!(error.as_response_error().status_code(), StatusCode::BAD_REQUEST);
+    }
+}
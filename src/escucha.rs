@@ -0,0 +1,109 @@
+//! Direcciones en las que escucha el servidor HTTP.
+//!
+//! Sin configurar nada se escucha en `host:port`. `ESCUCHAR` (o `escuchar` en el archivo)
+//! sustituye esa dirección por una lista: direcciones TCP como `0.0.0.0:8080` o
+//! `[::1]:8080` y sockets Unix como `unix:/run/rust-crud/http.sock`, para un proxy inverso
+//! en la misma máquina. HTTPS y gRPC siguen escuchando en `host`, con sus puertos.
+//!
+//! Un socket Unix que quedó de una ejecución anterior se borra al arrancar. Las peticiones
+//! que llegan por él no tienen IP de origen, así que no cuentan contra el límite por IP.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+/// Prefijo de los sockets Unix en la lista de direcciones.
+const PREFIJO_UNIX: &str = "unix:";
+
+/// Una dirección del listener HTTP.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Escucha {
+    /// `host:puerto`, con el host como IP o nombre.
+    Tcp(String),
+    /// Ruta del socket.
+    Unix(String),
+}
+
+impl Escucha {
+    /// Convierte una lista separada por comas, como la de `ESCUCHAR`.
+    pub fn parsear_lista(valor: &str) -> Result<Vec<Escucha>, String> {
+        valor
+            .split(',')
+            .map(str::trim)
+            .filter(|direccion| !direccion.is_empty())
+            .map(str::parse)
+            .collect()
+    }
+}
+
+impl FromStr for Escucha {
+    type Err = String;
+
+    fn from_str(valor: &str) -> Result<Self, Self::Err> {
+        if let Some(ruta) = valor.strip_prefix(PREFIJO_UNIX) {
+            return match ruta.trim() {
+                "" => Err(format!("Falta la ruta del socket en '{}'", valor)),
+                ruta => Ok(Escucha::Unix(ruta.to_string())),
+            };
+        }
+        match valor.rsplit_once(':') {
+            Some((host, puerto)) if !host.is_empty() && puerto.parse::<u16>().is_ok() => {
+                Ok(Escucha::Tcp(valor.to_string()))
+            }
+            _ => Err(format!("Dirección inválida '{}': use host:puerto o unix:/ruta", valor)),
+        }
+    }
+}
+
+impl TryFrom<String> for Escucha {
+    type Error = String;
+
+    fn try_from(valor: String) -> Result<Self, Self::Error> {
+        valor.parse()
+    }
+}
+
+impl fmt::Display for Escucha {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Escucha::Tcp(direccion) => write!(f, "http://{}", direccion),
+            Escucha::Unix(ruta) => write!(f, "{}{}", PREFIJO_UNIX, ruta),
+        }
+    }
+}
+
+/// Borra el socket de `ruta` si quedó de una ejecución anterior, para poder volver a
+/// enlazarlo. No toca lo que no sea un socket.
+#[cfg(unix)]
+pub fn borrar_socket_anterior(ruta: &str) -> std::io::Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+
+    match std::fs::symlink_metadata(ruta) {
+        Ok(metadatos) if metadatos.file_type().is_socket() => std::fs::remove_file(ruta),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lee_direcciones_tcp_y_sockets_unix() {
+        let direcciones = Escucha::parsear_lista("0.0.0.0:8080, [::1]:8081,unix:/run/crud.sock").unwrap();
+        assert_eq!(
+            direcciones,
+            [
+                Escucha::Tcp("0.0.0.0:8080".to_string()),
+                Escucha::Tcp("[::1]:8081".to_string()),
+                Escucha::Unix("/run/crud.sock".to_string()),
+            ]
+        );
+        assert_eq!(direcciones[2].to_string(), "unix:/run/crud.sock");
+        assert!("0.0.0.0".parse::<Escucha>().is_err());
+        assert!("localhost:http".parse::<Escucha>().is_err());
+        assert!("unix:".parse::<Escucha>().is_err());
+    }
+}
@@ -0,0 +1,228 @@
+//! Exportación de entradas a CSV y XLSX para `GET /entradas/export`.
+//!
+//! En CSV, cada fila se escribe por separado para transmitirlas a medida que llegan de la
+//! base de datos. Los campos de texto con separadores, comillas o saltos de línea van entre
+//! comillas, y los que empiezan por `=`, `+`, `-` o `@` llevan delante un apóstrofo para que
+//! la hoja de cálculo no los tome por fórmulas.
+//!
+//! El XLSX es un ZIP que se arma entero en memoria: una hoja con las entradas y otra con
+//! los totales de cada función, agrupadas por fecha.
+
+use std::collections::BTreeMap;
+
+use chrono::SecondsFormat;
+use rust_xlsxwriter::{Color, Format, FormatBorder, Workbook, Worksheet, XlsxError};
+
+use crate::models::{Entrada, EstadoEntrada};
+
+/// Primera línea del CSV, con el nombre de cada columna.
+pub const ENCABEZADO_CSV: &str = "id,estado,numero_cedula,nombre_cliente,funcion_id,nombre_funcion,sala,\
+                                  horario_funcion,cantidad_entradas,total,created_at,updated_at\r\n";
+
+/// Escribe un campo de texto en `linea`, escapándolo si hace falta.
+fn escribir_texto(linea: &mut String, valor: &str) {
+    let formula = valor.starts_with(['=', '+', '-', '@']);
+    if formula || valor.contains([',', '"', '\n', '\r']) {
+        linea.push('"');
+        if formula {
+            linea.push('\'');
+        }
+        linea.push_str(&valor.replace('"', "\"\""));
+        linea.push('"');
+    } else {
+        linea.push_str(valor);
+    }
+}
+
+/// Línea del CSV de una entrada, con su salto de línea, en el orden de [`ENCABEZADO_CSV`].
+pub fn fila_csv(entrada: &Entrada) -> String {
+    let funcion = &entrada.funcion;
+    let mut linea = format!("{},{},", entrada.id.unwrap_or_default(), entrada.estado.como_str());
+    escribir_texto(&mut linea, &entrada.cliente.numero_cedula);
+    linea.push(',');
+    escribir_texto(&mut linea, &entrada.cliente.nombre);
+    linea.push_str(&format!(",{},", funcion.id));
+    escribir_texto(&mut linea, &funcion.titulo);
+    linea.push(',');
+    escribir_texto(&mut linea, &funcion.sala.nombre);
+    linea.push_str(&format!(
+        ",{},{},{:.2},{},{}\r\n",
+        funcion.horario.to_rfc3339_opts(SecondsFormat::Secs, true),
+        entrada.cantidad_entradas,
+        entrada.total,
+        entrada.created_at,
+        entrada.updated_at
+    ));
+    linea
+}
+
+/// Columnas de la hoja de entradas del XLSX, con su ancho, en el orden de [`ENCABEZADO_CSV`].
+const COLUMNAS_ENTRADAS: [(&str, f64); 12] = [
+    ("ID", 8.0),
+    ("Estado", 11.0),
+    ("Cédula", 13.0),
+    ("Cliente", 28.0),
+    ("ID función", 11.0),
+    ("Función", 28.0),
+    ("Sala", 12.0),
+    ("Horario", 17.0),
+    ("Cantidad", 10.0),
+    ("Total", 11.0),
+    ("Alta", 20.0),
+    ("Último cambio", 20.0),
+];
+
+/// Columnas de la hoja de resumen.
+const COLUMNAS_RESUMEN: [(&str, f64); 7] = [
+    ("Fecha", 12.0),
+    ("Función", 28.0),
+    ("Sala", 12.0),
+    ("Horario", 9.0),
+    ("Entradas", 10.0),
+    ("Cantidad", 10.0),
+    ("Total", 12.0),
+];
+
+/// Totales de una función en la hoja de resumen.
+#[derive(Default)]
+struct TotalFuncion {
+    titulo: String,
+    sala: String,
+    hora: String,
+    entradas: u32,
+    cantidad: u32,
+    total: f64,
+}
+
+/// Escribe la fila de encabezado con su formato, fija su ancho a cada columna y la deja
+/// inmóvil al desplazarse.
+fn encabezado(hoja: &mut Worksheet, columnas: &[(&str, f64)], formato: &Format) -> Result<(), XlsxError> {
+    for (columna, (nombre, ancho)) in (0..).zip(columnas) {
+        hoja.write_with_format(0, columna, *nombre, formato)?;
+        hoja.set_column_width(columna, *ancho)?;
+    }
+    hoja.set_freeze_panes(1, 0)?;
+    Ok(())
+}
+
+/// Libro XLSX con la hoja `Entradas` y la hoja `Resumen`, con los totales de cada función
+/// por fecha y el total general. El resumen no cuenta las entradas canceladas.
+pub fn libro_xlsx(entradas: &[Entrada]) -> Result<Vec<u8>, XlsxError> {
+    let titulo = Format::new().set_bold().set_font_color(Color::White).set_background_color(Color::RGB(0x1F4E78));
+    let dinero = Format::new().set_num_format("#,##0.00");
+    let suma = Format::new().set_bold().set_border_top(FormatBorder::Thin);
+    let suma_dinero = suma.clone().set_num_format("#,##0.00");
+
+    let mut libro = Workbook::new();
+    let hoja = libro.add_worksheet().set_name("Entradas")?;
+    encabezado(hoja, &COLUMNAS_ENTRADAS, &titulo)?;
+    let mut resumen: BTreeMap<(String, String, u32), TotalFuncion> = BTreeMap::new();
+    for (fila, entrada) in (1..).zip(entradas) {
+        let funcion = &entrada.funcion;
+        hoja.write(fila, 0, entrada.id.unwrap_or_default())?;
+        hoja.write(fila, 1, entrada.estado.como_str())?;
+        hoja.write(fila, 2, &entrada.cliente.numero_cedula)?;
+        hoja.write(fila, 3, &entrada.cliente.nombre)?;
+        hoja.write(fila, 4, funcion.id)?;
+        hoja.write(fila, 5, &funcion.titulo)?;
+        hoja.write(fila, 6, &funcion.sala.nombre)?;
+        hoja.write(fila, 7, funcion.horario.format("%Y-%m-%d %H:%M").to_string())?;
+        hoja.write(fila, 8, entrada.cantidad_entradas)?;
+        hoja.write_with_format(fila, 9, entrada.total, &dinero)?;
+        hoja.write(fila, 10, &entrada.created_at)?;
+        hoja.write(fila, 11, &entrada.updated_at)?;
+
+        if entrada.estado != EstadoEntrada::Cancelada {
+            let hora = funcion.horario.format("%H:%M").to_string();
+            let clave = (funcion.horario.format("%Y-%m-%d").to_string(), hora.clone(), funcion.id);
+            let totales = resumen.entry(clave).or_insert_with(|| TotalFuncion {
+                titulo: funcion.titulo.clone(),
+                sala: funcion.sala.nombre.clone(),
+                hora,
+                ..Default::default()
+            });
+            totales.entradas += 1;
+            totales.cantidad += entrada.cantidad_entradas;
+            totales.total += entrada.total;
+        }
+    }
+    hoja.autofilter(0, 0, entradas.len() as u32, COLUMNAS_ENTRADAS.len() as u16 - 1)?;
+
+    let hoja = libro.add_worksheet().set_name("Resumen")?;
+    encabezado(hoja, &COLUMNAS_RESUMEN, &titulo)?;
+    let mut fila = 1;
+    for ((fecha, _, _), totales) in &resumen {
+        hoja.write(fila, 0, fecha)?;
+        hoja.write(fila, 1, &totales.titulo)?;
+        hoja.write(fila, 2, &totales.sala)?;
+        hoja.write(fila, 3, &totales.hora)?;
+        hoja.write(fila, 4, totales.entradas)?;
+        hoja.write(fila, 5, totales.cantidad)?;
+        hoja.write_with_format(fila, 6, totales.total, &dinero)?;
+        fila += 1;
+    }
+    hoja.write_with_format(fila, 0, "Total", &suma)?;
+    for columna in 1..=3 {
+        hoja.write_blank(fila, columna, &suma)?;
+    }
+    hoja.write_with_format(fila, 4, resumen.values().map(|t| t.entradas).sum::<u32>(), &suma)?;
+    hoja.write_with_format(fila, 5, resumen.values().map(|t| t.cantidad).sum::<u32>(), &suma)?;
+    hoja.write_with_format(fila, 6, resumen.values().map(|t| t.total).sum::<f64>(), &suma_dinero)?;
+
+    libro.save_to_buffer()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+
+    use super::*;
+    use crate::models::{Cliente, Funcion, Sala};
+
+    fn entrada(id: u32, estado: EstadoEntrada) -> Entrada {
+        Entrada {
+            id: Some(id),
+            cantidad_entradas: 2,
+            total: 13.0,
+            estado,
+            cliente: Cliente { id, numero_cedula: "1710034065".into(), nombre: "Pérez, María".into(), email: None },
+            funcion: Funcion {
+                id: 1,
+                titulo: "Dune: Parte Dos".into(),
+                horario: DateTime::parse_from_rfc3339("2024-03-01T19:00:00-05:00").unwrap(),
+                precio: 6.5,
+                sala: Sala { id: 1, nombre: "Sala 1".into(), capacidad: 120 },
+                poster: None,
+            },
+            version: 1,
+            created_at: "2024-03-01 18:30:00".into(),
+            updated_at: "2024-03-01 18:30:00".into(),
+        }
+    }
+
+    #[test]
+    fn cada_entrada_es_una_linea_csv() {
+        assert_eq!(
+            fila_csv(&entrada(7, EstadoEntrada::Pagada)),
+            "7,pagada,1710034065,\"Pérez, María\",1,Dune: Parte Dos,Sala 1,2024-03-01T19:00:00-05:00,2,13.00,\
+             2024-03-01 18:30:00,2024-03-01 18:30:00\r\n"
+        );
+    }
+
+    #[test]
+    fn genera_el_libro_xlsx() {
+        let entradas = [entrada(1, EstadoEntrada::Pagada), entrada(2, EstadoEntrada::Cancelada)];
+        assert!(libro_xlsx(&entradas).unwrap().starts_with(b"PK"));
+        assert!(libro_xlsx(&[]).unwrap().starts_with(b"PK"));
+    }
+
+    #[test]
+    fn escapa_separadores_comillas_y_formulas() {
+        let mut linea = String::new();
+        for valor in ["Sala 1", "Pérez, María", "El \"Padrino\"", "=HYPERLINK(\"x\")"] {
+            escribir_texto(&mut linea, valor);
+            linea.push('|');
+        }
+        assert_eq!(linea, "Sala 1|\"Pérez, María\"|\"El \"\"Padrino\"\"\"|\"'=HYPERLINK(\"\"x\"\")\"|");
+    }
+}
@@ -0,0 +1,345 @@
+//! Funciones de cine (`/funciones`): película, sala, horario y precio. La capacidad es la
+//! de su sala (ver [`crate::salas`]), y el cine también: sólo se ven las funciones en salas
+//! del cine de la petición. El horario se guarda en UTC y se muestra en la zona de
+//! [`Config::zona_horaria`].
+//!
+//! Cada entrada referencia su función por `funcion_id`, así que una función con entradas
+//! vendidas no se puede eliminar.
+
+use std::sync::Arc;
+
+use actix_web::{web, HttpResponse};
+use mysql_async::{prelude::*, Pool};
+use serde::Deserialize;
+
+use crate::auth::Administrador;
+use crate::cache::CacheLecturas;
+use crate::cines::CineActual;
+use crate::config::Config;
+use crate::db;
+use crate::db::errores::{self, FalloMysql};
+use crate::error::ApiError;
+use crate::json::Json;
+use crate::listado::escapar_like;
+use crate::models::{AsientosFuncion, CrearFuncion, Funcion};
+use crate::respuesta::ApiResponse;
+use crate::validacion::{ErrorCampo, Validar};
+
+const SELECT_FUNCIONES: &str = "SELECT f.id, f.titulo, f.horario, f.precio, s.id, s.nombre, s.capacidad, f.poster \
+                                FROM funciones f JOIN salas s ON s.id = f.sala_id";
+
+/// Parámetros de consulta de `GET /funciones`.
+#[derive(Debug, Default, Deserialize)]
+pub struct ParametrosFunciones {
+    pub titulo: Option<String>,
+}
+
+/// Clasifica un error al escribir una función, distinguiendo las repetidas y las salas
+/// que no existen.
+fn error_escritura(mensaje: &'static str) -> impl FnOnce(mysql_async::Error) -> ApiError {
+    move |e| match errores::clasificar(&e) {
+        FalloMysql::Duplicado(restriccion) => ApiError::Duplicado {
+            mensaje: "Ya existe una función con ese título en la misma sala y horario".to_string(),
+            restriccion,
+        },
+        FalloMysql::ReferenciaInexistente(restriccion) if restriccion == "fk_funciones_sala" => {
+            ApiError::CamposInvalidos(vec![ErrorCampo {
+                campo: "sala_id".into(),
+                mensaje: "La sala indicada no existe".to_string(),
+            }])
+        }
+        _ => ApiError::escritura(mensaje)(e),
+    }
+}
+
+fn no_encontrada() -> ApiError {
+    ApiError::NoEncontrado("Función no encontrada".to_string())
+}
+
+/// Lee una función del cine `cine` por su ID, con su sala.
+pub(crate) async fn leer_funcion(conn: &mut db::Conexion, cine: u32, id: u32) -> Result<Option<Funcion>, mysql_async::Error> {
+    conn.exec_first(
+        format!("{} WHERE f.id = :id AND s.cine_id = :cine_id", SELECT_FUNCIONES),
+        params! { "id" => id, "cine_id" => cine },
+    )
+    .await
+}
+
+/// Comprueba que la sala de la función sea del cine `cine`. Las de otros cines se tratan
+/// como inexistentes, igual que la clave foránea con las que no existen.
+async fn verificar_sala(conn: &mut db::Conexion, cine: u32, sala_id: u32, mensaje: &'static str) -> Result<(), ApiError> {
+    let sala: Option<u32> = conn
+        .exec_first(
+            "SELECT id FROM salas WHERE id = :id AND cine_id = :cine_id",
+            params! { "id" => sala_id, "cine_id" => cine },
+        )
+        .await
+        .map_err(ApiError::base_datos(mensaje))?;
+    sala.map(|_| ()).ok_or_else(|| {
+        ApiError::CamposInvalidos(vec![ErrorCampo {
+            campo: "sala_id".into(),
+            mensaje: "La sala indicada no existe".to_string(),
+        }])
+    })
+}
+
+pub(crate) fn validar(datos: &CrearFuncion, config: &Config) -> Result<(), ApiError> {
+    datos.validar(&config.reglas_validacion()).map_err(ApiError::CamposInvalidos)
+}
+
+/// Handler que lista las funciones por título y horario, opcionalmente de un solo título.
+/// El listado se reutiliza desde [`CacheLecturas`] mientras no cambie.
+pub async fn listar_funciones(
+    CineActual(cine): CineActual,
+    pool: web::Data<Pool>,
+    config: web::Data<Config>,
+    cache: web::Data<CacheLecturas>,
+    query: web::Query<ParametrosFunciones>,
+) -> Result<ApiResponse<Vec<Funcion>>, ApiError> {
+    let en_zona = |funciones: &[Funcion]| {
+        funciones.iter().map(|funcion| funcion.clone().en_zona(config.zona_horaria)).collect()
+    };
+    let clave = (cine, query.into_inner().titulo);
+    if let Some(funciones) = cache.funciones.obtener(&clave).await {
+        return Ok(ApiResponse::ok(en_zona(&funciones)));
+    }
+    let mut conn = db::conectar(&pool).await.map_err(ApiError::conexion)?;
+    let funciones = match &clave.1 {
+        Some(titulo) => {
+            conn.exec(
+                format!(
+                    "{} WHERE s.cine_id = :cine_id AND f.titulo = :titulo ORDER BY f.titulo, f.horario, s.nombre",
+                    SELECT_FUNCIONES
+                ),
+                params! { "cine_id" => cine, "titulo" => titulo },
+            )
+            .await
+        }
+        None => {
+            conn.exec(
+                format!("{} WHERE s.cine_id = :cine_id ORDER BY f.titulo, f.horario, s.nombre", SELECT_FUNCIONES),
+                params! { "cine_id" => cine },
+            )
+            .await
+        }
+    }
+    .map_err(ApiError::base_datos("Error al obtener funciones"))?;
+    let respuesta = ApiResponse::ok(en_zona(&funciones));
+    cache.funciones.guardar(clave, Arc::new(funciones)).await;
+    Ok(respuesta)
+}
+
+/// Handler para obtener una función por su ID.
+pub async fn obtener_funcion(
+    CineActual(cine): CineActual,
+    pool: web::Data<Pool>,
+    config: web::Data<Config>,
+    id: web::Path<u32>,
+) -> Result<ApiResponse<Funcion>, ApiError> {
+    let mut conn = db::conectar(&pool).await.map_err(ApiError::conexion)?;
+    leer_funcion(&mut conn, cine, id.into_inner())
+        .await
+        .map_err(ApiError::base_datos("Error al obtener función"))?
+        .map(|funcion| ApiResponse::ok(funcion.en_zona(config.zona_horaria)))
+        .ok_or_else(no_encontrada)
+}
+
+/// Asientos libres de una función y entradas que aún se pueden vender, contando también las
+/// vendidas sin numerar. Las canceladas no ocupan sitio. `None` si la función no existe o,
+/// con `cine`, si no es de ese cine.
+pub async fn leer_asientos(
+    conn: &mut db::Conexion,
+    cine: Option<u32>,
+    id: u32,
+) -> Result<Option<AsientosFuncion>, mysql_async::Error> {
+    let Some((capacidad, vendidas)): Option<(u32, u64)> = conn
+        .exec_first(
+            "SELECT s.capacidad, CAST(COALESCE(SUM(e.cantidad_entradas), 0) AS UNSIGNED) \
+             FROM funciones f JOIN salas s ON s.id = f.sala_id LEFT JOIN entradas e ON e.funcion_id = f.id AND e.estado <> 'cancelada' \
+             WHERE f.id = :id AND (:cine_id IS NULL OR s.cine_id = :cine_id) GROUP BY s.capacidad",
+            params! { "id" => id, "cine_id" => cine },
+        )
+        .await?
+    else {
+        return Ok(None);
+    };
+    let ocupados: Vec<u32> = conn
+        .exec("SELECT numero FROM asientos WHERE funcion_id = :id ORDER BY numero", params! { "id" => id })
+        .await?;
+
+    Ok(Some(AsientosFuncion {
+        capacidad,
+        disponibles: u64::from(capacidad).saturating_sub(vendidas) as u32,
+        asientos: (1..=capacidad).filter(|asiento| ocupados.binary_search(asiento).is_err()).collect(),
+    }))
+}
+
+/// Handler con los asientos libres de una función (ver [`leer_asientos`]).
+pub async fn listar_asientos(
+    CineActual(cine): CineActual,
+    pool: web::Data<Pool>,
+    id: web::Path<u32>,
+) -> Result<ApiResponse<AsientosFuncion>, ApiError> {
+    let mut conn = db::conectar(&pool).await.map_err(ApiError::conexion)?;
+    leer_asientos(&mut conn, Some(cine), id.into_inner())
+        .await
+        .map_err(ApiError::base_datos("Error al obtener asientos"))?
+        .map(ApiResponse::ok)
+        .ok_or_else(no_encontrada)
+}
+
+/// Handler para crear una función. Sólo para administradores.
+pub async fn crear_funcion(
+    _admin: Administrador,
+    CineActual(cine): CineActual,
+    pool: web::Data<Pool>,
+    cache: web::Data<CacheLecturas>,
+    config: web::Data<Config>,
+    datos: Json<CrearFuncion>,
+) -> Result<ApiResponse<Funcion>, ApiError> {
+    let datos = datos.into_inner();
+    validar(&datos, &config)?;
+
+    let mut conn = db::conectar(&pool).await.map_err(ApiError::conexion)?;
+    let funcion = insertar_funcion(&mut conn, &cache, cine, &datos).await?;
+    Ok(ApiResponse::creada(funcion.en_zona(config.zona_horaria)))
+}
+
+/// Da de alta una función ya validada en una sala de `cine` y la devuelve, con el horario
+/// en UTC.
+pub(crate) async fn insertar_funcion(
+    conn: &mut db::Conexion,
+    cache: &CacheLecturas,
+    cine: u32,
+    datos: &CrearFuncion,
+) -> Result<Funcion, ApiError> {
+    verificar_sala(conn, cine, datos.sala_id, "Error al crear función").await?;
+    conn.exec_drop(
+        "INSERT INTO funciones (titulo, sala_id, horario, precio) VALUES (:titulo, :sala_id, :horario, :precio)",
+        params! {
+            "titulo" => &datos.titulo,
+            "sala_id" => datos.sala_id,
+            "horario" => datos.horario.naive_utc(),
+            "precio" => datos.precio,
+        },
+    )
+    .await
+    .map_err(error_escritura("Error al crear función"))?;
+    cache.funciones.vaciar().await;
+
+    let id = conn.last_insert_id().unwrap_or_default() as u32;
+    leer_funcion(conn, cine, id)
+        .await
+        .map_err(ApiError::base_datos("Error al crear función"))?
+        .ok_or_else(no_encontrada)
+}
+
+/// Handler que reemplaza todos los datos de una función. Sólo para administradores.
+/// Las entradas ya vendidas pasan a verla con los datos nuevos. La sala nueva tiene que
+/// ser del mismo cine.
+#[allow(clippy::too_many_arguments)]
+pub async fn reemplazar_funcion(
+    _admin: Administrador,
+    CineActual(cine): CineActual,
+    pool: web::Data<Pool>,
+    cache: web::Data<CacheLecturas>,
+    config: web::Data<Config>,
+    id: web::Path<u32>,
+    datos: Json<CrearFuncion>,
+) -> Result<ApiResponse<Funcion>, ApiError> {
+    let datos = datos.into_inner();
+    validar(&datos, &config)?;
+
+    let mut conn = db::conectar(&pool).await.map_err(ApiError::conexion)?;
+    let funcion = guardar_funcion(&mut conn, &cache, cine, id.into_inner(), &datos).await?;
+    Ok(ApiResponse::ok(funcion.en_zona(config.zona_horaria)))
+}
+
+/// Reemplaza los datos de la función `id` de `cine`, ya validados, y la devuelve como
+/// queda, con el horario en UTC.
+pub(crate) async fn guardar_funcion(
+    conn: &mut db::Conexion,
+    cache: &CacheLecturas,
+    cine: u32,
+    id: u32,
+    datos: &CrearFuncion,
+) -> Result<Funcion, ApiError> {
+    let existe: Option<u32> = conn
+        .exec_first(
+            "SELECT f.id FROM funciones f JOIN salas s ON s.id = f.sala_id WHERE f.id = :id AND s.cine_id = :cine_id",
+            params! { "id" => id, "cine_id" => cine },
+        )
+        .await
+        .map_err(ApiError::base_datos("Error al actualizar función"))?;
+    if existe.is_none() {
+        return Err(no_encontrada());
+    }
+    verificar_sala(conn, cine, datos.sala_id, "Error al actualizar función").await?;
+    conn.exec_drop(
+        "UPDATE funciones SET titulo = :titulo, sala_id = :sala_id, horario = :horario, precio = :precio \
+         WHERE id = :id",
+        params! {
+            "id" => id,
+            "titulo" => &datos.titulo,
+            "sala_id" => datos.sala_id,
+            "horario" => datos.horario.naive_utc(),
+            "precio" => datos.precio,
+        },
+    )
+    .await
+    .map_err(error_escritura("Error al actualizar función"))?;
+    cache.invalidar_catalogo().await;
+
+    leer_funcion(conn, cine, id)
+        .await
+        .map_err(ApiError::base_datos("Error al actualizar función"))?
+        .ok_or_else(no_encontrada)
+}
+
+/// Funciones de `cine` cuyo título contiene `texto`, o todas sin él, por título y horario.
+pub(crate) async fn buscar_funciones(
+    conn: &mut db::Conexion,
+    cine: u32,
+    texto: Option<&str>,
+) -> Result<Vec<Funcion>, mysql_async::Error> {
+    conn.exec(
+        format!(
+            "{} WHERE s.cine_id = :cine_id AND (:busqueda IS NULL OR f.titulo LIKE :busqueda) \
+             ORDER BY f.titulo, f.horario, s.nombre",
+            SELECT_FUNCIONES
+        ),
+        params! { "cine_id" => cine, "busqueda" => texto.map(|texto| format!("%{}%", escapar_like(texto))) },
+    )
+    .await
+}
+
+/// Handler que elimina una función sin entradas. Sólo para administradores.
+pub async fn eliminar_funcion(
+    _admin: Administrador,
+    CineActual(cine): CineActual,
+    pool: web::Data<Pool>,
+    cache: web::Data<CacheLecturas>,
+    id: web::Path<u32>,
+) -> Result<HttpResponse, ApiError> {
+    let mut conn = db::conectar(&pool).await.map_err(ApiError::conexion)?;
+    let resultado = conn
+        .exec_drop(
+            "DELETE f FROM funciones f JOIN salas s ON s.id = f.sala_id WHERE f.id = :id AND s.cine_id = :cine_id",
+            params! { "id" => id.into_inner(), "cine_id" => cine },
+        )
+        .await;
+    match resultado {
+        Ok(()) if conn.affected_rows() == 0 => Err(no_encontrada()),
+        Ok(()) => {
+            cache.funciones.vaciar().await;
+            Ok(HttpResponse::NoContent().finish())
+        }
+        Err(e) => Err(match errores::clasificar(&e) {
+            FalloMysql::Referenciada(restriccion) => ApiError::EnUso {
+                mensaje: "La función tiene entradas vendidas".to_string(),
+                restriccion,
+            },
+            _ => ApiError::escritura("Error al eliminar función")(e),
+        }),
+    }
+}
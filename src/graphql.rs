@@ -0,0 +1,245 @@
+//! API GraphQL en `/graphql`, junto a la REST y sobre el mismo [`ServicioEntradas`], para
+//! que los front-end pidan una entrada con su cliente y su función en una sola consulta.
+//!
+//! Exige el mismo token o clave de API que `/entradas`, trabaja sobre el mismo cine
+//! ([`CineActual`]), y modificar o eliminar una entrada es sólo para administradores. Cada error lleva en `extensions` el `code`, y los datos
+//! propios de su tipo, de las respuestas de error de la REST. Con
+//! `Config::graphql_playground`, `GET /graphql/playground` sirve GraphQL Playground.
+
+use std::sync::Arc;
+
+use async_graphql::http::{playground_source, GraphQLPlaygroundConfig};
+use async_graphql::{Context, EmptySubscription, ErrorExtensions, Object, Schema, SimpleObject};
+use actix_web::{web, HttpRequest, HttpResponse};
+use futures_util::TryStreamExt;
+
+use crate::auth::{Administrador, Sesion};
+use crate::cambios::CanalCambios;
+use crate::cines::CineActual;
+use crate::db::eventos::Evento;
+use crate::error::ApiError;
+use crate::json::Json;
+use crate::listado::{ConsultaListado, ParametrosListado};
+use crate::models::{ActualizarEntrada, CrearEntrada, Entrada, EstadoEntrada};
+use crate::recarga::ConfigVigente;
+use crate::servicio::{PaginaEntradas, ServicioEntradas};
+
+/// Profundidad máxima de una consulta.
+const PROFUNDIDAD_MAXIMA: usize = 10;
+
+pub type EsquemaGraphql = Schema<Consulta, Mutacion, EmptySubscription>;
+
+/// Esquema con el servicio de entradas y el canal de cambios que usan sus resolvers.
+pub fn esquema(servicio: Arc<ServicioEntradas>, cambios: Arc<CanalCambios>) -> EsquemaGraphql {
+    Schema::build(Consulta, Mutacion, EmptySubscription)
+        .data(servicio)
+        .data(cambios)
+        .limit_depth(PROFUNDIDAD_MAXIMA)
+        .finish()
+}
+
+/// Error de GraphQL con el mensaje y los datos del error de la REST.
+fn error(e: impl Into<ApiError>) -> async_graphql::Error {
+    let error = e.into();
+    let cuerpo = error.cuerpo();
+    async_graphql::Error::new(error.to_string()).extend_with(|_, extensiones| {
+        for (clave, valor) in cuerpo.as_object().into_iter().flatten() {
+            if clave != "message" {
+                extensiones.set(clave, async_graphql::Value::from_json(valor.clone()).unwrap_or_default());
+            }
+        }
+    })
+}
+
+fn servicio<'a>(ctx: &Context<'a>) -> &'a ServicioEntradas {
+    ctx.data_unchecked::<Arc<ServicioEntradas>>()
+}
+
+fn cambios<'a>(ctx: &Context<'a>) -> &'a CanalCambios {
+    ctx.data_unchecked::<Arc<CanalCambios>>()
+}
+
+fn sesion<'a>(ctx: &Context<'a>) -> &'a Sesion {
+    ctx.data_unchecked::<Sesion>()
+}
+
+fn cine(ctx: &Context<'_>) -> u32 {
+    ctx.data_unchecked::<CineActual>().0
+}
+
+/// Sesión de la petición, si es de un administrador.
+fn administrador(ctx: &Context<'_>) -> async_graphql::Result<Sesion> {
+    Administrador::desde_sesion(sesion(ctx).clone()).map(|Administrador(sesion)| sesion).map_err(error)
+}
+
+/// Página de `entradas`.
+#[derive(SimpleObject)]
+pub struct PaginaGraphql {
+    /// Entradas que cumplen los filtros, sin paginar.
+    pub total: u64,
+    pub entradas: Vec<Entrada>,
+}
+
+pub struct Consulta;
+
+#[Object(rename_fields = "snake_case", rename_args = "snake_case")]
+impl Consulta {
+    /// Entrada por su ID.
+    async fn entrada(&self, ctx: &Context<'_>, id: u32) -> async_graphql::Result<Entrada> {
+        servicio(ctx).obtener(cine(ctx), id).await.map_err(error)
+    }
+
+    /// Página de entradas con los mismos filtros que `GET /entradas`.
+    #[allow(clippy::too_many_arguments)]
+    async fn entradas(
+        &self,
+        ctx: &Context<'_>,
+        cliente_id: Option<u32>,
+        funcion_id: Option<u32>,
+        numero_cedula: Option<String>,
+        nombre_funcion: Option<String>,
+        pagina: Option<u32>,
+        por_pagina: Option<u32>,
+    ) -> async_graphql::Result<PaginaGraphql> {
+        let parametros = ParametrosListado {
+            page: pagina,
+            per_page: por_pagina,
+            cliente_id,
+            funcion_id,
+            numero_cedula,
+            nombre_funcion,
+            ..Default::default()
+        };
+        let consulta = ConsultaListado::desde_parametros(&parametros).map_err(|e| error(ApiError::Validacion(e)))?;
+        let PaginaEntradas { total, filas } = servicio(ctx).listar(cine(ctx), &consulta).await.map_err(error)?;
+        Ok(PaginaGraphql { total, entradas: filas.try_collect().await.map_err(error)? })
+    }
+}
+
+pub struct Mutacion;
+
+impl Mutacion {
+    /// Pasa la entrada a `estado` y la devuelve como queda.
+    async fn cambiar_estado(&self, ctx: &Context<'_>, id: u32, estado: EstadoEntrada) -> async_graphql::Result<Entrada> {
+        servicio(ctx).cambiar_estado(cine(ctx), id, estado, &sesion(ctx).sub).await.map_err(error)?;
+        cambios(ctx).publicar_entrada(cine(ctx), Evento::Actualizada, id);
+        servicio(ctx).obtener(cine(ctx), id).await.map_err(error)
+    }
+}
+
+#[Object(rename_fields = "snake_case", rename_args = "snake_case")]
+impl Mutacion {
+    /// Crea una entrada, como `POST /entradas`.
+    async fn crear_entrada(&self, ctx: &Context<'_>, datos: CrearEntrada) -> async_graphql::Result<Entrada> {
+        let id = servicio(ctx).crear(cine(ctx), &datos, &sesion(ctx).sub).await.map_err(error)?;
+        cambios(ctx).publicar_entrada(cine(ctx), Evento::Creada, id);
+        servicio(ctx).obtener(cine(ctx), id).await.map_err(error)
+    }
+
+    /// Cambia los campos presentes de una entrada si sigue en `version`, o en cualquiera
+    /// sin ella. Sólo para administradores.
+    async fn actualizar_entrada(
+        &self,
+        ctx: &Context<'_>,
+        id: u32,
+        datos: ActualizarEntrada,
+        version: Option<u32>,
+    ) -> async_graphql::Result<Entrada> {
+        let admin = administrador(ctx)?;
+        servicio(ctx).actualizar(cine(ctx), id, &datos, version, &admin.sub).await.map_err(error)?;
+        cambios(ctx).publicar_entrada(cine(ctx), Evento::Actualizada, id);
+        servicio(ctx).obtener(cine(ctx), id).await.map_err(error)
+    }
+
+    /// Elimina una entrada si sigue en `version`, o en cualquiera sin ella, y devuelve su
+    /// ID. Sólo para administradores.
+    async fn eliminar_entrada(&self, ctx: &Context<'_>, id: u32, version: Option<u32>) -> async_graphql::Result<u32> {
+        let admin = administrador(ctx)?;
+        servicio(ctx).eliminar(cine(ctx), id, version, &admin.sub).await.map_err(error)?;
+        cambios(ctx).publicar_entrada(cine(ctx), Evento::Eliminada, id);
+        Ok(id)
+    }
+
+    /// Marca como pagada una entrada reservada.
+    async fn pagar_entrada(&self, ctx: &Context<'_>, id: u32) -> async_graphql::Result<Entrada> {
+        self.cambiar_estado(ctx, id, EstadoEntrada::Pagada).await
+    }
+
+    /// Cancela una entrada reservada o pagada.
+    async fn cancelar_entrada(&self, ctx: &Context<'_>, id: u32) -> async_graphql::Result<Entrada> {
+        self.cambiar_estado(ctx, id, EstadoEntrada::Cancelada).await
+    }
+}
+
+/// Handler de `POST /graphql`. Responde siempre 200; los errores van en `errors`.
+pub async fn ejecutar_graphql(
+    sesion: Sesion,
+    cine: CineActual,
+    esquema: web::Data<EsquemaGraphql>,
+    peticion: Json<async_graphql::Request>,
+) -> HttpResponse {
+    let respuesta = esquema.execute(peticion.into_inner().data(sesion).data(cine)).await;
+    HttpResponse::Ok().json(respuesta)
+}
+
+/// Handler de `GET /graphql/playground`: GraphQL Playground apuntando a la ruta de la API,
+/// si `Config::graphql_playground` lo permite tras la última recarga.
+pub async fn playground(req: HttpRequest, config: web::Data<ConfigVigente>) -> Result<HttpResponse, ApiError> {
+    if !config.load().graphql_playground {
+        return Err(ApiError::NoEncontrado("GraphQL Playground está desactivado".to_string()));
+    }
+    let ruta = req.path().trim_end_matches("/playground");
+    Ok(HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(playground_source(GraphQLPlaygroundConfig::new(ruta))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::memoria::RepositorioMemoria;
+    use crate::models::Rol;
+    use crate::validacion::ReglasValidacion;
+
+    async fn ejecutar(esquema: &EsquemaGraphql, rol: Rol, consulta: &str) -> async_graphql::Response {
+        let sesion = Sesion { sub: "pruebas".to_string(), rol, cine: None, iat: 0, exp: u64::MAX };
+        let cine = CineActual(crate::cines::CINE_PRINCIPAL);
+        esquema.execute(async_graphql::Request::new(consulta).data(sesion).data(cine)).await
+    }
+
+    #[actix_web::test]
+    async fn crea_y_lee_una_entrada_con_su_funcion_y_su_cliente() {
+        let servicio = ServicioEntradas::new(
+            Arc::new(RepositorioMemoria::default()),
+            ReglasValidacion { cedula_ecuatoriana: false },
+            chrono::FixedOffset::east_opt(0).unwrap(),
+        );
+        let esquema = esquema(Arc::new(servicio), Arc::new(CanalCambios::default()));
+
+        let respuesta = ejecutar(
+            &esquema,
+            Rol::Taquillero,
+            "mutation { crear_entrada(datos: { cliente_id: 1, funcion_id: 1, cantidad_entradas: 2 }) { id estado } }",
+        )
+        .await;
+        assert!(respuesta.errors.is_empty(), "{:?}", respuesta.errors);
+        let respuesta = ejecutar(
+            &esquema,
+            Rol::Taquillero,
+            "{ entrada(id: 1) { total cliente { nombre } funcion { titulo sala { capacidad } } } }",
+        )
+        .await;
+        assert_eq!(
+            respuesta.data.into_json().unwrap(),
+            serde_json::json!({ "entrada": {
+                "total": 13.0,
+                "cliente": { "nombre": "Ana" },
+                "funcion": { "titulo": "Dune", "sala": { "capacidad": 100 } },
+            } })
+        );
+
+        let respuesta = ejecutar(&esquema, Rol::Taquillero, "mutation { eliminar_entrada(id: 1) }").await;
+        let extensiones = respuesta.errors[0].extensions.as_ref().unwrap();
+        assert_eq!(extensiones.get("code"), Some(&async_graphql::Value::from("prohibido")));
+    }
+}
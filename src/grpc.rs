@@ -0,0 +1,302 @@
+//! Servidor gRPC de entradas para otros servicios internos, en su propio puerto
+//! (`Config::grpc_port`), con el CRUD de `proto/entradas.proto` sobre el mismo
+//! [`ServicioEntradas`] que los handlers HTTP.
+//!
+//! Cada llamada se autentica como en la API REST, con `authorization: Bearer <token>` o
+//! una clave en `x-api-key` en la metadata, y modificar o eliminar una entrada es sólo
+//! para administradores. El cine sale de la sesión o de la metadata `x-cine-id`, como
+//! `X-Cine-Id` en la REST (ver [`crate::cines`]). Los errores llevan el código gRPC equivalente a su estado HTTP y,
+//! en la metadata `error-bin`, el mismo JSON que el `error` de las respuestas de la REST.
+
+use std::sync::Arc;
+
+use actix_web::rt::{self, task::JoinHandle};
+use futures_util::TryStreamExt;
+use mysql_async::Pool;
+use tokio::sync::oneshot;
+use tonic::metadata::{MetadataMap, MetadataValue};
+use tonic::transport::server::TcpIncoming;
+use tonic::transport::Server;
+use tonic::{Code, Request, Response, Status};
+
+use crate::auth::{verificar_token, Administrador, ConfigAuth, Sesion};
+use crate::cambios::CanalCambios;
+use crate::cines;
+use crate::claves_api::{sesion_de_clave, CABECERA_CLAVE_API};
+use crate::db::eventos::Evento;
+use crate::error::ApiError;
+use crate::listado::{ConsultaListado, ParametrosListado};
+use crate::models::{ActualizarEntrada, CrearEntrada, Entrada, EstadoEntrada};
+use crate::servicio::{PaginaEntradas, ServicioEntradas};
+use crate::Estado;
+
+/// Mensajes y servidor generados de `proto/entradas.proto`.
+pub mod proto {
+    tonic::include_proto!("entradas.v1");
+}
+
+use proto::entradas_server::{Entradas, EntradasServer};
+
+/// Estado gRPC de un error de la API, con su JSON en la metadata `error-bin`.
+fn estado(e: impl Into<ApiError>) -> Status {
+    let error = e.into();
+    let codigo = match &error {
+        ApiError::NoEncontrado(_) => Code::NotFound,
+        ApiError::Validacion(_)
+        | ApiError::CamposInvalidos(_)
+        | ApiError::ReferenciaInexistente { .. }
+        | ApiError::CuerpoDemasiadoGrande(_)
+        | ApiError::TipoNoAdmitido(_) => Code::InvalidArgument,
+        ApiError::NoAutorizado(_) => Code::Unauthenticated,
+        ApiError::Prohibido(_) => Code::PermissionDenied,
+        ApiError::Duplicado { .. } => Code::AlreadyExists,
+        ApiError::Conflicto(_)
+        | ApiError::EnUso { .. }
+        | ApiError::SinCapacidad(_)
+        | ApiError::LimiteCedula(_)
+        | ApiError::PrecondicionRequerida(_) => Code::FailedPrecondition,
+        ApiError::PrecondicionFallida(_) => Code::Aborted,
+        ApiError::TiempoAgotado(_) => Code::DeadlineExceeded,
+        ApiError::DemasiadasPeticiones(_) => Code::ResourceExhausted,
+        ApiError::Contencion(_) => Code::Unavailable,
+        ApiError::BaseDatos(_) => Code::Internal,
+    };
+    let mut metadata = MetadataMap::new();
+    metadata.insert_bin("error-bin", MetadataValue::from_bytes(error.cuerpo().to_string().as_bytes()));
+    Status::with_metadata(codigo, error.to_string(), metadata)
+}
+
+impl From<EstadoEntrada> for proto::EstadoEntrada {
+    fn from(estado: EstadoEntrada) -> Self {
+        match estado {
+            EstadoEntrada::Reservada => proto::EstadoEntrada::Reservada,
+            EstadoEntrada::Pagada => proto::EstadoEntrada::Pagada,
+            EstadoEntrada::Usada => proto::EstadoEntrada::Usada,
+            EstadoEntrada::Cancelada => proto::EstadoEntrada::Cancelada,
+        }
+    }
+}
+
+impl From<Entrada> for proto::Entrada {
+    fn from(entrada: Entrada) -> Self {
+        let funcion = entrada.funcion;
+        proto::Entrada {
+            id: entrada.id.unwrap_or_default(),
+            cantidad_entradas: entrada.cantidad_entradas,
+            total: entrada.total,
+            estado: proto::EstadoEntrada::from(entrada.estado).into(),
+            cliente: Some(proto::Cliente {
+                id: entrada.cliente.id,
+                numero_cedula: entrada.cliente.numero_cedula,
+                nombre: entrada.cliente.nombre,
+            }),
+            funcion: Some(proto::Funcion {
+                id: funcion.id,
+                titulo: funcion.titulo,
+                horario: funcion.horario.to_rfc3339(),
+                precio: funcion.precio,
+                sala: Some(proto::Sala {
+                    id: funcion.sala.id,
+                    nombre: funcion.sala.nombre,
+                    capacidad: funcion.sala.capacidad,
+                }),
+            }),
+            version: entrada.version,
+            created_at: entrada.created_at,
+            updated_at: entrada.updated_at,
+        }
+    }
+}
+
+/// Implementación del servicio `entradas.v1.Entradas`.
+pub struct ServicioGrpc {
+    entradas: Arc<ServicioEntradas>,
+    cambios: Arc<CanalCambios>,
+    pool: Pool,
+    auth: ConfigAuth,
+}
+
+impl ServicioGrpc {
+    /// Servicio sobre las mismas entradas, canal de cambios y claves de API que la REST.
+    pub fn new(estado: &Estado) -> Self {
+        ServicioGrpc {
+            entradas: estado.entradas.clone(),
+            cambios: estado.cambios.clone(),
+            pool: estado.pool.clone(),
+            auth: estado.config.auth.clone(),
+        }
+    }
+
+    /// Sesión de la llamada, por su clave de API o su token, y su cine.
+    async fn sesion<T>(&self, peticion: &Request<T>) -> Result<(Sesion, u32), Status> {
+        let metadata = peticion.metadata();
+        let sesion = match metadata.get(CABECERA_CLAVE_API) {
+            Some(clave) => sesion_de_clave(&self.pool, clave.to_str().unwrap_or_default()).await.map_err(estado)?,
+            None => {
+                let token = metadata
+                    .get("authorization")
+                    .and_then(|valor| valor.to_str().ok())
+                    .and_then(|valor| valor.split_once(' '))
+                    .filter(|(esquema, _)| esquema.eq_ignore_ascii_case("bearer"))
+                    .map(|(_, token)| token.trim())
+                    .ok_or_else(|| {
+                        estado(ApiError::NoAutorizado("Falta la metadata 'authorization: Bearer <token>'".to_string()))
+                    })?;
+                verificar_token(&self.auth, token)
+                    .map_err(|_| estado(ApiError::NoAutorizado("Token inválido o caducado".to_string())))?
+            }
+        };
+        let pedido = metadata.get("x-cine-id").map(|valor| valor.to_str().unwrap_or_default());
+        let cine = cines::resolver(Some(&sesion), pedido).map_err(estado)?;
+        Ok((sesion, cine))
+    }
+
+    /// Sesión de la llamada, si es de un administrador, y su cine.
+    async fn administrador<T>(&self, peticion: &Request<T>) -> Result<(Sesion, u32), Status> {
+        let (sesion, cine) = self.sesion(peticion).await?;
+        let Administrador(sesion) = Administrador::desde_sesion(sesion).map_err(estado)?;
+        Ok((sesion, cine))
+    }
+
+    async fn obtener_entrada(&self, cine: u32, id: u32) -> Result<Response<proto::Entrada>, Status> {
+        let entrada = self.entradas.obtener(cine, id).await.map_err(estado)?;
+        Ok(Response::new(entrada.into()))
+    }
+}
+
+#[tonic::async_trait]
+impl Entradas for ServicioGrpc {
+    async fn obtener(&self, peticion: Request<proto::IdEntrada>) -> Result<Response<proto::Entrada>, Status> {
+        let (_, cine) = self.sesion(&peticion).await?;
+        self.obtener_entrada(cine, peticion.into_inner().id).await
+    }
+
+    async fn listar(&self, peticion: Request<proto::FiltroEntradas>) -> Result<Response<proto::PaginaEntradas>, Status> {
+        let (_, cine) = self.sesion(&peticion).await?;
+        let filtro = peticion.into_inner();
+        let parametros = ParametrosListado {
+            page: filtro.pagina,
+            per_page: filtro.por_pagina,
+            cliente_id: filtro.cliente_id,
+            funcion_id: filtro.funcion_id,
+            numero_cedula: filtro.numero_cedula,
+            nombre_funcion: filtro.nombre_funcion,
+            ..Default::default()
+        };
+        let consulta = ConsultaListado::desde_parametros(&parametros).map_err(|e| estado(ApiError::Validacion(e)))?;
+        let PaginaEntradas { total, filas } = self.entradas.listar(cine, &consulta).await.map_err(estado)?;
+        let entradas = filas.map_ok(proto::Entrada::from).try_collect().await.map_err(estado)?;
+        Ok(Response::new(proto::PaginaEntradas { total, entradas }))
+    }
+
+    async fn crear(&self, peticion: Request<proto::NuevaEntrada>) -> Result<Response<proto::Entrada>, Status> {
+        let (sesion, cine) = self.sesion(&peticion).await?;
+        let nueva = peticion.into_inner();
+        let datos = CrearEntrada {
+            cliente_id: nueva.cliente_id,
+            funcion_id: nueva.funcion_id,
+            cantidad_entradas: nueva.cantidad_entradas,
+            asientos: nueva.asientos,
+        };
+        let id = self.entradas.crear(cine, &datos, &sesion.sub).await.map_err(estado)?;
+        self.cambios.publicar_entrada(cine, Evento::Creada, id);
+        self.obtener_entrada(cine, id).await
+    }
+
+    async fn actualizar(&self, peticion: Request<proto::CambiosEntrada>) -> Result<Response<proto::Entrada>, Status> {
+        let (admin, cine) = self.administrador(&peticion).await?;
+        let cambios = peticion.into_inner();
+        let datos = ActualizarEntrada {
+            cliente_id: cambios.cliente_id,
+            funcion_id: cambios.funcion_id,
+            cantidad_entradas: cambios.cantidad_entradas,
+        };
+        self.entradas
+            .actualizar(cine, cambios.id, &datos, cambios.version, &admin.sub)
+            .await
+            .map_err(estado)?;
+        self.cambios.publicar_entrada(cine, Evento::Actualizada, cambios.id);
+        self.obtener_entrada(cine, cambios.id).await
+    }
+
+    async fn eliminar(&self, peticion: Request<proto::EliminarEntrada>) -> Result<Response<proto::IdEntrada>, Status> {
+        let (admin, cine) = self.administrador(&peticion).await?;
+        let proto::EliminarEntrada { id, version } = peticion.into_inner();
+        self.entradas.eliminar(cine, id, version, &admin.sub).await.map_err(estado)?;
+        self.cambios.publicar_entrada(cine, Evento::Eliminada, id);
+        Ok(Response::new(proto::IdEntrada { id }))
+    }
+}
+
+/// Abre el puerto gRPC en `direccion` y atiende `servicio` en una tarea hasta que se
+/// envíe por el emisor devuelto, esperando entonces a las llamadas en curso. El puerto se
+/// abre antes de volver, para que uno ocupado sea un error al arrancar.
+pub fn servir(direccion: (String, u16), servicio: ServicioGrpc) -> std::io::Result<(oneshot::Sender<()>, JoinHandle<()>)> {
+    let listener = std::net::TcpListener::bind(direccion)?;
+    listener.set_nonblocking(true)?;
+    let entrantes = TcpIncoming::from_listener(tokio::net::TcpListener::from_std(listener)?, true, None)
+        .map_err(std::io::Error::other)?;
+    let (parar, parada) = oneshot::channel();
+    let tarea = rt::spawn(async move {
+        let resultado = Server::builder()
+            .add_service(EntradasServer::new(servicio))
+            .serve_with_incoming_shutdown(entrantes, async {
+                parada.await.ok();
+            })
+            .await;
+        if let Err(e) = resultado {
+            log::error!("Error en el servidor gRPC: {}", e);
+        }
+    });
+    Ok((parar, tarea))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::auth::emitir_token;
+    use crate::config::Config;
+    use crate::db::memoria::RepositorioMemoria;
+    use crate::models::Rol;
+
+    fn peticion<T>(servicio: &ServicioGrpc, rol: Rol, mensaje: T) -> Request<T> {
+        let token = emitir_token(&servicio.auth, "pruebas", rol, None).unwrap();
+        let mut peticion = Request::new(mensaje);
+        peticion.metadata_mut().insert("authorization", format!("Bearer {}", token).parse().unwrap());
+        peticion
+    }
+
+    #[actix_web::test]
+    async fn crea_una_entrada_y_solo_un_administrador_la_elimina() {
+        let mut config = Config::new("mysql://root@127.0.0.1:1/pruebas");
+        config.auth.secreto = "secreto".to_string();
+        config.auth.duracion_token = Duration::from_secs(60);
+        let mut estado = Estado::new(config.clone(), mysql_async::Pool::new(config.database_url.as_str()));
+        estado.entradas = Arc::new(ServicioEntradas::new(
+            Arc::new(RepositorioMemoria::default()),
+            crate::validacion::ReglasValidacion { cedula_ecuatoriana: false },
+            config.zona_horaria,
+        ));
+        let servicio = ServicioGrpc::new(&estado);
+
+        let sin_token = servicio.obtener(Request::new(proto::IdEntrada { id: 1 })).await.unwrap_err();
+        assert_eq!(sin_token.code(), Code::Unauthenticated);
+
+        let nueva = proto::NuevaEntrada { cliente_id: 1, funcion_id: 1, cantidad_entradas: 2, asientos: vec![] };
+        let entrada = servicio.crear(peticion(&servicio, Rol::Taquillero, nueva)).await.unwrap().into_inner();
+        assert_eq!((entrada.id, entrada.estado()), (1, proto::EstadoEntrada::Reservada));
+        assert_eq!(entrada.funcion.unwrap().sala.unwrap().capacidad, 100);
+
+        let eliminar = proto::EliminarEntrada { id: 1, version: None };
+        let error = servicio.eliminar(peticion(&servicio, Rol::Taquillero, eliminar)).await.unwrap_err();
+        assert_eq!(error.code(), Code::PermissionDenied);
+        let cuerpo: serde_json::Value = serde_json::from_slice(&error.metadata().get_bin("error-bin").unwrap().to_bytes().unwrap()).unwrap();
+        assert_eq!(cuerpo["code"], "prohibido");
+
+        servicio.eliminar(peticion(&servicio, Rol::Admin, eliminar)).await.unwrap();
+        let error = servicio.obtener(peticion(&servicio, Rol::Taquillero, proto::IdEntrada { id: 1 })).await.unwrap_err();
+        assert_eq!(error.code(), Code::NotFound);
+    }
+}
@@ -0,0 +1,919 @@
+//! Handlers HTTP de la API de entradas. Sólo traducen entre HTTP y [`ServicioEntradas`],
+//! siempre sobre las entradas del cine de la petición ([`CineActual`]).
+
+use std::future::ready;
+use std::sync::Arc;
+
+use actix_web::http::header::{
+    ContentDisposition, ContentType, DispositionParam, DispositionType, HeaderName, HeaderValue, ETAG, IF_MATCH,
+    IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+};
+use actix_web::http::StatusCode;
+use actix_web::web::Bytes;
+use actix_multipart::Multipart;
+use actix_web::{web, HttpRequest, HttpResponse, Responder, ResponseError};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use futures_util::stream::{self, StreamExt, TryStreamExt};
+use mysql_async::Pool;
+use serde::{Deserialize, Serialize};
+
+use crate::agregado::ParametrosAgregado;
+use crate::auth::{Administrador, Sesion};
+use crate::cambios::{Cambio, CanalCambios};
+use crate::campos::{self, ParametrosCampos};
+use crate::cines::CineActual;
+use crate::compartido::Redis;
+use crate::config::Config;
+use crate::db::eventos::Evento;
+use crate::enlaces::ConEnlaces;
+use crate::error::ApiError;
+use crate::exportacion::{self, ENCABEZADO_CSV};
+use crate::idempotencia::{self, Reserva};
+use crate::importacion;
+use crate::json::Json;
+use crate::listado::{ConsultaListado, Cursor, ParametrosBusqueda, ParametrosListado};
+use crate::models::{
+    ActualizarEntrada, CancelarFuncion, CrearEntrada, EliminarEntradas, Entrada, EstadoEntrada, GuardarEntrada,
+    ReemplazarEntrada, RegistroAuditoria,
+};
+use crate::qr;
+use crate::respuesta::{ApiResponse, Meta, Paginacion, PaginacionCursor};
+use crate::servicio::{EntradaGuardada, EntradaSimulada, ErrorEntrada, PaginaEntradas, ResultadoLote, ServicioEntradas};
+use crate::simulacion::Simulacion;
+use crate::ticket;
+use crate::validacion::ErrorCampo;
+
+/// Serializa una entrada como elemento de un arreglo JSON, precedida de `separador`, con
+/// sólo `campos` si se pidieron y, si no, con sus enlaces.
+fn fragmento_json(separador: u8, entrada: &Entrada, campos: Option<&[&str]>) -> Bytes {
+    let mut fragmento = vec![separador];
+    match campos {
+        Some(campos) => serde_json::to_writer(&mut fragmento, &campos::proyectar(entrada, campos)),
+        None => serde_json::to_writer(&mut fragmento, &ConEnlaces::new(entrada)),
+    }
+    .expect("Entrada siempre es serializable");
+    Bytes::from(fragmento)
+}
+
+/// Handler para obtener una página de entradas de cine con su cliente y su función,
+/// opcionalmente filtradas por `cliente_id`, `numero_cedula`, `funcion_id`,
+/// `nombre_funcion` (título), `horario_funcion` y `created_after` (creadas después de esa
+/// fecha), y ordenadas con `sort` y `order`. Con `fields` cada entrada trae sólo esos
+/// campos (ver [`campos`]).
+///
+/// Con `limit`, y `after` desde la segunda página, pagina por cursor: la página va entera
+/// en `data`, sin total, y `meta.cursor.next_cursor` es el `after` de la siguiente.
+///
+/// El arreglo de la página va en `data`, transmitido por fragmentos a medida que llegan
+/// las filas, y `meta` se escribe al final. El total y la paginación aplicada viajan en
+/// `meta.paginacion` y también en las cabeceras `X-Total-Count`, `X-Pagina` y `X-Por-Pagina`.
+pub async fn obtener_entradas(
+    req: HttpRequest,
+    CineActual(cine): CineActual,
+    servicio: web::Data<ServicioEntradas>,
+    query: web::Query<ParametrosListado>,
+) -> Result<HttpResponse, ApiError> {
+    let consulta = ConsultaListado::desde_parametros(&query).map_err(ApiError::Validacion)?;
+    if consulta.por_cursor {
+        return responder_por_cursor(&req, &servicio, cine, &consulta).await;
+    }
+    responder_pagina(&req, &servicio, cine, &consulta).await
+}
+
+/// Página por cursor de `consulta`. Se pide una entrada de más para saber si hay otra página.
+async fn responder_por_cursor(
+    req: &HttpRequest,
+    servicio: &ServicioEntradas,
+    cine: u32,
+    consulta: &ConsultaListado,
+) -> Result<HttpResponse, ApiError> {
+    let con_siguiente = ConsultaListado { por_pagina: consulta.por_pagina + 1, ..consulta.clone() };
+    let mut entradas: Vec<Entrada> = servicio.filas(cine, &con_siguiente).await?.try_collect().await?;
+    let next_cursor = if entradas.len() > consulta.por_pagina as usize {
+        entradas.truncate(consulta.por_pagina as usize);
+        entradas.last().map(|ultima| Cursor::tras(ultima, consulta).codificar())
+    } else {
+        None
+    };
+    let datos: Vec<serde_json::Value> = entradas
+        .iter()
+        .map(|entrada| match &consulta.campos {
+            Some(campos) => serde_json::Value::Object(campos::proyectar(entrada, campos)),
+            None => serde_json::to_value(ConEnlaces::new(entrada)).expect("Entrada siempre es serializable"),
+        })
+        .collect();
+    let cursor = PaginacionCursor { por_pagina: consulta.por_pagina, next_cursor };
+    Ok(ApiResponse::ok(datos).con_cursor(cursor).respond_to(req))
+}
+
+/// Handler que busca `q` como parte del nombre del cliente o del título de la función,
+/// sin distinguir mayúsculas ni tildes. Pagina con `page` y `per_page` y responde igual
+/// que [`obtener_entradas`].
+pub async fn buscar_entradas(
+    req: HttpRequest,
+    CineActual(cine): CineActual,
+    servicio: web::Data<ServicioEntradas>,
+    query: web::Query<ParametrosBusqueda>,
+) -> Result<HttpResponse, ApiError> {
+    let consulta = ConsultaListado::busqueda(&query).map_err(ApiError::Validacion)?;
+    responder_pagina(&req, &servicio, cine, &consulta).await
+}
+
+/// Página de `consulta` transmitida como JSON, con su paginación en `meta` y en cabeceras.
+async fn responder_pagina(
+    req: &HttpRequest,
+    servicio: &ServicioEntradas,
+    cine: u32,
+    consulta: &ConsultaListado,
+) -> Result<HttpResponse, ApiError> {
+    let PaginaEntradas { total, mut filas } = servicio.listar(cine, consulta).await?;
+    let paginacion = Paginacion { pagina: consulta.pagina, por_pagina: consulta.por_pagina, total };
+    let mut cierre = b"],\"meta\":".to_vec();
+    serde_json::to_writer(&mut cierre, &Meta::de(req, Some(paginacion))).expect("Meta siempre es serializable");
+    cierre.push(b'}');
+
+    let mut respuesta = HttpResponse::Ok();
+    respuesta
+        .content_type(ContentType::json())
+        .insert_header(("X-Total-Count", total))
+        .insert_header(("X-Pagina", consulta.pagina))
+        .insert_header(("X-Por-Pagina", consulta.por_pagina));
+
+    // Se espera la primera fila para poder responder 500 si la consulta falla antes de empezar.
+    let primera = match filas.next().await {
+        Some(fila) => fila?,
+        None => return Ok(respuesta.body([&b"{\"data\":["[..], &cierre].concat())),
+    };
+
+    let inicio = fragmento_json(b'[', &primera, consulta.campos.as_deref());
+    let campos = consulta.campos.clone();
+    let resto = filas.map(move |fila| fila.map(|entrada| fragmento_json(b',', &entrada, campos.as_deref())));
+    let cuerpo = stream::once(ready(Ok(Bytes::from_static(b"{\"data\":"))))
+        .chain(stream::once(ready(Ok(inicio))))
+        .chain(resto)
+        .chain(stream::once(ready(Ok(Bytes::from(cierre)))));
+    Ok(respuesta.streaming(cuerpo))
+}
+
+/// Respuesta de `GET /entradas/count`.
+#[derive(Debug, Serialize)]
+pub struct ConteoEntradas {
+    pub total: u64,
+}
+
+/// Handler con el número de entradas que cumplen los mismos filtros que
+/// [`obtener_entradas`], sin leerlas.
+pub async fn contar_entradas(
+    CineActual(cine): CineActual,
+    servicio: web::Data<ServicioEntradas>,
+    query: web::Query<ParametrosListado>,
+) -> Result<ApiResponse<ConteoEntradas>, ApiError> {
+    let consulta = ConsultaListado::completa(&query).map_err(ApiError::Validacion)?;
+    Ok(ApiResponse::ok(ConteoEntradas { total: servicio.contar(cine, &consulta).await? }))
+}
+
+/// Handler de `HEAD /entradas`: el número de entradas del listado en `X-Total-Count`,
+/// sin cuerpo.
+pub async fn contar_entradas_cabecera(
+    CineActual(cine): CineActual,
+    servicio: web::Data<ServicioEntradas>,
+    query: web::Query<ParametrosListado>,
+) -> Result<HttpResponse, ApiError> {
+    let consulta = ConsultaListado::completa(&query).map_err(ApiError::Validacion)?;
+    let total = servicio.contar(cine, &consulta).await?;
+    Ok(HttpResponse::Ok().insert_header(("X-Total-Count", total)).finish())
+}
+
+/// Parámetros propios de `GET /entradas/export`, además de los filtros del listado.
+#[derive(Debug, Deserialize)]
+pub struct ParametrosExportacion {
+    /// Formato del archivo: `csv`, el valor por defecto, o `xlsx`.
+    pub format: Option<String>,
+}
+
+/// Handler que exporta todas las entradas que cumplen los mismos filtros que
+/// [`obtener_entradas`], en su mismo orden y sin paginar, con el total en `X-Total-Count`.
+/// En CSV las filas se transmiten a medida que llegan; el XLSX, con una hoja de resumen
+/// por función, se genera entero antes de responder (ver [`exportacion`]).
+pub async fn exportar_entradas(
+    CineActual(cine): CineActual,
+    servicio: web::Data<ServicioEntradas>,
+    query: web::Query<ParametrosListado>,
+    exportacion: web::Query<ParametrosExportacion>,
+) -> Result<HttpResponse, ApiError> {
+    let (extension, tipo) = match exportacion.format.as_deref() {
+        None | Some("csv") => ("csv", "text/csv; charset=utf-8"),
+        Some("xlsx") => ("xlsx", "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"),
+        Some(_) => return Err(ApiError::Validacion("El parámetro 'format' debe ser 'csv' o 'xlsx'".to_string())),
+    };
+    let consulta = ConsultaListado::completa(&query).map_err(ApiError::Validacion)?;
+    let PaginaEntradas { total, mut filas } = servicio.listar(cine, &consulta).await?;
+
+    let mut respuesta = HttpResponse::Ok();
+    let archivo = format!("entradas-{}.{}", Utc::now().format("%Y-%m-%d"), extension);
+    respuesta
+        .content_type(tipo)
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(archivo)],
+        })
+        .insert_header(("X-Total-Count", total));
+
+    if extension == "xlsx" {
+        let entradas: Vec<Entrada> = filas.try_collect().await?;
+        let libro = exportacion::libro_xlsx(&entradas)
+            .map_err(|e| ApiError::Validacion(format!("No se pudo generar el XLSX; acota el listado con filtros: {}", e)))?;
+        return Ok(respuesta.body(libro));
+    }
+
+    // Como en el listado, se espera la primera fila para poder responder 500 si la consulta falla.
+    let primera = match filas.next().await {
+        Some(fila) => fila?,
+        None => return Ok(respuesta.body(ENCABEZADO_CSV)),
+    };
+    let encabezado = Bytes::from(format!("{}{}", ENCABEZADO_CSV, exportacion::fila_csv(&primera)));
+    let resto = filas.map(|fila| fila.map(|entrada| Bytes::from(exportacion::fila_csv(&entrada))));
+    Ok(respuesta.streaming(stream::once(ready(Ok(encabezado))).chain(resto)))
+}
+
+/// Handler para obtener agregados de entradas agrupados por los campos solicitados.
+pub async fn obtener_agregado(
+    CineActual(cine): CineActual,
+    servicio: web::Data<ServicioEntradas>,
+    query: web::Query<ParametrosAgregado>,
+) -> Result<ApiResponse<impl Serialize>, ApiError> {
+    let agregados = servicio.agregar(cine, query.into_inner()).await?;
+    Ok(ApiResponse::ok(agregados))
+}
+
+/// Respuesta 200 con la entrada, con sus enlaces, y su versión en `ETag`.
+fn respuesta_con_etag(req: &HttpRequest, entrada: Entrada) -> HttpResponse {
+    let version = entrada.version;
+    respuesta_con_version(req, version, ConEnlaces::new(entrada))
+}
+
+/// Respuesta 200 con `datos` y la versión de su entrada en `ETag`.
+fn respuesta_con_version(req: &HttpRequest, version: u32, datos: impl Serialize + 'static) -> HttpResponse {
+    let etag = HeaderValue::from_str(&format!("\"{}\"", version)).expect("ETag siempre es una cabecera válida");
+    let mut respuesta = ApiResponse::ok(datos).respond_to(req);
+    respuesta.headers_mut().insert(ETAG, etag);
+    respuesta
+}
+
+/// Fecha y hora del último cambio de la entrada. Llega en UTC, la zona de cada conexión
+/// de la pool (ver [`crate::db::obtener_pool_db`]), como los horarios.
+fn ultima_modificacion(entrada: &Entrada) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(&entrada.updated_at, "%Y-%m-%d %H:%M:%S").ok().map(|fecha| fecha.and_utc())
+}
+
+/// Si la copia que tiene el cliente sigue vigente: según `If-None-Match` o, si no la envía,
+/// según `If-Modified-Since`.
+fn sin_cambios(req: &HttpRequest, version: u32, modificada: Option<DateTime<Utc>>) -> bool {
+    if let Some(valor) = req.headers().get(IF_NONE_MATCH) {
+        let etag = format!("\"{}\"", version);
+        return valor
+            .to_str()
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .any(|candidata| candidata == "*" || candidata.trim_start_matches("W/") == etag);
+    }
+    let desde = req
+        .headers()
+        .get(IF_MODIFIED_SINCE)
+        .and_then(|valor| valor.to_str().ok())
+        .and_then(|valor| DateTime::parse_from_rfc2822(valor).ok());
+    matches!((desde, modificada), (Some(desde), Some(modificada)) if modificada <= desde)
+}
+
+/// Handler para obtener una entrada específica por su ID, con su versión en `ETag` y su
+/// último cambio en `Last-Modified`. Si el cliente ya la tiene, por `If-None-Match` o
+/// `If-Modified-Since`, responde 304 sin cuerpo. Con `fields` devuelve sólo esos campos;
+/// la entrada se lee entera igualmente, con la sentencia preparada de siempre.
+pub async fn obtener_entrada_por_id(
+    req: HttpRequest,
+    CineActual(cine): CineActual,
+    servicio: web::Data<ServicioEntradas>,
+    path: web::Path<u32>,
+    query: web::Query<ParametrosCampos>,
+) -> Result<HttpResponse, ApiError> {
+    let campos = query.fields.as_deref().map(campos::leer).transpose().map_err(ApiError::Validacion)?;
+    let entrada = servicio.obtener(cine, path.into_inner()).await?;
+    let modificada = ultima_modificacion(&entrada);
+    let mut respuesta = if sin_cambios(&req, entrada.version, modificada) {
+        HttpResponse::NotModified()
+            .insert_header((ETAG, format!("\"{}\"", entrada.version)))
+            .finish()
+    } else {
+        match campos {
+            Some(campos) => respuesta_con_version(&req, entrada.version, campos::proyectar(&entrada, &campos)),
+            None => respuesta_con_etag(&req, entrada),
+        }
+    };
+    if let Some(modificada) = modificada {
+        let fecha = modificada.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+        respuesta.headers_mut().insert(LAST_MODIFIED, HeaderValue::from_str(&fecha).expect("Fecha HTTP válida"));
+    }
+    Ok(respuesta)
+}
+
+/// Handler con el ticket imprimible de una entrada en PDF, con su código QR, para que los
+/// quioscos no tengan que componerlo.
+pub async fn obtener_ticket(
+    CineActual(cine): CineActual,
+    servicio: web::Data<ServicioEntradas>,
+    config: web::Data<Config>,
+    path: web::Path<u32>,
+) -> Result<HttpResponse, ApiError> {
+    let id = path.into_inner();
+    let entrada = servicio.obtener(cine, id).await?;
+    let pdf = ticket::generar(&entrada, &qr::firmar(&config.auth, id));
+    Ok(HttpResponse::Ok()
+        .content_type("application/pdf")
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Inline,
+            parameters: vec![DispositionParam::Filename(format!("entrada-{}.pdf", id))],
+        })
+        .body(pdf))
+}
+
+/// Handler con el historial de cambios de una entrada, incluso si ya se eliminó: quién
+/// hizo cada uno, cuándo, y la entrada antes y después.
+pub async fn obtener_historial(
+    CineActual(cine): CineActual,
+    servicio: web::Data<ServicioEntradas>,
+    path: web::Path<u32>,
+) -> Result<ApiResponse<Vec<RegistroAuditoria>>, ApiError> {
+    Ok(ApiResponse::ok(servicio.historial(cine, path.into_inner()).await?))
+}
+
+/// Versión que exige la cabecera `If-Match` de PUT, PATCH y DELETE: el `ETag` de la entrada
+/// tal como se leyó, o `*` (`None`) para aceptar cualquiera. Sin la cabecera es un 428.
+fn version_esperada(req: &HttpRequest) -> Result<Option<u32>, ApiError> {
+    let valor = req.headers().get(IF_MATCH).ok_or_else(|| {
+        ApiError::PrecondicionRequerida("Falta la cabecera If-Match con el ETag de la entrada".to_string())
+    })?;
+    let valor = valor.to_str().unwrap_or_default().trim();
+    if valor == "*" {
+        return Ok(None);
+    }
+    valor
+        .strip_prefix('"')
+        .and_then(|resto| resto.strip_suffix('"'))
+        .and_then(|version| version.parse().ok())
+        .map(Some)
+        .ok_or_else(|| ApiError::PrecondicionFallida("If-Match no corresponde a ninguna versión de la entrada".to_string()))
+}
+
+/// Entrada recién creada, con el código QR que se escanea en la puerta.
+#[derive(Debug, Serialize)]
+pub struct EntradaCreada {
+    pub id: u32,
+    pub qr: String,
+}
+
+/// Handler para crear una nueva entrada de cine. Con `Idempotency-Key`, un reenvío de la
+/// misma venta recibe la respuesta original en lugar de crear otra (ver [`idempotencia`]).
+/// Dos ventas idénticas simultáneas, aun sin clave, crean una sola entrada y reciben las dos
+/// su respuesta (ver [`ServicioEntradas::vender`]). Con `dry_run=true` responde con la
+/// entrada que se crearía, sin guardarla.
+#[allow(clippy::too_many_arguments)]
+pub async fn crear_entrada(
+    req: HttpRequest,
+    sesion: Sesion,
+    CineActual(cine): CineActual,
+    Simulacion(simulacion): Simulacion,
+    servicio: web::Data<ServicioEntradas>,
+    cambios: web::Data<CanalCambios>,
+    pool: web::Data<Pool>,
+    redis: web::Data<Option<Arc<Redis>>>,
+    config: web::Data<Config>,
+    entrada_data: Json<CrearEntrada>,
+) -> Result<HttpResponse, ApiError> {
+    if simulacion {
+        let entrada = servicio.simular_alta(cine, &entrada_data, &sesion.sub).await?;
+        return Ok(ApiResponse::creada(ConEnlaces::new(entrada)).simulada().respond_to(&req));
+    }
+    let redis = redis.as_deref();
+    let creada = |id| EntradaCreada { id, qr: qr::firmar(&config.auth, id) };
+    let Some(clave) = idempotencia::clave(&req)? else {
+        let venta = servicio.vender(cine, &entrada_data, &sesion.sub).await?;
+        if !venta.repetida {
+            cambios.publicar_entrada(cine, Evento::Creada, venta.id);
+        }
+        return Ok(ApiResponse::creada(creada(venta.id)).respond_to(&req));
+    };
+
+    // El cine forma parte de la huella: la misma clave en otro cine no repite la respuesta.
+    let huella = idempotencia::huella(&(cine, &*entrada_data));
+    if let Reserva::Repetida(estado, data) =
+        idempotencia::reservar(&pool, redis, &clave, &huella, config.idempotencia_ttl).await?
+    {
+        let mut respuesta = ApiResponse::con_estado(estado, data).respond_to(&req);
+        respuesta
+            .headers_mut()
+            .insert(HeaderName::from_static(idempotencia::CABECERA_REPETIDA), HeaderValue::from_static("true"));
+        return Ok(respuesta);
+    }
+    match servicio.vender(cine, &entrada_data, &sesion.sub).await {
+        Ok(venta) => {
+            if !venta.repetida {
+                cambios.publicar_entrada(cine, Evento::Creada, venta.id);
+            }
+            let creada = creada(venta.id);
+            idempotencia::guardar(&pool, redis, &clave, &huella, StatusCode::CREATED, &creada).await;
+            Ok(ApiResponse::creada(creada).respond_to(&req))
+        }
+        Err(e) => {
+            idempotencia::liberar(&pool, redis, &clave).await;
+            Err(e.into())
+        }
+    }
+}
+
+/// Resultado de una entrada de `POST /entradas/bulk`, en la posición `indice` del lote.
+#[derive(Debug, Serialize)]
+pub struct ResultadoEntradaLote {
+    pub indice: usize,
+    /// `creada`, `rechazada` o `revertida` (válida, pero el lote no se guardó).
+    pub estado: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<u32>,
+    /// Código QR de las creadas, como en `POST /entradas`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub qr: Option<String>,
+    /// Con `dry_run=true`, la entrada que se crearía en lugar de su ID y su código.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entrada: Option<Entrada>,
+    /// Mismo formato que el `error` de las respuestas de error.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<serde_json::Value>,
+}
+
+/// Handler para crear varias entradas a la vez: se guardan todas o ninguna. Responde 201
+/// si se guardaron y, si no, con el código del primer error; en ambos casos `data` trae
+/// el resultado de cada entrada. Con `dry_run=true` el lote sólo se simula.
+pub async fn crear_entradas_lote(
+    sesion: Sesion,
+    CineActual(cine): CineActual,
+    Simulacion(simulacion): Simulacion,
+    servicio: web::Data<ServicioEntradas>,
+    cambios: web::Data<CanalCambios>,
+    config: web::Data<Config>,
+    entradas: Json<Vec<CrearEntrada>>,
+) -> Result<ApiResponse<Vec<ResultadoEntradaLote>>, ApiError> {
+    if simulacion {
+        let resultados = servicio.simular_lote(cine, &entradas, &sesion.sub).await?;
+        return Ok(respuesta_lote(resultados, |entrada| (None, None, Some(entrada))).simulada());
+    }
+    let resultados = servicio.crear_lote(cine, &entradas, &sesion.sub).await?;
+    cambios.publicar_entradas(cine, Evento::Creada, resultados.iter().filter_map(ResultadoLote::creada));
+    Ok(respuesta_lote(resultados, |id| (Some(id), Some(qr::firmar(&config.auth, id)), None)))
+}
+
+/// Respuesta de `POST /entradas/bulk` con el resultado de cada entrada; `creada` da el ID,
+/// el código y la entrada de las creadas.
+fn respuesta_lote<T>(
+    resultados: Vec<ResultadoLote<T>>,
+    creada: impl Fn(T) -> (Option<u32>, Option<String>, Option<Entrada>),
+) -> ApiResponse<Vec<ResultadoEntradaLote>> {
+    let mut estado = StatusCode::CREATED;
+    let resultados = resultados
+        .into_iter()
+        .enumerate()
+        .map(|(indice, resultado)| {
+            let (nombre, (id, qr, entrada), error) = match resultado {
+                ResultadoLote::Creada(valor) => ("creada", creada(valor), None),
+                ResultadoLote::Revertida => ("revertida", (None, None, None), None),
+                ResultadoLote::Rechazada(e) => {
+                    let error = ApiError::from(e);
+                    if estado == StatusCode::CREATED {
+                        estado = error.status_code();
+                    }
+                    ("rechazada", (None, None, None), Some(error.cuerpo()))
+                }
+            };
+            ResultadoEntradaLote { indice, estado: nombre, id, qr, entrada, error }
+        })
+        .collect();
+    ApiResponse::con_estado(estado, resultados)
+}
+
+/// Resultado de una fila de `POST /entradas/import`.
+#[derive(Debug, Serialize)]
+pub struct ResultadoFilaImportacion {
+    /// Línea del CSV, contando el encabezado como la 1.
+    pub fila: u64,
+    /// `creada` o `rechazada`.
+    pub estado: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<u32>,
+    /// Mismo formato que el `error` de las respuestas de error.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<serde_json::Value>,
+}
+
+/// Informe de `POST /entradas/import`.
+#[derive(Debug, Serialize)]
+pub struct ResultadoImportacion {
+    pub creadas: usize,
+    pub rechazadas: usize,
+    pub filas: Vec<ResultadoFilaImportacion>,
+}
+
+/// Handler que carga entradas desde un CSV subido en el campo `archivo` de un formulario
+/// multipart (ver [`importacion`]), para migrar datos de otros sistemas. Las filas válidas
+/// se guardan en lotes transaccionales y las demás se informan con su error, sin impedir
+/// que se guarden las otras. Sólo para administradores.
+pub async fn importar_entradas(
+    Administrador(admin): Administrador,
+    CineActual(cine): CineActual,
+    servicio: web::Data<ServicioEntradas>,
+    cambios: web::Data<CanalCambios>,
+    mut formulario: Multipart,
+) -> Result<ApiResponse<ResultadoImportacion>, ApiError> {
+    let contenido = importacion::leer_archivo(&mut formulario).await?;
+    let filas = importacion::leer_csv(&contenido).map_err(ApiError::Validacion)?;
+    let validas: Vec<CrearEntrada> = filas.iter().filter_map(|(_, fila)| fila.as_ref().ok().cloned()).collect();
+    let guardadas = servicio.importar(cine, &validas, &admin.sub).await?;
+    cambios.publicar_entradas(cine, Evento::Creada, guardadas.iter().filter_map(ResultadoLote::creada));
+    let mut guardadas = guardadas.into_iter();
+
+    let filas: Vec<_> = filas
+        .into_iter()
+        .map(|(fila, leida)| {
+            let resultado = leida.map(|_| guardadas.next().expect("Un resultado por cada fila válida"));
+            let (estado, id, error) = match resultado {
+                Ok(ResultadoLote::Creada(id)) => ("creada", Some(id), None),
+                Ok(ResultadoLote::Revertida) => ("revertida", None, None),
+                Ok(ResultadoLote::Rechazada(e)) => ("rechazada", None, Some(ApiError::from(e).cuerpo())),
+                Err(campos) => ("rechazada", None, Some(ApiError::CamposInvalidos(campos).cuerpo())),
+            };
+            ResultadoFilaImportacion { fila, estado, id, error }
+        })
+        .collect();
+    let creadas = filas.iter().filter(|fila| fila.id.is_some()).count();
+    Ok(ApiResponse::ok(ResultadoImportacion { creadas, rechazadas: filas.len() - creadas, filas }))
+}
+
+/// Handler que crea la entrada del cliente con esa cédula para la función, o la actualiza
+/// si ya la tiene, para que los quioscos puedan reenviar una venta sin duplicarla. Responde
+/// 201 o 200 con la entrada guardada o, con `dry_run=true`, con la que se guardaría.
+pub async fn guardar_entrada_por_cedula(
+    sesion: Sesion,
+    CineActual(cine): CineActual,
+    Simulacion(simulacion): Simulacion,
+    servicio: web::Data<ServicioEntradas>,
+    cambios: web::Data<CanalCambios>,
+    cedula: web::Path<String>,
+    datos: Json<GuardarEntrada>,
+) -> Result<ApiResponse<ConEnlaces<Entrada>>, ApiError> {
+    let error = |e| match e {
+        ErrorEntrada::ClienteInexistente => ApiError::NoEncontrado("Cliente no encontrado".to_string()),
+        e => e.into(),
+    };
+    if simulacion {
+        return match servicio.simular_guardado(cine, &cedula, &datos, &sesion.sub).await.map_err(error)? {
+            EntradaSimulada::Creada(entrada) => Ok(ApiResponse::creada(ConEnlaces::new(entrada)).simulada()),
+            EntradaSimulada::Actualizada(entrada) => Ok(ApiResponse::ok(ConEnlaces::new(entrada)).simulada()),
+            EntradaSimulada::SinCambios => Err(ErrorEntrada::NoEncontrada.into()),
+        };
+    }
+    let guardada = servicio.guardar_por_cedula(cine, &cedula, &datos, &sesion.sub).await.map_err(error)?;
+    match guardada {
+        EntradaGuardada::Creada(id) => {
+            cambios.publicar_entrada(cine, Evento::Creada, id);
+            Ok(ApiResponse::creada(ConEnlaces::new(servicio.obtener(cine, id).await?)))
+        }
+        EntradaGuardada::Actualizada(id) => {
+            cambios.publicar_entrada(cine, Evento::Actualizada, id);
+            Ok(ApiResponse::ok(ConEnlaces::new(servicio.obtener(cine, id).await?)))
+        }
+    }
+}
+
+/// Respuesta común de `PUT` y `PATCH` sobre la entrada `id`. Con `dry_run=true` no la
+/// cambia: responde con la entrada como quedaría.
+async fn actualizar_o_simular(
+    req: &HttpRequest,
+    simulacion: bool,
+    servicio: &ServicioEntradas,
+    cambios: &CanalCambios,
+    (cine, id): (u32, u32),
+    datos: &ActualizarEntrada,
+    actor: &str,
+) -> Result<HttpResponse, ApiError> {
+    let version = version_esperada(req)?;
+    let error = |e| match e {
+        ErrorEntrada::NoEncontrada => ApiError::NoEncontrado("Entrada no encontrada o sin cambios".to_string()),
+        e => e.into(),
+    };
+    if simulacion {
+        let entrada = servicio.simular_actualizacion(cine, id, datos, version, actor).await.map_err(error)?;
+        return Ok(ApiResponse::ok(ConEnlaces::new(entrada)).simulada().respond_to(req));
+    }
+    servicio.actualizar(cine, id, datos, version, actor).await.map_err(error)?;
+    cambios.publicar_entrada(cine, Evento::Actualizada, id);
+    Ok(ApiResponse::ok("Entrada actualizada exitosamente").respond_to(req))
+}
+
+/// Handler que reemplaza todos los datos de una entrada; faltar cualquiera es un 422.
+/// Sólo para administradores.
+#[allow(clippy::too_many_arguments)]
+pub async fn reemplazar_entrada(
+    Administrador(admin): Administrador,
+    CineActual(cine): CineActual,
+    Simulacion(simulacion): Simulacion,
+    servicio: web::Data<ServicioEntradas>,
+    cambios: web::Data<CanalCambios>,
+    path: web::Path<u32>,
+    entrada_data: Json<ReemplazarEntrada>,
+    req: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let datos = ActualizarEntrada::from(&*entrada_data);
+    let entrada = (cine, path.into_inner());
+    actualizar_o_simular(&req, simulacion, &servicio, &cambios, entrada, &datos, &admin.sub).await
+}
+
+/// Handler que actualiza sólo los campos presentes de una entrada. Sólo para administradores.
+#[allow(clippy::too_many_arguments)]
+pub async fn actualizar_entrada(
+    Administrador(admin): Administrador,
+    CineActual(cine): CineActual,
+    Simulacion(simulacion): Simulacion,
+    servicio: web::Data<ServicioEntradas>,
+    cambios: web::Data<CanalCambios>,
+    path: web::Path<u32>,
+    entrada_data: Json<ActualizarEntrada>,
+    req: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let entrada = (cine, path.into_inner());
+    actualizar_o_simular(&req, simulacion, &servicio, &cambios, entrada, &entrada_data, &admin.sub).await
+}
+
+/// Handler para eliminar una entrada de cine por su ID. Sólo para administradores.
+pub async fn eliminar_entrada(
+    Administrador(admin): Administrador,
+    CineActual(cine): CineActual,
+    servicio: web::Data<ServicioEntradas>,
+    cambios: web::Data<CanalCambios>,
+    path: web::Path<u32>,
+    req: HttpRequest,
+) -> Result<ApiResponse<&'static str>, ApiError> {
+    let version = version_esperada(&req)?;
+    let id = path.into_inner();
+    servicio.eliminar(cine, id, version, &admin.sub).await?;
+    cambios.publicar_entrada(cine, Evento::Eliminada, id);
+    Ok(ApiResponse::ok("Entrada eliminada exitosamente"))
+}
+
+/// Pasa la entrada a `estado` y responde con ella como queda. Una transición que su estado
+/// actual no admite es un 409.
+async fn cambiar_estado(
+    req: HttpRequest,
+    sesion: Sesion,
+    CineActual(cine): CineActual,
+    servicio: web::Data<ServicioEntradas>,
+    cambios: web::Data<CanalCambios>,
+    id: u32,
+    estado: EstadoEntrada,
+) -> Result<HttpResponse, ApiError> {
+    servicio.cambiar_estado(cine, id, estado, &sesion.sub).await?;
+    cambios.publicar_entrada(cine, Evento::Actualizada, id);
+    let entrada = servicio.obtener(cine, id).await?;
+    Ok(respuesta_con_etag(&req, entrada))
+}
+
+/// Handler que marca como pagada una entrada reservada.
+pub async fn pagar_entrada(
+    req: HttpRequest,
+    sesion: Sesion,
+    cine: CineActual,
+    servicio: web::Data<ServicioEntradas>,
+    cambios: web::Data<CanalCambios>,
+    path: web::Path<u32>,
+) -> Result<HttpResponse, ApiError> {
+    cambiar_estado(req, sesion, cine, servicio, cambios, path.into_inner(), EstadoEntrada::Pagada).await
+}
+
+/// Cuerpo de `POST /entradas/{id}/checkin`: el código QR escaneado.
+#[derive(Debug, Deserialize)]
+pub struct Checkin {
+    pub qr: String,
+}
+
+/// Handler del control de acceso: marca como usada una entrada pagada al escanear su código
+/// QR en la puerta. Sólo se puede usar una vez; volver a escanearla es un 409.
+#[allow(clippy::too_many_arguments)]
+pub async fn checkin_entrada(
+    req: HttpRequest,
+    sesion: Sesion,
+    CineActual(cine): CineActual,
+    servicio: web::Data<ServicioEntradas>,
+    cambios: web::Data<CanalCambios>,
+    config: web::Data<Config>,
+    path: web::Path<u32>,
+    datos: Json<Checkin>,
+) -> Result<HttpResponse, ApiError> {
+    let id = path.into_inner();
+    if qr::verificar(&config.auth, &datos.qr) != Some(id) {
+        return Err(ApiError::CamposInvalidos(vec![ErrorCampo {
+            campo: "qr".into(),
+            mensaje: "El código QR no es válido para esta entrada".to_string(),
+        }]));
+    }
+    match servicio.cambiar_estado(cine, id, EstadoEntrada::Usada, &sesion.sub).await {
+        Err(ErrorEntrada::TransicionInvalida(EstadoEntrada::Usada)) => {
+            return Err(ApiError::Conflicto("La entrada ya se usó".to_string()));
+        }
+        resultado => resultado?,
+    }
+    cambios.publicar_entrada(cine, Evento::Checkin, id);
+    Ok(respuesta_con_etag(&req, servicio.obtener(cine, id).await?))
+}
+
+/// Handler que cancela una entrada reservada o pagada, liberando su capacidad y sus asientos.
+pub async fn cancelar_entrada(
+    req: HttpRequest,
+    sesion: Sesion,
+    cine: CineActual,
+    servicio: web::Data<ServicioEntradas>,
+    cambios: web::Data<CanalCambios>,
+    path: web::Path<u32>,
+) -> Result<HttpResponse, ApiError> {
+    cambiar_estado(req, sesion, cine, servicio, cambios, path.into_inner(), EstadoEntrada::Cancelada).await
+}
+
+/// Entradas que canceló `POST /funciones/{id}/cancelar`.
+#[derive(Debug, Serialize)]
+pub struct ResultadoCancelacion {
+    pub canceladas: usize,
+    pub ids: Vec<u32>,
+}
+
+/// Handler que cancela todas las entradas reservadas o pagadas de una función, por ejemplo
+/// si la función se suspende, con el motivo del cuerpo en la auditoría de cada una. Se
+/// cancelan todas o ninguna. Sólo para administradores.
+pub async fn cancelar_funcion(
+    Administrador(admin): Administrador,
+    CineActual(cine): CineActual,
+    servicio: web::Data<ServicioEntradas>,
+    cambios: web::Data<CanalCambios>,
+    path: web::Path<u32>,
+    datos: Json<CancelarFuncion>,
+) -> Result<ApiResponse<ResultadoCancelacion>, ApiError> {
+    let ids = match servicio.cancelar_funcion(cine, path.into_inner(), &datos.motivo, &admin.sub).await {
+        Err(ErrorEntrada::FuncionInexistente) => return Err(ApiError::NoEncontrado("Función no encontrada".to_string())),
+        resultado => resultado?,
+    };
+    cambios.publicar_entradas(cine, Evento::Actualizada, ids.iter().copied());
+    Ok(ApiResponse::ok(ResultadoCancelacion { canceladas: ids.len(), ids }))
+}
+
+/// Entradas que eliminó `DELETE /entradas`.
+#[derive(Debug, Serialize)]
+pub struct ResultadoEliminacion {
+    pub eliminadas: u64,
+}
+
+/// Handler que elimina todas las entradas que cumplen el filtro del cuerpo, por ejemplo
+/// las de una función cancelada. Sólo para administradores.
+pub async fn eliminar_entradas(
+    Administrador(admin): Administrador,
+    CineActual(cine): CineActual,
+    servicio: web::Data<ServicioEntradas>,
+    cambios: web::Data<CanalCambios>,
+    filtro: Json<EliminarEntradas>,
+) -> Result<ApiResponse<ResultadoEliminacion>, ApiError> {
+    let eliminadas = servicio.eliminar_filtradas(cine, &filtro, &admin.sub).await?;
+    if eliminadas > 0 {
+        cambios.publicar(Cambio::Recargar);
+    }
+    Ok(ApiResponse::ok(ResultadoEliminacion { eliminadas }))
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test;
+
+    use super::*;
+    use crate::auth::emitir_token;
+    use crate::db::memoria::RepositorioMemoria;
+    use crate::db::obtener_pool_db;
+    use crate::models::Rol;
+    use crate::{create_app, Estado};
+
+    /// La aplicación completa sobre el repositorio en memoria. La pool no llega a conectarse:
+    /// las rutas de entradas con token no la usan.
+    fn estado() -> Estado {
+        let mut config = Config::new("mysql://root@127.0.0.1:1/pruebas");
+        config.auth.secreto = "secreto-de-pruebas".to_string();
+        let pool = obtener_pool_db(&config).unwrap();
+        let mut estado = Estado::new(config, pool);
+        estado.entradas = Arc::new(ServicioEntradas::new(
+            Arc::new(RepositorioMemoria::default().con_limite_por_cedula(Some(4))),
+            estado.config.reglas_validacion(),
+            estado.config.zona_horaria,
+        ));
+        estado
+    }
+
+    fn autorizacion(estado: &Estado, rol: Rol) -> (&'static str, String) {
+        ("Authorization", format!("Bearer {}", emitir_token(&estado.config.auth, "pruebas", rol, None).unwrap()))
+    }
+
+    #[actix_web::test]
+    async fn crea_y_lee_una_entrada() {
+        let estado = estado();
+        let admin = autorizacion(&estado, Rol::Admin);
+        let mut cambios = estado.cambios.suscribir();
+        let app = test::init_service(create_app(estado)).await;
+
+        let req = test::TestRequest::post()
+            .uri("/v1/entradas")
+            .insert_header(admin.clone())
+            .set_json(serde_json::json!({ "cliente_id": 1, "funcion_id": 1, "cantidad_entradas": 2 }))
+            .to_request();
+        let respuesta = test::call_service(&app, req).await;
+        assert_eq!(respuesta.status(), StatusCode::CREATED);
+        let creada = Cambio::Entrada { cine: crate::cines::CINE_PRINCIPAL, evento: Evento::Creada, id: 1 };
+        assert_eq!(cambios.try_recv().unwrap(), creada);
+
+        let req = test::TestRequest::get().uri("/v1/entradas/1").insert_header(admin.clone()).to_request();
+        let respuesta = test::call_service(&app, req).await;
+        assert_eq!(respuesta.status(), StatusCode::OK);
+        assert_eq!(respuesta.headers().get(ETAG).unwrap(), "\"1\"");
+        let ApiResponse { data: entrada, .. }: ApiResponse<serde_json::Value> = test::read_body_json(respuesta).await;
+        assert_eq!(entrada["cantidad_entradas"], 2);
+        assert_eq!(entrada["total"], 13.0);
+        assert_eq!(entrada["links"]["update"], serde_json::json!({ "href": "/v1/entradas/1", "method": "PATCH" }));
+        assert_eq!(entrada["links"]["funcion"]["href"], "/v1/funciones/1");
+        assert!(entrada["links"].get("checkin").is_none());
+
+        let req = test::TestRequest::get().uri("/v1/entradas/99").insert_header(admin).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn responde_los_errores_del_servicio_con_su_estado() {
+        let estado = estado();
+        let admin = autorizacion(&estado, Rol::Admin);
+        let taquillero = autorizacion(&estado, Rol::Taquillero);
+        let app = test::init_service(create_app(estado)).await;
+
+        let crear = |cantidad: u32| {
+            test::TestRequest::post()
+                .uri("/v1/entradas")
+                .insert_header(admin.clone())
+                .set_json(serde_json::json!({ "cliente_id": 1, "funcion_id": 1, "cantidad_entradas": cantidad }))
+                .to_request()
+        };
+        assert_eq!(test::call_service(&app, crear(0)).await.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(test::call_service(&app, crear(2)).await.status(), StatusCode::CREATED);
+        assert_eq!(test::call_service(&app, crear(3)).await.status(), StatusCode::CONFLICT);
+
+        let req = test::TestRequest::get().uri("/v1/entradas/1").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::UNAUTHORIZED);
+        let req = test::TestRequest::delete().uri("/v1/entradas/1").insert_header(taquillero).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+
+        let req = test::TestRequest::patch()
+            .uri("/v1/entradas/1")
+            .insert_header(admin.clone())
+            .insert_header((IF_MATCH, "\"7\""))
+            .set_json(serde_json::json!({ "cantidad_entradas": 4 }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::PRECONDITION_FAILED);
+    }
+
+    #[actix_web::test]
+    async fn cada_cine_ve_solo_sus_entradas() {
+        let estado = estado();
+        let admin = autorizacion(&estado, Rol::Admin);
+        let del_norte = (
+            "Authorization",
+            format!("Bearer {}", emitir_token(&estado.config.auth, "norte", Rol::Admin, Some(2)).unwrap()),
+        );
+        let app = test::init_service(create_app(estado)).await;
+
+        let req = test::TestRequest::post()
+            .uri("/v1/entradas")
+            .insert_header(admin.clone())
+            .set_json(serde_json::json!({ "cliente_id": 1, "funcion_id": 1, "cantidad_entradas": 2 }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CREATED);
+
+        let leer = |autorizacion: (&'static str, String), cine: Option<&'static str>| {
+            let mut req = test::TestRequest::get().uri("/v1/entradas/1").insert_header(autorizacion);
+            if let Some(cine) = cine {
+                req = req.insert_header(("X-Cine-Id", cine));
+            }
+            req.to_request()
+        };
+        assert_eq!(test::call_service(&app, leer(admin.clone(), None)).await.status(), StatusCode::OK);
+        assert_eq!(test::call_service(&app, leer(admin.clone(), Some("2"))).await.status(), StatusCode::NOT_FOUND);
+        assert_eq!(test::call_service(&app, leer(admin, Some("norte"))).await.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(test::call_service(&app, leer(del_norte.clone(), None)).await.status(), StatusCode::NOT_FOUND);
+        assert_eq!(test::call_service(&app, leer(del_norte, Some("1"))).await.status(), StatusCode::FORBIDDEN);
+    }
+}
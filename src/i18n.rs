@@ -0,0 +1,481 @@
+//! Idioma de los mensajes de la API, elegido con `Accept-Language` entre español, el de
+//! siempre, e inglés.
+//!
+//! Los mensajes se escriben en español en el código; [`Idioma::traducir`] los busca en el
+//! catálogo inglés, donde `{}` marca los valores que cambian en cada mensaje. Un mensaje
+//! que no está en el catálogo, como los de los extractores de actix, se deja como está. El
+//! middleware [`elegir_idioma`] guarda el idioma de la petición y traduce los errores; los
+//! mensajes de [`crate::respuesta::ApiResponse`] se traducen al responder.
+
+use std::borrow::Cow;
+
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::error::InternalError;
+use actix_web::http::header::{HeaderMap, HeaderValue, ACCEPT_LANGUAGE, CONTENT_LANGUAGE, VARY};
+use actix_web::middleware::Next;
+use actix_web::{Error, HttpMessage, HttpRequest};
+
+use crate::error::ApiError;
+
+/// Idioma de las respuestas.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Idioma {
+    #[default]
+    Es,
+    En,
+}
+
+impl Idioma {
+    /// Etiqueta del idioma para `Content-Language`.
+    pub fn como_str(self) -> &'static str {
+        match self {
+            Idioma::Es => "es",
+            Idioma::En => "en",
+        }
+    }
+
+    /// Idioma preferido en un valor de `Accept-Language`, como `en-US,en;q=0.9,es;q=0.8`.
+    /// Sin ningún idioma conocido, español.
+    pub fn de_cabecera(valor: &str) -> Idioma {
+        let mut elegido: Option<(f32, Idioma)> = None;
+        for rango in valor.split(',') {
+            let mut partes = rango.split(';');
+            let etiqueta = partes.next().unwrap_or_default().trim();
+            let calidad = partes
+                .find_map(|parte| parte.trim().strip_prefix("q="))
+                .map_or(1.0, |q| q.trim().parse::<f32>().unwrap_or(0.0));
+            let primaria = etiqueta.split('-').next().unwrap_or_default();
+            let idioma = if primaria.eq_ignore_ascii_case("es") {
+                Idioma::Es
+            } else if primaria.eq_ignore_ascii_case("en") {
+                Idioma::En
+            } else {
+                continue;
+            };
+            if calidad > 0.0 && elegido.is_none_or(|(mejor, _)| calidad > mejor) {
+                elegido = Some((calidad, idioma));
+            }
+        }
+        elegido.map(|(_, idioma)| idioma).unwrap_or_default()
+    }
+
+    /// Idioma que [`elegir_idioma`] guardó en la petición, o español si no pasó por él.
+    pub fn de_peticion(req: &HttpRequest) -> Idioma {
+        req.extensions().get::<Idioma>().copied().unwrap_or_default()
+    }
+
+    /// `mensaje`, escrito en español, en este idioma.
+    pub fn traducir(self, mensaje: &str) -> Cow<'_, str> {
+        match self {
+            Idioma::Es => Cow::Borrowed(mensaje),
+            Idioma::En => CATALOGO_EN
+                .iter()
+                .find_map(|(original, traduccion)| {
+                    coincidir(original, mensaje).map(|valores| Cow::Owned(rellenar(traduccion, &valores)))
+                })
+                .unwrap_or(Cow::Borrowed(mensaje)),
+        }
+    }
+}
+
+/// Valores que toman los `{}` de `patron` en `mensaje`, si `mensaje` sigue el patrón.
+fn coincidir<'a>(patron: &str, mensaje: &'a str) -> Option<Vec<&'a str>> {
+    let mut partes = patron.split("{}");
+    let mut resto = mensaje.strip_prefix(partes.next().unwrap_or_default())?;
+    let mut valores = Vec::new();
+    let mut partes = partes.peekable();
+    while let Some(parte) = partes.next() {
+        let fin = if partes.peek().is_none() {
+            resto.strip_suffix(parte).map(str::len)?
+        } else if parte.is_empty() {
+            return None;
+        } else {
+            resto.find(parte)?
+        };
+        valores.push(&resto[..fin]);
+        resto = &resto[fin + parte.len()..];
+    }
+    resto.is_empty().then_some(valores)
+}
+
+/// `plantilla` con sus `{}` sustituidos, en orden, por `valores`.
+fn rellenar(plantilla: &str, valores: &[&str]) -> String {
+    let mut texto = String::with_capacity(plantilla.len());
+    for (i, parte) in plantilla.split("{}").enumerate() {
+        if i > 0 {
+            texto.push_str(valores.get(i - 1).copied().unwrap_or_default());
+        }
+        texto.push_str(parte);
+    }
+    texto
+}
+
+/// Middleware que guarda en la petición el idioma de su `Accept-Language` y, si no es
+/// español, traduce el error con que responde. Indica el idioma en `Content-Language`.
+pub async fn elegir_idioma(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let idioma = req
+        .headers()
+        .get(ACCEPT_LANGUAGE)
+        .and_then(|valor| valor.to_str().ok())
+        .map(Idioma::de_cabecera)
+        .unwrap_or_default();
+    req.extensions_mut().insert(idioma);
+    let respuesta = match next.call(req).await {
+        Ok(respuesta) => respuesta.map_into_boxed_body(),
+        Err(error) => {
+            return match error.as_error::<ApiError>() {
+                Some(error) if idioma != Idioma::Es => {
+                    let mut traducida = error.respuesta(idioma);
+                    indicar_idioma(traducida.headers_mut(), idioma);
+                    Err(InternalError::from_response(error.clone(), traducida).into())
+                }
+                _ => Err(error),
+            };
+        }
+    };
+    let error = respuesta.response().error().and_then(|error| error.as_error::<ApiError>()).cloned();
+    let mut respuesta = match error {
+        Some(error) if idioma != Idioma::Es => {
+            let (peticion, original) = respuesta.into_parts();
+            let mut traducida = error.respuesta(idioma);
+            for (nombre, valor) in original.headers() {
+                if !traducida.headers().contains_key(nombre) {
+                    traducida.headers_mut().append(nombre.clone(), valor.clone());
+                }
+            }
+            ServiceResponse::new(peticion, traducida)
+        }
+        _ => respuesta,
+    };
+    indicar_idioma(respuesta.headers_mut(), idioma);
+    Ok(respuesta)
+}
+
+fn indicar_idioma(cabeceras: &mut HeaderMap, idioma: Idioma) {
+    cabeceras.insert(CONTENT_LANGUAGE, HeaderValue::from_static(idioma.como_str()));
+    cabeceras.append(VARY, HeaderValue::from_static("accept-language"));
+}
+
+/// Mensajes en inglés, por su original en español.
+const CATALOGO_EN: &[(&str, &str)] = &[
+    // Errores comunes
+    ("Error al conectar a la base de datos", "Could not connect to the database"),
+    ("La base de datos está ocupada; reintenta la operación", "The database is busy; retry the operation"),
+    ("La base de datos no respondió en {} s", "The database did not respond within {} s"),
+    ("La pool no prestó ninguna conexión en {} ms", "No database connection became available within {} ms"),
+    ("La petición no terminó en {} ms", "The request did not finish within {} ms"),
+    ("Demasiadas peticiones, reintenta en {} s", "Too many requests, retry in {} s"),
+    ("El cuerpo supera el máximo de {} bytes", "The body exceeds the maximum of {} bytes"),
+    ("El archivo supera el máximo de {} bytes", "The file exceeds the maximum of {} bytes"),
+    ("Ya existe un registro con ese valor", "A record with that value already exists"),
+    ("Hay otros registros que dependen de este", "Other records depend on this one"),
+    ("Uno de los recursos indicados no existe", "One of the referenced resources does not exist"),
+    ("El valor es demasiado largo", "The value is too long"),
+    ("El parámetro dry_run debe ser true o false", "The dry_run parameter must be true or false"),
+    ("Esta ruta no admite dry_run", "This route does not support dry_run"),
+    // Autenticación
+    ("Se requiere autenticación", "Authentication is required"),
+    ("Falta la cabecera 'Authorization: Bearer <token>'", "Missing 'Authorization: Bearer <token>' header"),
+    ("Token inválido o caducado", "Invalid or expired token"),
+    ("Token de refresco inválido, caducado o revocado", "Invalid, expired or revoked refresh token"),
+    ("Usuario o clave incorrectos", "Incorrect username or password"),
+    ("El usuario ya no existe", "The user no longer exists"),
+    ("El usuario debe tener entre 1 y {} caracteres", "The username must be between 1 and {} characters"),
+    ("La clave debe tener al menos {} caracteres", "The password must be at least {} characters"),
+    ("Ya existe un usuario con ese nombre", "A user with that name already exists"),
+    ("Esta operación requiere el rol de administrador", "This operation requires the administrator role"),
+    ("Clave de API inválida o revocada", "Invalid or revoked API key"),
+    ("Clave de API no encontrada o ya revocada", "API key not found or already revoked"),
+    ("El nombre de la clave es obligatorio", "The key name is required"),
+    ("No puede dar acceso a otro cine", "Cannot grant access to another cinema"),
+    ("Error al iniciar sesión", "Error while logging in"),
+    ("Error al refrescar la sesión", "Error while refreshing the session"),
+    ("Error al cerrar la sesión", "Error while logging out"),
+    ("Error al emitir el token", "Error while issuing the token"),
+    ("Error al registrar usuario", "Error while registering the user"),
+    ("Error al crear la clave de API", "Error while creating the API key"),
+    ("Error al obtener las claves de API", "Error while fetching the API keys"),
+    ("Error al revocar la clave de API", "Error while revoking the API key"),
+    ("Error al verificar la clave de API", "Error while verifying the API key"),
+    // Cines
+    ("Cine no encontrado", "Cinema not found"),
+    ("El cine indicado no existe", "The given cinema does not exist"),
+    ("El cine principal no se puede eliminar", "The main cinema cannot be deleted"),
+    ("El cine todavía tiene datos o usuarios", "The cinema still has data or users"),
+    ("Ya existe un cine con ese nombre", "A cinema with that name already exists"),
+    ("El nombre del cine debe tener entre 1 y {} caracteres", "The cinema name must be between 1 and {} characters"),
+    ("La cabecera {} debe ser el ID de un cine", "The {} header must be a cinema ID"),
+    ("La sesión no tiene acceso a ese cine", "The session has no access to that cinema"),
+    (
+        "Sólo un administrador de toda la cadena puede gestionar los cines",
+        "Only a chain-wide administrator can manage cinemas",
+    ),
+    ("Error al obtener los cines", "Error while fetching the cinemas"),
+    ("Error al crear el cine", "Error while creating the cinema"),
+    ("Error al renombrar el cine", "Error while renaming the cinema"),
+    ("Error al eliminar el cine", "Error while deleting the cinema"),
+    // Entradas
+    ("Entrada no encontrada", "Ticket not found"),
+    ("Entrada no encontrada o sin cambios", "Ticket not found or unchanged"),
+    ("Entrada actualizada exitosamente", "Ticket updated successfully"),
+    ("Entrada eliminada exitosamente", "Ticket deleted successfully"),
+    ("No se proporcionaron datos para actualizar", "No data was provided to update"),
+    ("La entrada contiene campos inválidos", "The request contains invalid fields"),
+    ("Sólo quedan {} asientos disponibles para la función", "Only {} seats are left for the showing"),
+    (
+        "La cédula sólo puede comprar {} entradas más para la función",
+        "The ID number can only buy {} more tickets for the showing",
+    ),
+    ("Los asientos {} ya están ocupados", "Seats {} are already taken"),
+    ("El asiento {} no existe en la sala", "Seat {} does not exist in the room"),
+    (
+        "La entrada tiene asientos asignados; para cambiar la función o la cantidad hay que eliminarla y volver a comprarla",
+        "The ticket has assigned seats; to change the showing or the quantity, delete it and buy it again",
+    ),
+    (
+        "La entrada cambió desde que se leyó; su versión actual es la {}",
+        "The ticket changed since it was read; its current version is {}",
+    ),
+    ("El cliente indicado no existe", "The given customer does not exist"),
+    ("La función indicada no existe", "The given showing does not exist"),
+    ("La entrada está {} y no puede pasar a ese estado", "The ticket is {} and cannot move to that state"),
+    ("La entrada ya se usó", "The ticket has already been used"),
+    ("El código QR no es válido para esta entrada", "The QR code is not valid for this ticket"),
+    ("Falta la cabecera If-Match con el ETag de la entrada", "Missing If-Match header with the ticket's ETag"),
+    ("If-Match no corresponde a ninguna versión de la entrada", "If-Match does not match any version of the ticket"),
+    ("La clave de idempotencia ya se usó con otros datos", "The idempotency key was already used with different data"),
+    ("Ya hay una petición en curso con esa clave de idempotencia", "A request with that idempotency key is already in progress"),
+    ("Error al comprobar la clave de idempotencia", "Error while checking the idempotency key"),
+    // Listados y consultas
+    ("El parámetro 'page' empieza en 1", "The 'page' parameter starts at 1"),
+    ("El parámetro 'per_page' debe ser mayor que 0", "The 'per_page' parameter must be greater than 0"),
+    ("El parámetro 'limit' debe ser mayor que 0", "The 'limit' parameter must be greater than 0"),
+    ("El parámetro 'after' no es un cursor válido", "The 'after' parameter is not a valid cursor"),
+    ("El cursor de 'after' es de un listado con otro orden", "The 'after' cursor belongs to a listing with a different order"),
+    (
+        "Los parámetros 'limit' y 'after' no se combinan con 'page' ni 'per_page'",
+        "The 'limit' and 'after' parameters cannot be combined with 'page' or 'per_page'",
+    ),
+    ("El parámetro 'order' debe ser 'asc' o 'desc'", "The 'order' parameter must be 'asc' or 'desc'"),
+    ("El parámetro 'fields' no puede estar vacío", "The 'fields' parameter cannot be empty"),
+    ("El parámetro 'format' debe ser 'csv' o 'xlsx'", "The 'format' parameter must be 'csv' or 'xlsx'"),
+    ("El parámetro 'q' no puede estar vacío", "The 'q' parameter cannot be empty"),
+    ("El parámetro 'nombre' no puede estar vacío", "The 'nombre' parameter cannot be empty"),
+    ("El parámetro 'umbral' debe estar entre 0 y 1", "The 'umbral' parameter must be between 0 and 1"),
+    (
+        "El parámetro 'created_after' debe ser una fecha AAAA-MM-DD o AAAA-MM-DD HH:MM:SS",
+        "The 'created_after' parameter must be a YYYY-MM-DD or YYYY-MM-DD HH:MM:SS date",
+    ),
+    (
+        "El parámetro 'horario_funcion' debe ser una fecha y hora RFC 3339",
+        "The 'horario_funcion' parameter must be an RFC 3339 date and time",
+    ),
+    ("El parámetro 'desde' no puede ser posterior a 'hasta'", "The 'desde' parameter cannot be later than 'hasta'"),
+    ("El parámetro '{}' debe ser una fecha AAAA-MM-DD", "The '{}' parameter must be a YYYY-MM-DD date"),
+    ("No existe el campo '{}'. Campos disponibles: {}", "There is no field '{}'. Available fields: {}"),
+    (
+        "No se permite ordenar por el campo '{}'. Campos disponibles: {}",
+        "Sorting by the field '{}' is not allowed. Available fields: {}",
+    ),
+    (
+        "No se ofrecen sugerencias para el campo '{}'. Campos disponibles: {}",
+        "No suggestions are offered for the field '{}'. Available fields: {}",
+    ),
+    ("No se permite agrupar por el campo '{}'", "Grouping by the field '{}' is not allowed"),
+    ("No se permite agregar sobre el campo '{}'", "Aggregating over the field '{}' is not allowed"),
+    ("Función de agregación no soportada: '{}'", "Unsupported aggregate function: '{}'"),
+    (
+        "La agregación '{}' requiere un campo (por ejemplo {}:cantidad_entradas)",
+        "The aggregate '{}' requires a field (for example {}:cantidad_entradas)",
+    ),
+    ("No se pudo generar el XLSX; acota el listado con filtros: {}", "Could not generate the XLSX; narrow the listing with filters: {}"),
+    // Importación
+    ("Falta el campo 'archivo' con el CSV", "Missing the 'archivo' field with the CSV"),
+    ("El formulario no es válido: {}", "The form is not valid: {}"),
+    ("No se pudo leer el encabezado del CSV: {}", "Could not read the CSV header: {}"),
+    ("El CSV debe tener las columnas {}", "The CSV must have the columns {}"),
+    ("El CSV no tiene filas de entradas", "The CSV has no ticket rows"),
+    ("El CSV no puede tener más de {} filas", "The CSV cannot have more than {} rows"),
+    ("La fila no es CSV válido", "The row is not valid CSV"),
+    // Validación de campos
+    ("No puede estar vacío", "Cannot be empty"),
+    ("No puede superar los {} caracteres", "Cannot exceed {} characters"),
+    ("Sólo puede contener dígitos", "Can only contain digits"),
+    ("Debe tener entre {} y {} dígitos", "Must have between {} and {} digits"),
+    ("Debe estar entre {} y {}", "Must be between {} and {}"),
+    ("Debe ser mayor que 0", "Must be greater than 0"),
+    ("Debe ser un importe mayor o igual que 0", "Must be an amount greater than or equal to 0"),
+    ("Debe ser un número entero positivo", "Must be a positive integer"),
+    ("Debe indicar un asiento por entrada", "Must give one seat per ticket"),
+    ("Deben ser números de asiento separados por espacios", "Must be seat numbers separated by spaces"),
+    ("Los asientos se numeran desde 1", "Seats are numbered from 1"),
+    ("No puede repetir asientos", "Cannot repeat seats"),
+    ("No es una dirección de correo válida", "Not a valid email address"),
+    ("Una cédula ecuatoriana tiene 10 dígitos", "An Ecuadorian ID number has 10 digits"),
+    ("El código de provincia de la cédula no es válido", "The province code of the ID number is not valid"),
+    ("El tercer dígito de la cédula debe ser menor que 6", "The third digit of the ID number must be less than 6"),
+    ("El dígito verificador de la cédula no es correcto", "The check digit of the ID number is not correct"),
+    // Clientes
+    ("Cliente no encontrado", "Customer not found"),
+    ("Ya existe un cliente con ese número de cédula", "A customer with that ID number already exists"),
+    ("El cliente tiene entradas compradas", "The customer has purchased tickets"),
+    ("Error al obtener cliente", "Error while fetching the customer"),
+    ("Error al obtener clientes", "Error while fetching the customers"),
+    ("Error al crear cliente", "Error while creating the customer"),
+    ("Error al actualizar cliente", "Error while updating the customer"),
+    ("Error al eliminar cliente", "Error while deleting the customer"),
+    ("Error al buscar clientes", "Error while searching the customers"),
+    // Salas y funciones
+    ("Sala no encontrada", "Room not found"),
+    ("La sala indicada no existe", "The given room does not exist"),
+    ("La sala todavía tiene funciones", "The room still has showings"),
+    ("Ya existe una sala con ese nombre en el cine", "A room with that name already exists in the cinema"),
+    ("Error al obtener sala", "Error while fetching the room"),
+    ("Error al obtener salas", "Error while fetching the rooms"),
+    ("Error al crear sala", "Error while creating the room"),
+    ("Error al actualizar sala", "Error while updating the room"),
+    ("Error al eliminar sala", "Error while deleting the room"),
+    ("Función no encontrada", "Showing not found"),
+    ("El motivo debe tener entre 1 y {} caracteres", "The reason must be between 1 and {} characters"),
+    ("Error al cancelar las entradas de la función", "Error while cancelling the showing's tickets"),
+    ("La función tiene entradas vendidas", "The showing has sold tickets"),
+    (
+        "Ya existe una función con ese título en la misma sala y horario",
+        "A showing with that title already exists in the same room and time",
+    ),
+    ("Error al obtener función", "Error while fetching the showing"),
+    ("Error al obtener funciones", "Error while fetching the showings"),
+    ("Error al crear función", "Error while creating the showing"),
+    ("Error al actualizar función", "Error while updating the showing"),
+    ("Error al eliminar función", "Error while deleting the showing"),
+    ("Error al obtener asientos", "Error while fetching the seats"),
+    ("Error al obtener sugerencias", "Error while fetching the suggestions"),
+    ("Póster no encontrado", "Poster not found"),
+    ("El póster debe ser una imagen PNG, JPEG o WebP", "The poster must be a PNG, JPEG or WebP image"),
+    ("El archivo es {} pero se envió como {}", "The file is {} but was sent as {}"),
+    ("Falta el campo 'archivo' con la imagen", "Missing the 'archivo' field with the image"),
+    ("Error al guardar el póster", "Error while saving the poster"),
+    ("Error al leer el póster", "Error while reading the poster"),
+    // Reportes
+    ("Error al obtener el reporte de ventas", "Error while fetching the sales report"),
+    ("Error al obtener las funciones más vendidas", "Error while fetching the best-selling showings"),
+    // Dispositivos
+    ("Dispositivo no encontrado", "Device not found"),
+    ("Ya existe un dispositivo con ese nombre", "A device with that name already exists"),
+    ("nombre, sucursal y version son obligatorios", "nombre, sucursal and version are required"),
+    ("Error al obtener dispositivo", "Error while fetching the device"),
+    ("Error al obtener dispositivos", "Error while fetching the devices"),
+    ("Error al registrar dispositivo", "Error while registering the device"),
+    ("Error al registrar latido", "Error while recording the heartbeat"),
+    // Webhooks
+    ("Webhook no encontrado", "Webhook not found"),
+    ("Webhook no encontrado o ya desactivado", "Webhook not found or already deactivated"),
+    ("La URL del webhook debe ser http o https", "The webhook URL must be http or https"),
+    ("El secreto no puede estar vacío", "The secret cannot be empty"),
+    ("Indique al menos un evento", "Give at least one event"),
+    ("Error al crear el webhook", "Error while creating the webhook"),
+    ("Error al obtener el webhook", "Error while fetching the webhook"),
+    ("Error al obtener los webhooks", "Error while fetching the webhooks"),
+    ("Error al obtener las entregas del webhook", "Error while fetching the webhook deliveries"),
+    ("Error al desactivar el webhook", "Error while deactivating the webhook"),
+    // Registro de accesos
+    (
+        "Sólo un administrador de toda la cadena puede consultar el registro de accesos",
+        "Only a chain-wide administrator can query the access log",
+    ),
+    ("Error al obtener los accesos", "Error while fetching the access log"),
+    // Archivo
+    (
+        "Sólo un administrador de toda la cadena puede archivar entradas",
+        "Only a chain-wide administrator can archive tickets",
+    ),
+    ("Indique antiguedad_dias mayor que 0", "Give antiguedad_dias greater than 0"),
+    ("Error al archivar las entradas", "Error while archiving the tickets"),
+    ("Error al obtener las entradas archivadas", "Error while fetching the archived tickets"),
+    // Purga
+    (
+        "Sólo un administrador de toda la cadena puede purgar entradas",
+        "Only a chain-wide administrator can purge tickets",
+    ),
+    ("Indique older_than en días mayor que 0, como 30d", "Give older_than in days greater than 0, such as 30d"),
+    ("Error al purgar las entradas", "Error while purging the tickets"),
+    // Respaldos
+    (
+        "Sólo un administrador de toda la cadena puede respaldar la base de datos",
+        "Only a chain-wide administrator can back up the database",
+    ),
+    ("Error al iniciar el respaldo", "Error while starting the backup"),
+    // Otros
+    ("GraphQL Playground está desactivado", "GraphQL Playground is disabled"),
+    ("EXPLAIN no devolvió ningún plan", "EXPLAIN returned no plan"),
+    ("Error al obtener el plan de la consulta", "Error while fetching the query plan"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::middleware::from_fn;
+    use actix_web::test::{call_service, init_service, read_body_json, TestRequest};
+    use actix_web::{http::StatusCode, web, App, HttpResponse};
+    use serde_json::Value;
+
+    use crate::respuesta::ApiResponse;
+    use crate::servicio::ErrorEntrada;
+
+    #[test]
+    fn elige_el_idioma_preferido_que_conoce() {
+        assert_eq!(Idioma::de_cabecera("en-US,en;q=0.9"), Idioma::En);
+        assert_eq!(Idioma::de_cabecera("fr, es;q=0.5, en;q=0.8"), Idioma::En);
+        assert_eq!(Idioma::de_cabecera("EN;q=0.3, es-EC"), Idioma::Es);
+        assert_eq!(Idioma::de_cabecera("en;q=0"), Idioma::Es);
+        assert_eq!(Idioma::de_cabecera("fr, *"), Idioma::Es);
+    }
+
+    #[test]
+    fn traduce_los_mensajes_con_valores() {
+        assert_eq!(Idioma::En.traducir("Entrada no encontrada"), "Ticket not found");
+        assert_eq!(
+            Idioma::En.traducir("Sólo quedan 3 asientos disponibles para la función"),
+            "Only 3 seats are left for the showing"
+        );
+        assert_eq!(Idioma::En.traducir("Debe tener entre 6 y 15 dígitos"), "Must have between 6 and 15 digits");
+        assert_eq!(
+            Idioma::En.traducir("No existe el campo 'x'. Campos disponibles: id, total"),
+            "There is no field 'x'. Available fields: id, total"
+        );
+        assert_eq!(Idioma::En.traducir("Json deserialize error"), "Json deserialize error");
+        assert_eq!(Idioma::Es.traducir("Entrada no encontrada"), "Entrada no encontrada");
+    }
+
+    #[actix_web::test]
+    async fn traduce_errores_y_mensajes_de_la_respuesta() {
+        let app = init_service(
+            App::new()
+                .route("/error", web::get().to(|| async { Err::<HttpResponse, _>(ApiError::from(ErrorEntrada::SinCapacidad(2))) }))
+                .route("/ok", web::get().to(|| async { ApiResponse::ok("Entrada eliminada exitosamente") }))
+                .wrap(from_fn(elegir_idioma)),
+        )
+        .await;
+
+        let req = TestRequest::get().uri("/error").insert_header((ACCEPT_LANGUAGE, "en")).to_request();
+        let res = call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::CONFLICT);
+        assert_eq!(res.headers().get(CONTENT_LANGUAGE).unwrap(), "en");
+        let cuerpo: Value = read_body_json(res).await;
+        assert_eq!(cuerpo["error"]["message"], "Only 2 seats are left for the showing");
+        assert_eq!(cuerpo["error"]["disponibles"], 2);
+
+        let res = call_service(&app, TestRequest::get().uri("/error").to_request()).await;
+        assert_eq!(res.headers().get(CONTENT_LANGUAGE).unwrap(), "es");
+        let cuerpo: Value = read_body_json(res).await;
+        assert_eq!(cuerpo["error"]["message"], "Sólo quedan 2 asientos disponibles para la función");
+
+        let req = TestRequest::get().uri("/ok").insert_header((ACCEPT_LANGUAGE, "en-GB")).to_request();
+        let cuerpo: Value = read_body_json(call_service(&app, req).await).await;
+        assert_eq!(cuerpo["data"], "Ticket deleted successfully");
+    }
+}
@@ -0,0 +1,221 @@
+//! Claves de idempotencia de `POST /entradas` (cabecera `Idempotency-Key`).
+//!
+//! La primera petición con una clave la reserva y, si sale bien, guarda su respuesta; las
+//! repeticiones con la misma clave y el mismo cuerpo reciben esa respuesta, marcada con
+//! `Idempotent-Replayed: true`, sin volver a crear la entrada. Una petición que falla
+//! libera la clave para que se pueda reintentar. Las claves caducan a los
+//! [`Config::idempotencia_ttl`](crate::config::Config::idempotencia_ttl).
+//!
+//! Las claves se guardan en la tabla `claves_idempotencia`, o en Redis si está configurado
+//! (ver [`crate::compartido`]), donde caducan solas.
+
+use std::time::Duration;
+
+use actix_web::http::StatusCode;
+use actix_web::HttpRequest;
+use mysql_async::{prelude::*, Pool};
+use serde::{Deserialize, Serialize};
+
+use crate::auth::hash_secreto;
+use crate::compartido::Redis;
+use crate::db;
+use crate::db::errores::{self, FalloMysql};
+use crate::error::ApiError;
+
+/// Cabecera con la clave que envía el cliente.
+pub const CABECERA_IDEMPOTENCIA: &str = "Idempotency-Key";
+
+/// Cabecera que marca las respuestas repetidas.
+pub const CABECERA_REPETIDA: &str = "idempotent-replayed";
+
+/// Longitud máxima de una clave, la de su columna.
+const LONGITUD_MAXIMA_CLAVE: usize = 255;
+
+/// Mensaje de error de las operaciones sobre claves.
+const ERROR_COMPROBAR: &str = "Error al comprobar la clave de idempotencia";
+
+/// Resultado de reservar una clave.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reserva {
+    /// La clave no se había usado: la petición debe procesarse.
+    Nueva,
+    /// La clave ya tiene respuesta: estado y `data` que hay que repetir.
+    Repetida(StatusCode, serde_json::Value),
+}
+
+/// Clave de la petición, si la trae. Debe ser texto visible de hasta 255 caracteres.
+pub fn clave(req: &HttpRequest) -> Result<Option<String>, ApiError> {
+    let Some(valor) = req.headers().get(CABECERA_IDEMPOTENCIA) else {
+        return Ok(None);
+    };
+    match valor.to_str().map(str::trim) {
+        Ok(clave) if !clave.is_empty() && clave.len() <= LONGITUD_MAXIMA_CLAVE => Ok(Some(clave.to_string())),
+        _ => Err(ApiError::Validacion(format!(
+            "{} debe ser texto visible de entre 1 y {} caracteres",
+            CABECERA_IDEMPOTENCIA, LONGITUD_MAXIMA_CLAVE
+        ))),
+    }
+}
+
+/// Huella del cuerpo de la petición, para detectar una clave reutilizada con otros datos.
+pub fn huella(cuerpo: &impl Serialize) -> String {
+    hash_secreto(&serde_json::to_string(cuerpo).unwrap_or_default())
+}
+
+/// Una clave ya reservada: la huella de su cuerpo y, si terminó, su respuesta.
+#[derive(Debug, Serialize, Deserialize)]
+struct ClaveGuardada {
+    huella: String,
+    estado: Option<u16>,
+    cuerpo: Option<serde_json::Value>,
+}
+
+/// Respuesta para una petición con `huella` que encontró la clave ya reservada. Una clave
+/// reservada por una petición que aún no terminó, o usada con otro cuerpo, es un conflicto.
+fn repetir(guardada: Option<ClaveGuardada>, huella: &str) -> Result<Reserva, ApiError> {
+    match guardada {
+        Some(guardada) if guardada.huella != huella => Err(ApiError::Conflicto(
+            "La clave de idempotencia ya se usó con otros datos".to_string(),
+        )),
+        Some(ClaveGuardada { estado: Some(estado), cuerpo: Some(cuerpo), .. }) => {
+            Ok(Reserva::Repetida(StatusCode::from_u16(estado).unwrap_or(StatusCode::OK), cuerpo))
+        }
+        _ => Err(ApiError::Conflicto(
+            "Ya hay una petición en curso con esa clave de idempotencia".to_string(),
+        )),
+    }
+}
+
+fn clave_redis(clave: &str) -> String {
+    format!("idempotencia:{}", clave)
+}
+
+/// Registra en el log un error de Redis y lo convierte en uno de la API.
+fn error_redis(e: String) -> ApiError {
+    log::error!("{}: {}", ERROR_COMPROBAR, e);
+    ApiError::BaseDatos(ERROR_COMPROBAR.to_string())
+}
+
+/// Reserva la clave o, si ya estaba, devuelve su respuesta guardada. En MySQL, antes borra
+/// las claves caducadas.
+pub async fn reservar(
+    pool: &Pool,
+    redis: Option<&Redis>,
+    clave: &str,
+    huella: &str,
+    ttl: Duration,
+) -> Result<Reserva, ApiError> {
+    if let Some(redis) = redis {
+        let nueva = ClaveGuardada { huella: huella.to_string(), estado: None, cuerpo: None };
+        if redis.reservar(&clave_redis(clave), &nueva, ttl).await.map_err(error_redis)? {
+            return Ok(Reserva::Nueva);
+        }
+        return repetir(redis.leer(&clave_redis(clave)).await.map_err(error_redis)?, huella);
+    }
+
+    let mut conn = db::conectar(pool).await.map_err(ApiError::conexion)?;
+    conn.exec_drop(
+        "DELETE FROM claves_idempotencia WHERE creada_en < NOW() - INTERVAL :ttl SECOND",
+        params! { "ttl" => ttl.as_secs() },
+    )
+    .await
+    .map_err(ApiError::base_datos(ERROR_COMPROBAR))?;
+    let resultado = conn
+        .exec_drop(
+            "INSERT INTO claves_idempotencia (clave, huella) VALUES (:clave, :huella)",
+            params! { "clave" => clave, "huella" => huella },
+        )
+        .await;
+    match resultado {
+        Ok(()) => return Ok(Reserva::Nueva),
+        Err(e) if matches!(errores::clasificar(&e), FalloMysql::Duplicado(_)) => {}
+        Err(e) => return Err(ApiError::base_datos(ERROR_COMPROBAR)(e)),
+    }
+
+    let guardada: Option<(String, Option<u16>, Option<String>)> = conn
+        .exec_first(
+            "SELECT huella, estado, cuerpo FROM claves_idempotencia WHERE clave = :clave",
+            params! { "clave" => clave },
+        )
+        .await
+        .map_err(ApiError::base_datos(ERROR_COMPROBAR))?;
+    let guardada = guardada.map(|(huella, estado, cuerpo)| ClaveGuardada {
+        huella,
+        estado,
+        cuerpo: cuerpo.map(|cuerpo| serde_json::from_str(&cuerpo).unwrap_or_default()),
+    });
+    repetir(guardada, huella)
+}
+
+/// Guarda la respuesta de una clave reservada. Si falla sólo se registra en el log: la
+/// operación ya se hizo y su respuesta no debe convertirse en un error.
+pub async fn guardar(
+    pool: &Pool,
+    redis: Option<&Redis>,
+    clave: &str,
+    huella: &str,
+    estado: StatusCode,
+    data: &impl Serialize,
+) {
+    if let Some(redis) = redis {
+        let guardada = ClaveGuardada {
+            huella: huella.to_string(),
+            estado: Some(estado.as_u16()),
+            cuerpo: Some(serde_json::to_value(data).unwrap_or_default()),
+        };
+        if let Err(e) = redis.actualizar(&clave_redis(clave), &guardada).await {
+            log::error!("Error al guardar la respuesta de la clave de idempotencia: {}", e);
+        }
+        return;
+    }
+    let resultado = async {
+        let mut conn = db::conectar(pool).await?;
+        conn.exec_drop(
+            "UPDATE claves_idempotencia SET estado = :estado, cuerpo = :cuerpo WHERE clave = :clave",
+            params! {
+                "clave" => clave,
+                "estado" => estado.as_u16(),
+                "cuerpo" => serde_json::to_string(data).unwrap_or_default(),
+            },
+        )
+        .await
+    };
+    if let Err(e) = resultado.await {
+        log::error!("Error al guardar la respuesta de la clave de idempotencia: {:?}", e);
+    }
+}
+
+/// Libera una clave reservada cuya petición falló, para que se pueda reintentar.
+pub async fn liberar(pool: &Pool, redis: Option<&Redis>, clave: &str) {
+    if let Some(redis) = redis {
+        if let Err(e) = redis.borrar(&clave_redis(clave)).await {
+            log::error!("Error al liberar la clave de idempotencia: {}", e);
+        }
+        return;
+    }
+    let resultado = async {
+        let mut conn = db::conectar(pool).await?;
+        conn.exec_drop("DELETE FROM claves_idempotencia WHERE clave = :clave", params! { "clave" => clave })
+            .await
+    };
+    if let Err(e) = resultado.await {
+        log::error!("Error al liberar la clave de idempotencia: {:?}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    #[test]
+    fn la_clave_es_opcional_pero_debe_ser_valida() {
+        assert_eq!(clave(&TestRequest::default().to_http_request()), Ok(None));
+        let req = TestRequest::default().insert_header((CABECERA_IDEMPOTENCIA, " venta-42 ")).to_http_request();
+        assert_eq!(clave(&req), Ok(Some("venta-42".to_string())));
+        let req = TestRequest::default().insert_header((CABECERA_IDEMPOTENCIA, "x".repeat(256))).to_http_request();
+        assert!(clave(&req).is_err());
+        let req = TestRequest::default().insert_header((CABECERA_IDEMPOTENCIA, "")).to_http_request();
+        assert!(clave(&req).is_err());
+    }
+}
@@ -0,0 +1,140 @@
+//! Carga masiva de entradas desde un CSV, para `POST /entradas/import`.
+//!
+//! La primera línea nombra las columnas, en cualquier orden: `cliente_id`, `funcion_id` y
+//! `cantidad_entradas` son obligatorias y `asientos`, con los números separados por
+//! espacios, es opcional. Cada fila se lee por separado, así que una fila mal escrita se
+//! informa sin descartar las demás.
+
+use actix_multipart::{Multipart, MultipartError};
+use futures_util::StreamExt;
+
+use crate::error::ApiError;
+use crate::models::CrearEntrada;
+use crate::validacion::ErrorCampo;
+
+/// Tamaño máximo del archivo.
+pub const BYTES_POR_IMPORTACION: usize = 5 * 1024 * 1024;
+
+/// Máximo de filas de datos de un archivo.
+pub const ENTRADAS_POR_IMPORTACION: usize = 10_000;
+
+const COLUMNAS_OBLIGATORIAS: [&str; 3] = ["cliente_id", "funcion_id", "cantidad_entradas"];
+
+/// Una fila del CSV: su número de línea en el archivo, contando el encabezado como la 1,
+/// y la entrada que describe o los campos que no se pudieron leer.
+pub type FilaImportacion = (u64, Result<CrearEntrada, Vec<ErrorCampo>>);
+
+fn error_campo(campo: &'static str, mensaje: &str) -> ErrorCampo {
+    ErrorCampo { campo: campo.into(), mensaje: mensaje.to_string() }
+}
+
+/// Lee el campo `archivo` del formulario multipart, de hasta [`BYTES_POR_IMPORTACION`].
+pub async fn leer_archivo(formulario: &mut Multipart) -> Result<Vec<u8>, ApiError> {
+    let invalido = |e: MultipartError| ApiError::Validacion(format!("El formulario no es válido: {}", e));
+    while let Some(mut campo) = formulario.next().await.transpose().map_err(invalido)? {
+        if campo.name() != Some("archivo") {
+            continue;
+        }
+        let mut contenido = Vec::new();
+        while let Some(trozo) = campo.next().await.transpose().map_err(invalido)? {
+            if contenido.len() + trozo.len() > BYTES_POR_IMPORTACION {
+                return Err(ApiError::CuerpoDemasiadoGrande(format!(
+                    "El archivo supera el máximo de {} bytes",
+                    BYTES_POR_IMPORTACION
+                )));
+            }
+            contenido.extend_from_slice(&trozo);
+        }
+        return Ok(contenido);
+    }
+    Err(ApiError::Validacion("Falta el campo 'archivo' con el CSV".to_string()))
+}
+
+/// Lee las filas del CSV. Falla sólo si el archivo entero no se puede usar: sin encabezado,
+/// sin alguna columna obligatoria, sin filas o con demasiadas.
+pub fn leer_csv(contenido: &[u8]) -> Result<Vec<FilaImportacion>, String> {
+    let mut lector = csv::ReaderBuilder::new().flexible(true).trim(csv::Trim::All).from_reader(contenido);
+    let encabezado = lector.headers().map_err(|e| format!("No se pudo leer el encabezado del CSV: {}", e))?.clone();
+    let posicion = |columna: &str| encabezado.iter().position(|nombre| nombre == columna);
+    let [cliente_id, funcion_id, cantidad_entradas] = COLUMNAS_OBLIGATORIAS.map(posicion);
+    let (Some(cliente_id), Some(funcion_id), Some(cantidad_entradas)) = (cliente_id, funcion_id, cantidad_entradas) else {
+        return Err(format!("El CSV debe tener las columnas {}", COLUMNAS_OBLIGATORIAS.join(", ")));
+    };
+    let asientos = posicion("asientos");
+
+    let mut filas = Vec::new();
+    for registro in lector.records() {
+        if filas.len() == ENTRADAS_POR_IMPORTACION {
+            return Err(format!("El CSV no puede tener más de {} filas", ENTRADAS_POR_IMPORTACION));
+        }
+        let registro = match registro {
+            Ok(registro) => registro,
+            Err(e) => {
+                let linea = e.position().map_or(0, |posicion| posicion.line());
+                filas.push((linea, Err(vec![error_campo("fila", "La fila no es CSV válido")])));
+                continue;
+            }
+        };
+        let linea = registro.position().map_or(0, |posicion| posicion.line());
+        let mut errores = Vec::new();
+        let mut numero = |indice: usize, campo: &'static str| match registro.get(indice).unwrap_or("").parse::<u32>() {
+            Ok(valor) => valor,
+            Err(_) => {
+                errores.push(error_campo(campo, "Debe ser un número entero positivo"));
+                0
+            }
+        };
+        let entrada = CrearEntrada {
+            cliente_id: numero(cliente_id, "cliente_id"),
+            funcion_id: numero(funcion_id, "funcion_id"),
+            cantidad_entradas: numero(cantidad_entradas, "cantidad_entradas"),
+            asientos: Vec::new(),
+        };
+        let asientos = asientos
+            .and_then(|indice| registro.get(indice))
+            .unwrap_or("")
+            .split_whitespace()
+            .map(str::parse)
+            .collect::<Result<Vec<u32>, _>>();
+        match asientos {
+            Ok(asientos) if errores.is_empty() => filas.push((linea, Ok(CrearEntrada { asientos, ..entrada }))),
+            Ok(_) => filas.push((linea, Err(errores))),
+            Err(_) => {
+                errores.push(error_campo("asientos", "Deben ser números de asiento separados por espacios"));
+                filas.push((linea, Err(errores)));
+            }
+        }
+    }
+    if filas.is_empty() {
+        return Err("El CSV no tiene filas de entradas".to_string());
+    }
+    Ok(filas)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lee_cada_fila_por_separado() {
+        let csv = "funcion_id,cliente_id,cantidad_entradas,asientos\n\
+                   1,7,2,\"10 11\"\n\
+                   1,x,2,\n\
+                   2,8,1\n";
+        let filas = leer_csv(csv.as_bytes()).unwrap();
+        assert_eq!(filas.len(), 3);
+        let (linea, entrada) = &filas[0];
+        let entrada = entrada.as_ref().unwrap();
+        assert_eq!((*linea, entrada.cliente_id, entrada.funcion_id, &entrada.asientos[..]), (2, 7, 1, &[10, 11][..]));
+        assert_eq!(filas[1].0, 3);
+        assert_eq!(filas[1].1.as_ref().unwrap_err()[0].campo, "cliente_id");
+        assert!(filas[2].1.as_ref().unwrap().asientos.is_empty());
+    }
+
+    #[test]
+    fn rechaza_el_archivo_sin_columnas_obligatorias_o_sin_filas() {
+        assert!(leer_csv(b"cliente_id,cantidad_entradas\n1,2\n").is_err());
+        assert!(leer_csv(b"cliente_id,funcion_id,cantidad_entradas\n").is_err());
+        assert!(leer_csv(b"").is_err());
+    }
+}
@@ -0,0 +1,124 @@
+//! Extractor de cuerpos JSON con errores por campo.
+//!
+//! Sustituye a `web::Json`: el cuerpo se lee con la misma `JsonConfig` (límite de tamaño y
+//! errores de sintaxis siguen igual), pero si el JSON es válido y no encaja con el tipo
+//! esperado se responde 422 con el campo que falló y el motivo, como en las validaciones.
+
+use std::borrow::Cow;
+use std::ops::Deref;
+
+use actix_web::dev::Payload;
+use actix_web::{web, FromRequest, HttpRequest};
+use futures_util::future::LocalBoxFuture;
+use serde::de::DeserializeOwned;
+
+use crate::error::ApiError;
+use crate::validacion::ErrorCampo;
+
+/// Cuerpo JSON deserializado como `T`.
+#[derive(Debug)]
+pub struct Json<T>(pub T);
+
+impl<T> Json<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for Json<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: DeserializeOwned + 'static> FromRequest for Json<T> {
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let valor = web::Json::<serde_json::Value>::from_request(req, payload);
+        Box::pin(async move {
+            let valor = valor.await?.into_inner();
+            serde_path_to_error::deserialize(valor)
+                .map(Json)
+                .map_err(|e| ApiError::CamposInvalidos(vec![error_campo(e)]).into())
+        })
+    }
+}
+
+/// Traduce el error de serde, que trae la ruta del campo (`lineas[2].cantidad`) y un
+/// mensaje en inglés. Los campos que faltan se informan en el objeto que los contiene,
+/// así que su nombre se saca del mensaje.
+fn error_campo(error: serde_path_to_error::Error<serde_json::Error>) -> ErrorCampo {
+    let ruta = error.path().to_string();
+    let mensaje = error.inner().to_string();
+    let (nombre, mensaje) = if let Some(resto) = mensaje.strip_prefix("missing field `") {
+        (resto.split('`').next(), "Campo obligatorio".to_string())
+    } else if let Some(resto) = mensaje.strip_prefix("unknown field `") {
+        (resto.split('`').next(), "Campo desconocido".to_string())
+    } else if let Some(resto) = mensaje.strip_prefix("invalid type: ") {
+        (None, format!("Tipo inválido: {}", resto.replacen(", expected ", ", se esperaba ", 1)))
+    } else if let Some(resto) = mensaje.strip_prefix("invalid value: ") {
+        (None, format!("Valor inválido: {}", resto.replacen(", expected ", ", se esperaba ", 1)))
+    } else if let Some(resto) = mensaje.strip_prefix("unknown variant ") {
+        (None, format!("Valor no admitido {}", resto.replacen(", expected ", ", se esperaba ", 1)))
+    } else {
+        (None, mensaje)
+    };
+    let campo: Cow<'static, str> = match (ruta.as_str(), nombre) {
+        (".", Some(nombre)) => nombre.to_string().into(),
+        (".", None) => "cuerpo".into(),
+        (ruta, Some(nombre)) => format!("{}.{}", ruta, nombre).into(),
+        (ruta, None) => ruta.to_string().into(),
+    };
+    ErrorCampo { campo, mensaje }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CrearEntrada, CrearFuncion};
+
+    fn error_de(cuerpo: serde_json::Value) -> ErrorCampo {
+        error_campo(serde_path_to_error::deserialize::<_, CrearEntrada>(cuerpo).unwrap_err())
+    }
+
+    #[test]
+    fn indica_el_campo_y_el_motivo() {
+        let mut cuerpo = serde_json::json!({
+            "cliente_id": 1,
+            "funcion_id": 1,
+            "cantidad_entradas": "dos",
+        });
+        let error = error_de(cuerpo.clone());
+        assert_eq!(error.campo, "cantidad_entradas");
+        assert_eq!(error.mensaje, "Tipo inválido: string \"dos\", se esperaba u32");
+
+        cuerpo["cantidad_entradas"] = 2.into();
+        cuerpo.as_object_mut().unwrap().remove("funcion_id");
+        let error = error_de(cuerpo);
+        assert_eq!((error.campo.as_ref(), error.mensaje.as_str()), ("funcion_id", "Campo obligatorio"));
+
+        assert_eq!(error_de(serde_json::json!("texto")).campo, "cuerpo");
+    }
+
+    #[test]
+    fn los_horarios_deben_ser_rfc_3339() {
+        let mut funcion = serde_json::json!({
+            "titulo": "Dune",
+            "sala_id": 1,
+            "horario": "7pm",
+            "precio": 6.5,
+        });
+        let error = serde_path_to_error::deserialize::<_, CrearFuncion>(funcion.clone()).unwrap_err();
+        let error = error_campo(error);
+        assert_eq!(error.campo, "horario");
+        assert!(error.mensaje.contains("RFC 3339"));
+
+        funcion["horario"] = "2024-03-01T19:00:00-05:00".into();
+        let creada: CrearFuncion = serde_json::from_value(funcion).unwrap();
+        assert_eq!(creada.horario.naive_utc().to_string(), "2024-03-02 00:00:00");
+    }
+}
@@ -0,0 +1,260 @@
+//! API CRUD de entradas de cine.
+//!
+//! Expone la fábrica [`create_app`] para montar la API en un `HttpServer` propio, y
+//! [`Server::builder`] para arrancarla con rutas y middlewares adicionales.
+
+pub mod accesos;
+pub mod actualizacion;
+pub mod agregado;
+pub mod archivo;
+pub mod auth;
+pub mod autocompletado;
+pub mod busqueda_aproximada;
+pub mod cache;
+pub mod cambios;
+pub mod campos;
+pub mod cines;
+pub mod claves_api;
+pub mod clientes;
+pub mod coalescencia;
+pub mod correos;
+pub mod compartido;
+pub mod config;
+pub mod cors;
+pub mod db;
+#[cfg(feature = "debug-explain")]
+pub mod depuracion;
+pub mod disponibilidad;
+pub mod dispositivos;
+pub mod enlaces;
+pub mod error;
+pub mod escucha;
+pub mod exportacion;
+pub mod funciones;
+pub mod graphql;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod handlers;
+pub mod i18n;
+pub mod idempotencia;
+pub mod importacion;
+pub mod json;
+pub mod limite;
+pub mod listado;
+pub mod metricas;
+pub mod models;
+pub mod panel;
+pub mod posters;
+pub mod purga;
+pub mod qr;
+pub mod recarga;
+pub mod reloj;
+pub mod reportes;
+pub mod reservas;
+pub mod respaldo;
+pub mod respuesta;
+pub mod routes;
+pub mod salas;
+pub mod salud;
+pub mod semilla;
+pub mod server;
+pub mod servicio;
+pub mod simulacion;
+pub mod slo;
+pub mod tareas;
+pub mod ticket;
+pub mod tiempo_maximo;
+pub mod tls;
+pub mod validacion;
+pub mod webhooks;
+
+pub use server::{Server, ServerBuilder, ServidorEnMarcha};
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceFactory, ServiceRequest, ServiceResponse};
+use actix_web::middleware::from_fn;
+use actix_web::{web, App, Error};
+use mysql_async::Pool;
+use std::sync::Arc;
+
+use crate::accesos::RegistroAccesos;
+use crate::autocompletado::CacheAutocompletado;
+use crate::cache::{CacheLecturas, RepositorioCache};
+use crate::cambios::CanalCambios;
+use crate::busqueda_aproximada::IndiceClientes;
+use crate::compartido::Redis;
+use crate::config::Config;
+use crate::disponibilidad::AsientosEnVivo;
+use crate::limite::LimitesPeticiones;
+use crate::db::medicion::RepositorioMedido;
+use crate::db::replica::{self, Replica};
+use crate::db::repository::{EntradaRepository, RepositorioMysql};
+use crate::db::obtener_pool_replica;
+use crate::metricas::Metricas;
+use crate::posters::Posters;
+use crate::recarga::ConfigVigente;
+use crate::reloj::{Reloj, RelojSistema};
+use crate::servicio::ServicioEntradas;
+use crate::slo::SeguimientoSlo;
+use crate::tareas::Planificador;
+
+/// Estado que comparten todos los workers del servidor. Se crea una sola vez y cada
+/// worker recibe un clon, que sólo copia referencias.
+#[derive(Clone)]
+pub struct Estado {
+    /// Configuración del arranque.
+    pub config: Config,
+    /// Configuración con los cambios recargados (ver [`recarga`]).
+    pub vigente: Arc<ConfigVigente>,
+    pub pool: Pool,
+    pub slo: Arc<SeguimientoSlo>,
+    pub autocompletado: Arc<CacheAutocompletado>,
+    pub indice_clientes: Arc<IndiceClientes>,
+    pub cache: Arc<CacheLecturas>,
+    /// Réplica de lectura de la base de datos, si hay `DATABASE_URL_RO`.
+    pub replica: Option<Arc<Replica>>,
+    /// Almacén compartido con las demás réplicas, si hay `REDIS_URL`.
+    pub redis: Option<Arc<Redis>>,
+    pub entradas: Arc<ServicioEntradas>,
+    /// Cambios de las entradas para `GET /entradas/stream`.
+    pub cambios: Arc<CanalCambios>,
+    /// Ocupación de las funciones con clientes en `/ws/funciones/{id}`.
+    pub asientos: Arc<AsientosEnVivo>,
+    pub metricas: Arc<Metricas>,
+    pub limites: Arc<LimitesPeticiones>,
+    pub planificador: Arc<Planificador>,
+    pub posters: Arc<Posters>,
+    /// Peticiones a la espera de guardarse en el registro de accesos.
+    pub accesos: Arc<RegistroAccesos>,
+    /// Hora con la que se fechan las entradas y se deciden los vencimientos y el archivo.
+    pub reloj: Arc<dyn Reloj>,
+}
+
+impl Estado {
+    /// Una `REDIS_URL` o una `DATABASE_URL_RO` inválidas se registran en el log y se
+    /// ignoran; [`Server::builder`] las rechaza antes de llegar aquí.
+    pub fn new(config: Config, pool: Pool) -> Self {
+        Estado::con_reloj(config, pool, Arc::new(RelojSistema))
+    }
+
+    /// Como [`Estado::new`], con la hora de `reloj`; las pruebas lo usan para adelantarla.
+    pub fn con_reloj(config: Config, pool: Pool, reloj: Arc<dyn Reloj>) -> Self {
+        let slo = Arc::new(SeguimientoSlo::new(config.slo.clone()));
+        let redis = config.redis_url.as_deref().and_then(|url| match Redis::new(url) {
+            Ok(redis) => Some(Arc::new(redis)),
+            Err(e) => {
+                log::warn!("{}; se usa la memoria del proceso", e);
+                None
+            }
+        });
+        let replica = match obtener_pool_replica(&config) {
+            Ok(pool) => pool.map(|pool| Arc::new(Replica::new(pool))),
+            Err(e) => {
+                log::warn!("DATABASE_URL_RO inválida: {}; se lee de la primaria", e);
+                None
+            }
+        };
+        let limites = Arc::new(LimitesPeticiones::new(config.limite_ip, config.limite_clave_api, redis.clone()));
+        let cache = Arc::new(CacheLecturas::new(config.cache_ttl, redis.clone()));
+        let posters = Arc::new(Posters::new(&config.posters));
+        let accesos = Arc::new(RegistroAccesos::new(config.accesos_retencion_dias > 0));
+        let mysql = Arc::new(
+            RepositorioMysql::new(pool.clone(), config.reintentos)
+                .con_limite_por_cedula(config.limite_por_cedula)
+                .con_reloj(reloj.clone()),
+        );
+        let entradas = Arc::new(servicio_entradas(&config, mysql, &cache));
+        Estado {
+            vigente: Arc::new(ConfigVigente::from_pointee(config.clone())),
+            config,
+            slo,
+            autocompletado: Arc::new(CacheAutocompletado::default()),
+            indice_clientes: Arc::new(IndiceClientes::default()),
+            metricas: Arc::new(Metricas::default()),
+            limites,
+            planificador: Arc::new(Planificador::default()),
+            posters,
+            accesos,
+            reloj,
+            entradas,
+            cambios: Arc::new(CanalCambios::default()),
+            asientos: Arc::new(AsientosEnVivo::default()),
+            cache,
+            replica,
+            redis,
+            pool,
+        }
+    }
+
+    /// Cambia el repositorio de entradas, con la misma medición y caché que el de MySQL.
+    pub fn con_repositorio(mut self, repositorio: Arc<dyn EntradaRepository>) -> Self {
+        self.entradas = Arc::new(servicio_entradas(&self.config, repositorio, &self.cache));
+        self
+    }
+}
+
+/// Servicio de entradas sobre `repositorio`, medido y con la caché de lecturas delante.
+fn servicio_entradas(
+    config: &Config,
+    repositorio: Arc<dyn EntradaRepository>,
+    cache: &Arc<CacheLecturas>,
+) -> ServicioEntradas {
+    let medido = RepositorioMedido::new(repositorio, config.consulta_lenta);
+    let repositorio = RepositorioCache::new(Arc::new(medido), cache.clone());
+    ServicioEntradas::new(Arc::new(repositorio), config.reglas_validacion(), config.zona_horaria)
+}
+
+/// Construye la aplicación con su estado compartido y todas las rutas registradas.
+pub fn create_app(
+    estado: Estado,
+) -> App<
+    impl ServiceFactory<
+        ServiceRequest,
+        Config = (),
+        Response = ServiceResponse<impl MessageBody>,
+        Error = Error,
+        InitError = (),
+    >,
+> {
+    let cors = estado.config.cors.middleware();
+    let limite_json = estado.config.limite_json;
+    let graphql = graphql::esquema(estado.entradas.clone(), estado.cambios.clone());
+    App::new()
+        .app_data(
+            web::JsonConfig::default()
+                .limit(limite_json)
+                .error_handler(|e, _| error::error_json(e)),
+        )
+        .app_data(web::QueryConfig::default().error_handler(|e, _| error::error_extractor(e)))
+        .app_data(web::PathConfig::default().error_handler(|e, _| error::error_extractor(e)))
+        .app_data(web::Data::new(estado.config))
+        .app_data(web::Data::from(estado.vigente))
+        .app_data(web::Data::new(estado.pool))
+        .app_data(web::Data::new(estado.replica))
+        .app_data(web::Data::from(estado.slo))
+        .app_data(web::Data::from(estado.autocompletado))
+        .app_data(web::Data::from(estado.indice_clientes))
+        .app_data(web::Data::from(estado.cache))
+        .app_data(web::Data::new(estado.redis))
+        .app_data(web::Data::from(estado.entradas))
+        .app_data(web::Data::from(estado.cambios))
+        .app_data(web::Data::from(estado.asientos))
+        .app_data(web::Data::new(graphql))
+        .app_data(web::Data::from(estado.metricas))
+        .app_data(web::Data::from(estado.limites))
+        .app_data(web::Data::from(estado.planificador))
+        .app_data(web::Data::from(estado.posters))
+        .app_data(web::Data::from(estado.accesos))
+        .app_data(web::Data::from(estado.reloj))
+        .configure(routes::configurar_rutas)
+        .wrap(from_fn(simulacion::rechazar_no_simulables))
+        .wrap(from_fn(replica::enrutar_lecturas))
+        .wrap(from_fn(tiempo_maximo::limitar_duracion))
+        .wrap(from_fn(limite::limitar_peticiones))
+        .wrap(cors)
+        .wrap(from_fn(slo::medir_slo))
+        .wrap(from_fn(metricas::medir_peticiones))
+        .wrap(from_fn(accesos::registrar_acceso))
+        .wrap(from_fn(i18n::elegir_idioma))
+        .wrap(from_fn(respuesta::identificar_peticion))
+}
@@ -0,0 +1,215 @@
+//! Límite de peticiones por cliente, con una cubeta de tokens por IP o por clave de API.
+//!
+//! Las peticiones con una `X-Api-Key` vigente cuentan contra la clave, para que los kioscos
+//! detrás de una misma IP no se limiten entre sí; el resto, también las que traen una clave
+//! que no existe o está revocada, cuenta contra la IP de origen. Al vaciarse la cubeta se
+//! responde 429 con `Retry-After`.
+//!
+//! Con Redis configurado las cubetas se guardan allí, para que el límite sea el mismo
+//! aunque las peticiones de un cliente se repartan entre varias réplicas.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{web, Error};
+use mysql_async::{prelude::*, Pool};
+
+use crate::auth::hash_secreto;
+use crate::claves_api::CABECERA_CLAVE_API;
+use crate::compartido::Redis;
+use crate::db;
+use crate::error::ApiError;
+
+/// Rutas que no se limitan, para no cortar las sondas ni la recolección de métricas.
+const RUTAS_EXENTAS: [&str; 3] = ["/health", "/ready", "/metrics"];
+
+/// Clientes a partir de los cuales se olvidan las cubetas que ya se han vuelto a llenar.
+const MAX_CLIENTES: usize = 10_000;
+
+/// Ritmo sostenido y ráfaga máxima de un tipo de cliente. Con `por_segundo` a 0 no se limita.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConfigLimite {
+    pub por_segundo: f64,
+    pub rafaga: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Cubeta {
+    tokens: f64,
+    actualizada: Instant,
+}
+
+/// Cubetas de un tipo de cliente, por identificador.
+pub struct Limitador {
+    config: Mutex<ConfigLimite>,
+    cubetas: Mutex<HashMap<String, Cubeta>>,
+}
+
+impl Limitador {
+    pub fn new(config: ConfigLimite) -> Self {
+        Limitador {
+            config: Mutex::new(config),
+            cubetas: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn config(&self) -> ConfigLimite {
+        *self.config.lock().unwrap()
+    }
+
+    /// Cambia el ritmo y la ráfaga. Las cubetas siguen con sus tokens, hasta la nueva ráfaga.
+    pub fn configurar(&self, config: ConfigLimite) {
+        *self.config.lock().unwrap() = config;
+    }
+
+    fn rellenar(config: ConfigLimite, cubeta: &mut Cubeta, ahora: Instant) {
+        let transcurrido = ahora.saturating_duration_since(cubeta.actualizada).as_secs_f64();
+        cubeta.tokens = (cubeta.tokens + transcurrido * config.por_segundo).min(config.rafaga as f64);
+        cubeta.actualizada = ahora;
+    }
+
+    /// Gasta un token de `cliente`. Sin tokens, devuelve cuánto falta para el siguiente.
+    pub fn consumir(&self, cliente: &str, ahora: Instant) -> Result<(), Duration> {
+        let config = self.config();
+        if config.por_segundo <= 0.0 {
+            return Ok(());
+        }
+        let mut cubetas = self.cubetas.lock().unwrap();
+        if cubetas.len() >= MAX_CLIENTES && !cubetas.contains_key(cliente) {
+            cubetas.retain(|_, cubeta| {
+                Limitador::rellenar(config, cubeta, ahora);
+                cubeta.tokens < config.rafaga as f64
+            });
+        }
+        let cubeta = cubetas.entry(cliente.to_string()).or_insert(Cubeta {
+            tokens: config.rafaga as f64,
+            actualizada: ahora,
+        });
+        Limitador::rellenar(config, cubeta, ahora);
+        if cubeta.tokens >= 1.0 {
+            cubeta.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - cubeta.tokens) / config.por_segundo))
+        }
+    }
+}
+
+/// Limitadores de la aplicación. Se comparten entre los workers.
+pub struct LimitesPeticiones {
+    pub ip: Limitador,
+    pub clave_api: Limitador,
+    redis: Option<Arc<Redis>>,
+}
+
+impl LimitesPeticiones {
+    /// Límites con las cubetas en la memoria del proceso, o en `redis` si se indica.
+    pub fn new(ip: ConfigLimite, clave_api: ConfigLimite, redis: Option<Arc<Redis>>) -> Self {
+        LimitesPeticiones {
+            ip: Limitador::new(ip),
+            clave_api: Limitador::new(clave_api),
+            redis,
+        }
+    }
+
+    /// Cambia los límites en marcha (ver [`crate::recarga`]).
+    pub fn configurar(&self, ip: ConfigLimite, clave_api: ConfigLimite) {
+        self.ip.configurar(ip);
+        self.clave_api.configurar(clave_api);
+    }
+
+    /// Gasta un token de `cliente` en el limitador `tipo`. Si Redis falla, la petición
+    /// pasa: es preferible no limitar a cortar el servicio.
+    async fn consumir(&self, tipo: &str, limitador: &Limitador, cliente: &str) -> Result<(), Duration> {
+        let config = limitador.config();
+        match &self.redis {
+            Some(redis) if config.por_segundo > 0.0 => redis
+                .consumir_token(&format!("limite:{}:{}", tipo, cliente), config.por_segundo, config.rafaga)
+                .await
+                .unwrap_or_else(|e| {
+                    log::error!("Error al consultar el límite de peticiones en Redis: {}", e);
+                    Ok(())
+                }),
+            Some(_) => Ok(()),
+            None => limitador.consumir(cliente, Instant::now()),
+        }
+    }
+}
+
+/// Hash de la `X-Api-Key` de la petición, si es una clave vigente. Sin ella, o si no se
+/// puede consultar, la petición se limita por IP: una clave inventada no tiene cubeta propia.
+async fn clave_vigente(pool: &Pool, req: &ServiceRequest) -> Option<String> {
+    let hash = hash_secreto(req.headers().get(CABECERA_CLAVE_API)?.to_str().unwrap_or_default());
+    let mut conn = db::conectar(pool).await.ok()?;
+    let vigente: Option<u8> = conn
+        .exec_first("SELECT 1 FROM api_keys WHERE hash = :hash AND revocado_en IS NULL", params! { "hash" => &hash })
+        .await
+        .unwrap_or_else(|e| {
+            log::error!("Error al consultar la clave de API para el límite de peticiones: {}", e);
+            None
+        });
+    vigente.map(|_| hash)
+}
+
+/// Middleware que aplica los límites. El 429 se devuelve como respuesta, no como error,
+/// para que los middlewares externos (CORS) le añadan sus cabeceras.
+pub async fn limitar_peticiones<B: MessageBody>(
+    limites: web::Data<LimitesPeticiones>,
+    pool: web::Data<Pool>,
+    req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<EitherBody<B>>, Error> {
+    if RUTAS_EXENTAS.contains(&req.path()) {
+        return next.call(req).await.map(ServiceResponse::map_into_left_body);
+    }
+    let resultado = match clave_vigente(&pool, &req).await {
+        Some(clave) => limites.consumir("clave_api", &limites.clave_api, &clave).await,
+        None => match req.peer_addr() {
+            Some(direccion) => limites.consumir("ip", &limites.ip, &direccion.ip().to_string()).await,
+            None => Ok(()),
+        },
+    };
+    match resultado {
+        Ok(()) => next.call(req).await.map(ServiceResponse::map_into_left_body),
+        Err(espera) => {
+            let error = ApiError::DemasiadasPeticiones(espera.as_secs_f64().ceil().max(1.0) as u64);
+            Ok(req.error_response(error).map_into_right_body())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn la_cubeta_admite_la_rafaga_y_se_rellena_con_el_tiempo() {
+        let limitador = Limitador::new(ConfigLimite {
+            por_segundo: 2.0,
+            rafaga: 3,
+        });
+        let inicio = Instant::now();
+        for _ in 0..3 {
+            assert_eq!(limitador.consumir("kiosko", inicio), Ok(()));
+        }
+        assert_eq!(limitador.consumir("kiosko", inicio), Err(Duration::from_millis(500)));
+        assert_eq!(limitador.consumir("otro", inicio), Ok(()));
+
+        let despues = inicio + Duration::from_millis(500);
+        assert_eq!(limitador.consumir("kiosko", despues), Ok(()));
+        assert!(limitador.consumir("kiosko", despues).is_err());
+    }
+
+    #[test]
+    fn sin_ritmo_no_se_limita() {
+        let limitador = Limitador::new(ConfigLimite {
+            por_segundo: 0.0,
+            rafaga: 0,
+        });
+        assert!((0..100).all(|_| limitador.consumir("kiosko", Instant::now()).is_ok()));
+    }
+}
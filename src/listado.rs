@@ -0,0 +1,534 @@
+//! Parámetros del listado de entradas y construcción de su consulta filtrada, ordenada y paginada.
+//!
+//! El listado se pagina por número de página (`page` y `per_page`) o por cursor (`limit` y
+//! `after`): cada página por cursor empieza justo después de la última entrada de la
+//! anterior, así que no recorre las filas ya vistas y no salta ni repite entradas aunque
+//! se creen o borren otras entre una petición y la siguiente.
+
+use std::ops::RangeInclusive;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::DateTime;
+use mysql_async::Value;
+use serde::{Deserialize, Serialize};
+
+use crate::models::Entrada;
+
+/// Entradas por página si no se indica `per_page`.
+pub const POR_PAGINA_DEFECTO: u32 = 50;
+
+/// Máximo de entradas por página; valores mayores se recortan a este.
+pub const POR_PAGINA_MAXIMO: u32 = 100;
+
+/// Campos por los que se permite ordenar el listado.
+pub const CAMPOS_ORDENABLES: &[&str] = &["id", "nombre_cliente", "horario_funcion", "cantidad_entradas"];
+
+/// Columna de [`crate::db::TABLAS_ENTRADAS`] de cada campo por el que se filtra u ordena.
+/// Los del cliente y la función conservan los nombres que tenían cuando eran columnas de la entrada.
+const COLUMNAS: &[(&str, &str)] = &[
+    ("id", "e.id"),
+    ("cliente_id", "e.cliente_id"),
+    ("numero_cedula", "c.numero_cedula"),
+    ("nombre_cliente", "c.nombre"),
+    ("cantidad_entradas", "e.cantidad_entradas"),
+    ("funcion_id", "e.funcion_id"),
+    ("nombre_funcion", "f.titulo"),
+    ("horario_funcion", "f.horario"),
+    ("created_after", "e.created_at"),
+];
+
+fn columna(campo: &str) -> &'static str {
+    COLUMNAS.iter().find(|(c, _)| *c == campo).map(|(_, columna)| *columna).expect("Campo sin columna")
+}
+
+/// Comparación de cada filtro con su valor: igualdad salvo en los de fecha.
+fn comparacion(campo: &str) -> &'static str {
+    if campo == "created_after" { ">" } else { "=" }
+}
+
+/// Comprueba que el valor sea una fecha `AAAA-MM-DD`, opcionalmente seguida de la hora
+/// `HH:MM:SS` separada por un espacio o una `T`.
+fn es_fecha(valor: &str) -> bool {
+    let numero = |desde: usize, hasta: usize, rango: RangeInclusive<u32>| {
+        valor
+            .get(desde..hasta)
+            .filter(|parte| parte.bytes().all(|b| b.is_ascii_digit()))
+            .and_then(|parte| parte.parse().ok())
+            .is_some_and(|n| rango.contains(&n))
+    };
+    let fecha = numero(0, 4, 0..=9999)
+        && valor.get(4..5) == Some("-")
+        && numero(5, 7, 1..=12)
+        && valor.get(7..8) == Some("-")
+        && numero(8, 10, 1..=31);
+    match valor.len() {
+        10 => fecha,
+        19 => {
+            fecha
+                && matches!(valor.get(10..11), Some(" " | "T"))
+                && numero(11, 13, 0..=23)
+                && valor.get(13..14) == Some(":")
+                && numero(14, 16, 0..=59)
+                && valor.get(16..17) == Some(":")
+                && numero(17, 19, 0..=59)
+        }
+        _ => false,
+    }
+}
+
+/// Parámetros de consulta de `GET /entradas`. Los filtros comparan por igualdad y se combinan con AND.
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct ParametrosListado {
+    pub page: Option<u32>,
+    pub per_page: Option<u32>,
+    pub cliente_id: Option<u32>,
+    pub funcion_id: Option<u32>,
+    /// Título de la función.
+    pub nombre_funcion: Option<String>,
+    pub numero_cedula: Option<String>,
+    /// Horario de la función, en RFC 3339.
+    pub horario_funcion: Option<String>,
+    /// Sólo las entradas creadas después de esta fecha (ver [`es_fecha`]).
+    pub created_after: Option<String>,
+    pub sort: Option<String>,
+    /// `asc` (por defecto) o `desc`.
+    pub order: Option<String>,
+    /// Campos de cada entrada, separados por comas (ver [`crate::campos`]).
+    pub fields: Option<String>,
+    /// Entradas por página al paginar por cursor; incompatible con `page` y `per_page`.
+    pub limit: Option<u32>,
+    /// Cursor `next_cursor` de la página anterior.
+    pub after: Option<String>,
+}
+
+/// Posición de la última entrada de una página en el orden del listado. Viaja como texto
+/// opaco en `next_cursor` y `after`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Cursor {
+    /// Campo de ordenación y dirección con los que se generó.
+    pub orden: String,
+    pub descendente: bool,
+    /// Valor del campo de ordenación en la entrada, como se compara en la consulta.
+    pub valor: String,
+    pub id: u32,
+}
+
+impl Cursor {
+    /// Cursor que apunta a `entrada` en el orden de `consulta`.
+    pub fn tras(entrada: &Entrada, consulta: &ConsultaListado) -> Cursor {
+        let valor = match consulta.orden {
+            "nombre_cliente" => entrada.cliente.nombre.clone(),
+            "horario_funcion" => entrada.funcion.horario.naive_utc().to_string(),
+            "cantidad_entradas" => entrada.cantidad_entradas.to_string(),
+            _ => String::new(),
+        };
+        Cursor {
+            orden: consulta.orden.to_string(),
+            descendente: consulta.descendente,
+            valor,
+            id: entrada.id.unwrap_or_default(),
+        }
+    }
+
+    pub fn codificar(&self) -> String {
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).expect("Cursor siempre es serializable"))
+    }
+
+    pub fn decodificar(texto: &str) -> Option<Cursor> {
+        serde_json::from_slice(&URL_SAFE_NO_PAD.decode(texto).ok()?).ok()
+    }
+}
+
+/// Parámetros de consulta de `GET /entradas/buscar`.
+#[derive(Debug, Default, Deserialize)]
+pub struct ParametrosBusqueda {
+    /// Parte del nombre del cliente o del título de la función.
+    pub q: String,
+    pub page: Option<u32>,
+    pub per_page: Option<u32>,
+    pub fields: Option<String>,
+}
+
+/// Escapa los comodines de LIKE para que el texto del usuario se busque literalmente.
+pub(crate) fn escapar_like(texto: &str) -> String {
+    let mut escapado = String::with_capacity(texto.len());
+    for c in texto.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            escapado.push('\\');
+        }
+        escapado.push(c);
+    }
+    escapado
+}
+
+/// Listado ya validado.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConsultaListado {
+    pub pagina: u32,
+    pub por_pagina: u32,
+    /// Campo y valor de cada filtro. Los campos salen siempre de una lista fija.
+    pub filtros: Vec<(&'static str, String)>,
+    /// Campo de ordenación, tomado de [`CAMPOS_ORDENABLES`].
+    pub orden: &'static str,
+    pub descendente: bool,
+    /// Texto que debe aparecer en el nombre del cliente o en el título de la función.
+    pub busqueda: Option<String>,
+    /// Campos pedidos con `fields`; sin ellos, la entrada completa.
+    pub campos: Option<Vec<&'static str>>,
+    /// Si se pagina por cursor; entonces `pagina` es siempre 1.
+    pub por_cursor: bool,
+    /// Sólo las entradas posteriores a este cursor.
+    pub despues: Option<Cursor>,
+}
+
+/// Consultas de filas y de conteo del listado, con sus parámetros nombrados.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SentenciaListado {
+    pub consulta: String,
+    pub params: Vec<(String, Value)>,
+    pub conteo: String,
+    pub params_conteo: Vec<(String, Value)>,
+}
+
+impl ConsultaListado {
+    /// Valida los parámetros y aplica los valores por defecto.
+    pub fn desde_parametros(parametros: &ParametrosListado) -> Result<ConsultaListado, String> {
+        let pagina = parametros.page.unwrap_or(1);
+        if pagina == 0 {
+            return Err("El parámetro 'page' empieza en 1".to_string());
+        }
+        let por_pagina = parametros.per_page.unwrap_or(POR_PAGINA_DEFECTO);
+        if por_pagina == 0 {
+            return Err("El parámetro 'per_page' debe ser mayor que 0".to_string());
+        }
+        let orden = match parametros.sort.as_deref() {
+            None => "id",
+            Some(campo) => CAMPOS_ORDENABLES.iter().find(|c| **c == campo).copied().ok_or_else(|| {
+                format!(
+                    "No se permite ordenar por el campo '{}'. Campos disponibles: {}",
+                    campo,
+                    CAMPOS_ORDENABLES.join(", ")
+                )
+            })?,
+        };
+        let descendente = match parametros.order.as_deref().map(str::to_ascii_lowercase).as_deref() {
+            None | Some("asc") => false,
+            Some("desc") => true,
+            Some(_) => return Err("El parámetro 'order' debe ser 'asc' o 'desc'".to_string()),
+        };
+        if let Some(fecha) = &parametros.created_after
+            && !es_fecha(fecha)
+        {
+            return Err("El parámetro 'created_after' debe ser una fecha AAAA-MM-DD o AAAA-MM-DD HH:MM:SS".to_string());
+        }
+        // Los horarios se guardan en UTC, así que se comparan con el instante en UTC.
+        let horario_funcion = match &parametros.horario_funcion {
+            Some(horario) => Some(
+                DateTime::parse_from_rfc3339(horario)
+                    .map_err(|_| "El parámetro 'horario_funcion' debe ser una fecha y hora RFC 3339".to_string())?
+                    .naive_utc()
+                    .to_string(),
+            ),
+            None => None,
+        };
+        let campos = parametros.fields.as_deref().map(crate::campos::leer).transpose()?;
+        let por_cursor = parametros.limit.is_some() || parametros.after.is_some();
+        if por_cursor && (parametros.page.is_some() || parametros.per_page.is_some()) {
+            return Err("Los parámetros 'limit' y 'after' no se combinan con 'page' ni 'per_page'".to_string());
+        }
+        if parametros.limit == Some(0) {
+            return Err("El parámetro 'limit' debe ser mayor que 0".to_string());
+        }
+        let despues = match &parametros.after {
+            Some(texto) => {
+                let cursor = Cursor::decodificar(texto).ok_or("El parámetro 'after' no es un cursor válido")?;
+                if cursor.orden != orden || cursor.descendente != descendente {
+                    return Err("El cursor de 'after' es de un listado con otro orden".to_string());
+                }
+                Some(cursor)
+            }
+            None => None,
+        };
+        let por_pagina = parametros.limit.unwrap_or(por_pagina);
+        let filtros = [
+            ("cliente_id", &parametros.cliente_id.map(|id| id.to_string())),
+            ("funcion_id", &parametros.funcion_id.map(|id| id.to_string())),
+            ("nombre_funcion", &parametros.nombre_funcion),
+            ("numero_cedula", &parametros.numero_cedula),
+            ("horario_funcion", &horario_funcion),
+            ("created_after", &parametros.created_after),
+        ]
+        .into_iter()
+        .filter_map(|(campo, valor)| valor.clone().map(|valor| (campo, valor)))
+        .collect();
+        Ok(ConsultaListado {
+            pagina,
+            por_pagina: por_pagina.min(POR_PAGINA_MAXIMO),
+            filtros,
+            orden,
+            descendente,
+            busqueda: None,
+            campos,
+            por_cursor,
+            despues,
+        })
+    }
+
+    /// Página de la búsqueda de `q`, ordenada por `id`.
+    pub fn busqueda(parametros: &ParametrosBusqueda) -> Result<ConsultaListado, String> {
+        let texto = parametros.q.trim();
+        if texto.is_empty() {
+            return Err("El parámetro 'q' no puede estar vacío".to_string());
+        }
+        let parametros_listado = ParametrosListado {
+            page: parametros.page,
+            per_page: parametros.per_page,
+            fields: parametros.fields.clone(),
+            ..Default::default()
+        };
+        Ok(ConsultaListado {
+            busqueda: Some(texto.to_string()),
+            ..ConsultaListado::desde_parametros(&parametros_listado)?
+        })
+    }
+
+    /// Listado completo, sin paginar, con los mismos filtros y orden; para exportarlo.
+    /// La paginación y `fields` se ignoran.
+    pub fn completa(parametros: &ParametrosListado) -> Result<ConsultaListado, String> {
+        let parametros = ParametrosListado {
+            page: None,
+            per_page: None,
+            limit: None,
+            after: None,
+            fields: None,
+            ..parametros.clone()
+        };
+        Ok(ConsultaListado { por_pagina: u32::MAX, ..ConsultaListado::desde_parametros(&parametros)? })
+    }
+
+    /// Construye la consulta de la página y la del total, sólo con las entradas de `cine`.
+    /// El `id` desempata la ordenación para que las páginas sean estables entre peticiones.
+    pub fn sentencia(&self, cine: u32) -> SentenciaListado {
+        let mut condiciones = vec!["e.cine_id = :cine_id".to_string()];
+        let mut params_conteo = vec![("cine_id".to_string(), Value::from(cine))];
+        for (campo, valor) in &self.filtros {
+            condiciones.push(format!("{} {} :{}", columna(campo), comparacion(campo), campo));
+            params_conteo.push((campo.to_string(), Value::from(valor.as_str())));
+        }
+        // Las entradas tras el cursor en el orden del listado, desempatando por `id` como ORDER BY.
+        if let Some(cursor) = &self.despues {
+            let mayor = if self.descendente { "<" } else { ">" };
+            if self.orden == "id" {
+                condiciones.push(format!("e.id {} :cursor_id", mayor));
+            } else {
+                condiciones.push(format!(
+                    "({columna} {mayor} :cursor_valor OR ({columna} = :cursor_valor AND e.id {mayor} :cursor_id))",
+                    columna = columna(self.orden),
+                    mayor = mayor
+                ));
+                params_conteo.push(("cursor_valor".to_string(), Value::from(cursor.valor.as_str())));
+            }
+            params_conteo.push(("cursor_id".to_string(), Value::from(cursor.id)));
+        }
+        // La intercalación de las columnas ya ignora mayúsculas y tildes al comparar.
+        if let Some(texto) = &self.busqueda {
+            condiciones.push(format!("({} LIKE :busqueda OR {} LIKE :busqueda)", columna("nombre_cliente"), columna("nombre_funcion")));
+            params_conteo.push(("busqueda".to_string(), Value::from(format!("%{}%", escapar_like(texto)))));
+        }
+        let filtro = format!(" WHERE {}", condiciones.join(" AND "));
+
+        let desplazamiento = u64::from(self.pagina - 1) * u64::from(self.por_pagina);
+        let mut params = params_conteo.clone();
+        params.push(("limite".to_string(), Value::from(self.por_pagina)));
+        params.push(("desplazamiento".to_string(), Value::from(desplazamiento)));
+        let direccion = if self.descendente { "DESC" } else { "ASC" };
+        let orden = if self.orden == "id" {
+            format!("e.id {}", direccion)
+        } else {
+            format!("{} {}, e.id {}", columna(self.orden), direccion, direccion)
+        };
+        // El `id` y el campo de ordenación se leen siempre: de ellos sale el cursor siguiente.
+        let select = match &self.campos {
+            Some(campos) => {
+                let leidos = [&campos[..], &["id", self.orden]].concat();
+                format!("SELECT {} FROM {}", crate::campos::columnas(&leidos), crate::db::TABLAS_ENTRADAS)
+            }
+            None => crate::db::SELECT_ENTRADAS.to_string(),
+        };
+        SentenciaListado {
+            consulta: format!(
+                "{}{} ORDER BY {} LIMIT :limite OFFSET :desplazamiento",
+                select,
+                filtro,
+                orden
+            ),
+            params,
+            conteo: format!("SELECT COUNT(*) FROM {}{}", crate::db::TABLAS_ENTRADAS, filtro),
+            params_conteo,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aplica_valores_por_defecto_y_recorta_el_maximo() {
+        let consulta = ConsultaListado::desde_parametros(&ParametrosListado::default()).unwrap();
+        assert_eq!(
+            consulta,
+            ConsultaListado {
+                pagina: 1,
+                por_pagina: POR_PAGINA_DEFECTO,
+                filtros: Vec::new(),
+                orden: "id",
+                descendente: false,
+                busqueda: None,
+                campos: None,
+                por_cursor: false,
+                despues: None,
+            }
+        );
+
+        let parametros = ParametrosListado { page: Some(3), per_page: Some(10_000), ..Default::default() };
+        let consulta = ConsultaListado::desde_parametros(&parametros).unwrap();
+        assert_eq!(consulta.por_pagina, POR_PAGINA_MAXIMO);
+        assert_eq!(
+            consulta.sentencia(1).params[2],
+            ("desplazamiento".to_string(), Value::from(2 * u64::from(POR_PAGINA_MAXIMO)))
+        );
+    }
+
+    #[test]
+    fn la_consulta_completa_no_pagina() {
+        let parametros = ParametrosListado {
+            page: Some(0),
+            funcion_id: Some(3),
+            fields: Some("id".to_string()),
+            ..Default::default()
+        };
+        let consulta = ConsultaListado::completa(&parametros).unwrap();
+        assert_eq!((consulta.pagina, consulta.por_pagina, consulta.campos), (1, u32::MAX, None));
+        assert_eq!(consulta.filtros, [("funcion_id", "3".to_string())]);
+    }
+
+    #[test]
+    fn pagina_por_cursor_tras_la_ultima_entrada() {
+        let cursor = Cursor { orden: "nombre_cliente".into(), descendente: true, valor: "Pérez".into(), id: 7 };
+        let parametros = ParametrosListado {
+            sort: Some("nombre_cliente".into()),
+            order: Some("desc".into()),
+            limit: Some(20),
+            after: Some(cursor.codificar()),
+            ..Default::default()
+        };
+        let consulta = ConsultaListado::desde_parametros(&parametros).unwrap();
+        assert_eq!((consulta.por_cursor, consulta.por_pagina, consulta.despues.as_ref()), (true, 20, Some(&cursor)));
+        let sentencia = consulta.sentencia(1);
+        assert!(sentencia.consulta.contains(
+            " WHERE e.cine_id = :cine_id AND (c.nombre < :cursor_valor OR (c.nombre = :cursor_valor AND e.id < :cursor_id)) ORDER BY"
+        ));
+        assert!(sentencia.consulta.ends_with("OFFSET :desplazamiento"));
+        assert_eq!(sentencia.params[3], ("limite".to_string(), Value::from(20u32)));
+
+        let otro_orden = ParametrosListado { sort: None, ..parametros.clone() };
+        assert!(ConsultaListado::desde_parametros(&otro_orden).is_err());
+        let con_pagina = ParametrosListado { page: Some(2), ..parametros.clone() };
+        assert!(ConsultaListado::desde_parametros(&con_pagina).is_err());
+        let invalido = ParametrosListado { after: Some("no es un cursor".into()), ..parametros };
+        assert!(ConsultaListado::desde_parametros(&invalido).is_err());
+    }
+
+    #[test]
+    fn rechaza_pagina_o_tamano_cero() {
+        let pagina_cero = ParametrosListado { page: Some(0), ..Default::default() };
+        assert!(ConsultaListado::desde_parametros(&pagina_cero).is_err());
+        let tamano_cero = ParametrosListado { per_page: Some(0), ..Default::default() };
+        assert!(ConsultaListado::desde_parametros(&tamano_cero).is_err());
+    }
+
+    #[test]
+    fn los_filtros_viajan_como_parametros() {
+        let parametros = ParametrosListado {
+            nombre_funcion: Some("Dune' OR 1=1 --".to_string()),
+            numero_cedula: Some("12345".to_string()),
+            ..Default::default()
+        };
+        let sentencia = ConsultaListado::desde_parametros(&parametros).unwrap().sentencia(2);
+        assert_eq!(
+            sentencia.conteo,
+            "SELECT COUNT(*) FROM entradas e JOIN clientes c ON c.id = e.cliente_id \
+             JOIN funciones f ON f.id = e.funcion_id JOIN salas s ON s.id = f.sala_id \
+             WHERE e.cine_id = :cine_id AND f.titulo = :nombre_funcion AND c.numero_cedula = :numero_cedula"
+        );
+        assert!(sentencia
+            .consulta
+            .contains(" AND f.titulo = :nombre_funcion AND c.numero_cedula = :numero_cedula ORDER BY e.id ASC"));
+        assert_eq!(sentencia.params_conteo[0], ("cine_id".to_string(), Value::from(2u32)));
+        assert_eq!(
+            sentencia.params_conteo[1],
+            ("nombre_funcion".to_string(), Value::from("Dune' OR 1=1 --"))
+        );
+        assert_eq!(sentencia.params.len(), sentencia.params_conteo.len() + 2);
+    }
+
+    #[test]
+    fn busca_en_el_cliente_y_la_funcion() {
+        let parametros = ParametrosBusqueda { q: " 50% ".to_string(), per_page: Some(5), ..Default::default() };
+        let sentencia = ConsultaListado::busqueda(&parametros).unwrap().sentencia(1);
+        assert!(sentencia.conteo.ends_with(" WHERE e.cine_id = :cine_id AND (c.nombre LIKE :busqueda OR f.titulo LIKE :busqueda)"));
+        assert_eq!(sentencia.params_conteo[1], ("busqueda".to_string(), Value::from("%50\\%%")));
+        assert_eq!(sentencia.params[2], ("limite".to_string(), Value::from(5u32)));
+
+        let parametros = ParametrosBusqueda { q: "dune".to_string(), fields: Some("id".to_string()), ..Default::default() };
+        let sentencia = ConsultaListado::busqueda(&parametros).unwrap().sentencia(1);
+        assert!(sentencia.consulta.starts_with("SELECT e.id, 0, 0, '', '',"));
+
+        let vacia = ParametrosBusqueda { q: "  ".to_string(), ..Default::default() };
+        assert!(ConsultaListado::busqueda(&vacia).is_err());
+    }
+
+    #[test]
+    fn el_horario_se_compara_en_utc() {
+        let parametros = ParametrosListado {
+            horario_funcion: Some("2024-03-01T19:00:00-05:00".to_string()),
+            ..Default::default()
+        };
+        let consulta = ConsultaListado::desde_parametros(&parametros).unwrap();
+        assert_eq!(consulta.filtros, [("horario_funcion", "2024-03-02 00:00:00".to_string())]);
+
+        let parametros = ParametrosListado { horario_funcion: Some("19:00".to_string()), ..Default::default() };
+        assert!(ConsultaListado::desde_parametros(&parametros).is_err());
+    }
+
+    #[test]
+    fn filtra_por_fecha_de_alta() {
+        for fecha in ["2024-03-01", "2024-03-01 18:30:00", "2024-03-01T18:30:00"] {
+            let parametros = ParametrosListado { created_after: Some(fecha.to_string()), ..Default::default() };
+            let sentencia = ConsultaListado::desde_parametros(&parametros).unwrap().sentencia(1);
+            assert!(sentencia.conteo.ends_with(" AND e.created_at > :created_after"), "{}", fecha);
+        }
+        for fecha in ["ayer", "2024-3-1", "2024-13-01", "2024-03-00", "2024-03-01 25:00:00", "2024-03-01 18:30"] {
+            let parametros = ParametrosListado { created_after: Some(fecha.to_string()), ..Default::default() };
+            assert!(ConsultaListado::desde_parametros(&parametros).is_err(), "{}", fecha);
+        }
+    }
+
+    #[test]
+    fn ordena_solo_por_campos_permitidos() {
+        let parametros = ParametrosListado {
+            sort: Some("cantidad_entradas".to_string()),
+            order: Some("DESC".to_string()),
+            ..Default::default()
+        };
+        let sentencia = ConsultaListado::desde_parametros(&parametros).unwrap().sentencia(1);
+        assert!(sentencia.consulta.contains(" ORDER BY e.cantidad_entradas DESC, e.id DESC LIMIT"));
+
+        let inyeccion = ParametrosListado { sort: Some("id; DROP TABLE entradas".to_string()), ..Default::default() };
+        assert!(ConsultaListado::desde_parametros(&inyeccion).is_err());
+        let direccion = ParametrosListado { order: Some("sideways".to_string()), ..Default::default() };
+        assert!(ConsultaListado::desde_parametros(&direccion).is_err());
+    }
+}
@@ -1,277 +1,222 @@
-use actix_web::{web, App, HttpServer, Responder, HttpResponse};
-use mysql_async::{Pool, Opts, prelude::*};
-use serde::{Serialize, Deserialize};
-use dotenv::dotenv;
-use std::env;
-
-/// Estructura que representa una entrada de cine en la base de datos.
-#[derive(Debug, Serialize, Deserialize, FromRow)]
-struct Entrada {
-    id: Option<u32>, 
-    numero_cedula: String,
-    nombre_cliente: String,
-    nombre_funcion: String,
-    cantidad_entradas: u32,
-    horario_funcion: String,
-}
-
-/// Estructura para la creación de una nueva entrada.
-#[derive(Debug, Serialize, Deserialize)]
-struct CrearEntrada {
-    numero_cedula: String,
-    nombre_cliente: String,
-    nombre_funcion: String,
-    cantidad_entradas: u32,
-    horario_funcion: String,
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix_web::rt::time::timeout;
+use clap::{Parser, Subcommand};
+use rust_crud::config::Config;
+use rust_crud::db::repository::RepositorioMysql;
+use rust_crud::db::migraciones::migrar as aplicar_migraciones;
+use rust_crud::db::{esperar_base_datos, obtener_pool_db, precalentar_pool};
+use rust_crud::escucha::Escucha;
+use rust_crud::{recarga, respaldo, semilla};
+use rust_crud::servicio::ServicioEntradas;
+use rust_crud::Server;
+
+/// API CRUD de entradas de cine.
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    /// Archivo de configuración TOML. Sustituye a `CONFIG_ARCHIVO`.
+    #[arg(long, global = true)]
+    config: Option<String>,
+
+    #[command(subcommand)]
+    comando: Option<Comando>,
 }
 
-/// Estructura para la actualización de una entrada.
-#[derive(Debug, Serialize, Deserialize)]
-struct ActualizarEntrada {
-    numero_cedula: Option<String>,
-    nombre_cliente: Option<String>,
-    nombre_funcion: Option<String>,
-    cantidad_entradas: Option<u32>,
-    horario_funcion: Option<String>,
+#[derive(Subcommand)]
+enum Comando {
+    /// Arranca el servidor HTTP (por defecto).
+    Serve,
+    /// Aplica las migraciones pendientes de la base de datos.
+    Migrate,
+    /// Inserta entradas de ejemplo para desarrollo. Se niega si ya hay datos.
+    Seed {
+        /// Carga los ejemplos aunque la base de datos no esté vacía, saltando los que ya existen.
+        #[arg(long)]
+        force: bool,
+    },
+    /// Restaura un respaldo de `POST /admin/backup`, reemplazando todos los datos. Se niega
+    /// si ya hay entradas.
+    Restore {
+        /// Archivo del respaldo, o `-` para leerlo de la entrada estándar.
+        archivo: String,
+        /// Reemplaza los datos aunque la base de datos tenga entradas.
+        #[arg(long)]
+        force: bool,
+    },
+    /// Consulta `/ready` del servidor configurado y termina con error si no está disponible.
+    Healthcheck,
 }
 
-/// Función para obtener la pool de conexiones a la base de datos.
-async fn obtener_pool_db() -> Result<Pool, Box<dyn std::error::Error>> {
-    dotenv().ok(); 
-    let database_url = env::var("DATABASE_URL")
-        .expect("DATABASE_URL debe estar configurada en el archivo .env");
-    let opts = Opts::from_url(&database_url)?;
-    Ok(Pool::new(opts))
-}
-
-/// Handler para obtener todas las entradas de cine.
-async fn obtener_entradas(pool: web::Data<Pool>) -> impl Responder {
-    let mut conn = match pool.get_conn().await {
-        Ok(conn) => conn,
+/// Función principal
+#[actix_web::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let config = match Config::cargar(cli.config.as_deref()) {
+        Ok(c) => c,
         Err(e) => {
-            eprintln!("Error al obtener conexión: {:?}", e);
-            return HttpResponse::InternalServerError().json("Error al conectar a la base de datos");
+            eprintln!("Configuración inválida: {}", e);
+            return ExitCode::FAILURE;
         }
     };
-
-    let result = conn.query_map(
-        "SELECT id, numero_cedula, nombre_cliente, nombre_funcion, cantidad_entradas, horario_funcion FROM entradas",
-        |(id, numero_cedula, nombre_cliente, nombre_funcion, cantidad_entradas, horario_funcion)| {
-            Entrada {
-                id: Some(id),
-                numero_cedula,
-                nombre_cliente,
-                nombre_funcion,
-                cantidad_entradas,
-                horario_funcion,
-            }
-        }
-    ).await;
-
-    match result {
-        Ok(entradas) => HttpResponse::Ok().json(entradas),
+    recarga::iniciar_log(&config.nivel_log);
+
+    let resultado = match cli.comando.unwrap_or(Comando::Serve) {
+        Comando::Serve => servir(config).await,
+        Comando::Migrate => migrar(&config).await,
+        Comando::Seed { force } => sembrar(&config, force).await,
+        Comando::Restore { archivo, force } => restaurar(&config, &archivo, force).await,
+        Comando::Healthcheck => comprobar_salud(&config),
+    };
+    match resultado {
+        Ok(()) => ExitCode::SUCCESS,
         Err(e) => {
-            eprintln!("Error al consultar entradas: {:?}", e);
-            HttpResponse::InternalServerError().json("Error al obtener entradas")
+            eprintln!("{}", e);
+            ExitCode::FAILURE
         }
     }
 }
 
-/// Handler para obtener una entrada específica por su ID.
-async fn obtener_entrada_por_id(pool: web::Data<Pool>, path: web::Path<u32>) -> impl Responder {
-    let entrada_id = path.into_inner();
-    let mut conn = match pool.get_conn().await {
-        Ok(conn) => conn,
-        Err(e) => {
-            eprintln!("Error al obtener conexión: {:?}", e);
-            return HttpResponse::InternalServerError().json("Error al conectar a la base de datos");
-        }
-    };
+type Resultado = Result<(), Box<dyn std::error::Error>>;
 
-    let result = conn.exec_first(
-        "SELECT id, numero_cedula, nombre_cliente, nombre_funcion, cantidad_entradas, horario_funcion FROM entradas WHERE id = :id",
-        params! { "id" => entrada_id }
-    ).await;
+async fn servir(config: Config) -> Resultado {
+    let pool = abrir_pool(&config).await?;
 
-    match result {
-        Ok(Some((id, numero_cedula, nombre_cliente, nombre_funcion, cantidad_entradas, horario_funcion))) => {
-            HttpResponse::Ok().json(Entrada {
-                id: Some(id),
-                numero_cedula,
-                nombre_cliente,
-                nombre_funcion,
-                cantidad_entradas,
-                horario_funcion,
-            })
-        },
-        Ok(None) => HttpResponse::NotFound().json("Entrada no encontrada"),
-        Err(e) => {
-            eprintln!("Error al consultar entrada: {:?}", e);
-            HttpResponse::InternalServerError().json("Error al obtener entrada")
-        }
+    if config.migrar_al_iniciar {
+        ejecutar_migraciones(&pool).await?;
     }
-}
 
-/// Handler para crear una nueva entrada de cine.
-async fn crear_entrada(pool: web::Data<Pool>, entrada_data: web::Json<CrearEntrada>) -> impl Responder {
-    let mut conn = match pool.get_conn().await {
-        Ok(conn) => conn,
-        Err(e) => {
-            eprintln!("Error al obtener conexión: {:?}", e);
-            return HttpResponse::InternalServerError().json("Error al conectar a la base de datos");
+    if config.precalentar_pool {
+        match precalentar_pool(&pool, config.pool_min).await {
+            Ok(()) => log::info!("Pool precalentada con {} conexiones", config.pool_min),
+            Err(e) => log::warn!("No se pudo precalentar la pool, se continúa sin ella: {:?}", e),
         }
-    };
+    }
 
-    let result = conn.exec_drop(
-        "INSERT INTO entradas (numero_cedula, nombre_cliente, nombre_funcion, cantidad_entradas, horario_funcion) VALUES (:numero_cedula, :nombre_cliente, :nombre_funcion, :cantidad_entradas, :horario_funcion)",
-        params! {
-            "numero_cedula" => &entrada_data.numero_cedula,
-            "nombre_cliente" => &entrada_data.nombre_cliente,
-            "nombre_funcion" => &entrada_data.nombre_funcion,
-            "cantidad_entradas" => entrada_data.cantidad_entradas,
-            "horario_funcion" => &entrada_data.horario_funcion,
-        }
-    ).await;
-    
-    // Manejo de error específico para cedulas duplicadas
-    match result {
-        Ok(_) => HttpResponse::Created().json("Entrada creada exitosamente"),
-        Err(e) => {
-            eprintln!("Error al crear entrada: {:?}", e);
-            if e.to_string().contains("Duplicate entry") {
-                HttpResponse::Conflict().json("El número de cédula ya existe para otra entrada")
-            } else {
-                HttpResponse::InternalServerError().json("Error al crear entrada")
-            }
-        }
+    let direcciones: Vec<String> = config.direcciones().iter().map(ToString::to_string).collect();
+    log::info!("El servidor ha iniciado en: {}", direcciones.join(", "));
+    let drenaje = config.drenaje;
+    Server::builder().config(config).pool(pool.clone()).build()?.await?;
+
+    log::info!("Servidor detenido, cerrando las conexiones a la base de datos");
+    match timeout(drenaje, pool.disconnect()).await {
+        Ok(Ok(())) => log::info!("Conexiones cerradas"),
+        Ok(Err(e)) => log::warn!("Error al cerrar las conexiones: {}", e),
+        Err(_) => log::warn!("Las conexiones no se cerraron en {} s, se abandonan", drenaje.as_secs()),
     }
+    Ok(())
 }
 
-/// Handler para actualizar una entrada de cine existente.
-async fn actualizar_entrada(
-    pool: web::Data<Pool>,
-    path: web::Path<u32>,
-    entrada_data: web::Json<ActualizarEntrada>,
-) -> impl Responder {
-    let entrada_id = path.into_inner();
-    let mut conn = match pool.get_conn().await {
-        Ok(conn) => conn,
-        Err(e) => {
-            eprintln!("Error al obtener conexión: {:?}", e);
-            return HttpResponse::InternalServerError().json("Error al conectar a la base de datos");
-        }
-    };
-
-    let mut query_parts = Vec::new();
+/// Crea la pool y espera a que la base de datos responda, como mucho `espera_base_datos`.
+async fn abrir_pool(config: &Config) -> Result<mysql_async::Pool, Box<dyn std::error::Error>> {
+    let pool = obtener_pool_db(config)
+        .map_err(|e| format!("Fallo al inicializar la pool de la base de datos: {:?}", e))?;
+    esperar_base_datos(&pool, config.espera_base_datos).await.map_err(|e| {
+        format!(
+            "La base de datos no está disponible tras {} s: {}",
+            config.espera_base_datos.as_secs(),
+            e
+        )
+    })?;
+    Ok(pool)
+}
 
-    let mut params_vec = Vec::new(); 
-    params_vec.push(("id".to_string(), mysql_async::Value::from(entrada_id)));
+async fn migrar(config: &Config) -> Resultado {
+    let pool = abrir_pool(config).await?;
+    ejecutar_migraciones(&pool).await?;
+    pool.disconnect().await?;
+    Ok(())
+}
 
-    if let Some(numero_cedula) = &entrada_data.numero_cedula {
-        query_parts.push("numero_cedula = :numero_cedula".to_string());
-        params_vec.push(("numero_cedula".to_string(), numero_cedula.clone().into()));
-    }
-    if let Some(nombre_cliente) = &entrada_data.nombre_cliente {
-        query_parts.push("nombre_cliente = :nombre_cliente".to_string());
-        params_vec.push(("nombre_cliente".to_string(), nombre_cliente.clone().into()));
-    }
-    if let Some(nombre_funcion) = &entrada_data.nombre_funcion {
-        query_parts.push("nombre_funcion = :nombre_funcion".to_string());
-        params_vec.push(("nombre_funcion".to_string(), nombre_funcion.clone().into()));
-    }
-    if let Some(cantidad_entradas) = entrada_data.cantidad_entradas {
-        query_parts.push("cantidad_entradas = :cantidad_entradas".to_string());
-        params_vec.push(("cantidad_entradas".to_string(), cantidad_entradas.into()));
+async fn ejecutar_migraciones(pool: &mysql_async::Pool) -> Resultado {
+    let aplicadas = aplicar_migraciones(pool)
+        .await
+        .map_err(|e| format!("Fallo al aplicar las migraciones: {}", e))?;
+    if aplicadas.is_empty() {
+        log::info!("El esquema está al día");
     }
-    if let Some(horario_funcion) = &entrada_data.horario_funcion {
-        query_parts.push("horario_funcion = :horario_funcion".to_string());
-        params_vec.push(("horario_funcion".to_string(), horario_funcion.clone().into()));
+    for migracion in aplicadas {
+        log::info!("Migración {} aplicada", migracion.nombre);
     }
+    Ok(())
+}
 
-    if query_parts.is_empty() {
-        return HttpResponse::BadRequest().json("No se proporcionaron datos para actualizar");
-    }
-
-    let query = format!("UPDATE entradas SET {} WHERE id = :id", query_parts.join(", "));
-    let result = conn.exec_drop(query, params_vec).await;
-
-    match result {
-        Ok(_) => {
-            let affected_rows = conn.affected_rows();
-            if affected_rows == 0 {
-                HttpResponse::NotFound().json("Entrada no encontrada o sin cambios")
-            } else {
-                HttpResponse::Ok().json("Entrada actualizada exitosamente")
-            }
-        },
-        Err(e) => {
-            eprintln!("Error al actualizar entrada: {:?}", e);
-            if e.to_string().contains("Duplicate entry") {
-                HttpResponse::Conflict().json("El número de cédula ya existe para otra entrada")
-            } else {
-                HttpResponse::InternalServerError().json("Error al actualizar entrada")
-            }
-        }
-    }
+async fn sembrar(config: &Config, forzar: bool) -> Resultado {
+    let pool = abrir_pool(config).await?;
+    let servicio = ServicioEntradas::new(
+        Arc::new(RepositorioMysql::new(pool.clone(), config.reintentos)),
+        config.reglas_validacion(),
+        config.zona_horaria,
+    );
+    let resultado = semilla::sembrar(&pool, &servicio, forzar).await;
+    drop(servicio);
+    pool.disconnect().await?;
+    let resumen = resultado?;
+    log::info!(
+        "Datos de ejemplo cargados: {} entradas creadas, {} ya existían",
+        resumen.creadas,
+        resumen.existentes
+    );
+    Ok(())
 }
 
-/// Handler para eliminar una entrada de cine por su ID.
-async fn eliminar_entrada(pool: web::Data<Pool>, path: web::Path<u32>) -> impl Responder {
-    let entrada_id = path.into_inner();
-    let mut conn = match pool.get_conn().await {
-        Ok(conn) => conn,
-        Err(e) => {
-            eprintln!("Error al obtener conexión: {:?}", e);
-            return HttpResponse::InternalServerError().json("Error al conectar a la base de datos");
-        }
+/// Aplica antes las migraciones, para que el esquema sea al menos el del respaldo.
+async fn restaurar(config: &Config, archivo: &str, forzar: bool) -> Resultado {
+    let lector: Box<dyn BufRead> = if archivo == "-" {
+        Box::new(io::stdin().lock())
+    } else {
+        Box::new(BufReader::new(File::open(archivo).map_err(|e| format!("No se pudo abrir {}: {}", archivo, e))?))
     };
+    let pool = abrir_pool(config).await?;
+    ejecutar_migraciones(&pool).await?;
+    let resultado = respaldo::restaurar(&pool, lector, forzar).await;
+    pool.disconnect().await?;
+    log::info!("Respaldo restaurado: {} filas", resultado?);
+    Ok(())
+}
 
-    let result = conn.exec_drop(
-        "DELETE FROM entradas WHERE id = :id",
-        params! { "id" => entrada_id }
-    ).await;
-
-    match result {
-        Ok(_) => {
-            let affected_rows = conn.affected_rows();
-            if affected_rows == 0 {
-                HttpResponse::NotFound().json("Entrada no encontrada")
-            } else {
-                HttpResponse::Ok().json("Entrada eliminada exitosamente")
-            }
-        },
-        Err(e) => {
-            eprintln!("Error al eliminar entrada: {:?}", e);
-            HttpResponse::InternalServerError().json("Error al eliminar entrada")
+/// Petición HTTP mínima a `/ready` en la primera dirección del servidor, para no depender
+/// de `curl` en la imagen.
+fn comprobar_salud(config: &Config) -> Resultado {
+    match config.direcciones().remove(0) {
+        Escucha::Tcp(direccion) => {
+            // Escuchar en todas las interfaces incluye la local.
+            let direccion = match direccion.rsplit_once(':') {
+                Some(("0.0.0.0", puerto)) => format!("127.0.0.1:{}", puerto),
+                Some(("[::]", puerto)) => format!("[::1]:{}", puerto),
+                _ => direccion,
+            };
+            let conexion =
+                TcpStream::connect(&direccion).map_err(|e| format!("No se pudo conectar a {}: {}", direccion, e))?;
+            conexion.set_read_timeout(Some(Duration::from_secs(5)))?;
+            consultar_disponibilidad(conexion, &direccion)
         }
+        #[cfg(unix)]
+        Escucha::Unix(ruta) => {
+            let conexion = std::os::unix::net::UnixStream::connect(&ruta)
+                .map_err(|e| format!("No se pudo conectar a {}: {}", ruta, e))?;
+            conexion.set_read_timeout(Some(Duration::from_secs(5)))?;
+            consultar_disponibilidad(conexion, "localhost")
+        }
+        #[cfg(not(unix))]
+        Escucha::Unix(ruta) => Err(format!("No se puede conectar al socket {} fuera de Unix", ruta).into()),
     }
 }
 
-/// Función principal 
-#[actix_web::main]
-async fn main() -> std::io::Result<()> {
-    let pool = match obtener_pool_db().await {
-        Ok(p) => p,
-        Err(e) => {
-            eprintln!("Fallo al inicializar la pool de la base de datos: {:?}", e);
-            std::process::exit(1); // Sale si no se puede conectar a la DB
-        }
-    };
+fn consultar_disponibilidad(mut conexion: impl Read + Write, host: &str) -> Resultado {
+    write!(conexion, "GET /ready HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n", host)?;
 
-    println!("El servidor ha iniciado en la ruta: http://127.0.0.1:8080");
-    HttpServer::new(move || {
-        App::new()
-            .app_data(web::Data::new(pool.clone())) 
-            .service(
-                web::scope("/entradas") // Todas las rutas bajo /entradas
-                    .route("", web::get().to(obtener_entradas))
-                    .route("", web::post().to(crear_entrada))
-                    .route("/{id}", web::get().to(obtener_entrada_por_id))
-                    .route("/{id}", web::put().to(actualizar_entrada))
-                    .route("/{id}", web::delete().to(eliminar_entrada)),
-            )
-    })
-    .bind(("127.0.0.1", 8080))?
-    .run()
-    .await
+    let mut respuesta = String::new();
+    conexion.read_to_string(&mut respuesta)?;
+    let estado = respuesta.lines().next().unwrap_or_default();
+    if estado.split_whitespace().nth(1) == Some("200") {
+        Ok(())
+    } else {
+        Err(format!("El servidor no está disponible: {}", estado).into())
+    }
 }
@@ -0,0 +1,350 @@
+//! Métricas en formato de texto de Prometheus, servidas en `/metrics`.
+//!
+//! [`medir_peticiones`] cuenta cada petición por método, patrón de ruta y código de
+//! estado, y guarda su duración en un histograma por ruta. A eso se suman el uso de la
+//! pool de conexiones que mide [`crate::db::conectar`], la duración de cada operación del
+//! repositorio (ver [`crate::db::medicion`]), los contadores de coalescencia
+//! de lecturas y de ventas, los reintentos de la base de datos, los aciertos de la caché y las reservas
+//! vencidas. El uso de la pool también se consulta en JSON en `/admin/pool`.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{web, Error, HttpResponse};
+use serde::Serialize;
+
+use crate::auth::Administrador;
+use crate::cache::CacheLecturas;
+use crate::config::Config;
+use crate::db::medicion::METRICAS_CONSULTAS;
+use crate::db::reintentos::METRICAS_REINTENTOS;
+use crate::db::replica::Replica;
+use crate::db::METRICAS_POOL;
+use crate::reservas::RESERVAS_VENCIDAS;
+use crate::respuesta::ApiResponse;
+use crate::servicio::ServicioEntradas;
+use crate::webhooks::METRICAS_WEBHOOKS;
+
+/// Límites superiores, en segundos, de las cubetas de los histogramas.
+const LIMITES_SEGUNDOS: [f64; 11] = [0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0];
+
+/// Ruta con la que se agrupan las peticiones que no coinciden con ninguna, para no crear
+/// una serie por cada URL desconocida.
+const RUTA_DESCONOCIDA: &str = "desconocida";
+
+/// Histograma acumulativo de duraciones.
+#[derive(Debug, Clone, Default)]
+pub struct Histograma {
+    cubetas: [u64; LIMITES_SEGUNDOS.len()],
+    cuenta: u64,
+    suma: f64,
+}
+
+impl Histograma {
+    pub const fn new() -> Self {
+        Histograma {
+            cubetas: [0; LIMITES_SEGUNDOS.len()],
+            cuenta: 0,
+            suma: 0.0,
+        }
+    }
+
+    pub fn observar(&mut self, duracion: Duration) {
+        let segundos = duracion.as_secs_f64();
+        for (cubeta, limite) in self.cubetas.iter_mut().zip(LIMITES_SEGUNDOS) {
+            if segundos <= limite {
+                *cubeta += 1;
+            }
+        }
+        self.cuenta += 1;
+        self.suma += segundos;
+    }
+
+    pub fn cuenta(&self) -> u64 {
+        self.cuenta
+    }
+
+    /// Duración media de lo observado, o `None` si no hay nada.
+    pub fn media(&self) -> Option<Duration> {
+        (self.cuenta > 0).then(|| Duration::from_secs_f64(self.suma / self.cuenta as f64))
+    }
+
+    /// Escribe las series `_bucket`, `_sum` y `_count` con las etiquetas dadas.
+    fn escribir(&self, salida: &mut String, nombre: &str, etiquetas: &str) {
+        let separador = if etiquetas.is_empty() { "" } else { "," };
+        for (cubeta, limite) in self.cubetas.iter().zip(LIMITES_SEGUNDOS) {
+            let _ = writeln!(salida, "{}_bucket{{{}{}le=\"{}\"}} {}", nombre, etiquetas, separador, limite, cubeta);
+        }
+        let _ = writeln!(salida, "{}_bucket{{{}{}le=\"+Inf\"}} {}", nombre, etiquetas, separador, self.cuenta);
+        let llaves = if etiquetas.is_empty() { String::new() } else { format!("{{{}}}", etiquetas) };
+        let _ = writeln!(salida, "{}_sum{} {}", nombre, llaves, self.suma);
+        let _ = writeln!(salida, "{}_count{} {}", nombre, llaves, self.cuenta);
+    }
+}
+
+/// Contadores e histogramas de las peticiones HTTP. Se comparte entre los workers.
+#[derive(Default)]
+pub struct Metricas {
+    peticiones: Mutex<HashMap<(String, String, u16), u64>>,
+    duraciones: Mutex<HashMap<(String, String), Histograma>>,
+}
+
+impl Metricas {
+    pub fn registrar(&self, metodo: &str, ruta: &str, estado: u16, duracion: Duration) {
+        *self
+            .peticiones
+            .lock()
+            .unwrap()
+            .entry((metodo.to_string(), ruta.to_string(), estado))
+            .or_default() += 1;
+        self.duraciones
+            .lock()
+            .unwrap()
+            .entry((metodo.to_string(), ruta.to_string()))
+            .or_default()
+            .observar(duracion);
+    }
+
+    /// Texto de exposición de las métricas HTTP, ordenado para que la salida sea estable.
+    fn escribir(&self, salida: &mut String) {
+        salida.push_str("# HELP http_peticiones_total Peticiones atendidas por método, ruta y código de estado.\n");
+        salida.push_str("# TYPE http_peticiones_total counter\n");
+        let mut peticiones: Vec<_> = self.peticiones.lock().unwrap().clone().into_iter().collect();
+        peticiones.sort();
+        for ((metodo, ruta, estado), cuenta) in peticiones {
+            let _ = writeln!(
+                salida,
+                "http_peticiones_total{{metodo=\"{}\",ruta=\"{}\",estado=\"{}\"}} {}",
+                metodo, ruta, estado, cuenta
+            );
+        }
+
+        salida.push_str("# HELP http_duracion_segundos Duración de las peticiones por método y ruta.\n");
+        salida.push_str("# TYPE http_duracion_segundos histogram\n");
+        let mut duraciones: Vec<_> = self.duraciones.lock().unwrap().clone().into_iter().collect();
+        duraciones.sort_by(|a, b| a.0.cmp(&b.0));
+        for ((metodo, ruta), histograma) in duraciones {
+            let etiquetas = format!("metodo=\"{}\",ruta=\"{}\"", metodo, ruta);
+            histograma.escribir(salida, "http_duracion_segundos", &etiquetas);
+        }
+    }
+}
+
+/// Middleware que mide cada petición.
+pub async fn medir_peticiones(
+    metricas: web::Data<Metricas>,
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let metodo = req.method().to_string();
+    let ruta = req.match_pattern().unwrap_or_else(|| RUTA_DESCONOCIDA.to_string());
+    let inicio = Instant::now();
+    let respuesta = next.call(req).await;
+
+    let estado = match &respuesta {
+        Ok(res) => res.status(),
+        Err(e) => e.as_response_error().status_code(),
+    };
+    metricas.registrar(&metodo, &ruta, estado.as_u16(), inicio.elapsed());
+    respuesta
+}
+
+/// Configuración y uso de la pool en `GET /admin/pool`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EstadoPool {
+    pub minimo: usize,
+    pub maximo: usize,
+    pub ttl_inactiva_segundos: u64,
+    /// Vida máxima de cada conexión; `None` si no se limita.
+    pub ttl_segundos: Option<u64>,
+    /// Espera máxima por una conexión; 0 si no se limita.
+    pub espera_maxima_ms: u64,
+    pub en_uso: u64,
+    pub disponibles: u64,
+    /// Peticiones esperando ahora mismo una conexión.
+    pub esperando: u64,
+    /// Conexiones pedidas desde el arranque y su espera media.
+    pub prestadas: u64,
+    pub espera_media_ms: Option<f64>,
+    /// Peticiones que se rindieron sin conseguir conexión desde el arranque.
+    pub esperas_agotadas: u64,
+    /// Réplica de lectura, si hay `DATABASE_URL_RO`.
+    pub replica: Option<EstadoReplica>,
+}
+
+/// Estado de la réplica de lectura en `GET /admin/pool`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EstadoReplica {
+    /// Si se está leyendo de ella, o de la primaria porque no dio conexión hace poco.
+    pub disponible: bool,
+    /// Veces que no dio conexión desde el arranque.
+    pub caidas: u64,
+}
+
+/// Handler de `/admin/pool`, para ver si la pool se queda corta. Sólo para administradores.
+pub async fn estado_pool(
+    _admin: Administrador,
+    config: web::Data<Config>,
+    replica: web::Data<Option<Arc<Replica>>>,
+) -> ApiResponse<EstadoPool> {
+    let en_uso = METRICAS_POOL.en_uso.load(Ordering::Relaxed);
+    let espera = METRICAS_POOL.espera.lock().unwrap().clone();
+    ApiResponse::ok(EstadoPool {
+        minimo: config.pool_min,
+        maximo: config.pool_max,
+        ttl_inactiva_segundos: config.pool_ttl_inactiva.as_secs(),
+        ttl_segundos: config.pool_ttl.map(|ttl| ttl.as_secs()),
+        espera_maxima_ms: config.pool_espera_maxima.as_millis() as u64,
+        en_uso,
+        disponibles: (config.pool_max as u64).saturating_sub(en_uso),
+        esperando: METRICAS_POOL.esperando.load(Ordering::Relaxed),
+        prestadas: espera.cuenta(),
+        espera_media_ms: espera.media().map(|media| media.as_secs_f64() * 1000.0),
+        esperas_agotadas: METRICAS_POOL.agotadas.load(Ordering::Relaxed),
+        replica: replica.as_ref().as_ref().map(|replica| EstadoReplica {
+            disponible: replica.disponible(),
+            caidas: replica.caidas.load(Ordering::Relaxed),
+        }),
+    })
+}
+
+/// Handler de `/metrics`.
+pub async fn exportar_metricas(
+    metricas: web::Data<Metricas>,
+    servicio: web::Data<ServicioEntradas>,
+    cache: web::Data<CacheLecturas>,
+    config: web::Data<Config>,
+) -> HttpResponse {
+    let mut salida = String::new();
+    metricas.escribir(&mut salida);
+
+    salida.push_str("# HELP db_pool_conexiones_en_uso Conexiones de la pool prestadas en este momento.\n");
+    salida.push_str("# TYPE db_pool_conexiones_en_uso gauge\n");
+    let en_uso = METRICAS_POOL.en_uso.load(Ordering::Relaxed);
+    let _ = writeln!(salida, "db_pool_conexiones_en_uso {}", en_uso);
+    salida.push_str("# HELP db_pool_conexiones_disponibles Conexiones que aún se pueden prestar (máximo menos en uso).\n");
+    salida.push_str("# TYPE db_pool_conexiones_disponibles gauge\n");
+    let _ = writeln!(
+        salida,
+        "db_pool_conexiones_disponibles {}",
+        (config.pool_max as u64).saturating_sub(en_uso)
+    );
+    salida.push_str("# HELP db_pool_conexiones_maximas Límite de conexiones de la pool.\n");
+    salida.push_str("# TYPE db_pool_conexiones_maximas gauge\n");
+    let _ = writeln!(salida, "db_pool_conexiones_maximas {}", config.pool_max);
+    salida.push_str("# HELP db_pool_espera_segundos Tiempo de espera para obtener una conexión de la pool.\n");
+    salida.push_str("# TYPE db_pool_espera_segundos histogram\n");
+    METRICAS_POOL
+        .espera
+        .lock()
+        .unwrap()
+        .escribir(&mut salida, "db_pool_espera_segundos", "");
+    salida.push_str("# HELP db_pool_peticiones_esperando Peticiones esperando ahora mismo una conexión de la pool.\n");
+    salida.push_str("# TYPE db_pool_peticiones_esperando gauge\n");
+    let _ = writeln!(salida, "db_pool_peticiones_esperando {}", METRICAS_POOL.esperando.load(Ordering::Relaxed));
+    salida.push_str("# HELP db_pool_esperas_agotadas_total Peticiones que no obtuvieron conexión en la espera máxima.\n");
+    salida.push_str("# TYPE db_pool_esperas_agotadas_total counter\n");
+    let _ = writeln!(salida, "db_pool_esperas_agotadas_total {}", METRICAS_POOL.agotadas.load(Ordering::Relaxed));
+    salida.push_str("# HELP db_reintentos_total Operaciones repetidas por un error transitorio de MySQL, por motivo.\n");
+    salida.push_str("# TYPE db_reintentos_total counter\n");
+    for (motivo, contador) in [("bloqueo", &METRICAS_REINTENTOS.bloqueo), ("conexion", &METRICAS_REINTENTOS.conexion)] {
+        let _ = writeln!(salida, "db_reintentos_total{{motivo=\"{}\"}} {}", motivo, contador.load(Ordering::Relaxed));
+    }
+    salida.push_str("# HELP db_reintentos_agotados_total Operaciones que siguieron fallando tras agotar los reintentos.\n");
+    salida.push_str("# TYPE db_reintentos_agotados_total counter\n");
+    let _ = writeln!(
+        salida,
+        "db_reintentos_agotados_total {}",
+        METRICAS_REINTENTOS.agotados.load(Ordering::Relaxed)
+    );
+    salida.push_str("# HELP db_consulta_duracion_segundos Duración de las operaciones del repositorio de entradas, por operación.\n");
+    salida.push_str("# TYPE db_consulta_duracion_segundos histogram\n");
+    for (consulta, histograma) in METRICAS_CONSULTAS.duraciones.lock().unwrap().iter() {
+        histograma.escribir(&mut salida, "db_consulta_duracion_segundos", &format!("consulta=\"{}\"", consulta));
+    }
+    salida.push_str("# HELP db_consultas_lentas_total Operaciones del repositorio que superaron el umbral de consulta lenta.\n");
+    salida.push_str("# TYPE db_consultas_lentas_total counter\n");
+    for (consulta, cuenta) in METRICAS_CONSULTAS.lentas.lock().unwrap().iter() {
+        let _ = writeln!(salida, "db_consultas_lentas_total{{consulta=\"{}\"}} {}", consulta, cuenta);
+    }
+
+    salida.push_str("# HELP lecturas_coalescidas_total Lecturas por grupo, según llegaran a la base de datos o reutilizaran una en curso.\n");
+    salida.push_str("# TYPE lecturas_coalescidas_total counter\n");
+    let lecturas = servicio.lecturas();
+    for (grupo, estadisticas) in [
+        ("entradas", lecturas.entradas.estadisticas()),
+        ("agregados", lecturas.agregados.estadisticas()),
+    ] {
+        let _ = writeln!(
+            salida,
+            "lecturas_coalescidas_total{{grupo=\"{}\",resultado=\"ejecutada\"}} {}",
+            grupo, estadisticas.ejecutadas
+        );
+        let _ = writeln!(
+            salida,
+            "lecturas_coalescidas_total{{grupo=\"{}\",resultado=\"coalescida\"}} {}",
+            grupo, estadisticas.coalescidas
+        );
+    }
+
+    salida.push_str("# HELP altas_coalescidas_total Ventas de entradas, según las creara la propia petición o recibieran el resultado de otra idéntica simultánea.\n");
+    salida.push_str("# TYPE altas_coalescidas_total counter\n");
+    let altas = servicio.altas();
+    let _ = writeln!(salida, "altas_coalescidas_total{{resultado=\"ejecutada\"}} {}", altas.ejecutadas);
+    let _ = writeln!(salida, "altas_coalescidas_total{{resultado=\"coalescida\"}} {}", altas.coalescidas);
+
+    salida.push_str("# HELP cache_lecturas_total Lecturas por caché, según se sirvieran desde ella o fueran a la base de datos.\n");
+    salida.push_str("# TYPE cache_lecturas_total counter\n");
+    for (nombre, estadisticas) in [
+        ("entradas", cache.entradas.estadisticas()),
+        ("funciones", cache.funciones.estadisticas()),
+    ] {
+        let _ = writeln!(salida, "cache_lecturas_total{{cache=\"{}\",resultado=\"acierto\"}} {}", nombre, estadisticas.aciertos);
+        let _ = writeln!(salida, "cache_lecturas_total{{cache=\"{}\",resultado=\"fallo\"}} {}", nombre, estadisticas.fallos);
+    }
+
+    salida.push_str("# HELP reservas_vencidas_total Entradas reservadas que se cancelaron por no pagarse a tiempo.\n");
+    salida.push_str("# TYPE reservas_vencidas_total counter\n");
+    let _ = writeln!(salida, "reservas_vencidas_total {}", RESERVAS_VENCIDAS.load(Ordering::Relaxed));
+
+    salida.push_str("# HELP webhooks_entregas_total Intentos de entrega de avisos de webhooks, por resultado.\n");
+    salida.push_str("# TYPE webhooks_entregas_total counter\n");
+    for (resultado, contador) in [
+        ("entregado", &METRICAS_WEBHOOKS.entregados),
+        ("reintento", &METRICAS_WEBHOOKS.reintentos),
+        ("fallido", &METRICAS_WEBHOOKS.fallidos),
+    ] {
+        let _ = writeln!(salida, "webhooks_entregas_total{{resultado=\"{}\"}} {}", resultado, contador.load(Ordering::Relaxed));
+    }
+
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4; charset=utf-8")
+        .body(salida)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn el_histograma_es_acumulativo() {
+        let mut histograma = Histograma::new();
+        histograma.observar(Duration::from_millis(3));
+        histograma.observar(Duration::from_millis(200));
+        let mut salida = String::new();
+        histograma.escribir(&mut salida, "h", "ruta=\"/x\"");
+        assert!(salida.contains("h_bucket{ruta=\"/x\",le=\"0.001\"} 0\n"));
+        assert!(salida.contains("h_bucket{ruta=\"/x\",le=\"0.005\"} 1\n"));
+        assert!(salida.contains("h_bucket{ruta=\"/x\",le=\"0.25\"} 2\n"));
+        assert!(salida.contains("h_bucket{ruta=\"/x\",le=\"+Inf\"} 2\n"));
+        assert!(salida.contains("h_count{ruta=\"/x\"} 2\n"));
+        assert_eq!(histograma.media(), Some(Duration::from_micros(101_500)));
+        assert_eq!(Histograma::new().media(), None);
+    }
+}
@@ -0,0 +1,435 @@
+//! Modelos de datos expuestos por la API.
+
+use async_graphql::{Enum, InputObject, SimpleObject};
+use chrono::{DateTime, FixedOffset, NaiveDateTime};
+use mysql_async::prelude::{FromRow, FromValue};
+use mysql_async::{from_row_opt, FromRowError, Row};
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize};
+
+pub use crate::actualizacion::ActualizarEntrada;
+
+/// Estructura que representa una entrada de cine en la base de datos, con su cliente y su función.
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
+#[graphql(rename_fields = "snake_case")]
+pub struct Entrada {
+    pub id: Option<u32>,
+    pub cantidad_entradas: u32,
+    /// Precio de la función por la cantidad, calculado al vender la entrada y en cada cambio.
+    pub total: f64,
+    pub estado: EstadoEntrada,
+    pub cliente: Cliente,
+    pub funcion: Funcion,
+    /// Aumenta con cada cambio; es el `ETag` de la entrada.
+    pub version: u32,
+    /// Fecha y hora del alta, `AAAA-MM-DD HH:MM:SS`.
+    pub created_at: String,
+    /// Fecha y hora del último cambio; al crearla, la del alta.
+    pub updated_at: String,
+}
+
+/// Columnas de `db::SELECT_ENTRADAS`.
+const COLUMNAS_ENTRADA: usize = 17;
+
+/// Toma la columna `indice` de la fila, si existe y es del tipo pedido.
+fn columna<T: FromValue>(row: &mut Row, indice: usize) -> Option<T> {
+    row.take_opt(indice)?.ok()
+}
+
+/// Mapeo posicional según las columnas de `db::SELECT_ENTRADAS`. Es varias veces más rápido
+/// que el `FromRow` derivado, que busca cada columna por nombre en cada fila
+/// (ver `benches/mapeo_filas.rs`). Son más columnas de las que admite una tupla, así que
+/// se toman una a una.
+impl FromRow for Entrada {
+    fn from_row_opt(mut row: Row) -> Result<Self, FromRowError> {
+        if row.len() != COLUMNAS_ENTRADA {
+            return Err(FromRowError(row));
+        }
+        let entrada = (|| {
+            Some(Entrada {
+                id: Some(columna(&mut row, 0)?),
+                cantidad_entradas: columna(&mut row, 1)?,
+                total: columna(&mut row, 15)?,
+                estado: EstadoEntrada::desde_str(&columna::<String>(&mut row, 16)?)?,
+                cliente: Cliente {
+                    id: columna(&mut row, 2)?,
+                    numero_cedula: columna(&mut row, 3)?,
+                    nombre: columna(&mut row, 4)?,
+                    email: None,
+                },
+                funcion: Funcion {
+                    id: columna(&mut row, 5)?,
+                    titulo: columna(&mut row, 6)?,
+                    horario: horario_utc(columna(&mut row, 7)?),
+                    precio: columna(&mut row, 8)?,
+                    sala: Sala {
+                        id: columna(&mut row, 9)?,
+                        nombre: columna(&mut row, 10)?,
+                        capacidad: columna(&mut row, 11)?,
+                    },
+                    poster: None,
+                },
+                version: columna(&mut row, 12)?,
+                created_at: columna(&mut row, 13)?,
+                updated_at: columna(&mut row, 14)?,
+            })
+        })();
+        entrada.ok_or(FromRowError(row))
+    }
+}
+
+impl Entrada {
+    /// La entrada con el horario de su función en la zona indicada.
+    pub fn en_zona(mut self, zona: FixedOffset) -> Entrada {
+        self.funcion = self.funcion.en_zona(zona);
+        self
+    }
+}
+
+/// Estado de una entrada: se reserva, se paga y se usa al entrar a la función. Se puede
+/// cancelar mientras no se haya usado.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Enum)]
+#[serde(rename_all = "snake_case")]
+pub enum EstadoEntrada {
+    Reservada,
+    Pagada,
+    Usada,
+    Cancelada,
+}
+
+impl EstadoEntrada {
+    /// Valor de la columna `entradas.estado`.
+    pub fn como_str(self) -> &'static str {
+        match self {
+            EstadoEntrada::Reservada => "reservada",
+            EstadoEntrada::Pagada => "pagada",
+            EstadoEntrada::Usada => "usada",
+            EstadoEntrada::Cancelada => "cancelada",
+        }
+    }
+
+    pub fn desde_str(valor: &str) -> Option<EstadoEntrada> {
+        match valor {
+            "reservada" => Some(EstadoEntrada::Reservada),
+            "pagada" => Some(EstadoEntrada::Pagada),
+            "usada" => Some(EstadoEntrada::Usada),
+            "cancelada" => Some(EstadoEntrada::Cancelada),
+            _ => None,
+        }
+    }
+
+    /// Si una entrada en este estado puede pasar a `nuevo`.
+    pub fn puede_pasar_a(self, nuevo: EstadoEntrada) -> bool {
+        use EstadoEntrada::*;
+        matches!((self, nuevo), (Reservada, Pagada) | (Reservada | Pagada, Cancelada) | (Pagada, Usada))
+    }
+}
+
+/// Cliente, identificado ante la API por su cédula.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, SimpleObject)]
+#[graphql(rename_fields = "snake_case")]
+pub struct Cliente {
+    pub id: u32,
+    pub numero_cedula: String,
+    pub nombre: String,
+    /// Dirección a la que se envía la confirmación de cada compra (ver [`crate::correos`]).
+    /// El cliente de una entrada no la incluye.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[graphql(skip)]
+    pub email: Option<String>,
+}
+
+/// Mapeo posicional según las columnas de `clientes::SELECT_CLIENTES`.
+impl FromRow for Cliente {
+    fn from_row_opt(row: Row) -> Result<Self, FromRowError> {
+        let (id, numero_cedula, nombre, email) = from_row_opt(row)?;
+        Ok(Cliente { id, numero_cedula, nombre, email })
+    }
+}
+
+/// Datos con los que se registra un cliente.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrearCliente {
+    pub numero_cedula: String,
+    pub nombre: String,
+    #[serde(default)]
+    pub email: Option<String>,
+}
+
+/// Datos de un cliente que se pueden cambiar. La cédula lo identifica y no cambia.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActualizarCliente {
+    pub nombre: String,
+    /// Sin él, el cliente deja de recibir confirmaciones.
+    #[serde(default)]
+    pub email: Option<String>,
+}
+
+/// Sala de cine. Su capacidad es la de todas sus funciones.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, SimpleObject)]
+pub struct Sala {
+    pub id: u32,
+    pub nombre: String,
+    pub capacidad: u32,
+}
+
+/// Mapeo posicional según las columnas de `salas::SELECT_SALAS`.
+impl FromRow for Sala {
+    fn from_row_opt(row: Row) -> Result<Self, FromRowError> {
+        let (id, nombre, capacidad) = from_row_opt(row)?;
+        Ok(Sala { id, nombre, capacidad })
+    }
+}
+
+/// Datos de una sala al crearla o reemplazarla.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrearSala {
+    pub nombre: String,
+    pub capacidad: u32,
+}
+
+/// Función de cine: una película en una sala y un horario.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, SimpleObject)]
+pub struct Funcion {
+    pub id: u32,
+    pub titulo: String,
+    /// En RFC 3339, con el desplazamiento de la zona horaria configurada.
+    pub horario: DateTime<FixedOffset>,
+    pub precio: f64,
+    pub sala: Sala,
+    /// URL del póster (ver [`crate::posters`]). Las entradas no la incluyen en su función.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub poster: Option<String>,
+}
+
+impl Funcion {
+    /// La función con su horario en la zona indicada. Es el mismo instante.
+    pub fn en_zona(mut self, zona: FixedOffset) -> Funcion {
+        self.horario = self.horario.with_timezone(&zona);
+        self
+    }
+}
+
+/// Horario tal como se guarda, en UTC y sin zona. Queda en UTC hasta que se muestra con
+/// [`Funcion::en_zona`].
+fn horario_utc(horario: NaiveDateTime) -> DateTime<FixedOffset> {
+    horario.and_utc().fixed_offset()
+}
+
+/// Mapeo posicional según las columnas de `funciones::SELECT_FUNCIONES`.
+impl FromRow for Funcion {
+    fn from_row_opt(row: Row) -> Result<Self, FromRowError> {
+        let (id, titulo, horario, precio, sala_id, sala, capacidad, poster): (_, _, _, _, _, _, _, Option<String>) =
+            from_row_opt(row)?;
+        Ok(Funcion {
+            id,
+            titulo,
+            horario: horario_utc(horario),
+            precio,
+            sala: Sala { id: sala_id, nombre: sala, capacidad },
+            poster: poster.map(|nombre| crate::posters::url(&nombre)),
+        })
+    }
+}
+
+/// Datos de una función al crearla o reemplazarla.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrearFuncion {
+    pub titulo: String,
+    pub sala_id: u32,
+    /// En RFC 3339 con su desplazamiento, por ejemplo `2024-03-01T19:00:00-05:00`.
+    #[serde(deserialize_with = "horario_rfc3339")]
+    pub horario: DateTime<FixedOffset>,
+    pub precio: f64,
+}
+
+/// Lee un horario en RFC 3339, con un mensaje de error que indica el formato esperado.
+fn horario_rfc3339<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DateTime<FixedOffset>, D::Error> {
+    let texto = String::deserialize(deserializer)?;
+    DateTime::parse_from_rfc3339(&texto).map_err(|_| {
+        D::Error::custom("Debe ser una fecha y hora RFC 3339 con su zona, por ejemplo 2024-03-01T19:00:00-05:00")
+    })
+}
+
+/// Todos los datos de una entrada, para reemplazarla con `PUT /entradas/{id}`. Sus
+/// asientos numerados, si los tiene, se conservan.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReemplazarEntrada {
+    pub cliente_id: u32,
+    pub funcion_id: u32,
+    pub cantidad_entradas: u32,
+}
+
+impl From<&ReemplazarEntrada> for ActualizarEntrada {
+    fn from(datos: &ReemplazarEntrada) -> Self {
+        ActualizarEntrada {
+            cliente_id: Some(datos.cliente_id),
+            funcion_id: Some(datos.funcion_id),
+            cantidad_entradas: Some(datos.cantidad_entradas),
+        }
+    }
+}
+
+/// Datos de `PUT /entradas/cedula/{numero_cedula}`, que crea o actualiza la entrada del
+/// cliente con esa cédula.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GuardarEntrada {
+    pub funcion_id: u32,
+    pub cantidad_entradas: u32,
+}
+
+/// Cuerpo de `POST /funciones/{id}/cancelar`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CancelarFuncion {
+    /// Queda en la auditoría de cada entrada cancelada.
+    pub motivo: String,
+}
+
+/// Criterios de `DELETE /entradas`. Los presentes se combinan con AND.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct EliminarEntradas {
+    pub funcion_id: Option<u32>,
+    pub ids: Option<Vec<u32>>,
+}
+
+/// Cambio registrado en el historial de una entrada (`GET /entradas/{id}/historial`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RegistroAuditoria {
+    pub id: u64,
+    pub entrada_id: u32,
+    /// `crear`, `actualizar` o `eliminar`.
+    pub operacion: String,
+    /// Usuario o clave de API que hizo el cambio.
+    pub actor: String,
+    /// Motivo que indicó, si lo hizo.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub motivo: Option<String>,
+    /// La entrada antes del cambio; no hay en las altas.
+    pub valor_anterior: Option<serde_json::Value>,
+    /// La entrada después del cambio; no hay en las bajas.
+    pub valor_nuevo: Option<serde_json::Value>,
+    pub realizada_en: String,
+}
+
+/// Mapeo posicional según las columnas de `repository::SELECT_AUDITORIA`. Los valores
+/// llegan como texto JSON.
+impl FromRow for RegistroAuditoria {
+    fn from_row_opt(row: Row) -> Result<Self, FromRowError> {
+        let (id, entrada_id, operacion, actor, motivo, anterior, nuevo, realizada_en): (_, _, _, _, _, Option<String>, Option<String>, _) =
+            from_row_opt(row)?;
+        let json = |valor: Option<String>| valor.and_then(|texto| serde_json::from_str(&texto).ok());
+        Ok(RegistroAuditoria {
+            id,
+            entrada_id,
+            operacion,
+            actor,
+            motivo,
+            valor_anterior: json(anterior),
+            valor_nuevo: json(nuevo),
+            realizada_en,
+        })
+    }
+}
+
+/// Ocupación de una función (`GET /funciones/{id}/asientos`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AsientosFuncion {
+    pub capacidad: u32,
+    /// Entradas que aún se pueden vender, numeradas o no.
+    pub disponibles: u32,
+    /// Números de asiento libres.
+    pub asientos: Vec<u32>,
+}
+
+/// Entradas vendidas y lo recaudado por ellas en un grupo de `GET /reportes/ventas`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Ventas {
+    pub entradas: u64,
+    pub recaudado: f64,
+}
+
+/// Ventas de una función.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VentasFuncion {
+    pub funcion_id: u32,
+    pub titulo: String,
+    pub sala: String,
+    pub horario: DateTime<FixedOffset>,
+    #[serde(flatten)]
+    pub ventas: Ventas,
+}
+
+/// Ventas de las funciones de un día, `AAAA-MM-DD`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VentasDia {
+    pub fecha: String,
+    #[serde(flatten)]
+    pub ventas: Ventas,
+}
+
+/// Ventas de las funciones que empiezan en una franja de una hora, `HH:00-HH:00`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VentasFranja {
+    pub franja: String,
+    #[serde(flatten)]
+    pub ventas: Ventas,
+}
+
+/// Reporte de `GET /reportes/ventas`. Las fechas y franjas son las del horario de cada
+/// función en la zona configurada, y no cuentan las entradas canceladas.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReporteVentas {
+    pub totales: Ventas,
+    pub por_funcion: Vec<VentasFuncion>,
+    pub por_dia: Vec<VentasDia>,
+    pub por_franja: Vec<VentasFranja>,
+}
+
+/// Rol de un usuario de la API.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rol {
+    Admin,
+    Taquillero,
+}
+
+impl Rol {
+    /// Valor de la columna `usuarios.rol`.
+    pub fn como_str(self) -> &'static str {
+        match self {
+            Rol::Admin => "admin",
+            Rol::Taquillero => "taquillero",
+        }
+    }
+
+    pub fn desde_str(valor: &str) -> Option<Rol> {
+        match valor {
+            "admin" => Some(Rol::Admin),
+            "taquillero" => Some(Rol::Taquillero),
+            _ => None,
+        }
+    }
+}
+
+/// Usuario registrado. La clave nunca forma parte del modelo.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Usuario {
+    pub id: u32,
+    pub usuario: String,
+    pub rol: Rol,
+    /// Cine al que está atado; sin él, puede trabajar en cualquiera.
+    pub cine_id: Option<u32>,
+}
+
+/// Estructura para la creación de una nueva entrada.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, InputObject)]
+#[graphql(rename_fields = "snake_case")]
+pub struct CrearEntrada {
+    pub cliente_id: u32,
+    pub funcion_id: u32,
+    pub cantidad_entradas: u32,
+    /// Números de asiento, uno por entrada. Vacío compra entradas sin numerar.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[graphql(default)]
+    pub asientos: Vec<u32>,
+}
@@ -0,0 +1,884 @@
+//! Panel de administración en HTML (`/admin/panel`), para consultar, buscar, crear y editar
+//! entradas y funciones sin desplegar un front-end aparte.
+//!
+//! Las páginas se generan en el servidor con [`maud`] y usan los mismos servicios que la
+//! API. La sesión es el token de acceso de un administrador en una cookie `HttpOnly` con
+//! `SameSite=Strict`, para que otros sitios no puedan enviar los formularios en su nombre;
+//! al caducar se vuelve a iniciar sesión. El cine es el del administrador o, si es de toda
+//! la cadena, el [`crate::cines::CINE_PRINCIPAL`].
+
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::cookie::time::Duration;
+use actix_web::cookie::{Cookie, SameSite};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::LOCATION;
+use actix_web::http::StatusCode;
+use actix_web::middleware::{from_fn, Next};
+use actix_web::{web, Error, HttpMessage, HttpRequest, HttpResponse, ResponseError};
+use chrono::{NaiveDateTime, TimeZone};
+use futures_util::TryStreamExt;
+use maud::{html, Markup, PreEscaped, DOCTYPE};
+use mysql_async::Pool;
+use serde::Deserialize;
+
+use crate::auth::{comprobar_credenciales, emitir_token, verificar_token, Administrador};
+use crate::cache::CacheLecturas;
+use crate::cambios::CanalCambios;
+use crate::cines::CineActual;
+use crate::config::Config;
+use crate::db;
+use crate::db::eventos::Evento;
+use crate::error::ApiError;
+use crate::funciones;
+use crate::listado::{ConsultaListado, ParametrosBusqueda, ParametrosListado};
+use crate::models::{ActualizarEntrada, CrearEntrada, CrearFuncion, Entrada, Funcion, Rol};
+use crate::salas;
+use crate::servicio::{ErrorEntrada, ServicioEntradas};
+use crate::validacion::ErrorCampo;
+
+/// Prefijo de las páginas del panel.
+pub const RAIZ: &str = "/admin/panel";
+
+/// Cookie con el token de la sesión del panel.
+const COOKIE_SESION: &str = "panel_sesion";
+
+/// Formato de los horarios en las tablas.
+const FORMATO_HORARIO: &str = "%d/%m/%Y %H:%M";
+
+/// Formato de los campos `datetime-local`.
+const FORMATO_FORMULARIO: &str = "%Y-%m-%dT%H:%M";
+
+const ESTILO: &str = "body{font-family:system-ui,sans-serif;margin:0;color:#222}\
+nav{display:flex;gap:1rem;align-items:center;padding:.75rem 1.5rem;background:#222}\
+nav a{color:#fff;text-decoration:none}nav form{margin-left:auto}\
+main{padding:1.5rem;max-width:70rem}table{border-collapse:collapse;width:100%}\
+th,td{text-align:left;padding:.4rem .6rem;border-bottom:1px solid #ddd}\
+form.datos{display:grid;gap:.75rem;max-width:28rem}label{display:grid;gap:.25rem}\
+.aviso{padding:.6rem;background:#fde8e8;border:1px solid #e0a0a0}.error{color:#b00020}\
+.acciones{display:flex;gap:.5rem;margin:1rem 0}";
+
+/// Registra las páginas del panel. Va antes que las rutas sin versión, cuyo `/admin` se
+/// quedaría con todas las peticiones del prefijo.
+pub fn configurar(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope(RAIZ)
+            .route("/login", web::get().to(formulario_login))
+            .route("/login", web::post().to(iniciar_sesion))
+            .route("/logout", web::post().to(cerrar_sesion))
+            .service(
+                web::scope("")
+                    .wrap(from_fn(exigir_administrador))
+                    .route("", web::get().to(inicio))
+                    .route("/entradas", web::get().to(listar_entradas))
+                    .route("/entradas", web::post().to(crear_entrada))
+                    .route("/entradas/nueva", web::get().to(nueva_entrada))
+                    .route("/entradas/{id}", web::get().to(editar_entrada))
+                    .route("/entradas/{id}", web::post().to(actualizar_entrada))
+                    .route("/funciones", web::get().to(listar_funciones))
+                    .route("/funciones", web::post().to(crear_funcion))
+                    .route("/funciones/nueva", web::get().to(nueva_funcion))
+                    .route("/funciones/{id}", web::get().to(editar_funcion))
+                    .route("/funciones/{id}", web::post().to(actualizar_funcion)),
+            ),
+    );
+}
+
+/// Error de una página del panel, que se muestra como página en lugar de JSON.
+#[derive(Debug)]
+pub struct ErrorPanel(ApiError);
+
+impl std::fmt::Display for ErrorPanel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl ResponseError for ErrorPanel {
+    fn status_code(&self) -> StatusCode {
+        self.0.status_code()
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let contenido = html! {
+            p.aviso { (self.0) }
+            p { a href=(format!("{}/entradas", RAIZ)) { "Volver al panel" } }
+        };
+        documento(self.status_code(), pagina("Error", true, contenido))
+    }
+}
+
+impl From<ApiError> for ErrorPanel {
+    fn from(error: ApiError) -> Self {
+        ErrorPanel(error)
+    }
+}
+
+impl From<ErrorEntrada> for ErrorPanel {
+    fn from(error: ErrorEntrada) -> Self {
+        ErrorPanel(error.into())
+    }
+}
+
+/// Middleware que deja pasar sólo a los administradores con sesión en el panel, con su
+/// [`crate::auth::Sesion`] en la petición. Los demás van al inicio de sesión.
+async fn exigir_administrador<B: MessageBody>(
+    config: web::Data<Config>,
+    req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<EitherBody<B>>, Error> {
+    let sesion = req
+        .cookie(COOKIE_SESION)
+        .and_then(|cookie| verificar_token(&config.auth, cookie.value()).ok())
+        .filter(|sesion| sesion.rol == Rol::Admin);
+    match sesion {
+        Some(sesion) => {
+            req.extensions_mut().insert(sesion);
+            next.call(req).await.map(ServiceResponse::map_into_left_body)
+        }
+        None => Ok(req.into_response(redirigir(&format!("{}/login", RAIZ))).map_into_right_body()),
+    }
+}
+
+/// Respuesta 303 hacia `ruta`, la que sigue a un formulario enviado.
+fn redirigir(ruta: &str) -> HttpResponse {
+    HttpResponse::SeeOther().insert_header((LOCATION, ruta)).finish()
+}
+
+fn documento(estado: StatusCode, contenido: Markup) -> HttpResponse {
+    HttpResponse::build(estado).content_type("text/html; charset=utf-8").body(contenido.into_string())
+}
+
+/// Página completa con `titulo` y, si hay sesión, el menú.
+fn pagina(titulo: &str, con_menu: bool, contenido: Markup) -> Markup {
+    html! {
+        (DOCTYPE)
+        html lang="es" {
+            head {
+                meta charset="utf-8";
+                meta name="viewport" content="width=device-width, initial-scale=1";
+                title { (titulo) " · Panel de administración" }
+                style { (PreEscaped(ESTILO)) }
+            }
+            body {
+                @if con_menu {
+                    nav {
+                        a href=(format!("{}/entradas", RAIZ)) { "Entradas" }
+                        a href=(format!("{}/funciones", RAIZ)) { "Funciones" }
+                        form method="post" action=(format!("{}/logout", RAIZ)) { button { "Cerrar sesión" } }
+                    }
+                }
+                main {
+                    h1 { (titulo) }
+                    (contenido)
+                }
+            }
+        }
+    }
+}
+
+/// Mensaje de error del campo `nombre`, si tiene.
+fn error_de<'a>(errores: &'a [ErrorCampo], nombre: &str) -> Option<&'a str> {
+    errores.iter().find(|error| error.campo == nombre).map(|error| error.mensaje.as_str())
+}
+
+/// Campo de formulario con su etiqueta y su error.
+fn campo(etiqueta: &str, nombre: &str, control: Markup, errores: &[ErrorCampo]) -> Markup {
+    html! {
+        label {
+            (etiqueta)
+            (control)
+            @if let Some(mensaje) = error_de(errores, nombre) {
+                span.error { (mensaje) }
+            }
+        }
+    }
+}
+
+/// Mensaje general y errores por campo con que se vuelve a mostrar un formulario.
+#[derive(Debug, Default)]
+struct Problemas {
+    aviso: Option<String>,
+    campos: Vec<ErrorCampo>,
+}
+
+impl Problemas {
+    fn de(error: &ApiError) -> Problemas {
+        match error {
+            ApiError::CamposInvalidos(campos) => Problemas { aviso: None, campos: campos.clone() },
+            error => Problemas { aviso: Some(error.to_string()), campos: Vec::new() },
+        }
+    }
+}
+
+fn numero(campo: &'static str, valor: &str, errores: &mut Vec<ErrorCampo>) -> u32 {
+    valor.trim().parse().unwrap_or_else(|_| {
+        errores.push(ErrorCampo { campo: campo.into(), mensaje: "Debe ser un número entero positivo".to_string() });
+        0
+    })
+}
+
+// Sesión
+
+/// Datos del formulario de inicio de sesión.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct FormularioLogin {
+    pub usuario: String,
+    pub clave: String,
+}
+
+fn pagina_login(estado: StatusCode, usuario: &str, aviso: Option<&str>) -> HttpResponse {
+    let contenido = html! {
+        @if let Some(aviso) = aviso {
+            p.aviso { (aviso) }
+        }
+        form.datos method="post" action=(format!("{}/login", RAIZ)) {
+            label { "Usuario" input name="usuario" value=(usuario) required autofocus; }
+            label { "Clave" input type="password" name="clave" required; }
+            button { "Entrar" }
+        }
+    };
+    documento(estado, pagina("Iniciar sesión", false, contenido))
+}
+
+/// Página de inicio de sesión.
+pub async fn formulario_login() -> HttpResponse {
+    pagina_login(StatusCode::OK, "", None)
+}
+
+/// Comprueba las credenciales de un administrador y abre la sesión del panel.
+pub async fn iniciar_sesion(
+    req: HttpRequest,
+    pool: web::Data<Pool>,
+    config: web::Data<Config>,
+    datos: web::Form<FormularioLogin>,
+) -> HttpResponse {
+    let FormularioLogin { usuario, clave } = datos.into_inner();
+    let credenciales = async {
+        let mut conn = db::conectar(&pool).await.map_err(ApiError::conexion)?;
+        match comprobar_credenciales(&mut conn, &usuario, clave).await? {
+            (_, Rol::Admin, cine) => Ok(cine),
+            _ => Err(ApiError::Prohibido("Esta operación requiere el rol de administrador".to_string())),
+        }
+    };
+    let cine = match credenciales.await {
+        Ok(cine) => cine,
+        Err(error) => return pagina_login(error.status_code(), &usuario, Some(&error.to_string())),
+    };
+    let token = match emitir_token(&config.auth, &usuario, Rol::Admin, cine) {
+        Ok(token) => token,
+        Err(e) => {
+            log::error!("Error al firmar token: {:?}", e);
+            return pagina_login(StatusCode::INTERNAL_SERVER_ERROR, &usuario, Some("Error al emitir el token"));
+        }
+    };
+    let cookie = Cookie::build(COOKIE_SESION, token)
+        .path(RAIZ)
+        .http_only(true)
+        .same_site(SameSite::Strict)
+        .secure(req.connection_info().scheme() == "https")
+        .max_age(Duration::seconds(config.auth.duracion_token.as_secs() as i64))
+        .finish();
+    let mut respuesta = redirigir(&format!("{}/entradas", RAIZ));
+    let _ = respuesta.add_cookie(&cookie);
+    respuesta
+}
+
+/// Cierra la sesión del panel borrando su cookie.
+pub async fn cerrar_sesion() -> HttpResponse {
+    let mut cookie = Cookie::build(COOKIE_SESION, "").path(RAIZ).finish();
+    cookie.make_removal();
+    let mut respuesta = redirigir(&format!("{}/login", RAIZ));
+    let _ = respuesta.add_cookie(&cookie);
+    respuesta
+}
+
+/// El panel empieza en el listado de entradas.
+pub async fn inicio() -> HttpResponse {
+    redirigir(&format!("{}/entradas", RAIZ))
+}
+
+// Entradas
+
+/// Parámetros del listado de entradas del panel.
+#[derive(Debug, Default, Deserialize)]
+pub struct ParametrosPanel {
+    /// Parte del nombre del cliente o del título de la función, o del título en funciones.
+    pub q: Option<String>,
+    pub page: Option<u32>,
+}
+
+impl ParametrosPanel {
+    fn busqueda(&self) -> Option<&str> {
+        self.q.as_deref().map(str::trim).filter(|texto| !texto.is_empty())
+    }
+}
+
+/// Buscador de la página en `accion`.
+fn buscador(accion: &str, texto: Option<&str>, indicacion: &str) -> Markup {
+    html! {
+        form.acciones method="get" action=(accion) {
+            input type="search" name="q" value=[texto] placeholder=(indicacion);
+            button { "Buscar" }
+            a href=(format!("{}/nueva", accion)) { "Nueva" }
+        }
+    }
+}
+
+/// Entradas del cine, las últimas primero, o las que coinciden con la búsqueda.
+pub async fn listar_entradas(
+    CineActual(cine): CineActual,
+    servicio: web::Data<ServicioEntradas>,
+    query: web::Query<ParametrosPanel>,
+) -> Result<HttpResponse, ErrorPanel> {
+    let texto = query.busqueda();
+    let consulta = match texto {
+        Some(texto) => ConsultaListado::busqueda(&ParametrosBusqueda {
+            q: texto.to_string(),
+            page: query.page,
+            per_page: None,
+            fields: None,
+        }),
+        None => ConsultaListado::desde_parametros(&ParametrosListado {
+            page: query.page,
+            order: Some("desc".to_string()),
+            ..Default::default()
+        }),
+    }
+    .map_err(ApiError::Validacion)?;
+    let pagina_actual = servicio.listar(cine, &consulta).await?;
+    let entradas: Vec<Entrada> = pagina_actual.filas.try_collect().await?;
+    let paginas = pagina_actual.total.div_ceil(u64::from(consulta.por_pagina)).max(1);
+
+    let accion = format!("{}/entradas", RAIZ);
+    let contenido = html! {
+        (buscador(&accion, texto, "Cliente o película"))
+        table {
+            thead { tr { th { "ID" } th { "Cliente" } th { "Función" } th { "Sala" } th { "Cantidad" } th { "Total" } th { "Estado" } } }
+            tbody {
+                @for entrada in &entradas {
+                    @let id = entrada.id.unwrap_or_default();
+                    tr {
+                        td { a href=(format!("{}/{}", accion, id)) { (id) } }
+                        td { (entrada.cliente.nombre) " (" (entrada.cliente.numero_cedula) ")" }
+                        td { (entrada.funcion.titulo) ", " (entrada.funcion.horario.format(FORMATO_HORARIO)) }
+                        td { (entrada.funcion.sala.nombre) }
+                        td { (entrada.cantidad_entradas) }
+                        td { (format!("{:.2}", entrada.total)) }
+                        td { (entrada.estado.como_str()) }
+                    }
+                }
+            }
+        }
+        @if entradas.is_empty() {
+            p { "No hay entradas." }
+        }
+        (paginacion(&accion, texto, consulta.pagina, paginas))
+    };
+    Ok(documento(StatusCode::OK, pagina("Entradas", true, contenido)))
+}
+
+/// Enlaces a la página anterior y a la siguiente, conservando la búsqueda.
+fn paginacion(accion: &str, texto: Option<&str>, pagina: u32, paginas: u64) -> Markup {
+    let boton = |destino: u32, etiqueta: &str| {
+        html! {
+            form method="get" action=(accion) {
+                @if let Some(texto) = texto {
+                    input type="hidden" name="q" value=(texto);
+                }
+                input type="hidden" name="page" value=(destino);
+                button { (etiqueta) }
+            }
+        }
+    };
+    html! {
+        div.acciones {
+            @if pagina > 1 {
+                (boton(pagina - 1, "Anterior"))
+            }
+            span { "Página " (pagina) " de " (paginas) }
+            @if u64::from(pagina) < paginas {
+                (boton(pagina + 1, "Siguiente"))
+            }
+        }
+    }
+}
+
+/// Datos del formulario de una entrada, tal como se escribieron.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct FormularioEntrada {
+    pub cliente_id: String,
+    pub funcion_id: String,
+    pub cantidad_entradas: String,
+    /// Números de asiento separados por espacios; sólo al crearla.
+    pub asientos: String,
+    /// Versión de la entrada que se editó; sólo al actualizarla.
+    pub version: String,
+}
+
+impl FormularioEntrada {
+    fn desde_entrada(entrada: &Entrada) -> FormularioEntrada {
+        FormularioEntrada {
+            cliente_id: entrada.cliente.id.to_string(),
+            funcion_id: entrada.funcion.id.to_string(),
+            cantidad_entradas: entrada.cantidad_entradas.to_string(),
+            asientos: String::new(),
+            version: entrada.version.to_string(),
+        }
+    }
+
+    fn leer(&self) -> Result<CrearEntrada, ApiError> {
+        let mut errores = Vec::new();
+        let cliente_id = numero("cliente_id", &self.cliente_id, &mut errores);
+        let funcion_id = numero("funcion_id", &self.funcion_id, &mut errores);
+        let cantidad_entradas = numero("cantidad_entradas", &self.cantidad_entradas, &mut errores);
+        let asientos = self.asientos.split_whitespace().map(str::parse).collect::<Result<Vec<u32>, _>>();
+        let asientos = asientos.unwrap_or_else(|_| {
+            errores.push(ErrorCampo {
+                campo: "asientos".into(),
+                mensaje: "Deben ser números de asiento separados por espacios".to_string(),
+            });
+            Vec::new()
+        });
+        if !errores.is_empty() {
+            return Err(ApiError::CamposInvalidos(errores));
+        }
+        Ok(CrearEntrada { cliente_id, funcion_id, cantidad_entradas, asientos })
+    }
+}
+
+/// Formulario de una entrada nueva o, con `entrada`, de la existente.
+fn formulario_entrada(
+    estado: StatusCode,
+    entrada: Option<&Entrada>,
+    datos: &FormularioEntrada,
+    problemas: &Problemas,
+) -> HttpResponse {
+    let (titulo, accion) = match entrada {
+        Some(entrada) => (
+            format!("Entrada {}", entrada.id.unwrap_or_default()),
+            format!("{}/entradas/{}", RAIZ, entrada.id.unwrap_or_default()),
+        ),
+        None => ("Nueva entrada".to_string(), format!("{}/entradas", RAIZ)),
+    };
+    let errores = &problemas.campos;
+    let contenido = html! {
+        @if let Some(aviso) = &problemas.aviso {
+            p.aviso { (aviso) }
+        }
+        @if let Some(entrada) = entrada {
+            p {
+                (entrada.cliente.nombre) " · " (entrada.funcion.titulo) ", "
+                (entrada.funcion.horario.format(FORMATO_HORARIO)) " · " (entrada.estado.como_str())
+                " · total " (format!("{:.2}", entrada.total))
+            }
+        }
+        form.datos method="post" action=(accion) {
+            (campo("ID del cliente", "cliente_id", html! {
+                input type="number" name="cliente_id" min="1" value=(datos.cliente_id) required;
+            }, errores))
+            (campo("ID de la función", "funcion_id", html! {
+                input type="number" name="funcion_id" min="1" value=(datos.funcion_id) required;
+            }, errores))
+            (campo("Cantidad", "cantidad_entradas", html! {
+                input type="number" name="cantidad_entradas" min="1" value=(datos.cantidad_entradas) required;
+            }, errores))
+            @if entrada.is_none() {
+                (campo("Asientos (opcional, separados por espacios)", "asientos", html! {
+                    input name="asientos" value=(datos.asientos);
+                }, errores))
+            } @else {
+                input type="hidden" name="version" value=(datos.version);
+            }
+            button { "Guardar" }
+        }
+    };
+    documento(estado, pagina(&titulo, true, contenido))
+}
+
+/// Formulario de una entrada nueva.
+pub async fn nueva_entrada() -> HttpResponse {
+    formulario_entrada(StatusCode::OK, None, &FormularioEntrada::default(), &Problemas::default())
+}
+
+/// Vende una entrada con los datos del formulario y lleva a su página.
+pub async fn crear_entrada(
+    Administrador(admin): Administrador,
+    CineActual(cine): CineActual,
+    servicio: web::Data<ServicioEntradas>,
+    cambios: web::Data<CanalCambios>,
+    datos: web::Form<FormularioEntrada>,
+) -> HttpResponse {
+    let creada = match datos.leer() {
+        Ok(entrada) => servicio.crear(cine, &entrada, &admin.sub).await.map_err(ApiError::from),
+        Err(error) => Err(error),
+    };
+    match creada {
+        Ok(id) => {
+            cambios.publicar_entrada(cine, Evento::Creada, id);
+            redirigir(&format!("{}/entradas/{}", RAIZ, id))
+        }
+        Err(error) => formulario_entrada(error.status_code(), None, &datos, &Problemas::de(&error)),
+    }
+}
+
+/// Formulario con los datos de una entrada.
+pub async fn editar_entrada(
+    CineActual(cine): CineActual,
+    servicio: web::Data<ServicioEntradas>,
+    id: web::Path<u32>,
+) -> Result<HttpResponse, ErrorPanel> {
+    let entrada = servicio.obtener(cine, id.into_inner()).await?;
+    let datos = FormularioEntrada::desde_entrada(&entrada);
+    Ok(formulario_entrada(StatusCode::OK, Some(&entrada), &datos, &Problemas::default()))
+}
+
+/// Guarda los cambios del formulario si nadie cambió la entrada mientras tanto.
+pub async fn actualizar_entrada(
+    Administrador(admin): Administrador,
+    CineActual(cine): CineActual,
+    servicio: web::Data<ServicioEntradas>,
+    cambios: web::Data<CanalCambios>,
+    id: web::Path<u32>,
+    datos: web::Form<FormularioEntrada>,
+) -> Result<HttpResponse, ErrorPanel> {
+    let id = id.into_inner();
+    let entrada = servicio.obtener(cine, id).await?;
+    let cambio = datos.leer().map(|nueva| ActualizarEntrada {
+        cliente_id: Some(nueva.cliente_id),
+        funcion_id: Some(nueva.funcion_id),
+        cantidad_entradas: Some(nueva.cantidad_entradas),
+    });
+    // Sin la versión que se editó no se puede saber si otro la cambió mientras tanto.
+    let version = datos.version.trim().parse().map_err(|_| {
+        ApiError::Validacion("Falta la versión de la entrada; vuelve a abrir el formulario".to_string())
+    });
+    let resultado = match (cambio, version) {
+        (Ok(cambio), Ok(version)) => match servicio.actualizar(cine, id, &cambio, Some(version), &admin.sub).await {
+            Ok(()) => Ok(true),
+            // Sin cambios efectivos no hay nada que guardar, siempre que la entrada siga ahí.
+            Err(ErrorEntrada::NoEncontrada) => servicio.obtener(cine, id).await.map(|_| false).map_err(ApiError::from),
+            Err(e) => Err(ApiError::from(e)),
+        },
+        (Err(error), _) | (_, Err(error)) => Err(error),
+    };
+    Ok(match resultado {
+        Ok(cambiada) => {
+            if cambiada {
+                cambios.publicar_entrada(cine, Evento::Actualizada, id);
+            }
+            redirigir(&format!("{}/entradas/{}", RAIZ, id))
+        }
+        Err(error) => formulario_entrada(error.status_code(), Some(&entrada), &datos, &Problemas::de(&error)),
+    })
+}
+
+// Funciones
+
+/// Funciones del cine, o las que tienen la búsqueda en el título.
+pub async fn listar_funciones(
+    CineActual(cine): CineActual,
+    pool: web::Data<Pool>,
+    config: web::Data<Config>,
+    query: web::Query<ParametrosPanel>,
+) -> Result<HttpResponse, ErrorPanel> {
+    let texto = query.busqueda();
+    let mut conn = db::conectar(&pool).await.map_err(ApiError::conexion)?;
+    let lista = funciones::buscar_funciones(&mut conn, cine, texto)
+        .await
+        .map_err(ApiError::base_datos("Error al obtener funciones"))?;
+
+    let accion = format!("{}/funciones", RAIZ);
+    let contenido = html! {
+        (buscador(&accion, texto, "Película"))
+        table {
+            thead { tr { th { "ID" } th { "Película" } th { "Sala" } th { "Horario" } th { "Precio" } } }
+            tbody {
+                @for funcion in lista.into_iter().map(|funcion| funcion.en_zona(config.zona_horaria)) {
+                    tr {
+                        td { a href=(format!("{}/{}", accion, funcion.id)) { (funcion.id) } }
+                        td { (funcion.titulo) }
+                        td { (funcion.sala.nombre) }
+                        td { (funcion.horario.format(FORMATO_HORARIO)) }
+                        td { (format!("{:.2}", funcion.precio)) }
+                    }
+                }
+            }
+        }
+    };
+    Ok(documento(StatusCode::OK, pagina("Funciones", true, contenido)))
+}
+
+/// Datos del formulario de una función, tal como se escribieron.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct FormularioFuncion {
+    pub titulo: String,
+    pub sala_id: String,
+    /// `AAAA-MM-DDTHH:MM` en la zona horaria configurada.
+    pub horario: String,
+    pub precio: String,
+}
+
+impl FormularioFuncion {
+    fn desde_funcion(funcion: &Funcion) -> FormularioFuncion {
+        FormularioFuncion {
+            titulo: funcion.titulo.clone(),
+            sala_id: funcion.sala.id.to_string(),
+            horario: funcion.horario.format(FORMATO_FORMULARIO).to_string(),
+            precio: funcion.precio.to_string(),
+        }
+    }
+
+    fn leer(&self, config: &Config) -> Result<CrearFuncion, ApiError> {
+        let mut errores = Vec::new();
+        let sala_id = numero("sala_id", &self.sala_id, &mut errores);
+        let horario = NaiveDateTime::parse_from_str(self.horario.trim(), FORMATO_FORMULARIO)
+            .ok()
+            .and_then(|horario| config.zona_horaria.from_local_datetime(&horario).single());
+        if horario.is_none() {
+            errores.push(ErrorCampo {
+                campo: "horario".into(),
+                mensaje: "Debe ser una fecha y hora AAAA-MM-DDTHH:MM".to_string(),
+            });
+        }
+        let precio = self.precio.trim().parse().unwrap_or_else(|_| {
+            errores.push(ErrorCampo {
+                campo: "precio".into(),
+                mensaje: "Debe ser un importe mayor o igual que 0".to_string(),
+            });
+            0.0
+        });
+        match horario {
+            Some(horario) if errores.is_empty() => {
+                let datos = CrearFuncion { titulo: self.titulo.clone(), sala_id, horario, precio };
+                funciones::validar(&datos, config)?;
+                Ok(datos)
+            }
+            _ => Err(ApiError::CamposInvalidos(errores)),
+        }
+    }
+}
+
+/// Formulario de una función nueva o, con `id`, de la existente, con las salas del cine.
+async fn formulario_funcion(
+    pool: &Pool,
+    cine: u32,
+    estado: StatusCode,
+    id: Option<u32>,
+    datos: &FormularioFuncion,
+    problemas: &Problemas,
+) -> Result<HttpResponse, ErrorPanel> {
+    let mut conn = db::conectar(pool).await.map_err(ApiError::conexion)?;
+    let salas = salas::leer_salas(&mut conn, cine).await.map_err(ApiError::base_datos("Error al obtener salas"))?;
+    let (titulo, accion) = match id {
+        Some(id) => (format!("Función {}", id), format!("{}/funciones/{}", RAIZ, id)),
+        None => ("Nueva función".to_string(), format!("{}/funciones", RAIZ)),
+    };
+    let errores = &problemas.campos;
+    let contenido = html! {
+        @if let Some(aviso) = &problemas.aviso {
+            p.aviso { (aviso) }
+        }
+        form.datos method="post" action=(accion) {
+            (campo("Película", "titulo", html! {
+                input name="titulo" value=(datos.titulo) required;
+            }, errores))
+            (campo("Sala", "sala_id", html! {
+                select name="sala_id" required {
+                    @for sala in &salas {
+                        option value=(sala.id) selected[sala.id.to_string() == datos.sala_id] {
+                            (sala.nombre) " (" (sala.capacidad) " asientos)"
+                        }
+                    }
+                }
+            }, errores))
+            (campo("Horario", "horario", html! {
+                input type="datetime-local" name="horario" value=(datos.horario) required;
+            }, errores))
+            (campo("Precio", "precio", html! {
+                input type="number" name="precio" min="0" step="0.01" value=(datos.precio) required;
+            }, errores))
+            button { "Guardar" }
+        }
+    };
+    Ok(documento(estado, pagina(&titulo, true, contenido)))
+}
+
+/// Formulario de una función nueva.
+pub async fn nueva_funcion(CineActual(cine): CineActual, pool: web::Data<Pool>) -> Result<HttpResponse, ErrorPanel> {
+    formulario_funcion(&pool, cine, StatusCode::OK, None, &FormularioFuncion::default(), &Problemas::default()).await
+}
+
+/// Da de alta una función con los datos del formulario y lleva a su página.
+pub async fn crear_funcion(
+    CineActual(cine): CineActual,
+    pool: web::Data<Pool>,
+    cache: web::Data<CacheLecturas>,
+    config: web::Data<Config>,
+    datos: web::Form<FormularioFuncion>,
+) -> Result<HttpResponse, ErrorPanel> {
+    let creada = match datos.leer(&config) {
+        Ok(funcion) => {
+            let mut conn = db::conectar(&pool).await.map_err(ApiError::conexion)?;
+            funciones::insertar_funcion(&mut conn, &cache, cine, &funcion).await
+        }
+        Err(error) => Err(error),
+    };
+    match creada {
+        Ok(funcion) => Ok(redirigir(&format!("{}/funciones/{}", RAIZ, funcion.id))),
+        Err(error) => formulario_funcion(&pool, cine, error.status_code(), None, &datos, &Problemas::de(&error)).await,
+    }
+}
+
+/// Formulario con los datos de una función.
+pub async fn editar_funcion(
+    CineActual(cine): CineActual,
+    pool: web::Data<Pool>,
+    config: web::Data<Config>,
+    id: web::Path<u32>,
+) -> Result<HttpResponse, ErrorPanel> {
+    let id = id.into_inner();
+    let mut conn = db::conectar(&pool).await.map_err(ApiError::conexion)?;
+    let funcion = funciones::leer_funcion(&mut conn, cine, id)
+        .await
+        .map_err(ApiError::base_datos("Error al obtener función"))?
+        .ok_or_else(|| ApiError::NoEncontrado("Función no encontrada".to_string()))?
+        .en_zona(config.zona_horaria);
+    let datos = FormularioFuncion::desde_funcion(&funcion);
+    formulario_funcion(&pool, cine, StatusCode::OK, Some(id), &datos, &Problemas::default()).await
+}
+
+/// Reemplaza los datos de una función con los del formulario.
+pub async fn actualizar_funcion(
+    CineActual(cine): CineActual,
+    pool: web::Data<Pool>,
+    cache: web::Data<CacheLecturas>,
+    config: web::Data<Config>,
+    id: web::Path<u32>,
+    datos: web::Form<FormularioFuncion>,
+) -> Result<HttpResponse, ErrorPanel> {
+    let id = id.into_inner();
+    let guardada = match datos.leer(&config) {
+        Ok(funcion) => {
+            let mut conn = db::conectar(&pool).await.map_err(ApiError::conexion)?;
+            funciones::guardar_funcion(&mut conn, &cache, cine, id, &funcion).await
+        }
+        Err(error) => Err(error),
+    };
+    match guardada {
+        Ok(_) => Ok(redirigir(&format!("{}/funciones/{}", RAIZ, id))),
+        Err(error @ ApiError::NoEncontrado(_)) => Err(error.into()),
+        Err(error) => formulario_funcion(&pool, cine, error.status_code(), Some(id), &datos, &Problemas::de(&error)).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use actix_web::test;
+
+    use super::*;
+    use crate::db::memoria::RepositorioMemoria;
+    use crate::db::obtener_pool_db;
+    use crate::{create_app, Estado};
+
+    /// La aplicación sobre el repositorio en memoria; las páginas de entradas no usan la pool.
+    fn estado() -> Estado {
+        let mut config = Config::new("mysql://root@127.0.0.1:1/pruebas");
+        config.auth.secreto = "secreto-de-pruebas".to_string();
+        let pool = obtener_pool_db(&config).unwrap();
+        let mut estado = Estado::new(config, pool);
+        estado.entradas = Arc::new(ServicioEntradas::new(
+            Arc::new(RepositorioMemoria::default()),
+            estado.config.reglas_validacion(),
+            estado.config.zona_horaria,
+        ));
+        estado
+    }
+
+    fn cookie(estado: &Estado, rol: Rol) -> Cookie<'static> {
+        Cookie::new(COOKIE_SESION, emitir_token(&estado.config.auth, "pruebas", rol, None).unwrap())
+    }
+
+    #[actix_web::test]
+    async fn sin_sesion_de_administrador_lleva_al_login() {
+        let estado = estado();
+        let taquillero = cookie(&estado, Rol::Taquillero);
+        let app = test::init_service(create_app(estado)).await;
+
+        let res = test::call_service(&app, test::TestRequest::get().uri("/admin/panel/entradas").to_request()).await;
+        assert_eq!(res.status(), StatusCode::SEE_OTHER);
+        assert_eq!(res.headers().get(LOCATION).unwrap(), "/admin/panel/login");
+
+        let req = test::TestRequest::get().uri("/admin/panel/entradas").cookie(taquillero).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::SEE_OTHER);
+
+        let res = test::call_service(&app, test::TestRequest::get().uri("/admin/panel/login").to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let cuerpo = test::read_body(res).await;
+        assert!(String::from_utf8_lossy(&cuerpo).contains("name=\"clave\""));
+    }
+
+    #[actix_web::test]
+    async fn crea_y_edita_entradas_desde_el_formulario() {
+        let estado = estado();
+        let admin = cookie(&estado, Rol::Admin);
+        let app = test::init_service(create_app(estado)).await;
+
+        let req = test::TestRequest::post()
+            .uri("/admin/panel/entradas")
+            .cookie(admin.clone())
+            .set_form([("cliente_id", "1"), ("funcion_id", "1"), ("cantidad_entradas", "dos")])
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let cuerpo = test::read_body(res).await;
+        assert!(String::from_utf8_lossy(&cuerpo).contains("Debe ser un número entero positivo"));
+
+        let req = test::TestRequest::post()
+            .uri("/admin/panel/entradas")
+            .cookie(admin.clone())
+            .set_form([("cliente_id", "1"), ("funcion_id", "1"), ("cantidad_entradas", "2")])
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::SEE_OTHER);
+        assert_eq!(res.headers().get(LOCATION).unwrap(), "/admin/panel/entradas/1");
+
+        let req = test::TestRequest::post()
+            .uri("/admin/panel/entradas/1")
+            .cookie(admin.clone())
+            .set_form([("cliente_id", "1"), ("funcion_id", "1"), ("cantidad_entradas", "3"), ("version", "1")])
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::SEE_OTHER);
+
+        // Sin versión, o con una vieja, no se guarda; reenviar lo mismo no es un error.
+        for (version, estado) in [
+            ("", StatusCode::BAD_REQUEST),
+            ("1", StatusCode::PRECONDITION_FAILED),
+            ("2", StatusCode::SEE_OTHER),
+        ] {
+            let req = test::TestRequest::post()
+                .uri("/admin/panel/entradas/1")
+                .cookie(admin.clone())
+                .set_form([("cliente_id", "1"), ("funcion_id", "1"), ("cantidad_entradas", "3"), ("version", version)])
+                .to_request();
+            assert_eq!(test::call_service(&app, req).await.status(), estado);
+        }
+
+        let req = test::TestRequest::get().uri("/admin/panel/entradas/1").cookie(admin.clone()).to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let cuerpo = test::read_body(res).await;
+        assert!(String::from_utf8_lossy(&cuerpo).contains("name=\"cantidad_entradas\" min=\"1\" value=\"3\""));
+
+        let req = test::TestRequest::get().uri("/admin/panel/entradas").cookie(admin.clone()).to_request();
+        let cuerpo = test::read_body(test::call_service(&app, req).await).await;
+        assert!(String::from_utf8_lossy(&cuerpo).contains("href=\"/admin/panel/entradas/1\""));
+
+        let req = test::TestRequest::get().uri("/admin/panel/entradas/9").cookie(admin).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,475 @@
+//! Pósters de las funciones. `POST /funciones/{id}/poster` recibe la imagen en el campo
+//! `archivo` de un formulario multipart y `GET /static/posters/{archivo}` la sirve, sin
+//! autenticación, con la URL que muestra la función en `poster`.
+//!
+//! Se aceptan PNG, JPEG y WebP de hasta [`ConfigPosters::bytes_maximos`], reconocidos por
+//! su contenido y no sólo por el tipo declarado. Se guardan en un directorio local o, con
+//! [`ConfigPosters::s3_endpoint`], en un bucket compatible con S3 (AWS, MinIO...). El
+//! nombre lleva un resumen del contenido, así que cada versión tiene su propia URL y se
+//! puede cachear sin caducidad.
+
+use std::path::PathBuf;
+
+use actix_multipart::{Multipart, MultipartError};
+use actix_web::http::header::{CacheControl, CacheDirective, ContentType};
+use actix_web::{web, HttpResponse};
+use chrono::Utc;
+use futures_util::StreamExt;
+use hmac::{Hmac, Mac};
+use mysql_async::{prelude::*, Pool};
+use reqwest::{Client, Method, StatusCode, Url};
+use sha2::{Digest, Sha256};
+
+use crate::auth::Administrador;
+use crate::cache::CacheLecturas;
+use crate::cines::CineActual;
+use crate::config::Config;
+use crate::db;
+use crate::error::ApiError;
+use crate::funciones::leer_funcion;
+use crate::models::Funcion;
+use crate::respuesta::ApiResponse;
+
+/// Ruta bajo la que se sirven los pósters.
+pub const RUTA: &str = "/static/posters";
+
+/// Dónde se guardan los pósters y cuánto pueden ocupar.
+#[derive(Debug, Clone)]
+pub struct ConfigPosters {
+    /// Directorio en el que se guardan si no hay `s3_endpoint`. Se crea si no existe.
+    pub directorio: PathBuf,
+    /// Tamaño máximo de una imagen, en bytes.
+    pub bytes_maximos: usize,
+    /// URL del servicio compatible con S3, como `https://s3.us-east-1.amazonaws.com`. Con
+    /// ella los pósters van a `s3_bucket`, bajo el prefijo `posters/`.
+    pub s3_endpoint: Option<String>,
+    pub s3_bucket: Option<String>,
+    pub s3_region: String,
+    pub s3_clave_acceso: Option<String>,
+    pub s3_clave_secreta: Option<String>,
+}
+
+impl Default for ConfigPosters {
+    fn default() -> Self {
+        ConfigPosters {
+            directorio: PathBuf::from("posters"),
+            bytes_maximos: 2 * 1024 * 1024,
+            s3_endpoint: None,
+            s3_bucket: None,
+            s3_region: "us-east-1".to_string(),
+            s3_clave_acceso: None,
+            s3_clave_secreta: None,
+        }
+    }
+}
+
+impl ConfigPosters {
+    /// Comprueba que S3, si se usa, tenga la URL, el bucket y las claves.
+    pub fn validar(&self) -> Result<(), String> {
+        let Some(endpoint) = &self.s3_endpoint else {
+            return Ok(());
+        };
+        if Url::parse(endpoint).map_or(true, |url| !matches!(url.scheme(), "http" | "https")) {
+            return Err(format!("POSTERS_S3_ENDPOINT debe ser una URL http o https: {}", endpoint));
+        }
+        if self.s3_bucket.is_none() || self.s3_clave_acceso.is_none() || self.s3_clave_secreta.is_none() {
+            return Err(
+                "POSTERS_S3_ENDPOINT requiere POSTERS_S3_BUCKET, POSTERS_S3_CLAVE_ACCESO y POSTERS_S3_CLAVE_SECRETA"
+                    .to_string(),
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Formato de imagen admitido.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Formato {
+    Png,
+    Jpeg,
+    Webp,
+}
+
+impl Formato {
+    const TODOS: [Formato; 3] = [Formato::Png, Formato::Jpeg, Formato::Webp];
+
+    /// Formato de la imagen según sus primeros bytes.
+    pub fn detectar(contenido: &[u8]) -> Option<Formato> {
+        match contenido {
+            [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n', ..] => Some(Formato::Png),
+            [0xff, 0xd8, 0xff, ..] => Some(Formato::Jpeg),
+            [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => Some(Formato::Webp),
+            _ => None,
+        }
+    }
+
+    pub fn tipo_mime(self) -> &'static str {
+        match self {
+            Formato::Png => "image/png",
+            Formato::Jpeg => "image/jpeg",
+            Formato::Webp => "image/webp",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Formato::Png => "png",
+            Formato::Jpeg => "jpg",
+            Formato::Webp => "webp",
+        }
+    }
+
+    /// Formato de un nombre de póster, si tiene la forma que le da [`nombre_archivo`]:
+    /// `{funcion}-{resumen}.{extension}`. Cualquier otro nombre, incluidas las rutas, no
+    /// es un póster.
+    pub fn de_nombre(nombre: &str) -> Option<Formato> {
+        let (base, extension) = nombre.split_once('.')?;
+        let (funcion, resumen) = base.split_once('-')?;
+        let valido = !funcion.is_empty()
+            && funcion.bytes().all(|b| b.is_ascii_digit())
+            && resumen.len() == 16
+            && resumen.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'));
+        Formato::TODOS
+            .into_iter()
+            .find(|formato| valido && formato.extension() == extension)
+    }
+}
+
+/// Nombre con el que se guarda el póster `contenido` de la función `funcion`.
+pub fn nombre_archivo(funcion: u32, formato: Formato, contenido: &[u8]) -> String {
+    format!("{}-{}.{}", funcion, &hexadecimal(&Sha256::digest(contenido))[..16], formato.extension())
+}
+
+/// URL pública de un póster guardado.
+pub fn url(nombre: &str) -> String {
+    format!("{}/{}", RUTA, nombre)
+}
+
+fn hexadecimal(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Almacén de los pósters, creado con [`ConfigPosters`].
+pub struct Posters {
+    almacen: Almacen,
+    bytes_maximos: usize,
+}
+
+enum Almacen {
+    Directorio(PathBuf),
+    S3(BucketS3),
+}
+
+impl Posters {
+    /// Almacén de la configuración, que ya pasó [`ConfigPosters::validar`].
+    pub fn new(config: &ConfigPosters) -> Posters {
+        let almacen = match (&config.s3_endpoint, &config.s3_bucket, &config.s3_clave_acceso, &config.s3_clave_secreta) {
+            (Some(endpoint), Some(bucket), Some(clave_acceso), Some(clave_secreta)) => Almacen::S3(BucketS3 {
+                cliente: Client::new(),
+                endpoint: endpoint.trim_end_matches('/').to_string(),
+                bucket: bucket.clone(),
+                region: config.s3_region.clone(),
+                clave_acceso: clave_acceso.clone(),
+                clave_secreta: clave_secreta.clone(),
+            }),
+            _ => Almacen::Directorio(config.directorio.clone()),
+        };
+        Posters { almacen, bytes_maximos: config.bytes_maximos }
+    }
+
+    async fn guardar(&self, nombre: &str, formato: Formato, contenido: Vec<u8>) -> Result<(), String> {
+        match &self.almacen {
+            Almacen::Directorio(directorio) => {
+                tokio::fs::create_dir_all(directorio).await.map_err(|e| e.to_string())?;
+                tokio::fs::write(directorio.join(nombre), contenido).await.map_err(|e| e.to_string())
+            }
+            Almacen::S3(bucket) => bucket.peticion(Method::PUT, nombre, contenido, Some(formato.tipo_mime())).await.map(|_| ()),
+        }
+    }
+
+    /// Contenido del póster `nombre`, o `None` si no existe.
+    async fn leer(&self, nombre: &str) -> Result<Option<Vec<u8>>, String> {
+        match &self.almacen {
+            Almacen::Directorio(directorio) => match tokio::fs::read(directorio.join(nombre)).await {
+                Ok(contenido) => Ok(Some(contenido)),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(e.to_string()),
+            },
+            Almacen::S3(bucket) => match bucket.peticion(Method::GET, nombre, Vec::new(), None).await? {
+                Some(respuesta) => respuesta.bytes().await.map(|bytes| Some(bytes.to_vec())).map_err(|e| e.to_string()),
+                None => Ok(None),
+            },
+        }
+    }
+
+    async fn eliminar(&self, nombre: &str) -> Result<(), String> {
+        match &self.almacen {
+            Almacen::Directorio(directorio) => match tokio::fs::remove_file(directorio.join(nombre)).await {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.to_string()),
+                _ => Ok(()),
+            },
+            Almacen::S3(bucket) => bucket.peticion(Method::DELETE, nombre, Vec::new(), None).await.map(|_| ()),
+        }
+    }
+}
+
+/// Bucket compatible con S3, con las peticiones firmadas con AWS Signature Version 4.
+struct BucketS3 {
+    cliente: Client,
+    endpoint: String,
+    bucket: String,
+    region: String,
+    clave_acceso: String,
+    clave_secreta: String,
+}
+
+impl BucketS3 {
+    /// Envía una petición sobre el objeto `posters/{nombre}`, con la URL en estilo de ruta
+    /// (`{endpoint}/{bucket}/posters/{nombre}`), que admiten todos los servicios
+    /// compatibles. Devuelve `None` si el objeto no existe.
+    async fn peticion(
+        &self,
+        metodo: Method,
+        nombre: &str,
+        cuerpo: Vec<u8>,
+        tipo: Option<&str>,
+    ) -> Result<Option<reqwest::Response>, String> {
+        let url = Url::parse(&format!("{}/{}/posters/{}", self.endpoint, self.bucket, nombre)).map_err(|e| e.to_string())?;
+        let firma = firmar_s3(&self.clave_acceso, &self.clave_secreta, &self.region, metodo.as_str(), &url, &cuerpo, Utc::now());
+        let mut peticion = self
+            .cliente
+            .request(metodo, url)
+            .header("x-amz-date", firma.fecha)
+            .header("x-amz-content-sha256", firma.resumen_cuerpo)
+            .header("authorization", firma.autorizacion);
+        if let Some(tipo) = tipo {
+            peticion = peticion.header("content-type", tipo);
+        }
+        let respuesta = peticion.body(cuerpo).send().await.map_err(|e| e.to_string())?;
+        match respuesta.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            estado if estado.is_success() => Ok(Some(respuesta)),
+            estado => Err(format!("S3 respondió {}: {}", estado, respuesta.text().await.unwrap_or_default())),
+        }
+    }
+}
+
+/// Cabeceras con las que se firma una petición a S3.
+struct FirmaS3 {
+    fecha: String,
+    resumen_cuerpo: String,
+    autorizacion: String,
+}
+
+/// Firma una petición sin parámetros de consulta con AWS Signature Version 4, cubriendo
+/// el host, la fecha y el resumen del cuerpo.
+fn firmar_s3(
+    clave_acceso: &str,
+    clave_secreta: &str,
+    region: &str,
+    metodo: &str,
+    url: &Url,
+    cuerpo: &[u8],
+    ahora: chrono::DateTime<Utc>,
+) -> FirmaS3 {
+    let fecha = ahora.format("%Y%m%dT%H%M%SZ").to_string();
+    let dia = ahora.format("%Y%m%d").to_string();
+    let resumen_cuerpo = hexadecimal(&Sha256::digest(cuerpo));
+    let host = match url.port() {
+        Some(puerto) => format!("{}:{}", url.host_str().unwrap_or_default(), puerto),
+        None => url.host_str().unwrap_or_default().to_string(),
+    };
+    let cabeceras = "host;x-amz-content-sha256;x-amz-date";
+    let peticion_canonica = format!(
+        "{}\n{}\n\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+        metodo,
+        url.path(),
+        host,
+        resumen_cuerpo,
+        fecha,
+        cabeceras,
+        resumen_cuerpo
+    );
+    let ambito = format!("{}/{}/s3/aws4_request", dia, region);
+    let texto = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        fecha,
+        ambito,
+        hexadecimal(&Sha256::digest(peticion_canonica.as_bytes()))
+    );
+    let clave = [region, "s3", "aws4_request"]
+        .iter()
+        .fold(hmac(format!("AWS4{}", clave_secreta).as_bytes(), dia.as_bytes()), |clave, parte| {
+            hmac(&clave, parte.as_bytes())
+        });
+    let autorizacion = format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        clave_acceso,
+        ambito,
+        cabeceras,
+        hexadecimal(&hmac(&clave, texto.as_bytes()))
+    );
+    FirmaS3 { fecha, resumen_cuerpo, autorizacion }
+}
+
+fn hmac(clave: &[u8], datos: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(clave).expect("HMAC admite claves de cualquier longitud");
+    mac.update(datos);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Lee el campo `archivo` del formulario: una imagen de un formato admitido, de hasta
+/// `bytes_maximos`, cuyo contenido coincide con el tipo declarado.
+async fn leer_imagen(formulario: &mut Multipart, bytes_maximos: usize) -> Result<(Formato, Vec<u8>), ApiError> {
+    let invalido = |e: MultipartError| ApiError::Validacion(format!("El formulario no es válido: {}", e));
+    let no_admitido = || ApiError::TipoNoAdmitido("El póster debe ser una imagen PNG, JPEG o WebP".to_string());
+    while let Some(mut campo) = formulario.next().await.transpose().map_err(invalido)? {
+        if campo.name() != Some("archivo") {
+            continue;
+        }
+        let declarado = campo.content_type().map(|tipo| tipo.essence_str().to_string());
+        if let Some(declarado) = &declarado
+            && !Formato::TODOS.iter().any(|formato| formato.tipo_mime() == declarado)
+        {
+            return Err(no_admitido());
+        }
+        let mut contenido = Vec::new();
+        while let Some(trozo) = campo.next().await.transpose().map_err(invalido)? {
+            if contenido.len() + trozo.len() > bytes_maximos {
+                return Err(ApiError::CuerpoDemasiadoGrande(format!(
+                    "El archivo supera el máximo de {} bytes",
+                    bytes_maximos
+                )));
+            }
+            contenido.extend_from_slice(&trozo);
+        }
+        let formato = Formato::detectar(&contenido).ok_or_else(no_admitido)?;
+        if let Some(declarado) = declarado
+            && declarado != formato.tipo_mime()
+        {
+            return Err(ApiError::TipoNoAdmitido(format!(
+                "El archivo es {} pero se envió como {}",
+                formato.tipo_mime(),
+                declarado
+            )));
+        }
+        return Ok((formato, contenido));
+    }
+    Err(ApiError::Validacion("Falta el campo 'archivo' con la imagen".to_string()))
+}
+
+fn error_almacen(mensaje: &'static str) -> impl FnOnce(String) -> ApiError {
+    move |e| {
+        log::error!("{}: {}", mensaje, e);
+        ApiError::BaseDatos(mensaje.to_string())
+    }
+}
+
+/// Handler que sube o reemplaza el póster de una función. Sólo para administradores.
+/// El póster anterior se elimina del almacén.
+#[allow(clippy::too_many_arguments)]
+pub async fn subir_poster(
+    _admin: Administrador,
+    CineActual(cine): CineActual,
+    pool: web::Data<Pool>,
+    cache: web::Data<CacheLecturas>,
+    config: web::Data<Config>,
+    posters: web::Data<Posters>,
+    id: web::Path<u32>,
+    mut formulario: Multipart,
+) -> Result<ApiResponse<Funcion>, ApiError> {
+    let id = id.into_inner();
+    let mut conn = db::conectar(&pool).await.map_err(ApiError::conexion)?;
+    let anterior: Option<Option<String>> = conn
+        .exec_first(
+            "SELECT f.poster FROM funciones f JOIN salas s ON s.id = f.sala_id WHERE f.id = :id AND s.cine_id = :cine_id",
+            params! { "id" => id, "cine_id" => cine },
+        )
+        .await
+        .map_err(ApiError::base_datos("Error al guardar el póster"))?;
+    let Some(anterior) = anterior else {
+        return Err(ApiError::NoEncontrado("Función no encontrada".to_string()));
+    };
+
+    let (formato, contenido) = leer_imagen(&mut formulario, posters.bytes_maximos).await?;
+    let nombre = nombre_archivo(id, formato, &contenido);
+    posters
+        .guardar(&nombre, formato, contenido)
+        .await
+        .map_err(error_almacen("Error al guardar el póster"))?;
+    conn.exec_drop("UPDATE funciones SET poster = :poster WHERE id = :id", params! { "id" => id, "poster" => &nombre })
+        .await
+        .map_err(ApiError::base_datos("Error al guardar el póster"))?;
+    cache.invalidar_catalogo().await;
+    if let Some(anterior) = anterior.filter(|anterior| *anterior != nombre)
+        && let Err(e) = posters.eliminar(&anterior).await
+    {
+        log::warn!("No se pudo eliminar el póster anterior {}: {}", anterior, e);
+    }
+
+    leer_funcion(&mut conn, cine, id)
+        .await
+        .map_err(ApiError::base_datos("Error al guardar el póster"))?
+        .map(|funcion| ApiResponse::ok(funcion.en_zona(config.zona_horaria)))
+        .ok_or_else(|| ApiError::NoEncontrado("Función no encontrada".to_string()))
+}
+
+/// Handler de `GET /static/posters/{archivo}`. Los nombres cambian con el contenido, así
+/// que la respuesta se puede cachear sin caducidad.
+pub async fn servir_poster(posters: web::Data<Posters>, archivo: web::Path<String>) -> Result<HttpResponse, ApiError> {
+    let no_encontrado = || ApiError::NoEncontrado("Póster no encontrado".to_string());
+    let formato = Formato::de_nombre(&archivo).ok_or_else(no_encontrado)?;
+    let contenido = posters
+        .leer(&archivo)
+        .await
+        .map_err(error_almacen("Error al leer el póster"))?
+        .ok_or_else(no_encontrado)?;
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType(formato.tipo_mime().parse().expect("Tipo MIME válido")))
+        .insert_header(CacheControl(vec![
+            CacheDirective::Public,
+            CacheDirective::MaxAge(365 * 24 * 3600),
+            CacheDirective::Extension("immutable".to_string(), None),
+        ]))
+        .body(contenido))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reconoce_las_imagenes_por_su_contenido() {
+        assert_eq!(Formato::detectar(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"), Some(Formato::Png));
+        assert_eq!(Formato::detectar(b"\xff\xd8\xff\xe0\0\x10JFIF"), Some(Formato::Jpeg));
+        assert_eq!(Formato::detectar(b"RIFF\x24\0\0\0WEBPVP8 "), Some(Formato::Webp));
+        assert_eq!(Formato::detectar(b"GIF89a"), None);
+        assert_eq!(Formato::detectar(b"<svg"), None);
+    }
+
+    #[test]
+    fn solo_sirve_nombres_de_poster() {
+        let nombre = nombre_archivo(12, Formato::Webp, b"imagen");
+        assert!(nombre.starts_with("12-") && nombre.ends_with(".webp"));
+        assert_eq!(Formato::de_nombre(&nombre), Some(Formato::Webp));
+        assert_eq!(Formato::de_nombre("12-0123456789abcdef.gif"), None);
+        assert_eq!(Formato::de_nombre("..-0123456789abcdef.png"), None);
+        assert_eq!(Formato::de_nombre("12-0123456789ABCDEF.png"), None);
+        assert_eq!(Formato::de_nombre("12-0123456789abcdef.png.png"), None);
+    }
+
+    #[test]
+    fn firma_las_peticiones_a_s3() {
+        let url = Url::parse("http://127.0.0.1:9000/cine/posters/1-0123456789abcdef.png").unwrap();
+        let ahora = chrono::DateTime::parse_from_rfc3339("2024-03-01T19:00:00Z").unwrap().with_timezone(&Utc);
+        let firma = firmar_s3("AKID", "secreto", "us-east-1", "GET", &url, b"", ahora);
+        assert_eq!(firma.fecha, "20240301T190000Z");
+        assert_eq!(firma.resumen_cuerpo, "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+        assert!(firma.autorizacion.starts_with(
+            "AWS4-HMAC-SHA256 Credential=AKID/20240301/us-east-1/s3/aws4_request, \
+             SignedHeaders=host;x-amz-content-sha256;x-amz-date, Signature="
+        ));
+        let otra = firmar_s3("AKID", "otro", "us-east-1", "GET", &url, b"", ahora);
+        assert_ne!(firma.autorizacion, otra.autorizacion);
+    }
+}
@@ -0,0 +1,139 @@
+//! Purga de las entradas canceladas.
+//!
+//! Una entrada cancelada ya no ocupa asientos ni cuenta en los reportes, pero sigue en la
+//! base de datos. `DELETE /admin/entradas/purge?older_than=30d` borra para siempre las
+//! canceladas sin cambios desde hace más de ese tiempo, tanto en `entradas` como en
+//! `entradas_archivo` (ver [`crate::archivo`]). Como archivar, purgar no deja eventos; el
+//! historial de cambios de las entradas purgadas se conserva en la auditoría.
+
+use actix_web::web;
+use chrono::{DateTime, Days};
+use mysql_async::prelude::*;
+use mysql_async::{Pool, TxOpts};
+use serde::{Deserialize, Serialize};
+
+use crate::auth::Administrador;
+use crate::cache::CacheLecturas;
+use crate::db;
+use crate::error::ApiError;
+use crate::reloj::Reloj;
+use crate::respuesta::ApiResponse;
+
+/// Entradas que se borran en cada transacción, para no bloquear la tabla mucho tiempo.
+const ENTRADAS_POR_LOTE: u32 = 1000;
+
+/// Parámetros de `DELETE /admin/entradas/purge`.
+#[derive(Debug, Deserialize)]
+pub struct ParametrosPurga {
+    /// Tiempo sin cambios a partir del que se purga, en días: `30d`.
+    pub older_than: Option<String>,
+}
+
+/// Entradas purgadas, según la tabla de la que salieron.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResultadoPurga {
+    pub entradas: u64,
+    pub archivadas: u64,
+}
+
+/// Días de un `older_than` como `30d`; ninguno o cero no es válido.
+fn dias(older_than: &str) -> Option<u32> {
+    older_than.trim().strip_suffix('d')?.parse().ok().filter(|dias| *dias > 0)
+}
+
+/// Borra por lotes, cada uno en su transacción, las entradas canceladas sin cambios desde
+/// hace más de `antiguedad_dias` según `reloj`, de todos los cines. Si falla a mitad, las de
+/// los lotes anteriores quedan borradas.
+pub async fn purgar(
+    pool: &Pool,
+    cache: &CacheLecturas,
+    reloj: &dyn Reloj,
+    antiguedad_dias: u32,
+) -> Result<ResultadoPurga, mysql_async::Error> {
+    let antes_de = reloj
+        .ahora_naive()
+        .checked_sub_days(Days::new(antiguedad_dias.into()))
+        .unwrap_or(DateTime::UNIX_EPOCH.naive_utc());
+    let mut conn = db::conectar(pool).await?;
+    let mut resultado = ResultadoPurga { entradas: 0, archivadas: 0 };
+    let purgadas = loop {
+        let lote = async {
+            let mut tx = conn.start_transaction(TxOpts::default()).await?;
+            let ids: Vec<u32> = tx
+                .exec(
+                    "SELECT id FROM entradas WHERE estado = 'cancelada' AND updated_at < :antes_de \
+                     ORDER BY id LIMIT :limite FOR UPDATE SKIP LOCKED",
+                    params! { "antes_de" => antes_de, "limite" => ENTRADAS_POR_LOTE },
+                )
+                .await?;
+            if !ids.is_empty() {
+                let ids: Vec<String> = ids.iter().map(u32::to_string).collect();
+                // Sus asientos se borran en cascada.
+                tx.query_drop(format!("DELETE FROM entradas WHERE id IN ({})", ids.join(",")))
+                    .await?;
+            }
+            tx.commit().await?;
+            Ok::<_, mysql_async::Error>(ids.len() as u64)
+        };
+        match lote.await {
+            Ok(0) => break Ok(()),
+            Ok(n) => resultado.entradas += n,
+            Err(e) => break Err(e),
+        }
+    };
+    if resultado.entradas > 0 {
+        cache.entradas.vaciar().await;
+    }
+    purgadas?;
+
+    loop {
+        conn.exec_drop(
+            "DELETE FROM entradas_archivo WHERE estado = 'cancelada' AND updated_at < :antes_de \
+             ORDER BY id LIMIT :limite",
+            params! { "antes_de" => antes_de, "limite" => ENTRADAS_POR_LOTE },
+        )
+        .await?;
+        match conn.affected_rows() {
+            0 => break,
+            n => resultado.archivadas += n,
+        }
+    }
+    Ok(resultado)
+}
+
+/// Handler que purga las entradas canceladas. Como las borra de todos los cines, sólo lo
+/// usa un administrador de toda la cadena.
+pub async fn purgar_entradas(
+    admin: Administrador,
+    pool: web::Data<Pool>,
+    cache: web::Data<CacheLecturas>,
+    reloj: web::Data<dyn Reloj>,
+    parametros: web::Query<ParametrosPurga>,
+) -> Result<ApiResponse<ResultadoPurga>, ApiError> {
+    if admin.0.cine.is_some() {
+        return Err(ApiError::Prohibido(
+            "Sólo un administrador de toda la cadena puede purgar entradas".to_string(),
+        ));
+    }
+    let antiguedad_dias = parametros.older_than.as_deref().and_then(dias).ok_or_else(|| {
+        ApiError::Validacion("Indique older_than en días mayor que 0, como 30d".to_string())
+    })?;
+    let resultado = purgar(&pool, &cache, reloj.as_ref(), antiguedad_dias)
+        .await
+        .map_err(ApiError::escritura("Error al purgar las entradas"))?;
+    Ok(ApiResponse::ok(resultado))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lee_la_antiguedad_en_dias() {
+        assert_eq!(dias("30d"), Some(30));
+        assert_eq!(dias(" 7d "), Some(7));
+        assert_eq!(dias("0d"), None);
+        assert_eq!(dias("30"), None);
+        assert_eq!(dias("12h"), None);
+    }
+}
@@ -0,0 +1,69 @@
+//! Contenido firmado de los códigos QR de las entradas.
+//!
+//! Al crear una entrada se entrega su código: un JWT firmado con el mismo secreto que los
+//! tokens de sesión, pero con otra audiencia y sin usuario ni rol, así que no sirve como
+//! sesión. El personal de la puerta lo escanea y lo envía a `POST /entradas/{id}/checkin`,
+//! que sólo acepta el código de esa entrada.
+
+use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+
+use crate::auth::{ahora, ConfigAuth};
+
+/// Audiencia de los códigos, que los distingue de los tokens de sesión.
+const AUDIENCIA: &str = "checkin";
+
+/// Datos firmados en el código de una entrada.
+#[derive(Debug, Serialize, Deserialize)]
+struct CodigoEntrada {
+    entrada: u32,
+    aud: String,
+    iat: u64,
+}
+
+/// Código QR de la entrada, para mostrarlo o imprimirlo.
+pub fn firmar(config: &ConfigAuth, entrada: u32) -> String {
+    let codigo = CodigoEntrada { entrada, aud: AUDIENCIA.to_string(), iat: ahora() };
+    encode(&Header::default(), &codigo, &EncodingKey::from_secret(config.secreto.as_bytes()))
+        .expect("Un código con clave HMAC siempre se puede firmar")
+}
+
+/// ID de la entrada del código, si la firma es válida. Los códigos no caducan: una entrada
+/// deja de valer al usarse o cancelarse.
+pub fn verificar(config: &ConfigAuth, codigo: &str) -> Option<u32> {
+    let mut validacion = Validation::default();
+    validacion.set_audience(&[AUDIENCIA]);
+    validacion.set_required_spec_claims(&["aud"]);
+    validacion.validate_exp = false;
+    decode::<CodigoEntrada>(codigo, &DecodingKey::from_secret(config.secreto.as_bytes()), &validacion)
+        .ok()
+        .map(|datos| datos.claims.entrada)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::auth::{emitir_token, verificar_token};
+    use crate::models::Rol;
+
+    fn config(secreto: &str) -> ConfigAuth {
+        ConfigAuth {
+            secreto: secreto.to_string(),
+            duracion_token: Duration::from_secs(60),
+            duracion_refresco: Duration::from_secs(3600),
+        }
+    }
+
+    #[test]
+    fn el_codigo_solo_vale_con_su_firma_y_no_como_sesion() {
+        let codigo = firmar(&config("secreto"), 7);
+        assert_eq!(verificar(&config("secreto"), &codigo), Some(7));
+        assert_eq!(verificar(&config("otro"), &codigo), None);
+        assert!(verificar_token(&config("secreto"), &codigo).is_err());
+
+        let token = emitir_token(&config("secreto"), "admin", Rol::Admin, None).unwrap();
+        assert_eq!(verificar(&config("secreto"), &token), None);
+    }
+}
@@ -0,0 +1,165 @@
+//! Recarga de la configuración sin reiniciar el servidor.
+//!
+//! Con SIGHUP se vuelve a leer el archivo de configuración de [`Config::archivo`] y se
+//! aplican sólo los valores que pueden cambiar en marcha: `nivel_log`, los límites de
+//! peticiones, el tiempo máximo de cada petición y `graphql_playground`. El resto (la base
+//! de datos, la dirección, la pool...) sigue como al arrancar hasta reiniciar. Si el
+//! archivo es inválido se avisa en el log y no cambia nada.
+//!
+//! La configuración en uso es un [`ConfigVigente`] que se reemplaza entera de una vez, así
+//! que cada petición la ve o antes o después de la recarga, nunca a medias. Los middlewares
+//! y handlers de esos valores la leen de ahí en lugar de `web::Data<Config>`, que es la del
+//! arranque.
+
+use std::sync::{Arc, OnceLock};
+
+use arc_swap::ArcSwap;
+use log::{LevelFilter, Log, Metadata, Record};
+
+use crate::config::Config;
+use crate::limite::LimitesPeticiones;
+
+/// Configuración en uso, con los cambios recargados.
+pub type ConfigVigente = ArcSwap<Config>;
+
+/// Logger de `env_logger` que se puede reemplazar para cambiar su filtro.
+struct LogRecargable(ArcSwap<env_logger::Logger>);
+
+impl Log for LogRecargable {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.0.load().enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        self.0.load().log(record)
+    }
+
+    fn flush(&self) {
+        self.0.load().flush()
+    }
+}
+
+impl LogRecargable {
+    fn new(filtro: &str) -> Self {
+        LogRecargable(ArcSwap::from_pointee(logger(filtro)))
+    }
+
+    /// Reemplaza el filtro y devuelve el nivel más detallado que deja pasar.
+    fn cambiar(&self, filtro: &str) -> LevelFilter {
+        let nuevo = logger(filtro);
+        let nivel = nuevo.filter();
+        self.0.store(Arc::new(nuevo));
+        nivel
+    }
+}
+
+static LOG: OnceLock<LogRecargable> = OnceLock::new();
+
+fn logger(filtro: &str) -> env_logger::Logger {
+    env_logger::Builder::new().parse_filters(filtro).build()
+}
+
+/// Instala el logger del proceso con el filtro `filtro`, como `info` o
+/// `rust_crud=debug,actix_web=warn`. Sólo la primera llamada tiene efecto.
+pub fn iniciar_log(filtro: &str) {
+    let log = LOG.get_or_init(|| LogRecargable::new(filtro));
+    if log::set_logger(log).is_ok() {
+        log::set_max_level(log.0.load().filter());
+    }
+}
+
+/// Cambia el filtro del logger instalado con [`iniciar_log`]; sin él, no hace nada.
+fn cambiar_nivel_log(filtro: &str) {
+    if let Some(log) = LOG.get() {
+        log::set_max_level(log.cambiar(filtro));
+    }
+}
+
+/// Aplica a `vigente` y a los límites los valores de `nueva` que se pueden recargar.
+pub fn aplicar(vigente: &ConfigVigente, limites: &LimitesPeticiones, nueva: Config) {
+    let mut config = Config::clone(&vigente.load());
+    if config.nivel_log != nueva.nivel_log {
+        cambiar_nivel_log(&nueva.nivel_log);
+    }
+    config.nivel_log = nueva.nivel_log;
+    config.limite_ip = nueva.limite_ip;
+    config.limite_clave_api = nueva.limite_clave_api;
+    config.tiempo_maximo_peticion = nueva.tiempo_maximo_peticion;
+    config.graphql_playground = nueva.graphql_playground;
+    limites.configurar(config.limite_ip, config.limite_clave_api);
+    vigente.store(Arc::new(config));
+}
+
+/// Vuelve a leer el archivo de configuración con cada SIGHUP y aplica sus cambios. Sin
+/// archivo no hay nada que releer y termina enseguida.
+pub async fn recargar_con_sighup(vigente: Arc<ConfigVigente>, limites: Arc<LimitesPeticiones>) {
+    let Some(archivo) = vigente.load().archivo.clone() else {
+        return;
+    };
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut senales = match signal(SignalKind::hangup()) {
+            Ok(senales) => senales,
+            Err(e) => {
+                log::warn!("No se pudo escuchar SIGHUP, la configuración no se recargará: {}", e);
+                return;
+            }
+        };
+        while senales.recv().await.is_some() {
+            match Config::cargar(Some(&archivo)) {
+                Ok(nueva) => {
+                    aplicar(&vigente, &limites, nueva);
+                    log::info!("Configuración recargada de {}", archivo);
+                }
+                Err(e) => log::warn!("No se recargó la configuración: {}", e),
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = (archivo, limites);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    use log::Level;
+
+    use crate::limite::ConfigLimite;
+
+    #[test]
+    fn subir_el_nivel_filtra_los_mensajes() {
+        let mensaje = |nivel| Metadata::builder().level(nivel).target("rust_crud::recarga").build();
+        let log = LogRecargable::new("info");
+        assert!(log.enabled(&mensaje(Level::Info)));
+
+        assert_eq!(log.cambiar("warn"), LevelFilter::Warn);
+        assert!(!log.enabled(&mensaje(Level::Info)));
+        assert!(log.enabled(&mensaje(Level::Warn)));
+    }
+
+    #[test]
+    fn solo_cambia_los_valores_recargables() {
+        let config = Config::new("mysql://localhost/crud");
+        let limites = LimitesPeticiones::new(config.limite_ip, config.limite_clave_api, None);
+        let vigente = ConfigVigente::from_pointee(config);
+
+        let mut nueva = Config::new("mysql://otra/crud");
+        nueva.port = 9090;
+        nueva.graphql_playground = true;
+        nueva.tiempo_maximo_peticion = Duration::from_secs(5);
+        nueva.limite_ip = ConfigLimite { por_segundo: 1.0, rafaga: 1 };
+        aplicar(&vigente, &limites, nueva);
+
+        let config = vigente.load();
+        assert_eq!((config.database_url.as_str(), config.port), ("mysql://localhost/crud", 8080));
+        assert!(config.graphql_playground);
+        assert_eq!(config.tiempo_maximo_peticion, Duration::from_secs(5));
+        let ahora = Instant::now();
+        assert!(limites.ip.consumir("kiosko", ahora).is_ok());
+        assert!(limites.ip.consumir("kiosko", ahora).is_err());
+    }
+}
@@ -0,0 +1,64 @@
+//! Hora actual de la API.
+//!
+//! Las fechas de las entradas, el vencimiento de las reservas, el archivo y la purga leen la
+//! hora de un [`Reloj`] en vez de `Utc::now()` o `NOW()`, para que las pruebas puedan
+//! adelantarla con un [`RelojFijo`] en lugar de esperar o reescribir las fechas en la base
+//! de datos.
+
+use std::sync::Mutex;
+
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+
+/// Fuente de la hora actual, en UTC.
+pub trait Reloj: Send + Sync {
+    fn ahora(&self) -> DateTime<Utc>;
+
+    /// La hora actual como la guarda MySQL, que trabaja en UTC (ver [`crate::db::obtener_pool_db`]).
+    fn ahora_naive(&self) -> NaiveDateTime {
+        self.ahora().naive_utc()
+    }
+}
+
+/// La hora del sistema.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RelojSistema;
+
+impl Reloj for RelojSistema {
+    fn ahora(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// Un reloj que sólo avanza cuando se le pide.
+#[derive(Debug)]
+pub struct RelojFijo(Mutex<DateTime<Utc>>);
+
+impl RelojFijo {
+    pub fn new(ahora: DateTime<Utc>) -> Self {
+        RelojFijo(Mutex::new(ahora))
+    }
+
+    pub fn avanzar(&self, tiempo: Duration) {
+        *self.0.lock().unwrap() += tiempo;
+    }
+}
+
+impl Reloj for RelojFijo {
+    fn ahora(&self) -> DateTime<Utc> {
+        *self.0.lock().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn el_reloj_fijo_solo_avanza_cuando_se_le_pide() {
+        let inicio = DateTime::parse_from_rfc3339("2024-03-01T18:30:00Z").unwrap().to_utc();
+        let reloj = RelojFijo::new(inicio);
+        assert_eq!(reloj.ahora(), inicio);
+        reloj.avanzar(Duration::minutes(15));
+        assert_eq!(reloj.ahora_naive().to_string(), "2024-03-01 18:45:00");
+    }
+}
\ No newline at end of file
@@ -0,0 +1,228 @@
+//! Reportes de ventas (`/reportes`), agregados con GROUP BY en la base de datos para que
+//! nadie tenga que calcularlos sobre el listado completo, con las ventas del cine de la
+//! petición. Sólo para administradores.
+//!
+//! Las fechas son las del horario de cada función en la zona de [`Config::zona_horaria`], y
+//! las entradas canceladas no cuentan como vendidas.
+
+use actix_web::web;
+use chrono::{Days, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
+use mysql_async::{prelude::*, Params, Pool, Value};
+use serde::Deserialize;
+
+use crate::auth::Administrador;
+use crate::cines::CineActual;
+use crate::config::Config;
+use crate::db;
+use crate::error::ApiError;
+use crate::models::{ReporteVentas, Ventas, VentasDia, VentasFranja, VentasFuncion};
+use crate::respuesta::ApiResponse;
+
+/// Horario de la función en la zona configurada, que llega en `:desfase` segundos.
+const HORARIO_LOCAL: &str = "DATE_ADD(f.horario, INTERVAL :desfase SECOND)";
+
+/// Columnas de ventas de cada grupo: entradas vendidas y recaudado.
+const COLUMNAS_VENTAS: &str = "CAST(SUM(e.cantidad_entradas) AS UNSIGNED) AS vendidas, SUM(e.total) AS recaudado";
+
+/// Columnas y agrupación de las ventas por función.
+const COLUMNAS_FUNCION: &str = "f.id, f.titulo, s.nombre, f.horario";
+const GRUPO_FUNCION: &str = "f.id, f.titulo, s.nombre, f.horario";
+
+/// Funciones de `GET /reportes/top-funciones` si no se indica `limit`, y máximo permitido.
+pub const TOP_FUNCIONES_DEFECTO: u32 = 10;
+pub const TOP_FUNCIONES_MAXIMO: u32 = 100;
+
+/// Fila de ventas por función, con el horario en UTC.
+type FilaFuncion = (u32, String, String, NaiveDateTime, u64, f64);
+
+/// Parámetros de consulta de los reportes: el rango de fechas, con ambas incluidas.
+#[derive(Debug, Default, Deserialize)]
+pub struct ParametrosReporte {
+    /// Primer día, `AAAA-MM-DD`.
+    pub desde: Option<String>,
+    /// Último día, `AAAA-MM-DD`.
+    pub hasta: Option<String>,
+}
+
+/// Parámetro propio de `GET /reportes/top-funciones`, además del rango de fechas.
+#[derive(Debug, Default, Deserialize)]
+pub struct ParametrosTopFunciones {
+    /// Cuántas funciones devolver, hasta [`TOP_FUNCIONES_MAXIMO`].
+    pub limit: Option<u32>,
+}
+
+/// Condición WHERE de un reporte y sus parámetros nombrados.
+struct Filtro {
+    condicion: String,
+    params: Vec<(String, Value)>,
+}
+
+impl Filtro {
+    /// Valida el rango y lo traduce a horarios en UTC, que es como se guardan.
+    fn desde_parametros(parametros: &ParametrosReporte, zona: FixedOffset, cine: u32) -> Result<Filtro, ApiError> {
+        let fecha = |campo: &str, valor: Option<&str>| {
+            valor
+                .map(|valor| NaiveDate::parse_from_str(valor, "%Y-%m-%d"))
+                .transpose()
+                .map_err(|_| ApiError::Validacion(format!("El parámetro '{}' debe ser una fecha AAAA-MM-DD", campo)))
+        };
+        let desde = fecha("desde", parametros.desde.as_deref())?;
+        let hasta = fecha("hasta", parametros.hasta.as_deref())?;
+        if let (Some(desde), Some(hasta)) = (desde, hasta)
+            && desde > hasta
+        {
+            return Err(ApiError::Validacion("El parámetro 'desde' no puede ser posterior a 'hasta'".to_string()));
+        }
+
+        let inicio_en_utc = |dia: NaiveDate| -> NaiveDateTime {
+            zona.from_local_datetime(&dia.and_time(NaiveTime::MIN))
+                .single()
+                .expect("Un desplazamiento fijo no tiene horas ambiguas")
+                .naive_utc()
+        };
+        let mut condiciones = vec!["e.cine_id = :cine_id", "e.estado <> 'cancelada'"];
+        let mut params = vec![
+            ("desfase".to_string(), Value::from(zona.local_minus_utc())),
+            ("cine_id".to_string(), Value::from(cine)),
+        ];
+        if let Some(desde) = desde {
+            condiciones.push("f.horario >= :desde");
+            params.push(("desde".to_string(), Value::from(inicio_en_utc(desde))));
+        }
+        if let Some(siguiente) = hasta.and_then(|hasta| hasta.checked_add_days(Days::new(1))) {
+            condiciones.push("f.horario < :hasta");
+            params.push(("hasta".to_string(), Value::from(inicio_en_utc(siguiente))));
+        }
+        Ok(Filtro { condicion: condiciones.join(" AND "), params })
+    }
+
+    /// Consulta que agrupa las entradas del filtro por `grupo` y selecciona `columnas`
+    /// seguidas de las de ventas, ordenada por `orden`.
+    fn consulta(&self, columnas: &str, grupo: &str, orden: &str) -> String {
+        format!(
+            "SELECT {}, {} FROM {} WHERE {} GROUP BY {} ORDER BY {}",
+            columnas,
+            COLUMNAS_VENTAS,
+            db::TABLAS_ENTRADAS,
+            self.condicion,
+            grupo,
+            orden
+        )
+    }
+
+    fn params(&self) -> Params {
+        Params::from(self.params.clone())
+    }
+}
+
+fn ventas_funcion(fila: FilaFuncion, zona: FixedOffset) -> VentasFuncion {
+    let (funcion_id, titulo, sala, horario, entradas, recaudado) = fila;
+    VentasFuncion {
+        funcion_id,
+        titulo,
+        sala,
+        horario: zona.from_utc_datetime(&horario),
+        ventas: Ventas { entradas, recaudado },
+    }
+}
+
+/// Handler con las entradas vendidas y lo recaudado en total, por función, por día y por
+/// franja horaria, opcionalmente entre las fechas `desde` y `hasta`.
+pub async fn reporte_ventas(
+    _admin: Administrador,
+    CineActual(cine): CineActual,
+    pool: web::Data<Pool>,
+    config: web::Data<Config>,
+    query: web::Query<ParametrosReporte>,
+) -> Result<ApiResponse<ReporteVentas>, ApiError> {
+    let zona = config.zona_horaria;
+    let filtro = Filtro::desde_parametros(&query, zona, cine)?;
+    let error = || ApiError::base_datos("Error al obtener el reporte de ventas");
+    let mut conn = db::conectar(&pool).await.map_err(ApiError::conexion)?;
+
+    let por_funcion: Vec<FilaFuncion> = conn
+        .exec(filtro.consulta(COLUMNAS_FUNCION, GRUPO_FUNCION, "f.horario, f.id"), filtro.params())
+        .await
+        .map_err(error())?;
+    let por_dia: Vec<(String, u64, f64)> = conn
+        .exec(
+            filtro.consulta(&format!("DATE_FORMAT({}, '%Y-%m-%d') AS fecha", HORARIO_LOCAL), "fecha", "fecha"),
+            filtro.params(),
+        )
+        .await
+        .map_err(error())?;
+    let por_franja: Vec<(u32, u64, f64)> = conn
+        .exec(filtro.consulta(&format!("HOUR({}) AS hora", HORARIO_LOCAL), "hora", "hora"), filtro.params())
+        .await
+        .map_err(error())?;
+
+    let ventas = |entradas, recaudado| Ventas { entradas, recaudado };
+    let totales = por_dia.iter().fold(Ventas::default(), |total, (_, entradas, recaudado)| {
+        ventas(total.entradas + entradas, total.recaudado + recaudado)
+    });
+    Ok(ApiResponse::ok(ReporteVentas {
+        totales,
+        por_funcion: por_funcion.into_iter().map(|fila| ventas_funcion(fila, zona)).collect(),
+        por_dia: por_dia
+            .into_iter()
+            .map(|(fecha, entradas, recaudado)| VentasDia { fecha, ventas: ventas(entradas, recaudado) })
+            .collect(),
+        por_franja: por_franja
+            .into_iter()
+            .map(|(hora, entradas, recaudado)| VentasFranja {
+                franja: format!("{:02}:00-{:02}:00", hora, (hora + 1) % 24),
+                ventas: ventas(entradas, recaudado),
+            })
+            .collect(),
+    }))
+}
+
+/// Handler con las funciones que más entradas vendieron, de más a menos, opcionalmente
+/// entre las fechas `desde` y `hasta`. A igual cantidad, primero la que más recaudó.
+pub async fn top_funciones(
+    _admin: Administrador,
+    CineActual(cine): CineActual,
+    pool: web::Data<Pool>,
+    config: web::Data<Config>,
+    query: web::Query<ParametrosReporte>,
+    top: web::Query<ParametrosTopFunciones>,
+) -> Result<ApiResponse<Vec<VentasFuncion>>, ApiError> {
+    let limite = top.limit.unwrap_or(TOP_FUNCIONES_DEFECTO);
+    if limite == 0 {
+        return Err(ApiError::Validacion("El parámetro 'limit' debe ser mayor que 0".to_string()));
+    }
+    let filtro = Filtro::desde_parametros(&query, config.zona_horaria, cine)?;
+    let orden = format!("vendidas DESC, recaudado DESC, f.id LIMIT {}", limite.min(TOP_FUNCIONES_MAXIMO));
+    let mut conn = db::conectar(&pool).await.map_err(ApiError::conexion)?;
+    let filas: Vec<FilaFuncion> = conn
+        .exec(filtro.consulta(COLUMNAS_FUNCION, GRUPO_FUNCION, &orden), filtro.params())
+        .await
+        .map_err(ApiError::base_datos("Error al obtener las funciones más vendidas"))?;
+    Ok(ApiResponse::ok(filas.into_iter().map(|fila| ventas_funcion(fila, config.zona_horaria)).collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn el_rango_se_traduce_a_utc_con_ambos_dias_incluidos() {
+        let zona = FixedOffset::west_opt(5 * 3600).unwrap();
+        let parametros = ParametrosReporte { desde: Some("2024-03-01".into()), hasta: Some("2024-03-02".into()) };
+        let filtro = Filtro::desde_parametros(&parametros, zona, 2).unwrap();
+        assert_eq!(
+            filtro.condicion,
+            "e.cine_id = :cine_id AND e.estado <> 'cancelada' AND f.horario >= :desde AND f.horario < :hasta"
+        );
+        let hora = |texto| Value::from(NaiveDateTime::parse_from_str(texto, "%Y-%m-%d %H:%M").unwrap());
+        assert_eq!(filtro.params[0].1, Value::from(-5 * 3600));
+        assert_eq!(filtro.params[1].1, Value::from(2u32));
+        assert_eq!(filtro.params[2].1, hora("2024-03-01 05:00"));
+        assert_eq!(filtro.params[3].1, hora("2024-03-03 05:00"));
+
+        let invertido = ParametrosReporte { desde: Some("2024-03-02".into()), hasta: Some("2024-03-01".into()) };
+        assert!(Filtro::desde_parametros(&invertido, zona, 1).is_err());
+        let invalido = ParametrosReporte { desde: Some("01/03/2024".into()), ..Default::default() };
+        assert!(Filtro::desde_parametros(&invalido, zona, 1).is_err());
+    }
+}
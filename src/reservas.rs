@@ -0,0 +1,60 @@
+//! Vencimiento de las reservas sin pagar. Una entrada reservada ocupa capacidad y asientos
+//! hasta que se paga o se cancela; la tarea de [`vencer_reservas`] cancela las que llevan
+//! más de `Config::reservas_ttl` sin pagarse para que no los bloqueen para siempre.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::cambios::{Cambio, CanalCambios};
+use crate::servicio::ServicioEntradas;
+use crate::tareas::{self, Intervalo, Trabajo};
+
+/// Actor con el que las cancelaciones por vencimiento quedan en la auditoría.
+pub const ACTOR_VENCIMIENTO: &str = "vencimiento";
+
+/// Reservas canceladas por vencimiento desde el arranque, para `/metrics`.
+pub static RESERVAS_VENCIDAS: AtomicU64 = AtomicU64::new(0);
+
+/// Cada cuánto se buscan reservas vencidas: una fracción del plazo, entre 1 s y 1 min.
+fn periodo_revision(ttl: Duration) -> Duration {
+    (ttl / 4).clamp(Duration::from_secs(1), Duration::from_secs(60))
+}
+
+/// Tarea que cancela las reservas con más de `ttl` sin pagarse. Si una revisión falla, se
+/// reintenta en la siguiente. Las cancelaciones se avisan por `cambios`.
+pub fn vencer_reservas(
+    servicio: Arc<ServicioEntradas>,
+    cambios: Arc<CanalCambios>,
+    ttl: Duration,
+) -> (Intervalo, Trabajo) {
+    let trabajo = tareas::trabajo(move || {
+        let servicio = servicio.clone();
+        let cambios = cambios.clone();
+        async move {
+            let canceladas = servicio
+                .cancelar_vencidas(ttl, ACTOR_VENCIMIENTO)
+                .await
+                .map_err(|e| e.to_string())?;
+            if canceladas > 0 {
+                RESERVAS_VENCIDAS.fetch_add(canceladas, Ordering::Relaxed);
+                cambios.publicar(Cambio::Recargar);
+                log::info!("Se cancelaron {} reservas sin pagar tras {} s", canceladas, ttl.as_secs());
+            }
+            Ok(())
+        }
+    });
+    (Intervalo::cada(periodo_revision(ttl)), trabajo)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn la_revision_es_una_fraccion_acotada_del_plazo() {
+        assert_eq!(periodo_revision(Duration::from_secs(900)), Duration::from_secs(60));
+        assert_eq!(periodo_revision(Duration::from_secs(40)), Duration::from_secs(10));
+        assert_eq!(periodo_revision(Duration::from_secs(2)), Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,402 @@
+//! Respaldo lógico de la base de datos, para instalaciones sin DBA.
+//!
+//! `POST /admin/backup` transmite las tablas de [`TABLAS`] en JSON Lines, leídas en una
+//! transacción con instantánea consistente: todas las filas son del mismo instante aunque
+//! la API siga vendiendo mientras tanto. Las fechas van en UTC. El comando `restore` de la
+//! CLI las carga con [`restaurar`] en una base de datos con el esquema al día.
+//!
+//! Formato, una línea JSON por elemento:
+//!
+//! ```text
+//! {"formato":"rust-crud","version":1,"esquema":23,"creado_en":"2024-03-01T18:30:00Z"}
+//! {"tabla":"cines","columnas":["id","nombre","creado_en"]}
+//! [1,"Principal","2024-03-01 18:30:00"]
+//! ...
+//! {"fin":true,"filas":1234}
+//! ```
+//!
+//! Los enteros van como números y el resto de valores como texto, tal como los da MySQL.
+//! La última línea cuenta las filas: sin ella el respaldo se da por cortado y no se
+//! restaura. No incluye los pósters, que están en disco, ni las claves de idempotencia.
+
+use std::fmt;
+use std::io::BufRead;
+
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::web::{self, Bytes};
+use actix_web::HttpResponse;
+use chrono::{SecondsFormat, Utc};
+use futures_util::stream;
+use mysql_async::consts::ColumnType;
+use mysql_async::prelude::*;
+use mysql_async::{Pool, TxOpts, Value};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+use crate::auth::Administrador;
+use crate::db::migraciones::MIGRACIONES;
+use crate::db::{self, Conexion};
+use crate::error::ApiError;
+
+/// Tablas que se respaldan, las referenciadas antes que las que las referencian.
+pub const TABLAS: &[&str] = &[
+    "cines",
+    "usuarios",
+    "tokens_refresco",
+    "api_keys",
+    "dispositivos",
+    "salas",
+    "funciones",
+    "clientes",
+    "entradas",
+    "asientos",
+    "auditoria",
+    "eventos",
+    "webhooks",
+    "webhook_entregas",
+    "correos",
+    "entradas_archivo",
+    "accesos",
+];
+
+/// Nombre del formato en la cabecera.
+const FORMATO: &str = "rust-crud";
+
+/// Versión del formato de las líneas.
+const VERSION_FORMATO: u32 = 1;
+
+/// Bytes que se acumulan antes de enviarlos al cliente.
+const BYTES_POR_ENVIO: usize = 64 * 1024;
+
+/// Envíos que pueden quedar en cola mientras el cliente lee.
+const ENVIOS_EN_BUFFER: usize = 8;
+
+/// Filas que se insertan en cada `INSERT` al restaurar.
+const FILAS_POR_INSERCION: usize = 200;
+
+/// Primera línea del respaldo.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Cabecera {
+    formato: String,
+    version: u32,
+    /// Última migración aplicada a la base de datos respaldada.
+    esquema: u32,
+    creado_en: String,
+}
+
+/// Una línea del respaldo.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+enum Linea {
+    Cabecera(Cabecera),
+    /// Empiezan las filas de `tabla`, con sus valores en el orden de `columnas`.
+    Tabla { tabla: String, columnas: Vec<String> },
+    Fila(Vec<serde_json::Value>),
+    Fin { fin: bool, filas: u64 },
+}
+
+/// Última versión del esquema que conoce esta versión de la aplicación.
+fn esquema_actual() -> u32 {
+    MIGRACIONES.last().map_or(0, |migracion| migracion.version)
+}
+
+/// Handler que transmite el respaldo. Contiene los datos de todos los cines y los hashes
+/// de las claves de los usuarios, así que sólo lo pide un administrador de toda la cadena.
+pub async fn crear_respaldo(admin: Administrador, pool: web::Data<Pool>) -> Result<HttpResponse, ApiError> {
+    if admin.0.cine.is_some() {
+        return Err(ApiError::Prohibido(
+            "Sólo un administrador de toda la cadena puede respaldar la base de datos".to_string(),
+        ));
+    }
+    // Siempre de la primaria: una réplica atrasada daría un respaldo antiguo.
+    let mut conn = db::conectar_primaria(&pool).await.map_err(ApiError::conexion)?;
+    let abrir = async {
+        conn.query_drop("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ").await?;
+        conn.query_drop("START TRANSACTION WITH CONSISTENT SNAPSHOT, READ ONLY").await
+    };
+    abrir.await.map_err(ApiError::base_datos("Error al iniciar el respaldo"))?;
+
+    let ahora = Utc::now();
+    let cabecera = Cabecera {
+        formato: FORMATO.to_string(),
+        version: VERSION_FORMATO,
+        esquema: esquema_actual(),
+        creado_en: ahora.to_rfc3339_opts(SecondsFormat::Secs, true),
+    };
+    let (tx, rx) = mpsc::channel(ENVIOS_EN_BUFFER);
+    actix_web::rt::spawn(volcar(conn, cabecera, tx));
+    let cuerpo = stream::unfold(rx, |mut rx| async move { rx.recv().await.map(|envio| (envio, rx)) });
+    Ok(HttpResponse::Ok()
+        .content_type("application/x-ndjson")
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(format!("respaldo-{}.jsonl", ahora.format("%Y%m%d-%H%M%S")))],
+        })
+        .streaming(cuerpo))
+}
+
+/// Escribe en `tx` el respaldo de la transacción abierta en `conn`. Si una consulta falla,
+/// envía el error, que corta la respuesta antes de la línea final.
+async fn volcar(mut conn: Conexion, cabecera: Cabecera, tx: mpsc::Sender<Result<Bytes, mysql_async::Error>>) {
+    let mut buffer = serde_json::to_vec(&cabecera).expect("Cabecera siempre es serializable");
+    buffer.push(b'\n');
+    let mut filas = 0u64;
+    for tabla in TABLAS {
+        let resultado = async {
+            let mut resultado = conn.query_iter(format!("SELECT * FROM {}", tabla)).await?;
+            let nombres: Vec<String> =
+                resultado.columns_ref().iter().map(|columna| columna.name_str().into_owned()).collect();
+            serde_json::to_writer(&mut buffer, &serde_json::json!({ "tabla": tabla, "columnas": nombres }))
+                .expect("Tabla siempre es serializable");
+            buffer.push(b'\n');
+            while let Some(fila) = resultado.next().await? {
+                let tipos: Vec<ColumnType> = fila.columns_ref().iter().map(|columna| columna.column_type()).collect();
+                let valores: Vec<serde_json::Value> =
+                    fila.unwrap().into_iter().zip(tipos).map(|(valor, tipo)| a_json(valor, tipo)).collect();
+                serde_json::to_writer(&mut buffer, &valores).expect("Fila siempre es serializable");
+                buffer.push(b'\n');
+                filas += 1;
+                if buffer.len() >= BYTES_POR_ENVIO && tx.send(Ok(Bytes::from(std::mem::take(&mut buffer)))).await.is_err() {
+                    return Ok(false);
+                }
+            }
+            Ok::<_, mysql_async::Error>(true)
+        };
+        match resultado.await {
+            Ok(true) => {}
+            // El cliente cerró la conexión.
+            Ok(false) => return,
+            Err(e) => {
+                log::error!("Error al respaldar la tabla {}: {:?}", tabla, e);
+                let _ = tx.send(Err(e)).await;
+                return;
+            }
+        }
+    }
+    let _ = conn.query_drop("COMMIT").await;
+    serde_json::to_writer(&mut buffer, &serde_json::json!({ "fin": true, "filas": filas }))
+        .expect("Fin siempre es serializable");
+    buffer.push(b'\n');
+    let _ = tx.send(Ok(Bytes::from(buffer))).await;
+}
+
+/// Valor de una columna leída por el protocolo de texto, donde todo llega como bytes.
+fn a_json(valor: Value, tipo: ColumnType) -> serde_json::Value {
+    let Value::Bytes(bytes) = valor else {
+        return serde_json::Value::Null;
+    };
+    let texto = String::from_utf8_lossy(&bytes);
+    let entero = matches!(
+        tipo,
+        ColumnType::MYSQL_TYPE_TINY
+            | ColumnType::MYSQL_TYPE_SHORT
+            | ColumnType::MYSQL_TYPE_INT24
+            | ColumnType::MYSQL_TYPE_LONG
+            | ColumnType::MYSQL_TYPE_LONGLONG
+    );
+    let numero = entero
+        .then(|| texto.parse::<i64>().map(Into::into).or_else(|_| texto.parse::<u64>().map(Into::into)).ok())
+        .flatten();
+    numero.unwrap_or_else(|| serde_json::Value::String(texto.into_owned()))
+}
+
+/// Valor de un respaldo como parámetro de un `INSERT`.
+fn desde_json(valor: &serde_json::Value) -> Result<Value, String> {
+    match valor {
+        serde_json::Value::Null => Ok(Value::NULL),
+        serde_json::Value::String(texto) => Ok(Value::from(texto.as_str())),
+        serde_json::Value::Number(numero) => numero
+            .as_i64()
+            .map(Value::from)
+            .or_else(|| numero.as_u64().map(Value::from))
+            .or_else(|| numero.as_f64().map(Value::from))
+            .ok_or_else(|| format!("Número inválido: {}", numero)),
+        otro => Err(format!("Valor inválido: {}", otro)),
+    }
+}
+
+/// Errores de [`restaurar`].
+#[derive(Debug)]
+pub enum ErrorRestauracion {
+    /// La base de datos ya tiene entradas y no se pidió forzar la restauración.
+    BaseNoVacia(u64),
+    /// El respaldo es de un esquema más nuevo que el de esta versión.
+    EsquemaPosterior(u32),
+    /// El archivo no es un respaldo válido; en la línea indicada.
+    Formato(usize, String),
+    Lectura(std::io::Error),
+    BaseDatos(mysql_async::Error),
+}
+
+impl fmt::Display for ErrorRestauracion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorRestauracion::BaseNoVacia(total) => write!(
+                f,
+                "La base de datos ya tiene {} entradas; usa --force para reemplazar todos sus datos por los del respaldo",
+                total
+            ),
+            ErrorRestauracion::EsquemaPosterior(esquema) => write!(
+                f,
+                "El respaldo es del esquema {}, posterior al {} de esta versión; actualiza la aplicación antes de restaurarlo",
+                esquema,
+                esquema_actual()
+            ),
+            ErrorRestauracion::Formato(linea, motivo) => write!(f, "Respaldo inválido en la línea {}: {}", linea, motivo),
+            ErrorRestauracion::Lectura(e) => write!(f, "Error al leer el respaldo: {}", e),
+            ErrorRestauracion::BaseDatos(e) => write!(f, "Error de base de datos: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ErrorRestauracion {}
+
+/// Carga en la base de datos, con el esquema ya migrado, el respaldo que lee de `lector`, y
+/// devuelve las filas restauradas. Reemplaza los datos de todas las [`TABLAS`], en una sola
+/// transacción: si el respaldo está cortado o falla algo, la base de datos queda como
+/// estaba. Se niega si ya hay entradas, salvo con `forzar`.
+pub async fn restaurar(pool: &Pool, lector: impl BufRead, forzar: bool) -> Result<u64, ErrorRestauracion> {
+    let mut conn = db::conectar_primaria(pool).await.map_err(ErrorRestauracion::BaseDatos)?;
+    let total: u64 = conn
+        .query_first("SELECT COUNT(*) FROM entradas")
+        .await
+        .map_err(ErrorRestauracion::BaseDatos)?
+        .unwrap_or_default();
+    if total > 0 && !forzar {
+        return Err(ErrorRestauracion::BaseNoVacia(total));
+    }
+    let mut tx = conn.start_transaction(TxOpts::default()).await.map_err(ErrorRestauracion::BaseDatos)?;
+    // Las filas de cada tabla se cargan en el orden del respaldo, no en el de sus claves.
+    tx.query_drop("SET FOREIGN_KEY_CHECKS = 0").await.map_err(ErrorRestauracion::BaseDatos)?;
+    for tabla in TABLAS {
+        tx.query_drop(format!("DELETE FROM {}", tabla)).await.map_err(ErrorRestauracion::BaseDatos)?;
+    }
+
+    let mut carga = Carga::default();
+    let mut numero = 0;
+    for linea in lector.lines() {
+        numero += 1;
+        let linea = linea.map_err(ErrorRestauracion::Lectura)?;
+        let invalida = |motivo: String| ErrorRestauracion::Formato(numero, motivo);
+        let linea: Linea = serde_json::from_str(&linea).map_err(|e| invalida(e.to_string()))?;
+        match (numero, linea) {
+            (1, Linea::Cabecera(cabecera)) => {
+                if cabecera.formato != FORMATO || cabecera.version != VERSION_FORMATO {
+                    return Err(invalida(format!("formato {} {} desconocido", cabecera.formato, cabecera.version)));
+                }
+                if cabecera.esquema > esquema_actual() {
+                    return Err(ErrorRestauracion::EsquemaPosterior(cabecera.esquema));
+                }
+            }
+            (1, _) => return Err(invalida("falta la cabecera".to_string())),
+            (_, Linea::Tabla { tabla, columnas }) => {
+                carga.insertar(&mut tx).await.map_err(ErrorRestauracion::BaseDatos)?;
+                carga.empezar(tabla, columnas).map_err(invalida)?;
+            }
+            (_, Linea::Fila(valores)) => {
+                carga.anotar(&valores).map_err(invalida)?;
+                if carga.pendientes.len() >= FILAS_POR_INSERCION {
+                    carga.insertar(&mut tx).await.map_err(ErrorRestauracion::BaseDatos)?;
+                }
+            }
+            (_, Linea::Fin { fin: true, filas }) => {
+                carga.insertar(&mut tx).await.map_err(ErrorRestauracion::BaseDatos)?;
+                if filas != carga.filas {
+                    return Err(invalida(format!("anuncia {} filas y trae {}", filas, carga.filas)));
+                }
+                tx.query_drop("SET FOREIGN_KEY_CHECKS = 1").await.map_err(ErrorRestauracion::BaseDatos)?;
+                tx.commit().await.map_err(ErrorRestauracion::BaseDatos)?;
+                return Ok(filas);
+            }
+            (_, _) => return Err(invalida("línea inesperada".to_string())),
+        }
+    }
+    Err(ErrorRestauracion::Formato(numero, "el respaldo está cortado: falta la línea final".to_string()))
+}
+
+/// Filas de la tabla en curso a la espera de insertarse.
+#[derive(Default)]
+struct Carga {
+    tabla: Option<&'static str>,
+    columnas: Vec<String>,
+    pendientes: Vec<Vec<Value>>,
+    filas: u64,
+}
+
+impl Carga {
+    /// Empieza `tabla`, que debe ser una de [`TABLAS`], con columnas de nombre simple: van
+    /// tal cual en la sentencia.
+    fn empezar(&mut self, tabla: String, columnas: Vec<String>) -> Result<(), String> {
+        let tabla = TABLAS
+            .iter()
+            .find(|conocida| **conocida == tabla)
+            .ok_or_else(|| format!("tabla {} desconocida", tabla))?;
+        let simple = |nombre: &String| !nombre.is_empty() && nombre.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if columnas.is_empty() || !columnas.iter().all(simple) {
+            return Err(format!("columnas inválidas en la tabla {}", tabla));
+        }
+        self.tabla = Some(tabla);
+        self.columnas = columnas;
+        Ok(())
+    }
+
+    fn anotar(&mut self, valores: &[serde_json::Value]) -> Result<(), String> {
+        if self.tabla.is_none() {
+            return Err("fila fuera de una tabla".to_string());
+        }
+        if valores.len() != self.columnas.len() {
+            return Err(format!("la fila tiene {} valores y la tabla {} columnas", valores.len(), self.columnas.len()));
+        }
+        self.pendientes.push(valores.iter().map(desde_json).collect::<Result<_, _>>()?);
+        self.filas += 1;
+        Ok(())
+    }
+
+    async fn insertar(&mut self, tx: &mut mysql_async::Transaction<'_>) -> Result<(), mysql_async::Error> {
+        let (Some(tabla), false) = (self.tabla, self.pendientes.is_empty()) else {
+            return Ok(());
+        };
+        let fila = format!("({})", vec!["?"; self.columnas.len()].join(", "));
+        let filas = vec![fila.as_str(); self.pendientes.len()].join(", ");
+        let columnas: Vec<String> = self.columnas.iter().map(|columna| format!("`{}`", columna)).collect();
+        let valores: Vec<Value> = std::mem::take(&mut self.pendientes).into_iter().flatten().collect();
+        tx.exec_drop(format!("INSERT INTO {} ({}) VALUES {}", tabla, columnas.join(", "), filas), valores)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lee_cada_tipo_de_linea() {
+        let cabecera = r#"{"formato":"rust-crud","version":1,"esquema":23,"creado_en":"2024-03-01T18:30:00Z"}"#;
+        assert!(matches!(serde_json::from_str(cabecera), Ok(Linea::Cabecera(Cabecera { esquema: 23, .. }))));
+        let tabla: Linea = serde_json::from_str(r#"{"tabla":"cines","columnas":["id","nombre"]}"#).unwrap();
+        assert_eq!(tabla, Linea::Tabla { tabla: "cines".into(), columnas: vec!["id".into(), "nombre".into()] });
+        assert_eq!(serde_json::from_str::<Linea>(r#"[1,"Principal",null]"#).unwrap(), Linea::Fila(vec![1.into(), "Principal".into(), serde_json::Value::Null]));
+        assert_eq!(serde_json::from_str::<Linea>(r#"{"fin":true,"filas":3}"#).unwrap(), Linea::Fin { fin: true, filas: 3 });
+    }
+
+    #[test]
+    fn solo_carga_tablas_y_columnas_conocidas() {
+        let mut carga = Carga::default();
+        assert!(carga.anotar(&[1.into()]).is_err());
+        assert!(carga.empezar("schema_version".into(), vec!["version".into()]).is_err());
+        assert!(carga.empezar("cines".into(), vec!["id`; DROP TABLE cines; --".into()]).is_err());
+        carga.empezar("cines".into(), vec!["id".into(), "nombre".into()]).unwrap();
+        assert!(carga.anotar(&[1.into()]).is_err());
+        carga.anotar(&[1.into(), "Principal".into()]).unwrap();
+        assert_eq!(carga.pendientes, vec![vec![Value::from(1), Value::from("Principal")]]);
+    }
+
+    #[test]
+    fn los_enteros_van_como_numeros_y_el_resto_como_texto() {
+        assert_eq!(a_json(Value::from("42"), ColumnType::MYSQL_TYPE_LONG), serde_json::json!(42));
+        assert_eq!(a_json(Value::from("13.50"), ColumnType::MYSQL_TYPE_NEWDECIMAL), serde_json::json!("13.50"));
+        assert_eq!(a_json(Value::NULL, ColumnType::MYSQL_TYPE_LONG), serde_json::Value::Null);
+        assert_eq!(desde_json(&serde_json::json!(42)), Ok(Value::from(42)));
+        assert!(desde_json(&serde_json::json!([1])).is_err());
+    }
+}
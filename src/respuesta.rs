@@ -0,0 +1,249 @@
+//! Sobre común de las respuestas correctas: `{"data": ..., "meta": {...}}`.
+//!
+//! `meta` lleva el identificador de la petición, que también viaja en la cabecera
+//! `X-Request-Id`, los instantes de recepción y de respuesta en milisegundos desde la época
+//! Unix y, en los listados, la paginación con los enlaces a las páginas vecinas. Cuando `data` es un mensaje, va en el idioma de
+//! la petición (ver [`crate::i18n`]). Los errores conservan su formato (`{"error": ...}`),
+//! y las sondas y `/metrics` responden sin sobre.
+
+use std::any::Any;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::LazyLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::http::StatusCode;
+use actix_web::middleware::Next;
+use actix_web::{Error, HttpMessage, HttpRequest, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::enlaces::EnlacesPagina;
+use crate::i18n::Idioma;
+
+/// Cabecera con el identificador de la petición, en la petición y en la respuesta.
+pub const CABECERA_ID_PETICION: &str = "x-request-id";
+
+/// Longitud máxima de un `X-Request-Id` recibido para reutilizarlo.
+const LONGITUD_MAXIMA_ID: usize = 128;
+
+/// Identificador y hora de llegada de la petición, guardados en sus extensiones.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DatosPeticion {
+    pub id: String,
+    pub recibida_en: u64,
+}
+
+/// Paginación aplicada a un listado.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Paginacion {
+    pub pagina: u32,
+    pub por_pagina: u32,
+    pub total: u64,
+}
+
+/// Paginación por cursor aplicada a un listado. `next_cursor` falta en la última página.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PaginacionCursor {
+    pub por_pagina: u32,
+    pub next_cursor: Option<String>,
+}
+
+/// Metadatos de una respuesta.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Meta {
+    /// Vacío si la aplicación no monta [`identificar_peticion`].
+    pub id_peticion: String,
+    pub recibida_en: u64,
+    pub respondida_en: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub paginacion: Option<Paginacion>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<PaginacionCursor>,
+    /// En los listados, las rutas de sus páginas (ver [`crate::enlaces`]).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub links: Option<EnlacesPagina>,
+    /// La respuesta describe una escritura simulada que no se guardó (ver [`crate::simulacion`]).
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub simulacion: bool,
+}
+
+impl Meta {
+    /// Metadatos de la respuesta a `req`, generada en este instante.
+    pub fn de(req: &HttpRequest, paginacion: Option<Paginacion>) -> Meta {
+        let respondida_en = ahora_ms();
+        let (id_peticion, recibida_en) = match req.extensions().get::<DatosPeticion>() {
+            Some(datos) => (datos.id.clone(), datos.recibida_en),
+            None => (String::new(), respondida_en),
+        };
+        let links = paginacion.map(|paginacion| EnlacesPagina::por_numero(req.path(), req.query_string(), &paginacion));
+        Meta { id_peticion, recibida_en, respondida_en, paginacion, cursor: None, links, simulacion: false }
+    }
+
+    /// Metadatos de una página por cursor de la respuesta a `req`.
+    pub fn con_cursor(req: &HttpRequest, cursor: PaginacionCursor) -> Meta {
+        let links = EnlacesPagina::por_cursor(req.path(), req.query_string(), cursor.next_cursor.as_deref());
+        Meta { cursor: Some(cursor), links: Some(links), ..Meta::de(req, None) }
+    }
+}
+
+/// Respuesta correcta de la API. Como `Responder` completa `meta` con los datos de la
+/// petición, así que los handlers sólo indican el estado, los datos y la paginación.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiResponse<T> {
+    pub data: T,
+    pub meta: Meta,
+    #[serde(skip)]
+    estado: StatusCode,
+    #[serde(skip)]
+    paginacion: Option<Paginacion>,
+    #[serde(skip)]
+    cursor: Option<PaginacionCursor>,
+    #[serde(skip)]
+    simulacion: bool,
+}
+
+impl<T> ApiResponse<T> {
+    pub fn con_estado(estado: StatusCode, data: T) -> Self {
+        ApiResponse {
+            data,
+            meta: Meta {
+                id_peticion: String::new(),
+                recibida_en: 0,
+                respondida_en: 0,
+                paginacion: None,
+                cursor: None,
+                links: None,
+                simulacion: false,
+            },
+            estado,
+            paginacion: None,
+            cursor: None,
+            simulacion: false,
+        }
+    }
+
+    /// Respuesta 200.
+    pub fn ok(data: T) -> Self {
+        Self::con_estado(StatusCode::OK, data)
+    }
+
+    /// Respuesta 201.
+    pub fn creada(data: T) -> Self {
+        Self::con_estado(StatusCode::CREATED, data)
+    }
+
+    pub fn paginada(mut self, paginacion: Paginacion) -> Self {
+        self.paginacion = Some(paginacion);
+        self
+    }
+
+    pub fn con_cursor(mut self, cursor: PaginacionCursor) -> Self {
+        self.cursor = Some(cursor);
+        self
+    }
+
+    /// Marca la respuesta como el resultado de una escritura simulada.
+    pub fn simulada(mut self) -> Self {
+        self.simulacion = true;
+        self
+    }
+}
+
+impl<T: Serialize + 'static> Responder for ApiResponse<T> {
+    type Body = BoxBody;
+
+    fn respond_to(mut self, req: &HttpRequest) -> HttpResponse {
+        let meta = match self.cursor.take() {
+            Some(cursor) => Meta::con_cursor(req, cursor),
+            None => Meta::de(req, self.paginacion),
+        };
+        self.meta = Meta { simulacion: self.simulacion, ..meta };
+        let idioma = Idioma::de_peticion(req);
+        match (&self.data as &dyn Any).downcast_ref::<&'static str>() {
+            Some(mensaje) if idioma != Idioma::Es => {
+                HttpResponse::build(self.estado).json(json!({ "data": idioma.traducir(mensaje), "meta": self.meta }))
+            }
+            _ => HttpResponse::build(self.estado).json(&self),
+        }
+    }
+}
+
+fn ahora_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or_default()
+}
+
+/// Reutiliza el `X-Request-Id` del cliente o de un proxy si es razonable; si no, genera
+/// uno con la hora de arranque del proceso y un contador, único aunque haya varios workers.
+fn id_peticion(recibido: Option<&HeaderValue>) -> String {
+    static ARRANQUE: LazyLock<u64> = LazyLock::new(ahora_ms);
+    static CONTADOR: AtomicU64 = AtomicU64::new(0);
+
+    let valido = |id: &&str| {
+        !id.is_empty()
+            && id.len() <= LONGITUD_MAXIMA_ID
+            && id.bytes().all(|b| b.is_ascii_alphanumeric() || b"-_.:".contains(&b))
+    };
+    match recibido.and_then(|v| v.to_str().ok()).filter(valido) {
+        Some(id) => id.to_string(),
+        None => format!("{:x}-{:x}", *ARRANQUE, CONTADOR.fetch_add(1, Ordering::Relaxed)),
+    }
+}
+
+/// Middleware que asigna el identificador a la petición y lo devuelve en `X-Request-Id`.
+pub async fn identificar_peticion(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let cabecera = HeaderName::from_static(CABECERA_ID_PETICION);
+    let id = id_peticion(req.headers().get(&cabecera));
+    let valor = HeaderValue::from_str(&id);
+    req.extensions_mut().insert(DatosPeticion { id, recibida_en: ahora_ms() });
+    let mut respuesta = next.call(req).await?;
+    if let Ok(valor) = valor {
+        respuesta.headers_mut().insert(cabecera, valor);
+    }
+    Ok(respuesta)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::middleware::from_fn;
+    use actix_web::test::{call_service, init_service, read_body_json, TestRequest};
+    use actix_web::{web, App};
+
+    #[actix_web::test]
+    async fn envuelve_los_datos_con_el_id_de_la_peticion() {
+        let app = init_service(
+            App::new()
+                .route(
+                    "/",
+                    web::get().to(|| async {
+                        ApiResponse::ok(vec![1, 2]).paginada(Paginacion { pagina: 1, por_pagina: 2, total: 5 })
+                    }),
+                )
+                .wrap(from_fn(identificar_peticion)),
+        )
+        .await;
+
+        let res = call_service(&app, TestRequest::get().uri("/").to_request()).await;
+        let id = res.headers().get(CABECERA_ID_PETICION).unwrap().to_str().unwrap().to_string();
+        let cuerpo: ApiResponse<Vec<u32>> = read_body_json(res).await;
+        assert_eq!(cuerpo.data, [1, 2]);
+        assert_eq!(cuerpo.meta.id_peticion, id);
+        assert!(cuerpo.meta.recibida_en <= cuerpo.meta.respondida_en);
+        assert_eq!(cuerpo.meta.paginacion.unwrap().total, 5);
+        assert_eq!(cuerpo.meta.links.unwrap().next.as_deref(), Some("/?page=2"));
+
+        let req = TestRequest::get().uri("/").insert_header((CABECERA_ID_PETICION, "abc-123")).to_request();
+        let cuerpo: ApiResponse<Vec<u32>> = read_body_json(call_service(&app, req).await).await;
+        assert_eq!(cuerpo.meta.id_peticion, "abc-123");
+
+        let req = TestRequest::get().uri("/").insert_header((CABECERA_ID_PETICION, "con espacios")).to_request();
+        let cuerpo: ApiResponse<Vec<u32>> = read_body_json(call_service(&app, req).await).await;
+        assert_ne!(cuerpo.meta.id_peticion, "con espacios");
+    }
+}
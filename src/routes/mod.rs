@@ -0,0 +1,71 @@
+//! Definición de las rutas de la API.
+//!
+//! Las rutas de negocio van bajo un prefijo de versión; cada versión es un submódulo que
+//! registra sus propios handlers, para que una `/v2` con otros DTO pueda convivir con
+//! `/v1`. Las sondas, las métricas, el panel de administración en HTML y los pósters no
+//! llevan versión.
+//!
+//! Las rutas anteriores a `/v1` (`/entradas`, `/auth`...) siguen respondiendo como alias
+//! obsoletos de `/v1`, con las cabeceras `Deprecation` y `Link` hacia su sucesora.
+
+pub mod v1;
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue, LINK};
+use actix_web::middleware::{from_fn, Next};
+use actix_web::{web, Error};
+
+/// Registra las rutas de la API sobre la configuración de la aplicación.
+pub fn configurar_rutas(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::scope("/v1").configure(v1::configurar));
+
+    cfg.route("/health", web::get().to(crate::salud::vida));
+    cfg.route("/ready", web::get().to(crate::salud::disponibilidad));
+    cfg.route("/metrics", web::get().to(crate::metricas::exportar_metricas));
+    cfg.configure(crate::panel::configurar);
+    cfg.route(
+        &format!("{}/{{archivo}}", crate::posters::RUTA),
+        web::get().to(crate::posters::servir_poster),
+    );
+
+    for (prefijo, grupo) in v1::GRUPOS {
+        cfg.service(web::scope(prefijo).wrap(from_fn(marcar_obsoleta)).configure(*grupo));
+    }
+}
+
+/// Middleware de las rutas sin versión: las marca como obsoletas e indica la ruta de `/v1`.
+async fn marcar_obsoleta(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let sucesora = HeaderValue::from_str(&format!("</v1{}>; rel=\"successor-version\"", req.path()));
+    let mut respuesta = next.call(req).await?;
+    let cabeceras = respuesta.headers_mut();
+    cabeceras.insert(HeaderName::from_static("deprecation"), HeaderValue::from_static("true"));
+    if let Ok(sucesora) = sucesora {
+        cabeceras.insert(LINK, sucesora);
+    }
+    Ok(respuesta)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::{call_service, init_service, TestRequest};
+    use actix_web::App;
+
+    #[actix_web::test]
+    async fn las_rutas_sin_version_son_alias_obsoletos() {
+        let app = init_service(App::new().configure(configurar_rutas)).await;
+
+        let res = call_service(&app, TestRequest::post().uri("/auth/logout").to_request()).await;
+        assert_ne!(res.status(), 404);
+        assert_eq!(res.headers().get("deprecation").unwrap(), "true");
+        assert_eq!(res.headers().get(LINK).unwrap(), "</v1/auth/logout>; rel=\"successor-version\"");
+
+        let res = call_service(&app, TestRequest::post().uri("/v1/auth/logout").to_request()).await;
+        assert_ne!(res.status(), 404);
+        assert!(res.headers().get("deprecation").is_none());
+    }
+}
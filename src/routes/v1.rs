@@ -0,0 +1,234 @@
+//! Rutas de la versión 1 de la API, con los DTO de [`crate::models`].
+//!
+//! Cada grupo se registra bajo `/v1` y, mientras dure la transición, también en su ruta
+//! sin versión (ver [`super::configurar_rutas`]).
+
+use actix_web::middleware::from_fn;
+use actix_web::web;
+
+use crate::dispositivos::{
+    obtener_dispositivo, obtener_dispositivos, registrar_dispositivo, registrar_latido,
+};
+use crate::handlers::*;
+
+/// Función que registra un grupo de rutas dentro de su prefijo.
+pub type Grupo = fn(&mut web::ServiceConfig);
+
+/// Prefijo de cada grupo de rutas y la función que lo registra.
+pub const GRUPOS: &[(&str, Grupo)] = &[
+    ("/auth", auth),
+    ("/clientes", clientes),
+    ("/entradas", entradas),
+    ("/funciones", funciones),
+    ("/salas", salas),
+    ("/dispositivos", dispositivos),
+    ("/autocomplete", autocompletado),
+    ("/reportes", reportes),
+    ("/admin", admin),
+    ("/ws", ws),
+    ("/graphql", graphql),
+];
+
+/// Registra todos los grupos, sin prefijo de versión.
+pub fn configurar(cfg: &mut web::ServiceConfig) {
+    for (prefijo, grupo) in GRUPOS {
+        cfg.service(web::scope(prefijo).configure(*grupo));
+    }
+}
+
+fn auth(cfg: &mut web::ServiceConfig) {
+    cfg.route("/login", web::post().to(crate::auth::iniciar_sesion))
+        .route("/register", web::post().to(crate::auth::registrar_usuario))
+        .route("/refresh", web::post().to(crate::auth::refrescar_sesion))
+        .route("/logout", web::post().to(crate::auth::cerrar_sesion));
+}
+
+/// Clientes por cédula, con token o clave de API. Modificarlos o eliminarlos es sólo para
+/// administradores.
+fn clientes(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("")
+            .wrap(from_fn(crate::auth::exigir_autenticacion))
+            .route("", web::get().to(crate::clientes::listar_clientes))
+            .route("", web::post().to(crate::clientes::crear_cliente))
+            .route("/{cedula}", web::get().to(crate::clientes::obtener_cliente))
+            .route("/{cedula}", web::put().to(crate::clientes::actualizar_cliente))
+            .route("/{cedula}", web::delete().to(crate::clientes::eliminar_cliente)),
+    );
+}
+
+/// Todas las rutas de entradas, con token o clave de API. `PUT /{id}` reemplaza la
+/// entrada y exige todos sus campos; `PATCH /{id}` cambia sólo los que se envían. Ambos,
+/// y `DELETE /{id}`, exigen en `If-Match` el `ETag` que devuelve `GET /{id}`.
+/// `GET /`, `GET /buscar` y `GET /{id}` aceptan `fields` para devolver sólo esos campos.
+/// `GET /count` y `HEAD /` cuentan las entradas del listado con sus mismos filtros.
+/// `GET /buscar?q=` busca por parte del nombre del cliente o del título de la función.
+/// `PUT /cedula/{numero_cedula}` crea o actualiza la entrada de un cliente.
+/// `GET /export?format=csv|xlsx` exporta el listado completo, con sus mismos filtros, y
+/// `POST /import` carga entradas desde un CSV.
+/// `GET /{id}/historial` lista los cambios de la entrada registrados en la auditoría.
+/// `GET /{id}/ticket.pdf` devuelve su ticket imprimible.
+/// `POST /{id}/pagar`, `/{id}/checkin` (con el código QR) y `/{id}/cancelar` cambian su estado.
+/// `GET /stream` avisa los cambios de las entradas por Server-Sent Events.
+/// `POST /`, `POST /bulk`, `PUT /cedula/{numero_cedula}`, `PUT /{id}` y `PATCH /{id}`
+/// aceptan `dry_run=true` para comprobar la escritura sin guardarla (ver [`crate::simulacion`]).
+fn entradas(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("")
+            .wrap(from_fn(crate::auth::exigir_autenticacion))
+            .route("", web::get().to(obtener_entradas))
+            .route("", web::head().to(contar_entradas_cabecera))
+            .route("", web::post().to(crear_entrada))
+            .route("", web::delete().to(eliminar_entradas))
+            .route("/bulk", web::post().to(crear_entradas_lote))
+            .route("/count", web::get().to(contar_entradas))
+            .route("/export", web::get().to(exportar_entradas))
+            .route("/import", web::post().to(importar_entradas))
+            .route("/agregado", web::get().to(obtener_agregado))
+            .route("/buscar", web::get().to(buscar_entradas))
+            .route(
+                "/buscar-aproximado",
+                web::get().to(crate::busqueda_aproximada::buscar_cliente_aproximado),
+            )
+            .route("/cedula/{numero_cedula}", web::put().to(guardar_entrada_por_cedula))
+            .route("/stream", web::get().to(crate::cambios::transmitir_cambios))
+            .route("/{id}", web::get().to(obtener_entrada_por_id))
+            .route("/{id}/historial", web::get().to(obtener_historial))
+            .route("/{id}/ticket.pdf", web::get().to(obtener_ticket))
+            .route("/{id}/pagar", web::post().to(pagar_entrada))
+            .route("/{id}/checkin", web::post().to(checkin_entrada))
+            .route("/{id}/cancelar", web::post().to(cancelar_entrada))
+            .route("/{id}", web::put().to(reemplazar_entrada))
+            .route("/{id}", web::patch().to(actualizar_entrada))
+            .route("/{id}", web::delete().to(eliminar_entrada)),
+    );
+}
+
+/// Funciones de cine, con token o clave de API. Modificarlas es sólo para administradores,
+/// igual que subir su póster con `POST /{id}/poster` y cancelar todas sus entradas con
+/// `POST /{id}/cancelar`.
+fn funciones(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("")
+            .wrap(from_fn(crate::auth::exigir_autenticacion))
+            .route("", web::get().to(crate::funciones::listar_funciones))
+            .route("", web::post().to(crate::funciones::crear_funcion))
+            .route("/{id}", web::get().to(crate::funciones::obtener_funcion))
+            .route("/{id}/asientos", web::get().to(crate::funciones::listar_asientos))
+            .route("/{id}/poster", web::post().to(crate::posters::subir_poster))
+            .route("/{id}/cancelar", web::post().to(cancelar_funcion))
+            .route("/{id}", web::put().to(crate::funciones::reemplazar_funcion))
+            .route("/{id}", web::delete().to(crate::funciones::eliminar_funcion)),
+    );
+}
+
+/// Salas de cine, con token o clave de API. Modificarlas es sólo para administradores.
+fn salas(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("")
+            .wrap(from_fn(crate::auth::exigir_autenticacion))
+            .route("", web::get().to(crate::salas::listar_salas))
+            .route("", web::post().to(crate::salas::crear_sala))
+            .route("/{id}", web::get().to(crate::salas::obtener_sala))
+            .route("/{id}", web::put().to(crate::salas::reemplazar_sala))
+            .route("/{id}", web::delete().to(crate::salas::eliminar_sala)),
+    );
+}
+
+fn dispositivos(cfg: &mut web::ServiceConfig) {
+    cfg.route("", web::get().to(obtener_dispositivos))
+        .route("", web::post().to(registrar_dispositivo))
+        .route("/{id}", web::get().to(obtener_dispositivo))
+        .route("/{id}/latido", web::post().to(registrar_latido));
+}
+
+fn autocompletado(cfg: &mut web::ServiceConfig) {
+    cfg.route("", web::get().to(crate::autocompletado::autocompletar));
+}
+
+/// Reportes de ventas, sólo para administradores.
+fn reportes(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("")
+            .wrap(from_fn(crate::auth::exigir_autenticacion))
+            .route("/ventas", web::get().to(crate::reportes::reporte_ventas))
+            .route("/top-funciones", web::get().to(crate::reportes::top_funciones)),
+    );
+}
+
+/// WebSockets, sin autenticación: `/funciones/{id}` avisa la ocupación de la función.
+fn ws(cfg: &mut web::ServiceConfig) {
+    cfg.route("/funciones/{id}", web::get().to(crate::disponibilidad::conectar_asientos));
+}
+
+/// API GraphQL, con token o clave de API, y su Playground sin autenticación si está activado.
+fn graphql(cfg: &mut web::ServiceConfig) {
+    cfg.route("/playground", web::get().to(crate::graphql::playground)).service(
+        web::scope("")
+            .wrap(from_fn(crate::auth::exigir_autenticacion))
+            .route("", web::post().to(crate::graphql::ejecutar_graphql)),
+    );
+}
+
+fn admin(cfg: &mut web::ServiceConfig) {
+    cfg.route("/slo", web::get().to(crate::slo::obtener_slo)).service(
+        web::scope("/api-keys")
+            .wrap(from_fn(crate::auth::exigir_autenticacion))
+            .route("", web::get().to(crate::claves_api::listar_claves))
+            .route("", web::post().to(crate::claves_api::crear_clave))
+            .route("/{id}", web::delete().to(crate::claves_api::revocar_clave)),
+    )
+    .service(
+        web::scope("/cines")
+            .wrap(from_fn(crate::auth::exigir_autenticacion))
+            .route("", web::get().to(crate::cines::listar_cines))
+            .route("", web::post().to(crate::cines::crear_cine))
+            .route("/{id}", web::put().to(crate::cines::renombrar_cine))
+            .route("/{id}", web::delete().to(crate::cines::eliminar_cine)),
+    )
+    .service(
+        web::scope("/tareas")
+            .wrap(from_fn(crate::auth::exigir_autenticacion))
+            .route("", web::get().to(crate::tareas::listar_tareas)),
+    )
+    .service(
+        web::scope("/pool")
+            .wrap(from_fn(crate::auth::exigir_autenticacion))
+            .route("", web::get().to(crate::metricas::estado_pool)),
+    )
+    .service(
+        web::scope("/webhooks")
+            .wrap(from_fn(crate::auth::exigir_autenticacion))
+            .route("", web::get().to(crate::webhooks::listar_webhooks))
+            .route("", web::post().to(crate::webhooks::crear_webhook))
+            .route("/{id}", web::delete().to(crate::webhooks::desactivar_webhook))
+            .route("/{id}/entregas", web::get().to(crate::webhooks::listar_entregas)),
+    )
+    .service(
+        web::scope("/accesos")
+            .wrap(from_fn(crate::auth::exigir_autenticacion))
+            .route("", web::get().to(crate::accesos::listar_accesos)),
+    )
+    .service(
+        web::scope("/archivo")
+            .wrap(from_fn(crate::auth::exigir_autenticacion))
+            .route("", web::get().to(crate::archivo::listar_archivadas))
+            .route("", web::post().to(crate::archivo::archivar_ahora)),
+    )
+    .service(
+        web::scope("/entradas")
+            .wrap(from_fn(crate::auth::exigir_autenticacion))
+            .route("/purge", web::delete().to(crate::purga::purgar_entradas)),
+    )
+    .service(
+        web::scope("/backup")
+            .wrap(from_fn(crate::auth::exigir_autenticacion))
+            .route("", web::post().to(crate::respaldo::crear_respaldo)),
+    );
+
+    #[cfg(feature = "debug-explain")]
+    cfg.service(
+        web::scope("/debug")
+            .route("/explain", web::post().to(crate::depuracion::explicar_consulta)),
+    );
+}
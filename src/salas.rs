@@ -0,0 +1,160 @@
+//! Salas (`/salas`) del cine de la petición: nombre, único en el cine, y capacidad.
+//!
+//! Cada función referencia su sala por `sala_id` y toma de ella su capacidad, así que una
+//! sala con funciones no se puede eliminar.
+
+use actix_web::{web, HttpResponse};
+use mysql_async::{prelude::*, Pool};
+
+use crate::auth::Administrador;
+use crate::cache::CacheLecturas;
+use crate::cines::CineActual;
+use crate::config::Config;
+use crate::db;
+use crate::db::errores::{self, FalloMysql};
+use crate::error::ApiError;
+use crate::json::Json;
+use crate::models::{CrearSala, Sala};
+use crate::respuesta::ApiResponse;
+use crate::validacion::Validar;
+
+const SELECT_SALAS: &str = "SELECT id, nombre, capacidad FROM salas";
+
+fn no_encontrada() -> ApiError {
+    ApiError::NoEncontrado("Sala no encontrada".to_string())
+}
+
+/// Clasifica un error al escribir una sala, distinguiendo los nombres repetidos.
+fn error_escritura(mensaje: &'static str) -> impl FnOnce(mysql_async::Error) -> ApiError {
+    move |e| match errores::clasificar(&e) {
+        FalloMysql::Duplicado(restriccion) => ApiError::Duplicado {
+            mensaje: "Ya existe una sala con ese nombre en el cine".to_string(),
+            restriccion,
+        },
+        _ => ApiError::escritura(mensaje)(e),
+    }
+}
+
+fn validar(datos: &CrearSala, config: &Config) -> Result<(), ApiError> {
+    datos.validar(&config.reglas_validacion()).map_err(ApiError::CamposInvalidos)
+}
+
+/// Handler que lista las salas por nombre.
+pub async fn listar_salas(CineActual(cine): CineActual, pool: web::Data<Pool>) -> Result<ApiResponse<Vec<Sala>>, ApiError> {
+    let mut conn = db::conectar(&pool).await.map_err(ApiError::conexion)?;
+    let salas = leer_salas(&mut conn, cine).await.map_err(ApiError::base_datos("Error al obtener salas"))?;
+    Ok(ApiResponse::ok(salas))
+}
+
+/// Salas de `cine` por nombre.
+pub(crate) async fn leer_salas(conn: &mut db::Conexion, cine: u32) -> Result<Vec<Sala>, mysql_async::Error> {
+    conn.exec(format!("{} WHERE cine_id = :cine_id ORDER BY nombre", SELECT_SALAS), params! { "cine_id" => cine })
+        .await
+}
+
+/// Handler para obtener una sala por su ID.
+pub async fn obtener_sala(
+    CineActual(cine): CineActual,
+    pool: web::Data<Pool>,
+    id: web::Path<u32>,
+) -> Result<ApiResponse<Sala>, ApiError> {
+    let mut conn = db::conectar(&pool).await.map_err(ApiError::conexion)?;
+    conn.exec_first(
+        format!("{} WHERE id = :id AND cine_id = :cine_id", SELECT_SALAS),
+        params! { "id" => id.into_inner(), "cine_id" => cine },
+    )
+        .await
+        .map_err(ApiError::base_datos("Error al obtener sala"))?
+        .map(ApiResponse::ok)
+        .ok_or_else(no_encontrada)
+}
+
+/// Handler para crear una sala. Sólo para administradores.
+pub async fn crear_sala(
+    _admin: Administrador,
+    CineActual(cine): CineActual,
+    pool: web::Data<Pool>,
+    config: web::Data<Config>,
+    datos: Json<CrearSala>,
+) -> Result<ApiResponse<Sala>, ApiError> {
+    let datos = datos.into_inner();
+    validar(&datos, &config)?;
+
+    let mut conn = db::conectar(&pool).await.map_err(ApiError::conexion)?;
+    conn.exec_drop(
+        "INSERT INTO salas (cine_id, nombre, capacidad) VALUES (:cine_id, :nombre, :capacidad)",
+        params! { "cine_id" => cine, "nombre" => &datos.nombre, "capacidad" => datos.capacidad },
+    )
+    .await
+    .map_err(error_escritura("Error al crear sala"))?;
+
+    let CrearSala { nombre, capacidad } = datos;
+    let id = conn.last_insert_id().unwrap_or_default() as u32;
+    Ok(ApiResponse::creada(Sala { id, nombre, capacidad }))
+}
+
+/// Handler que reemplaza el nombre y la capacidad de una sala. Sólo para administradores.
+/// Sus funciones pasan a tener la capacidad nueva.
+#[allow(clippy::too_many_arguments)]
+pub async fn reemplazar_sala(
+    _admin: Administrador,
+    CineActual(cine): CineActual,
+    pool: web::Data<Pool>,
+    cache: web::Data<CacheLecturas>,
+    config: web::Data<Config>,
+    id: web::Path<u32>,
+    datos: Json<CrearSala>,
+) -> Result<ApiResponse<Sala>, ApiError> {
+    let id = id.into_inner();
+    let datos = datos.into_inner();
+    validar(&datos, &config)?;
+
+    let mut conn = db::conectar(&pool).await.map_err(ApiError::conexion)?;
+    let existe: Option<u32> = conn
+        .exec_first(
+            "SELECT id FROM salas WHERE id = :id AND cine_id = :cine_id",
+            params! { "id" => id, "cine_id" => cine },
+        )
+        .await
+        .map_err(ApiError::base_datos("Error al actualizar sala"))?;
+    if existe.is_none() {
+        return Err(no_encontrada());
+    }
+    conn.exec_drop(
+        "UPDATE salas SET nombre = :nombre, capacidad = :capacidad WHERE id = :id",
+        params! { "id" => id, "nombre" => &datos.nombre, "capacidad" => datos.capacidad },
+    )
+    .await
+    .map_err(error_escritura("Error al actualizar sala"))?;
+    cache.invalidar_catalogo().await;
+
+    let CrearSala { nombre, capacidad } = datos;
+    Ok(ApiResponse::ok(Sala { id, nombre, capacidad }))
+}
+
+/// Handler que elimina una sala sin funciones. Sólo para administradores.
+pub async fn eliminar_sala(
+    _admin: Administrador,
+    CineActual(cine): CineActual,
+    pool: web::Data<Pool>,
+    id: web::Path<u32>,
+) -> Result<HttpResponse, ApiError> {
+    let mut conn = db::conectar(&pool).await.map_err(ApiError::conexion)?;
+    let resultado = conn
+        .exec_drop(
+            "DELETE FROM salas WHERE id = :id AND cine_id = :cine_id",
+            params! { "id" => id.into_inner(), "cine_id" => cine },
+        )
+        .await;
+    match resultado {
+        Ok(()) if conn.affected_rows() == 0 => Err(no_encontrada()),
+        Ok(()) => Ok(HttpResponse::NoContent().finish()),
+        Err(e) => Err(match errores::clasificar(&e) {
+            FalloMysql::Referenciada(restriccion) => ApiError::EnUso {
+                mensaje: "La sala todavía tiene funciones".to_string(),
+                restriccion,
+            },
+            _ => ApiError::escritura("Error al eliminar sala")(e),
+        }),
+    }
+}
@@ -0,0 +1,55 @@
+//! Sondas de vida y disponibilidad para el orquestador.
+
+use std::time::{Duration, Instant};
+
+use actix_web::rt::time::timeout;
+use actix_web::{web, HttpResponse};
+use mysql_async::{prelude::*, Pool};
+use serde_json::json;
+
+use crate::config::Config;
+use crate::db;
+
+/// Tiempo máximo que `/ready` espera a la base de datos antes de darla por caída.
+const ESPERA_PING: Duration = Duration::from_secs(2);
+
+/// Handler de `/health`: el proceso está vivo y atiende peticiones.
+pub async fn vida() -> HttpResponse {
+    HttpResponse::Ok().json(json!({ "estado": "ok" }))
+}
+
+/// Handler de `/ready`: hace `SELECT 1` en la pool y responde 503 si la base de datos no contesta.
+/// Se prueba la primaria aunque haya réplica, porque sin ella no se puede escribir.
+pub async fn disponibilidad(pool: web::Data<Pool>, config: web::Data<Config>) -> HttpResponse {
+    let inicio = Instant::now();
+    let ping = timeout(ESPERA_PING, async {
+        let mut conn = db::conectar_primaria(&pool).await?;
+        conn.query_drop("SELECT 1").await
+    })
+    .await;
+    let latencia_ms = inicio.elapsed().as_millis() as u64;
+
+    let error = match ping {
+        Ok(Ok(())) => None,
+        Ok(Err(e)) => Some(e.to_string()),
+        Err(_) => Some(format!("Sin respuesta en {} ms", ESPERA_PING.as_millis())),
+    };
+    let cuerpo = json!({
+        "estado": if error.is_none() { "ok" } else { "no_disponible" },
+        "base_datos": {
+            "latencia_ms": latencia_ms,
+            "error": error,
+        },
+        "pool": {
+            "minimo": config.pool_min,
+            "maximo": config.pool_max,
+        },
+    });
+    match error {
+        None => HttpResponse::Ok().json(cuerpo),
+        Some(e) => {
+            log::warn!("La base de datos no responde a /ready: {}", e);
+            HttpResponse::ServiceUnavailable().json(cuerpo)
+        }
+    }
+}
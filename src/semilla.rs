@@ -0,0 +1,243 @@
+//! Datos de ejemplo para desarrollo y pruebas, que carga el subcomando `seed`.
+//!
+//! Primero se crean las salas de [`SALAS_EJEMPLO`], las funciones de [`FUNCIONES_EJEMPLO`]
+//! en ellas y los clientes de [`ENTRADAS_EJEMPLO`], y después una entrada por cliente,
+//! repartidas entre las funciones, todo en el [`CINE_PRINCIPAL`].
+//! Todas las cédulas son válidas con la regla de la cédula ecuatoriana.
+
+use std::fmt;
+
+use chrono::DateTime;
+use mysql_async::{prelude::*, Pool};
+
+use crate::cines::CINE_PRINCIPAL;
+use crate::db;
+use crate::models::{CrearCliente, CrearEntrada, CrearFuncion, CrearSala};
+use crate::servicio::{ErrorEntrada, ServicioEntradas};
+
+/// Salas de ejemplo: nombre y capacidad.
+pub const SALAS_EJEMPLO: &[(&str, u32)] = &[("Sala 1", 120), ("Sala 2", 80), ("Sala 3", 150), ("Sala 4", 100)];
+
+/// Funciones de ejemplo: título, posición de su sala en [`SALAS_EJEMPLO`], horario en
+/// RFC 3339 y precio.
+pub const FUNCIONES_EJEMPLO: &[(&str, usize, &str, f64)] = &[
+    ("Dune: Parte Dos", 0, "2024-03-01T19:00:00-05:00", 6.5),
+    ("Dune: Parte Dos", 0, "2024-03-01T21:30:00-05:00", 6.5),
+    ("Alien: Romulus", 1, "2024-03-01T17:15:00-05:00", 5.5),
+    ("Alien: Romulus", 1, "2024-03-01T22:00:00-05:00", 5.5),
+    ("Intensamente 2", 2, "2024-03-02T15:00:00-05:00", 4.5),
+    ("Intensamente 2", 2, "2024-03-02T17:30:00-05:00", 4.5),
+    ("Oppenheimer", 3, "2024-03-02T18:00:00-05:00", 7.0),
+    ("Oppenheimer", 3, "2024-03-02T20:45:00-05:00", 7.0),
+];
+
+/// Entradas de ejemplo: cédula, cliente, posición de su función en [`FUNCIONES_EJEMPLO`] y cantidad.
+pub const ENTRADAS_EJEMPLO: &[(&str, &str, usize, u32)] = &[
+    ("1710034065", "María Pérez", 0, 2),
+    ("0926687856", "Juan Andrade", 1, 4),
+    ("0102030400", "Lucía Vera", 2, 1),
+    ("1722601810", "Carlos Mendoza", 4, 3),
+    ("0929083012", "Ana Lucía Torres", 4, 5),
+    ("1309139093", "Diego Zambrano", 7, 2),
+    ("1846030821", "Gabriela Naranjo", 3, 2),
+    ("0726281942", "Andrés Ordóñez", 0, 1),
+    ("1142199353", "Valeria Jaramillo", 6, 6),
+    ("2308190939", "Santiago Cedeño", 5, 2),
+    ("1738657970", "Paola Guerrero", 3, 3),
+    ("0924323199", "José Luis Macías", 7, 1),
+];
+
+/// Actor con el que las entradas de ejemplo quedan en la auditoría.
+pub const ACTOR_SEMILLA: &str = "seed";
+
+/// Resultado de una carga de datos de ejemplo.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ResumenSemilla {
+    pub creadas: usize,
+    /// Entradas que ya estaban y se dejaron como estaban.
+    pub existentes: usize,
+}
+
+/// Errores de la carga de datos de ejemplo.
+#[derive(Debug)]
+pub enum ErrorSemilla {
+    /// La tabla ya tiene datos y no se pidió forzar la carga.
+    BaseNoVacia(u64),
+    BaseDatos(mysql_async::Error),
+    Entrada(ErrorEntrada),
+}
+
+impl fmt::Display for ErrorSemilla {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorSemilla::BaseNoVacia(total) => write!(
+                f,
+                "La base de datos ya tiene {} entradas; usa --force para cargar los datos de ejemplo igualmente",
+                total
+            ),
+            ErrorSemilla::BaseDatos(e) => write!(f, "Error de base de datos: {}", e),
+            ErrorSemilla::Entrada(e) => write!(f, "No se pudo crear una entrada de ejemplo: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ErrorSemilla {}
+
+fn sala_ejemplo(ejemplo: &(&str, u32)) -> CrearSala {
+    let (nombre, capacidad) = *ejemplo;
+    CrearSala { nombre: nombre.to_string(), capacidad }
+}
+
+fn funcion_ejemplo(ejemplo: &(&str, usize, &str, f64), salas: &[u32]) -> CrearFuncion {
+    let (titulo, sala, horario, precio) = *ejemplo;
+    CrearFuncion {
+        titulo: titulo.to_string(),
+        sala_id: salas[sala],
+        horario: DateTime::parse_from_rfc3339(horario).expect("Horario de ejemplo en RFC 3339"),
+        precio,
+    }
+}
+
+fn cliente_ejemplo(ejemplo: &(&str, &str, usize, u32)) -> CrearCliente {
+    let (numero_cedula, nombre, _, _) = *ejemplo;
+    CrearCliente { numero_cedula: numero_cedula.to_string(), nombre: nombre.to_string(), email: None }
+}
+
+fn entrada_ejemplo(ejemplo: &(&str, &str, usize, u32), cliente_id: u32, funciones: &[u32]) -> CrearEntrada {
+    let (_, _, funcion, cantidad_entradas) = *ejemplo;
+    CrearEntrada { cliente_id, funcion_id: funciones[funcion], cantidad_entradas, asientos: Vec::new() }
+}
+
+/// Crea las salas de ejemplo que falten y devuelve el ID de cada una, existiera o no.
+async fn sembrar_salas(pool: &Pool) -> Result<Vec<u32>, mysql_async::Error> {
+    let mut conn = db::conectar(pool).await?;
+    let mut ids = Vec::with_capacity(SALAS_EJEMPLO.len());
+    for ejemplo in SALAS_EJEMPLO {
+        let sala = sala_ejemplo(ejemplo);
+        conn.exec_drop(
+            "INSERT INTO salas (cine_id, nombre, capacidad) VALUES (:cine_id, :nombre, :capacidad) \
+             ON DUPLICATE KEY UPDATE id = LAST_INSERT_ID(id)",
+            params! { "cine_id" => CINE_PRINCIPAL, "nombre" => &sala.nombre, "capacidad" => sala.capacidad },
+        )
+        .await?;
+        ids.push(conn.last_insert_id().unwrap_or_default() as u32);
+    }
+    Ok(ids)
+}
+
+/// Crea las funciones de ejemplo que falten y devuelve el ID de cada una, existiera o no.
+async fn sembrar_funciones(pool: &Pool, salas: &[u32]) -> Result<Vec<u32>, mysql_async::Error> {
+    let mut conn = db::conectar(pool).await?;
+    let mut ids = Vec::with_capacity(FUNCIONES_EJEMPLO.len());
+    for ejemplo in FUNCIONES_EJEMPLO {
+        let funcion = funcion_ejemplo(ejemplo, salas);
+        // Con una función repetida, LAST_INSERT_ID(id) hace que se informe el ID de la existente.
+        conn.exec_drop(
+            "INSERT INTO funciones (titulo, sala_id, horario, precio) \
+             VALUES (:titulo, :sala_id, :horario, :precio) \
+             ON DUPLICATE KEY UPDATE id = LAST_INSERT_ID(id)",
+            params! {
+                "titulo" => &funcion.titulo,
+                "sala_id" => funcion.sala_id,
+                "horario" => funcion.horario.naive_utc(),
+                "precio" => funcion.precio,
+            },
+        )
+        .await?;
+        ids.push(conn.last_insert_id().unwrap_or_default() as u32);
+    }
+    Ok(ids)
+}
+
+/// Crea los clientes de ejemplo que falten y devuelve el ID de cada uno, en el orden de
+/// [`ENTRADAS_EJEMPLO`].
+async fn sembrar_clientes(pool: &Pool) -> Result<Vec<u32>, mysql_async::Error> {
+    let mut conn = db::conectar(pool).await?;
+    let mut ids = Vec::with_capacity(ENTRADAS_EJEMPLO.len());
+    for ejemplo in ENTRADAS_EJEMPLO {
+        let cliente = cliente_ejemplo(ejemplo);
+        conn.exec_drop(
+            "INSERT INTO clientes (cine_id, numero_cedula, nombre) VALUES (:cine_id, :numero_cedula, :nombre) \
+             ON DUPLICATE KEY UPDATE id = LAST_INSERT_ID(id)",
+            params! {
+                "cine_id" => CINE_PRINCIPAL,
+                "numero_cedula" => &cliente.numero_cedula,
+                "nombre" => &cliente.nombre,
+            },
+        )
+        .await?;
+        ids.push(conn.last_insert_id().unwrap_or_default() as u32);
+    }
+    Ok(ids)
+}
+
+/// Carga [`SALAS_EJEMPLO`], [`FUNCIONES_EJEMPLO`] y [`ENTRADAS_EJEMPLO`] con sus clientes;
+/// las entradas, a través del servicio, con sus mismas validaciones. Se niega si ya hay
+/// entradas, salvo con `forzar`; en ese caso lo que ya existe se salta, así que repetir la
+/// carga no duplica nada.
+pub async fn sembrar(pool: &Pool, servicio: &ServicioEntradas, forzar: bool) -> Result<ResumenSemilla, ErrorSemilla> {
+    let total: u64 = db::conectar(pool)
+        .await
+        .map_err(ErrorSemilla::BaseDatos)?
+        .exec_first("SELECT COUNT(*) FROM entradas WHERE cine_id = :cine_id", params! { "cine_id" => CINE_PRINCIPAL })
+        .await
+        .map_err(ErrorSemilla::BaseDatos)?
+        .unwrap_or_default();
+    if total > 0 && !forzar {
+        return Err(ErrorSemilla::BaseNoVacia(total));
+    }
+
+    let salas = sembrar_salas(pool).await.map_err(ErrorSemilla::BaseDatos)?;
+    let funciones = sembrar_funciones(pool, &salas).await.map_err(ErrorSemilla::BaseDatos)?;
+    let clientes = sembrar_clientes(pool).await.map_err(ErrorSemilla::BaseDatos)?;
+    // Un cliente puede tener varias entradas, así que los que ya tienen alguna se saltan.
+    let con_entrada: Vec<u32> = db::conectar(pool)
+        .await
+        .map_err(ErrorSemilla::BaseDatos)?
+        .exec(
+            "SELECT DISTINCT cliente_id FROM entradas WHERE cine_id = :cine_id",
+            params! { "cine_id" => CINE_PRINCIPAL },
+        )
+        .await
+        .map_err(ErrorSemilla::BaseDatos)?;
+    let mut resumen = ResumenSemilla::default();
+    for (ejemplo, cliente_id) in ENTRADAS_EJEMPLO.iter().zip(clientes) {
+        if con_entrada.contains(&cliente_id) {
+            resumen.existentes += 1;
+            continue;
+        }
+        servicio
+            .crear(CINE_PRINCIPAL, &entrada_ejemplo(ejemplo, cliente_id, &funciones), ACTOR_SEMILLA)
+            .await
+            .map_err(ErrorSemilla::Entrada)?;
+        resumen.creadas += 1;
+    }
+    Ok(resumen)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validacion::{ReglasValidacion, Validar};
+
+    #[test]
+    fn los_ejemplos_son_validos_y_sin_cedulas_repetidas() {
+        let reglas = ReglasValidacion { cedula_ecuatoriana: true };
+        for ejemplo in SALAS_EJEMPLO {
+            assert_eq!(sala_ejemplo(ejemplo).validar(&reglas), Ok(()), "{}", ejemplo.0);
+        }
+        let salas: Vec<u32> = (1..=SALAS_EJEMPLO.len() as u32).collect();
+        for ejemplo in FUNCIONES_EJEMPLO {
+            assert_eq!(funcion_ejemplo(ejemplo, &salas).validar(&reglas), Ok(()), "{}", ejemplo.0);
+        }
+        let funciones: Vec<u32> = (1..=FUNCIONES_EJEMPLO.len() as u32).collect();
+        let mut cedulas: Vec<_> = ENTRADAS_EJEMPLO.iter().map(|e| e.0).collect();
+        for (cliente_id, ejemplo) in (1..).zip(ENTRADAS_EJEMPLO) {
+            assert_eq!(cliente_ejemplo(ejemplo).validar(&reglas), Ok(()), "{}", ejemplo.0);
+            assert_eq!(entrada_ejemplo(ejemplo, cliente_id, &funciones).validar(&reglas), Ok(()), "{}", ejemplo.0);
+        }
+        cedulas.sort();
+        cedulas.dedup();
+        assert_eq!(cedulas.len(), ENTRADAS_EJEMPLO.len());
+    }
+}
@@ -0,0 +1,388 @@
+//! Constructor del servidor HTTP para quienes embeben la API.
+//!
+//! ```no_run
+//! # async fn ejemplo(config: rust_crud::config::Config) -> std::io::Result<()> {
+//! use actix_web::{web, HttpResponse};
+//! use rust_crud::Server;
+//!
+//! Server::builder()
+//!     .config(config)
+//!     .rutas(|cfg| {
+//!         cfg.route("/kiosko/estado", web::get().to(|| async { HttpResponse::Ok().finish() }));
+//!     })
+//!     .middleware(|req, siguiente| async move {
+//!         println!("{} {}", req.method(), req.path());
+//!         siguiente.call(req).await
+//!     })
+//!     .tarea("informe_diario", "@daily", || async {
+//!         println!("Generando el informe diario");
+//!         Ok(())
+//!     })
+//!     .build()?
+//!     .await
+//! # }
+//! ```
+
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, ServerHandle, Transform};
+use actix_web::rt::task::JoinHandle;
+use actix_web::rt::time::timeout;
+use actix_web::{web, Error, HttpServer};
+use mysql_async::Pool;
+use tokio::sync::oneshot;
+
+use crate::accesos::guardar_accesos;
+use crate::archivo::archivar_entradas;
+use crate::compartido::Redis;
+use crate::config::Config;
+use crate::correos::{enviar_correos, Confirmaciones};
+use crate::{create_app, Estado};
+use crate::db::{obtener_pool_db, obtener_pool_replica};
+use crate::db::repository::EntradaRepository;
+use crate::dispositivos::vigilar_dispositivos;
+#[cfg(unix)]
+use crate::escucha::borrar_socket_anterior;
+use crate::escucha::Escucha;
+use crate::recarga::recargar_con_sighup;
+use crate::reservas::vencer_reservas;
+use crate::tareas::{self, Intervalo, Trabajo};
+use crate::webhooks::{entregar_webhooks, publicar_eventos};
+
+/// Futuro sin `Send` devuelto por los middlewares.
+pub type FuturoRespuesta = Pin<Box<dyn Future<Output = Result<ServiceResponse<BoxBody>, Error>>>>;
+
+type Capa = Arc<dyn Fn(ServiceRequest, Siguiente) -> FuturoRespuesta + Send + Sync>;
+type Rutas = Arc<dyn Fn(&mut web::ServiceConfig) + Send + Sync>;
+
+/// Resto de la pila de middlewares (y finalmente el handler) a partir de una capa.
+#[derive(Clone)]
+pub struct Siguiente(Rc<dyn Fn(ServiceRequest) -> FuturoRespuesta>);
+
+impl Siguiente {
+    /// Pasa la petición a la siguiente capa.
+    pub async fn call(self, req: ServiceRequest) -> Result<ServiceResponse<BoxBody>, Error> {
+        (self.0)(req).await
+    }
+}
+
+/// Punto de entrada para construir un servidor con la API montada.
+pub struct Server;
+
+impl Server {
+    pub fn builder() -> ServerBuilder {
+        ServerBuilder::default()
+    }
+}
+
+/// Acumula la configuración, rutas y middlewares adicionales antes de arrancar el servidor.
+#[derive(Default)]
+pub struct ServerBuilder {
+    config: Option<Config>,
+    pool: Option<Pool>,
+    repositorio: Option<Arc<dyn EntradaRepository>>,
+    rutas: Vec<Rutas>,
+    middlewares: Vec<Capa>,
+    tareas: Vec<(String, String, Trabajo)>,
+}
+
+impl ServerBuilder {
+    /// Configuración a usar. Si no se indica, se lee del entorno al construir.
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Pool de conexiones ya creada, por ejemplo para compartirla con el resto del binario.
+    pub fn pool(mut self, pool: Pool) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    /// Repositorio de entradas a usar en lugar del de MySQL sobre la pool, por ejemplo
+    /// [`RepositorioMemoria`](crate::db::memoria::RepositorioMemoria) en desarrollo, con la
+    /// misma medición y caché (ver [`Estado::con_repositorio`]). Es el repositorio el que
+    /// aplica `limite_por_cedula`, así que hay que configurárselo. Sólo cambian las entradas:
+    /// el resto de la API y las tareas del planificador salvo el vencimiento de reservas
+    /// (eventos, correos, webhooks, accesos, archivo y dispositivos) siguen necesitando
+    /// MySQL en la pool.
+    pub fn repositorio<R: EntradaRepository + 'static>(mut self, repositorio: R) -> Self {
+        self.repositorio = Some(Arc::new(repositorio));
+        self
+    }
+
+    /// Registra rutas adicionales junto a las de la API.
+    pub fn rutas<F>(mut self, rutas: F) -> Self
+    where
+        F: Fn(&mut web::ServiceConfig) + Send + Sync + 'static,
+    {
+        self.rutas.push(Arc::new(rutas));
+        self
+    }
+
+    /// Añade un middleware a la pila. El primero registrado es el más externo.
+    pub fn middleware<F, Fut>(mut self, middleware: F) -> Self
+    where
+        F: Fn(ServiceRequest, Siguiente) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<ServiceResponse<BoxBody>, Error>> + 'static,
+    {
+        self.middlewares
+            .push(Arc::new(move |req, siguiente| Box::pin(middleware(req, siguiente))));
+        self
+    }
+
+    /// Registra una tarea periódica en el planificador, con un intervalo como `@every 15m`
+    /// o `@daily` (ver [`Intervalo::desde_str`]). Un intervalo inválido hace fallar `build`.
+    pub fn tarea<F, Fut>(mut self, nombre: impl Into<String>, intervalo: impl Into<String>, trabajo: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), String>> + 'static,
+    {
+        self.tareas.push((nombre.into(), intervalo.into(), tareas::trabajo(trabajo)));
+        self
+    }
+
+    /// Enlaza las direcciones configuradas (ver [`Config::direcciones`]), y la de HTTPS si
+    /// hay certificado, y devuelve el servidor listo para ejecutarse con `.await`.
+    /// Con SIGTERM deja de aceptar conexiones y espera hasta `drenaje` a que terminen las
+    /// peticiones en curso; el futuro se resuelve cuando el servidor se ha detenido y las
+    /// tareas periódicas en curso han terminado, esperándolas también hasta `drenaje`. Con
+    /// SIGHUP recarga la configuración, si se leyó de un archivo (ver [`crate::recarga`]).
+    ///
+    /// También inicia las tareas periódicas: la vigilancia de dispositivos silenciosos, el
+    /// vencimiento de reservas, la publicación de eventos, la entrega de webhooks, el envío
+    /// de confirmaciones por correo y las registradas con [`ServerBuilder::tarea`], por lo que
+    /// debe llamarse dentro del runtime de actix. Con `Config::grpc_port` abre además el
+    /// servidor gRPC, que se detiene junto al HTTP.
+    pub fn build(self) -> std::io::Result<ServidorEnMarcha> {
+        let config = match self.config {
+            Some(config) => config,
+            None => Config::desde_entorno().map_err(|e| std::io::Error::other(e.to_string()))?,
+        };
+        let pool = match self.pool {
+            Some(pool) => pool,
+            None => obtener_pool_db(&config).map_err(|e| std::io::Error::other(e.to_string()))?,
+        };
+        if config.auth.secreto.is_empty() {
+            return Err(std::io::Error::other("Falta el secreto para firmar los tokens (JWT_SECRETO)"));
+        }
+        obtener_pool_replica(&config).map_err(|e| std::io::Error::other(format!("DATABASE_URL_RO inválida: {}", e)))?;
+        if let Some(url) = &config.redis_url {
+            Redis::new(url).map_err(std::io::Error::other)?;
+        }
+        let tls = config
+            .tls
+            .archivos()
+            .map_err(std::io::Error::other)?
+            .map(|(certificado, clave)| (certificado.to_string(), clave.to_string()));
+        let direcciones = config.direcciones();
+        let direccion_tls = (config.host.clone(), config.tls.port);
+        let solo_https = config.tls.solo_https;
+        let direccion_grpc = config.grpc_port.map(|port| (config.host.clone(), port));
+        let vigilancia = vigilar_dispositivos(pool.clone(), config.dispositivos_silencio);
+        let mut estado = Estado::new(config, pool);
+        if let Some(repositorio) = self.repositorio {
+            estado = estado.con_repositorio(repositorio);
+        }
+        let recarga = actix_web::rt::spawn(recargar_con_sighup(estado.vigente.clone(), estado.limites.clone()));
+        let planificador = estado.planificador.clone();
+        planificador.registrar("vigilancia_dispositivos", vigilancia.0, vigilancia.1);
+        if !estado.config.reservas_ttl.is_zero() {
+            let (intervalo, trabajo) =
+                vencer_reservas(estado.entradas.clone(), estado.cambios.clone(), estado.config.reservas_ttl);
+            planificador.registrar("vencimiento_reservas", intervalo, trabajo);
+        }
+        let confirmaciones = Confirmaciones::new(&estado).map_err(std::io::Error::other)?;
+        let (intervalo, trabajo) = publicar_eventos(estado.pool.clone(), confirmaciones.is_some());
+        planificador.registrar("publicacion_eventos", intervalo, trabajo);
+        if let Some(confirmaciones) = confirmaciones {
+            let (intervalo, trabajo) = enviar_correos(confirmaciones);
+            planificador.registrar("envio_correos", intervalo, trabajo);
+        }
+        let (intervalo, trabajo) = entregar_webhooks(
+            estado.pool.clone(),
+            estado.config.webhooks_intentos,
+            estado.config.webhooks_tiempo_maximo,
+        );
+        planificador.registrar("entrega_webhooks", intervalo, trabajo);
+        if estado.config.accesos_retencion_dias > 0 {
+            let (intervalo, trabajo) =
+                guardar_accesos(estado.accesos.clone(), estado.pool.clone(), estado.config.accesos_retencion_dias);
+            planificador.registrar("registro_accesos", intervalo, trabajo);
+        }
+        if estado.config.archivo_antiguedad_dias > 0 {
+            let (intervalo, trabajo) = archivar_entradas(
+                estado.pool.clone(),
+                estado.cache.clone(),
+                estado.reloj.clone(),
+                estado.config.archivo_antiguedad_dias,
+            );
+            planificador.registrar("archivo_entradas", intervalo, trabajo);
+        }
+        for (nombre, expresion, trabajo) in self.tareas {
+            let intervalo = Intervalo::desde_str(&expresion).ok_or_else(|| {
+                std::io::Error::other(format!("Intervalo inválido para la tarea '{}': {}", nombre, expresion))
+            })?;
+            planificador.registrar(nombre, intervalo, trabajo);
+        }
+        #[cfg(feature = "grpc")]
+        let servicio_grpc = crate::grpc::ServicioGrpc::new(&estado);
+        let rutas = Arc::new(self.rutas);
+        let pila = PilaMiddlewares {
+            capas: Arc::new(self.middlewares),
+        };
+
+        let workers = estado.config.workers;
+        let drenaje = estado.config.drenaje;
+
+        let mut server = HttpServer::new(move || {
+            let rutas = rutas.clone();
+            create_app(estado.clone())
+                .configure(move |cfg| rutas.iter().for_each(|registrar| registrar(cfg)))
+                .wrap(pila.clone())
+        })
+        .shutdown_timeout(drenaje.as_secs());
+        if let Some(workers) = workers {
+            server = server.workers(workers);
+        }
+        if !solo_https {
+            for direccion in direcciones {
+                server = match direccion {
+                    Escucha::Tcp(direccion) => server.bind(direccion)?,
+                    #[cfg(unix)]
+                    Escucha::Unix(ruta) => {
+                        borrar_socket_anterior(&ruta)?;
+                        server.bind_uds(ruta)?
+                    }
+                    #[cfg(not(unix))]
+                    Escucha::Unix(_) => {
+                        return Err(std::io::Error::other("Los sockets Unix sólo están disponibles en Unix"));
+                    }
+                };
+            }
+        }
+        if let Some((certificado, clave)) = tls {
+            #[cfg(feature = "tls")]
+            {
+                server = server.bind_rustls_0_23(direccion_tls, crate::tls::cargar(&certificado, &clave)?)?;
+            }
+            #[cfg(not(feature = "tls"))]
+            {
+                let _ = (certificado, clave, direccion_tls);
+                return Err(std::io::Error::other(
+                    "Hay un certificado TLS configurado, pero el binario se compiló sin la feature `tls`",
+                ));
+            }
+        }
+        let grpc: Option<(oneshot::Sender<()>, JoinHandle<()>)> = match direccion_grpc {
+            #[cfg(feature = "grpc")]
+            Some(direccion) => Some(crate::grpc::servir(direccion, servicio_grpc)?),
+            #[cfg(not(feature = "grpc"))]
+            Some(_) => {
+                return Err(std::io::Error::other(
+                    "Hay un puerto gRPC configurado, pero el binario se compiló sin la feature `grpc`",
+                ));
+            }
+            None => None,
+        };
+        let servidor = server.run();
+        let handle = servidor.handle();
+        planificador.iniciar();
+        let futuro = async move {
+            let resultado = servidor.await;
+            recarga.abort();
+            if let Some((parar, tarea)) = grpc {
+                parar.send(()).ok();
+                if timeout(drenaje, tarea).await.is_err() {
+                    log::warn!("El servidor gRPC no terminó en {} s, se abandona", drenaje.as_secs());
+                }
+            }
+            if timeout(drenaje, planificador.detener()).await.is_err() {
+                log::warn!("Las tareas periódicas no terminaron en {} s, se abandonan", drenaje.as_secs());
+            }
+            resultado
+        };
+        Ok(ServidorEnMarcha { handle, futuro: Box::pin(futuro) })
+    }
+}
+
+/// Servidor en marcha. Se resuelve al detenerse, con sus tareas periódicas ya terminadas.
+pub struct ServidorEnMarcha {
+    handle: ServerHandle,
+    futuro: Pin<Box<dyn Future<Output = std::io::Result<()>>>>,
+}
+
+impl ServidorEnMarcha {
+    /// Handle para detener o pausar el servidor desde fuera.
+    pub fn handle(&self) -> ServerHandle {
+        self.handle.clone()
+    }
+}
+
+impl Future for ServidorEnMarcha {
+    type Output = std::io::Result<()>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.futuro.as_mut().poll(cx)
+    }
+}
+
+/// Middleware de actix que ejecuta las capas registradas en el builder.
+#[derive(Clone)]
+struct PilaMiddlewares {
+    capas: Arc<Vec<Capa>>,
+}
+
+impl<S, B> Transform<S, ServiceRequest> for PilaMiddlewares
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Transform = PilaMiddlewaresService<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(PilaMiddlewaresService {
+            servicio: Rc::new(service),
+            capas: self.capas.clone(),
+        }))
+    }
+}
+
+struct PilaMiddlewaresService<S> {
+    servicio: Rc<S>,
+    capas: Arc<Vec<Capa>>,
+}
+
+impl<S, B> Service<ServiceRequest> for PilaMiddlewaresService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Future = FuturoRespuesta;
+
+    forward_ready!(servicio);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let servicio = self.servicio.clone();
+        let handler: Rc<dyn Fn(ServiceRequest) -> FuturoRespuesta> = Rc::new(move |req| {
+            let servicio = servicio.clone();
+            Box::pin(async move { servicio.call(req).await.map(ServiceResponse::map_into_boxed_body) })
+        });
+        let cadena = self.capas.iter().rev().fold(handler, |siguiente, capa| {
+            let capa = capa.clone();
+            Rc::new(move |req| capa(req, Siguiente(siguiente.clone())))
+        });
+        cadena(req)
+    }
+}
@@ -0,0 +1,952 @@
+//! Capa de servicio de las entradas: reglas de negocio entre los handlers HTTP y el
+//! repositorio.
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::FixedOffset;
+use futures_util::stream::{BoxStream, StreamExt};
+
+use crate::agregado::{ParametrosAgregado, ResultadoAgregado};
+use crate::coalescencia::{EstadisticasCoalescencia, GrupoSerializado, LecturasCoalescidas, Lectura};
+use crate::db::errores::{self, FalloMysql};
+use crate::db::repository::{EntradaRepository, ErrorRepositorio, Escritura};
+use crate::listado::ConsultaListado;
+use crate::models::{
+    ActualizarEntrada, CrearEntrada, EliminarEntradas, Entrada, EstadoEntrada, GuardarEntrada, RegistroAuditoria,
+};
+use crate::validacion::{ErrorCampo, ReglasValidacion, Validar};
+
+pub use crate::db::repository::{EntradaGuardada, EntradaSimulada};
+
+/// Errores de las operaciones sobre entradas, ya con el mensaje que verá el cliente.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorEntrada {
+    NoEncontrada,
+    SinDatos,
+    ParametrosInvalidos(String),
+    CamposInvalidos(Vec<ErrorCampo>),
+    /// La función no tiene asientos suficientes; quedan los indicados.
+    SinCapacidad(u32),
+    /// La cédula del cliente llegó al límite de entradas por función; puede comprar las
+    /// indicadas.
+    LimiteCedula(u32),
+    AsientosOcupados(Vec<u32>),
+    AsientoInexistente(u32),
+    AsientosAsignados,
+    /// `If-Match` no coincide con la versión actual, que es la indicada.
+    VersionDistinta(u32),
+    ClienteInexistente,
+    FuncionInexistente,
+    /// La entrada está en el estado indicado, desde el que no puede pasar al pedido.
+    TransicionInvalida(EstadoEntrada),
+    /// El valor no cabe en la columna indicada.
+    ValorDemasiadoLargo(String),
+    /// La base de datos abortó la operación por un bloqueo.
+    Contencion,
+    Interno(&'static str),
+}
+
+impl fmt::Display for ErrorEntrada {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorEntrada::NoEncontrada => f.write_str("Entrada no encontrada"),
+            ErrorEntrada::SinDatos => f.write_str("No se proporcionaron datos para actualizar"),
+            ErrorEntrada::ParametrosInvalidos(mensaje) => f.write_str(mensaje),
+            ErrorEntrada::CamposInvalidos(_) => f.write_str("La entrada contiene campos inválidos"),
+            ErrorEntrada::SinCapacidad(disponibles) => {
+                write!(f, "Sólo quedan {} asientos disponibles para la función", disponibles)
+            }
+            ErrorEntrada::LimiteCedula(restantes) => {
+                write!(f, "La cédula sólo puede comprar {} entradas más para la función", restantes)
+            }
+            ErrorEntrada::AsientosOcupados(asientos) => {
+                let asientos: Vec<String> = asientos.iter().map(u32::to_string).collect();
+                write!(f, "Los asientos {} ya están ocupados", asientos.join(", "))
+            }
+            ErrorEntrada::AsientoInexistente(asiento) => write!(f, "El asiento {} no existe en la sala", asiento),
+            ErrorEntrada::AsientosAsignados => f.write_str(
+                "La entrada tiene asientos asignados; para cambiar la función o la cantidad hay que eliminarla y volver a comprarla",
+            ),
+            ErrorEntrada::VersionDistinta(version) => write!(
+                f,
+                "La entrada cambió desde que se leyó; su versión actual es la {}",
+                version
+            ),
+            ErrorEntrada::ClienteInexistente => f.write_str("El cliente indicado no existe"),
+            ErrorEntrada::FuncionInexistente => f.write_str("La función indicada no existe"),
+            ErrorEntrada::TransicionInvalida(actual) => {
+                write!(f, "La entrada está {} y no puede pasar a ese estado", actual.como_str())
+            }
+            ErrorEntrada::ValorDemasiadoLargo(_) => f.write_str("El valor es demasiado largo"),
+            ErrorEntrada::Contencion => f.write_str("La base de datos está ocupada; reintenta la operación"),
+            ErrorEntrada::Interno(mensaje) => f.write_str(mensaje),
+        }
+    }
+}
+
+impl std::error::Error for ErrorEntrada {}
+
+/// Traduce un error del repositorio, registrando en el log los fallos internos.
+fn convertir(error: ErrorRepositorio, mensaje: &'static str) -> ErrorEntrada {
+    match error {
+        ErrorRepositorio::Conexion(e) => {
+            log::error!("Error al obtener conexión: {:?}", e);
+            ErrorEntrada::Interno("Error al conectar a la base de datos")
+        }
+        ErrorRepositorio::Consulta(e) => {
+            log::error!("{}: {:?}", mensaje, e);
+            if errores::clasificar(&e) == FalloMysql::Bloqueo {
+                ErrorEntrada::Contencion
+            } else {
+                ErrorEntrada::Interno(mensaje)
+            }
+        }
+        ErrorRepositorio::SinCapacidad(disponibles) => ErrorEntrada::SinCapacidad(disponibles),
+        ErrorRepositorio::LimiteCedula(restantes) => ErrorEntrada::LimiteCedula(restantes),
+        ErrorRepositorio::AsientosOcupados(asientos) => ErrorEntrada::AsientosOcupados(asientos),
+        ErrorRepositorio::AsientoInexistente(asiento) => ErrorEntrada::AsientoInexistente(asiento),
+        ErrorRepositorio::AsientosAsignados => ErrorEntrada::AsientosAsignados,
+        ErrorRepositorio::VersionDistinta(version) => ErrorEntrada::VersionDistinta(version),
+        ErrorRepositorio::ClienteInexistente => ErrorEntrada::ClienteInexistente,
+        ErrorRepositorio::FuncionInexistente => ErrorEntrada::FuncionInexistente,
+        ErrorRepositorio::TransicionInvalida(actual) => ErrorEntrada::TransicionInvalida(actual),
+        ErrorRepositorio::ParametrosInvalidos(mensaje) => ErrorEntrada::ParametrosInvalidos(mensaje),
+        ErrorRepositorio::DemasiadoLargo(columna) => ErrorEntrada::ValorDemasiadoLargo(columna),
+    }
+}
+
+/// Máximo de entradas de `POST /entradas/bulk`.
+pub const ENTRADAS_POR_LOTE: usize = 100;
+
+/// Máximo de IDs de `DELETE /entradas`.
+pub const IDS_POR_ELIMINACION: usize = 1000;
+
+/// Longitud máxima del motivo de `POST /funciones/{id}/cancelar`, la de su columna.
+pub const MOTIVO_MAXIMO: usize = 255;
+
+/// Resultado de cada entrada de un lote, que se guarda entero o no se guarda. Las creadas
+/// llevan su ID, o la entrada entera si el lote sólo se simuló.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResultadoLote<T = u32> {
+    Creada(T),
+    Rechazada(ErrorEntrada),
+    /// Era válida, pero no se guardó porque otra entrada del lote falló.
+    Revertida,
+}
+
+impl ResultadoLote {
+    /// ID de la entrada, si se guardó.
+    pub fn creada(&self) -> Option<u32> {
+        match self {
+            ResultadoLote::Creada(id) => Some(*id),
+            _ => None,
+        }
+    }
+}
+
+/// Entrada creada por [`ServicioEntradas::vender`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Venta {
+    pub id: u32,
+    /// La creó otra venta idéntica simultánea, que ya publicó su evento.
+    pub repetida: bool,
+}
+
+/// Página del listado de entradas.
+pub struct PaginaEntradas {
+    /// Entradas que abarca el listado sin paginar.
+    pub total: u64,
+    pub filas: BoxStream<'static, Result<Entrada, ErrorEntrada>>,
+}
+
+/// Operaciones sobre entradas. Se comparte entre los workers. Todas salvo
+/// [`ServicioEntradas::cancelar_vencidas`] se limitan al `cine` de la petición. Las que
+/// modifican entradas reciben el `actor` que queda en la auditoría: el usuario o la clave de
+/// API de la sesión. Las entradas que devuelve traen el horario de su función en la `zona`
+/// configurada.
+pub struct ServicioEntradas {
+    repositorio: Arc<dyn EntradaRepository>,
+    reglas: ReglasValidacion,
+    zona: FixedOffset,
+    lecturas: LecturasCoalescidas,
+    /// Altas en curso por cine, cliente y función (ver [`ServicioEntradas::vender`]).
+    altas: GrupoSerializado<(u32, u32, u32), CrearEntrada, Lectura<u32>>,
+}
+
+impl ServicioEntradas {
+    pub fn new(repositorio: Arc<dyn EntradaRepository>, reglas: ReglasValidacion, zona: FixedOffset) -> Self {
+        ServicioEntradas {
+            repositorio,
+            reglas,
+            zona,
+            lecturas: LecturasCoalescidas::default(),
+            altas: GrupoSerializado::default(),
+        }
+    }
+
+    /// Contadores de coalescencia de las lecturas por ID y de los agregados.
+    pub fn lecturas(&self) -> &LecturasCoalescidas {
+        &self.lecturas
+    }
+
+    /// Contadores de las altas de [`ServicioEntradas::vender`].
+    pub fn altas(&self) -> EstadisticasCoalescencia {
+        self.altas.estadisticas()
+    }
+
+    /// Página del listado con el total de entradas que abarca. Las filas se leen a medida
+    /// que se consumen.
+    pub async fn listar(&self, cine: u32, consulta: &ConsultaListado) -> Result<PaginaEntradas, ErrorEntrada> {
+        let total = self
+            .repositorio
+            .contar(cine, consulta)
+            .await
+            .map_err(|e| convertir(e, "Error al obtener entradas"))?;
+        Ok(PaginaEntradas { total, filas: self.filas(cine, consulta).await? })
+    }
+
+    /// Filas de la página, sin contar el total; para la paginación por cursor.
+    pub async fn filas(
+        &self,
+        cine: u32,
+        consulta: &ConsultaListado,
+    ) -> Result<BoxStream<'static, Result<Entrada, ErrorEntrada>>, ErrorEntrada> {
+        let filas = self
+            .repositorio
+            .listar(cine, consulta)
+            .await
+            .map_err(|e| convertir(e, "Error al obtener entradas"))?;
+        let zona = self.zona;
+        Ok(filas
+            .map(move |fila| {
+                fila.map(|entrada| entrada.en_zona(zona))
+                    .map_err(|e| convertir(e, "Error al obtener entradas"))
+            })
+            .boxed())
+    }
+
+    /// Entradas que abarca el listado, sin paginar.
+    pub async fn contar(&self, cine: u32, consulta: &ConsultaListado) -> Result<u64, ErrorEntrada> {
+        self.repositorio
+            .contar(cine, consulta)
+            .await
+            .map_err(|e| convertir(e, "Error al contar entradas"))
+    }
+
+    /// Entrada por ID. Las lecturas concurrentes del mismo ID comparten una sola consulta.
+    pub async fn obtener(&self, cine: u32, id: u32) -> Result<Entrada, ErrorEntrada> {
+        let repositorio = self.repositorio.clone();
+        self.lecturas
+            .entradas
+            .ejecutar((cine, id), async move {
+                repositorio
+                    .obtener(cine, id)
+                    .await
+                    .map_err(|e| convertir(e, "Error al obtener entrada"))
+            })
+            .await?
+            .map(|entrada| entrada.en_zona(self.zona))
+            .ok_or(ErrorEntrada::NoEncontrada)
+    }
+
+    /// Comprobaciones de [`ServicioEntradas::crear`] antes de llegar al repositorio.
+    fn preparar_alta(&self, entrada: &CrearEntrada) -> Result<(), ErrorEntrada> {
+        entrada.validar(&self.reglas).map_err(ErrorEntrada::CamposInvalidos)
+    }
+
+    /// Crea la entrada y devuelve su ID.
+    pub async fn crear(&self, cine: u32, entrada: &CrearEntrada, actor: &str) -> Result<u32, ErrorEntrada> {
+        self.preparar_alta(entrada)?;
+        self.repositorio
+            .crear(cine, entrada, actor)
+            .await
+            .map_err(|e| convertir(e, "Error al crear entrada"))
+    }
+
+    /// Crea la entrada como [`ServicioEntradas::crear`], pero de una en una por cliente y
+    /// función: dos ventas simultáneas a la misma cédula para la misma función no pasan a la
+    /// vez las comprobaciones. Si llega mientras se crea otra idéntica, no crea otra: recibe
+    /// el resultado de aquélla, con [`Venta::repetida`].
+    pub async fn vender(&self, cine: u32, entrada: &CrearEntrada, actor: &str) -> Result<Venta, ErrorEntrada> {
+        let clave = (cine, entrada.cliente_id, entrada.funcion_id);
+        let (resultado, repetida) = self.altas.ejecutar(clave, entrada.clone(), || self.crear(cine, entrada, actor)).await;
+        resultado.map(|id| Venta { id, repetida })
+    }
+
+    /// Comprobaciones de cada entrada del lote antes de llegar al repositorio.
+    fn preparar_lote(&self, entradas: &[CrearEntrada]) -> Result<Vec<Result<(), ErrorEntrada>>, ErrorEntrada> {
+        if entradas.is_empty() || entradas.len() > ENTRADAS_POR_LOTE {
+            return Err(ErrorEntrada::ParametrosInvalidos(format!(
+                "El lote debe tener entre 1 y {} entradas",
+                ENTRADAS_POR_LOTE
+            )));
+        }
+        Ok(entradas
+            .iter()
+            .map(|entrada| entrada.validar(&self.reglas).map_err(ErrorEntrada::CamposInvalidos))
+            .collect())
+    }
+
+    /// Crea todas las entradas en una sola transacción, o ninguna si alguna falla. Si
+    /// alguna no pasa la validación, el lote no llega al repositorio.
+    pub async fn crear_lote(
+        &self,
+        cine: u32,
+        entradas: &[CrearEntrada],
+        actor: &str,
+    ) -> Result<Vec<ResultadoLote>, ErrorEntrada> {
+        let validaciones = self.preparar_lote(entradas)?;
+        if validaciones.iter().any(Result::is_err) {
+            return Ok(rechazos(validaciones));
+        }
+
+        let resultados = self
+            .repositorio
+            .crear_lote(cine, entradas, actor)
+            .await
+            .map_err(|e| convertir(e, "Error al crear entradas"))?;
+        let guardado = resultados.iter().all(Result::is_ok);
+        Ok(resultados
+            .into_iter()
+            .map(|resultado| match resultado {
+                Ok(id) if guardado => ResultadoLote::Creada(id),
+                Ok(_) => ResultadoLote::Revertida,
+                Err(e) => ResultadoLote::Rechazada(convertir(e, "Error al crear entrada")),
+            })
+            .collect())
+    }
+
+    /// Lo que haría [`ServicioEntradas::crear_lote`], sin guardar nada: cada entrada como
+    /// quedaría creada, si el lote entero sale bien.
+    pub async fn simular_lote(
+        &self,
+        cine: u32,
+        entradas: &[CrearEntrada],
+        actor: &str,
+    ) -> Result<Vec<ResultadoLote<Entrada>>, ErrorEntrada> {
+        let validaciones = self.preparar_lote(entradas)?;
+        if validaciones.iter().any(Result::is_err) {
+            return Ok(rechazos(validaciones));
+        }
+        let resultados = self.simular(cine, Escritura::Crear(entradas), actor, "Error al crear entradas").await?;
+        let guardado = resultados.iter().all(Result::is_ok);
+        Ok(resultados
+            .into_iter()
+            .map(|resultado| match resultado {
+                Ok(EntradaSimulada::Creada(entrada)) if guardado => ResultadoLote::Creada(entrada),
+                Err(e) => ResultadoLote::Rechazada(e),
+                Ok(_) => ResultadoLote::Revertida,
+            })
+            .collect())
+    }
+
+    /// Crea las entradas en lotes de [`ENTRADAS_POR_LOTE`], cada uno en su transacción. A
+    /// diferencia de [`ServicioEntradas::crear_lote`], una entrada rechazada no impide
+    /// guardar las demás: su lote se reintenta sin ella. Por eso ninguna queda revertida.
+    /// Si falla la base de datos, los lotes anteriores ya quedaron guardados.
+    pub async fn importar(
+        &self,
+        cine: u32,
+        entradas: &[CrearEntrada],
+        actor: &str,
+    ) -> Result<Vec<ResultadoLote>, ErrorEntrada> {
+        let mut resultados = vec![ResultadoLote::Revertida; entradas.len()];
+        for (inicio, lote) in (0..).step_by(ENTRADAS_POR_LOTE).zip(entradas.chunks(ENTRADAS_POR_LOTE)) {
+            let mut pendientes: Vec<usize> = (0..lote.len()).collect();
+            // Cada intento fallido rechaza al menos una entrada, así que el bucle termina.
+            while !pendientes.is_empty() {
+                let intento: Vec<CrearEntrada> = pendientes.iter().map(|&i| lote[i].clone()).collect();
+                let mut revertidas = Vec::new();
+                for (i, resultado) in pendientes.into_iter().zip(self.crear_lote(cine, &intento, actor).await?) {
+                    match resultado {
+                        ResultadoLote::Revertida => revertidas.push(i),
+                        resultado => resultados[inicio + i] = resultado,
+                    }
+                }
+                pendientes = revertidas;
+            }
+        }
+        Ok(resultados)
+    }
+
+    /// Comprobaciones de [`ServicioEntradas::guardar_por_cedula`] antes de llegar al repositorio.
+    fn preparar_guardado(&self, datos: &GuardarEntrada) -> Result<(), ErrorEntrada> {
+        datos.validar(&self.reglas).map_err(ErrorEntrada::CamposInvalidos)
+    }
+
+    /// Crea o actualiza, en una sola transacción, la entrada del cliente con esa cédula para
+    /// la función. Un envío repetido deja la entrada como estaba en lugar de duplicarla.
+    pub async fn guardar_por_cedula(
+        &self,
+        cine: u32,
+        numero_cedula: &str,
+        datos: &GuardarEntrada,
+        actor: &str,
+    ) -> Result<EntradaGuardada, ErrorEntrada> {
+        self.preparar_guardado(datos)?;
+        self.repositorio
+            .guardar_por_cedula(cine, numero_cedula, datos, actor)
+            .await
+            .map_err(|e| convertir(e, "Error al guardar entrada"))
+    }
+
+    /// Actualiza los campos presentes en `datos`, si la entrada sigue en `version` (cualquiera
+    /// con `None`). Sin cambios efectivos se considera no encontrada.
+    pub async fn actualizar(
+        &self,
+        cine: u32,
+        id: u32,
+        datos: &ActualizarEntrada,
+        version: Option<u32>,
+        actor: &str,
+    ) -> Result<(), ErrorEntrada> {
+        self.preparar_actualizacion(datos)?;
+        let actualizada = self
+            .repositorio
+            .actualizar(cine, id, datos, version, actor)
+            .await
+            .map_err(|e| convertir(e, "Error al actualizar entrada"))?;
+        if actualizada { Ok(()) } else { Err(ErrorEntrada::NoEncontrada) }
+    }
+
+    /// Comprobaciones de [`ServicioEntradas::actualizar`] antes de llegar al repositorio.
+    fn preparar_actualizacion(&self, datos: &ActualizarEntrada) -> Result<(), ErrorEntrada> {
+        if datos.es_vacia() {
+            return Err(ErrorEntrada::SinDatos);
+        }
+        datos.validar(&self.reglas).map_err(ErrorEntrada::CamposInvalidos)
+    }
+
+    /// Ejecuta `escritura` en el repositorio sin guardarla y traduce el resultado de cada
+    /// entrada, que ya trae el horario en la zona del servicio.
+    async fn simular(
+        &self,
+        cine: u32,
+        escritura: Escritura<'_>,
+        actor: &str,
+        mensaje: &'static str,
+    ) -> Result<Vec<Result<EntradaSimulada, ErrorEntrada>>, ErrorEntrada> {
+        let resultados = self
+            .repositorio
+            .simular(cine, escritura, actor)
+            .await
+            .map_err(|e| convertir(e, mensaje))?;
+        Ok(resultados
+            .into_iter()
+            .map(|resultado| match resultado {
+                Ok(EntradaSimulada::Creada(entrada)) => Ok(EntradaSimulada::Creada(entrada.en_zona(self.zona))),
+                Ok(EntradaSimulada::Actualizada(entrada)) => Ok(EntradaSimulada::Actualizada(entrada.en_zona(self.zona))),
+                Ok(EntradaSimulada::SinCambios) => Ok(EntradaSimulada::SinCambios),
+                Err(e) => Err(convertir(e, mensaje)),
+            })
+            .collect())
+    }
+
+    /// Lo que haría [`ServicioEntradas::crear`], sin guardar nada: la entrada como quedaría.
+    pub async fn simular_alta(&self, cine: u32, entrada: &CrearEntrada, actor: &str) -> Result<Entrada, ErrorEntrada> {
+        self.preparar_alta(entrada)?;
+        let escritura = Escritura::Crear(std::slice::from_ref(entrada));
+        match self.simular(cine, escritura, actor, "Error al crear entrada").await?.pop() {
+            Some(Ok(EntradaSimulada::Creada(entrada))) => Ok(entrada),
+            Some(Err(e)) => Err(e),
+            _ => Err(ErrorEntrada::Interno("Error al crear entrada")),
+        }
+    }
+
+    /// Lo que haría [`ServicioEntradas::guardar_por_cedula`], sin guardar nada.
+    pub async fn simular_guardado(
+        &self,
+        cine: u32,
+        numero_cedula: &str,
+        datos: &GuardarEntrada,
+        actor: &str,
+    ) -> Result<EntradaSimulada, ErrorEntrada> {
+        self.preparar_guardado(datos)?;
+        let escritura = Escritura::GuardarPorCedula(numero_cedula, datos);
+        self.simular(cine, escritura, actor, "Error al guardar entrada")
+            .await?
+            .pop()
+            .unwrap_or(Err(ErrorEntrada::Interno("Error al guardar entrada")))
+    }
+
+    /// Lo que haría [`ServicioEntradas::actualizar`], sin guardar nada: la entrada como
+    /// quedaría.
+    pub async fn simular_actualizacion(
+        &self,
+        cine: u32,
+        id: u32,
+        datos: &ActualizarEntrada,
+        version: Option<u32>,
+        actor: &str,
+    ) -> Result<Entrada, ErrorEntrada> {
+        self.preparar_actualizacion(datos)?;
+        let escritura = Escritura::Actualizar(id, datos, version);
+        match self.simular(cine, escritura, actor, "Error al actualizar entrada").await?.pop() {
+            Some(Ok(EntradaSimulada::Actualizada(entrada))) => Ok(entrada),
+            Some(Err(e)) => Err(e),
+            _ => Err(ErrorEntrada::NoEncontrada),
+        }
+    }
+
+    /// Elimina la entrada si sigue en `version` (cualquiera con `None`).
+    pub async fn eliminar(&self, cine: u32, id: u32, version: Option<u32>, actor: &str) -> Result<(), ErrorEntrada> {
+        let eliminada = self
+            .repositorio
+            .eliminar(cine, id, version, actor)
+            .await
+            .map_err(|e| convertir(e, "Error al eliminar entrada"))?;
+        if eliminada { Ok(()) } else { Err(ErrorEntrada::NoEncontrada) }
+    }
+
+    /// Pasa la entrada a `estado`: se paga, se usa o se cancela. Las transiciones que el
+    /// estado actual no admite son un [`ErrorEntrada::TransicionInvalida`].
+    pub async fn cambiar_estado(
+        &self,
+        cine: u32,
+        id: u32,
+        estado: EstadoEntrada,
+        actor: &str,
+    ) -> Result<(), ErrorEntrada> {
+        let cambiada = self
+            .repositorio
+            .cambiar_estado(cine, id, estado, actor)
+            .await
+            .map_err(|e| convertir(e, "Error al cambiar el estado de la entrada"))?;
+        if cambiada { Ok(()) } else { Err(ErrorEntrada::NoEncontrada) }
+    }
+
+    /// Cancela las entradas que siguen reservadas más de `antiguedad` después de su alta,
+    /// liberando su capacidad, y devuelve cuántas eran. Abarca todos los cines.
+    pub async fn cancelar_vencidas(&self, antiguedad: Duration, actor: &str) -> Result<u64, ErrorEntrada> {
+        self.repositorio
+            .cancelar_vencidas(antiguedad, actor)
+            .await
+            .map_err(|e| convertir(e, "Error al cancelar reservas vencidas"))
+    }
+
+    /// Cancela de una vez, en una transacción, las entradas reservadas o pagadas de la
+    /// función, con `motivo` en la auditoría de cada una, y devuelve sus IDs. Sus asientos
+    /// quedan libres y cada una deja su evento para los webhooks.
+    pub async fn cancelar_funcion(
+        &self,
+        cine: u32,
+        funcion_id: u32,
+        motivo: &str,
+        actor: &str,
+    ) -> Result<Vec<u32>, ErrorEntrada> {
+        let motivo = motivo.trim();
+        if motivo.is_empty() || motivo.chars().count() > MOTIVO_MAXIMO {
+            return Err(ErrorEntrada::ParametrosInvalidos(format!(
+                "El motivo debe tener entre 1 y {} caracteres",
+                MOTIVO_MAXIMO
+            )));
+        }
+        self.repositorio
+            .cancelar_funcion(cine, funcion_id, motivo, actor)
+            .await
+            .map_err(|e| convertir(e, "Error al cancelar las entradas de la función"))
+    }
+
+    /// Elimina de una vez, en una transacción, las entradas que cumplen el filtro y
+    /// devuelve cuántas eran. Exige al menos un criterio para no vaciar la tabla por error.
+    pub async fn eliminar_filtradas(
+        &self,
+        cine: u32,
+        filtro: &EliminarEntradas,
+        actor: &str,
+    ) -> Result<u64, ErrorEntrada> {
+        if filtro.funcion_id.is_none() && filtro.ids.is_none() {
+            return Err(ErrorEntrada::ParametrosInvalidos("Indique funcion_id, ids o ambos".to_string()));
+        }
+        if let Some(ids) = &filtro.ids
+            && (ids.is_empty() || ids.len() > IDS_POR_ELIMINACION)
+        {
+            return Err(ErrorEntrada::ParametrosInvalidos(format!(
+                "La lista de ids debe tener entre 1 y {} elementos",
+                IDS_POR_ELIMINACION
+            )));
+        }
+        self.repositorio
+            .eliminar_filtradas(cine, filtro, actor)
+            .await
+            .map_err(|e| convertir(e, "Error al eliminar entradas"))
+    }
+
+    /// Cambios registrados de la entrada, también si ya se eliminó. Es una entrada no
+    /// encontrada sólo si no existe ni tiene ningún cambio registrado.
+    pub async fn historial(&self, cine: u32, id: u32) -> Result<Vec<RegistroAuditoria>, ErrorEntrada> {
+        let registros = self
+            .repositorio
+            .historial(cine, id)
+            .await
+            .map_err(|e| convertir(e, "Error al obtener el historial"))?;
+        if registros.is_empty() {
+            self.obtener(cine, id).await?;
+        }
+        Ok(registros)
+    }
+
+    /// Agregación por los campos solicitados. Las peticiones concurrentes con los mismos
+    /// parámetros comparten una sola consulta.
+    pub async fn agregar(&self, cine: u32, parametros: ParametrosAgregado) -> Result<Arc<ResultadoAgregado>, ErrorEntrada> {
+        let clave = format!("{}|{:?}|{:?}", cine, parametros.group_by, parametros.agg);
+        let repositorio = self.repositorio.clone();
+        self.lecturas
+            .agregados
+            .ejecutar(clave, async move {
+                repositorio
+                    .agregar(cine, &parametros)
+                    .await
+                    .map(Arc::new)
+                    .map_err(|e| convertir(e, "Error al obtener agregados"))
+            })
+            .await
+    }
+}
+
+/// Resultados de un lote que no pasó las comprobaciones previas: las entradas válidas
+/// quedan revertidas.
+fn rechazos<T>(validaciones: Vec<Result<(), ErrorEntrada>>) -> Vec<ResultadoLote<T>> {
+    validaciones
+        .into_iter()
+        .map(|validacion| match validacion {
+            Ok(()) => ResultadoLote::Revertida,
+            Err(error) => ResultadoLote::Rechazada(error),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, TimeDelta};
+    use crate::cache::{CacheLecturas, RepositorioCache};
+    use crate::db::memoria::RepositorioMemoria;
+    use crate::reloj::RelojFijo;
+
+    const CINE: u32 = crate::cines::CINE_PRINCIPAL;
+
+    fn servicio() -> ServicioEntradas {
+        con_limite(None)
+    }
+
+    fn con_limite(limite: Option<u32>) -> ServicioEntradas {
+        ServicioEntradas::new(
+            Arc::new(RepositorioMemoria::default().con_limite_por_cedula(limite)),
+            ReglasValidacion { cedula_ecuatoriana: true },
+            FixedOffset::west_opt(5 * 3600).unwrap(),
+        )
+    }
+
+    fn nueva(cliente_id: u32) -> CrearEntrada {
+        CrearEntrada { cliente_id, funcion_id: 1, cantidad_entradas: 2, asientos: Vec::new() }
+    }
+
+    #[actix_web::test]
+    async fn ciclo_de_vida_de_una_entrada() {
+        let servicio = servicio();
+        servicio.crear(CINE, &nueva(1), "admin").await.unwrap();
+
+        let datos = ActualizarEntrada { cantidad_entradas: Some(5), ..Default::default() };
+        servicio.actualizar(CINE, 1, &datos, Some(1), "admin").await.unwrap();
+        assert_eq!(servicio.obtener(CINE, 1).await.unwrap().cantidad_entradas, 5);
+        assert_eq!(servicio.obtener(CINE, 1).await.unwrap().total, 32.5);
+        // El horario, guardado en UTC, se entrega en la zona del servicio.
+        assert_eq!(servicio.obtener(CINE, 1).await.unwrap().funcion.horario.to_rfc3339(), "2024-03-01T14:00:00-05:00");
+
+        let consulta = ConsultaListado {
+            pagina: 1,
+            por_pagina: 10,
+            filtros: Vec::new(),
+            orden: "id",
+            descendente: false,
+            busqueda: None,
+            campos: None,
+            por_cursor: false,
+            despues: None,
+        };
+        let pagina = servicio.listar(CINE, &consulta).await.unwrap();
+        assert_eq!(pagina.total, 1);
+        assert_eq!(pagina.filas.collect::<Vec<_>>().await.len(), 1);
+
+        servicio.eliminar(CINE, 1, None, "admin").await.unwrap();
+        assert_eq!(servicio.obtener(CINE, 1).await.unwrap_err(), ErrorEntrada::NoEncontrada);
+        assert_eq!(servicio.eliminar(CINE, 1, None, "admin").await, Err(ErrorEntrada::NoEncontrada));
+    }
+
+    #[actix_web::test]
+    async fn la_cache_sirve_las_lecturas_y_se_invalida_al_escribir() {
+        let cache = Arc::new(CacheLecturas::new(Duration::from_secs(60), None));
+        let servicio = ServicioEntradas::new(
+            Arc::new(RepositorioCache::new(Arc::new(RepositorioMemoria::default()), cache.clone())),
+            ReglasValidacion { cedula_ecuatoriana: true },
+            FixedOffset::west_opt(5 * 3600).unwrap(),
+        );
+        servicio.crear(CINE, &nueva(1), "admin").await.unwrap();
+        servicio.obtener(CINE, 1).await.unwrap();
+        let entrada = servicio.obtener(CINE, 1).await.unwrap();
+        assert_eq!(entrada.funcion.horario.to_rfc3339(), "2024-03-01T14:00:00-05:00");
+        let estadisticas = cache.entradas.estadisticas();
+        assert_eq!((estadisticas.aciertos, estadisticas.fallos), (1, 1));
+
+        let datos = ActualizarEntrada { cantidad_entradas: Some(5), ..Default::default() };
+        servicio.actualizar(CINE, 1, &datos, None, "admin").await.unwrap();
+        assert_eq!(servicio.obtener(CINE, 1).await.unwrap().cantidad_entradas, 5);
+        servicio.eliminar(CINE, 1, None, "admin").await.unwrap();
+        assert_eq!(servicio.obtener(CINE, 1).await.unwrap_err(), ErrorEntrada::NoEncontrada);
+    }
+
+    #[actix_web::test]
+    async fn el_estado_solo_avanza_por_transiciones_validas() {
+        let servicio = servicio();
+        servicio.crear(CINE, &nueva(1), "admin").await.unwrap();
+        servicio.crear(CINE, &nueva(2), "admin").await.unwrap();
+        assert_eq!(servicio.obtener(CINE, 1).await.unwrap().estado, EstadoEntrada::Reservada);
+
+        assert_eq!(
+            servicio.cambiar_estado(CINE, 1, EstadoEntrada::Usada, "admin").await,
+            Err(ErrorEntrada::TransicionInvalida(EstadoEntrada::Reservada))
+        );
+        servicio.cambiar_estado(CINE, 1, EstadoEntrada::Pagada, "admin").await.unwrap();
+        servicio.cambiar_estado(CINE, 1, EstadoEntrada::Usada, "admin").await.unwrap();
+        assert_eq!(
+            servicio.cambiar_estado(CINE, 1, EstadoEntrada::Cancelada, "admin").await,
+            Err(ErrorEntrada::TransicionInvalida(EstadoEntrada::Usada))
+        );
+        let entrada = servicio.obtener(CINE, 1).await.unwrap();
+        assert_eq!((entrada.estado, entrada.version), (EstadoEntrada::Usada, 3));
+
+        servicio.cambiar_estado(CINE, 2, EstadoEntrada::Cancelada, "admin").await.unwrap();
+        assert_eq!(
+            servicio.cambiar_estado(CINE, 2, EstadoEntrada::Pagada, "admin").await,
+            Err(ErrorEntrada::TransicionInvalida(EstadoEntrada::Cancelada))
+        );
+        assert_eq!(servicio.cambiar_estado(CINE, 9, EstadoEntrada::Pagada, "admin").await, Err(ErrorEntrada::NoEncontrada));
+    }
+
+    #[actix_web::test]
+    async fn las_reservas_vencidas_se_cancelan() {
+        let reloj = Arc::new(RelojFijo::new(DateTime::parse_from_rfc3339("2024-03-01T18:30:00Z").unwrap().to_utc()));
+        let servicio = ServicioEntradas::new(
+            Arc::new(RepositorioMemoria::default().con_reloj(reloj.clone())),
+            ReglasValidacion { cedula_ecuatoriana: true },
+            FixedOffset::west_opt(5 * 3600).unwrap(),
+        );
+        servicio.crear(CINE, &nueva(1), "admin").await.unwrap();
+        servicio.crear(CINE, &nueva(2), "admin").await.unwrap();
+        servicio.cambiar_estado(CINE, 2, EstadoEntrada::Pagada, "admin").await.unwrap();
+        assert_eq!(servicio.cancelar_vencidas(Duration::from_secs(900), "vencimiento").await, Ok(0));
+
+        reloj.avanzar(TimeDelta::minutes(10));
+        servicio.crear(CINE, &nueva(3), "admin").await.unwrap();
+        reloj.avanzar(TimeDelta::minutes(6));
+        assert_eq!(servicio.cancelar_vencidas(Duration::from_secs(900), "vencimiento").await, Ok(1));
+        let vencida = servicio.obtener(CINE, 1).await.unwrap();
+        assert_eq!(vencida.estado, EstadoEntrada::Cancelada);
+        assert_eq!(vencida.created_at, "2024-03-01 18:30:00");
+        assert_eq!(vencida.updated_at, "2024-03-01 18:46:00");
+        assert_eq!(servicio.obtener(CINE, 2).await.unwrap().estado, EstadoEntrada::Pagada);
+        assert_eq!(servicio.obtener(CINE, 3).await.unwrap().estado, EstadoEntrada::Reservada);
+        assert_eq!(servicio.historial(CINE, 1).await.unwrap().last().unwrap().actor, "vencimiento");
+        assert_eq!(servicio.cancelar_vencidas(Duration::from_secs(900), "vencimiento").await, Ok(0));
+    }
+
+    #[actix_web::test]
+    async fn una_cedula_no_pasa_del_limite_por_funcion() {
+        let servicio = con_limite(Some(4));
+        // Dos ventas separadas de la misma cédula para la misma función suman para el límite.
+        let primera = servicio.crear(CINE, &nueva(1), "admin").await.unwrap();
+        let segunda = servicio.crear(CINE, &nueva(1), "admin").await.unwrap();
+        assert_ne!(primera, segunda);
+        let otra = CrearEntrada { cantidad_entradas: 1, ..nueva(1) };
+        assert_eq!(servicio.crear(CINE, &otra, "admin").await, Err(ErrorEntrada::LimiteCedula(0)));
+        let otra_funcion = CrearEntrada { funcion_id: 2, cantidad_entradas: 4, ..nueva(1) };
+        servicio.crear(CINE, &otra_funcion, "admin").await.unwrap();
+
+        // Al actualizar no cuenta la propia entrada, ni las canceladas.
+        let datos = ActualizarEntrada { cantidad_entradas: Some(3), ..Default::default() };
+        assert_eq!(servicio.actualizar(CINE, primera, &datos, None, "admin").await, Err(ErrorEntrada::LimiteCedula(2)));
+        servicio.cambiar_estado(CINE, segunda, EstadoEntrada::Cancelada, "admin").await.unwrap();
+        servicio.actualizar(CINE, primera, &datos, None, "admin").await.unwrap();
+
+        // En un lote también cuentan las entradas anteriores del mismo lote.
+        let lote = [nueva(2), CrearEntrada { cantidad_entradas: 3, ..nueva(2) }];
+        let resultados = servicio.crear_lote(CINE, &lote, "admin").await.unwrap();
+        assert_eq!(resultados, [ResultadoLote::Revertida, ResultadoLote::Rechazada(ErrorEntrada::LimiteCedula(2))]);
+    }
+
+    #[actix_web::test]
+    async fn las_simulaciones_no_guardan_nada() {
+        let servicio = servicio();
+        let entrada = servicio.simular_alta(CINE, &nueva(1), "admin").await.unwrap();
+        assert_eq!((entrada.id, entrada.total), (Some(1), 13.0));
+        assert_eq!(servicio.obtener(CINE, 1).await.unwrap_err(), ErrorEntrada::NoEncontrada);
+
+        let id = servicio.crear(CINE, &nueva(1), "admin").await.unwrap();
+        let invalida = CrearEntrada { cantidad_entradas: 0, ..nueva(1) };
+        assert!(matches!(servicio.simular_alta(CINE, &invalida, "admin").await, Err(ErrorEntrada::CamposInvalidos(_))));
+        let datos = ActualizarEntrada { cantidad_entradas: Some(4), ..Default::default() };
+        let simulada = servicio.simular_actualizacion(CINE, id, &datos, Some(1), "admin").await.unwrap();
+        assert_eq!((simulada.cantidad_entradas, simulada.version), (4, 2));
+        assert_eq!(servicio.obtener(CINE, id).await.unwrap().cantidad_entradas, 2);
+        assert_eq!(servicio.historial(CINE, id).await.unwrap().len(), 1);
+
+        let resultados = servicio.simular_lote(CINE, &[nueva(2), nueva(3)], "admin").await.unwrap();
+        assert!(matches!(&resultados[..], [ResultadoLote::Creada(_), ResultadoLote::Creada(_)]));
+        assert_eq!(servicio.obtener(CINE, 2).await.unwrap_err(), ErrorEntrada::NoEncontrada);
+    }
+
+    #[actix_web::test]
+    async fn cancelar_una_funcion_cancela_sus_entradas() {
+        let servicio = servicio();
+        for cliente_id in 1..=3 {
+            servicio.crear(CINE, &nueva(cliente_id), "admin").await.unwrap();
+        }
+        servicio.cambiar_estado(CINE, 2, EstadoEntrada::Pagada, "admin").await.unwrap();
+        servicio.cambiar_estado(CINE, 3, EstadoEntrada::Pagada, "admin").await.unwrap();
+        servicio.cambiar_estado(CINE, 3, EstadoEntrada::Usada, "admin").await.unwrap();
+        assert!(matches!(
+            servicio.cancelar_funcion(CINE, 1, " ", "admin").await,
+            Err(ErrorEntrada::ParametrosInvalidos(_))
+        ));
+
+        assert_eq!(servicio.cancelar_funcion(CINE, 1, "Falla del proyector", "admin").await.unwrap(), [1, 2]);
+        assert_eq!(servicio.obtener(CINE, 2).await.unwrap().estado, EstadoEntrada::Cancelada);
+        assert_eq!(servicio.obtener(CINE, 3).await.unwrap().estado, EstadoEntrada::Usada);
+        let historial = servicio.historial(CINE, 1).await.unwrap();
+        assert_eq!(historial.last().unwrap().motivo.as_deref(), Some("Falla del proyector"));
+        assert!(servicio.cancelar_funcion(CINE, 1, "Otra vez", "admin").await.unwrap().is_empty());
+    }
+
+    #[actix_web::test]
+    async fn un_lote_se_guarda_entero_o_no_se_guarda() {
+        let servicio = con_limite(Some(4));
+        let lote = [nueva(1), nueva(2), CrearEntrada { cantidad_entradas: 3, ..nueva(1) }];
+        let resultados = servicio.crear_lote(CINE, &lote, "admin").await.unwrap();
+        assert_eq!(
+            resultados,
+            [ResultadoLote::Revertida, ResultadoLote::Revertida, ResultadoLote::Rechazada(ErrorEntrada::LimiteCedula(2))]
+        );
+        assert_eq!(servicio.obtener(CINE, 1).await.unwrap_err(), ErrorEntrada::NoEncontrada);
+
+        let resultados = servicio.crear_lote(CINE, &lote[..2], "admin").await.unwrap();
+        assert_eq!(resultados, [ResultadoLote::Creada(1), ResultadoLote::Creada(2)]);
+        assert!(servicio.crear_lote(CINE, &[], "admin").await.is_err());
+    }
+
+    #[actix_web::test]
+    async fn la_importacion_guarda_todo_lo_valido() {
+        let servicio = con_limite(Some(4));
+        let mut entradas: Vec<_> = (1..=ENTRADAS_POR_LOTE as u32 + 2).map(nueva).collect();
+        entradas[1] = CrearEntrada { cantidad_entradas: 3, ..nueva(1) };
+        entradas[3].cantidad_entradas = 0;
+        let resultados = servicio.importar(CINE, &entradas, "admin").await.unwrap();
+        assert_eq!(resultados.len(), entradas.len());
+        assert_eq!(resultados[1], ResultadoLote::Rechazada(ErrorEntrada::LimiteCedula(2)));
+        assert!(matches!(resultados[3], ResultadoLote::Rechazada(ErrorEntrada::CamposInvalidos(_))));
+        let creadas = resultados.iter().filter(|r| matches!(r, ResultadoLote::Creada(_))).count();
+        assert_eq!(creadas, entradas.len() - 2);
+    }
+
+    #[actix_web::test]
+    async fn los_cambios_exigen_la_version_vigente() {
+        let servicio = servicio();
+        servicio.crear(CINE, &nueva(1), "admin").await.unwrap();
+        let datos = ActualizarEntrada { cantidad_entradas: Some(3), ..Default::default() };
+        servicio.actualizar(CINE, 1, &datos, Some(1), "admin").await.unwrap();
+        assert_eq!(servicio.obtener(CINE, 1).await.unwrap().version, 2);
+
+        // Quien leyó la versión 1 ya no puede cambiarla ni eliminarla.
+        assert_eq!(servicio.actualizar(CINE, 1, &datos, Some(1), "admin").await, Err(ErrorEntrada::VersionDistinta(2)));
+        assert_eq!(servicio.eliminar(CINE, 1, Some(1), "admin").await, Err(ErrorEntrada::VersionDistinta(2)));
+        servicio.eliminar(CINE, 1, Some(2), "admin").await.unwrap();
+    }
+
+    #[actix_web::test]
+    async fn el_historial_registra_cada_cambio_y_sobrevive_a_la_baja() {
+        let servicio = servicio();
+        servicio.crear(CINE, &nueva(1), "ana").await.unwrap();
+        let datos = ActualizarEntrada { cantidad_entradas: Some(4), ..Default::default() };
+        servicio.actualizar(CINE, 1, &datos, None, "luis").await.unwrap();
+        servicio.eliminar(CINE, 1, None, "admin").await.unwrap();
+
+        let historial = servicio.historial(CINE, 1).await.unwrap();
+        let resumen: Vec<_> = historial.iter().map(|r| (r.operacion.as_str(), r.actor.as_str())).collect();
+        assert_eq!(resumen, [("crear", "ana"), ("actualizar", "luis"), ("eliminar", "admin")]);
+        assert!(historial[0].valor_anterior.is_none() && historial[2].valor_nuevo.is_none());
+        assert_eq!(historial[1].valor_anterior.as_ref().unwrap()["cantidad_entradas"], 2);
+        assert_eq!(historial[1].valor_nuevo.as_ref().unwrap()["cantidad_entradas"], 4);
+
+        assert_eq!(servicio.historial(CINE, 2).await, Err(ErrorEntrada::NoEncontrada));
+    }
+
+    #[actix_web::test]
+    async fn guardar_por_cedula_crea_y_despues_actualiza() {
+        let servicio = servicio();
+        let datos = GuardarEntrada { funcion_id: 1, cantidad_entradas: 2 };
+        assert_eq!(servicio.guardar_por_cedula(CINE, "0000000007", &datos, "admin").await, Ok(EntradaGuardada::Creada(1)));
+        assert_eq!(servicio.guardar_por_cedula(CINE, "0000000007", &datos, "admin").await, Ok(EntradaGuardada::Actualizada(1)));
+
+        // Cada función tiene su propia entrada.
+        let datos = GuardarEntrada { funcion_id: 2, cantidad_entradas: 4 };
+        assert_eq!(servicio.guardar_por_cedula(CINE, "0000000007", &datos, "admin").await, Ok(EntradaGuardada::Creada(2)));
+        let datos = GuardarEntrada { funcion_id: 1, cantidad_entradas: 3 };
+        assert_eq!(servicio.guardar_por_cedula(CINE, "0000000007", &datos, "admin").await, Ok(EntradaGuardada::Actualizada(1)));
+        let entrada = servicio.obtener(CINE, 1).await.unwrap();
+        assert_eq!((entrada.funcion.id, entrada.cantidad_entradas), (1, 3));
+
+        let datos = GuardarEntrada { funcion_id: 1, cantidad_entradas: 0 };
+        assert!(matches!(
+            servicio.guardar_por_cedula(CINE, "0000000007", &datos, "admin").await,
+            Err(ErrorEntrada::CamposInvalidos(_))
+        ));
+    }
+
+    #[actix_web::test]
+    async fn elimina_por_filtro_y_exige_algun_criterio() {
+        let servicio = servicio();
+        for cliente_id in 1..=3 {
+            servicio.crear(CINE, &nueva(cliente_id), "admin").await.unwrap();
+        }
+        let filtro = EliminarEntradas { funcion_id: Some(1), ids: Some(vec![1, 3, 99]) };
+        assert_eq!(servicio.eliminar_filtradas(CINE, &filtro, "admin").await, Ok(2));
+        assert!(servicio.obtener(CINE, 2).await.is_ok());
+
+        assert!(servicio.eliminar_filtradas(CINE, &EliminarEntradas::default(), "admin").await.is_err());
+        let vacia = EliminarEntradas { ids: Some(Vec::new()), ..Default::default() };
+        assert!(servicio.eliminar_filtradas(CINE, &vacia, "admin").await.is_err());
+    }
+
+    #[actix_web::test]
+    async fn rechaza_campos_invalidos_antes_del_repositorio() {
+        let servicio = servicio();
+        let entrada = CrearEntrada { cantidad_entradas: 0, ..nueva(1) };
+        assert!(matches!(servicio.crear(CINE, &entrada, "admin").await, Err(ErrorEntrada::CamposInvalidos(_))));
+        assert_eq!(servicio.obtener(CINE, 1).await.unwrap_err(), ErrorEntrada::NoEncontrada);
+    }
+
+    #[actix_web::test]
+    async fn actualizar_sin_datos_no_llega_al_repositorio() {
+        let servicio = servicio();
+        assert_eq!(
+            servicio.actualizar(CINE, 99, &ActualizarEntrada::default(), None, "admin").await,
+            Err(ErrorEntrada::SinDatos)
+        );
+    }
+
+    #[actix_web::test]
+    async fn las_entradas_de_otro_cine_no_existen() {
+        let servicio = servicio();
+        servicio.crear(CINE, &nueva(1), "admin").await.unwrap();
+        // Cada cine tiene sus clientes, así que el mismo ID no choca con el de otro cine.
+        servicio.crear(2, &nueva(1), "admin").await.unwrap();
+
+        assert_eq!(servicio.obtener(2, 1).await.unwrap_err(), ErrorEntrada::NoEncontrada);
+        assert_eq!(servicio.eliminar(2, 1, None, "admin").await, Err(ErrorEntrada::NoEncontrada));
+        assert_eq!(servicio.historial(2, 1).await, Err(ErrorEntrada::NoEncontrada));
+        assert_eq!(servicio.obtener(CINE, 1).await.unwrap().cliente.id, 1);
+        assert_eq!(servicio.obtener(2, 2).await.unwrap().cliente.id, 1);
+    }
+}
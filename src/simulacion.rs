@@ -0,0 +1,100 @@
+//! Simulación de escrituras con `?dry_run=true`.
+//!
+//! Las rutas que crean o modifican entradas aceptan `dry_run=true`: hacen todas las
+//! comprobaciones de la escritura real (validación, capacidad, asientos, límite por
+//! cédula) dentro de una transacción que se revierte, y responden con la entrada tal
+//! como quedaría y `"simulacion": true` en `meta`. No se guarda nada, ni se publican
+//! eventos ni se registra la clave de idempotencia.
+//!
+//! El resto de rutas que escriben rechazan `dry_run=true` con un 400 en lugar de ignorarlo,
+//! para que un cliente que cree estar simulando no guarde nada por error.
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::http::Method;
+use actix_web::middleware::Next;
+use actix_web::{web, Error, FromRequest, HttpRequest};
+use futures_util::future::{ready, Ready};
+use serde::Deserialize;
+
+use crate::error::ApiError;
+
+/// Rutas que admiten `dry_run`, sin el prefijo de versión.
+const RUTAS_SIMULABLES: &[&str] = &["/entradas", "/entradas/bulk", "/entradas/{id}", "/entradas/cedula/{numero_cedula}"];
+
+#[derive(Deserialize)]
+struct Parametros {
+    #[serde(default)]
+    dry_run: bool,
+}
+
+/// Si la petición pide sólo simular la escritura.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Simulacion(pub bool);
+
+impl Simulacion {
+    fn de_consulta(consulta: &str) -> Result<Simulacion, ApiError> {
+        web::Query::<Parametros>::from_query(consulta)
+            .map(|parametros| Simulacion(parametros.dry_run))
+            .map_err(|_| ApiError::Validacion("El parámetro dry_run debe ser true o false".to_string()))
+    }
+}
+
+impl FromRequest for Simulacion {
+    type Error = ApiError;
+    type Future = Ready<Result<Simulacion, ApiError>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(Simulacion::de_consulta(req.query_string()))
+    }
+}
+
+/// Middleware que rechaza `dry_run=true` en las escrituras que no lo admiten.
+pub async fn rechazar_no_simulables(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let escritura = matches!(*req.method(), Method::POST | Method::PUT | Method::PATCH);
+    if escritura && req.query_string().contains("dry_run") && Simulacion::de_consulta(req.query_string())?.0 {
+        let patron = req.match_pattern().unwrap_or_default();
+        let ruta = patron.strip_prefix("/v1").unwrap_or(&patron);
+        if !RUTAS_SIMULABLES.contains(&ruta) {
+            return Err(ApiError::Validacion("Esta ruta no admite dry_run".to_string()).into());
+        }
+    }
+    next.call(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+    use actix_web::middleware::from_fn;
+    use actix_web::test::{init_service, try_call_service, TestRequest};
+    use actix_web::{App, HttpResponse};
+
+    #[actix_web::test]
+    async fn solo_las_rutas_simulables_admiten_dry_run() {
+        let app = init_service(
+            App::new()
+                .service(
+                    web::scope("/v1")
+                        .service(web::scope("/entradas").route("/{id}", web::patch().to(HttpResponse::Ok)))
+                        .service(web::scope("/salas").route("", web::post().to(HttpResponse::Ok))),
+                )
+                .wrap(from_fn(rechazar_no_simulables)),
+        )
+        .await;
+
+        let estado = async |metodo: Method, uri: &str| {
+            match try_call_service(&app, TestRequest::default().method(metodo).uri(uri).to_request()).await {
+                Ok(res) => res.status(),
+                Err(error) => error.as_response_error().status_code(),
+            }
+        };
+        assert_eq!(estado(Method::PATCH, "/v1/entradas/3?dry_run=true").await, StatusCode::OK);
+        assert_eq!(estado(Method::POST, "/v1/salas?dry_run=false").await, StatusCode::OK);
+        assert_eq!(estado(Method::POST, "/v1/salas?dry_run=true").await, StatusCode::BAD_REQUEST);
+        assert_eq!(estado(Method::PATCH, "/v1/entradas/3?dry_run=quizas").await, StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,240 @@
+//! Seguimiento de SLOs de latencia y errores por ruta.
+//!
+//! Cada petición cuenta como "buena" si responde sin error 5xx y dentro de la latencia
+//! objetivo de su ruta. El cumplimiento se calcula sobre una ventana móvil dividida en
+//! cubetas de un minuto.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{web, Error, Responder};
+use serde::Serialize;
+
+use crate::respuesta::ApiResponse;
+
+/// Peticiones mínimas en la ventana antes de evaluar alertas, para no alertar por una sola petición lenta.
+const PETICIONES_MINIMAS_ALERTA: u64 = 20;
+
+/// Objetivo de una ruta: latencia máxima de una petición buena y fracción de peticiones buenas exigida.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ObjetivoSlo {
+    pub latencia: Duration,
+    pub objetivo: f64,
+}
+
+/// Configuración de los SLOs.
+#[derive(Debug, Clone)]
+pub struct ConfigSlo {
+    /// Objetivo de las rutas sin configuración propia.
+    pub por_defecto: ObjetivoSlo,
+    /// Objetivos por ruta, con claves `"METODO /patron"` (por ejemplo `"GET /entradas/{id}"`).
+    pub rutas: HashMap<String, ObjetivoSlo>,
+    pub ventana: Duration,
+    /// Burn rate a partir del cual se registra una alerta en el log. `None` desactiva las alertas.
+    pub alerta_burn_rate: Option<f64>,
+}
+
+impl ConfigSlo {
+    /// Interpreta objetivos por ruta con el formato `METODO /ruta=LATENCIA_MS:OBJETIVO_%`,
+    /// separados por `;`. Ejemplo: `GET /entradas=300:99.5;POST /entradas=800:99`.
+    pub fn parsear_rutas(texto: &str) -> Result<HashMap<String, ObjetivoSlo>, String> {
+        let mut rutas = HashMap::new();
+        for definicion in texto.split(';').map(str::trim).filter(|d| !d.is_empty()) {
+            let invalida = || format!("Definición de SLO inválida: '{}'", definicion);
+            let (ruta, objetivo) = definicion.split_once('=').ok_or_else(invalida)?;
+            let (latencia, porcentaje) = objetivo.split_once(':').ok_or_else(invalida)?;
+            let latencia: u64 = latencia.trim().parse().map_err(|_| invalida())?;
+            let porcentaje: f64 = porcentaje.trim().parse().map_err(|_| invalida())?;
+            if !(0.0..100.0).contains(&porcentaje) {
+                return Err(invalida());
+            }
+            let ruta = ruta.split_whitespace().collect::<Vec<_>>().join(" ");
+            rutas.insert(
+                ruta,
+                ObjetivoSlo {
+                    latencia: Duration::from_millis(latencia),
+                    objetivo: porcentaje / 100.0,
+                },
+            );
+        }
+        Ok(rutas)
+    }
+}
+
+/// Cubeta de un minuto de la ventana móvil.
+#[derive(Debug, Clone, Copy)]
+struct Cubeta {
+    minuto: u64,
+    total: u64,
+    malas: u64,
+}
+
+#[derive(Debug, Default)]
+struct VentanaRuta {
+    cubetas: VecDeque<Cubeta>,
+    ultima_alerta: Option<u64>,
+}
+
+impl VentanaRuta {
+    fn totales(&self) -> (u64, u64) {
+        self.cubetas
+            .iter()
+            .fold((0, 0), |(total, malas), c| (total + c.total, malas + c.malas))
+    }
+}
+
+/// Estado de cumplimiento de una ruta, tal como lo devuelve `/admin/slo`.
+#[derive(Debug, Serialize)]
+pub struct EstadoSlo {
+    pub ruta: String,
+    pub latencia_objetivo_ms: u128,
+    pub objetivo: f64,
+    pub total: u64,
+    pub buenas: u64,
+    pub cumplimiento: f64,
+    /// Fracción del presupuesto de error que queda en la ventana (negativa si se agotó).
+    pub presupuesto_restante: f64,
+    /// Ritmo de consumo del presupuesto: 1.0 lo agota justo al final de la ventana.
+    pub burn_rate: f64,
+}
+
+/// Acumula las mediciones de todas las rutas. Se comparte entre los workers.
+pub struct SeguimientoSlo {
+    config: ConfigSlo,
+    inicio: Instant,
+    rutas: Mutex<HashMap<String, VentanaRuta>>,
+}
+
+impl SeguimientoSlo {
+    pub fn new(config: ConfigSlo) -> Self {
+        SeguimientoSlo {
+            config,
+            inicio: Instant::now(),
+            rutas: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn objetivo(&self, ruta: &str) -> ObjetivoSlo {
+        self.config.rutas.get(ruta).copied().unwrap_or(self.config.por_defecto)
+    }
+
+    fn minuto_actual(&self) -> u64 {
+        self.inicio.elapsed().as_secs() / 60
+    }
+
+    fn minutos_ventana(&self) -> u64 {
+        self.config.ventana.as_secs().div_ceil(60).max(1)
+    }
+
+    /// Registra una petición completada.
+    pub fn registrar(&self, ruta: &str, latencia: Duration, error_servidor: bool) {
+        let objetivo = self.objetivo(ruta);
+        let mala = error_servidor || latencia > objetivo.latencia;
+        let minuto = self.minuto_actual();
+        let primer_minuto = minuto.saturating_sub(self.minutos_ventana() - 1);
+
+        let mut rutas = self.rutas.lock().unwrap();
+        let ventana = rutas.entry(ruta.to_string()).or_default();
+        while ventana.cubetas.front().is_some_and(|c| c.minuto < primer_minuto) {
+            ventana.cubetas.pop_front();
+        }
+        match ventana.cubetas.back_mut() {
+            Some(cubeta) if cubeta.minuto == minuto => {
+                cubeta.total += 1;
+                cubeta.malas += u64::from(mala);
+            }
+            _ => ventana.cubetas.push_back(Cubeta { minuto, total: 1, malas: u64::from(mala) }),
+        }
+
+        if let Some(umbral) = self.config.alerta_burn_rate {
+            let (total, malas) = ventana.totales();
+            let burn_rate = calcular_burn_rate(total, malas, objetivo.objetivo);
+            if total >= PETICIONES_MINIMAS_ALERTA && burn_rate > umbral && ventana.ultima_alerta != Some(minuto) {
+                ventana.ultima_alerta = Some(minuto);
+                log::warn!(
+                    "Alerta SLO: {} consume el presupuesto de error a {:.2}x (umbral {:.2}x, {} de {} peticiones malas)",
+                    ruta, burn_rate, umbral, malas, total
+                );
+            }
+        }
+    }
+
+    /// Cumplimiento actual de las rutas con tráfico en la ventana y de las configuradas explícitamente.
+    pub fn estado(&self) -> Vec<EstadoSlo> {
+        let primer_minuto = self.minuto_actual().saturating_sub(self.minutos_ventana() - 1);
+        let rutas = self.rutas.lock().unwrap();
+
+        let mut nombres: Vec<&String> = rutas.keys().chain(self.config.rutas.keys()).collect();
+        nombres.sort();
+        nombres.dedup();
+
+        nombres
+            .into_iter()
+            .map(|ruta| {
+                let (total, malas) = rutas
+                    .get(ruta)
+                    .map(|v| {
+                        v.cubetas
+                            .iter()
+                            .filter(|c| c.minuto >= primer_minuto)
+                            .fold((0, 0), |(t, m), c| (t + c.total, m + c.malas))
+                    })
+                    .unwrap_or((0, 0));
+                let objetivo = self.objetivo(ruta);
+                let burn_rate = calcular_burn_rate(total, malas, objetivo.objetivo);
+                EstadoSlo {
+                    ruta: ruta.clone(),
+                    latencia_objetivo_ms: objetivo.latencia.as_millis(),
+                    objetivo: objetivo.objetivo,
+                    total,
+                    buenas: total - malas,
+                    cumplimiento: if total == 0 { 1.0 } else { (total - malas) as f64 / total as f64 },
+                    presupuesto_restante: 1.0 - burn_rate,
+                    burn_rate,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Proporción de error observada respecto a la permitida por el objetivo.
+fn calcular_burn_rate(total: u64, malas: u64, objetivo: f64) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+    (malas as f64 / total as f64) / (1.0 - objetivo)
+}
+
+/// Middleware que mide cada petición que coincide con una ruta registrada.
+pub async fn medir_slo(
+    seguimiento: web::Data<SeguimientoSlo>,
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let ruta = req
+        .match_pattern()
+        .map(|patron| format!("{} {}", req.method(), patron));
+    let inicio = Instant::now();
+    let respuesta = next.call(req).await;
+
+    if let Some(ruta) = ruta {
+        let error_servidor = match &respuesta {
+            Ok(res) => res.status().is_server_error(),
+            Err(e) => e.as_response_error().status_code().is_server_error(),
+        };
+        seguimiento.registrar(&ruta, inicio.elapsed(), error_servidor);
+    }
+    respuesta
+}
+
+/// Handler que devuelve el cumplimiento y el burn rate de cada ruta.
+pub async fn obtener_slo(seguimiento: web::Data<SeguimientoSlo>) -> impl Responder {
+    ApiResponse::ok(serde_json::json!({
+        "ventana_segundos": seguimiento.config.ventana.as_secs(),
+        "rutas": seguimiento.estado(),
+    }))
+}
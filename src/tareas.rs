@@ -0,0 +1,230 @@
+//! Planificador de tareas periódicas: el vencimiento de reservas, la vigilancia de
+//! dispositivos, la publicación de eventos, la entrega de webhooks y las que registre quien embebe la API con
+//! `ServerBuilder::tarea`.
+//!
+//! Cada tarea se ejecuta en el runtime de actix cada [`Intervalo`], la primera vez al
+//! iniciar, y nunca se solapa consigo misma. El estado de su última ejecución se consulta
+//! en `GET /admin/tareas`. Al detenerse el servidor se dejan de lanzar ejecuciones y se
+//! espera a que terminen las que están en curso.
+
+use std::future::Future;
+use std::pin::pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use actix_web::rt::task::JoinHandle;
+use actix_web::web;
+use chrono::{SecondsFormat, Utc};
+use futures_util::future::{select, Either, FutureExt, LocalBoxFuture};
+use serde::Serialize;
+use tokio::sync::watch;
+
+use crate::auth::Administrador;
+use crate::respuesta::ApiResponse;
+
+/// Cada cuánto se ejecuta una tarea, con la expresión con la que se mostrará.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Intervalo {
+    periodo: Duration,
+    expresion: String,
+}
+
+impl Intervalo {
+    pub fn cada(periodo: Duration) -> Intervalo {
+        Intervalo { periodo, expresion: format!("@every {}s", periodo.as_secs()) }
+    }
+
+    /// Lee una expresión al estilo de cron: `@every` seguido de una cantidad en segundos
+    /// (`30s`), minutos (`15m`), horas (`2h`) o días (`1d`), `@hourly` o `@daily`.
+    pub fn desde_str(expresion: &str) -> Option<Intervalo> {
+        let expresion = expresion.trim();
+        let periodo = match expresion {
+            "@hourly" => Duration::from_secs(3600),
+            "@daily" => Duration::from_secs(24 * 3600),
+            _ => {
+                let cantidad = expresion.strip_prefix("@every ")?.trim();
+                let unidad = match cantidad.chars().last()? {
+                    's' => 1,
+                    'm' => 60,
+                    'h' => 3600,
+                    'd' => 24 * 3600,
+                    _ => return None,
+                };
+                let valor: u64 = cantidad[..cantidad.len() - 1].parse().ok()?;
+                Duration::from_secs(valor.checked_mul(unidad)?)
+            }
+        };
+        if periodo.is_zero() {
+            return None;
+        }
+        Some(Intervalo { periodo, expresion: expresion.to_string() })
+    }
+
+    pub fn periodo(&self) -> Duration {
+        self.periodo
+    }
+}
+
+/// Trabajo de una tarea. Cada llamada es una ejecución; un `Err` es un fallo con su motivo.
+pub type Trabajo = Arc<dyn Fn() -> LocalBoxFuture<'static, Result<(), String>> + Send + Sync>;
+
+/// Convierte una función asíncrona en un [`Trabajo`].
+pub fn trabajo<F, Fut>(funcion: F) -> Trabajo
+where
+    F: Fn() -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<(), String>> + 'static,
+{
+    Arc::new(move || funcion().boxed_local())
+}
+
+/// Estado de una tarea en `GET /admin/tareas`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct EstadoTarea {
+    pub nombre: String,
+    pub intervalo: String,
+    pub ejecuciones: u64,
+    pub fallos: u64,
+    pub en_curso: bool,
+    /// Inicio de la última ejecución, en RFC 3339 y UTC.
+    pub ultima_ejecucion: Option<String>,
+    pub ultima_duracion_ms: Option<u64>,
+    /// Motivo del fallo de la última ejecución, si falló.
+    pub ultimo_error: Option<String>,
+}
+
+struct Tarea {
+    intervalo: Intervalo,
+    trabajo: Trabajo,
+    estado: Mutex<EstadoTarea>,
+}
+
+/// Tareas registradas y las que están en marcha. Se comparte entre los workers.
+pub struct Planificador {
+    tareas: Mutex<Vec<Arc<Tarea>>>,
+    en_marcha: Mutex<Vec<JoinHandle<()>>>,
+    detener: watch::Sender<bool>,
+}
+
+impl Default for Planificador {
+    fn default() -> Self {
+        Planificador {
+            tareas: Mutex::default(),
+            en_marcha: Mutex::default(),
+            detener: watch::Sender::new(false),
+        }
+    }
+}
+
+impl Planificador {
+    /// Registra una tarea. Empieza a ejecutarse con [`Planificador::iniciar`].
+    pub fn registrar(&self, nombre: impl Into<String>, intervalo: Intervalo, trabajo: Trabajo) {
+        let estado = EstadoTarea { nombre: nombre.into(), intervalo: intervalo.expresion.clone(), ..Default::default() };
+        self.tareas.lock().unwrap().push(Arc::new(Tarea { intervalo, trabajo, estado: Mutex::new(estado) }));
+    }
+
+    /// Lanza las tareas registradas que aún no están en marcha. Debe llamarse dentro del
+    /// runtime de actix.
+    pub fn iniciar(&self) {
+        let tareas = self.tareas.lock().unwrap();
+        let mut en_marcha = self.en_marcha.lock().unwrap();
+        for tarea in &tareas[en_marcha.len()..] {
+            en_marcha.push(actix_web::rt::spawn(ejecutar(tarea.clone(), self.detener.subscribe())));
+        }
+    }
+
+    /// Estado de cada tarea, en el orden en que se registraron.
+    pub fn estados(&self) -> Vec<EstadoTarea> {
+        self.tareas.lock().unwrap().iter().map(|tarea| tarea.estado.lock().unwrap().clone()).collect()
+    }
+
+    /// Deja de lanzar ejecuciones y espera a que terminen las que están en curso.
+    pub async fn detener(&self) {
+        self.detener.send_replace(true);
+        let en_marcha = std::mem::take(&mut *self.en_marcha.lock().unwrap());
+        for tarea in en_marcha {
+            let _ = tarea.await;
+        }
+    }
+}
+
+/// Bucle de una tarea: espera su intervalo o la orden de detenerse, lo que llegue antes.
+async fn ejecutar(tarea: Arc<Tarea>, mut detener: watch::Receiver<bool>) {
+    let mut intervalo = actix_web::rt::time::interval(tarea.intervalo.periodo);
+    while !*detener.borrow_and_update() {
+        if let Either::Right(_) = select(pin!(intervalo.tick()), pin!(detener.changed())).await {
+            return;
+        }
+        let inicio = Instant::now();
+        let nombre = {
+            let mut estado = tarea.estado.lock().unwrap();
+            estado.en_curso = true;
+            estado.ultima_ejecucion = Some(Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true));
+            estado.nombre.clone()
+        };
+        let resultado = (tarea.trabajo)().await;
+        if let Err(e) = &resultado {
+            log::warn!("La tarea '{}' falló: {}", nombre, e);
+        }
+        let mut estado = tarea.estado.lock().unwrap();
+        estado.en_curso = false;
+        estado.ejecuciones += 1;
+        estado.fallos += u64::from(resultado.is_err());
+        estado.ultima_duracion_ms = Some(inicio.elapsed().as_millis() as u64);
+        estado.ultimo_error = resultado.err();
+    }
+}
+
+/// Handler con el estado de las tareas periódicas. Sólo para administradores.
+pub async fn listar_tareas(
+    _admin: Administrador,
+    planificador: web::Data<Planificador>,
+) -> ApiResponse<Vec<EstadoTarea>> {
+    ApiResponse::ok(planificador.estados())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use super::*;
+
+    #[test]
+    fn lee_intervalos_al_estilo_de_cron() {
+        assert_eq!(Intervalo::desde_str("@every 30s").unwrap().periodo(), Duration::from_secs(30));
+        assert_eq!(Intervalo::desde_str("@every 15m").unwrap().periodo(), Duration::from_secs(900));
+        assert_eq!(Intervalo::desde_str("@hourly").unwrap().periodo(), Duration::from_secs(3600));
+        assert_eq!(Intervalo::cada(Duration::from_secs(60)).expresion, "@every 60s");
+        for invalida in ["@every 0s", "@every 5", "@every m", "*/5 * * * *", "@weekly"] {
+            assert_eq!(Intervalo::desde_str(invalida), None, "{}", invalida);
+        }
+    }
+
+    #[actix_web::test]
+    async fn ejecuta_al_iniciar_y_registra_los_fallos() {
+        let planificador = Planificador::default();
+        let llamadas = Arc::new(AtomicU32::new(0));
+        let contador = llamadas.clone();
+        planificador.registrar(
+            "contar",
+            Intervalo::cada(Duration::from_secs(3600)),
+            trabajo(move || {
+                contador.fetch_add(1, Ordering::Relaxed);
+                async { Ok(()) }
+            }),
+        );
+        planificador.registrar(
+            "fallar",
+            Intervalo::cada(Duration::from_secs(3600)),
+            trabajo(|| async { Err("sin conexión".to_string()) }),
+        );
+        planificador.iniciar();
+        actix_web::rt::time::sleep(Duration::from_millis(50)).await;
+        planificador.detener().await;
+
+        assert_eq!(llamadas.load(Ordering::Relaxed), 1);
+        let estados = planificador.estados();
+        assert_eq!((estados[0].ejecuciones, estados[0].fallos, estados[0].en_curso), (1, 0, false));
+        assert_eq!((estados[1].fallos, estados[1].ultimo_error.as_deref()), (1, Some("sin conexión")));
+        assert!(estados[1].ultima_ejecucion.is_some());
+    }
+}
@@ -0,0 +1,87 @@
+//! Ticket imprimible de una entrada en PDF, para `GET /entradas/{id}/ticket.pdf`: los datos
+//! del cliente y la función y el código QR de [`crate::qr`] que se escanea en la puerta.
+
+use printpdf::{BuiltinFont, Mm, PdfDocument, Rect};
+use qrcode::{Color, QrCode};
+
+use crate::models::Entrada;
+
+/// Ancho y alto de la página, del tamaño de un ticket de taquilla.
+const ANCHO: f32 = 80.0;
+const ALTO: f32 = 150.0;
+const MARGEN: f32 = 8.0;
+/// Lado del código QR, sin contar su margen blanco.
+const LADO_QR: f32 = 52.0;
+
+/// Genera el PDF de una página con el ticket de `entrada` y su código QR.
+pub fn generar(entrada: &Entrada, codigo: &str) -> Vec<u8> {
+    let id = entrada.id.unwrap_or_default();
+    let (documento, pagina, capa) = PdfDocument::new(format!("Entrada {}", id), Mm(ANCHO), Mm(ALTO), "Ticket");
+    let capa = documento.get_page(pagina).get_layer(capa);
+    let normal = documento.add_builtin_font(BuiltinFont::Helvetica).expect("Fuente incluida en todo lector de PDF");
+    let negrita = documento.add_builtin_font(BuiltinFont::HelveticaBold).expect("Fuente incluida en todo lector de PDF");
+
+    let mut y = ALTO - MARGEN - 6.0;
+    capa.use_text(&entrada.funcion.titulo, 14.0, Mm(MARGEN), Mm(y), &negrita);
+    y -= 7.0;
+    let funcion = &entrada.funcion;
+    let lineas = [
+        format!("{} - {}", funcion.sala.nombre, funcion.horario.format("%d/%m/%Y %H:%M")),
+        format!("Cliente: {}", entrada.cliente.nombre),
+        format!("Cédula: {}", entrada.cliente.numero_cedula),
+        format!("Cantidad: {}", entrada.cantidad_entradas),
+        format!("Total: {:.2}", entrada.total),
+    ];
+    for linea in &lineas {
+        capa.use_text(linea, 9.0, Mm(MARGEN), Mm(y), &normal);
+        y -= 5.0;
+    }
+
+    let qr = QrCode::new(codigo).expect("El código de una entrada cabe en un QR");
+    let modulos = qr.width();
+    let modulo = LADO_QR / modulos as f32;
+    let (izquierda, abajo) = ((ANCHO - LADO_QR) / 2.0, y - 4.0 - LADO_QR);
+    for (indice, color) in qr.to_colors().into_iter().enumerate() {
+        if color == Color::Dark {
+            let (columna, fila) = ((indice % modulos) as f32, (indice / modulos) as f32);
+            let x = izquierda + columna * modulo;
+            let y = abajo + LADO_QR - (fila + 1.0) * modulo;
+            capa.add_rect(Rect::new(Mm(x), Mm(y), Mm(x + modulo), Mm(y + modulo)));
+        }
+    }
+    capa.use_text(format!("Entrada #{}", id), 8.0, Mm(izquierda), Mm(abajo - 6.0), &normal);
+
+    documento.save_to_bytes().expect("Un documento en memoria siempre se puede guardar")
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+
+    use super::*;
+    use crate::models::{Cliente, EstadoEntrada, Funcion, Sala};
+
+    #[test]
+    fn genera_un_pdf_con_el_ticket() {
+        let entrada = Entrada {
+            id: Some(7),
+            cantidad_entradas: 2,
+            total: 13.0,
+            estado: EstadoEntrada::Pagada,
+            cliente: Cliente { id: 1, numero_cedula: "1710034065".into(), nombre: "María Pérez".into(), email: None },
+            funcion: Funcion {
+                id: 1,
+                titulo: "Dune: Parte Dos".into(),
+                horario: DateTime::parse_from_rfc3339("2024-03-01T19:00:00-05:00").unwrap(),
+                precio: 6.5,
+                sala: Sala { id: 1, nombre: "Sala 1".into(), capacidad: 120 },
+                poster: None,
+            },
+            version: 2,
+            created_at: "2024-03-01 18:30:00".into(),
+            updated_at: "2024-03-01 18:30:00".into(),
+        };
+        let pdf = generar(&entrada, &"x".repeat(200));
+        assert!(pdf.starts_with(b"%PDF-"));
+    }
+}
@@ -0,0 +1,77 @@
+//! Tiempo máximo de atención de una petición.
+//!
+//! Si el handler no ha respondido a tiempo se abandona, lo que cancela también su consulta
+//! en curso, y el cliente recibe un 408 con el formato de error de la API. Cuenta hasta que
+//! hay cabeceras de respuesta: un listado que ya empezó a transmitirse no se corta.
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::rt::time::timeout;
+use actix_web::{web, Error};
+
+use crate::error::ApiError;
+use crate::recarga::ConfigVigente;
+
+/// Middleware que aplica `Config::tiempo_maximo_peticion`, con su valor recargado.
+pub async fn limitar_duracion(
+    config: web::Data<ConfigVigente>,
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let limite = config.load().tiempo_maximo_peticion;
+    if limite.is_zero() {
+        return next.call(req).await;
+    }
+    let peticion = format!("{} {}", req.method(), req.path());
+    match timeout(limite, next.call(req)).await {
+        Ok(respuesta) => respuesta,
+        Err(_) => {
+            log::warn!("{} superó el tiempo máximo de {} ms", peticion, limite.as_millis());
+            Err(ApiError::TiempoAgotado(format!("La petición no terminó en {} ms", limite.as_millis())).into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    use crate::config::Config;
+
+    use actix_web::http::StatusCode;
+    use actix_web::middleware::from_fn;
+    use actix_web::rt::time::sleep;
+    use actix_web::test::{call_service, init_service, try_call_service, TestRequest};
+    use actix_web::{App, HttpResponse};
+
+    #[actix_web::test]
+    async fn corta_los_handlers_lentos_con_un_408() {
+        let mut config = Config::new("mysql://localhost/crud");
+        config.tiempo_maximo_peticion = Duration::from_millis(50);
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(ConfigVigente::from_pointee(config)))
+                .route("/rapida", web::get().to(HttpResponse::Ok))
+                .route(
+                    "/lenta",
+                    web::get().to(|| async {
+                        sleep(Duration::from_secs(5)).await;
+                        HttpResponse::Ok().finish()
+                    }),
+                )
+                .wrap(from_fn(limitar_duracion)),
+        )
+        .await;
+
+        let res = call_service(&app, TestRequest::get().uri("/rapida").to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let error = try_call_service(&app, TestRequest::get().uri("/lenta").to_request())
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(error.as_response_error().status_code(), StatusCode::REQUEST_TIMEOUT);
+        assert_eq!(error.to_string(), "La petición no terminó en 50 ms");
+    }
+}
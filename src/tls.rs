@@ -0,0 +1,80 @@
+//! Terminación TLS con rustls, para servir HTTPS sin un proxy delante.
+//!
+//! La configuración existe siempre, pero cargar el certificado necesita la feature `tls`;
+//! sin ella el servidor se niega a arrancar si hay un certificado configurado.
+
+/// Certificado y clave del listener HTTPS.
+#[derive(Debug, Clone)]
+pub struct ConfigTls {
+    /// Cadena de certificados en PEM, empezando por el del servidor.
+    pub certificado: Option<String>,
+    /// Clave privada en PEM (PKCS#8, PKCS#1 o SEC1).
+    pub clave: Option<String>,
+    pub port: u16,
+    /// Si sólo se escucha por HTTPS, sin el listener HTTP de `port`.
+    pub solo_https: bool,
+}
+
+impl ConfigTls {
+    /// Rutas del certificado y la clave si HTTPS está configurado. Falla si falta una de las dos.
+    pub fn archivos(&self) -> Result<Option<(&str, &str)>, String> {
+        match (&self.certificado, &self.clave) {
+            (Some(certificado), Some(clave)) => Ok(Some((certificado, clave))),
+            (None, None) if self.solo_https => Err("TLS_SOLO requiere TLS_CERTIFICADO y TLS_CLAVE".to_string()),
+            (None, None) => Ok(None),
+            _ => Err("TLS_CERTIFICADO y TLS_CLAVE deben configurarse juntas".to_string()),
+        }
+    }
+}
+
+/// Lee el certificado y la clave y arma la configuración de rustls.
+#[cfg(feature = "tls")]
+pub fn cargar(certificado: &str, clave: &str) -> std::io::Result<rustls::ServerConfig> {
+    use std::io::Error;
+    use std::sync::Arc;
+
+    use rustls::pki_types::pem::PemObject;
+    use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+
+    let cadena = CertificateDer::pem_file_iter(certificado)
+        .and_then(|certificados| certificados.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| Error::other(format!("Certificado TLS {} inválido: {}", certificado, e)))?;
+    let clave_privada = PrivateKeyDer::from_pem_file(clave)
+        .map_err(|e| Error::other(format!("Clave TLS {} inválida: {}", clave, e)))?;
+
+    rustls::ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .map_err(Error::other)?
+        .with_no_client_auth()
+        .with_single_cert(cadena, clave_privada)
+        .map_err(|e| Error::other(format!("El certificado y la clave TLS no coinciden: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(certificado: Option<&str>, clave: Option<&str>, solo_https: bool) -> ConfigTls {
+        ConfigTls {
+            certificado: certificado.map(str::to_string),
+            clave: clave.map(str::to_string),
+            port: 8443,
+            solo_https,
+        }
+    }
+
+    #[test]
+    fn certificado_y_clave_van_juntos() {
+        assert_eq!(config(None, None, false).archivos(), Ok(None));
+        assert_eq!(config(Some("c.pem"), Some("k.pem"), true).archivos(), Ok(Some(("c.pem", "k.pem"))));
+        assert!(config(Some("c.pem"), None, false).archivos().is_err());
+        assert!(config(None, None, true).archivos().is_err());
+    }
+
+    #[cfg(feature = "tls")]
+    #[test]
+    fn un_certificado_inexistente_es_un_error() {
+        let error = cargar("/no/existe.pem", "/no/existe.key").unwrap_err();
+        assert!(error.to_string().contains("/no/existe.pem"));
+    }
+}
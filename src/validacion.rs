@@ -0,0 +1,313 @@
+//! Validación de los datos de entrada antes de llegar a la base de datos.
+
+use std::borrow::Cow;
+
+use serde::Serialize;
+
+use crate::models::{
+    ActualizarCliente, ActualizarEntrada, CrearCliente, CrearEntrada, CrearFuncion, CrearSala, GuardarEntrada,
+};
+
+/// Longitud máxima de los campos de texto, la de las columnas `VARCHAR(255)`.
+const LONGITUD_MAXIMA: usize = 255;
+
+/// Longitud máxima de `salas.nombre`.
+const LONGITUD_MAXIMA_SALA: usize = 100;
+
+/// Dígitos admitidos en `numero_cedula`.
+const CEDULA_MIN_DIGITOS: usize = 6;
+const CEDULA_MAX_DIGITOS: usize = 15;
+
+/// Entradas que se pueden comprar en una sola operación.
+pub const CANTIDAD_MINIMA: u32 = 1;
+pub const CANTIDAD_MAXIMA: u32 = 20;
+
+/// Reglas de validación que dependen de la configuración.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReglasValidacion {
+    /// Exige que `numero_cedula` sea una cédula ecuatoriana con dígito verificador correcto.
+    pub cedula_ecuatoriana: bool,
+}
+
+/// Problema encontrado en un campo concreto. Los campos anidados usan la ruta completa,
+/// como `lineas[0].cantidad`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ErrorCampo {
+    pub campo: Cow<'static, str>,
+    pub mensaje: String,
+}
+
+/// Datos que se validan campo a campo, acumulando todos los errores.
+pub trait Validar {
+    fn validar(&self, reglas: &ReglasValidacion) -> Result<(), Vec<ErrorCampo>>;
+}
+
+/// Acumulador de errores por campo.
+#[derive(Default)]
+struct Errores(Vec<ErrorCampo>);
+
+impl Errores {
+    fn agregar(&mut self, campo: &'static str, mensaje: impl Into<String>) {
+        self.0.push(ErrorCampo {
+            campo: campo.into(),
+            mensaje: mensaje.into(),
+        });
+    }
+
+    fn texto(&mut self, campo: &'static str, valor: &str) {
+        self.texto_hasta(campo, valor, LONGITUD_MAXIMA);
+    }
+
+    fn texto_hasta(&mut self, campo: &'static str, valor: &str, maximo: usize) {
+        if valor.trim().is_empty() {
+            self.agregar(campo, "No puede estar vacío");
+        } else if valor.chars().count() > maximo {
+            self.agregar(campo, format!("No puede superar los {} caracteres", maximo));
+        }
+    }
+
+    fn cedula(&mut self, valor: &str, reglas: &ReglasValidacion) {
+        if !valor.chars().all(|c| c.is_ascii_digit()) {
+            self.agregar("numero_cedula", "Sólo puede contener dígitos");
+        } else if reglas.cedula_ecuatoriana {
+            if let Err(mensaje) = verificar_cedula_ecuatoriana(valor) {
+                self.agregar("numero_cedula", mensaje);
+            }
+        } else if !(CEDULA_MIN_DIGITOS..=CEDULA_MAX_DIGITOS).contains(&valor.len()) {
+            self.agregar(
+                "numero_cedula",
+                format!("Debe tener entre {} y {} dígitos", CEDULA_MIN_DIGITOS, CEDULA_MAX_DIGITOS),
+            );
+        }
+    }
+
+    /// Dirección de correo, si la hay: la misma sintaxis que acepta el envío por SMTP.
+    fn email(&mut self, valor: Option<&str>) {
+        if let Some(valor) = valor {
+            if valor.chars().count() > LONGITUD_MAXIMA {
+                self.agregar("email", format!("No puede superar los {} caracteres", LONGITUD_MAXIMA));
+            } else if valor.parse::<lettre::Address>().is_err() {
+                self.agregar("email", "No es una dirección de correo válida");
+            }
+        }
+    }
+
+    fn cantidad(&mut self, valor: u32) {
+        if !(CANTIDAD_MINIMA..=CANTIDAD_MAXIMA).contains(&valor) {
+            self.agregar(
+                "cantidad_entradas",
+                format!("Debe estar entre {} y {}", CANTIDAD_MINIMA, CANTIDAD_MAXIMA),
+            );
+        }
+    }
+
+    fn resultado(self) -> Result<(), Vec<ErrorCampo>> {
+        if self.0.is_empty() { Ok(()) } else { Err(self.0) }
+    }
+}
+
+/// Verifica una cédula ecuatoriana de persona natural: 10 dígitos, código de provincia
+/// (01 a 24, o 30 para inscritos en el exterior), tercer dígito menor que 6 y dígito
+/// verificador por módulo 10 con coeficientes 2-1-2-1...
+pub fn verificar_cedula_ecuatoriana(cedula: &str) -> Result<(), &'static str> {
+    let digitos: Vec<u32> = cedula.chars().filter_map(|c| c.to_digit(10)).collect();
+    if digitos.len() != 10 || cedula.len() != 10 {
+        return Err("Una cédula ecuatoriana tiene 10 dígitos");
+    }
+    let provincia = digitos[0] * 10 + digitos[1];
+    if !(1..=24).contains(&provincia) && provincia != 30 {
+        return Err("El código de provincia de la cédula no es válido");
+    }
+    if digitos[2] >= 6 {
+        return Err("El tercer dígito de la cédula debe ser menor que 6");
+    }
+
+    let suma: u32 = digitos[..9]
+        .iter()
+        .enumerate()
+        .map(|(i, d)| {
+            let producto = if i % 2 == 0 { d * 2 } else { *d };
+            if producto > 9 { producto - 9 } else { producto }
+        })
+        .sum();
+    let verificador = (10 - suma % 10) % 10;
+    if verificador != digitos[9] {
+        return Err("El dígito verificador de la cédula no es correcto");
+    }
+    Ok(())
+}
+
+/// El cliente y la función se comprueban al escribir, por sus claves foráneas.
+impl Validar for CrearEntrada {
+    fn validar(&self, _: &ReglasValidacion) -> Result<(), Vec<ErrorCampo>> {
+        let mut errores = Errores::default();
+        errores.cantidad(self.cantidad_entradas);
+        if !self.asientos.is_empty() {
+            let mut asientos = self.asientos.clone();
+            asientos.sort_unstable();
+            asientos.dedup();
+            if self.asientos.len() != self.cantidad_entradas as usize {
+                errores.agregar("asientos", "Debe indicar un asiento por entrada");
+            } else if asientos.len() != self.asientos.len() {
+                errores.agregar("asientos", "No puede repetir asientos");
+            } else if asientos[0] == 0 {
+                errores.agregar("asientos", "Los asientos se numeran desde 1");
+            }
+        }
+        errores.resultado()
+    }
+}
+
+/// Sólo se validan los campos presentes.
+impl Validar for ActualizarEntrada {
+    fn validar(&self, _: &ReglasValidacion) -> Result<(), Vec<ErrorCampo>> {
+        let mut errores = Errores::default();
+        if let Some(cantidad) = self.cantidad_entradas {
+            errores.cantidad(cantidad);
+        }
+        errores.resultado()
+    }
+}
+
+impl Validar for GuardarEntrada {
+    fn validar(&self, _: &ReglasValidacion) -> Result<(), Vec<ErrorCampo>> {
+        let mut errores = Errores::default();
+        errores.cantidad(self.cantidad_entradas);
+        errores.resultado()
+    }
+}
+
+impl Validar for CrearCliente {
+    fn validar(&self, reglas: &ReglasValidacion) -> Result<(), Vec<ErrorCampo>> {
+        let mut errores = Errores::default();
+        errores.cedula(&self.numero_cedula, reglas);
+        errores.texto("nombre", &self.nombre);
+        errores.email(self.email.as_deref());
+        errores.resultado()
+    }
+}
+
+impl Validar for ActualizarCliente {
+    fn validar(&self, _: &ReglasValidacion) -> Result<(), Vec<ErrorCampo>> {
+        let mut errores = Errores::default();
+        errores.texto("nombre", &self.nombre);
+        errores.email(self.email.as_deref());
+        errores.resultado()
+    }
+}
+
+/// La sala se comprueba al escribir, por su clave foránea, y el formato del horario al
+/// leer el cuerpo.
+impl Validar for CrearFuncion {
+    fn validar(&self, _: &ReglasValidacion) -> Result<(), Vec<ErrorCampo>> {
+        let mut errores = Errores::default();
+        errores.texto("titulo", &self.titulo);
+        if !self.precio.is_finite() || self.precio < 0.0 {
+            errores.agregar("precio", "Debe ser un importe mayor o igual que 0");
+        }
+        errores.resultado()
+    }
+}
+
+impl Validar for CrearSala {
+    fn validar(&self, _: &ReglasValidacion) -> Result<(), Vec<ErrorCampo>> {
+        let mut errores = Errores::default();
+        errores.texto_hasta("nombre", &self.nombre, LONGITUD_MAXIMA_SALA);
+        if self.capacidad == 0 {
+            errores.agregar("capacidad", "Debe ser mayor que 0");
+        }
+        errores.resultado()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn valida() -> CrearEntrada {
+        CrearEntrada { cliente_id: 1, funcion_id: 1, cantidad_entradas: 2, asientos: Vec::new() }
+    }
+
+    fn cliente() -> CrearCliente {
+        CrearCliente {
+            numero_cedula: "1710034065".to_string(),
+            nombre: "María Pérez".to_string(),
+            email: Some("maria@example.com".to_string()),
+        }
+    }
+
+    const ECUADOR: ReglasValidacion = ReglasValidacion { cedula_ecuatoriana: true };
+    const GENERICAS: ReglasValidacion = ReglasValidacion { cedula_ecuatoriana: false };
+
+    #[test]
+    fn acepta_una_entrada_y_un_cliente_completos() {
+        assert_eq!(valida().validar(&ECUADOR), Ok(()));
+        assert_eq!(cliente().validar(&ECUADOR), Ok(()));
+    }
+
+    #[test]
+    fn informa_de_cada_campo_invalido() {
+        let entrada = CrearEntrada { cantidad_entradas: 0, ..valida() };
+        assert_eq!(entrada.validar(&GENERICAS).unwrap_err()[0].campo, "cantidad_entradas");
+
+        let cliente = CrearCliente {
+            numero_cedula: "17-100".to_string(),
+            nombre: "   ".to_string(),
+            email: Some("maria@".to_string()),
+        };
+        let errores = cliente.validar(&GENERICAS).unwrap_err();
+        let campos: Vec<&str> = errores.iter().map(|e| e.campo.as_ref()).collect();
+        assert_eq!(campos, ["numero_cedula", "nombre", "email"]);
+    }
+
+    #[test]
+    fn los_asientos_son_uno_por_entrada_y_distintos() {
+        assert_eq!(CrearEntrada { asientos: vec![4, 5], ..valida() }.validar(&ECUADOR), Ok(()));
+        for asientos in [vec![4], vec![4, 4], vec![0, 1]] {
+            let errores = CrearEntrada { asientos, ..valida() }.validar(&ECUADOR).unwrap_err();
+            assert_eq!(errores[0].campo, "asientos");
+        }
+    }
+
+    #[test]
+    fn en_actualizaciones_solo_valida_lo_presente() {
+        assert_eq!(ActualizarEntrada { cliente_id: Some(3), ..Default::default() }.validar(&ECUADOR), Ok(()));
+        let datos = ActualizarEntrada { cantidad_entradas: Some(0), ..Default::default() };
+        assert_eq!(datos.validar(&ECUADOR).unwrap_err()[0].campo, "cantidad_entradas");
+    }
+
+    #[test]
+    fn valida_los_datos_de_una_funcion_y_su_sala() {
+        let funcion = CrearFuncion {
+            titulo: " ".to_string(),
+            sala_id: 1,
+            horario: chrono::DateTime::parse_from_rfc3339("2024-03-01T19:00:00-05:00").unwrap(),
+            precio: -1.0,
+        };
+        let errores = funcion.validar(&ECUADOR).unwrap_err();
+        let campos: Vec<&str> = errores.iter().map(|e| e.campo.as_ref()).collect();
+        assert_eq!(campos, ["titulo", "precio"]);
+
+        let sala = CrearSala { nombre: "s".repeat(LONGITUD_MAXIMA_SALA + 1), capacidad: 0 };
+        let errores = sala.validar(&ECUADOR).unwrap_err();
+        let campos: Vec<&str> = errores.iter().map(|e| e.campo.as_ref()).collect();
+        assert_eq!(campos, ["nombre", "capacidad"]);
+    }
+
+    #[test]
+    fn verifica_el_digito_de_la_cedula_ecuatoriana() {
+        assert_eq!(verificar_cedula_ecuatoriana("1710034065"), Ok(()));
+        assert_eq!(verificar_cedula_ecuatoriana("0926687856"), Ok(()));
+        assert!(verificar_cedula_ecuatoriana("1710034066").is_err());
+        assert!(verificar_cedula_ecuatoriana("2510034065").is_err());
+        assert!(verificar_cedula_ecuatoriana("1770034065").is_err());
+        assert!(verificar_cedula_ecuatoriana("171003406").is_err());
+    }
+
+    #[test]
+    fn la_verificacion_ecuatoriana_es_opcional() {
+        let extranjera = CrearCliente { numero_cedula: "123456789".to_string(), ..cliente() };
+        assert!(extranjera.validar(&ECUADOR).is_err());
+        assert_eq!(extranjera.validar(&GENERICAS), Ok(()));
+    }
+}
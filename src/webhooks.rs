@@ -0,0 +1,554 @@
+//! Avisos a sistemas externos, como el CRM, cuando cambia una entrada.
+//!
+//! Un administrador suscribe una URL a algunos [`Evento`]s en `/admin/webhooks`. Cada alta,
+//! cambio, baja o check-in de una entrada deja su evento en la bandeja de salida (ver
+//! [`crate::db::eventos`]); la tarea de [`publicar_eventos`] lo convierte en un aviso
+//! pendiente por suscripción, y la de [`entregar_webhooks`] envía los avisos por POST con el
+//! JSON del evento, firmado con el secreto de la suscripción (ver [`firmar`]). Los que fallan
+//! se reintentan con esperas crecientes hasta `Config::webhooks_intentos`; el resultado de
+//! cada aviso se consulta en `GET /admin/webhooks/{id}/entregas`.
+//!
+//! El JSON lleva la entrada como queda tras el cambio, o como estaba si se eliminó, con el
+//! formato de la auditoría. Cada aviso llega al menos una vez, pero puede llegar repetido o
+//! en otro orden: el receptor debe descartar los que ya recibió por [`CABECERA_ENTREGA`].
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, SecondsFormat};
+use futures_util::future::join_all;
+use hmac::{Hmac, Mac};
+use mysql_async::{prelude::*, Conn, Pool, TxOpts};
+use reqwest::header::CONTENT_TYPE;
+use reqwest::redirect::Policy;
+use reqwest::{Client, Url};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::auth::{ahora, generar_secreto, Administrador};
+use crate::correos;
+use crate::db;
+pub use crate::db::eventos::Evento;
+use crate::error::ApiError;
+use crate::json::Json;
+use crate::respuesta::ApiResponse;
+use crate::tareas::{self, Intervalo, Trabajo};
+
+/// Cabecera con la firma del cuerpo, como `sha256=<hex>`.
+pub const CABECERA_FIRMA: &str = "X-Webhook-Firma";
+/// Cabecera con el instante del envío, en segundos desde 1970, que entra en la firma.
+pub const CABECERA_MARCA_TIEMPO: &str = "X-Webhook-Marca-Tiempo";
+/// Cabecera con el nombre del evento.
+pub const CABECERA_EVENTO: &str = "X-Webhook-Evento";
+/// Cabecera con el ID del aviso, igual en todos sus reintentos.
+pub const CABECERA_ENTREGA: &str = "X-Webhook-Entrega";
+
+/// Cada cuánto se reparten los eventos nuevos de la bandeja de salida.
+const PERIODO_PUBLICACION: Duration = Duration::from_secs(1);
+
+/// Eventos que se reparten como máximo en cada ronda.
+const EVENTOS_POR_RONDA: u32 = 200;
+
+/// Días que se conservan los eventos ya publicados.
+const RETENCION_PUBLICADOS_DIAS: u32 = 7;
+
+/// Cada cuánto se buscan avisos pendientes.
+const PERIODO_ENTREGA: Duration = Duration::from_secs(5);
+
+/// Avisos que se envían como máximo en cada ronda.
+const AVISOS_POR_RONDA: u32 = 50;
+
+/// Espera antes del primer reintento. Se duplica en cada uno hasta [`ESPERA_TOPE`].
+const ESPERA_INICIAL: Duration = Duration::from_secs(30);
+const ESPERA_TOPE: Duration = Duration::from_secs(3600);
+
+/// Tiempo durante el que un aviso en curso no se vuelve a enviar, además de la espera de
+/// la respuesta, por si el proceso que lo envía cae a mitad.
+const RESERVA: Duration = Duration::from_secs(60);
+
+/// Avisos más recientes que muestra `GET /admin/webhooks/{id}/entregas`.
+const ENTREGAS_POR_CONSULTA: u32 = 100;
+
+/// Avisos desde el arranque por resultado de cada intento, para `/metrics`.
+pub struct MetricasWebhooks {
+    pub entregados: AtomicU64,
+    pub reintentos: AtomicU64,
+    pub fallidos: AtomicU64,
+}
+
+pub static METRICAS_WEBHOOKS: MetricasWebhooks = MetricasWebhooks {
+    entregados: AtomicU64::new(0),
+    reintentos: AtomicU64::new(0),
+    fallidos: AtomicU64::new(0),
+};
+
+/// Datos para suscribir una URL. Sin `secreto`, se genera uno.
+#[derive(Debug, Deserialize)]
+pub struct CrearWebhook {
+    pub url: String,
+    pub eventos: Vec<Evento>,
+    pub secreto: Option<String>,
+}
+
+/// Suscripción registrada, sin el secreto.
+#[derive(Debug, Clone, Serialize)]
+pub struct Webhook {
+    pub id: u32,
+    pub url: String,
+    pub eventos: Vec<Evento>,
+    pub creado_en: String,
+    pub desactivado_en: Option<String>,
+}
+
+type FilaWebhook = (u32, String, String, String, Option<String>);
+
+impl Webhook {
+    fn desde_fila((id, url, eventos, creado_en, desactivado_en): FilaWebhook) -> Webhook {
+        Webhook {
+            id,
+            url,
+            eventos: eventos.split(',').filter_map(Evento::desde_str).collect(),
+            creado_en,
+            desactivado_en,
+        }
+    }
+}
+
+/// Un aviso en `GET /admin/webhooks/{id}/entregas`.
+#[derive(Debug, Clone, Serialize)]
+pub struct EntregaWebhook {
+    pub id: u64,
+    pub evento: String,
+    /// `pendiente`, `entregada` o `fallida` (agotó los intentos).
+    pub estado: String,
+    pub intentos: u32,
+    /// Código HTTP de la última respuesta del receptor, si respondió.
+    pub ultimo_codigo: Option<u16>,
+    pub ultimo_error: Option<String>,
+    pub creado_en: String,
+    /// Cuándo se volverá a intentar, si sigue pendiente.
+    pub siguiente_intento: Option<String>,
+    pub entregado_en: Option<String>,
+    /// Cuerpo que se envía.
+    pub carga: serde_json::Value,
+}
+
+type FilaEntrega = (
+    u64,
+    String,
+    String,
+    u32,
+    Option<u16>,
+    Option<String>,
+    String,
+    Option<String>,
+    Option<String>,
+    String,
+);
+
+impl EntregaWebhook {
+    fn desde_fila(fila: FilaEntrega) -> EntregaWebhook {
+        let (id, evento, estado, intentos, ultimo_codigo, ultimo_error, creado_en, siguiente_intento, entregado_en, carga) =
+            fila;
+        EntregaWebhook {
+            id,
+            evento,
+            estado,
+            intentos,
+            ultimo_codigo,
+            ultimo_error,
+            creado_en,
+            siguiente_intento,
+            entregado_en,
+            carga: serde_json::from_str(&carga).unwrap_or_default(),
+        }
+    }
+}
+
+/// Firma de un envío: HMAC-SHA256, en hexadecimal, de la marca de tiempo, un punto y el
+/// cuerpo, con el secreto de la suscripción. El receptor la recalcula para comprobar que
+/// el aviso viene de la API, y puede rechazar las marcas antiguas para evitar reenvíos.
+pub fn firmar(secreto: &str, marca_tiempo: u64, cuerpo: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secreto.as_bytes()).expect("HMAC admite claves de cualquier longitud");
+    mac.update(format!("{}.", marca_tiempo).as_bytes());
+    mac.update(cuerpo.as_bytes());
+    mac.finalize().into_bytes().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Espera antes de reintentar un aviso que ya falló `intentos` veces.
+pub(crate) fn espera(intentos: u32) -> Duration {
+    ESPERA_INICIAL
+        .saturating_mul(2u32.saturating_pow(intentos.saturating_sub(1)))
+        .min(ESPERA_TOPE)
+}
+
+/// Las suscripciones reciben los eventos de todos los cines y sus entregas guardan la
+/// entrada entera, así que sólo las gestiona un administrador de toda la cadena.
+fn exigir_cadena(admin: &Administrador) -> Result<(), ApiError> {
+    if admin.0.cine.is_some() {
+        return Err(ApiError::Prohibido(
+            "Sólo un administrador de toda la cadena puede gestionar los webhooks".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Handler que suscribe una URL a los eventos indicados y devuelve el secreto con que se
+/// firmarán los avisos, la única vez que se muestra.
+pub async fn crear_webhook(
+    admin: Administrador,
+    pool: web::Data<Pool>,
+    datos: Json<CrearWebhook>,
+) -> Result<ApiResponse<serde_json::Value>, ApiError> {
+    exigir_cadena(&admin)?;
+    let CrearWebhook { url, mut eventos, secreto } = datos.into_inner();
+    if !Url::parse(&url).is_ok_and(|url| matches!(url.scheme(), "http" | "https")) {
+        return Err(ApiError::Validacion("La URL del webhook debe ser http o https".to_string()));
+    }
+    eventos.sort_by_key(|evento| evento.como_str());
+    eventos.dedup();
+    if eventos.is_empty() {
+        return Err(ApiError::Validacion("Indique al menos un evento".to_string()));
+    }
+    let secreto = match secreto {
+        Some(secreto) if secreto.trim().is_empty() => {
+            return Err(ApiError::Validacion("El secreto no puede estar vacío".to_string()));
+        }
+        Some(secreto) => secreto,
+        None => generar_secreto().0,
+    };
+    let nombres: Vec<&str> = eventos.iter().map(|evento| evento.como_str()).collect();
+
+    let mut conn = db::conectar(&pool).await.map_err(ApiError::conexion)?;
+    conn.exec_drop(
+        "INSERT INTO webhooks (url, secreto, eventos) VALUES (:url, :secreto, :eventos)",
+        params! { "url" => &url, "secreto" => &secreto, "eventos" => nombres.join(",") },
+    )
+    .await
+    .map_err(ApiError::escritura("Error al crear el webhook"))?;
+
+    Ok(ApiResponse::creada(serde_json::json!({
+        "id": conn.last_insert_id().unwrap_or_default(),
+        "url": url,
+        "eventos": eventos,
+        "secreto": secreto,
+    })))
+}
+
+/// Handler que lista las suscripciones, activas y desactivadas.
+pub async fn listar_webhooks(admin: Administrador, pool: web::Data<Pool>) -> Result<ApiResponse<Vec<Webhook>>, ApiError> {
+    exigir_cadena(&admin)?;
+    let mut conn = db::conectar(&pool).await.map_err(ApiError::conexion)?;
+    let webhooks = conn
+        .query_map(
+            "SELECT id, url, eventos, DATE_FORMAT(creado_en, '%Y-%m-%d %H:%i:%s'), \
+             DATE_FORMAT(desactivado_en, '%Y-%m-%d %H:%i:%s') FROM webhooks ORDER BY id",
+            Webhook::desde_fila,
+        )
+        .await
+        .map_err(ApiError::base_datos("Error al obtener los webhooks"))?;
+    Ok(ApiResponse::ok(webhooks))
+}
+
+/// Handler que desactiva una suscripción. Sus avisos pendientes quedan fallidos y el
+/// registro de entregas se conserva.
+pub async fn desactivar_webhook(
+    admin: Administrador,
+    pool: web::Data<Pool>,
+    id: web::Path<u32>,
+) -> Result<HttpResponse, ApiError> {
+    exigir_cadena(&admin)?;
+    let id = id.into_inner();
+    let mut conn = db::conectar(&pool).await.map_err(ApiError::conexion)?;
+    conn.exec_drop(
+        "UPDATE webhooks SET desactivado_en = NOW() WHERE id = :id AND desactivado_en IS NULL",
+        params! { "id" => id },
+    )
+    .await
+    .map_err(ApiError::escritura("Error al desactivar el webhook"))?;
+    if conn.affected_rows() == 0 {
+        return Err(ApiError::NoEncontrado("Webhook no encontrado o ya desactivado".to_string()));
+    }
+    conn.exec_drop(
+        "UPDATE webhook_entregas SET estado = 'fallida', ultimo_error = 'Se desactivó el webhook' \
+         WHERE webhook_id = :id AND estado = 'pendiente'",
+        params! { "id" => id },
+    )
+    .await
+    .map_err(ApiError::escritura("Error al desactivar el webhook"))?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Handler con los últimos avisos de una suscripción, del más reciente al más antiguo.
+pub async fn listar_entregas(
+    admin: Administrador,
+    pool: web::Data<Pool>,
+    id: web::Path<u32>,
+) -> Result<ApiResponse<Vec<EntregaWebhook>>, ApiError> {
+    exigir_cadena(&admin)?;
+    let id = id.into_inner();
+    let mut conn = db::conectar(&pool).await.map_err(ApiError::conexion)?;
+    let existe: Option<u32> = conn
+        .exec_first("SELECT id FROM webhooks WHERE id = :id", params! { "id" => id })
+        .await
+        .map_err(ApiError::base_datos("Error al obtener el webhook"))?;
+    if existe.is_none() {
+        return Err(ApiError::NoEncontrado("Webhook no encontrado".to_string()));
+    }
+    let entregas = conn
+        .exec_map(
+            "SELECT id, evento, estado, intentos, ultimo_codigo, ultimo_error, \
+             DATE_FORMAT(creado_en, '%Y-%m-%d %H:%i:%s'), \
+             IF(estado = 'pendiente', DATE_FORMAT(siguiente_intento, '%Y-%m-%d %H:%i:%s'), NULL), \
+             DATE_FORMAT(entregado_en, '%Y-%m-%d %H:%i:%s'), carga \
+             FROM webhook_entregas WHERE webhook_id = :id ORDER BY id DESC LIMIT :limite",
+            params! { "id" => id, "limite" => ENTREGAS_POR_CONSULTA },
+            EntregaWebhook::desde_fila,
+        )
+        .await
+        .map_err(ApiError::base_datos("Error al obtener las entregas del webhook"))?;
+    Ok(ApiResponse::ok(entregas))
+}
+
+/// Tarea que reparte entre las suscripciones los eventos de la bandeja de salida cada
+/// [`PERIODO_PUBLICACION`], y con `confirmaciones` encola además el correo de cada
+/// entrada creada (ver [`crate::correos`]). Si una ronda falla, sus eventos se reparten en
+/// la siguiente.
+pub fn publicar_eventos(pool: Pool, confirmaciones: bool) -> (Intervalo, Trabajo) {
+    let trabajo = tareas::trabajo(move || {
+        let pool = pool.clone();
+        async move {
+            publicar_pendientes(&pool, confirmaciones)
+                .await
+                .map_err(|e| format!("Error al publicar los eventos: {:?}", e))
+        }
+    });
+    (Intervalo::cada(PERIODO_PUBLICACION), trabajo)
+}
+
+/// Una ronda de [`publicar_eventos`]: deja un aviso pendiente por cada evento sin publicar
+/// y cada suscripción activa a él, y, con `confirmaciones`, un correo por cada entrada
+/// creada, y marca el evento como publicado, todo en la misma transacción. Borra también
+/// los publicados hace más de [`RETENCION_PUBLICADOS_DIAS`].
+pub async fn publicar_pendientes(pool: &Pool, confirmaciones: bool) -> Result<(), mysql_async::Error> {
+    let mut conn = db::conectar(pool).await?;
+    let mut tx = conn.start_transaction(TxOpts::default()).await?;
+    let eventos: Vec<(u64, String, u32, Option<String>, i64)> = tx
+        .exec(
+            "SELECT id, evento, entrada_id, entrada, UNIX_TIMESTAMP(creado_en) FROM eventos \
+             WHERE publicado_en IS NULL ORDER BY id LIMIT :limite FOR UPDATE SKIP LOCKED",
+            params! { "limite" => EVENTOS_POR_RONDA },
+        )
+        .await?;
+    if !eventos.is_empty() {
+        let suscripciones: Vec<(u32, String)> =
+            tx.query("SELECT id, eventos FROM webhooks WHERE desactivado_en IS NULL").await?;
+        let mut avisos = Vec::new();
+        for (_, evento, entrada_id, entrada, creado_en) in &eventos {
+            let carga = serde_json::json!({
+                "evento": evento,
+                "entrada_id": entrada_id,
+                "ocurrido_en": DateTime::from_timestamp(*creado_en, 0)
+                    .unwrap_or_default()
+                    .to_rfc3339_opts(SecondsFormat::Secs, true),
+                "entrada": entrada.as_deref().and_then(|entrada| serde_json::from_str::<serde_json::Value>(entrada).ok()),
+            })
+            .to_string();
+            for (webhook_id, suscritos) in &suscripciones {
+                if suscritos.split(',').any(|suscrito| suscrito == evento) {
+                    avisos.push(params! { "webhook_id" => webhook_id, "evento" => evento, "carga" => carga.clone() });
+                }
+            }
+        }
+        if !avisos.is_empty() {
+            tx.exec_batch(
+                "INSERT INTO webhook_entregas (webhook_id, evento, carga) VALUES (:webhook_id, :evento, :carga)",
+                avisos,
+            )
+            .await?;
+        }
+        if confirmaciones {
+            let creadas: Vec<u32> = eventos
+                .iter()
+                .filter(|(_, evento, ..)| evento == Evento::Creada.como_str())
+                .map(|(_, _, entrada_id, ..)| *entrada_id)
+                .collect();
+            correos::encolar_confirmaciones(&mut tx, &creadas).await?;
+        }
+        let ids: Vec<String> = eventos.iter().map(|(id, ..)| id.to_string()).collect();
+        tx.query_drop(format!("UPDATE eventos SET publicado_en = NOW() WHERE id IN ({})", ids.join(",")))
+            .await?;
+    }
+    tx.exec_drop(
+        "DELETE FROM eventos WHERE publicado_en < NOW() - INTERVAL :dias DAY",
+        params! { "dias" => RETENCION_PUBLICADOS_DIAS },
+    )
+    .await?;
+    tx.commit().await
+}
+
+/// Aviso pendiente junto a la suscripción a la que se envía.
+struct Aviso {
+    id: u64,
+    evento: String,
+    carga: String,
+    intentos: u32,
+    url: String,
+    secreto: String,
+}
+
+/// Resultado de un intento: el código HTTP, si hubo respuesta, y el motivo, si falló.
+struct Intento {
+    codigo: Option<u16>,
+    error: Option<String>,
+}
+
+/// Tarea que envía los avisos pendientes cada [`PERIODO_ENTREGA`]. Cada aviso se intenta
+/// hasta `intentos` veces y cada intento espera la respuesta hasta `tiempo_maximo`, o sin
+/// límite con cero.
+pub fn entregar_webhooks(pool: Pool, intentos: u32, tiempo_maximo: Duration) -> (Intervalo, Trabajo) {
+    let mut cliente = Client::builder().redirect(Policy::none());
+    if !tiempo_maximo.is_zero() {
+        cliente = cliente.timeout(tiempo_maximo);
+    }
+    let cliente = cliente.build().expect("Cliente HTTP de los webhooks");
+    let reserva = RESERVA + tiempo_maximo;
+    let trabajo = tareas::trabajo(move || entregar_pendientes(pool.clone(), cliente.clone(), intentos, reserva));
+    (Intervalo::cada(PERIODO_ENTREGA), trabajo)
+}
+
+/// Una ronda de [`entregar_webhooks`]: envía a la vez los avisos pendientes y anota el
+/// resultado de cada uno. Los que no alcanzan a enviarse quedan para la siguiente.
+pub async fn entregar_pendientes(pool: Pool, cliente: Client, intentos: u32, reserva: Duration) -> Result<(), String> {
+    let avisos = reservar_avisos(&pool, reserva)
+        .await
+        .map_err(|e| format!("Error al leer los avisos pendientes: {:?}", e))?;
+    if avisos.is_empty() {
+        return Ok(());
+    }
+    let resultados = join_all(avisos.iter().map(|aviso| enviar(&cliente, aviso))).await;
+    let mut conn = db::conectar(&pool)
+        .await
+        .map_err(|e| format!("Error al registrar las entregas: {:?}", e))?;
+    for (aviso, intento) in avisos.iter().zip(resultados) {
+        registrar(&mut conn, aviso, intento, intentos)
+            .await
+            .map_err(|e| format!("Error al registrar la entrega {}: {:?}", aviso.id, e))?;
+    }
+    Ok(())
+}
+
+/// Toma los avisos pendientes cuyo intento ya tocaba y aplaza el siguiente `reserva`, para
+/// que otra réplica no los envíe a la vez.
+async fn reservar_avisos(pool: &Pool, reserva: Duration) -> Result<Vec<Aviso>, mysql_async::Error> {
+    let mut conn = db::conectar(pool).await?;
+    let mut tx = conn.start_transaction(TxOpts::default()).await?;
+    let avisos = tx
+        .exec_map(
+            "SELECT e.id, e.evento, e.carga, e.intentos, w.url, w.secreto FROM webhook_entregas e \
+             JOIN webhooks w ON w.id = e.webhook_id \
+             WHERE e.estado = 'pendiente' AND e.siguiente_intento <= NOW() AND w.desactivado_en IS NULL \
+             ORDER BY e.id LIMIT :limite FOR UPDATE OF e SKIP LOCKED",
+            params! { "limite" => AVISOS_POR_RONDA },
+            |(id, evento, carga, intentos, url, secreto)| Aviso { id, evento, carga, intentos, url, secreto },
+        )
+        .await?;
+    if !avisos.is_empty() {
+        let ids: Vec<String> = avisos.iter().map(|aviso| aviso.id.to_string()).collect();
+        tx.exec_drop(
+            format!(
+                "UPDATE webhook_entregas SET siguiente_intento = NOW() + INTERVAL :reserva SECOND WHERE id IN ({})",
+                ids.join(",")
+            ),
+            params! { "reserva" => reserva.as_secs() },
+        )
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(avisos)
+}
+
+/// Envía un aviso. Sólo una respuesta 2xx cuenta como entregado; las redirecciones no se
+/// siguen.
+async fn enviar(cliente: &Client, aviso: &Aviso) -> Intento {
+    let marca_tiempo = ahora();
+    let respuesta = cliente
+        .post(&aviso.url)
+        .header(CONTENT_TYPE, "application/json")
+        .header(CABECERA_EVENTO, &aviso.evento)
+        .header(CABECERA_ENTREGA, aviso.id)
+        .header(CABECERA_MARCA_TIEMPO, marca_tiempo)
+        .header(CABECERA_FIRMA, format!("sha256={}", firmar(&aviso.secreto, marca_tiempo, &aviso.carga)))
+        .body(aviso.carga.clone())
+        .send()
+        .await;
+    match respuesta {
+        Ok(respuesta) if respuesta.status().is_success() => Intento {
+            codigo: Some(respuesta.status().as_u16()),
+            error: None,
+        },
+        Ok(respuesta) => Intento {
+            codigo: Some(respuesta.status().as_u16()),
+            error: Some(format!("El receptor respondió {}", respuesta.status())),
+        },
+        Err(e) => Intento { codigo: None, error: Some(e.to_string()) },
+    }
+}
+
+/// Anota el resultado de un intento: el aviso queda entregado, fallido si agotó los
+/// `maximo` intentos, o pendiente de la espera que le toque.
+async fn registrar(conn: &mut Conn, aviso: &Aviso, intento: Intento, maximo: u32) -> Result<(), mysql_async::Error> {
+    let intentos = aviso.intentos + 1;
+    let (estado, contador) = match &intento.error {
+        None => ("entregada", &METRICAS_WEBHOOKS.entregados),
+        Some(_) if intentos >= maximo => ("fallida", &METRICAS_WEBHOOKS.fallidos),
+        Some(_) => ("pendiente", &METRICAS_WEBHOOKS.reintentos),
+    };
+    contador.fetch_add(1, Ordering::Relaxed);
+    if let Some(error) = intento.error.as_ref().filter(|_| estado == "fallida") {
+        log::warn!(
+            "El aviso {} a {} se da por fallido tras {} intentos: {}",
+            aviso.id, aviso.url, intentos, error
+        );
+    }
+    conn.exec_drop(
+        "UPDATE webhook_entregas SET estado = :estado, intentos = :intentos, ultimo_codigo = :codigo, \
+         ultimo_error = LEFT(:error, 512), siguiente_intento = NOW() + INTERVAL :espera SECOND, \
+         entregado_en = IF(:entregada, NOW(), NULL) WHERE id = :id",
+        params! {
+            "estado" => estado,
+            "intentos" => intentos,
+            "codigo" => intento.codigo,
+            "error" => &intento.error,
+            "espera" => espera(intentos).as_secs(),
+            "entregada" => intento.error.is_none(),
+            "id" => aviso.id,
+        },
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn firma_la_marca_de_tiempo_y_el_cuerpo() {
+        assert_eq!(
+            firmar("secreto", 1_700_000_000, r#"{"evento":"entrada.creada"}"#),
+            "0c565dfccfcc7f36a332afccb0b07fe2c1743023f29015fd8097b7adc70305c2"
+        );
+        assert_ne!(
+            firmar("secreto", 1_700_000_001, r#"{"evento":"entrada.creada"}"#),
+            firmar("secreto", 1_700_000_000, r#"{"evento":"entrada.creada"}"#)
+        );
+    }
+
+    #[test]
+    fn los_reintentos_esperan_cada_vez_mas_hasta_el_tope() {
+        assert_eq!(espera(1), Duration::from_secs(30));
+        assert_eq!(espera(3), Duration::from_secs(120));
+        assert_eq!(espera(20), ESPERA_TOPE);
+    }
+}
@@ -0,0 +1,1832 @@
+//! Pruebas de integración contra un MySQL real levantado con testcontainers.
+//!
+//! Requieren Docker, por lo que están marcadas como `#[ignore]`. Para ejecutarlas:
+//! `cargo test -- --ignored`.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use actix_web::{http::StatusCode, test, web, App, HttpRequest, HttpResponse, HttpServer};
+use chrono::{TimeDelta, Utc};
+use mysql_async::{prelude::*, Pool};
+use rust_crud::{
+    auth::emitir_token,
+    cines::CINE_PRINCIPAL,
+    config::Config,
+    create_app,
+    db::{migraciones::migrar, obtener_pool_db},
+    limite::ConfigLimite,
+    models::{AsientosFuncion, Cliente, Entrada, EstadoEntrada, Funcion, RegistroAuditoria, ReporteVentas, Rol, Sala},
+    reloj::RelojFijo,
+    respaldo,
+    respuesta::ApiResponse,
+    semilla,
+    webhooks,
+    Estado,
+};
+use testcontainers_modules::{
+    mysql::Mysql,
+    redis::{Redis, REDIS_PORT},
+    testcontainers::{runners::AsyncRunner, ContainerAsync},
+};
+
+/// Contenedor de MySQL junto a la pool conectada a él. El contenedor se detiene al soltarse.
+struct EntornoPrueba {
+    _contenedor: ContainerAsync<Mysql>,
+    config: Config,
+    pool: Pool,
+}
+
+impl EntornoPrueba {
+    /// Cabecera con un token de administrador válido para las rutas protegidas.
+    fn autorizacion(&self) -> (&'static str, String) {
+        self.autorizacion_con_rol(Rol::Admin)
+    }
+
+    fn autorizacion_con_rol(&self, rol: Rol) -> (&'static str, String) {
+        let token = emitir_token(&self.config.auth, "pruebas", rol, None).expect("Token de pruebas");
+        ("Authorization", format!("Bearer {}", token))
+    }
+
+    /// Cabecera con un token de administrador atado al cine `cine`.
+    fn autorizacion_de_cine(&self, cine: u32) -> (&'static str, String) {
+        let token = emitir_token(&self.config.auth, "pruebas", Rol::Admin, Some(cine)).expect("Token de pruebas");
+        ("Authorization", format!("Bearer {}", token))
+    }
+}
+
+/// Crea una función en la sala 1 directamente en la base de datos y devuelve su ID.
+async fn crear_funcion(pool: &Pool, titulo: &str, horario: &str) -> u32 {
+    let mut conn = pool.get_conn().await.expect("Conexión de pruebas");
+    conn.exec_drop(
+        "INSERT INTO funciones (titulo, sala_id, horario, precio) VALUES (?, 1, ?, 6.5)",
+        (titulo, horario),
+    )
+    .await
+    .expect("Función de pruebas");
+    conn.last_insert_id().unwrap() as u32
+}
+
+/// Clientes que crea [`levantar_entorno`], con IDs consecutivos desde 1.
+const CLIENTES_DE_PRUEBA: [(&str, &str); 3] =
+    [("1710034065", "María Pérez"), ("0926687856", "Juan Andrade"), ("0102030400", "Lucía Vera")];
+
+/// Levanta un MySQL efímero, le aplica las migraciones de la aplicación y crea la sala y
+/// la función con ID 1 y los [`CLIENTES_DE_PRUEBA`] que usa [`entrada_de_prueba`].
+async fn levantar_entorno() -> EntornoPrueba {
+    let contenedor = Mysql::default()
+        .start()
+        .await
+        .expect("No se pudo iniciar el contenedor de MySQL");
+    let host = contenedor.get_host().await.expect("Host del contenedor");
+    let puerto = contenedor.get_host_port_ipv4(3306).await.expect("Puerto del contenedor");
+    let mut config = Config::new(format!("mysql://root@{}:{}/test", host, puerto));
+    config.auth.secreto = "secreto-de-pruebas".to_string();
+    let pool = obtener_pool_db(&config).expect("URL de conexión válida");
+    migrar(&pool).await.expect("Migraciones aplicadas");
+    let mut conn = pool.get_conn().await.expect("Conexión de pruebas");
+    conn.query_drop("INSERT INTO salas (nombre, capacidad) VALUES ('Sala 1', 100)").await.expect("Sala de pruebas");
+    assert_eq!(crear_funcion(&pool, "Dune", "2024-03-01 19:00:00").await, 1);
+    conn.exec_batch("INSERT INTO clientes (numero_cedula, nombre) VALUES (?, ?)", CLIENTES_DE_PRUEBA)
+        .await
+        .expect("Clientes de pruebas");
+    drop(conn);
+    EntornoPrueba {
+        _contenedor: contenedor,
+        config,
+        pool,
+    }
+}
+
+/// Inicializa la aplicación con la misma fábrica que usa el binario.
+macro_rules! iniciar_app {
+    ($entorno:expr) => {
+        test::init_service(create_app(Estado::new($entorno.config.clone(), $entorno.pool.clone()))).await
+    };
+}
+
+/// Entrada para la función 1 del cliente de [`CLIENTES_DE_PRUEBA`] con esa cédula.
+fn entrada_de_prueba(numero_cedula: &str) -> serde_json::Value {
+    let cliente_id = CLIENTES_DE_PRUEBA.iter().position(|(cedula, _)| *cedula == numero_cedula).expect("Cliente de pruebas") + 1;
+    serde_json::json!({
+        "cliente_id": cliente_id,
+        "funcion_id": 1,
+        "cantidad_entradas": 2,
+    })
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker"]
+async fn ciclo_completo_de_una_entrada() {
+    let entorno = levantar_entorno().await;
+    let app = iniciar_app!(entorno);
+
+    let req = test::TestRequest::post().insert_header(entorno.autorizacion()).uri("/v1/entradas").set_json(entrada_de_prueba("1710034065")).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CREATED);
+
+    let req = test::TestRequest::get().insert_header(entorno.autorizacion()).uri("/v1/entradas").to_request();
+    let ApiResponse { data: entradas, .. }: ApiResponse<Vec<Entrada>> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(entradas.len(), 1);
+    let id = entradas[0].id.expect("La entrada listada debe tener id");
+
+    let req = test::TestRequest::get().insert_header(entorno.autorizacion()).uri(&format!("/v1/entradas/{}", id)).to_request();
+    let ApiResponse { data: entrada, .. }: ApiResponse<Entrada> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(entrada.cliente.numero_cedula, "1710034065");
+    assert_eq!(entrada.cantidad_entradas, 2);
+    assert_eq!((entrada.funcion.titulo.as_str(), entrada.funcion.horario.to_rfc3339().as_str()), ("Dune", "2024-03-01T19:00:00+00:00"));
+
+    let otra_funcion = crear_funcion(&entorno.pool, "Dune", "2024-03-01 21:30:00").await;
+    let req = test::TestRequest::patch().insert_header(entorno.autorizacion())
+        .uri(&format!("/v1/entradas/{}", id))
+        .set_json(serde_json::json!({ "cantidad_entradas": 5, "funcion_id": otra_funcion }))
+        .insert_header(("If-Match", "*"))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let req = test::TestRequest::get().insert_header(entorno.autorizacion()).uri(&format!("/v1/entradas/{}", id)).to_request();
+    let ApiResponse { data: entrada, .. }: ApiResponse<Entrada> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(entrada.cantidad_entradas, 5);
+    assert_eq!(entrada.total, 32.5);
+    assert_eq!(entrada.funcion.horario.to_rfc3339(), "2024-03-01T21:30:00+00:00");
+    assert_eq!(entrada.cliente.nombre, "María Pérez");
+
+    // PUT reemplaza la entrada entera, así que exige todos sus campos.
+    let req = test::TestRequest::put().insert_header(entorno.autorizacion())
+        .uri(&format!("/v1/entradas/{}", id))
+        .set_json(serde_json::json!({ "cantidad_entradas": 3 }))
+        .insert_header(("If-Match", "*"))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let req = test::TestRequest::put().insert_header(entorno.autorizacion())
+        .uri(&format!("/v1/entradas/{}", id))
+        .set_json(serde_json::json!({ "cliente_id": entrada.cliente.id, "funcion_id": otra_funcion, "cantidad_entradas": 3 }))
+        .insert_header(("If-Match", "*"))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    let req = test::TestRequest::get().insert_header(entorno.autorizacion()).uri(&format!("/v1/entradas/{}", id)).to_request();
+    let ApiResponse { data: entrada, .. }: ApiResponse<Entrada> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(entrada.cantidad_entradas, 3);
+
+    let req = test::TestRequest::delete().insert_header(entorno.autorizacion()).uri(&format!("/v1/entradas/{}", id)).insert_header(("If-Match", "*")).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let req = test::TestRequest::get().insert_header(entorno.autorizacion()).uri(&format!("/v1/entradas/{}", id)).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker"]
+async fn ediciones_concurrentes_con_etag() {
+    let entorno = levantar_entorno().await;
+    let app = iniciar_app!(entorno);
+
+    let req = test::TestRequest::post().insert_header(entorno.autorizacion()).uri("/v1/entradas").set_json(entrada_de_prueba("1710034065")).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CREATED);
+    let req = test::TestRequest::get().insert_header(entorno.autorizacion()).uri("/v1/entradas/1").to_request();
+    let respuesta = test::call_service(&app, req).await;
+    let etag = respuesta.headers().get("ETag").unwrap().to_str().unwrap().to_string();
+    assert_eq!(etag, "\"1\"");
+
+    // Dos taquilleros leyeron la versión 1: el primero la cambia y el segundo recibe 412.
+    for estado in [StatusCode::OK, StatusCode::PRECONDITION_FAILED] {
+        let req = test::TestRequest::patch()
+            .uri("/v1/entradas/1")
+            .insert_header(entorno.autorizacion())
+            .insert_header(("If-Match", etag.as_str()))
+            .set_json(serde_json::json!({ "cantidad_entradas": 4 }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), estado);
+    }
+    let req = test::TestRequest::get().insert_header(entorno.autorizacion()).uri("/v1/entradas/1").to_request();
+    let respuesta = test::call_service(&app, req).await;
+    assert_eq!(respuesta.headers().get("ETag").unwrap(), "\"2\"");
+    let modificada = respuesta.headers().get("Last-Modified").unwrap().to_str().unwrap().to_string();
+
+    // Un quiosco que ya tiene la versión vigente recibe 304 sin cuerpo; con la anterior, la entrada.
+    for (cabecera, valor, estado) in [
+        ("If-None-Match", "\"2\"", StatusCode::NOT_MODIFIED),
+        ("If-None-Match", etag.as_str(), StatusCode::OK),
+        ("If-Modified-Since", modificada.as_str(), StatusCode::NOT_MODIFIED),
+        ("If-Modified-Since", "Mon, 01 Jan 2024 00:00:00 GMT", StatusCode::OK),
+    ] {
+        let req = test::TestRequest::get().insert_header(entorno.autorizacion()).uri("/v1/entradas/1").insert_header((cabecera, valor)).to_request();
+        let respuesta = test::call_service(&app, req).await;
+        assert_eq!(respuesta.status(), estado, "{}: {}", cabecera, valor);
+        if estado == StatusCode::NOT_MODIFIED {
+            assert!(test::read_body(respuesta).await.is_empty());
+        }
+    }
+
+    // Sin If-Match no se modifica ni se elimina.
+    let req = test::TestRequest::delete().uri("/v1/entradas/1").insert_header(entorno.autorizacion()).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::PRECONDITION_REQUIRED);
+    let req = test::TestRequest::delete()
+        .uri("/v1/entradas/1")
+        .insert_header(entorno.autorizacion())
+        .insert_header(("If-Match", etag.as_str()))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::PRECONDITION_FAILED);
+    let req = test::TestRequest::delete()
+        .uri("/v1/entradas/1")
+        .insert_header(entorno.autorizacion())
+        .insert_header(("If-Match", "\"2\""))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker"]
+async fn last_modified_en_utc_aunque_el_servidor_use_otra_zona() {
+    let entorno = levantar_entorno().await;
+    let mut conn = entorno.pool.get_conn().await.expect("Conexión de pruebas");
+    // Las conexiones nuevas de MySQL arrancan en -05:00; las de la pool deben seguir en UTC.
+    conn.query_drop("SET GLOBAL time_zone = '-05:00'").await.expect("Zona del servidor");
+    drop(conn);
+    let app = iniciar_app!(entorno);
+
+    let req = test::TestRequest::post().insert_header(entorno.autorizacion()).uri("/v1/entradas").set_json(entrada_de_prueba("1710034065")).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CREATED);
+    let mut conn = entorno.pool.get_conn().await.expect("Conexión de pruebas");
+    conn.query_drop("UPDATE entradas SET updated_at = CONVERT_TZ('2026-03-01 14:30:00', '+00:00', @@session.time_zone) WHERE id = 1")
+        .await
+        .expect("Fecha de pruebas");
+    drop(conn);
+
+    let req = test::TestRequest::get().insert_header(entorno.autorizacion()).uri("/v1/entradas/1").to_request();
+    let respuesta = test::call_service(&app, req).await;
+    assert_eq!(respuesta.headers().get("Last-Modified").unwrap(), "Sun, 01 Mar 2026 14:30:00 GMT");
+    let req = test::TestRequest::get()
+        .insert_header(entorno.autorizacion())
+        .uri("/v1/entradas/1")
+        .insert_header(("If-Modified-Since", "Sun, 01 Mar 2026 14:30:00 GMT"))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_MODIFIED);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker"]
+async fn historial_de_cambios_de_una_entrada() {
+    let entorno = levantar_entorno().await;
+    let app = iniciar_app!(entorno);
+
+    let req = test::TestRequest::post().insert_header(entorno.autorizacion()).uri("/v1/entradas").set_json(entrada_de_prueba("1710034065")).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CREATED);
+    let req = test::TestRequest::patch()
+        .uri("/v1/entradas/1")
+        .insert_header(entorno.autorizacion())
+        .insert_header(("If-Match", "*"))
+        .set_json(serde_json::json!({ "cantidad_entradas": 5 }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    let req = test::TestRequest::delete().uri("/v1/entradas/1").insert_header(entorno.autorizacion()).insert_header(("If-Match", "*")).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    // El historial sigue disponible después de eliminar la entrada.
+    let req = test::TestRequest::get().insert_header(entorno.autorizacion()).uri("/v1/entradas/1/historial").to_request();
+    let ApiResponse { data: historial, .. }: ApiResponse<Vec<RegistroAuditoria>> = test::call_and_read_body_json(&app, req).await;
+    let operaciones: Vec<_> = historial.iter().map(|r| r.operacion.as_str()).collect();
+    assert_eq!(operaciones, ["crear", "actualizar", "eliminar"]);
+    assert!(historial.iter().all(|r| r.actor == "pruebas"));
+    assert_eq!(historial[1].valor_anterior.as_ref().unwrap()["cantidad_entradas"], 2);
+    assert_eq!(historial[1].valor_nuevo.as_ref().unwrap()["cantidad_entradas"], 5);
+    assert!(historial[2].valor_nuevo.is_none());
+
+    let req = test::TestRequest::get().insert_header(entorno.autorizacion()).uri("/v1/entradas/99/historial").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker"]
+async fn ciclo_de_pago_de_una_entrada() {
+    let entorno = levantar_entorno().await;
+    let app = iniciar_app!(entorno);
+
+    let mut codigos = Vec::new();
+    for cedula in ["1710034065", "0926687856"] {
+        let req = test::TestRequest::post().insert_header(entorno.autorizacion()).uri("/v1/entradas").set_json(entrada_de_prueba(cedula)).to_request();
+        let respuesta = test::call_service(&app, req).await;
+        assert_eq!(respuesta.status(), StatusCode::CREATED);
+        let ApiResponse { data: creada, .. }: ApiResponse<serde_json::Value> = test::read_body_json(respuesta).await;
+        codigos.push(creada["qr"].as_str().unwrap().to_string());
+    }
+    let req = test::TestRequest::get().insert_header(entorno.autorizacion()).uri("/v1/entradas/1").to_request();
+    let ApiResponse { data: entrada, .. }: ApiResponse<Entrada> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(entrada.estado, EstadoEntrada::Reservada);
+    let checkin = |id: u32, qr: &str| {
+        test::TestRequest::post()
+            .insert_header(entorno.autorizacion_con_rol(Rol::Taquillero))
+            .uri(&format!("/v1/entradas/{}/checkin", id))
+            .set_json(serde_json::json!({ "qr": qr }))
+            .to_request()
+    };
+
+    // Una entrada reservada no se puede usar sin pagarla antes.
+    assert_eq!(test::call_service(&app, checkin(1, &codigos[0])).await.status(), StatusCode::CONFLICT);
+
+    let req = test::TestRequest::post().insert_header(entorno.autorizacion_con_rol(Rol::Taquillero)).uri("/v1/entradas/1/pagar").to_request();
+    let respuesta = test::call_service(&app, req).await;
+    assert_eq!(respuesta.status(), StatusCode::OK);
+    assert_eq!(respuesta.headers().get("ETag").unwrap(), "\"2\"");
+    let ApiResponse { data: entrada, .. }: ApiResponse<Entrada> = test::read_body_json(respuesta).await;
+    assert_eq!(entrada.estado, EstadoEntrada::Pagada);
+    let req = test::TestRequest::post().insert_header(entorno.autorizacion()).uri("/v1/entradas/1/pagar").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CONFLICT);
+
+    // El código de otra entrada o uno alterado no sirven.
+    assert_eq!(test::call_service(&app, checkin(1, &codigos[1])).await.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let alterado = format!("{}x", codigos[0]);
+    assert_eq!(test::call_service(&app, checkin(1, &alterado)).await.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    let ApiResponse { data: entrada, .. }: ApiResponse<Entrada> = test::call_and_read_body_json(&app, checkin(1, &codigos[0])).await;
+    assert_eq!(entrada.estado, EstadoEntrada::Usada);
+    let respuesta = test::call_service(&app, checkin(1, &codigos[0])).await;
+    assert_eq!(respuesta.status(), StatusCode::CONFLICT);
+    let cuerpo: serde_json::Value = test::read_body_json(respuesta).await;
+    assert_eq!(cuerpo["error"]["message"], "La entrada ya se usó");
+
+    let req = test::TestRequest::post().insert_header(entorno.autorizacion()).uri("/v1/entradas/2/cancelar").to_request();
+    let ApiResponse { data: cancelada, .. }: ApiResponse<Entrada> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(cancelada.estado, EstadoEntrada::Cancelada);
+    assert_eq!(test::call_service(&app, checkin(2, &codigos[1])).await.status(), StatusCode::CONFLICT);
+
+    // Las canceladas no ocupan capacidad.
+    let req = test::TestRequest::get().insert_header(entorno.autorizacion()).uri("/v1/funciones/1/asientos").to_request();
+    let ApiResponse { data: asientos, .. }: ApiResponse<AsientosFuncion> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(asientos.disponibles, asientos.capacidad - entrada.cantidad_entradas);
+
+    let req = test::TestRequest::post().insert_header(entorno.autorizacion()).uri("/v1/entradas/99/pagar").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker"]
+async fn el_ticket_de_una_entrada_es_un_pdf() {
+    let entorno = levantar_entorno().await;
+    let app = iniciar_app!(entorno);
+
+    let req = test::TestRequest::post().insert_header(entorno.autorizacion()).uri("/v1/entradas").set_json(entrada_de_prueba("1710034065")).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CREATED);
+    let req = test::TestRequest::get().insert_header(entorno.autorizacion()).uri("/v1/entradas/1/ticket.pdf").to_request();
+    let respuesta = test::call_service(&app, req).await;
+    assert_eq!(respuesta.status(), StatusCode::OK);
+    assert_eq!(respuesta.headers().get("Content-Type").unwrap(), "application/pdf");
+    assert_eq!(respuesta.headers().get("Content-Disposition").unwrap(), "inline; filename=\"entrada-1.pdf\"");
+    assert!(test::read_body(respuesta).await.starts_with(b"%PDF-"));
+
+    let req = test::TestRequest::get().insert_header(entorno.autorizacion()).uri("/v1/entradas/99/ticket.pdf").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker"]
+async fn exporta_el_listado_filtrado_a_csv() {
+    let entorno = levantar_entorno().await;
+    let app = iniciar_app!(entorno);
+
+    for cedula in ["1710034065", "0926687856"] {
+        let req = test::TestRequest::post().insert_header(entorno.autorizacion()).uri("/v1/entradas").set_json(entrada_de_prueba(cedula)).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CREATED);
+    }
+    let req = test::TestRequest::get().insert_header(entorno.autorizacion()).uri("/v1/entradas/export?format=csv&numero_cedula=0926687856").to_request();
+    let respuesta = test::call_service(&app, req).await;
+    assert_eq!(respuesta.status(), StatusCode::OK);
+    assert_eq!(respuesta.headers().get("Content-Type").unwrap(), "text/csv; charset=utf-8");
+    assert!(respuesta.headers().get("Content-Disposition").unwrap().to_str().unwrap().starts_with("attachment; filename=\"entradas-"));
+    let cuerpo = String::from_utf8(test::read_body(respuesta).await.to_vec()).unwrap();
+    let lineas: Vec<_> = cuerpo.lines().collect();
+    assert_eq!(lineas.len(), 2);
+    assert!(lineas[0].starts_with("id,estado,numero_cedula"));
+    assert!(lineas[1].starts_with("2,reservada,0926687856,"));
+
+    let req = test::TestRequest::get().insert_header(entorno.autorizacion()).uri("/v1/entradas/export").to_request();
+    let cuerpo = test::call_and_read_body(&app, req).await;
+    assert_eq!(cuerpo.split(|b| *b == b'\n').filter(|linea| !linea.is_empty()).count(), 3);
+
+    let req = test::TestRequest::get().insert_header(entorno.autorizacion()).uri("/v1/entradas/count?numero_cedula=0926687856").to_request();
+    let ApiResponse { data: conteo, .. }: ApiResponse<serde_json::Value> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(conteo["total"], 1);
+    let req = test::TestRequest::default().method(actix_web::http::Method::HEAD).insert_header(entorno.autorizacion()).uri("/v1/entradas").to_request();
+    let respuesta = test::call_service(&app, req).await;
+    assert_eq!(respuesta.status(), StatusCode::OK);
+    assert_eq!(respuesta.headers().get("X-Total-Count").unwrap(), "2");
+    assert!(test::read_body(respuesta).await.is_empty());
+
+    let req = test::TestRequest::get().insert_header(entorno.autorizacion()).uri("/v1/entradas/buscar?q=ANDRA").to_request();
+    let ApiResponse { data: encontradas, .. }: ApiResponse<Vec<Entrada>> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(encontradas.len(), 1);
+    assert_eq!(encontradas[0].cliente.numero_cedula, "0926687856");
+    let req = test::TestRequest::get().insert_header(entorno.autorizacion()).uri("/v1/entradas/buscar?q=perez").to_request();
+    let respuesta = test::call_service(&app, req).await;
+    assert_eq!(respuesta.headers().get("X-Total-Count").unwrap(), "1");
+    let req = test::TestRequest::get().insert_header(entorno.autorizacion()).uri("/v1/entradas/buscar?q=%20").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+
+    let req = test::TestRequest::get().insert_header(entorno.autorizacion()).uri("/v1/entradas?fields=id,nombre_cliente").to_request();
+    let ApiResponse { data: parciales, .. }: ApiResponse<Vec<serde_json::Value>> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(parciales[0], serde_json::json!({ "id": 1, "nombre_cliente": "María Pérez" }));
+    let req = test::TestRequest::get().insert_header(entorno.autorizacion()).uri("/v1/entradas/2?fields=numero_cedula").to_request();
+    let ApiResponse { data: parcial, .. }: ApiResponse<serde_json::Value> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(parcial, serde_json::json!({ "numero_cedula": "0926687856" }));
+    let req = test::TestRequest::get().insert_header(entorno.autorizacion()).uri("/v1/entradas?fields=clave").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+
+    let req = test::TestRequest::get().insert_header(entorno.autorizacion()).uri("/v1/entradas?limit=1&sort=nombre_cliente").to_request();
+    let primera: ApiResponse<Vec<Entrada>> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(primera.data[0].cliente.nombre, "Juan Andrade");
+    let cursor = primera.meta.cursor.unwrap().next_cursor.unwrap();
+    let uri = format!("/v1/entradas?limit=1&sort=nombre_cliente&after={}", cursor);
+    let req = test::TestRequest::get().insert_header(entorno.autorizacion()).uri(&uri).to_request();
+    let segunda: ApiResponse<Vec<Entrada>> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(segunda.data[0].cliente.nombre, "María Pérez");
+    assert_eq!(segunda.meta.cursor.unwrap().next_cursor, None);
+
+    let req = test::TestRequest::get().insert_header(entorno.autorizacion()).uri("/v1/entradas/export?format=xlsx").to_request();
+    let respuesta = test::call_service(&app, req).await;
+    assert_eq!(respuesta.status(), StatusCode::OK);
+    assert!(respuesta.headers().get("Content-Disposition").unwrap().to_str().unwrap().ends_with(".xlsx\""));
+    assert!(test::read_body(respuesta).await.starts_with(b"PK"));
+
+    let req = test::TestRequest::get().insert_header(entorno.autorizacion()).uri("/v1/entradas/export?format=pdf").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker"]
+async fn importa_entradas_desde_csv() {
+    let entorno = levantar_entorno().await;
+    let app = iniciar_app!(entorno);
+
+    let csv = "cliente_id,funcion_id,cantidad_entradas\n1,1,2\n1,1,3\nx,1,1\n2,1,1\n";
+    let cuerpo = format!(
+        "--limite\r\nContent-Disposition: form-data; name=\"archivo\"; filename=\"entradas.csv\"\r\n\
+         Content-Type: text/csv\r\n\r\n{}\r\n--limite--\r\n",
+        csv
+    );
+    let req = test::TestRequest::post()
+        .insert_header(entorno.autorizacion())
+        .insert_header(("Content-Type", "multipart/form-data; boundary=limite"))
+        .uri("/v1/entradas/import")
+        .set_payload(cuerpo)
+        .to_request();
+    let ApiResponse { data: informe, .. }: ApiResponse<serde_json::Value> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!((informe["creadas"].as_u64(), informe["rechazadas"].as_u64()), (Some(2), Some(2)));
+    let estados: Vec<_> = informe["filas"].as_array().unwrap().iter().map(|fila| (fila["fila"].as_u64().unwrap(), fila["estado"].as_str().unwrap())).collect();
+    assert_eq!(estados, [(2, "creada"), (3, "rechazada"), (4, "rechazada"), (5, "creada")]);
+    assert_eq!(informe["filas"][2]["error"]["campos"][0]["campo"], "cliente_id");
+
+    let req = test::TestRequest::get().insert_header(entorno.autorizacion()).uri("/v1/entradas").to_request();
+    let respuesta = test::call_service(&app, req).await;
+    assert_eq!(respuesta.headers().get("X-Total-Count").unwrap(), "2");
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker"]
+async fn reporte_de_ventas_por_funcion_dia_y_franja() {
+    let entorno = levantar_entorno().await;
+    let alien = crear_funcion(&entorno.pool, "Alien", "2024-03-02 22:00:00").await;
+    let app = iniciar_app!(entorno);
+
+    let ventas = [(1, 1, 2), (2, alien, 1), (3, 1, 4)];
+    for (cliente_id, funcion_id, cantidad_entradas) in ventas {
+        let datos = serde_json::json!({ "cliente_id": cliente_id, "funcion_id": funcion_id, "cantidad_entradas": cantidad_entradas });
+        let req = test::TestRequest::post().insert_header(entorno.autorizacion()).uri("/v1/entradas").set_json(datos).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CREATED);
+    }
+    // Las canceladas no cuentan como vendidas.
+    let req = test::TestRequest::post().insert_header(entorno.autorizacion()).uri("/v1/entradas/3/cancelar").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let req = test::TestRequest::get().insert_header(entorno.autorizacion()).uri("/v1/reportes/ventas").to_request();
+    let ApiResponse { data: reporte, .. }: ApiResponse<ReporteVentas> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!((reporte.totales.entradas, reporte.totales.recaudado), (3, 19.5));
+    let funciones: Vec<_> = reporte.por_funcion.iter().map(|f| (f.titulo.as_str(), f.ventas.entradas)).collect();
+    assert_eq!(funciones, [("Dune", 2), ("Alien", 1)]);
+    let dias: Vec<_> = reporte.por_dia.iter().map(|d| d.fecha.as_str()).collect();
+    assert_eq!(dias, ["2024-03-01", "2024-03-02"]);
+    let franjas: Vec<_> = reporte.por_franja.iter().map(|f| (f.franja.as_str(), f.ventas.recaudado)).collect();
+    assert_eq!(franjas, [("19:00-20:00", 13.0), ("22:00-23:00", 6.5)]);
+
+    let req = test::TestRequest::get().insert_header(entorno.autorizacion()).uri("/v1/reportes/ventas?desde=2024-03-02&hasta=2024-03-02").to_request();
+    let ApiResponse { data: reporte, .. }: ApiResponse<ReporteVentas> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!((reporte.totales.entradas, reporte.por_funcion.len()), (1, 1));
+
+    // Alien vendió menos entradas que Dune, así que con limit=1 sólo queda Dune.
+    let req = test::TestRequest::get().insert_header(entorno.autorizacion()).uri("/v1/reportes/top-funciones?limit=1").to_request();
+    let ApiResponse { data: top, .. }: ApiResponse<Vec<serde_json::Value>> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(top.len(), 1);
+    assert_eq!((top[0]["titulo"].as_str(), top[0]["entradas"].as_u64()), (Some("Dune"), Some(2)));
+    let req = test::TestRequest::get().insert_header(entorno.autorizacion()).uri("/v1/reportes/top-funciones?desde=2024-03-02").to_request();
+    let ApiResponse { data: top, .. }: ApiResponse<Vec<serde_json::Value>> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(top.iter().map(|f| f["titulo"].as_str().unwrap()).collect::<Vec<_>>(), ["Alien"]);
+    let req = test::TestRequest::get().insert_header(entorno.autorizacion()).uri("/v1/reportes/top-funciones?limit=0").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+
+    let req = test::TestRequest::get().insert_header(entorno.autorizacion_con_rol(Rol::Taquillero)).uri("/v1/reportes/ventas").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker"]
+async fn las_reservas_sin_pagar_vencen() {
+    let entorno = levantar_entorno().await;
+    let reloj = Arc::new(RelojFijo::new(Utc::now()));
+    let estado = Estado::con_reloj(entorno.config.clone(), entorno.pool.clone(), reloj.clone());
+    let servicio = estado.entradas.clone();
+    let app = test::init_service(create_app(estado)).await;
+
+    for cedula in ["1710034065", "0926687856"] {
+        let req = test::TestRequest::post().insert_header(entorno.autorizacion()).uri("/v1/entradas").set_json(entrada_de_prueba(cedula)).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CREATED);
+    }
+    let req = test::TestRequest::post().insert_header(entorno.autorizacion()).uri("/v1/entradas/2/pagar").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    assert_eq!(servicio.cancelar_vencidas(Duration::from_secs(900), "vencimiento").await, Ok(0));
+
+    reloj.avanzar(TimeDelta::hours(1));
+    assert_eq!(servicio.cancelar_vencidas(Duration::from_secs(900), "vencimiento").await, Ok(1));
+    assert_eq!(servicio.obtener(CINE_PRINCIPAL, 1).await.unwrap().estado, EstadoEntrada::Cancelada);
+    assert_eq!(servicio.obtener(CINE_PRINCIPAL, 2).await.unwrap().estado, EstadoEntrada::Pagada);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker"]
+async fn errores_de_validacion_y_restricciones() {
+    let entorno = levantar_entorno().await;
+    let app = iniciar_app!(entorno);
+
+    for cedula in ["1710034065", "0926687856"] {
+        let req = test::TestRequest::post().insert_header(entorno.autorizacion()).uri("/v1/entradas").set_json(entrada_de_prueba(cedula)).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CREATED);
+    }
+
+    // Un cliente puede tener varias entradas, al crear y al actualizar
+    let req = test::TestRequest::post().insert_header(entorno.autorizacion()).uri("/v1/entradas").set_json(entrada_de_prueba("1710034065")).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CREATED);
+    let req = test::TestRequest::get().insert_header(entorno.autorizacion()).uri("/v1/entradas").to_request();
+    let ApiResponse { data: entradas, .. }: ApiResponse<Vec<Entrada>> = test::call_and_read_body_json(&app, req).await;
+    let id = entradas.iter().find(|e| e.cliente.numero_cedula == "0926687856").and_then(|e| e.id).unwrap();
+    let req = test::TestRequest::patch().insert_header(entorno.autorizacion())
+        .uri(&format!("/v1/entradas/{}", id))
+        .set_json(serde_json::json!({ "cliente_id": 1 }))
+        .insert_header(("If-Match", "*"))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    // Actualización sin campos
+    let req = test::TestRequest::patch().insert_header(entorno.autorizacion()).uri(&format!("/v1/entradas/{}", id)).set_json(serde_json::json!({})).insert_header(("If-Match", "*")).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+
+    // Campos con valores inválidos
+    let req = test::TestRequest::post().insert_header(entorno.autorizacion())
+        .uri("/v1/entradas")
+        .set_json(serde_json::json!({ "cliente_id": 3, "funcion_id": 1, "cantidad_entradas": 0 }))
+        .to_request();
+    let respuesta = test::call_service(&app, req).await;
+    assert_eq!(respuesta.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let cuerpo: serde_json::Value = test::read_body_json(respuesta).await;
+    assert_eq!(cuerpo["error"]["campos"][0]["campo"], "cantidad_entradas");
+
+    // Cuerpo con tipos inválidos
+    let req = test::TestRequest::post().insert_header(entorno.autorizacion())
+        .uri("/v1/entradas")
+        .set_json(serde_json::json!({ "cliente_id": 1, "cantidad_entradas": "dos" }))
+        .to_request();
+    let respuesta = test::call_service(&app, req).await;
+    assert_eq!(respuesta.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let cuerpo: serde_json::Value = test::read_body_json(respuesta).await;
+    assert_eq!(cuerpo["error"]["campos"][0]["campo"], "cantidad_entradas");
+
+    // JSON mal formado
+    let req = test::TestRequest::post().insert_header(entorno.autorizacion())
+        .uri("/v1/entradas")
+        .insert_header(("Content-Type", "application/json"))
+        .set_payload("{\"cliente_id\": ")
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+
+    // Recursos inexistentes
+    let req = test::TestRequest::get().insert_header(entorno.autorizacion()).uri("/v1/entradas/999999").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+    let req = test::TestRequest::patch().insert_header(entorno.autorizacion())
+        .uri("/v1/entradas/999999")
+        .set_json(serde_json::json!({ "cantidad_entradas": 1 }))
+        .insert_header(("If-Match", "*"))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+    let req = test::TestRequest::delete().insert_header(entorno.autorizacion()).uri("/v1/entradas/999999").insert_header(("If-Match", "*")).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker"]
+async fn creaciones_concurrentes_con_la_misma_cedula() {
+    let entorno = levantar_entorno().await;
+    let app = iniciar_app!(entorno);
+
+    // Nueve ventas idénticas y una con otra cantidad, todas a la vez.
+    let solicitudes = (0..10).map(|i| {
+        let mut entrada = entrada_de_prueba("1710034065");
+        if i == 9 {
+            entrada["cantidad_entradas"] = 3.into();
+        }
+        let req = test::TestRequest::post().insert_header(entorno.autorizacion()).uri("/v1/entradas").set_json(entrada).to_request();
+        test::call_service(&app, req)
+    });
+    let mut respuestas = futures_util::future::join_all(solicitudes).await;
+
+    // La distinta es otra venta y crea su propia entrada.
+    let distinta = respuestas.pop().unwrap();
+    assert_eq!(distinta.status(), StatusCode::CREATED);
+    let ApiResponse { data: distinta, .. }: ApiResponse<serde_json::Value> = test::read_body_json(distinta).await;
+    let mut ids = Vec::new();
+    for respuesta in respuestas {
+        assert_eq!(respuesta.status(), StatusCode::CREATED);
+        let ApiResponse { data, .. }: ApiResponse<serde_json::Value> = test::read_body_json(respuesta).await;
+        ids.push(data["id"].as_u64().unwrap());
+    }
+    assert!(ids.iter().all(|id| *id == ids[0]), "Las ventas idénticas reciben la misma entrada: {:?}", ids);
+    assert_ne!(distinta["id"].as_u64(), Some(ids[0]));
+    let total: u64 = entorno.pool.get_conn().await.unwrap().query_first("SELECT COUNT(*) FROM entradas").await.unwrap().unwrap();
+    assert_eq!(total, 2);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker"]
+async fn agregado_por_funcion() {
+    let entorno = levantar_entorno().await;
+    let app = iniciar_app!(entorno);
+
+    let alien = crear_funcion(&entorno.pool, "Alien", "2024-03-01 21:00:00").await;
+    for (cedula, funcion, cantidad) in [("1710034065", 1, 2), ("0926687856", 1, 3), ("0102030400", alien, 1)] {
+        let mut entrada = entrada_de_prueba(cedula);
+        entrada["funcion_id"] = funcion.into();
+        entrada["cantidad_entradas"] = cantidad.into();
+        let req = test::TestRequest::post().insert_header(entorno.autorizacion()).uri("/v1/entradas").set_json(entrada).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CREATED);
+    }
+
+    let req = test::TestRequest::get().insert_header(entorno.autorizacion())
+        .uri("/v1/entradas/agregado?group_by=nombre_funcion&agg=sum:cantidad_entradas,count")
+        .to_request();
+    let ApiResponse { data: filas, .. }: ApiResponse<Vec<serde_json::Value>> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(
+        filas,
+        vec![
+            serde_json::json!({ "nombre_funcion": "Alien", "sum_cantidad_entradas": 1, "count": 1 }),
+            serde_json::json!({ "nombre_funcion": "Dune", "sum_cantidad_entradas": 5, "count": 2 }),
+        ]
+    );
+
+    let req = test::TestRequest::get().insert_header(entorno.autorizacion()).uri("/v1/entradas/agregado?group_by=id").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker"]
+async fn listado_paginado_y_filtrado() {
+    let entorno = levantar_entorno().await;
+    let app = iniciar_app!(entorno);
+
+    for cedula in ["1710034065", "0926687856", "0102030400"] {
+        let req = test::TestRequest::post().insert_header(entorno.autorizacion()).uri("/v1/entradas").set_json(entrada_de_prueba(cedula)).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CREATED);
+    }
+
+    let req = test::TestRequest::get().insert_header(entorno.autorizacion()).uri("/v1/entradas?page=2&per_page=2").to_request();
+    let respuesta = test::call_service(&app, req).await;
+    assert_eq!(respuesta.headers().get("X-Total-Count").unwrap(), "3");
+    assert_eq!(respuesta.headers().get("X-Pagina").unwrap(), "2");
+    let ApiResponse { data: entradas, meta, .. }: ApiResponse<Vec<Entrada>> = test::read_body_json(respuesta).await;
+    let paginacion = meta.paginacion.expect("El listado informa su paginación");
+    assert_eq!((paginacion.pagina, paginacion.por_pagina, paginacion.total), (2, 2, 3));
+    let enlaces = meta.links.expect("El listado enlaza sus páginas");
+    assert_eq!(enlaces.prev.as_deref(), Some("/v1/entradas?per_page=2&page=1"));
+    assert_eq!(enlaces.next, None);
+    assert_eq!(entradas.len(), 1);
+    assert_eq!(entradas[0].cliente.numero_cedula, "0102030400");
+
+    let req = test::TestRequest::get().insert_header(entorno.autorizacion()).uri("/v1/entradas?numero_cedula=0926687856&nombre_funcion=Dune").to_request();
+    let respuesta = test::call_service(&app, req).await;
+    assert_eq!(respuesta.headers().get("X-Total-Count").unwrap(), "1");
+    let ApiResponse { data: entradas, .. }: ApiResponse<Vec<Entrada>> = test::read_body_json(respuesta).await;
+    assert_eq!(entradas[0].cliente.numero_cedula, "0926687856");
+    assert_eq!(entradas[0].created_at, entradas[0].updated_at);
+
+    // Por fecha de alta: todas se crearon después de 2000 y ninguna después de 2999.
+    for (fecha, total) in [("2000-01-01", "3"), ("2999-01-01%2000:00:00", "0")] {
+        let req = test::TestRequest::get()
+            .insert_header(entorno.autorizacion())
+            .uri(&format!("/v1/entradas?created_after={}", fecha))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.headers().get("X-Total-Count").unwrap(), total);
+    }
+
+    for uri in ["/v1/entradas?page=0", "/v1/entradas?created_after=ayer"] {
+        let req = test::TestRequest::get().insert_header(entorno.autorizacion()).uri(uri).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+    }
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker"]
+async fn solo_un_registro_simultaneo_queda_como_primer_administrador() {
+    let entorno = levantar_entorno().await;
+    let app = iniciar_app!(entorno);
+
+    let solicitudes = (0..5).map(|i| {
+        let req = test::TestRequest::post()
+            .uri("/v1/auth/register")
+            .set_json(serde_json::json!({ "usuario": format!("primero-{}", i), "clave": "clave-de-pruebas" }))
+            .to_request();
+        test::call_service(&app, req)
+    });
+    let respuestas = futures_util::future::join_all(solicitudes).await;
+    assert_eq!(respuestas.iter().filter(|r| r.status() == StatusCode::CREATED).count(), 1);
+    let usuarios: u64 = entorno.pool.get_conn().await.unwrap().query_first("SELECT COUNT(*) FROM usuarios").await.unwrap().unwrap();
+    assert_eq!(usuarios, 1);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker"]
+async fn las_entradas_exigen_token() {
+    let entorno = levantar_entorno().await;
+    let app = iniciar_app!(entorno);
+
+    let req = test::TestRequest::get().uri("/v1/entradas").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::UNAUTHORIZED);
+    let req = test::TestRequest::get()
+        .uri("/v1/entradas")
+        .insert_header(("Authorization", "Bearer no-es-un-token"))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::UNAUTHORIZED);
+
+    // El primer usuario se registra sin token; los siguientes lo necesitan.
+    let req = test::TestRequest::post()
+        .uri("/v1/auth/register")
+        .set_json(serde_json::json!({ "usuario": "taquilla", "clave": "clave-de-pruebas" }))
+        .to_request();
+    let ApiResponse { data: usuario, .. }: ApiResponse<serde_json::Value> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(usuario["rol"], "admin");
+    assert!(usuario.get("clave").is_none() && usuario.get("clave_hash").is_none());
+    let req = test::TestRequest::post()
+        .uri("/v1/auth/register")
+        .set_json(serde_json::json!({ "usuario": "intruso", "clave": "clave-de-pruebas" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::UNAUTHORIZED);
+
+    let req = test::TestRequest::post()
+        .uri("/v1/auth/login")
+        .set_json(serde_json::json!({ "usuario": "taquilla", "clave": "incorrecta" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::UNAUTHORIZED);
+
+    let req = test::TestRequest::post()
+        .uri("/v1/auth/login")
+        .set_json(serde_json::json!({ "usuario": "taquilla", "clave": "clave-de-pruebas" }))
+        .to_request();
+    let ApiResponse { data: sesion, .. }: ApiResponse<serde_json::Value> = test::call_and_read_body_json(&app, req).await;
+    let token = sesion["token"].as_str().expect("El login devuelve un token");
+
+    let req = test::TestRequest::get()
+        .uri("/v1/entradas")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    // El refresco entrega un par nuevo y el token usado deja de servir.
+    let refresco = sesion["token_refresco"].clone();
+    let req = test::TestRequest::post()
+        .uri("/v1/auth/refresh")
+        .set_json(serde_json::json!({ "token_refresco": refresco }))
+        .to_request();
+    let ApiResponse { data: renovada, .. }: ApiResponse<serde_json::Value> = test::call_and_read_body_json(&app, req).await;
+    assert!(renovada["token"].is_string());
+    let req = test::TestRequest::post()
+        .uri("/v1/auth/refresh")
+        .set_json(serde_json::json!({ "token_refresco": refresco }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::UNAUTHORIZED);
+
+    // Tras cerrar la sesión el token de refresco queda revocado.
+    let refresco = renovada["token_refresco"].clone();
+    let req = test::TestRequest::post()
+        .uri("/v1/auth/logout")
+        .set_json(serde_json::json!({ "token_refresco": refresco }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NO_CONTENT);
+    let req = test::TestRequest::post()
+        .uri("/v1/auth/refresh")
+        .set_json(serde_json::json!({ "token_refresco": refresco }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker"]
+async fn los_taquilleros_no_modifican_entradas() {
+    let entorno = levantar_entorno().await;
+    let app = iniciar_app!(entorno);
+    let taquillero = entorno.autorizacion_con_rol(Rol::Taquillero);
+
+    let req = test::TestRequest::post()
+        .uri("/v1/entradas")
+        .insert_header(taquillero.clone())
+        .set_json(entrada_de_prueba("1710034065"))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CREATED);
+    let req = test::TestRequest::get().uri("/v1/entradas").insert_header(taquillero.clone()).to_request();
+    let ApiResponse { data: entradas, .. }: ApiResponse<Vec<Entrada>> = test::call_and_read_body_json(&app, req).await;
+    let id = entradas[0].id.unwrap();
+
+    let req = test::TestRequest::patch()
+        .uri(&format!("/v1/entradas/{}", id))
+        .insert_header(taquillero.clone())
+        .set_json(serde_json::json!({ "cantidad_entradas": 5 }))
+        .insert_header(("If-Match", "*"))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+    let req = test::TestRequest::delete()
+        .uri(&format!("/v1/entradas/{}", id))
+        .insert_header(taquillero.clone())
+        .insert_header(("If-Match", "*"))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/v1/entradas/{}", id))
+        .insert_header(entorno.autorizacion())
+        .insert_header(("If-Match", "*"))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker"]
+async fn claves_de_api_para_kioscos() {
+    let entorno = levantar_entorno().await;
+    let app = iniciar_app!(entorno);
+
+    let req = test::TestRequest::post()
+        .uri("/v1/admin/api-keys")
+        .insert_header(entorno.autorizacion_con_rol(Rol::Taquillero))
+        .set_json(serde_json::json!({ "nombre": "kiosco-norte" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::post()
+        .uri("/v1/admin/api-keys")
+        .insert_header(entorno.autorizacion())
+        .set_json(serde_json::json!({ "nombre": "kiosco-norte" }))
+        .to_request();
+    let ApiResponse { data: creada, .. }: ApiResponse<serde_json::Value> = test::call_and_read_body_json(&app, req).await;
+    let clave = creada["clave"].as_str().expect("La clave se devuelve al crearla").to_string();
+
+    let req = test::TestRequest::post()
+        .uri("/v1/entradas")
+        .insert_header(("X-Api-Key", clave.as_str()))
+        .set_json(entrada_de_prueba("1710034065"))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CREATED);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/v1/admin/api-keys/{}", creada["id"]))
+        .insert_header(entorno.autorizacion())
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NO_CONTENT);
+
+    let req = test::TestRequest::get().uri("/v1/entradas").insert_header(("X-Api-Key", clave.as_str())).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker"]
+async fn las_claves_inventadas_no_saltan_el_limite_por_ip() {
+    let mut entorno = levantar_entorno().await;
+    entorno.config.limite_ip = ConfigLimite { por_segundo: 0.01, rafaga: 2 };
+    let app = iniciar_app!(entorno);
+    let req = test::TestRequest::post()
+        .uri("/v1/admin/api-keys")
+        .insert_header(entorno.autorizacion())
+        .set_json(serde_json::json!({ "nombre": "kiosco-norte" }))
+        .to_request();
+    let ApiResponse { data: creada, .. }: ApiResponse<serde_json::Value> = test::call_and_read_body_json(&app, req).await;
+    let clave = creada["clave"].as_str().unwrap().to_string();
+
+    // Cada petición trae una clave distinta que no existe: todas cuentan contra la IP.
+    let ip = "203.0.113.9:40000".parse().unwrap();
+    let estados = [StatusCode::UNAUTHORIZED, StatusCode::UNAUTHORIZED, StatusCode::TOO_MANY_REQUESTS];
+    for (i, estado) in estados.into_iter().enumerate() {
+        let req = test::TestRequest::get()
+            .uri("/v1/entradas")
+            .insert_header(("X-Api-Key", format!("inventada-{}", i)))
+            .peer_addr(ip)
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), estado);
+    }
+
+    // Una clave vigente tiene su propia cubeta, aunque llegue desde la misma IP.
+    let req = test::TestRequest::get().uri("/v1/entradas").insert_header(("X-Api-Key", clave.as_str())).peer_addr(ip).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker"]
+async fn avisos_firmados_a_los_webhooks() {
+    let entorno = levantar_entorno().await;
+    let app = iniciar_app!(entorno);
+
+    // Receptor que guarda el evento, la firma, la marca de tiempo y el cuerpo de cada aviso.
+    type Recibidos = Mutex<Vec<(String, String, String, String)>>;
+    let recibidos = web::Data::new(Recibidos::default());
+    let datos = recibidos.clone();
+    let receptor = HttpServer::new(move || {
+        App::new().app_data(datos.clone()).route(
+            "/crm",
+            web::post().to(|req: HttpRequest, cuerpo: String, recibidos: web::Data<Recibidos>| async move {
+                let cabecera = |nombre| req.headers().get(nombre).and_then(|v| v.to_str().ok()).unwrap_or_default().to_string();
+                let aviso = (cabecera(webhooks::CABECERA_EVENTO), cabecera(webhooks::CABECERA_FIRMA), cabecera(webhooks::CABECERA_MARCA_TIEMPO), cuerpo);
+                recibidos.lock().unwrap().push(aviso);
+                HttpResponse::NoContent().finish()
+            }),
+        )
+    })
+    .workers(1)
+    .bind(("127.0.0.1", 0))
+    .expect("Receptor de pruebas");
+    let url = format!("http://127.0.0.1:{}/crm", receptor.addrs()[0].port());
+    actix_web::rt::spawn(receptor.run());
+
+    let req = test::TestRequest::post()
+        .uri("/v1/admin/webhooks")
+        .insert_header(entorno.autorizacion())
+        .set_json(serde_json::json!({ "url": "ftp://crm", "eventos": ["entrada.creada"] }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+    let req = test::TestRequest::post()
+        .uri("/v1/admin/webhooks")
+        .insert_header(entorno.autorizacion())
+        .set_json(serde_json::json!({ "url": url, "eventos": ["entrada.creada", "entrada.checkin"], "secreto": "secreto-crm" }))
+        .to_request();
+    let ApiResponse { data: webhook, .. }: ApiResponse<serde_json::Value> = test::call_and_read_body_json(&app, req).await;
+
+    // Se crea y se elimina una entrada; sólo la creación tiene suscripción.
+    let req = test::TestRequest::post().insert_header(entorno.autorizacion()).uri("/v1/entradas").set_json(entrada_de_prueba("1710034065")).to_request();
+    let ApiResponse { data: creada, .. }: ApiResponse<serde_json::Value> = test::call_and_read_body_json(&app, req).await;
+    let req = test::TestRequest::delete().insert_header(entorno.autorizacion()).uri(&format!("/v1/entradas/{}", creada["id"])).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    webhooks::publicar_pendientes(&entorno.pool, false).await.expect("Eventos publicados");
+    webhooks::entregar_pendientes(entorno.pool.clone(), reqwest::Client::new(), 3, Duration::from_secs(60))
+        .await
+        .expect("Ronda de entregas");
+    let recibidos = recibidos.lock().unwrap().clone();
+    assert_eq!(recibidos.len(), 1);
+    let (evento, firma, marca_tiempo, cuerpo) = &recibidos[0];
+    assert_eq!(evento, "entrada.creada");
+    assert_eq!(*firma, format!("sha256={}", webhooks::firmar("secreto-crm", marca_tiempo.parse().unwrap(), cuerpo)));
+    let cuerpo: serde_json::Value = serde_json::from_str(cuerpo).unwrap();
+    assert_eq!(cuerpo["entrada_id"], creada["id"]);
+    assert_eq!(cuerpo["entrada"]["cantidad_entradas"], 2);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/v1/admin/webhooks/{}/entregas", webhook["id"]))
+        .insert_header(entorno.autorizacion())
+        .to_request();
+    let ApiResponse { data: entregas, .. }: ApiResponse<Vec<serde_json::Value>> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(entregas.len(), 1);
+    assert_eq!((&entregas[0]["estado"], &entregas[0]["intentos"], &entregas[0]["ultimo_codigo"]), (&"entregada".into(), &1.into(), &204.into()));
+
+    // Las suscripciones ven todos los cines: un administrador de uno solo no las gestiona.
+    let de_otro_cine = entorno.autorizacion_de_cine(2);
+    let webhook_id = webhook["id"].as_u64().unwrap();
+    for req in [
+        test::TestRequest::get().uri("/v1/admin/webhooks"),
+        test::TestRequest::get().uri(&format!("/v1/admin/webhooks/{}/entregas", webhook_id)),
+        test::TestRequest::delete().uri(&format!("/v1/admin/webhooks/{}", webhook_id)),
+        test::TestRequest::post()
+            .uri("/v1/admin/webhooks")
+            .set_json(serde_json::json!({ "url": url, "eventos": ["entrada.creada"] })),
+    ] {
+        let req = req.insert_header(de_otro_cine.clone()).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+    }
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/v1/admin/webhooks/{}", webhook["id"]))
+        .insert_header(entorno.autorizacion())
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NO_CONTENT);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker"]
+async fn funciones_y_sus_entradas() {
+    let entorno = levantar_entorno().await;
+    let app = iniciar_app!(entorno);
+    let funcion = serde_json::json!({
+        "titulo": "Oppenheimer",
+        "sala_id": 1,
+        "horario": "2024-03-01T20:45:00-05:00",
+        "precio": 7.0,
+    });
+
+    let req = test::TestRequest::post()
+        .uri("/v1/funciones")
+        .insert_header(entorno.autorizacion_con_rol(Rol::Taquillero))
+        .set_json(&funcion)
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::post().uri("/v1/funciones").insert_header(entorno.autorizacion()).set_json(&funcion).to_request();
+    let ApiResponse { data: creada, .. }: ApiResponse<Funcion> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!((creada.titulo.as_str(), creada.precio), ("Oppenheimer", 7.0));
+    assert_eq!((creada.sala.nombre.as_str(), creada.sala.capacidad), ("Sala 1", 100));
+    assert_eq!(creada.horario.to_rfc3339(), "2024-03-02T01:45:00+00:00");
+    let req = test::TestRequest::post().uri("/v1/funciones").insert_header(entorno.autorizacion()).set_json(&funcion).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CONFLICT);
+
+    let mut entrada = entrada_de_prueba("1710034065");
+    entrada["funcion_id"] = creada.id.into();
+    let req = test::TestRequest::post().uri("/v1/entradas").insert_header(entorno.autorizacion()).set_json(entrada).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CREATED);
+
+    // Los cambios de la función se ven desde sus entradas.
+    let req = test::TestRequest::post()
+        .uri("/v1/salas")
+        .insert_header(entorno.autorizacion())
+        .set_json(serde_json::json!({ "nombre": "Sala 2", "capacidad": 80 }))
+        .to_request();
+    let ApiResponse { data: sala, .. }: ApiResponse<Sala> = test::call_and_read_body_json(&app, req).await;
+    let mut cambios = funcion.clone();
+    cambios["sala_id"] = sala.id.into();
+    let req = test::TestRequest::put()
+        .uri(&format!("/v1/funciones/{}", creada.id))
+        .insert_header(entorno.autorizacion())
+        .set_json(cambios)
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    let req = test::TestRequest::get()
+        .uri(&format!("/v1/entradas?funcion_id={}", creada.id))
+        .insert_header(entorno.autorizacion())
+        .to_request();
+    let ApiResponse { data: entradas, .. }: ApiResponse<Vec<Entrada>> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(entradas.len(), 1);
+    assert_eq!(entradas[0].funcion.sala, sala);
+
+    // Una función inexistente es un error del campo; una con entradas no se elimina.
+    let mut entrada = entrada_de_prueba("0926687856");
+    entrada["funcion_id"] = 999_999.into();
+    let req = test::TestRequest::post().uri("/v1/entradas").insert_header(entorno.autorizacion()).set_json(entrada).to_request();
+    let respuesta = test::call_service(&app, req).await;
+    assert_eq!(respuesta.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let cuerpo: serde_json::Value = test::read_body_json(respuesta).await;
+    assert_eq!(cuerpo["error"]["campos"][0]["campo"], "funcion_id");
+    let req = test::TestRequest::delete()
+        .uri(&format!("/v1/funciones/{}", creada.id))
+        .insert_header(entorno.autorizacion())
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CONFLICT);
+
+    let req = test::TestRequest::get().uri("/v1/funciones?titulo=Oppenheimer").insert_header(entorno.autorizacion()).to_request();
+    let ApiResponse { data: funciones, .. }: ApiResponse<Vec<Funcion>> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(funciones, [Funcion { sala: sala.clone(), ..creada }]);
+
+    // La capacidad sale de la sala; una sala inexistente es un error del campo y una con
+    // funciones no se elimina.
+    let req = test::TestRequest::put()
+        .uri(&format!("/v1/salas/{}", sala.id))
+        .insert_header(entorno.autorizacion())
+        .set_json(serde_json::json!({ "nombre": "Sala 2", "capacidad": 90 }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    let req = test::TestRequest::get().uri(&format!("/v1/funciones/{}", funciones[0].id)).insert_header(entorno.autorizacion()).to_request();
+    let ApiResponse { data: funcion_actual, .. }: ApiResponse<Funcion> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(funcion_actual.sala.capacidad, 90);
+    let mut sin_sala = funcion.clone();
+    sin_sala["sala_id"] = 999_999.into();
+    let req = test::TestRequest::post().uri("/v1/funciones").insert_header(entorno.autorizacion()).set_json(sin_sala).to_request();
+    let respuesta = test::call_service(&app, req).await;
+    assert_eq!(respuesta.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let cuerpo: serde_json::Value = test::read_body_json(respuesta).await;
+    assert_eq!(cuerpo["error"]["campos"][0]["campo"], "sala_id");
+    let req = test::TestRequest::delete().uri(&format!("/v1/salas/{}", sala.id)).insert_header(entorno.autorizacion()).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CONFLICT);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker"]
+async fn no_se_venden_mas_asientos_que_la_capacidad_de_la_sala() {
+    let entorno = levantar_entorno().await;
+    let app = iniciar_app!(entorno);
+    let mut conn = entorno.pool.get_conn().await.unwrap();
+    conn.query_drop("INSERT INTO salas (nombre, capacidad) VALUES ('Sala pequeña', 5)").await.unwrap();
+    let sala = conn.last_insert_id().unwrap();
+    conn.exec_drop("INSERT INTO funciones (titulo, sala_id, horario, precio) VALUES ('Dune', ?, '2024-03-01 23:00:00', 6.5)", (sala,))
+        .await
+        .unwrap();
+    let funcion = conn.last_insert_id().unwrap();
+
+    let mut entrada = entrada_de_prueba("1710034065");
+    entrada["funcion_id"] = funcion.into();
+    entrada["cantidad_entradas"] = 4.into();
+    let req = test::TestRequest::post().uri("/v1/entradas").insert_header(entorno.autorizacion()).set_json(entrada).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CREATED);
+
+    let mut entrada = entrada_de_prueba("0926687856");
+    entrada["funcion_id"] = funcion.into();
+    let req = test::TestRequest::post().uri("/v1/entradas").insert_header(entorno.autorizacion()).set_json(&entrada).to_request();
+    let respuesta = test::call_service(&app, req).await;
+    assert_eq!(respuesta.status(), StatusCode::CONFLICT);
+    let cuerpo: serde_json::Value = test::read_body_json(respuesta).await;
+    assert_eq!(cuerpo["error"]["disponibles"], 1);
+
+    // Al actualizar no cuentan los asientos que la entrada ya tenía.
+    let req = test::TestRequest::get().uri(&format!("/v1/entradas?funcion_id={}", funcion)).insert_header(entorno.autorizacion()).to_request();
+    let ApiResponse { data: entradas, .. }: ApiResponse<Vec<Entrada>> = test::call_and_read_body_json(&app, req).await;
+    let id = entradas[0].id.unwrap();
+    for (cantidad, estado) in [(5, StatusCode::OK), (6, StatusCode::CONFLICT)] {
+        let req = test::TestRequest::patch()
+            .uri(&format!("/v1/entradas/{}", id))
+            .insert_header(entorno.autorizacion())
+            .set_json(serde_json::json!({ "cantidad_entradas": cantidad }))
+            .insert_header(("If-Match", "*"))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), estado);
+    }
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker"]
+async fn ventas_de_una_cedula_respetan_el_limite_aun_desde_dos_replicas() {
+    let mut entorno = levantar_entorno().await;
+    entorno.config.limite_por_cedula = Some(4);
+    // Dos instancias con su propia pool, como dos réplicas detrás del balanceador: no
+    // comparten las altas en curso, sólo la base de datos.
+    let replicas = [
+        iniciar_app!(entorno),
+        test::init_service(create_app(Estado::new(
+            entorno.config.clone(),
+            obtener_pool_db(&entorno.config).expect("URL de conexión válida"),
+        )))
+        .await,
+    ];
+    let venta = |cedula: &str, cantidad: usize| {
+        let mut entrada = entrada_de_prueba(cedula);
+        entrada["cantidad_entradas"] = cantidad.into();
+        test::TestRequest::post().insert_header(entorno.autorizacion()).uri("/v1/entradas").set_json(entrada).to_request()
+    };
+
+    // Ventas separadas de la misma cédula para la misma función suman para el límite.
+    assert_eq!(test::call_service(&replicas[0], venta("0926687856", 2)).await.status(), StatusCode::CREATED);
+    assert_eq!(test::call_service(&replicas[1], venta("0926687856", 2)).await.status(), StatusCode::CREATED);
+    let respuesta = test::call_service(&replicas[0], venta("0926687856", 1)).await;
+    assert_eq!(respuesta.status(), StatusCode::CONFLICT);
+    let cuerpo: serde_json::Value = test::read_body_json(respuesta).await;
+    assert_eq!(cuerpo["error"]["restantes"], 0);
+    let mut conn = entorno.pool.get_conn().await.unwrap();
+    let guardadas: u64 = conn.query_first("SELECT COUNT(*) FROM entradas WHERE cliente_id = 2").await.unwrap().unwrap();
+    assert_eq!(guardadas, 2);
+
+    // Ventas distintas de otra cédula, todas a la vez y repartidas entre réplicas.
+    let solicitudes = (0..10).map(|i| test::call_service(&replicas[i % 2], venta("1710034065", i % 5 + 1)));
+    let respuestas = futures_util::future::join_all(solicitudes).await;
+    assert!(respuestas.iter().all(|r| [StatusCode::CREATED, StatusCode::CONFLICT].contains(&r.status())));
+    assert_eq!(respuestas[4].status(), StatusCode::CONFLICT, "Cinco entradas pasan del límite");
+    let creadas = respuestas.iter().filter(|r| r.status() == StatusCode::CREATED).count();
+
+    let guardadas: Vec<(u32, u32)> =
+        conn.query("SELECT id, cantidad_entradas FROM entradas WHERE cliente_id = 1 AND funcion_id = 1").await.unwrap();
+    assert_eq!(guardadas.len(), creadas);
+    let vendidas: u32 = guardadas.iter().map(|(_, cantidad)| cantidad).sum();
+    assert!((1..=4).contains(&vendidas), "La cédula tiene {} entradas", vendidas);
+
+    // Al actualizar también se cuenta dentro de la transacción, sin la propia entrada.
+    let (id, cantidad) = guardadas[0];
+    let restantes = 4 - (vendidas - cantidad);
+    let req = test::TestRequest::patch()
+        .uri(&format!("/v1/entradas/{}", id))
+        .insert_header(entorno.autorizacion())
+        .set_json(serde_json::json!({ "cantidad_entradas": restantes + 1 }))
+        .insert_header(("If-Match", "*"))
+        .to_request();
+    let respuesta = test::call_service(&replicas[1], req).await;
+    assert_eq!(respuesta.status(), StatusCode::CONFLICT);
+    let cuerpo: serde_json::Value = test::read_body_json(respuesta).await;
+    assert_eq!(cuerpo["error"]["restantes"], restantes);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker"]
+async fn alta_y_baja_de_entradas_en_lote() {
+    let mut entorno = levantar_entorno().await;
+    entorno.config.limite_por_cedula = Some(3);
+    let app = iniciar_app!(entorno);
+
+    // Con la tercera, el primer cliente pasa del límite para la función: no se guarda ninguna.
+    let lote = [entrada_de_prueba("1710034065"), entrada_de_prueba("0926687856"), entrada_de_prueba("1710034065")];
+    let req = test::TestRequest::post().uri("/v1/entradas/bulk").insert_header(entorno.autorizacion()).set_json(&lote).to_request();
+    let respuesta = test::call_service(&app, req).await;
+    assert_eq!(respuesta.status(), StatusCode::CONFLICT);
+    let ApiResponse { data: resultados, .. }: ApiResponse<Vec<serde_json::Value>> = test::read_body_json(respuesta).await;
+    let estados: Vec<&str> = resultados.iter().map(|r| r["estado"].as_str().unwrap()).collect();
+    assert_eq!(estados, ["revertida", "revertida", "rechazada"]);
+    assert_eq!(resultados[2]["error"]["code"], "limite_cedula");
+    let req = test::TestRequest::get().uri("/v1/entradas").insert_header(entorno.autorizacion()).to_request();
+    let ApiResponse { data: entradas, .. }: ApiResponse<Vec<Entrada>> = test::call_and_read_body_json(&app, req).await;
+    assert!(entradas.is_empty());
+
+    let req = test::TestRequest::post().uri("/v1/entradas/bulk").insert_header(entorno.autorizacion()).set_json(&lote[..2]).to_request();
+    let respuesta = test::call_service(&app, req).await;
+    assert_eq!(respuesta.status(), StatusCode::CREATED);
+    let ApiResponse { data: resultados, .. }: ApiResponse<Vec<serde_json::Value>> = test::read_body_json(respuesta).await;
+    assert!(resultados.iter().all(|r| r["estado"] == "creada" && r["id"].is_u64()));
+
+    // Y se eliminan de una vez por su función.
+    let req = test::TestRequest::delete()
+        .uri("/v1/entradas")
+        .insert_header(entorno.autorizacion())
+        .set_json(serde_json::json!({ "funcion_id": 1 }))
+        .to_request();
+    let ApiResponse { data: resultado, .. }: ApiResponse<serde_json::Value> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(resultado["eliminadas"], 2);
+    let req = test::TestRequest::delete().uri("/v1/entradas").insert_header(entorno.autorizacion()).set_json(serde_json::json!({})).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker"]
+async fn reintentos_con_clave_de_idempotencia() {
+    let mut entorno = levantar_entorno().await;
+    entorno.config.limite_por_cedula = Some(3);
+    let app = iniciar_app!(entorno);
+
+    // El reintento repite la respuesta sin crear otra entrada.
+    for repetida in [false, true] {
+        let req = test::TestRequest::post()
+            .uri("/v1/entradas")
+            .insert_header(entorno.autorizacion())
+            .insert_header(("Idempotency-Key", "venta-1"))
+            .set_json(entrada_de_prueba("1710034065"))
+            .to_request();
+        let respuesta = test::call_service(&app, req).await;
+        assert_eq!(respuesta.status(), StatusCode::CREATED);
+        assert_eq!(respuesta.headers().contains_key("Idempotent-Replayed"), repetida);
+    }
+    let req = test::TestRequest::get().uri("/v1/entradas").insert_header(entorno.autorizacion()).to_request();
+    let ApiResponse { data: entradas, .. }: ApiResponse<Vec<Entrada>> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(entradas.len(), 1);
+
+    // La misma clave con otros datos es un conflicto; una venta fallida, aquí por el límite
+    // por cédula, libera su clave.
+    let req = test::TestRequest::post()
+        .uri("/v1/entradas")
+        .insert_header(entorno.autorizacion())
+        .insert_header(("Idempotency-Key", "venta-1"))
+        .set_json(entrada_de_prueba("0926687856"))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CONFLICT);
+    for _ in 0..2 {
+        let req = test::TestRequest::post()
+            .uri("/v1/entradas")
+            .insert_header(entorno.autorizacion())
+            .insert_header(("Idempotency-Key", "venta-2"))
+            .set_json(entrada_de_prueba("1710034065"))
+            .to_request();
+        let respuesta = test::call_service(&app, req).await;
+        assert_eq!(respuesta.status(), StatusCode::CONFLICT);
+        assert!(!respuesta.headers().contains_key("Idempotent-Replayed"));
+    }
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker"]
+async fn las_replicas_comparten_redis() {
+    let mut entorno = levantar_entorno().await;
+    let redis = Redis::default().start().await.expect("No se pudo iniciar el contenedor de Redis");
+    let host = redis.get_host().await.expect("Host del contenedor");
+    let puerto = redis.get_host_port_ipv4(REDIS_PORT).await.expect("Puerto del contenedor");
+    entorno.config.redis_url = Some(format!("redis://{}:{}/0", host, puerto));
+    entorno.config.limite_ip = ConfigLimite { por_segundo: 0.01, rafaga: 2 };
+    let replica_a = iniciar_app!(entorno);
+    let replica_b = iniciar_app!(entorno);
+
+    // El reintento llega a otra réplica y recibe la respuesta guardada.
+    let mut ids = Vec::new();
+    for (app, repetida) in [(&replica_a, false), (&replica_b, true)] {
+        let req = test::TestRequest::post()
+            .uri("/v1/entradas")
+            .insert_header(entorno.autorizacion())
+            .insert_header(("Idempotency-Key", "venta-1"))
+            .set_json(entrada_de_prueba("1710034065"))
+            .to_request();
+        let respuesta = test::call_service(app, req).await;
+        assert_eq!(respuesta.status(), StatusCode::CREATED);
+        assert_eq!(respuesta.headers().contains_key("Idempotent-Replayed"), repetida);
+        let ApiResponse { data, .. }: ApiResponse<serde_json::Value> = test::read_body_json(respuesta).await;
+        ids.push(data["id"].clone());
+    }
+    assert_eq!(ids[0], ids[1]);
+
+    // Lo que cachea una réplica lo invalida la otra al modificarlo.
+    let uri = format!("/v1/entradas/{}", ids[0]);
+    let req = test::TestRequest::get().insert_header(entorno.autorizacion()).uri(&uri).to_request();
+    let ApiResponse { data: entrada, .. }: ApiResponse<Entrada> = test::call_and_read_body_json(&replica_a, req).await;
+    assert_eq!(entrada.cantidad_entradas, 2);
+    let req = test::TestRequest::patch()
+        .insert_header(entorno.autorizacion())
+        .insert_header(("If-Match", "*"))
+        .uri(&uri)
+        .set_json(serde_json::json!({ "cantidad_entradas": 3 }))
+        .to_request();
+    assert_eq!(test::call_service(&replica_b, req).await.status(), StatusCode::OK);
+    let req = test::TestRequest::get().insert_header(entorno.autorizacion()).uri(&uri).to_request();
+    let ApiResponse { data: entrada, .. }: ApiResponse<Entrada> = test::call_and_read_body_json(&replica_a, req).await;
+    assert_eq!(entrada.cantidad_entradas, 3);
+
+    // El límite por IP se cuenta entre las dos réplicas.
+    let ip = "203.0.113.7:40000".parse().unwrap();
+    for (app, estado) in [(&replica_a, StatusCode::OK), (&replica_b, StatusCode::OK), (&replica_a, StatusCode::TOO_MANY_REQUESTS)] {
+        let req = test::TestRequest::get().uri("/v1/funciones").peer_addr(ip).to_request();
+        assert_eq!(test::call_service(app, req).await.status(), estado);
+    }
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker"]
+async fn las_lecturas_usan_la_replica_y_caen_a_la_primaria() {
+    let mut entorno = levantar_entorno().await;
+    // La primaria hace de réplica de sí misma: las lecturas ven lo escrito.
+    entorno.config.database_url_ro = Some(entorno.config.database_url.clone());
+    let app = iniciar_app!(entorno);
+    let req = test::TestRequest::post().insert_header(entorno.autorizacion()).uri("/v1/entradas").set_json(entrada_de_prueba("1710034065")).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CREATED);
+    let req = test::TestRequest::get().insert_header(entorno.autorizacion()).uri("/v1/entradas").to_request();
+    let ApiResponse { data: entradas, .. }: ApiResponse<Vec<Entrada>> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(entradas.len(), 1);
+
+    // Con la réplica caída, las lecturas siguen funcionando desde la primaria.
+    entorno.config.database_url_ro = Some("mysql://root@127.0.0.1:1/test".to_string());
+    let app = iniciar_app!(entorno);
+    let req = test::TestRequest::get().insert_header(entorno.autorizacion()).uri("/v1/entradas").to_request();
+    let ApiResponse { data: entradas, .. }: ApiResponse<Vec<Entrada>> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(entradas.len(), 1);
+    let req = test::TestRequest::get().uri("/v1/admin/pool").insert_header(entorno.autorizacion()).to_request();
+    let ApiResponse { data: pool, .. }: ApiResponse<serde_json::Value> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(pool["replica"]["disponible"], false);
+    assert_eq!(pool["replica"]["caidas"], 1);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker"]
+async fn reenvio_de_una_venta_por_cedula() {
+    let entorno = levantar_entorno().await;
+    let app = iniciar_app!(entorno);
+
+    // El primer envío crea la entrada y los siguientes la actualizan, sin duplicarla.
+    for (cantidad, estado) in [(2, StatusCode::CREATED), (2, StatusCode::OK), (3, StatusCode::OK)] {
+        let req = test::TestRequest::put()
+            .uri("/v1/entradas/cedula/1710034065")
+            .insert_header(entorno.autorizacion())
+            .set_json(serde_json::json!({ "funcion_id": 1, "cantidad_entradas": cantidad }))
+            .to_request();
+        let respuesta = test::call_service(&app, req).await;
+        assert_eq!(respuesta.status(), estado);
+        let ApiResponse { data: entrada, .. }: ApiResponse<Entrada> = test::read_body_json(respuesta).await;
+        assert_eq!((entrada.cliente.numero_cedula.as_str(), entrada.cantidad_entradas), ("1710034065", cantidad));
+    }
+    let req = test::TestRequest::get().uri("/v1/entradas").insert_header(entorno.autorizacion()).to_request();
+    let ApiResponse { data: entradas, .. }: ApiResponse<Vec<Entrada>> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(entradas.len(), 1);
+
+    let req = test::TestRequest::put()
+        .uri("/v1/entradas/cedula/1722601810")
+        .insert_header(entorno.autorizacion())
+        .set_json(serde_json::json!({ "funcion_id": 1, "cantidad_entradas": 1 }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+    let req = test::TestRequest::put()
+        .uri("/v1/entradas/cedula/0926687856")
+        .insert_header(entorno.autorizacion())
+        .set_json(serde_json::json!({ "funcion_id": 1, "cantidad_entradas": 0 }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker"]
+async fn simulacion_de_una_venta() {
+    let mut entorno = levantar_entorno().await;
+    entorno.config.limite_por_cedula = Some(3);
+    let app = iniciar_app!(entorno);
+
+    // La simulación responde con la entrada que se crearía, pero no la guarda.
+    let req = test::TestRequest::post()
+        .uri("/v1/entradas?dry_run=true")
+        .insert_header(entorno.autorizacion())
+        .set_json(entrada_de_prueba("1710034065"))
+        .to_request();
+    let respuesta = test::call_service(&app, req).await;
+    assert_eq!(respuesta.status(), StatusCode::CREATED);
+    let ApiResponse { data: entrada, meta, .. }: ApiResponse<Entrada> = test::read_body_json(respuesta).await;
+    assert_eq!(entrada.cliente.numero_cedula, "1710034065");
+    assert!(meta.simulacion);
+    let req = test::TestRequest::get().uri("/v1/entradas").insert_header(entorno.autorizacion()).to_request();
+    let ApiResponse { data: entradas, .. }: ApiResponse<Vec<Entrada>> = test::call_and_read_body_json(&app, req).await;
+    assert!(entradas.is_empty());
+
+    // Las comprobaciones son las de la venta real: la segunda venta pasaría del límite.
+    let req = test::TestRequest::post().uri("/v1/entradas").insert_header(entorno.autorizacion()).set_json(entrada_de_prueba("1710034065")).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CREATED);
+    let req = test::TestRequest::post()
+        .uri("/v1/entradas?dry_run=true")
+        .insert_header(entorno.autorizacion())
+        .set_json(entrada_de_prueba("1710034065"))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CONFLICT);
+
+    // Las rutas que no simulan no aceptan dry_run.
+    let req = test::TestRequest::post()
+        .uri("/v1/entradas/1/pagar?dry_run=true")
+        .insert_header(entorno.autorizacion())
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker"]
+async fn seleccion_de_asientos() {
+    let entorno = levantar_entorno().await;
+    let app = iniciar_app!(entorno);
+    let mut conn = entorno.pool.get_conn().await.unwrap();
+    conn.query_drop("UPDATE salas SET capacidad = 5 WHERE id = 1").await.unwrap();
+
+    let mut entrada = entrada_de_prueba("1710034065");
+    entrada["asientos"] = serde_json::json!([1, 2]);
+    let req = test::TestRequest::post().uri("/v1/entradas").insert_header(entorno.autorizacion()).set_json(entrada).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CREATED);
+
+    // Asientos ocupados o fuera de la sala.
+    let mut entrada = entrada_de_prueba("0926687856");
+    entrada["asientos"] = serde_json::json!([2, 3]);
+    let req = test::TestRequest::post().uri("/v1/entradas").insert_header(entorno.autorizacion()).set_json(&entrada).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CONFLICT);
+    entrada["asientos"] = serde_json::json!([3, 6]);
+    let req = test::TestRequest::post().uri("/v1/entradas").insert_header(entorno.autorizacion()).set_json(&entrada).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    // Una entrada sin numerar ocupa capacidad, pero no un asiento concreto.
+    let mut entrada = entrada_de_prueba("0102030400");
+    entrada["cantidad_entradas"] = 1.into();
+    let req = test::TestRequest::post().uri("/v1/entradas").insert_header(entorno.autorizacion()).set_json(entrada).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CREATED);
+    let req = test::TestRequest::get().uri("/v1/funciones/1/asientos").insert_header(entorno.autorizacion()).to_request();
+    let ApiResponse { data: asientos, .. }: ApiResponse<AsientosFuncion> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(asientos, AsientosFuncion { capacidad: 5, disponibles: 2, asientos: vec![3, 4, 5] });
+
+    // Eliminar la entrada libera sus asientos.
+    let req = test::TestRequest::delete().uri("/v1/entradas/1").insert_header(entorno.autorizacion()).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    let req = test::TestRequest::get().uri("/v1/funciones/1/asientos").insert_header(entorno.autorizacion()).to_request();
+    let ApiResponse { data: asientos, .. }: ApiResponse<AsientosFuncion> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(asientos.asientos, [1, 2, 3, 4, 5]);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker"]
+async fn clientes_por_cedula() {
+    let entorno = levantar_entorno().await;
+    let app = iniciar_app!(entorno);
+
+    let cliente = serde_json::json!({ "numero_cedula": "1722601810", "nombre": "Carlos Mendoza" });
+    let req = test::TestRequest::post()
+        .uri("/v1/clientes")
+        .insert_header(entorno.autorizacion_con_rol(Rol::Taquillero))
+        .set_json(&cliente)
+        .to_request();
+    let ApiResponse { data: creado, .. }: ApiResponse<Cliente> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(creado.id, 4);
+    let req = test::TestRequest::post().uri("/v1/clientes").insert_header(entorno.autorizacion()).set_json(&cliente).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CONFLICT);
+    let req = test::TestRequest::post()
+        .uri("/v1/clientes")
+        .insert_header(entorno.autorizacion())
+        .set_json(serde_json::json!({ "numero_cedula": "1722601811", "nombre": "" }))
+        .to_request();
+    let respuesta = test::call_service(&app, req).await;
+    assert_eq!(respuesta.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let cuerpo: serde_json::Value = test::read_body_json(respuesta).await;
+    assert_eq!(cuerpo["error"]["campos"].as_array().map(Vec::len), Some(2));
+
+    // Las entradas muestran los datos actuales de su cliente.
+    let mut entrada = entrada_de_prueba("1710034065");
+    entrada["cliente_id"] = creado.id.into();
+    let req = test::TestRequest::post().uri("/v1/entradas").insert_header(entorno.autorizacion()).set_json(entrada).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CREATED);
+    let req = test::TestRequest::put()
+        .uri("/v1/clientes/1722601810")
+        .insert_header(entorno.autorizacion())
+        .set_json(serde_json::json!({ "nombre": "Carlos Mendoza Ruiz" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    let req = test::TestRequest::get().uri("/v1/entradas?numero_cedula=1722601810").insert_header(entorno.autorizacion()).to_request();
+    let ApiResponse { data: entradas, .. }: ApiResponse<Vec<Entrada>> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(entradas[0].cliente, Cliente { nombre: "Carlos Mendoza Ruiz".to_string(), ..creado });
+
+    // Un cliente inexistente es un error del campo; uno con entradas no se elimina.
+    let mut entrada = entrada_de_prueba("0926687856");
+    entrada["cliente_id"] = 999_999.into();
+    let req = test::TestRequest::post().uri("/v1/entradas").insert_header(entorno.autorizacion()).set_json(entrada).to_request();
+    let respuesta = test::call_service(&app, req).await;
+    assert_eq!(respuesta.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let cuerpo: serde_json::Value = test::read_body_json(respuesta).await;
+    assert_eq!(cuerpo["error"]["campos"][0]["campo"], "cliente_id");
+    let req = test::TestRequest::delete().uri("/v1/clientes/1722601810").insert_header(entorno.autorizacion()).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CONFLICT);
+    let req = test::TestRequest::delete().uri("/v1/clientes/0102030400").insert_header(entorno.autorizacion()).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NO_CONTENT);
+    let req = test::TestRequest::get().uri("/v1/clientes/0102030400").insert_header(entorno.autorizacion()).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker"]
+async fn sondas_de_vida_y_disponibilidad() {
+    let entorno = levantar_entorno().await;
+    let app = iniciar_app!(entorno);
+
+    let req = test::TestRequest::get().uri("/health").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    let req = test::TestRequest::get().uri("/ready").to_request();
+    let cuerpo: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(cuerpo["estado"], "ok");
+
+    let req = test::TestRequest::get().uri("/metrics").to_request();
+    let metricas = String::from_utf8(test::call_and_read_body(&app, req).await.to_vec()).unwrap();
+    assert!(metricas.contains("http_peticiones_total{metodo=\"GET\",ruta=\"/ready\",estado=\"200\"} 1"));
+    assert!(metricas.contains("db_pool_espera_segundos_count"));
+
+    // Una pool hacia un puerto sin servidor no está disponible.
+    let mut config = entorno.config.clone();
+    config.database_url = "mysql://root@127.0.0.1:1/test".to_string();
+    let pool = obtener_pool_db(&config).unwrap();
+    let app = test::init_service(create_app(Estado::new(config, pool))).await;
+    let req = test::TestRequest::get().uri("/ready").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker"]
+async fn semilla_idempotente() {
+    let entorno = levantar_entorno().await;
+    let estado = Estado::new(entorno.config.clone(), entorno.pool.clone());
+
+    let resumen = semilla::sembrar(&entorno.pool, &estado.entradas, false).await.unwrap();
+    assert_eq!(resumen.creadas, semilla::ENTRADAS_EJEMPLO.len());
+    assert!(matches!(
+        semilla::sembrar(&entorno.pool, &estado.entradas, false).await,
+        Err(semilla::ErrorSemilla::BaseNoVacia(_))
+    ));
+    let resumen = semilla::sembrar(&entorno.pool, &estado.entradas, true).await.unwrap();
+    assert_eq!((resumen.creadas, resumen.existentes), (0, semilla::ENTRADAS_EJEMPLO.len()));
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker"]
+async fn registro_y_latidos_de_dispositivos() {
+    let entorno = levantar_entorno().await;
+    let app = iniciar_app!(entorno);
+
+    let kiosco = serde_json::json!({ "nombre": "kiosco-1", "sucursal": "Centro", "version": "1.0.0" });
+    let req = test::TestRequest::post().uri("/v1/dispositivos").set_json(&kiosco).to_request();
+    let respuesta = test::call_service(&app, req).await;
+    assert_eq!(respuesta.status(), StatusCode::CREATED);
+    let ApiResponse { data, .. }: ApiResponse<serde_json::Value> = test::read_body_json(respuesta).await;
+    assert_eq!(data["estado"], "sin_latidos");
+    let id = data["id"].as_u64().unwrap();
+
+    let req = test::TestRequest::post().uri("/v1/dispositivos").set_json(&kiosco).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CONFLICT);
+    let req = test::TestRequest::post()
+        .uri("/v1/dispositivos")
+        .set_json(serde_json::json!({ "nombre": " ", "sucursal": "Centro", "version": "1.0.0" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+
+    // El latido marca el dispositivo como activo y puede informar de su nueva versión.
+    let req = test::TestRequest::post()
+        .uri(&format!("/v1/dispositivos/{}/latido", id))
+        .set_json(serde_json::json!({ "version": "1.1.0" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NO_CONTENT);
+    let req = test::TestRequest::get().uri(&format!("/v1/dispositivos/{}", id)).to_request();
+    let ApiResponse { data, .. }: ApiResponse<serde_json::Value> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!((data["estado"].as_str(), data["version"].as_str()), (Some("activo"), Some("1.1.0")));
+    let req = test::TestRequest::get().uri("/v1/dispositivos").to_request();
+    let ApiResponse { data, .. }: ApiResponse<Vec<serde_json::Value>> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(data.len(), 1);
+
+    let req = test::TestRequest::post().uri("/v1/dispositivos/999/latido").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+    let req = test::TestRequest::get().uri("/v1/dispositivos/999").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker"]
+async fn sugerencias_y_busqueda_aproximada_de_clientes() {
+    let entorno = levantar_entorno().await;
+    let app = iniciar_app!(entorno);
+    for cedula in ["1710034065", "0926687856"] {
+        let req = test::TestRequest::post()
+            .uri("/v1/entradas")
+            .insert_header(entorno.autorizacion())
+            .set_json(entrada_de_prueba(cedula))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CREATED);
+    }
+
+    let req = test::TestRequest::get().uri("/v1/autocomplete?campo=nombre_funcion&q=du").to_request();
+    let ApiResponse { data, .. }: ApiResponse<serde_json::Value> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(data, serde_json::json!([{ "valor": "Dune", "total": 2 }]));
+    let req = test::TestRequest::get().uri("/v1/autocomplete?campo=numero_cedula&q=17").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+    let req = test::TestRequest::get().uri("/v1/autocomplete?campo=nombre_cliente&q=%20").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+
+    // La búsqueda aproximada tolera tildes y errores de escritura.
+    let req = test::TestRequest::get()
+        .uri("/v1/entradas/buscar-aproximado?nombre=maria%20peres")
+        .insert_header(entorno.autorizacion())
+        .to_request();
+    let ApiResponse { data, .. }: ApiResponse<Vec<serde_json::Value>> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(data[0]["numero_cedula"], "1710034065");
+    let req = test::TestRequest::get()
+        .uri("/v1/entradas/buscar-aproximado?nombre=maria&umbral=2")
+        .insert_header(entorno.autorizacion())
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker"]
+async fn rutas_de_administracion_y_sin_version() {
+    let entorno = levantar_entorno().await;
+    let app = iniciar_app!(entorno);
+
+    let req = test::TestRequest::get().uri("/v1/admin/slo").to_request();
+    let ApiResponse { data, .. }: ApiResponse<serde_json::Value> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(data["ventana_segundos"], 3600);
+    let req = test::TestRequest::get().uri("/v1/admin/tareas").insert_header(entorno.autorizacion()).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    let req = test::TestRequest::get()
+        .uri("/v1/admin/tareas")
+        .insert_header(entorno.autorizacion_con_rol(Rol::Taquillero))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+    let req = test::TestRequest::get().uri("/v1/admin/pool").insert_header(entorno.autorizacion()).to_request();
+    let ApiResponse { data: pool, .. }: ApiResponse<serde_json::Value> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(pool["espera_maxima_ms"], 5000);
+    assert!(pool["prestadas"].as_u64().unwrap() > 0);
+    assert!(pool["replica"].is_null());
+
+    // Las rutas sin versión responden igual, errores incluidos, y avisan de su sucesora.
+    let req = test::TestRequest::get().uri("/entradas").insert_header(entorno.autorizacion()).to_request();
+    let respuesta = test::call_service(&app, req).await;
+    assert_eq!(respuesta.status(), StatusCode::OK);
+    assert_eq!(respuesta.headers().get("Deprecation").unwrap(), "true");
+    let req = test::TestRequest::get().uri("/entradas").to_request();
+    let respuesta = test::call_service(&app, req).await;
+    assert_eq!(respuesta.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(respuesta.headers().get("Deprecation").unwrap(), "true");
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker"]
+async fn registro_de_accesos() {
+    let entorno = levantar_entorno().await;
+    let estado = Estado::new(entorno.config.clone(), entorno.pool.clone());
+    let accesos = estado.accesos.clone();
+    let app = test::init_service(create_app(estado)).await;
+
+    let req = test::TestRequest::get().uri("/v1/entradas/999").insert_header(entorno.autorizacion()).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+    let req = test::TestRequest::get().uri("/v1/entradas").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::UNAUTHORIZED);
+    accesos.guardar(&entorno.pool).await.expect("Accesos guardados");
+
+    let req = test::TestRequest::get()
+        .uri("/v1/admin/accesos?actor=pruebas&ruta=/v1/entradas")
+        .insert_header(entorno.autorizacion())
+        .to_request();
+    let ApiResponse { data, meta, .. }: ApiResponse<Vec<serde_json::Value>> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(meta.paginacion.unwrap().total, 1);
+    assert_eq!(data[0]["metodo"], "GET");
+    assert_eq!(data[0]["ruta"], "/v1/entradas/999");
+    assert_eq!(data[0]["estado"], 404);
+    let req = test::TestRequest::get()
+        .uri("/v1/admin/accesos?estado=401")
+        .insert_header(entorno.autorizacion())
+        .to_request();
+    let ApiResponse { data, .. }: ApiResponse<Vec<serde_json::Value>> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(data.len(), 1);
+    assert!(data[0]["actor"].is_null());
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker"]
+async fn archivo_de_entradas_antiguas() {
+    let entorno = levantar_entorno().await;
+    let reloj = Arc::new(RelojFijo::new(Utc::now()));
+    let app = test::init_service(create_app(Estado::con_reloj(entorno.config.clone(), entorno.pool.clone(), reloj.clone()))).await;
+
+    let req = test::TestRequest::post().insert_header(entorno.autorizacion()).uri("/v1/entradas").set_json(entrada_de_prueba("1710034065")).to_request();
+    let ApiResponse { data, .. }: ApiResponse<serde_json::Value> = test::call_and_read_body_json(&app, req).await;
+    let id = data["id"].as_u64().unwrap();
+    let req = test::TestRequest::get().uri(&format!("/v1/entradas/{}", id)).insert_header(entorno.autorizacion()).to_request();
+    let ApiResponse { data: entrada, .. }: ApiResponse<Entrada> = test::call_and_read_body_json(&app, req).await;
+    let req = test::TestRequest::post().insert_header(entorno.autorizacion()).uri("/v1/entradas").set_json(entrada_de_prueba("0926687856")).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CREATED);
+    let req = test::TestRequest::post().insert_header(entorno.autorizacion()).uri(&format!("/v1/entradas/{}/cancelar", id)).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    let req = test::TestRequest::post().uri("/v1/admin/archivo?antiguedad_dias=365").insert_header(entorno.autorizacion()).to_request();
+    let ApiResponse { data, .. }: ApiResponse<serde_json::Value> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(data["archivadas"], 0);
+
+    reloj.avanzar(TimeDelta::days(400));
+    let req = test::TestRequest::post().uri("/v1/admin/archivo?antiguedad_dias=365").insert_header(entorno.autorizacion()).to_request();
+    let ApiResponse { data, .. }: ApiResponse<serde_json::Value> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(data["archivadas"], 1);
+    let req = test::TestRequest::get().uri(&format!("/v1/entradas/{}", id)).insert_header(entorno.autorizacion()).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+
+    let req = test::TestRequest::get().uri("/v1/admin/archivo?numero_cedula=1710034065").insert_header(entorno.autorizacion()).to_request();
+    let ApiResponse { data, .. }: ApiResponse<Vec<serde_json::Value>> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(data.len(), 1);
+    assert_eq!(data[0]["id"], id);
+    assert_eq!(data[0]["estado"], "cancelada");
+    assert_eq!(data[0]["titulo_funcion"], entrada.funcion.titulo);
+
+    let req = test::TestRequest::post().uri("/v1/admin/archivo").insert_header(entorno.autorizacion()).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker"]
+async fn respaldo_y_restauracion() {
+    let entorno = levantar_entorno().await;
+    let app = iniciar_app!(entorno);
+
+    let req = test::TestRequest::post().insert_header(entorno.autorizacion()).uri("/v1/entradas").set_json(entrada_de_prueba("1710034065")).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CREATED);
+    let req = test::TestRequest::post().uri("/v1/admin/backup").insert_header(entorno.autorizacion()).to_request();
+    let respaldo = test::call_and_read_body(&app, req).await;
+    let lineas: Vec<&str> = std::str::from_utf8(&respaldo).unwrap().lines().collect();
+    assert!(lineas[0].contains("\"formato\":\"rust-crud\""));
+    assert!(lineas.last().unwrap().starts_with("{\"fin\":true"));
+
+    // Sin --force no reemplaza una base con entradas; con él, la deja como estaba.
+    assert!(matches!(
+        respaldo::restaurar(&entorno.pool, &respaldo[..], false).await,
+        Err(respaldo::ErrorRestauracion::BaseNoVacia(1))
+    ));
+    let mut conn = entorno.pool.get_conn().await.expect("Conexión de pruebas");
+    conn.query_drop("DELETE FROM entradas").await.expect("Entradas eliminadas");
+    let filas = respaldo::restaurar(&entorno.pool, &respaldo[..], true).await.expect("Respaldo restaurado");
+    assert_eq!(filas as usize, lineas.iter().filter(|linea| linea.starts_with('[')).count());
+    let req = test::TestRequest::get().uri("/v1/entradas").insert_header(entorno.autorizacion()).to_request();
+    let ApiResponse { data: entradas, .. }: ApiResponse<Vec<Entrada>> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(entradas.len(), 1);
+
+    // Un respaldo cortado no se restaura.
+    let cortado = &respaldo[..respaldo.len() - lineas.last().unwrap().len() - 1];
+    assert!(respaldo::restaurar(&entorno.pool, cortado, true).await.is_err());
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker"]
+async fn purga_de_entradas_canceladas() {
+    let entorno = levantar_entorno().await;
+    let reloj = Arc::new(RelojFijo::new(Utc::now()));
+    let app = test::init_service(create_app(Estado::con_reloj(entorno.config.clone(), entorno.pool.clone(), reloj.clone()))).await;
+
+    let mut ids = Vec::new();
+    for cedula in ["1710034065", "0926687856"] {
+        let req = test::TestRequest::post().insert_header(entorno.autorizacion()).uri("/v1/entradas").set_json(entrada_de_prueba(cedula)).to_request();
+        let ApiResponse { data, .. }: ApiResponse<serde_json::Value> = test::call_and_read_body_json(&app, req).await;
+        ids.push(data["id"].as_u64().unwrap());
+    }
+    let req = test::TestRequest::post().insert_header(entorno.autorizacion()).uri(&format!("/v1/entradas/{}/cancelar", ids[0])).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    reloj.avanzar(TimeDelta::days(40));
+
+    let req = test::TestRequest::delete().uri("/v1/admin/entradas/purge?older_than=30d").insert_header(entorno.autorizacion()).to_request();
+    let ApiResponse { data, .. }: ApiResponse<serde_json::Value> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!((&data["entradas"], &data["archivadas"]), (&serde_json::json!(1), &serde_json::json!(0)));
+    let req = test::TestRequest::get().uri(&format!("/v1/entradas/{}", ids[0])).insert_header(entorno.autorizacion()).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+    let req = test::TestRequest::get().uri(&format!("/v1/entradas/{}", ids[1])).insert_header(entorno.autorizacion()).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let req = test::TestRequest::delete().uri("/v1/admin/entradas/purge?older_than=30").insert_header(entorno.autorizacion()).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+}